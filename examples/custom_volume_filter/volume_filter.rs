@@ -2,6 +2,8 @@ use ffmpeg_next::Frame;
 use ffmpeg_sys_next::{av_frame_copy_props, av_frame_get_buffer, AVMediaType};
 use ez_ffmpeg::filter::frame_filter::FrameFilter;
 use ez_ffmpeg::filter::frame_filter_context::FrameFilterContext;
+use ez_ffmpeg::filter::frame_planes::FramePlanes;
+use ez_ffmpeg::filter::frame_view::FrameView;
 
 pub struct VolumeFilter {
     volume: f32, // The volume gain factor (e.g., 1.0 for no change, 2.0 for doubling the volume)
@@ -21,23 +23,21 @@ impl FrameFilter for VolumeFilter {
 
     fn filter_frame(
         &mut self,
-        mut frame: Frame, // The input audio frame to be processed
+        frame: Frame, // The input audio frame to be processed
         _ctx: &FrameFilterContext, // Context of the filter (not used here)
     ) -> Result<Option<Frame>, String> {
-        unsafe {
-            // Ensure the frame is valid and not empty
-            if frame.as_ptr().is_null() || frame.is_empty() {
-                return Ok(Some(frame)); // If invalid, simply return the frame as-is
-            }
-        }
+        let view = FrameView::new(&frame);
+        // Frames without data (e.g. the end-of-stream frame) are passed on as-is
+        let Some(format) = view.sample_format().filter(|_| !view.is_props_only()) else {
+            return Ok(Some(frame));
+        };
 
         // Extract audio format, channel layout, sample rate, and number of samples from the frame
-        let format: ffmpeg_sys_next::AVSampleFormat =
-            unsafe { std::mem::transmute((*frame.as_ptr()).format) };
+        let sample_rate = view.sample_rate() as i32;
+        let nb_samples = view.nb_samples() as i32;
+        let nb_channels = view.channels();
+        // The channel layout is only reachable through the raw frame
         let ch_layout = unsafe { (*frame.as_ptr()).ch_layout };
-        let sample_rate = unsafe { (*frame.as_ptr()).sample_rate };
-        let nb_samples = unsafe { (*frame.as_ptr()).nb_samples };
-        let nb_channels = ch_layout.nb_channels;
 
         // If the audio format and layout do not match expected format, initialize resampler
         if format == ffmpeg_sys_next::AVSampleFormat::AV_SAMPLE_FMT_FLTP && nb_channels == 2 {
@@ -56,7 +56,7 @@ impl FrameFilter for VolumeFilter {
         }
 
         // Resample frame if necessary
-        let frame = match self.resampler.as_mut() {
+        let mut frame = match self.resampler.as_mut() {
             None => frame,
             Some(resampler) => {
                 let mut resample_frame = unsafe { Frame::empty() };
@@ -102,13 +102,8 @@ impl FrameFilter for VolumeFilter {
         };
 
         // Apply volume adjustment on both channels
-        let input_channel_0 = plane(&frame, 0);
-        let output_channel_0 = plane_mut(&frame, 0);
-        adjust_volume_f32(output_channel_0, input_channel_0, self.volume);
-
-        let input_channel_1 = plane(&frame, 1);
-        let output_channel_1 = plane_mut(&frame, 1);
-        adjust_volume_f32(output_channel_1, input_channel_1, self.volume);
+        adjust_volume_f32(frame.samples_mut::<f32>(0)?, self.volume);
+        adjust_volume_f32(frame.samples_mut::<f32>(1)?, self.volume);
 
         Ok(Some(frame)) // Return the processed frame
     }
@@ -120,11 +115,10 @@ use std::arch::aarch64::*;
 use std::arch::x86_64::*;
 use ez_ffmpeg::util::ffmpeg_utils::av_err2str;
 
-// SIMD-based volume adjustment
+// SIMD-based volume adjustment, in place
 #[inline]
-pub fn adjust_volume_f32(output: &mut [f32], input: &[f32], gain: f32) {
-    assert_eq!(output.len(), input.len());
-    let len = output.len();
+pub fn adjust_volume_f32(samples: &mut [f32], gain: f32) {
+    let len = samples.len();
     let (simd_len, _) = calculate_simd_params(len);
 
     // --- x86/x86_64 ---
@@ -133,20 +127,20 @@ pub fn adjust_volume_f32(output: &mut [f32], input: &[f32], gain: f32) {
         if is_x86_feature_detected!("avx2") {
             for i in (0..simd_len).step_by(8) {
                 let g = _mm256_set1_ps(gain);
-                let in_v = _mm256_loadu_ps(input.as_ptr().add(i));
+                let in_v = _mm256_loadu_ps(samples.as_ptr().add(i));
                 let scaled = _mm256_mul_ps(in_v, g);
                 let clamped_min = _mm256_max_ps(scaled, _mm256_set1_ps(-1.0));
                 let clamped = _mm256_min_ps(clamped_min, _mm256_set1_ps(1.0));
-                _mm256_storeu_ps(output.as_mut_ptr().add(i), clamped);
+                _mm256_storeu_ps(samples.as_mut_ptr().add(i), clamped);
             }
         } else if is_x86_feature_detected!("sse4.1") {
             for i in (0..simd_len).step_by(4) {
                 let g = _mm_set1_ps(gain);
-                let in_v = _mm_loadu_ps(input.as_ptr().add(i));
+                let in_v = _mm_loadu_ps(samples.as_ptr().add(i));
                 let scaled = _mm_mul_ps(in_v, g);
                 let clamped_min = _mm_max_ps(scaled, _mm_set1_ps(-1.0));
                 let clamped = _mm_min_ps(clamped_min, _mm_set1_ps(1.0));
-                _mm_storeu_ps(output.as_mut_ptr().add(i), clamped);
+                _mm_storeu_ps(samples.as_mut_ptr().add(i), clamped);
             }
         }
     }
@@ -156,16 +150,16 @@ pub fn adjust_volume_f32(output: &mut [f32], input: &[f32], gain: f32) {
     unsafe {
         for i in (0..simd_len).step_by(4) {
             let g = vdupq_n_f32(gain);
-            let in_v = vld1q_f32(input.as_ptr().add(i));
+            let in_v = vld1q_f32(samples.as_ptr().add(i));
             let scaled = vmulq_f32(in_v, g);
             let clamped_min = vminq_f32(scaled, vdupq_n_f32(1.0));
             let clamped = vmaxq_f32(clamped_min, vdupq_n_f32(-1.0));
-            vst1q_f32(output.as_mut_ptr().add(i), clamped);
+            vst1q_f32(samples.as_mut_ptr().add(i), clamped);
         }
     }
 
     for i in simd_len..len {
-        output[i] = (input[i] * gain).clamp(-1.0, 1.0);
+        samples[i] = (samples[i] * gain).clamp(-1.0, 1.0);
     }
 }
 
@@ -201,17 +195,3 @@ fn calculate_simd_params(len: usize) -> (usize, usize) {
     let remainder = len % simd_width;
     (simd_len, remainder)
 }
-
-// Helper function to extract a specific audio channel's data
-#[inline]
-pub fn plane(frame: &Frame, channel: usize) -> &[f32] {
-    let num_samples = unsafe { (*frame.as_ptr()).nb_samples } as usize;
-    unsafe { std::slice::from_raw_parts((*frame.as_ptr()).data[channel] as *mut f32, num_samples) }
-}
-
-// Helper function to get mutable access to a specific audio channel's data
-#[inline]
-pub fn plane_mut(frame: &Frame, channel: usize) -> &mut [f32] {
-    let num_samples = unsafe { (*frame.as_ptr()).nb_samples } as usize;
-    unsafe { std::slice::from_raw_parts_mut((*frame.as_ptr()).data[channel] as *mut f32, num_samples) }
-}
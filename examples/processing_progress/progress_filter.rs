@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use ez_ffmpeg::filter::frame_filter::FrameFilter;
 use ez_ffmpeg::filter::frame_filter_context::FrameFilterContext;
+use ez_ffmpeg::filter::frame_view::FrameView;
 use ez_ffmpeg::AVMediaType;
 use ez_ffmpeg::Frame;
 
@@ -66,11 +67,9 @@ impl FrameFilter for ProgressCallBackFilter {
         frame: Frame,
         _ctx: &FrameFilterContext,
     ) -> Result<Option<Frame>, String> {
-        unsafe {
-            // Ensure the frame is valid and not empty
-            if frame.as_ptr().is_null() || frame.is_empty() {
-                return Ok(Some(frame)); // If invalid, simply return the frame as-is
-            }
+        // Frames without data (e.g. the end-of-stream frame) are passed on as-is
+        if FrameView::new(&frame).is_props_only() {
+            return Ok(Some(frame));
         }
         self.progress_callback.print_progress(&frame);
         Ok(Some(frame))
@@ -0,0 +1,89 @@
+use crate::core::context::ffmpeg_context::FfmpegContext;
+use crate::core::context::input::Input;
+use crate::error::Result;
+
+/// Transcodes the part of `input` starting at `start_us` and lasting `duration_us` (both in
+/// microseconds) into `output`.
+///
+/// The input is opened with a seek to the last keyframe at or before `start_us`, so nothing
+/// before it is read, and the demuxer stops reading as soon as every audio and video stream
+/// is past the end of the clip. Grabbing a few seconds from the middle of a long file
+/// therefore only reads those seconds, plus up to one GOP before them.
+///
+/// # Keyframe alignment
+/// The streams are decoded and re-encoded, and the frames between the keyframe the seek
+/// lands on and `start_us` are decoded and dropped: the output starts exactly at `start_us`,
+/// whatever the keyframe interval of the input, with its timestamps starting at 0. This is
+/// [`Input::set_start_time_us`] and [`Input::set_recording_time_us`] with the default
+/// [accurate seek](Input::set_accurate_seek); use them directly to keep the keyframe
+/// instead, or to copy the streams without re-encoding, in which case the clip starts on
+/// the keyframe.
+///
+/// # Arguments
+/// - `input`: The path or URL of the media to read (e.g., `"movie.mkv"`).
+/// - `start_us`: Where the clip starts in the input, in microseconds.
+/// - `duration_us`: The length of the clip, in microseconds.
+/// - `output`: The path of the file to write (e.g., `"clip.mp4"`).
+///
+/// # Returns
+/// - `Ok(())` once the clip has been fully written.
+/// - Any error raised while opening, transcoding or writing the files.
+///
+/// # Example
+/// ```rust
+/// // 10 seconds starting at 1h23m20s
+/// clip("movie.mkv", 5_000_000_000, 10_000_000, "clip.mp4").unwrap();
+/// ```
+pub fn clip(input: &str, start_us: i64, duration_us: i64, output: &str) -> Result<()> {
+    FfmpegContext::builder()
+        .input(
+            Input::from(input)
+                .set_start_time_us(start_us)
+                .set_recording_time_us(duration_us),
+        )
+        .output(output)
+        .build()?
+        .start()?
+        .wait()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::container_info::get_duration_us;
+    use crate::core::filter::frame_planes::FramePlanes;
+    use crate::core::frame_reader::FrameReader;
+    use ffmpeg_next::Frame;
+    use ffmpeg_sys_next::AVMediaType::AVMEDIA_TYPE_VIDEO;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+    use ffmpeg_sys_next::av_q2d;
+
+    fn luma_distance(a: &Frame, b: &Frame) -> f64 {
+        let (a, b) = (a.plane_u8(0).unwrap(), b.plane_u8(0).unwrap());
+        let (sum, count) = a.iter().zip(&b).fold((0u64, 0u64), |(sum, count), (a, b)| {
+            let row_sum = a.iter().zip(*b).map(|(a, b)| a.abs_diff(*b) as u64).sum::<u64>();
+            (sum + row_sum, count + a.len() as u64)
+        });
+        sum as f64 / count as f64
+    }
+
+    #[test]
+    fn test_clip() {
+        clip("test.mp4", 1_500_000, 1_000_000, "output_clip.mp4").unwrap();
+
+        let duration_us = get_duration_us("output_clip.mp4").unwrap();
+        assert!((duration_us - 1_000_000).abs() < 100_000, "{duration_us}");
+
+        // the clip starts with the source frame at 1.5s, not with the keyframe before it
+        let gray = |url: &str| FrameReader::new(url, AVMEDIA_TYPE_VIDEO).set_pixel_format(AV_PIX_FMT_GRAY8);
+        let first = gray("output_clip.mp4").next().unwrap().unwrap();
+        let mut source = gray("test.mp4").map(|frame| frame.unwrap());
+        let source_first = source.next().unwrap();
+        let source_start = source
+            .find(|frame| unsafe { (*frame.as_ptr()).pts as f64 * av_q2d((*frame.as_ptr()).time_base) >= 1.5 })
+            .unwrap();
+        assert!(luma_distance(&first, &source_start) < luma_distance(&first, &source_first));
+
+        std::fs::remove_file("output_clip.mp4").unwrap();
+    }
+}
@@ -1,8 +1,17 @@
 use ffmpeg_sys_next::{
     av_codec_is_decoder, av_codec_is_encoder, av_codec_iterate, avcodec_descriptor_next,
-    AVCodecDescriptor, AVCodecID, AVMediaType,
+    avcodec_get_hw_config, AVCodecDescriptor, AVCodecID, AVMediaType, AV_CODEC_CAP_HARDWARE,
+    AV_CODEC_CAP_HYBRID,
 };
-use std::ffi::{c_void, CStr};
+use crate::error::{OpenOutputError, Result};
+#[cfg(not(feature = "docs-rs"))]
+use ffmpeg_sys_next::avcodec_get_supported_config;
+use ffmpeg_sys_next::{
+    av_get_pix_fmt_name, av_get_sample_fmt_name, avcodec_find_encoder_by_name, AVCodecConfig, AVPixelFormat,
+    AVSampleFormat,
+};
+use log::debug;
+use std::ffi::{c_void, CStr, CString};
 use std::ptr::{null, null_mut};
 
 #[derive(Clone)]
@@ -42,6 +51,8 @@ impl Codec {
 /// * `media_type` - The media category (`AVMEDIA_TYPE_AUDIO`, `AVMEDIA_TYPE_VIDEO`, etc.).
 /// * `codec_id` - The internal FFmpeg `AVCodecID`, an enum identifying the codec.
 /// * `codec_capabilities` - A bitmask indicating codec capabilities (e.g., intra-only, lossless).
+///
+/// Whether the codec is hardware-backed is given by [`hw_accelerated`](CodecInfo::hw_accelerated).
 #[derive(Clone, Debug)]
 pub struct CodecInfo {
     /// The short name from `AVCodec.name`.
@@ -57,6 +68,15 @@ pub struct CodecInfo {
     pub codec_id: AVCodecID,
     /// A bitmask of capabilities from `AVCodec.capabilities`.
     pub codec_capabilities: i32,
+    hw_accelerated: bool,
+}
+
+impl CodecInfo {
+    /// Returns `true` for hardware codecs (e.g. `h264_nvenc`, `hevc_qsv`) and for codecs that
+    /// can offload work to a hardware device (hwaccel decoders such as `h264`).
+    pub fn hw_accelerated(&self) -> bool {
+        self.hw_accelerated
+    }
 }
 
 /// Retrieves a list of **all encoders** (e.g., for H.264, AAC, etc.) recognized by FFmpeg.
@@ -95,6 +115,154 @@ pub fn get_decoders() -> Vec<CodecInfo> {
     get_codec_infos(0)
 }
 
+/// Lists the **encoders** available in the linked FFmpeg, optionally restricted to one media type.
+///
+/// Useful to check for a codec before selecting it, e.g. to prefer `h264_nvenc` over `libx264`
+/// when it is present.
+///
+/// # Example
+///
+/// ```rust
+/// let hw_video_encoders: Vec<_> = list_encoders(Some(AVMediaType::AVMEDIA_TYPE_VIDEO))
+///     .into_iter()
+///     .filter(|encoder| encoder.hw_accelerated())
+///     .collect();
+/// ```
+pub fn list_encoders(media_type: Option<AVMediaType>) -> Vec<CodecInfo> {
+    filter_codec_infos(get_encoders(), media_type)
+}
+
+/// Lists the **decoders** available in the linked FFmpeg, optionally restricted to one media type.
+///
+/// # Example
+///
+/// ```rust
+/// for decoder in list_decoders(Some(AVMediaType::AVMEDIA_TYPE_AUDIO)) {
+///     println!("Decoder: {} ({})", decoder.codec_name, decoder.codec_long_name);
+/// }
+/// ```
+pub fn list_decoders(media_type: Option<AVMediaType>) -> Vec<CodecInfo> {
+    filter_codec_infos(get_decoders(), media_type)
+}
+
+/// Lists the **pixel formats** the video encoder `name` accepts, e.g. `["yuv420p", "yuvj420p",
+/// "yuv422p", ...]` for `libx264`, in the encoder's order of preference.
+///
+/// Only these formats can be given to
+/// [`Output::set_video_pix_fmt`](crate::Output::set_video_pix_fmt) with this encoder;
+/// `av_get_pix_fmt` turns a name back into an `AVPixelFormat`.
+///
+/// # Returns
+/// - `Ok(formats)`: The names of the formats. The list is empty when the encoder does not
+///   declare its formats, which means that it takes any of them (e.g. `rawvideo`), and for
+///   encoders that are not video encoders.
+/// - `Err(OpenOutputError::EncoderNotFound)`: The linked FFmpeg has no encoder `name`.
+///
+/// # Example
+/// ```rust
+/// for format in encoder_pixel_formats("libx264").unwrap() {
+///     println!("{format}");
+/// }
+/// ```
+pub fn encoder_pixel_formats(name: &str) -> Result<Vec<String>> {
+    supported_formats(name, AVMediaType::AVMEDIA_TYPE_VIDEO, |format: AVPixelFormat| unsafe {
+        av_get_pix_fmt_name(format)
+    })
+}
+
+/// Lists the **sample formats** the audio encoder `name` accepts, e.g. `["fltp"]` for `aac`,
+/// in the encoder's order of preference.
+///
+/// # Returns
+/// - `Ok(formats)`: The names of the formats. The list is empty when the encoder does not
+///   declare its formats, which means that it takes any of them, and for encoders that are
+///   not audio encoders.
+/// - `Err(OpenOutputError::EncoderNotFound)`: The linked FFmpeg has no encoder `name`.
+///
+/// # Example
+/// ```rust
+/// assert!(encoder_sample_formats("libmp3lame").unwrap().contains(&"s16p".to_string()));
+/// ```
+pub fn encoder_sample_formats(name: &str) -> Result<Vec<String>> {
+    supported_formats(name, AVMediaType::AVMEDIA_TYPE_AUDIO, |format: AVSampleFormat| unsafe {
+        av_get_sample_fmt_name(format)
+    })
+}
+
+/// A format type an encoder declares a list of, terminated by `NONE`.
+trait SupportedFormat: Copy + PartialEq {
+    const CONFIG: AVCodecConfig;
+    const NONE: Self;
+}
+
+impl SupportedFormat for AVPixelFormat {
+    const CONFIG: AVCodecConfig = AVCodecConfig::AV_CODEC_CONFIG_PIX_FORMAT;
+    const NONE: Self = AVPixelFormat::AV_PIX_FMT_NONE;
+}
+
+impl SupportedFormat for AVSampleFormat {
+    const CONFIG: AVCodecConfig = AVCodecConfig::AV_CODEC_CONFIG_SAMPLE_FORMAT;
+    const NONE: Self = AVSampleFormat::AV_SAMPLE_FMT_NONE;
+}
+
+/// Reads the list of formats of the encoder `name`, if it has the media type of `F`.
+fn supported_formats<F: SupportedFormat>(
+    name: &str,
+    media_type: AVMediaType,
+    format_name: impl Fn(F) -> *const std::ffi::c_char,
+) -> Result<Vec<String>> {
+    let c_name = CString::new(name)?;
+    unsafe {
+        let codec = avcodec_find_encoder_by_name(c_name.as_ptr());
+        if codec.is_null() {
+            return Err(OpenOutputError::EncoderNotFound.into());
+        }
+        if (*codec).type_ != media_type {
+            return Ok(Vec::new());
+        }
+
+        let mut formats: *const F = null();
+        #[cfg(not(feature = "docs-rs"))]
+        {
+            let ret = avcodec_get_supported_config(
+                null(),
+                codec,
+                F::CONFIG,
+                0,
+                &mut formats as *mut _ as *mut *const c_void,
+                null_mut(),
+            );
+            if ret < 0 {
+                return Err(OpenOutputError::from(ret).into());
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut current = formats;
+        while !current.is_null() && *current != F::NONE {
+            let format_name = format_name(*current);
+            if !format_name.is_null() {
+                names.push(CStr::from_ptr(format_name).to_string_lossy().into_owned());
+            }
+            current = current.add(1);
+        }
+        if formats.is_null() {
+            debug!("Encoder '{name}' does not declare its formats, it accepts any of them");
+        }
+        Ok(names)
+    }
+}
+
+fn filter_codec_infos(codec_infos: Vec<CodecInfo>, media_type: Option<AVMediaType>) -> Vec<CodecInfo> {
+    match media_type {
+        None => codec_infos,
+        Some(media_type) => codec_infos
+            .into_iter()
+            .filter(|codec_info| codec_info.media_type == media_type)
+            .collect(),
+    }
+}
+
 fn get_codec_infos(encoder: i32) -> Vec<CodecInfo> {
     let mut codec_infos = Vec::new();
     let descs = get_codecs_sorted();
@@ -124,6 +292,10 @@ fn get_codec_infos(encoder: i32) -> Vec<CodecInfo> {
                         media_type: (*codec.inner).type_,
                         codec_id: (*codec.inner).id,
                         codec_capabilities: (*codec.inner).capabilities,
+                        hw_accelerated: (*codec.inner).capabilities
+                            & (AV_CODEC_CAP_HARDWARE | AV_CODEC_CAP_HYBRID) as i32
+                            != 0
+                            || !avcodec_get_hw_config(codec.inner, 0).is_null(),
                     };
                     codec_infos.push(codec_info);
                 }
@@ -205,4 +377,30 @@ mod tests {
         let decoders = get_decoders();
         // println!("{:?}", decoders);
     }
+
+    #[test]
+    fn test_list_encoders_by_media_type() {
+        let encoders = list_encoders(Some(AVMediaType::AVMEDIA_TYPE_VIDEO));
+        assert!(!encoders.is_empty());
+        assert!(encoders
+            .iter()
+            .all(|encoder| encoder.media_type == AVMediaType::AVMEDIA_TYPE_VIDEO));
+        assert!(list_encoders(None).len() >= encoders.len());
+    }
+
+    #[test]
+    fn test_encoder_formats() {
+        let pixel_formats = encoder_pixel_formats("libx264").unwrap();
+        assert!(pixel_formats.contains(&"yuv420p".to_string()));
+        let sample_formats = encoder_sample_formats("aac").unwrap();
+        assert_eq!(sample_formats, vec!["fltp".to_string()]);
+
+        // rawvideo takes any pixel format, and aac has no pixel formats
+        assert!(encoder_pixel_formats("rawvideo").unwrap().is_empty());
+        assert!(encoder_pixel_formats("aac").unwrap().is_empty());
+        assert!(matches!(
+            encoder_pixel_formats("no_such_encoder"),
+            Err(crate::error::Error::OpenOutput(OpenOutputError::EncoderNotFound))
+        ));
+    }
 }
@@ -1,4 +1,18 @@
-use ffmpeg_next::format;
+use ffmpeg_next::{format, media};
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_sys_next::{
+    av_find_best_stream, av_get_pix_fmt_name, av_get_sample_fmt_name, avcodec_descriptor_get,
+    AV_CODEC_PROP_INTRA_ONLY, AV_CODEC_PROP_REORDER,
+};
+use libc::EINVAL;
+use log::error;
+use std::ffi::CStr;
+use std::ptr::null_mut;
+use crate::util::ffmpeg_utils::{pixel_format, sample_format};
+
+/// Number of packets [`is_all_intra`] and [`has_b_frames`] read when the codec alone does not
+/// tell, enough to cover a GOP of 250 frames, the default of x264 and x265.
+const FRAME_TYPE_SAMPLE_PACKETS: usize = 300;
 
 /// Gets the duration of a media file in microseconds.
 ///
@@ -75,3 +89,308 @@ pub fn get_metadata(input: impl Into<String>) -> Result<Vec<(String, String)>, f
         .map(|(k, v)| (k.to_string(), v.to_string()))
         .collect())
 }
+
+/// Gets the pixel format of a video stream (e.g., "yuv420p"), i.e. the format its frames
+/// have once decoded, before any filtering.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"video.mp4"`).
+/// - `stream_index`: The index of the stream in the input.
+///
+/// # Returns
+/// - `Result<String, ffmpeg_next::Error>`: Returns the name of the pixel format.
+///   Returns `ffmpeg_next::Error::StreamNotFound` if the input has no stream `stream_index`,
+///   `EINVAL` if that stream is not a video stream, and `ffmpeg_next::Error::InvalidData`
+///   if its pixel format could not be determined.
+///
+/// # Example
+/// ```rust
+/// let pixel_format = get_video_pixel_format("video.mp4", 0).unwrap();
+/// if pixel_format != "yuv420p" {
+///     println!("needs a conversion from {}", pixel_format);
+/// }
+/// ```
+pub fn get_video_pixel_format(input: impl Into<String>, stream_index: usize) -> Result<String, ffmpeg_next::Error> {
+    let format = stream_format(input.into(), stream_index, media::Type::Video)?;
+
+    let pix_fmt = pixel_format(format).ok_or(ffmpeg_next::Error::InvalidData)?;
+    let name = unsafe { av_get_pix_fmt_name(pix_fmt) };
+    if name.is_null() {
+        return Err(ffmpeg_next::Error::InvalidData);
+    }
+    Ok(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+}
+
+/// Gets the sample format of an audio stream (e.g., "fltp"), i.e. the format its frames
+/// have once decoded, before any filtering.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"audio.m4a"`).
+/// - `stream_index`: The index of the stream in the input.
+///
+/// # Returns
+/// - `Result<String, ffmpeg_next::Error>`: Returns the name of the sample format.
+///   Returns `ffmpeg_next::Error::StreamNotFound` if the input has no stream `stream_index`,
+///   `EINVAL` if that stream is not an audio stream, and `ffmpeg_next::Error::InvalidData`
+///   if its sample format could not be determined.
+///
+/// # Example
+/// ```rust
+/// let sample_format = get_audio_sample_format("video.mp4", 1).unwrap();
+/// println!("Sample format: {}", sample_format);
+/// ```
+pub fn get_audio_sample_format(input: impl Into<String>, stream_index: usize) -> Result<String, ffmpeg_next::Error> {
+    let format = stream_format(input.into(), stream_index, media::Type::Audio)?;
+
+    let sample_fmt = sample_format(format).ok_or(ffmpeg_next::Error::InvalidData)?;
+    let name = unsafe { av_get_sample_fmt_name(sample_fmt) };
+    if name.is_null() {
+        return Err(ffmpeg_next::Error::InvalidData);
+    }
+    Ok(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+}
+
+/// Gets the index of the best stream of a media type, i.e. the one FFmpeg selects by default
+/// (`av_find_best_stream`), e.g. the main video stream of a file that also has thumbnails.
+///
+/// FFmpeg ranks the streams by their `default` disposition, the number of frames read while
+/// probing and their bitrate. Streams with the attached picture disposition (cover art,
+/// thumbnails) are only returned when the input has no other stream of that type.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"video.mp4"`).
+/// - `media_type`: The type of the stream, e.g. `media::Type::Video` or `media::Type::Audio`.
+///
+/// # Returns
+/// - `Result<usize, ffmpeg_next::Error>`: Returns the index of the stream in the input.
+///   Returns `ffmpeg_next::Error::StreamNotFound` if the input has no stream of `media_type`.
+///
+/// # Example
+/// ```rust
+/// let video_index = get_best_stream("video.mp4", media::Type::Video).unwrap();
+/// let pixel_format = get_video_pixel_format("video.mp4", video_index).unwrap();
+/// ```
+pub fn get_best_stream(input: impl Into<String>, media_type: media::Type) -> Result<usize, ffmpeg_next::Error> {
+    let input = input.into();
+    let format_context = format::input(&input)?;
+
+    let ret = unsafe { av_find_best_stream(format_context.as_ptr() as *mut _, media_type.into(), -1, -1, null_mut(), 0) };
+    if ret < 0 {
+        error!("Input '{input}' has no {media_type:?} stream.");
+        return Err(ffmpeg_next::Error::from(ret));
+    }
+    let best = ret as usize;
+
+    let is_attached_pic = |index: usize| {
+        format_context
+            .stream(index)
+            .is_some_and(|stream| stream.disposition().contains(Disposition::ATTACHED_PIC))
+    };
+    if !is_attached_pic(best) {
+        return Ok(best);
+    }
+
+    // the first of the remaining streams, preferring the default one
+    let fallback = format_context
+        .streams()
+        .filter(|stream| stream.parameters().medium() == media_type && !is_attached_pic(stream.index()))
+        .min_by_key(|stream| !stream.disposition().contains(Disposition::DEFAULT))
+        .map(|stream| stream.index());
+    Ok(fallback.unwrap_or(best))
+}
+
+/// Checks whether a video stream is all-intra, i.e. every frame is a keyframe, as with
+/// ProRes, DNxHD or MJPEG. Such streams can be cut and scrubbed at any frame, which editors
+/// prefer.
+///
+/// Codecs that can only code intra frames are recognized from the codec parameters. For the
+/// others the determination requires reading packets: up to 300 packets of the stream are
+/// read, and the stream is all-intra if they are all keyframes. A stream that never produces
+/// a non-keyframe, e.g. H.264 encoded with a GOP of 1, is therefore reported as all-intra,
+/// as is a stream with a GOP longer than the packets read.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"video.mov"`).
+/// - `stream_index`: The index of the stream in the input.
+///
+/// # Returns
+/// - `Result<bool, ffmpeg_next::Error>`: Returns `true` if the stream is all-intra.
+///   Returns `ffmpeg_next::Error::StreamNotFound` if the input has no stream `stream_index`,
+///   and `EINVAL` if that stream is not a video stream.
+///
+/// # Example
+/// ```rust
+/// if !is_all_intra("camera.mp4", 0).unwrap() {
+///     println!("transcode to ProRes for smooth scrubbing");
+/// }
+/// ```
+pub fn is_all_intra(input: impl Into<String>, stream_index: usize) -> Result<bool, ffmpeg_next::Error> {
+    let (mut format_context, props) = open_video_stream(input.into(), stream_index)?;
+    if props & AV_CODEC_PROP_INTRA_ONLY as i32 != 0 {
+        return Ok(true);
+    }
+    Ok(sample_packets(&mut format_context, stream_index).iter().all(|(key, _)| *key))
+}
+
+/// Checks whether a video stream has B-frames, i.e. frames stored before frames they are
+/// shown after, which makes decoding and exact seeking more expensive.
+///
+/// Codecs that cannot reorder frames (e.g. MJPEG or VP8) are recognized from the codec
+/// parameters. For the others the determination requires reading packets: up to 300
+/// packets of the stream are read, and the stream has B-frames if their presentation
+/// timestamps are out of order. If the packets have no presentation timestamps, the
+/// reorder delay declared by the codec parameters is used instead.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"video.mp4"`).
+/// - `stream_index`: The index of the stream in the input.
+///
+/// # Returns
+/// - `Result<bool, ffmpeg_next::Error>`: Returns `true` if the stream has B-frames.
+///   Returns `ffmpeg_next::Error::StreamNotFound` if the input has no stream `stream_index`,
+///   and `EINVAL` if that stream is not a video stream.
+///
+/// # Example
+/// ```rust
+/// let has_b_frames = has_b_frames("video.mp4", 0).unwrap();
+/// println!("B-frames: {}", has_b_frames);
+/// ```
+pub fn has_b_frames(input: impl Into<String>, stream_index: usize) -> Result<bool, ffmpeg_next::Error> {
+    let (mut format_context, props) = open_video_stream(input.into(), stream_index)?;
+    if props & AV_CODEC_PROP_INTRA_ONLY as i32 != 0 || props & AV_CODEC_PROP_REORDER as i32 == 0 {
+        return Ok(false);
+    }
+    let video_delay = match format_context.stream(stream_index) {
+        Some(stream) => unsafe { (*stream.parameters().as_ptr()).video_delay },
+        None => 0,
+    };
+
+    let mut max_pts = None;
+    for (_, pts) in sample_packets(&mut format_context, stream_index) {
+        let Some(pts) = pts else {
+            continue;
+        };
+        if max_pts.is_some_and(|max_pts| pts < max_pts) {
+            return Ok(true);
+        }
+        max_pts = max_pts.max(Some(pts));
+    }
+    Ok(max_pts.is_none() && video_delay > 0)
+}
+
+/// Opens `input` and checks that stream `stream_index` is a video stream. Returns the input
+/// and the properties (`AV_CODEC_PROP_*`) of the codec of the stream.
+fn open_video_stream(input: String, stream_index: usize) -> Result<(format::context::Input, i32), ffmpeg_next::Error> {
+    let format_context = format::input(&input)?;
+
+    let props = {
+        let Some(stream) = format_context.stream(stream_index) else {
+            error!("Input '{input}' has no stream {stream_index}.");
+            return Err(ffmpeg_next::Error::StreamNotFound);
+        };
+        let parameters = stream.parameters();
+        if parameters.medium() != media::Type::Video {
+            error!(
+                "Stream {stream_index} of input '{input}' is a {:?} stream, not a video stream.",
+                parameters.medium()
+            );
+            return Err(ffmpeg_next::Error::Other { errno: EINVAL });
+        }
+        let descriptor = unsafe { avcodec_descriptor_get(parameters.id().into()) };
+        if descriptor.is_null() {
+            0
+        } else {
+            unsafe { (*descriptor).props }
+        }
+    };
+    Ok((format_context, props))
+}
+
+/// Reads the first packets of stream `stream_index`, returning whether each one is a
+/// keyframe and its presentation timestamp, in decoding order.
+fn sample_packets(format_context: &mut format::context::Input, stream_index: usize) -> Vec<(bool, Option<i64>)> {
+    format_context
+        .packets()
+        .filter(|(stream, _)| stream.index() == stream_index)
+        .take(FRAME_TYPE_SAMPLE_PACKETS)
+        .map(|(_, packet)| (packet.is_key(), packet.pts()))
+        .collect()
+}
+
+/// Returns the raw `format` of the codec parameters of stream `stream_index`, checking
+/// that the stream is of `media_type`.
+fn stream_format(input: String, stream_index: usize, media_type: media::Type) -> Result<i32, ffmpeg_next::Error> {
+    let format_context = format::input(&input)?;
+
+    let Some(stream) = format_context.stream(stream_index) else {
+        error!("Input '{input}' has no stream {stream_index}.");
+        return Err(ffmpeg_next::Error::StreamNotFound);
+    };
+    let parameters = stream.parameters();
+    if parameters.medium() != media_type {
+        error!(
+            "Stream {stream_index} of input '{input}' is a {:?} stream, not a {media_type:?} stream.",
+            parameters.medium()
+        );
+        return Err(ffmpeg_next::Error::Other { errno: EINVAL });
+    }
+
+    Ok(unsafe { (*parameters.as_ptr()).format })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_formats() {
+        assert!(get_video_pixel_format("test.mp4", 0).unwrap().starts_with("yuv"));
+        assert!(get_audio_sample_format("test.mp4", 1).is_ok());
+
+        // the streams have the wrong type, or do not exist
+        assert_eq!(get_audio_sample_format("test.mp4", 0), Err(ffmpeg_next::Error::Other { errno: EINVAL }));
+        assert_eq!(get_video_pixel_format("test.mp4", 1), Err(ffmpeg_next::Error::Other { errno: EINVAL }));
+        assert_eq!(get_video_pixel_format("test.mp4", 99), Err(ffmpeg_next::Error::StreamNotFound));
+    }
+
+    #[test]
+    fn test_get_best_stream() {
+        assert_eq!(get_best_stream("test.mp4", media::Type::Video), Ok(0));
+        assert_eq!(get_best_stream("test.mp4", media::Type::Audio), Ok(1));
+        assert_eq!(get_best_stream("test.mp4", media::Type::Subtitle), Err(ffmpeg_next::Error::StreamNotFound));
+    }
+
+    #[test]
+    fn test_frame_types() {
+        use crate::core::context::ffmpeg_context::FfmpegContext;
+        use crate::core::context::output::Output;
+
+        // MJPEG only codes intra frames, MPEG-4 part 2 is set up with B-frames
+        let outputs = [
+            ("output_intra.avi", Output::from("output_intra.avi").set_video_codec("mjpeg"), true, false),
+            (
+                "output_b_frames.mp4",
+                Output::from("output_b_frames.mp4").set_video_codec("mpeg4").set_gop_size(12).set_video_codec_opt("bf", "2"),
+                false,
+                true,
+            ),
+        ];
+        for (file, output, all_intra, b_frames) in outputs {
+            let result = FfmpegContext::builder()
+                .input("test.mp4")
+                .output(output.add_stream_map("0:v").set_recording_time_us(2_000_000))
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait();
+            assert!(result.is_ok(), "{file}: {:?}", result.err());
+            assert_eq!(is_all_intra(file, 0), Ok(all_intra), "{file}");
+            assert_eq!(has_b_frames(file, 0), Ok(b_frames), "{file}");
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(is_all_intra("test.mp4", 1), Err(ffmpeg_next::Error::Other { errno: EINVAL }));
+        assert_eq!(has_b_frames("test.mp4", 99), Err(ffmpeg_next::Error::StreamNotFound));
+    }
+}
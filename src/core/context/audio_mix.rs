@@ -0,0 +1,169 @@
+use crate::core::context::filter_complex::FilterComplex;
+
+/// When the mix of an [`AudioMix`] ends (the `duration` option of `amix`).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum MixDuration {
+    /// The mix lasts as long as the longest input, the others are treated as silence
+    /// once they end. This is the default.
+    #[default]
+    Longest,
+    /// The mix ends with the shortest input.
+    Shortest,
+    /// The mix ends with the first input, e.g. the narration, cutting the background music.
+    First,
+}
+
+impl MixDuration {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MixDuration::Longest => "longest",
+            MixDuration::Shortest => "shortest",
+            MixDuration::First => "first",
+        }
+    }
+}
+
+/// Mixes two audio streams into one, e.g. background music under a narration, with the
+/// `amix` filter.
+///
+/// Both streams are first resampled to a common sample rate and channel layout (48 kHz
+/// stereo by default, see [`set_format`](AudioMix::set_format)), so inputs recorded with
+/// different formats can be mixed. The mix converts into a [`FilterComplex`] to pass to
+/// [`FfmpegContextBuilder::filter_desc`](crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder::filter_desc);
+/// its output is picked by the output like any unlabeled filter output.
+///
+/// # Example
+/// ```rust
+/// // narration at full volume, music at 30%, ending with the narration
+/// let mix = AudioMix::new("0:a", "1:a")
+///     .set_weights(1.0, 0.3)
+///     .set_duration(MixDuration::First);
+///
+/// let context = FfmpegContext::builder()
+///     .inputs(vec!["narration.wav", "music.mp3"])
+///     .filter_desc(mix)
+///     .output("voiceover.m4a")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct AudioMix {
+    inputs: [(String, f32); 2],
+    duration: MixDuration,
+    normalize: bool,
+    sample_rate: u32,
+    channel_layout: String,
+}
+
+impl AudioMix {
+    /// Creates a mix of two audio streams, given by stream specifiers such as `"0:a"` or
+    /// `"1:a:0"`, with equal weights.
+    pub fn new(first: impl Into<String>, second: impl Into<String>) -> Self {
+        Self {
+            inputs: [(first.into(), 1.0), (second.into(), 1.0)],
+            duration: MixDuration::default(),
+            normalize: true,
+            sample_rate: 48000,
+            channel_layout: "stereo".to_string(),
+        }
+    }
+
+    /// Sets the weight of each stream in the mix, e.g. `(1.0, 0.3)` to keep the music of the
+    /// second stream under the voice of the first one. Both default to `1.0`.
+    pub fn set_weights(mut self, first: f32, second: f32) -> Self {
+        self.inputs[0].1 = first;
+        self.inputs[1].1 = second;
+        self
+    }
+
+    /// Sets when the mix ends, see [`MixDuration`].
+    pub fn set_duration(mut self, duration: MixDuration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets whether the mix is normalized (`true` by default): the weighted sum is divided
+    /// by the sum of the weights, so it cannot clip.
+    ///
+    /// Without normalization the streams are summed at the volume given by their weights,
+    /// which keeps each stream as loud as in its input but clips when loud parts overlap.
+    pub fn set_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Sets the format both streams are resampled to before mixing, which is also the
+    /// format of the mix.
+    ///
+    /// ### Parameters:
+    /// - `sample_rate`: The sample rate in Hz, e.g. `44100`.
+    /// - `channel_layout`: The channel layout name, e.g. `"mono"`, `"stereo"` or `"5.1"`.
+    pub fn set_format(mut self, sample_rate: u32, channel_layout: impl Into<String>) -> Self {
+        self.sample_rate = sample_rate;
+        self.channel_layout = channel_layout.into();
+        self
+    }
+
+    fn filter_desc(&self) -> String {
+        let mut desc = String::new();
+        for i in 0..self.inputs.len() {
+            desc.push_str(&format!(
+                "[mix_in{i}]aresample={},aformat=sample_fmts=fltp:channel_layouts={}[mix{i}];",
+                self.sample_rate, self.channel_layout
+            ));
+        }
+        let weights = self.inputs.iter().map(|(_, weight)| weight.to_string()).collect::<Vec<_>>().join(" ");
+        desc.push_str(&format!(
+            "[mix0][mix1]amix=inputs=2:duration={}:weights='{weights}':normalize={}",
+            self.duration.as_str(),
+            self.normalize as u8
+        ));
+        desc
+    }
+}
+
+impl From<AudioMix> for FilterComplex {
+    fn from(mix: AudioMix) -> Self {
+        let mut filter_complex = FilterComplex::from(mix.filter_desc());
+        for (i, (stream_specifier, _)) in mix.inputs.into_iter().enumerate() {
+            filter_complex = filter_complex.add_input(stream_specifier, format!("mix_in{i}"));
+        }
+        filter_complex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::output::Output;
+
+    #[test]
+    fn test_filter_desc() {
+        let mix = AudioMix::new("0:a", "1:a")
+            .set_weights(1.0, 0.25)
+            .set_duration(MixDuration::First)
+            .set_format(44100, "mono");
+        assert_eq!(
+            mix.filter_desc(),
+            "[mix_in0]aresample=44100,aformat=sample_fmts=fltp:channel_layouts=mono[mix0];\
+             [mix_in1]aresample=44100,aformat=sample_fmts=fltp:channel_layouts=mono[mix1];\
+             [mix0][mix1]amix=inputs=2:duration=first:weights='1 0.25':normalize=1"
+        );
+    }
+
+    #[test]
+    fn test_audio_mix() {
+        let mix = AudioMix::new("0:a", "1:a").set_weights(1.0, 0.3).set_normalize(false);
+        let output = Output::from("output_mix.m4a");
+        let result = FfmpegContext::builder()
+            .inputs(vec!["test.mp4", "test.mp4"])
+            .filter_desc(mix)
+            .output(output)
+            .build();
+        assert!(result.is_ok(), "{:?}", result.err());
+        let result = result.unwrap().start().unwrap().wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+        std::fs::remove_file("output_mix.m4a").unwrap();
+    }
+}
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use crate::core::codec::Codec;
+use crate::core::context::input::ErrorResilience;
 use crate::core::context::{FrameBox, PacketBox, Stream};
 use crate::core::hwaccel::HWAccelID;
 use crossbeam_channel::{Receiver, Sender};
@@ -21,6 +24,13 @@ pub(crate) struct DecoderStream {
     pub(crate) time_base: AVRational,
     pub(crate) avg_framerate: AVRational,
     pub(crate) have_sub2video: bool,
+    pub(crate) keyframes_only: bool,
+    /// Set by `Input::ignore_*`, the stream is never connected to the pipeline.
+    pub(crate) ignored: bool,
+    pub(crate) decoder_opts: Option<HashMap<CString, CString>>,
+    pub(crate) error_resilience: ErrorResilience,
+    /// Shared with the other streams of the input, counts the packets that failed to decode.
+    pub(crate) decode_errors: Arc<AtomicU64>,
 
     pub(crate) hwaccel_id: HWAccelID,
     pub(crate) hwaccel_device_type: AVHWDeviceType,
@@ -58,6 +68,11 @@ impl DecoderStream {
             time_base,
             avg_framerate,
             have_sub2video: false,
+            keyframes_only: false,
+            ignored: false,
+            decoder_opts: None,
+            error_resilience: ErrorResilience::default(),
+            decode_errors: Arc::new(AtomicU64::new(0)),
             hwaccel_id,
             hwaccel_device_type,
             hwaccel_device,
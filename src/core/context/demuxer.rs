@@ -1,5 +1,6 @@
 use crate::core::codec::Codec;
 use crate::core::context::decoder_stream::DecoderStream;
+use crate::core::context::input::ErrorResilience;
 use crate::core::context::PacketBox;
 use crate::core::hwaccel::HWAccelID;
 use crate::core::scheduler::input_controller::SchNode;
@@ -15,13 +16,17 @@ use ffmpeg_sys_next::{
     av_codec_is_decoder, av_codec_iterate, av_get_pix_fmt, av_hwdevice_find_type_by_name,
     av_hwdevice_get_type_name, avcodec_descriptor_get, avcodec_descriptor_get_by_name,
     avcodec_find_decoder, avcodec_find_decoder_by_name,
-    avcodec_get_hw_config, AVCodecID, AVCodecParameters, AVFormatContext,
-    AVHWDeviceType, AVMediaType, AVPixelFormat, AVERROR, AVERROR_DECODER_NOT_FOUND, EINVAL,
+    avcodec_get_hw_config, AVCodecID, AVCodecParameters, AVDiscard, AVFormatContext,
+    av_gettime_relative, AVHWDeviceType, AVMediaType, AVPixelFormat, AVERROR, AVERROR_DECODER_NOT_FOUND,
+    EINVAL,
 };
 use log::{debug, error, warn};
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) struct Demuxer {
     pub(crate) url: String,
@@ -33,8 +38,22 @@ pub(crate) struct Demuxer {
     pub(crate) readrate: Option<f32>,
     pub(crate) start_time_us: Option<i64>,
     pub(crate) recording_time_us: Option<i64>,
+    pub(crate) accurate_seek: bool,
+    pub(crate) normalize_sar: bool,
     pub(crate) exit_on_error: Option<bool>,
+    /// Retries of transient read errors, see `Input::set_read_retry`.
+    pub(crate) read_retry: Option<(u32, Duration)>,
+    /// Corrects packets whose dts does not increase, see `Input::set_fix_timestamps`.
+    pub(crate) fix_timestamps: bool,
+    /// Scaled size and scaler of the video, see `Input::set_hw_scale`.
+    pub(crate) hw_scale: Option<(i32, i32, &'static str)>,
+    /// Number of packets the decoders of this input failed to decode.
+    pub(crate) decode_errors: Arc<AtomicU64>,
+    /// Set when the input has an open or read timeout, installed as the interrupt
+    /// callback of `in_fmt_ctx`, which must be freed before it.
+    pub(crate) interrupt: Option<Arc<InputInterrupt>>,
     pub(crate) stream_loop: Option<i32>,
+    pub(crate) canvas_size: Option<(u32, u32)>,
     pub(crate) copy_ts: bool,
 
     #[cfg(windows)]
@@ -50,6 +69,56 @@ pub(crate) struct Demuxer {
 unsafe impl Send for Demuxer {}
 unsafe impl Sync for Demuxer {}
 
+/// Deadline of the blocking FFmpeg calls made on an input, checked by its interrupt callback.
+pub(crate) struct InputInterrupt {
+    pub(crate) open_timeout: Option<Duration>,
+    pub(crate) read_timeout: Option<Duration>,
+    /// In `av_gettime_relative()` microseconds, 0 while no deadline is armed.
+    deadline_us: AtomicI64,
+    timed_out: AtomicBool,
+}
+
+impl InputInterrupt {
+    pub(crate) fn new(open_timeout: Option<Duration>, read_timeout: Option<Duration>) -> Self {
+        Self {
+            open_timeout,
+            read_timeout,
+            deadline_us: AtomicI64::new(0),
+            timed_out: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts a deadline `timeout` from now, or clears it when `timeout` is `None`.
+    pub(crate) fn arm(&self, timeout: Option<Duration>) {
+        let deadline_us = match timeout {
+            Some(timeout) => unsafe { av_gettime_relative() }.saturating_add(timeout.as_micros() as i64).max(1),
+            None => 0,
+        };
+        self.deadline_us.store(deadline_us, Ordering::Relaxed);
+    }
+
+    /// Returns whether the armed deadline has passed, remembering it for [`Self::timed_out`].
+    pub(crate) fn expired(&self) -> bool {
+        let deadline_us = self.deadline_us.load(Ordering::Relaxed);
+        if deadline_us == 0 || unsafe { av_gettime_relative() } < deadline_us {
+            return false;
+        }
+        self.timed_out.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Returns whether a call was interrupted because its deadline passed.
+    pub(crate) fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+}
+
+/// `AVIOInterruptCB` callback, `opaque` points to the [`InputInterrupt`] of the input.
+pub(crate) unsafe extern "C" fn input_interrupt_callback(opaque: *mut libc::c_void) -> libc::c_int {
+    let interrupt = &*(opaque as *const InputInterrupt);
+    interrupt.expired() as libc::c_int
+}
+
 impl Demuxer {
     pub(crate) fn new(
         url: String,
@@ -63,14 +132,25 @@ impl Demuxer {
         readrate: Option<f32>,
         start_time_us: Option<i64>,
         recording_time_us: Option<i64>,
+        accurate_seek: bool,
+        keyframes_only: bool,
+        ignored_media_types: Vec<AVMediaType>,
+        normalize_sar: bool,
+        decoder_opts: Option<HashMap<CString, CString>>,
         exit_on_error: Option<bool>,
+        read_retry: Option<(u32, Duration)>,
+        fix_timestamps: bool,
+        error_resilience: ErrorResilience,
+        interrupt: Option<Arc<InputInterrupt>>,
         stream_loop: Option<i32>,
+        canvas_size: Option<(u32, u32)>,
         hwaccel: Option<String>,
         hwaccel_device: Option<String>,
         hwaccel_output_format: Option<String>,
+        hw_scale: Option<(i32, i32, &'static str)>,
         copy_ts: bool,
     ) -> crate::error::Result<Self> {
-        let streams = Self::init_streams(
+        let mut streams = Self::init_streams(
             in_fmt_ctx,
             video_codec,
             audio_codec,
@@ -79,6 +159,22 @@ impl Demuxer {
             hwaccel_device,
             hwaccel_output_format,
         )?;
+        let decode_errors = Arc::new(AtomicU64::new(0));
+        for stream in &mut streams {
+            stream.keyframes_only = keyframes_only && stream.codec_type == AVMEDIA_TYPE_VIDEO;
+            stream.decoder_opts = decoder_opts.clone();
+            stream.error_resilience = error_resilience;
+            stream.decode_errors = decode_errors.clone();
+            if ignored_media_types.contains(&stream.codec_type) {
+                stream.ignored = true;
+                // no packets of this stream are read any further
+                unsafe { (*stream.stream.inner).discard = AVDiscard::AVDISCARD_ALL };
+            }
+        }
+        if !streams.is_empty() && streams.iter().all(|stream| stream.ignored) {
+            error!("No streams selected from input '{url}': all of its streams are ignored.");
+            return Err(OpenInputError::NoStreamsSelected(url).into());
+        }
 
         Ok(Self {
             url,
@@ -89,8 +185,16 @@ impl Demuxer {
             readrate,
             start_time_us,
             recording_time_us,
+            accurate_seek,
+            normalize_sar,
             exit_on_error,
+            read_retry,
+            fix_timestamps,
+            hw_scale,
+            decode_errors,
+            interrupt,
             stream_loop,
+            canvas_size,
             copy_ts,
             #[cfg(windows)]
             hwaccel,
@@ -1,6 +1,6 @@
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use crate::core::context::output::VSyncMethod;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use crate::core::context::output::{ForceKeyframes, QualityRamp, VSyncMethod};
 use crate::core::context::{FrameBox, PacketBox, Stream};
 use crossbeam_channel::{Receiver, Sender};
 use ffmpeg_sys_next::{AVCodec, AVMediaType, AVStream};
@@ -13,6 +13,17 @@ pub(crate) struct EncoderStream {
     pub(crate) encoder: *const AVCodec,
     pub(crate) vsync_method: Option<VSyncMethod>,
     pub(crate) qscale: Option<i32>,
+    pub(crate) gop_size: Option<i32>,
+    pub(crate) max_b_frames: Option<i32>,
+    pub(crate) keyint_min: Option<i32>,
+    // set when realtime drop is enabled, counts the dropped frames
+    pub(crate) dropped_frames: Option<Arc<AtomicU64>>,
+    // only every Nth frame is encoded when set
+    pub(crate) frame_interval: Option<u32>,
+    // frames to encode as keyframes, video only
+    pub(crate) force_keyframes: Option<ForceKeyframes>,
+    // the quality of each frame by timestamp, video only
+    pub(crate) quality_ramp: Option<QualityRamp>,
     src: Option<Receiver<FrameBox>>,
     dst: Option<Sender<PacketBox>>,
     dst_pre: Option<Sender<PacketBox>>,
@@ -27,6 +38,13 @@ impl EncoderStream {
         encoder: *const AVCodec,
         vsync_method: Option<VSyncMethod>,
         qscale: Option<i32>,
+        gop_size: Option<i32>,
+        max_b_frames: Option<i32>,
+        keyint_min: Option<i32>,
+        dropped_frames: Option<Arc<AtomicU64>>,
+        frame_interval: Option<u32>,
+        force_keyframes: Option<ForceKeyframes>,
+        quality_ramp: Option<QualityRamp>,
         src: Receiver<FrameBox>,
         dst: Sender<PacketBox>,
         dst_pre: Sender<PacketBox>,
@@ -39,6 +57,13 @@ impl EncoderStream {
             encoder,
             vsync_method,
             qscale,
+            gop_size,
+            max_b_frames,
+            keyint_min,
+            dropped_frames,
+            frame_interval,
+            force_keyframes,
+            quality_ramp,
             src: Some(src),
             dst: Some(dst),
             dst_pre: Some(dst_pre),
@@ -1,23 +1,28 @@
-use crate::core::context::demuxer::Demuxer;
+use crate::core::context::demuxer::{input_interrupt_callback, Demuxer, InputInterrupt};
 use crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder;
 use crate::core::context::filter_complex::FilterComplex;
 use crate::core::context::filter_graph::FilterGraph;
 use crate::core::context::input::Input;
-use crate::core::context::input_filter::{InputFilter, IFILTER_FLAG_AUTOROTATE};
-use crate::core::context::muxer::Muxer;
+use crate::core::context::input_filter::{InputFilter, IFILTER_FLAG_AUTOROTATE, IFILTER_FLAG_NORMALIZE_SAR};
+use crate::core::context::muxer::{Muxer, DEFAULT_MAX_MUXING_QUEUE_SIZE};
 use crate::core::context::output::{Output, StreamMap};
 use crate::core::context::output_filter::{
     OutputFilter, OFILTER_FLAG_AUDIO_24BIT, OFILTER_FLAG_AUTOSCALE, OFILTER_FLAG_DISABLE_CONVERT,
 };
-use crate::core::context::{frame_alloc, CodecContext};
+use crate::core::context::{frame_alloc, out_fmt_ctx_free, BsfContextBox, CodecContext};
+#[cfg(not(feature = "docs-rs"))]
+use crate::core::hwaccel::hw_scaler_for_format;
 use crate::core::scheduler::ffmpeg_scheduler;
 use crate::core::scheduler::ffmpeg_scheduler::{FfmpegScheduler, Initialization};
 #[cfg(not(feature = "docs-rs"))]
-use crate::core::scheduler::filter_task::graph_opts_apply;
+use crate::core::scheduler::enc_task::KeyframeForcer;
+#[cfg(not(feature = "docs-rs"))]
+use crate::core::scheduler::filter_task::{filter_opt_apply, graph_opts_apply};
 use crate::core::scheduler::input_controller::SchNode;
 use crate::error::Error::{FileSameAsInput, FilterDescUtf8, FilterNameUtf8, FilterZeroOutputs, FrameFilterStreamTypeNoMatched, FrameFilterTypeNoMatched, ParseInteger};
 use crate::error::FilterGraphParseError::{
-    InvalidFileIndexInFg, InvalidFilterSpecifier, OutputUnconnected,
+    FilterInitFailed, FilterNotFound, InputCountMismatch, InputPadAssignedTwice, InputPadNotFound,
+    InvalidFileIndexInFg, InvalidFilterOption, InvalidFilterSpecifier, OutputUnconnected,
 };
 use crate::error::OpenOutputError::InvalidFileIndexInIntput;
 use crate::error::{
@@ -26,7 +31,7 @@ use crate::error::{
 };
 use crate::error::{Error, Result};
 use crate::filter::frame_pipeline::FramePipeline;
-use crate::util::ffmpeg_utils::hashmap_to_avdictionary;
+use crate::util::ffmpeg_utils::{av_err2str, hashmap_to_avdictionary};
 #[cfg(not(feature = "docs-rs"))]
 use ffmpeg_sys_next::AVChannelOrder::AV_CHANNEL_ORDER_UNSPEC;
 #[cfg(not(feature = "docs-rs"))]
@@ -40,9 +45,9 @@ use ffmpeg_sys_next::AVMediaType::{
 };
 use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_NONE;
 use ffmpeg_sys_next::AVSampleFormat::AV_SAMPLE_FMT_NONE;
-use ffmpeg_sys_next::{av_add_q, av_codec_get_id, av_codec_get_tag2, av_dict_free, av_freep, av_get_exact_bits_per_sample, av_guess_codec, av_guess_format, av_guess_frame_rate, av_inv_q, av_malloc, av_rescale_q, av_seek_frame, avcodec_alloc_context3, avcodec_descriptor_get, avcodec_descriptor_get_by_name, avcodec_find_encoder, avcodec_find_encoder_by_name, avcodec_get_name, avcodec_parameters_from_context, avcodec_parameters_to_context, avfilter_graph_alloc, avfilter_graph_free, avfilter_inout_free, avfilter_pad_get_name, avfilter_pad_get_type, avformat_alloc_context, avformat_alloc_output_context2, avformat_close_input, avformat_find_stream_info, avformat_flush, avformat_free_context, avformat_open_input, avio_alloc_context, avio_context_free, avio_open, AVCodec, AVCodecID, AVColorRange, AVColorSpace, AVFilterContext, AVFilterInOut, AVFilterPad, AVFormatContext, AVMediaType, AVOutputFormat, AVPixelFormat, AVRational, AVSampleFormat, AVStream, AVERROR_ENCODER_NOT_FOUND, AVFMT_FLAG_CUSTOM_IO, AVFMT_GLOBALHEADER, AVFMT_NOBINSEARCH, AVFMT_NOFILE, AVFMT_NOGENSEARCH, AVFMT_NOSTREAMS, AVIO_FLAG_WRITE, AVSEEK_FLAG_BACKWARD, AV_CODEC_PROP_BITMAP_SUB, AV_CODEC_PROP_TEXT_SUB, AV_TIME_BASE};
+use ffmpeg_sys_next::{av_add_q, av_codec_get_id, av_filename_number_test, av_find_best_stream, av_codec_get_tag2, av_dict_free, av_freep, av_get_exact_bits_per_sample, av_get_pix_fmt_name, av_guess_codec, av_guess_format, av_guess_frame_rate, av_inv_q, av_malloc, av_opt_find, av_rescale_q, av_seek_frame, avcodec_alloc_context3, avcodec_descriptor_get, avcodec_descriptor_get_by_name, avcodec_find_encoder, avcodec_find_encoder_by_name, avcodec_get_name, avcodec_parameters_from_context, avcodec_parameters_to_context, avfilter_graph_alloc, avfilter_graph_free, avfilter_inout_free, avfilter_pad_get_name, avfilter_pad_get_type, avformat_alloc_context, avformat_alloc_output_context2, avformat_close_input, avformat_find_stream_info, avformat_flush, avformat_free_context, avformat_open_input, avio_alloc_context, avio_context_free, avio_find_protocol_name, avio_open, AVCodec, AVCodecID, AVColorRange, AVColorSpace, AVFilterContext, AVFilterInOut, AVFilterPad, AVFormatContext, AVMediaType, AVOutputFormat, AVPixelFormat, AVRational, AVSampleFormat, AVStream, AVERROR_ENCODER_NOT_FOUND, AVFMT_FLAG_CUSTOM_IO, AVFMT_GLOBALHEADER, AVFMT_NOBINSEARCH, AVFMT_NOFILE, AVFMT_NOGENSEARCH, AVFMT_NOSTREAMS, AVIO_FLAG_WRITE, AVSEEK_FLAG_BACKWARD, AV_CODEC_PROP_BITMAP_SUB, AV_CODEC_PROP_INTRA_ONLY, AV_OPT_SEARCH_FAKE_OBJ, AV_CODEC_PROP_TEXT_SUB, AV_DISPOSITION_ATTACHED_PIC, AV_DISPOSITION_DEFAULT, AV_TIME_BASE, AV_TIME_BASE_Q};
 #[cfg(not(feature = "docs-rs"))]
-use ffmpeg_sys_next::{av_channel_layout_copy, av_packet_side_data_new, avcodec_get_supported_config, avfilter_graph_segment_apply, avfilter_graph_segment_create_filters, avfilter_graph_segment_free, avfilter_graph_segment_parse, AVChannelLayout};
+use ffmpeg_sys_next::{av_bsf_init, av_bsf_list_parse_str, avcodec_parameters_copy, av_channel_layout_compare, av_channel_layout_copy, av_channel_layout_default, av_channel_layout_describe, av_channel_layout_from_string, av_channel_layout_uninit, av_packet_side_data_new, avcodec_get_supported_config, av_dict_iterate, avfilter_get_by_name, avfilter_graph_segment_apply, avfilter_graph_segment_create_filters, avfilter_graph_segment_free, avfilter_graph_segment_parse, avfilter_init_dict, AVChannelLayout, AVFilterGraph, AVFilterGraphSegment, AVFilterParams};
 use log::{debug, error, info, warn};
 use std::collections::HashMap;
 use std::ffi::{c_uint, c_void, CStr, CString};
@@ -144,6 +149,20 @@ impl FfmpegContext {
 
         outputs_bind(&mut muxs, &mut filter_graphs, &mut demuxs)?;
 
+        set_stream_tags(&muxs, &outputs)?;
+
+        set_stream_dispositions(&muxs, &outputs)?;
+
+        set_stream_time_bases(&muxs, &outputs)?;
+
+        set_output_tags(&muxs, &outputs)?;
+
+        strip_metadata(&muxs, &outputs)?;
+
+        set_creation_times(&muxs, &outputs, &demuxs)?;
+
+        init_bitstream_filters(&mut muxs, &outputs)?;
+
         correct_input_start_times(&mut demuxs, copy_ts);
 
         check_output_streams(&muxs)?;
@@ -310,6 +329,429 @@ fn check_output_streams(muxs: &Vec<Muxer>) -> Result<()> {
     Ok(())
 }
 
+/// Writes the tags set with `Output::set_stream_tag` into the metadata of the output
+/// streams. Runs once the streams are mapped, as the tags address output stream indices.
+fn set_stream_tags(muxs: &[Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        for (stream_index, key, value) in &output.stream_tags {
+            if *stream_index >= mux.stream_count() {
+                error!(
+                    "Cannot tag stream {stream_index} of output '{}', it only has {} streams.",
+                    mux.url,
+                    mux.stream_count()
+                );
+                return Err(OpenOutputError::StreamIndexOutOfRange(*stream_index, mux.stream_count()).into());
+            }
+            set_stream_tag(mux, *stream_index, key, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn set_stream_tag(mux: &Muxer, stream_index: usize, key: &str, value: &str) -> Result<()> {
+    if key == "language" && !is_iso639_code(value) {
+        warn!("Language '{value}' of output stream {stream_index} is not an ISO 639-2 code (e.g. 'eng').");
+    }
+
+    let key = CString::new(key)?;
+    let value = CString::new(value)?;
+    unsafe {
+        let st = *(*mux.out_fmt_ctx).streams.add(stream_index);
+        ffmpeg_sys_next::av_dict_set(&mut (*st).metadata, key.as_ptr(), value.as_ptr(), 0);
+    }
+    Ok(())
+}
+
+/// Marks one stream of each media type as the default one: the stream chosen with
+/// `Output::set_default_stream`, or else the first video and the first audio stream (not
+/// the first subtitle stream, players would show it unasked). The default flag of the
+/// other streams of that type is cleared, so players pick the intended track.
+fn set_stream_dispositions(muxs: &[Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        if let Some(&stream_index) = output.default_streams.iter().find(|&&i| i >= mux.stream_count()) {
+            error!(
+                "Cannot make stream {stream_index} of output '{}' the default one, it only has {} streams.",
+                mux.url,
+                mux.stream_count()
+            );
+            return Err(OpenOutputError::StreamIndexOutOfRange(stream_index, mux.stream_count()).into());
+        }
+
+        unsafe {
+            let streams = (0..mux.stream_count())
+                .map(|i| *(*mux.out_fmt_ctx).streams.add(i))
+                .collect::<Vec<_>>();
+            for media_type in [AVMEDIA_TYPE_VIDEO, AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE] {
+                let of_type = (0..streams.len())
+                    .filter(|&i| {
+                        let st = streams[i];
+                        (*(*st).codecpar).codec_type == media_type
+                            && (*st).disposition & AV_DISPOSITION_ATTACHED_PIC as i32 == 0
+                    })
+                    .collect::<Vec<_>>();
+                let default = output
+                    .default_streams
+                    .iter()
+                    .rev()
+                    .find(|i| of_type.contains(i))
+                    .copied()
+                    .or_else(|| of_type.first().copied().filter(|_| media_type != AVMEDIA_TYPE_SUBTITLE));
+                let Some(default) = default else {
+                    continue;
+                };
+                for i in of_type {
+                    if i == default {
+                        (*streams[i]).disposition |= AV_DISPOSITION_DEFAULT as i32;
+                    } else {
+                        (*streams[i]).disposition &= !(AV_DISPOSITION_DEFAULT as i32);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets the time bases chosen with `Output::set_time_base` on the output streams. Encoders
+/// keep a time base already set on their stream, and packets are rescaled to the stream
+/// time base when written, so this only has to run before the headers are written.
+fn set_stream_time_bases(muxs: &[Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        for &(stream_index, time_base) in &output.stream_time_bases {
+            if time_base.num <= 0 || time_base.den <= 0 {
+                error!(
+                    "Invalid time base {}/{} for stream {stream_index} of output '{}'.",
+                    time_base.num, time_base.den, mux.url
+                );
+                return Err(OpenOutputError::InvalidTimeBase(stream_index, time_base.num, time_base.den).into());
+            }
+            if stream_index >= mux.stream_count() {
+                error!(
+                    "Cannot set the time base of stream {stream_index} of output '{}', it only has {} streams.",
+                    mux.url,
+                    mux.stream_count()
+                );
+                return Err(OpenOutputError::StreamIndexOutOfRange(stream_index, mux.stream_count()).into());
+            }
+            unsafe {
+                let st = *(*mux.out_fmt_ctx).streams.add(stream_index);
+                // removes common factors
+                let time_base = av_add_q(time_base, AVRational { num: 0, den: 1 });
+                // the duration of stream-copied streams is already set, in the previous time base
+                let previous = (*st).time_base;
+                if (*st).duration > 0 && previous.num > 0 && previous.den > 0 {
+                    (*st).duration = av_rescale_q((*st).duration, previous, time_base);
+                }
+                (*st).time_base = time_base;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the tags set with `Output::set_tags` into the metadata of the output files. Ogg
+/// muxers write the Vorbis comments of each stream, so the tags are copied to the streams
+/// as well for them.
+fn set_output_tags(muxs: &[Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        let Some(tags) = &output.tags else {
+            continue;
+        };
+        unsafe {
+            let format_name = CStr::from_ptr((*(*mux.out_fmt_ctx).oformat).name).to_string_lossy().into_owned();
+            let per_stream = matches!(format_name.as_str(), "ogg" | "oga" | "ogv" | "opus" | "spx");
+            for (key, value) in tags.to_metadata(&format_name) {
+                let key = CString::new(key)?;
+                let value = CString::new(value)?;
+                ffmpeg_sys_next::av_dict_set(&mut (*mux.out_fmt_ctx).metadata, key.as_ptr(), value.as_ptr(), 0);
+                if per_stream {
+                    for i in 0..(*mux.out_fmt_ctx).nb_streams as usize {
+                        let st = *(*mux.out_fmt_ctx).streams.add(i);
+                        ffmpeg_sys_next::av_dict_set(
+                            &mut (*st).metadata,
+                            key.as_ptr(),
+                            value.as_ptr(),
+                            ffmpeg_sys_next::AV_DICT_DONT_OVERWRITE,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the creation time set with `Output::set_creation_time`, or copied from the inputs
+/// with `Output::copy_creation_time`, as the `creation_time` tag of the output files. The
+/// MP4 family of muxers also reads it from the streams, the other muxers from the file only.
+/// This runs after `strip_metadata`, so an explicitly requested creation time is kept.
+fn set_creation_times(muxs: &[Muxer], outputs: &[Output], demuxs: &[Demuxer]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        let time_us = match &output.creation_time {
+            Some(time) => Some(
+                parse_creation_time(time).ok_or_else(|| OpenOutputError::InvalidCreationTime(time.clone()))?,
+            ),
+            None if output.copy_creation_time => {
+                let time_us = demuxs.iter().find_map(input_creation_time);
+                if time_us.is_none() {
+                    warn!("No input has a creation time to copy to output '{}'.", mux.url);
+                }
+                time_us
+            }
+            None => None,
+        };
+        let Some(time_us) = time_us else {
+            continue;
+        };
+
+        let key = CString::new("creation_time")?;
+        let value = CString::new(format_creation_time(time_us))?;
+        unsafe {
+            ffmpeg_sys_next::av_dict_set(&mut (*mux.out_fmt_ctx).metadata, key.as_ptr(), value.as_ptr(), 0);
+            let format_name = CStr::from_ptr((*(*mux.out_fmt_ctx).oformat).name).to_string_lossy().into_owned();
+            if matches!(format_name.as_str(), "mp4" | "mov" | "ipod" | "3gp" | "3g2" | "psp" | "ismv" | "f4v") {
+                for i in 0..(*mux.out_fmt_ctx).nb_streams as usize {
+                    let st = *(*mux.out_fmt_ctx).streams.add(i);
+                    ffmpeg_sys_next::av_dict_set(&mut (*st).metadata, key.as_ptr(), value.as_ptr(), 0);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the `creation_time` of an input, from its container or else from its first stream
+/// having one, in microseconds since the epoch.
+fn input_creation_time(demux: &Demuxer) -> Option<i64> {
+    let key = CString::new("creation_time").ok()?;
+    unsafe {
+        let is = demux.in_fmt_ctx;
+        let metadata = std::iter::once((*is).metadata)
+            .chain((0..(*is).nb_streams as usize).map(|i| (**(*is).streams.add(i)).metadata));
+        metadata
+            .map(|metadata| ffmpeg_sys_next::av_dict_get(metadata, key.as_ptr(), null(), 0))
+            .filter(|entry| !entry.is_null())
+            .find_map(|entry| parse_creation_time(&CStr::from_ptr((**entry).value).to_string_lossy()))
+    }
+}
+
+/// Parses an RFC 3339 date, e.g. `2024-05-01T14:00:00.250+02:00`, or `now`, into microseconds
+/// since the epoch. A date without a time zone, as some muxers write them, is taken as UTC.
+fn parse_creation_time(time: &str) -> Option<i64> {
+    if time.eq_ignore_ascii_case("now") {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?;
+        return i64::try_from(now.as_micros()).ok();
+    }
+    let number = |s: Option<&str>| -> Option<i64> {
+        let s = s?;
+        if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    };
+    let separators = time.as_bytes();
+    if separators.len() < 19
+        || separators[4] != b'-'
+        || separators[7] != b'-'
+        || !matches!(separators[10], b'T' | b't' | b' ')
+        || separators[13] != b':'
+        || separators[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (number(time.get(0..4))?, number(time.get(5..7))?, number(time.get(8..10))?);
+    let (hour, minute, second) = (number(time.get(11..13))?, number(time.get(14..16))?, number(time.get(17..19))?);
+    let valid_date = (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day);
+    // 60 for leap seconds
+    if !valid_date || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &time[19..];
+    let mut micros = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        // digits past the microseconds are dropped
+        let kept = digits.min(6);
+        micros = number(fraction.get(..kept))? * 10i64.pow(6 - kept as u32);
+        rest = &fraction[digits..];
+    }
+    let offset_minutes = match rest {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+                return None;
+            }
+            let (hours, minutes) = (number(rest.get(1..3))?, number(rest.get(4..6))?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            sign * (hours * 60 + minutes)
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset_minutes * 60;
+    Some(seconds * 1_000_000 + micros)
+}
+
+/// Formats microseconds since the epoch as FFmpeg writes creation times,
+/// `2024-05-01T12:00:00.250000Z`.
+fn format_creation_time(time_us: i64) -> String {
+    let (seconds, micros) = (time_us.div_euclid(1_000_000), time_us.rem_euclid(1_000_000));
+    let (days, second_of_day) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{micros:06}Z",
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // years starting in March, so the leap day ends them
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date of a number of days since 1970-01-01, as `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Removes the metadata keys set with `Output::strip_metadata_keys`, or all the metadata
+/// with `Output::strip_all_metadata`, from the output files and their streams. The muxers
+/// add an `encoder` tag when writing the header, which only the `bitexact` flag prevents.
+fn strip_metadata(muxs: &[Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter().zip(outputs) {
+        if !output.strip_all_metadata && output.strip_metadata_keys.is_empty() {
+            continue;
+        }
+        let keys = output
+            .strip_metadata_keys
+            .iter()
+            .map(|key| CString::new(key.as_str()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let strip = |metadata: *mut *mut ffmpeg_sys_next::AVDictionary| unsafe {
+            if output.strip_all_metadata {
+                av_dict_free(metadata);
+                return;
+            }
+            for key in &keys {
+                // without AV_DICT_MATCH_CASE, the keys are compared without case
+                ffmpeg_sys_next::av_dict_set(metadata, key.as_ptr(), null(), 0);
+            }
+        };
+
+        unsafe {
+            strip(&mut (*mux.out_fmt_ctx).metadata);
+            for i in 0..(*mux.out_fmt_ctx).nb_streams as usize {
+                let st = *(*mux.out_fmt_ctx).streams.add(i);
+                strip(&mut (*st).metadata);
+            }
+            if output.strip_all_metadata || output.strip_metadata_keys.iter().any(|key| key.eq_ignore_ascii_case("encoder")) {
+                (*mux.out_fmt_ctx).flags |= ffmpeg_sys_next::AVFMT_FLAG_BITEXACT as i32;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the bitstream filters set with `Output::set_bitstream_filter`, chaining those of
+/// the same stream. They are initialized from the parameters of the copied streams, and
+/// replace them with the parameters of their output.
+fn init_bitstream_filters(muxs: &mut [Muxer], outputs: &[Output]) -> Result<()> {
+    for (mux, output) in muxs.iter_mut().zip(outputs) {
+        let mut chains: Vec<(usize, Vec<&str>)> = Vec::new();
+        for (stream_index, filter) in &output.bitstream_filters {
+            match chains.iter_mut().find(|(index, _)| index == stream_index) {
+                Some((_, filters)) => filters.push(filter),
+                None => chains.push((*stream_index, vec![filter])),
+            }
+        }
+
+        for (stream_index, filters) in chains {
+            let chain = filters.join(",");
+            if stream_index >= mux.stream_count() {
+                error!(
+                    "Cannot filter stream {stream_index} of output '{}', it only has {} streams.",
+                    mux.url,
+                    mux.stream_count()
+                );
+                return Err(OpenOutputError::StreamIndexOutOfRange(stream_index, mux.stream_count()).into());
+            }
+            if mux.get_streams().iter().any(|stream| stream.stream_index == stream_index) {
+                error!("Bitstream filter '{chain}' is set on output stream {stream_index}, which is encoded, not copied.");
+                return Err(OpenOutputError::InvalidBitstreamFilter(chain, stream_index).into());
+            }
+
+            let chain_cstr = CString::new(chain.as_str())?;
+            unsafe {
+                let mut bsf = BsfContextBox { bsf_ctx: null_mut() };
+                let ret = av_bsf_list_parse_str(chain_cstr.as_ptr(), &mut bsf.bsf_ctx);
+                if ret < 0 {
+                    error!("Error parsing bitstream filter '{chain}': {}", av_err2str(ret));
+                    return Err(OpenOutputError::InvalidBitstreamFilter(chain, stream_index).into());
+                }
+
+                let st = *(*mux.out_fmt_ctx).streams.add(stream_index);
+                let ret = avcodec_parameters_copy((*bsf.bsf_ctx).par_in, (*st).codecpar);
+                if ret < 0 {
+                    return Err(OpenOutputError::from(ret).into());
+                }
+                (*bsf.bsf_ctx).time_base_in = (*st).time_base;
+
+                let ret = av_bsf_init(bsf.bsf_ctx);
+                if ret < 0 {
+                    error!("Error initializing bitstream filter '{chain}' for output stream {stream_index}: {}", av_err2str(ret));
+                    return Err(OpenOutputError::InvalidBitstreamFilter(chain, stream_index).into());
+                }
+
+                let ret = avcodec_parameters_copy((*st).codecpar, (*bsf.bsf_ctx).par_out);
+                if ret < 0 {
+                    return Err(OpenOutputError::from(ret).into());
+                }
+                (*st).time_base = (*bsf.bsf_ctx).time_base_out;
+
+                mux.set_bitstream_filter(stream_index, bsf);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Loosely checks for an ISO 639-2 language code: three ASCII letters.
+fn is_iso639_code(language: &str) -> bool {
+    language.len() == 3 && language.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
 fn outputs_bind(
     muxs: &mut Vec<Muxer>,
     filter_graphs: &mut Vec<FilterGraph>,
@@ -323,7 +765,11 @@ fn outputs_bind(
             map_auto_streams(i, mux, demuxs, filter_graphs, auto_disable)?;
         } else {
             for stream_map in mux.stream_maps.clone() {
+                let stream_count = mux.stream_count();
                 map_manual(i, mux, &stream_map, filter_graphs, demuxs)?;
+                if let (Some(language), true) = (&stream_map.language, mux.stream_count() > stream_count) {
+                    set_stream_tag(mux, stream_count, "language", language)?;
+                }
             }
         }
 
@@ -468,6 +914,9 @@ fn map_manual(
                         filter_graphs,
                     )?;
                 } else {
+                    if media_type == AVMEDIA_TYPE_SUBTITLE {
+                        unsafe { check_subtitle_conversion((*input_stream.codec_parameters).codec_id, enc)? };
+                    }
                     let (frame_sender, output_stream_index) = mux.add_enc_stream(
                         media_type,
                         enc,
@@ -516,7 +965,9 @@ fn configure_output_filter_opts(
         output_filter.opts.name = format!("#{index}:{output_stream_index}");
         output_filter.opts.enc = enc;
         output_filter.opts.trim_start_us = mux.start_time_us;
-        output_filter.opts.trim_duration_us = mux.recording_time_us;
+        // the padding is cut at the target duration, unless the recording time stops it first
+        output_filter.opts.trim_duration_us = mux.recording_time_us.or(mux.pad_to_duration_us);
+        output_filter.opts.pad = mux.pad_to_duration_us.is_some();
         output_filter.opts.ts_offset = mux.start_time_us;
 
         output_filter.opts.flags = OFILTER_FLAG_DISABLE_CONVERT
@@ -556,6 +1007,21 @@ fn configure_output_filter_opts(
                 format_list.push(*current);
                 current = current.add(1);
             }
+            if let Some(pix_fmt) = mux.video_pix_fmt {
+                if !format_list.is_empty() && !format_list.contains(&pix_fmt) {
+                    let supported = format_list.iter().map(|format| pix_fmt_name(*format)).collect::<Vec<_>>();
+                    let encoder = CStr::from_ptr((*enc).name).to_string_lossy().into_owned();
+                    return Err(OpenOutputError::PixelFormatUnsupported(
+                        pix_fmt_name(pix_fmt),
+                        encoder,
+                        supported.join(", "),
+                    )
+                    .into());
+                }
+                output_filter.opts.format = pix_fmt;
+            } else if (*enc).id == AVCodecID::AV_CODEC_ID_RAWVIDEO {
+                warn!("Raw video output #{index}:{output_stream_index} has no explicit pixel format, the source's is kept; set one with `set_video_pix_fmt` to get a known byte layout.");
+            }
             output_filter.opts.formats = Some(format_list);
 
             // framerates
@@ -627,6 +1093,9 @@ fn configure_output_filter_opts(
 
             let stream = &mux.get_streams()[output_stream_index];
             output_filter.opts.vsync_method = stream.vsync_method;
+            output_filter.opts.even_dimensions_policy = mux.even_dimensions_policy;
+            output_filter.opts.dropped_frames = mux.get_dropped_frames().get(output_stream_index).cloned();
+            output_filter.opts.duplicated_frames = mux.get_duplicated_frames().get(output_stream_index).cloned();
         } else {
             if let Some(sample_fmt) = &mux.audio_sample_fmt {
                 output_filter.opts.audio_format = *sample_fmt;
@@ -679,9 +1148,6 @@ fn configure_output_filter_opts(
             output_filter.opts.sample_rates = Some(rate_list);
 
 
-            if let Some(channels) = &mux.audio_channels {
-                output_filter.opts.ch_layout.nb_channels = *channels;
-            }
             // channel_layouts
             let mut layouts: *const AVChannelLayout = null();
             ret = avcodec_get_supported_config(
@@ -701,6 +1167,9 @@ fn configure_output_filter_opts(
                 layout_list.push(*current);
                 current = current.add(1);
             }
+            if let Some(layout) = requested_channel_layout(mux, enc, &layout_list)? {
+                output_filter.opts.ch_layout = layout;
+            }
             output_filter.opts.ch_layouts = Some(layout_list);
         }
     };
@@ -728,18 +1197,51 @@ fn output_find_input_idx_by_linklabel(
         return Err(InvalidFileIndexInIntput(file_idx as usize, desc.to_string()).into());
     }
 
-    let (media_type, allow_unused) = stream_specifier_parse(remainder)?;
-
     let demux = &demuxs[file_idx as usize];
 
-    let mut stream_idx = -1i32;
+    // Absolute stream index, e.g. "0:2" or "0:2?"
+    let index_specifier = remainder.strip_prefix(':').unwrap_or(remainder);
+    let (index_str, allow_unused) = match index_specifier.strip_suffix('?') {
+        Some(index_str) => (index_str, true),
+        None => (index_specifier, false),
+    };
+    if !index_str.is_empty() && index_str.chars().all(|c| c.is_ascii_digit()) {
+        let stream_idx: usize = index_str.parse().map_err(|_| ParseInteger)?;
+        return match demux.get_streams().get(stream_idx) {
+            Some(dec_stream) if !dec_stream.ignored => Ok(Some((file_idx as usize, stream_idx, dec_stream.codec_type))),
+            _ if allow_unused => Ok(None),
+            Some(_) => {
+                warn!("Stream #{file_idx}:{stream_idx} is ignored by its input and cannot be mapped.");
+                Err(OpenOutputError::MatchesNoStreams(linklabel.to_string()).into())
+            }
+            None => {
+                warn!("Stream index '{stream_idx}' in output {desc} matches no streams.");
+                Err(OpenOutputError::MatchesNoStreams(linklabel.to_string()).into())
+            }
+        };
+    }
 
-    for (idx, dec_stream) in demux.get_streams().iter().enumerate() {
-        if (*dec_stream).codec_type == media_type {
-            stream_idx = idx as i32;
-            break;
+    // "a", "a?", "a:1" (the second audio stream) or "a:1?"
+    let (type_specifier, type_index) = match index_specifier.split_once(':') {
+        Some((media_type, type_index)) => {
+            let type_index = type_index.strip_suffix('?').unwrap_or(type_index);
+            let type_index = type_index
+                .parse::<usize>()
+                .map_err(|_| InvalidFilterSpecifier(remainder.to_string()))?;
+            let type_specifier = if allow_unused { format!("{media_type}?") } else { media_type.to_string() };
+            (type_specifier, type_index)
         }
-    }
+        None => (index_specifier.to_string(), 0),
+    };
+    let (media_type, allow_unused) = stream_specifier_parse(&type_specifier)?;
+
+    let stream_idx = demux
+        .get_streams()
+        .iter()
+        .enumerate()
+        .filter(|(_, dec_stream)| !dec_stream.ignored && dec_stream.codec_type == media_type)
+        .nth(type_index)
+        .map_or(-1i32, |(idx, _)| idx as i32);
 
     if stream_idx < 0 {
         if allow_unused {
@@ -828,7 +1330,7 @@ unsafe fn map_auto_subtitle(
             .iter()
             .enumerate()
             .find_map(|(index, input_stream)| {
-                if input_stream.codec_type == AVMEDIA_TYPE_SUBTITLE {
+                if !input_stream.ignored && input_stream.codec_type == AVMEDIA_TYPE_SUBTITLE {
                     Some(index)
                 } else {
                     None
@@ -851,6 +1353,11 @@ unsafe fn map_auto_subtitle(
             output_props = (*output_descriptor).props & (AV_CODEC_PROP_TEXT_SUB | AV_CODEC_PROP_BITMAP_SUB);
         }
 
+        // a subtitle-only format, e.g. .srt or .vtt, is useless without its subtitle stream
+        if (*oformat).video_codec == AVCodecID::AV_CODEC_ID_NONE && (*oformat).audio_codec == AVCodecID::AV_CODEC_ID_NONE {
+            check_subtitle_conversion((*demux.get_stream(stream_index).codec_parameters).codec_id, output_codec)?;
+        }
+
         if input_props & output_props != 0 ||
             // Map dvb teletext which has neither property to any output subtitle encoder
             !input_descriptor.is_null() && !output_descriptor.is_null() &&
@@ -915,7 +1422,7 @@ unsafe fn map_auto_data(
             .iter()
             .enumerate()
             .find_map(|(index, input_stream)| {
-                if input_stream.codec_type == AVMEDIA_TYPE_DATA && (*input_stream.codec_parameters).codec_id == codec_id {
+                if !input_stream.ignored && input_stream.codec_type == AVMEDIA_TYPE_DATA && (*input_stream.codec_parameters).codec_id == codec_id {
                     Some(index)
                 } else {
                     None
@@ -996,7 +1503,7 @@ unsafe fn map_auto_stream(
             .iter()
             .enumerate()
             .find_map(|(index, input_stream)| {
-                if input_stream.codec_type == media_type {
+                if !input_stream.ignored && input_stream.codec_type == media_type {
                     Some(index)
                 } else {
                     None
@@ -1293,6 +1800,29 @@ fn ofilter_bind_ost(
     Ok(())
 }
 
+/// Checks that subtitles of `input_codec_id` can be decoded and encoded with `enc`: subtitles are
+/// only converted from text to text (e.g. ASS to WebVTT) or from bitmap to bitmap.
+unsafe fn check_subtitle_conversion(input_codec_id: AVCodecID, enc: *const AVCodec) -> Result<()> {
+    let input_descriptor = avcodec_descriptor_get(input_codec_id);
+    let output_descriptor = avcodec_descriptor_get((*enc).id);
+    if input_descriptor.is_null() || output_descriptor.is_null() {
+        return Ok(());
+    }
+
+    let input_props = (*input_descriptor).props;
+    let output_props = (*output_descriptor).props;
+    let input_name = CStr::from_ptr((*input_descriptor).name).to_string_lossy().into_owned();
+    let output_name = CStr::from_ptr((*enc).name).to_string_lossy().into_owned();
+    if input_props & AV_CODEC_PROP_BITMAP_SUB != 0 && output_props & AV_CODEC_PROP_TEXT_SUB != 0 {
+        error!("Subtitle stream '{input_name}' is made of pictures; converting it to '{output_name}' requires OCR, which FFmpeg does not provide");
+        return Err(OpenOutputError::BitmapSubtitleToText(input_name, output_name).into());
+    }
+    if input_props & AV_CODEC_PROP_TEXT_SUB != 0 && output_props & AV_CODEC_PROP_BITMAP_SUB != 0 {
+        return Err(OpenOutputError::TextSubtitleToBitmap(input_name, output_name).into());
+    }
+    Ok(())
+}
+
 fn choose_encoder(
     mux: &Muxer,
     media_type: AVMediaType,
@@ -1367,6 +1897,17 @@ fn choose_encoder(
     Ok(None)
 }
 
+/// Returns `true` if `url` names a local file that exists. Other protocols are not checked.
+unsafe fn local_file_exists(url: &CStr) -> bool {
+    let protocol = avio_find_protocol_name(url.as_ptr());
+    if protocol.is_null() || CStr::from_ptr(protocol).to_bytes() != b"file" {
+        return false;
+    }
+    let url = url.to_string_lossy();
+    let path = url.strip_prefix("file:").unwrap_or(&url);
+    std::path::Path::new(path).exists()
+}
+
 fn check_duplicate_inputs_outputs(inputs: &[Input], outputs: &[Output]) -> Result<()> {
     for output in outputs {
         if let Some(output_url) = &output.url {
@@ -1413,6 +1954,65 @@ unsafe fn open_output_file(index: usize, output: &mut Output) -> Result<Muxer> {
 #[cfg(not(feature = "docs-rs"))]
 unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) -> Result<Muxer> {
     let mut out_fmt_ctx = null_mut();
+    for (name, max_frames) in [
+        ("max_video_frames", output.max_video_frames),
+        ("max_audio_frames", output.max_audio_frames),
+        ("max_subtitle_frames", output.max_subtitle_frames),
+    ] {
+        if let Some(max_frames) = max_frames.filter(|&max_frames| max_frames <= 0) {
+            error!("{name} must be greater than 0.");
+            return Err(OpenOutputError::OptionValueTooSmall(name.to_string(), max_frames, 1).into());
+        }
+    }
+    for (name, value) in [
+        ("gop_size", output.gop_size),
+        ("max_b_frames", output.max_b_frames),
+        ("keyint_min", output.keyint_min),
+    ] {
+        if let Some(value) = value.filter(|&value| value < 0) {
+            error!("{name} must not be negative.");
+            return Err(OpenOutputError::OptionValueTooSmall(name.to_string(), value.into(), 0).into());
+        }
+    }
+    if output.max_muxing_queue_size == Some(0) {
+        error!("max_muxing_queue_size must be greater than 0.");
+        return Err(OpenOutputError::OptionValueTooSmall("max_muxing_queue_size".to_string(), 0, 1).into());
+    }
+    if output.frame_interval == Some(0) {
+        error!("frame_interval must be greater than 0.");
+        return Err(OpenOutputError::InvalidArgument.into());
+    }
+    if let Some(force_keyframes) = &output.force_keyframes {
+        if let Err(ret) = KeyframeForcer::new(force_keyframes) {
+            error!("Invalid force_keyframes {force_keyframes:?}: {}", av_err2str(ret));
+            return Err(OpenOutputError::InvalidArgument.into());
+        }
+    }
+    if let Some(segment_duration_us) = output.segment_duration_us {
+        if segment_duration_us <= 0 || output.url.is_none() {
+            error!("segment duration must be greater than 0 and a file name pattern is required.");
+            return Err(OpenOutputError::InvalidArgument.into());
+        }
+        let format_opts = output.format_opts.get_or_insert_with(HashMap::new);
+        // the container of the segments themselves
+        if let Some(segment_format) = output.format.replace("segment".to_string()) {
+            format_opts.entry("segment_format".to_string()).or_insert(segment_format);
+        }
+        format_opts.entry("segment_time".to_string()).or_insert(format!(
+            "{}.{:06}",
+            segment_duration_us / 1_000_000,
+            segment_duration_us % 1_000_000
+        ));
+        format_opts.entry("reset_timestamps".to_string()).or_insert("1".to_string());
+    }
+    if output.format.is_none() && output.segment_duration_us.is_none() {
+        if let Some(url) = output.url.as_deref() {
+            if is_image_sequence(url)? {
+                debug!("{url} is an image sequence pattern, writing it with the image2 muxer.");
+                output.format = Some("image2".to_string());
+            }
+        }
+    }
     let format = get_format(&output.format)?;
     match &output.url {
         None => {
@@ -1462,7 +2062,7 @@ unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) ->
                 return Err(AllocOutputContextError::from(ret).into());
             }
 
-            if !have_seek_callback && output_requires_seek(out_fmt_ctx) {
+            if !have_seek_callback && output_requires_seek(out_fmt_ctx) && !is_fragmented_mp4(&output.format_opts) {
                 av_freep(&mut (*avio_ctx).buffer as *mut _ as *mut c_void);
                 avio_context_free(&mut avio_ctx);
                 avformat_free_context(out_fmt_ctx);
@@ -1486,8 +2086,20 @@ unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) ->
                 return Err(AllocOutputContextError::from(ret).into());
             }
 
+            if output.faststart {
+                if let Err(reason) = check_faststart(out_fmt_ctx, Some(url)) {
+                    avformat_free_context(out_fmt_ctx);
+                    return Err(OpenOutputError::FaststartUnsupported(url.clone(), reason.to_string()).into());
+                }
+            }
+
             let output_format = (*out_fmt_ctx).oformat;
             if (*output_format).flags & AVFMT_NOFILE == 0 {
+                if !output.overwrite && local_file_exists(&url_cstr) {
+                    error!("File '{url}' already exists and overwriting is disabled.");
+                    avformat_free_context(out_fmt_ctx);
+                    return Err(OpenOutputError::OutputExists(url.clone()).into());
+                }
                 let ret = avio_open(&mut (*out_fmt_ctx).pb, url_cstr.as_ptr(), AVIO_FLAG_WRITE);
                 if ret < 0 {
                     warn!("Error opening output {url}");
@@ -1497,6 +2109,20 @@ unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) ->
         }
     }
 
+    if output.faststart {
+        if let Err(reason) = check_faststart(out_fmt_ctx, output.url.as_deref()) {
+            let url = output.url.clone().unwrap_or_else(|| format!("write_callback[{index}]"));
+            out_fmt_ctx_free(out_fmt_ctx, output.url.is_none());
+            return Err(OpenOutputError::FaststartUnsupported(url, reason.to_string()).into());
+        }
+        output
+            .format_opts
+            .get_or_insert_with(HashMap::new)
+            .entry("movflags".to_string())
+            .and_modify(|movflags| movflags.push_str("+faststart"))
+            .or_insert_with(|| "+faststart".to_string());
+    }
+
     let recording_time_us = match output.stop_time_us {
         None => output.recording_time_us,
         Some(stop_time_us) => {
@@ -1518,6 +2144,7 @@ unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) ->
     let video_codec_opts = convert_options(output.video_codec_opts.clone())?;
     let audio_codec_opts = convert_options(output.audio_codec_opts.clone())?;
     let subtitle_codec_opts = convert_options(output.subtitle_codec_opts.clone())?;
+    let encoder_opts = convert_options(output.encoder_opts.clone())?;
     let format_opts = convert_options(output.format_opts.clone())?;
 
     let mux = Muxer::new(
@@ -1531,23 +2158,42 @@ unsafe fn open_output_file(index: usize, output: &mut Output, copy_ts: bool) ->
         output.subtitle_codec.clone(),
         output.start_time_us,
         recording_time_us,
+        output.output_ts_offset_us,
+        output.audio_sync_offset_us,
         output.framerate,
         output.vsync_method,
+        output.even_dimensions_policy,
+        output.pad_to_duration_us,
         output.bits_per_raw_sample,
         output.audio_sample_rate,
         output.audio_channels,
+        output.audio_channel_layout.clone(),
         output.audio_sample_fmt,
+        output.video_pix_fmt,
         output.video_qscale,
         output.audio_qscale,
+        output.gop_size,
+        output.max_b_frames,
+        output.keyint_min,
         output.max_video_frames,
         output.max_audio_frames,
         output.max_subtitle_frames,
+        output.max_muxing_queue_size.unwrap_or(DEFAULT_MAX_MUXING_QUEUE_SIZE),
+        output.realtime_drop,
+        output.interleaved,
+        output.frame_interval,
+        output.force_keyframes.clone(),
+        output.video_quality_ramp.clone(),
         video_codec_opts,
         audio_codec_opts,
         subtitle_codec_opts,
+        encoder_opts,
         format_opts,
-        copy_ts
+        copy_ts,
+        output.packet_sink.take(),
     );
+    // the receivers belong to the caller, the channel must disconnect when they are dropped
+    output.packet_channel = None;
 
     Ok(mux)
 }
@@ -1570,6 +2216,142 @@ fn get_format(format_option: &Option<String>) -> Result<*const AVOutputFormat> {
     }
 }
 
+fn pix_fmt_name(pix_fmt: AVPixelFormat) -> String {
+    let name = unsafe { av_get_pix_fmt_name(pix_fmt) };
+    if name.is_null() {
+        return format!("{pix_fmt:?}");
+    }
+    unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+}
+
+/// Resolves the channel layout requested with `set_audio_channel_layout` or
+/// `set_audio_channels`, checked against the layouts `enc` supports (`supported`, empty when
+/// it accepts any). A channel count alone picks the first supported layout with that many
+/// channels, or the default layout for it, like FFmpeg's `-ac`.
+#[cfg(not(feature = "docs-rs"))]
+unsafe fn requested_channel_layout(
+    mux: &Muxer,
+    enc: *const AVCodec,
+    supported: &[AVChannelLayout],
+) -> Result<Option<AVChannelLayout>> {
+    let mut layout: AVChannelLayout = std::mem::zeroed();
+    let unsupported = |requested: String| -> Error {
+        let supported = supported.iter().map(ch_layout_name).collect::<Vec<_>>();
+        let encoder = CStr::from_ptr((*enc).name).to_string_lossy().into_owned();
+        OpenOutputError::ChannelLayoutUnsupported(requested, encoder, supported.join(", ")).into()
+    };
+
+    match (&mux.audio_channel_layout, mux.audio_channels) {
+        (None, None) => return Ok(None),
+        (Some(name), channels) => {
+            let name_cstr = CString::new(name.as_str())?;
+            if av_channel_layout_from_string(&mut layout, name_cstr.as_ptr()) < 0 {
+                error!("Invalid audio channel layout '{name}'");
+                return Err(OpenOutputError::InvalidArgument.into());
+            }
+            let requested = ch_layout_name(&layout);
+            if let Some(channels) = channels.filter(|channels| *channels != layout.nb_channels) {
+                av_channel_layout_uninit(&mut layout);
+                error!("Audio channel layout '{requested}' does not have {channels} channels");
+                return Err(OpenOutputError::InvalidArgument.into());
+            }
+            if !supported.is_empty() && !supported.iter().any(|l| av_channel_layout_compare(l, &layout) == 0) {
+                av_channel_layout_uninit(&mut layout);
+                return Err(unsupported(requested));
+            }
+        }
+        (None, Some(channels)) => {
+            if channels <= 0 {
+                error!("Invalid number of audio channels {channels}");
+                return Err(OpenOutputError::InvalidArgument.into());
+            }
+            match supported.iter().find(|l| l.nb_channels == channels) {
+                Some(l) => {
+                    let ret = av_channel_layout_copy(&mut layout, l);
+                    if ret < 0 {
+                        return Err(OpenOutputError::from(ret).into());
+                    }
+                }
+                None if supported.is_empty() => av_channel_layout_default(&mut layout, channels),
+                None => return Err(unsupported(format!("{channels} channels"))),
+            }
+        }
+    }
+    Ok(Some(layout))
+}
+
+#[cfg(not(feature = "docs-rs"))]
+fn ch_layout_name(layout: &AVChannelLayout) -> String {
+    let mut name = [0 as std::ffi::c_char; 128];
+    let ret = unsafe { av_channel_layout_describe(layout, name.as_mut_ptr(), name.len()) };
+    if ret < 0 {
+        return format!("{} channels", layout.nb_channels);
+    }
+    unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// Whether `url` is a numbered file name pattern (e.g. `frame_%04d.png`) of an image format.
+///
+/// Some image extensions (e.g. `.webp`) are guessed as muxers writing a single (animated)
+/// file, which would ignore the pattern, so those are recognized by their intra-only video
+/// codec and lack of audio.
+unsafe fn is_image_sequence(url: &str) -> Result<bool> {
+    let url_cstr = CString::new(url)?;
+    if av_filename_number_test(url_cstr.as_ptr()) == 0 {
+        return Ok(false);
+    }
+    let format = av_guess_format(null(), url_cstr.as_ptr(), null());
+    if format.is_null() {
+        return Ok(false);
+    }
+    if CStr::from_ptr((*format).name).to_bytes() == b"image2" {
+        return Ok(true);
+    }
+    let descriptor = avcodec_descriptor_get((*format).video_codec);
+    Ok((*format).audio_codec == AVCodecID::AV_CODEC_ID_NONE
+        && !descriptor.is_null()
+        && (*descriptor).props & AV_CODEC_PROP_INTRA_ONLY != 0)
+}
+
+/// Checks that the muxer of `out_fmt_ctx` can move its index to the front of the output,
+/// which requires an MP4/MOV muxer and a seekable file to read back.
+///
+/// Whether the output can seek is only checked once it is open; checking before opening it
+/// keeps a rejected output from creating or truncating the file.
+unsafe fn check_faststart(out_fmt_ctx: *mut AVFormatContext, url: Option<&str>) -> std::result::Result<(), &'static str> {
+    let movflags = CString::new("movflags").unwrap();
+    let priv_class = &(*(*out_fmt_ctx).oformat).priv_class as *const _ as *mut c_void;
+    if (*(*out_fmt_ctx).oformat).priv_class.is_null()
+        || av_opt_find(priv_class, movflags.as_ptr(), null(), 0, AV_OPT_SEARCH_FAKE_OBJ).is_null()
+    {
+        return Err("only MP4/MOV outputs support it");
+    }
+    match url {
+        None => Err("the output is written through a callback, not a file"),
+        Some("-") => Err("the output is a pipe"),
+        Some(_) if !(*out_fmt_ctx).pb.is_null() && (*(*out_fmt_ctx).pb).seekable == 0 => {
+            Err("the output is not seekable")
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Returns whether `format_opts` make the MP4 muxer write fragments, which it can do
+/// without seeking back in the output.
+fn is_fragmented_mp4(format_opts: &Option<HashMap<String, String>>) -> bool {
+    let Some(format_opts) = format_opts else {
+        return false;
+    };
+    if format_opts.contains_key("frag_duration") || format_opts.contains_key("frag_size") {
+        return true;
+    }
+    format_opts.get("movflags").is_some_and(|movflags| {
+        ["frag_keyframe", "frag_every_frame", "frag_custom", "empty_moov", "dash", "cmaf"]
+            .iter()
+            .any(|flag| movflags.contains(flag))
+    })
+}
+
 unsafe fn output_requires_seek(fmt_ctx: *mut AVFormatContext) -> bool {
     if fmt_ctx.is_null() {
         return false;
@@ -1825,7 +2607,7 @@ fn fg_complex_bind_input(
             let mut stream_idx = 0;
             for (d_idx, demux) in demuxs.iter().enumerate() {
                 for (st_idx, intput_stream) in demux.get_streams().iter().enumerate() {
-                    if intput_stream.is_used() {
+                    if intput_stream.is_used() || intput_stream.ignored {
                         continue;
                     }
                     if intput_stream.codec_type == input_filter.media_type {
@@ -1879,8 +2661,11 @@ fn ifilter_bind_ist(
             let framerate = av_guess_frame_rate(demux.in_fmt_ctx, ist, null_mut());
             input_filter.opts.framerate = framerate;
         } else if (*par).codec_type == AVMEDIA_TYPE_SUBTITLE {
-            input_filter.opts.sub2video_width = (*par).width;
-            input_filter.opts.sub2video_height = (*par).height;
+            input_filter.type_src = AVMEDIA_TYPE_SUBTITLE;
+            (input_filter.opts.sub2video_width, input_filter.opts.sub2video_height) = match demux.canvas_size {
+                Some((width, height)) => (width as i32, height as i32),
+                None => ((*par).width, (*par).height),
+            };
 
             if input_filter.opts.sub2video_width <= 0 || input_filter.opts.sub2video_height <= 0 {
                 let nb_streams = (*demux.in_fmt_ctx).nb_streams;
@@ -1935,6 +2720,12 @@ fn ifilter_bind_ist(
 
         //TODO Set this flag according to the input stream parameters
         input_filter.opts.flags |= IFILTER_FLAG_AUTOROTATE;
+        if demux.normalize_sar {
+            input_filter.opts.flags |= IFILTER_FLAG_NORMALIZE_SAR;
+        }
+        if (*dec_ctx).codec_type == AVMEDIA_TYPE_VIDEO {
+            input_filter.opts.hw_scale = demux.hw_scale;
+        }
 
         let tsoffset = if demux.copy_ts {
             let mut tsoffset = if demux.start_time_us.is_some() {
@@ -1949,7 +2740,7 @@ fn ifilter_bind_ist(
         } else {
             0
         };
-        if demux.start_time_us.is_some() {
+        if demux.start_time_us.is_some() && demux.accurate_seek {
             input_filter.opts.trim_start_us = Some(tsoffset);
         }
         input_filter.opts.trim_end_us = demux.recording_time_us;
@@ -1994,23 +2785,34 @@ fn fg_find_input_idx_by_linklabel(
         return Err(InvalidFileIndexInFg(file_idx as usize, desc.to_string()).into());
     }
 
-    let (media_type, _allow_unused) = stream_specifier_parse(remainder)?;
+    // "v", "s" or "s:1" (the second subtitle stream)
+    let (type_specifier, type_index) = match remainder.trim_start_matches(':').split_once(':') {
+        Some((type_specifier, type_index)) => {
+            let type_index = type_index
+                .parse::<usize>()
+                .map_err(|_| InvalidFilterSpecifier(remainder.to_string()))?;
+            (type_specifier, type_index)
+        }
+        None => (remainder, 0),
+    };
+    let (media_type, _allow_unused) = stream_specifier_parse(type_specifier)?;
 
-    if media_type != filter_media_type {
+    // bitmap subtitles can feed video pads, they are rendered as video frames (sub2video)
+    let is_sub2video = media_type == AVMEDIA_TYPE_SUBTITLE && filter_media_type == AVMEDIA_TYPE_VIDEO;
+    if media_type != filter_media_type && !is_sub2video {
         warn!("Invalid stream label: {linklabel}");
         return Err(FilterGraphParseError::InvalidArgument.into());
     }
 
     let demux = &demuxs[file_idx as usize];
 
-    let mut stream_idx = -1i32;
-
-    for (idx, dec_stream) in demux.get_streams().iter().enumerate() {
-        if (*dec_stream).codec_type == media_type {
-            stream_idx = idx as i32;
-            break;
-        }
-    }
+    let stream_idx = demux
+        .get_streams()
+        .iter()
+        .enumerate()
+        .filter(|(_, dec_stream)| !dec_stream.ignored && dec_stream.codec_type == media_type)
+        .nth(type_index)
+        .map_or(-1i32, |(idx, _)| idx as i32);
 
     if stream_idx < 0 {
         warn!(
@@ -2081,12 +2883,40 @@ fn strtol(input: &str) -> Result<(i64, &str)> {
 fn init_filter_graphs(filter_complexs: Vec<FilterComplex>) -> Result<Vec<FilterGraph>> {
     let mut filter_graphs = Vec::with_capacity(filter_complexs.len());
     for (i, filter) in filter_complexs.iter().enumerate() {
-        let filter_graph = init_filter_graph(i, &filter.filter_descs, filter.hw_device.clone())?;
+        let mut filter_graph = init_filter_graph(i, &filter.filter_descs, filter.hw_device.clone())?;
+        assign_filter_inputs(&mut filter_graph.inputs, &filter.inputs)?;
         filter_graphs.push(filter_graph);
     }
     Ok(filter_graphs)
 }
 
+/// Replaces the labels of the input pads named by `FilterComplex::add_input` with their
+/// stream specifiers, so binding resolves them like a `[0:v]` label written in the description.
+fn assign_filter_inputs(input_filters: &mut [InputFilter], inputs: &[(String, String)]) -> Result<()> {
+    if inputs.is_empty() {
+        return Ok(());
+    }
+    if inputs.len() != input_filters.len() {
+        return Err(InputCountMismatch(input_filters.len(), inputs.len()).into());
+    }
+
+    let pad_labels = input_filters
+        .iter()
+        .map(|input_filter| input_filter.linklabel.clone())
+        .collect::<Vec<_>>();
+    for (i, (stream_specifier, pad_label)) in inputs.iter().enumerate() {
+        if inputs[..i].iter().any(|(_, label)| label == pad_label) {
+            return Err(InputPadAssignedTwice(pad_label.clone()).into());
+        }
+        let Some(pad_idx) = pad_labels.iter().position(|label| label == pad_label) else {
+            return Err(InputPadNotFound(pad_label.clone()).into());
+        };
+        debug!("Assigning input stream {stream_specifier} to filter input pad '{pad_label}'");
+        input_filters[pad_idx].linklabel = stream_specifier.clone();
+    }
+    Ok(())
+}
+
 
 #[cfg(feature = "docs-rs")]
 fn init_filter_graph(
@@ -2172,6 +3002,104 @@ fn init_filter_graph(
     }
 }
 
+#[cfg(feature = "docs-rs")]
+pub(crate) fn validate_filter_desc(filter_desc: &str) -> Result<()> {
+    Err(Bug)
+}
+
+/// Parses, creates and initializes the filters of `filter_desc` in a throwaway graph,
+/// without opening any input or output.
+#[cfg(not(feature = "docs-rs"))]
+pub(crate) fn validate_filter_desc(filter_desc: &str) -> Result<()> {
+    let desc_cstr = CString::new(filter_desc)?;
+
+    unsafe {
+        let mut graph = avfilter_graph_alloc();
+        if graph.is_null() {
+            return Err(FilterGraphParseError::OutOfMemory.into());
+        }
+        (*graph).nb_threads = 1;
+
+        let mut seg = null_mut();
+        let mut inputs = null_mut();
+        let mut outputs = null_mut();
+        let result = validate_graph_segment(graph, &desc_cstr, &mut seg, &mut inputs, &mut outputs);
+
+        avfilter_inout_free(&mut inputs);
+        avfilter_inout_free(&mut outputs);
+        avfilter_graph_segment_free(&mut seg);
+        avfilter_graph_free(&mut graph);
+        result
+    }
+}
+
+#[cfg(not(feature = "docs-rs"))]
+unsafe fn validate_graph_segment(
+    graph: *mut AVFilterGraph,
+    desc_cstr: &CStr,
+    seg: &mut *mut AVFilterGraphSegment,
+    inputs: &mut *mut AVFilterInOut,
+    outputs: &mut *mut AVFilterInOut,
+) -> Result<()> {
+    let ret = avfilter_graph_segment_parse(graph, desc_cstr.as_ptr(), 0, seg);
+    if ret < 0 {
+        return Err(FilterGraphParseError::from(ret).into());
+    }
+
+    let mut filter_params = Vec::new();
+    for i in 0..(**seg).nb_chains {
+        let chain = *(**seg).chains.add(i);
+        for j in 0..(*chain).nb_filters {
+            filter_params.push(*(*chain).filters.add(j));
+        }
+    }
+    let filter_name = |p: *mut AVFilterParams| CStr::from_ptr((*p).filter_name).to_string_lossy().into_owned();
+
+    // report unknown filters by name, FFmpeg only returns EINVAL
+    for &p in &filter_params {
+        if avfilter_get_by_name((*p).filter_name).is_null() {
+            return Err(FilterNotFound(filter_name(p)).into());
+        }
+    }
+
+    let ret = avfilter_graph_segment_create_filters(*seg, 0);
+    if ret < 0 {
+        return Err(FilterGraphParseError::from(ret).into());
+    }
+
+    // apply the options and initialize one filter at a time, to tell which one fails
+    for &p in &filter_params {
+        let mut e = null();
+        loop {
+            e = av_dict_iterate((*p).opts, e);
+            if e.is_null() {
+                break;
+            }
+            let ret = filter_opt_apply((*p).filter, (*e).key, (*e).value);
+            if ret < 0 {
+                let key = CStr::from_ptr((*e).key).to_string_lossy().into_owned();
+                return Err(InvalidFilterOption(filter_name(p), key, av_err2str(ret)).into());
+            }
+        }
+        av_dict_free(&mut (*p).opts);
+
+        let ret = avfilter_init_dict((*p).filter, null_mut());
+        if ret < 0 {
+            return Err(FilterInitFailed(filter_name(p), av_err2str(ret)).into());
+        }
+    }
+
+    // links the filters, the initialized ones are skipped
+    let ret = avfilter_graph_segment_apply(*seg, 0, inputs, outputs);
+    if ret < 0 {
+        return Err(FilterGraphParseError::from(ret).into());
+    }
+    if (*outputs).is_null() {
+        return Err(FilterZeroOutputs);
+    }
+    Ok(())
+}
+
 unsafe fn inouts_to_input_filters(
     fg_index: usize,
     inouts: *mut AVFilterInOut,
@@ -2308,24 +3236,52 @@ unsafe fn open_input_file(
     input: &mut Input,
     copy_ts: bool
 ) -> Result<Demuxer> {
+    let hw_scale = hw_scale(input)?;
+
     let mut in_fmt_ctx = avformat_alloc_context();
     if in_fmt_ctx.is_null() {
         return Err(OpenInputError::OutOfMemory.into());
     }
 
-    let recording_time_us = match input.stop_time_us {
-        None => input.recording_time_us,
-        Some(stop_time_us) => {
-            let start_time_us = input.start_time_us.unwrap_or_else(|| 0);
-            if stop_time_us <= start_time_us {
-                error!("stop_time_us value smaller than start_time_us; aborting.");
-                return Err(OpenOutputError::InvalidArgument.into());
-            } else {
-                Some(stop_time_us - start_time_us)
-            }
+    if let Some(start_percent) = input.start_percent {
+        if !(0.0..=100.0).contains(&start_percent) {
+            avformat_close_input(&mut in_fmt_ctx);
+            return Err(OpenInputError::InvalidStartPercent(start_percent).into());
+        }
+    }
+
+    if let Some(analyze_duration_us) = input.analyze_duration_us {
+        if analyze_duration_us < 0 {
+            error!("analyze_duration_us must not be negative: {analyze_duration_us}");
+            avformat_close_input(&mut in_fmt_ctx);
+            return Err(OpenInputError::InvalidArgument.into());
+        }
+        (*in_fmt_ctx).max_analyze_duration = analyze_duration_us;
+    }
+    if let Some(probe_size) = input.probe_size {
+        if probe_size < 0 {
+            error!("probe_size must not be negative: {probe_size}");
+            avformat_close_input(&mut in_fmt_ctx);
+            return Err(OpenInputError::InvalidArgument.into());
         }
+        (*in_fmt_ctx).probesize = probe_size;
+    }
+
+    let interrupt = if input.url.is_some() && (input.open_timeout.is_some() || input.read_timeout.is_some()) {
+        let interrupt = Arc::new(InputInterrupt::new(input.open_timeout, input.read_timeout));
+        (*in_fmt_ctx).interrupt_callback = ffmpeg_sys_next::AVIOInterruptCB {
+            callback: Some(input_interrupt_callback),
+            opaque: Arc::as_ptr(&interrupt) as *mut c_void,
+        };
+        Some(interrupt)
+    } else {
+        None
     };
 
+    if let Some(url) = input.url.clone() {
+        apply_playlist_defaults(input, &url);
+    }
+
     match &input.url {
         None => {
             if input.read_callback.is_none() {
@@ -2399,6 +3355,11 @@ unsafe fn open_input_file(
 
                 let file_iformat = ffmpeg_sys_next::av_find_input_format(format_cstr.as_ptr());
                 if file_iformat.is_null() {
+                    if crate::core::device::CAPTURE_FORMATS.contains(&format.as_str()) {
+                        let available = crate::core::device::get_input_device_formats().join(", ");
+                        error!("Capture device format '{format}' is not compiled into FFmpeg, available: [{available}]");
+                        return Err(OpenInputError::DeviceFormatUnavailable(format.clone(), available).into());
+                    }
                     error!("Unknown input format: '{format}'");
                     return Err(OpenInputError::InvalidFormat(format.clone()).into());
                 }
@@ -2407,6 +3368,15 @@ unsafe fn open_input_file(
                 null()
             };
 
+            if let Some(format @ ("hls" | "concat")) = input.format.as_deref() {
+                if let Some(missing) = missing_playlist_entry(format, url) {
+                    let missing = missing.display().to_string();
+                    error!("File '{missing}' referenced by playlist '{url}' does not exist");
+                    avformat_close_input(&mut in_fmt_ctx);
+                    return Err(OpenInputError::PlaylistEntryNotFound(missing, url.clone()).into());
+                }
+            }
+
             let url_cstr = CString::new(url.as_str())?;
 
             let format_opts = convert_options(input.format_opts.clone())?;
@@ -2416,24 +3386,59 @@ unsafe fn open_input_file(
                 let scan_all_pmts_value = CString::new("1")?;
                 ffmpeg_sys_next::av_dict_set(&mut format_opts, scan_all_pmts_key.as_ptr(), scan_all_pmts_value.as_ptr(), ffmpeg_sys_next::AV_DICT_DONT_OVERWRITE);
             };
+            if let Some(read_timeout) = input.read_timeout {
+                let rw_timeout_key = CString::new("rw_timeout")?;
+                let rw_timeout_value = CString::new(read_timeout.as_micros().to_string())?;
+                ffmpeg_sys_next::av_dict_set(&mut format_opts, rw_timeout_key.as_ptr(), rw_timeout_value.as_ptr(), ffmpeg_sys_next::AV_DICT_DONT_OVERWRITE);
+            }
             (*in_fmt_ctx).flags |= ffmpeg_sys_next::AVFMT_FLAG_NONBLOCK;
 
+            if let Some(interrupt) = &interrupt {
+                interrupt.arm(interrupt.open_timeout);
+            }
             let mut ret =
                 avformat_open_input(&mut in_fmt_ctx, url_cstr.as_ptr(), file_iformat, &mut format_opts);
+            if ret >= 0 {
+                // set by default, so not worth a warning when the demuxer is not MPEG-TS
+                ffmpeg_sys_next::av_dict_set(&mut format_opts, scan_all_pmts_key.as_ptr(), null(), ffmpeg_sys_next::AV_DICT_MATCH_CASE);
+                warn_unused_format_opts(format_opts, url);
+            }
             av_dict_free(&mut format_opts);
             if ret < 0 {
                 avformat_close_input(&mut in_fmt_ctx);
-                return Err(OpenInputError::from(ret).into());
+                return Err(open_timeout_error(&interrupt, url).unwrap_or_else(|| OpenInputError::from(ret).into()));
             }
 
             ret = avformat_find_stream_info(in_fmt_ctx, null_mut());
             if ret < 0 {
                 avformat_close_input(&mut in_fmt_ctx);
-                return Err(FindStreamError::from(ret).into());
+                return Err(open_timeout_error(&interrupt, url).unwrap_or_else(|| FindStreamError::from(ret).into()));
+            }
+            if let Some(interrupt) = &interrupt {
+                interrupt.arm(None);
             }
         }
     }
 
+    if let Err(e) = resolve_start_position(in_fmt_ctx, input) {
+        avformat_close_input(&mut in_fmt_ctx);
+        return Err(e);
+    }
+
+    let recording_time_us = match input.stop_time_us {
+        None => input.recording_time_us,
+        Some(stop_time_us) => {
+            let start_time_us = input.start_time_us.unwrap_or_else(|| 0);
+            if stop_time_us <= start_time_us {
+                error!("stop_time_us value smaller than start_time_us; aborting.");
+                avformat_close_input(&mut in_fmt_ctx);
+                return Err(OpenOutputError::InvalidArgument.into());
+            } else {
+                Some(stop_time_us - start_time_us)
+            }
+        }
+    };
+
     let mut timestamp = input.start_time_us.unwrap_or(0);
     /* add the stream start time */
     if (*in_fmt_ctx).start_time != ffmpeg_sys_next::AV_NOPTS_VALUE {
@@ -2484,17 +3489,228 @@ unsafe fn open_input_file(
         input.readrate,
         input.start_time_us,
         recording_time_us,
+        input.accurate_seek.unwrap_or(true),
+        input.keyframes_only.unwrap_or(false),
+        ignored_media_types(input),
+        input.normalize_sar.unwrap_or(false),
+        convert_options(input.decoder_opts.clone())?,
         input.exit_on_error,
+        input.read_retry,
+        input.fix_timestamps.unwrap_or(false),
+        input.error_resilience.unwrap_or_default(),
+        interrupt,
         input.stream_loop,
+        input.canvas_size,
         input.hwaccel.clone(),
         input.hwaccel_device.clone(),
         input.hwaccel_output_format.clone(),
+        hw_scale,
         copy_ts
     )?;
 
     Ok(demux)
 }
 
+/// Returns the size and scaler of [`Input::set_hw_scale`], failing when the FFmpeg build has
+/// no scaler for the frames the hardware decoder outputs.
+#[cfg(not(feature = "docs-rs"))]
+fn hw_scale(input: &Input) -> Result<Option<(i32, i32, &'static str)>> {
+    let Some((width, height)) = input.hw_scale else {
+        return Ok(None);
+    };
+    // the output formats find_hwaccel defaults to
+    let output_format = match (input.hwaccel.as_deref(), input.hwaccel_output_format.as_deref()) {
+        (_, Some(format)) => Some(format),
+        (Some("cuvid"), None) => Some("cuda"),
+        (Some("qsv"), None) => Some("qsv"),
+        (Some("mediacodec"), None) => Some("mediacodec"),
+        _ => None,
+    };
+    let scaler = hw_scaler_for_format(output_format)?;
+    Ok(Some((width, height, scaler)))
+}
+
+/// Returns [`Error::InputTimeout`] when opening `url` failed because its open timeout passed.
+#[cfg(not(feature = "docs-rs"))]
+fn open_timeout_error(interrupt: &Option<Arc<InputInterrupt>>, url: &str) -> Option<Error> {
+    let interrupt = interrupt.as_ref().filter(|interrupt| interrupt.timed_out())?;
+    error!("Opening input '{url}' timed out");
+    Some(Error::InputTimeout(url.to_string(), interrupt.open_timeout.unwrap_or_default()))
+}
+
+/// Protocols a playlist or concat list may reference its files with. Nested files inherit
+/// the whitelist of the list, which is only `file,crypto,data` for a local one.
+const PLAYLIST_PROTOCOL_WHITELIST: &str = "file,crypto,data,http,https,tcp,tls";
+
+/// Selects the `hls` demuxer for `.m3u8` URLs when no format is set, and lets `hls` playlists
+/// and `concat` lists reference remote files unless the user set a protocol whitelist.
+fn apply_playlist_defaults(input: &mut Input, url: &str) {
+    if input.format.is_none() && is_hls_playlist(url) {
+        input.format = Some("hls".to_string());
+    }
+    if matches!(input.format.as_deref(), Some("hls" | "concat")) {
+        input
+            .format_opts
+            .get_or_insert_with(HashMap::new)
+            .entry("protocol_whitelist".to_string())
+            .or_insert_with(|| PLAYLIST_PROTOCOL_WHITELIST.to_string());
+    }
+}
+
+fn is_hls_playlist(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.to_ascii_lowercase().ends_with(".m3u8")
+}
+
+/// Returns the path of `url` if it names a local file, i.e. it has no protocol or the
+/// `file:` one. A single letter before the colon is a Windows drive, not a protocol.
+fn local_path(url: &str) -> Option<&str> {
+    if let Some(path) = url.strip_prefix("file:") {
+        return Some(path);
+    }
+    match url.split_once(':') {
+        Some((scheme, _))
+            if scheme.len() > 1
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) =>
+        {
+            None
+        }
+        _ => Some(url),
+    }
+}
+
+/// Returns the files referenced by a playlist, in order: the `file` directives of a
+/// `concat` list, or the URI lines of an `hls` playlist.
+fn playlist_entries(format: &str, content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            if format != "concat" {
+                return Some(line.to_string());
+            }
+            let (directive, rest) = line.split_once(char::is_whitespace)?;
+            (directive == "file").then(|| unquote_concat_token(rest))
+        })
+        .collect()
+}
+
+/// Parses the first token of `token` like the concat demuxer does: `'` quotes a string
+/// and `\` escapes the next character outside quotes.
+fn unquote_concat_token(token: &str) -> String {
+    let mut unquoted = String::new();
+    let mut chars = token.trim_start().chars();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => quoted = !quoted,
+            '\\' if !quoted => unquoted.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => break,
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Returns the first local file referenced by the `hls` playlist or `concat` list at `url`
+/// that does not exist, resolved against the directory of the list. Remote lists and
+/// entries are not checked, and neither are lists that cannot be read, which FFmpeg reports.
+fn missing_playlist_entry(format: &str, url: &str) -> Option<std::path::PathBuf> {
+    let list_path = std::path::Path::new(local_path(url)?);
+    let content = std::fs::read_to_string(list_path).ok()?;
+    let base = list_path.parent().unwrap_or(std::path::Path::new(""));
+    playlist_entries(format, &content)
+        .iter()
+        .filter_map(|entry| local_path(entry).map(|path| base.join(path)))
+        .find(|path| !path.exists())
+}
+
+/// Logs the options left in `format_opts` by `avformat_open_input`, i.e. the ones neither
+/// the demuxer nor the protocol recognized (typically a typo or an option of another format).
+#[cfg(not(feature = "docs-rs"))]
+unsafe fn warn_unused_format_opts(format_opts: *const ffmpeg_sys_next::AVDictionary, url: &str) {
+    let mut e = null();
+    loop {
+        e = av_dict_iterate(format_opts, e);
+        if e.is_null() {
+            break;
+        }
+        warn!(
+            "Input option '{}' was not used by the demuxer or protocol of {url}.",
+            CStr::from_ptr((*e).key).to_string_lossy()
+        );
+    }
+}
+
+/// Converts `start_percent` / `start_frame` into `start_time_us` once the input is probed.
+#[cfg(not(feature = "docs-rs"))]
+unsafe fn resolve_start_position(in_fmt_ctx: *mut AVFormatContext, input: &mut Input) -> Result<()> {
+    if let Some(start_percent) = input.start_percent {
+        let duration = (*in_fmt_ctx).duration;
+        if duration == ffmpeg_sys_next::AV_NOPTS_VALUE || duration <= 0 {
+            error!("Cannot seek by percentage: input duration is unknown.");
+            return Err(OpenInputError::UnknownDuration.into());
+        }
+        let start_time_us = (duration as f64 * start_percent as f64 / 100.0) as i64;
+        debug!("Start percent {start_percent}% resolved to {start_time_us}us");
+        input.start_time_us = Some(start_time_us);
+    } else if let Some(start_frame) = input.start_frame {
+        let video_index = av_find_best_stream(in_fmt_ctx, AVMEDIA_TYPE_VIDEO, -1, -1, null_mut(), 0);
+        if video_index < 0 {
+            error!("Cannot seek by frame number: input has no video stream.");
+            return Err(OpenInputError::UnknownFrameRate.into());
+        }
+        let stream = *(*in_fmt_ctx).streams.add(video_index as usize);
+        let mut frame_rate = (*stream).avg_frame_rate;
+        if frame_rate.num <= 0 || frame_rate.den <= 0 {
+            frame_rate = (*stream).r_frame_rate;
+        }
+        if frame_rate.num <= 0 || frame_rate.den <= 0 {
+            error!("Cannot seek by frame number: video frame rate is unknown.");
+            return Err(OpenInputError::UnknownFrameRate.into());
+        }
+
+        let estimated_frames = if (*stream).nb_frames > 0 {
+            (*stream).nb_frames
+        } else if (*in_fmt_ctx).duration != ffmpeg_sys_next::AV_NOPTS_VALUE && (*in_fmt_ctx).duration > 0 {
+            av_rescale_q(
+                (*in_fmt_ctx).duration,
+                AV_TIME_BASE_Q,
+                AVRational { num: frame_rate.den, den: frame_rate.num },
+            )
+        } else {
+            -1
+        };
+        if estimated_frames >= 0 && start_frame >= estimated_frames as u64 {
+            error!("Start frame {start_frame} is beyond the estimated frame count {estimated_frames}.");
+            return Err(OpenInputError::StartFrameOutOfRange(start_frame, estimated_frames).into());
+        }
+
+        let start_time_us = av_rescale_q(
+            start_frame as i64,
+            AVRational { num: frame_rate.den, den: frame_rate.num },
+            AV_TIME_BASE_Q,
+        );
+        debug!("Start frame {start_frame} resolved to {start_time_us}us");
+        input.start_time_us = Some(start_time_us);
+    }
+    Ok(())
+}
+
+/// The media types whose streams the input leaves out of the pipeline.
+fn ignored_media_types(input: &Input) -> Vec<AVMediaType> {
+    [
+        (input.ignore_video, AVMEDIA_TYPE_VIDEO),
+        (input.ignore_audio, AVMEDIA_TYPE_AUDIO),
+        (input.ignore_subtitles, AVMEDIA_TYPE_SUBTITLE),
+    ]
+    .into_iter()
+    .filter(|(ignore, _)| ignore.unwrap_or(false))
+    .map(|(_, media_type)| media_type)
+    .collect()
+}
+
 fn convert_options(
     opts: Option<HashMap<String, String>>,
 ) -> Result<Option<HashMap<CString, CString>>> {
@@ -2626,13 +3842,481 @@ unsafe fn input_requires_seek(fmt_ctx: *mut AVFormatContext) -> bool {
 #[cfg(test)]
 mod tests {
     use std::ffi::{CStr, CString};
-    use std::ptr::null_mut;
+    use std::ptr::{null, null_mut};
+    use std::time::{Duration, Instant};
 
-    use crate::core::context::ffmpeg_context::{strtol, FfmpegContext, Output};
+    use crate::core::context::ffmpeg_context::{
+        format_creation_time, is_hls_playlist, local_path, parse_creation_time, playlist_entries, strtol,
+        FfmpegContext, FilterComplex, Input, Output,
+    };
+    use crate::core::tags::Tags;
+    use crate::error::{Error, FilterGraphParseError, OpenInputError, OpenOutputError};
     use ffmpeg_sys_next::{
-        avfilter_graph_alloc, avfilter_graph_free, avfilter_graph_parse_ptr, avfilter_inout_free,
+        av_dict_iterate, av_rescale_q, avfilter_graph_alloc, avfilter_graph_free, avfilter_graph_parse_ptr,
+        avfilter_inout_free, AVRational,
     };
 
+    #[test]
+    fn test_faststart_requires_seekable_mp4() {
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("-").set_format("mp4").set_faststart(true))
+            .build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::FaststartUnsupported(_, _)))));
+
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("faststart_rejected.mkv").set_faststart(true))
+            .build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::FaststartUnsupported(_, _)))));
+        // rejected before the file is created
+        assert!(!std::path::Path::new("faststart_rejected.mkv").exists());
+    }
+
+    #[test]
+    fn test_template() {
+        use crate::core::filter::frame_filter::NoopFilter;
+        use crate::filter::frame_pipeline_builder::FramePipelineBuilder;
+        use ffmpeg_sys_next::AVMediaType::AVMEDIA_TYPE_VIDEO;
+
+        let pipeline = FramePipelineBuilder::new(AVMEDIA_TYPE_VIDEO)
+            .filter_factory("noop", || Box::new(NoopFilter::new(AVMEDIA_TYPE_VIDEO)));
+        let template = FfmpegContext::builder()
+            .filter_desc("hue=s=0")
+            .output(Output::from("").add_frame_pipeline(pipeline));
+        for _ in 0..2 {
+            let context = template.template().unwrap().input("test.mp4").set_output_url(0, "output.mp4").build();
+            assert!(context.is_ok());
+        }
+
+        let pipeline = FramePipelineBuilder::new(AVMEDIA_TYPE_VIDEO)
+            .filter("noop", Box::new(NoopFilter::new(AVMEDIA_TYPE_VIDEO)));
+        let result = FfmpegContext::builder().output(Output::from("output.mp4").add_frame_pipeline(pipeline)).template();
+        assert!(matches!(result, Err(Error::TemplateUnsupported(_))));
+
+        let output = Output::new_by_write_callback(|buf: &[u8]| buf.len() as i32);
+        let result = FfmpegContext::builder().output(output).template();
+        assert!(matches!(result, Err(Error::TemplateUnsupported(_))));
+    }
+
+    #[test]
+    fn test_unsupported_pix_fmt() {
+        let output = Output::from("output.mp4")
+            .set_video_codec("mpeg4")
+            .set_video_pix_fmt(ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGB24);
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(
+            matches!(result, Err(Error::OpenOutput(OpenOutputError::PixelFormatUnsupported(ref pix_fmt, _, _))) if pix_fmt == "rgb24"),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_unsupported_channel_layout() {
+        // mp2 only encodes mono and stereo
+        let output = Output::from("output.mp4")
+            .set_audio_codec("mp2")
+            .set_audio_channel_layout("5.1");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(
+            matches!(result, Err(Error::OpenOutput(OpenOutputError::ChannelLayoutUnsupported(ref layout, _, ref supported))) if layout == "5.1" && supported.contains("stereo")),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_stream_tags() {
+        let output = Output::from("output.mp4")
+            .add_stream_map("0:a")
+            .set_stream_language(0, "eng")
+            .set_stream_tag(0, "title", "Main");
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+        unsafe {
+            let st = *(*context.muxs[0].out_fmt_ctx).streams;
+            let key = CString::new("language").unwrap();
+            let entry = ffmpeg_sys_next::av_dict_get((*st).metadata, key.as_ptr(), null(), 0);
+            assert!(!entry.is_null());
+            assert_eq!(CStr::from_ptr((*entry).value).to_str().unwrap(), "eng");
+        }
+
+        // the output only has one stream, whatever the input has
+        let output = Output::from("output.mp4").add_stream_map("0:a").set_stream_language(1, "eng");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::StreamIndexOutOfRange(1, 1)))));
+    }
+
+    #[test]
+    fn test_audio_tracks() {
+        let output = Output::from("output_tracks.mkv")
+            .add_stream_map("0:v")
+            .add_audio_track("0:a", "eng")
+            .add_audio_track("0:a:0", "spa")
+            .set_default_stream(2)
+            .set_recording_time_us(1_000_000);
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+        context.start().unwrap().wait().unwrap();
+
+        let input = ffmpeg_next::format::input(&"output_tracks.mkv").unwrap();
+        let tracks = input
+            .streams()
+            .filter(|stream| stream.parameters().medium() == ffmpeg_next::media::Type::Audio)
+            .map(|stream| {
+                (
+                    stream.metadata().get("language").map(str::to_string),
+                    stream.disposition().contains(ffmpeg_next::format::stream::Disposition::DEFAULT),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tracks, vec![(Some("eng".to_string()), false), (Some("spa".to_string()), true)]);
+        std::fs::remove_file("output_tracks.mkv").unwrap();
+
+        // input #0 has a single audio stream
+        let output = Output::from("output.mp4").add_audio_track("0:a:1", "eng");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::MatchesNoStreams(_)))));
+    }
+
+    #[test]
+    fn test_time_base() {
+        let output = Output::from("output_time_base.mp4")
+            .set_time_base(0, AVRational { num: 1, den: 90000 })
+            .set_recording_time_us(1_000_000);
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+        context.start().unwrap().wait().unwrap();
+
+        let input = ffmpeg_next::format::input(&"output_time_base.mp4").unwrap();
+        let time_base = input.stream(0).unwrap().time_base();
+        assert_eq!((time_base.numerator(), time_base.denominator()), (1, 90000));
+        std::fs::remove_file("output_time_base.mp4").unwrap();
+
+        // the duration of a copied stream follows its new time base
+        let input = ffmpeg_next::format::input(&"test.mp4").unwrap();
+        let (duration, time_base) = (input.stream(0).unwrap().duration(), input.stream(0).unwrap().time_base());
+        let output = Output::from("output_time_base.mp4")
+            .add_stream_map("0:v")
+            .set_video_codec("copy")
+            .set_time_base(0, AVRational { num: 1, den: 90000 });
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+        unsafe {
+            let st = *(*context.muxs[0].out_fmt_ctx).streams;
+            let expected = av_rescale_q(duration, time_base.into(), AVRational { num: 1, den: 90000 });
+            assert!(((*st).duration - expected).abs() <= 1, "{} / {expected}", (*st).duration);
+        }
+        drop(context);
+        let _ = std::fs::remove_file("output_time_base.mp4");
+
+        let output = Output::from("output.mp4").set_time_base(0, AVRational { num: 1, den: 0 });
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::InvalidTimeBase(0, 1, 0)))));
+    }
+
+    #[test]
+    fn test_start_position() {
+        let start_time_us = |input: Input| {
+            let context = FfmpegContext::builder().input(input).output("output.mp4").build().unwrap();
+            context.demuxs[0].start_time_us.unwrap()
+        };
+        let duration_us = crate::container_info::get_duration_us("test.mp4").unwrap();
+        let half = start_time_us(Input::from("test.mp4").set_start_percent(50.0));
+        assert!((half - duration_us / 2).abs() <= 1, "{half} / {duration_us}");
+        assert_eq!(start_time_us(Input::from("test.mp4").set_start_percent(0.0)), 0);
+
+        let frame_rate = ffmpeg_next::format::input(&"test.mp4").unwrap().stream(0).unwrap().avg_frame_rate();
+        let expected_us = 10 * 1_000_000 * frame_rate.denominator() as i64 / frame_rate.numerator() as i64;
+        let tenth = start_time_us(Input::from("test.mp4").set_start_frame(10));
+        assert!((tenth - expected_us).abs() <= 1, "{tenth} / {expected_us}");
+        // the last setter wins
+        assert_eq!(start_time_us(Input::from("test.mp4").set_start_frame(10).set_start_time_us(5)), 5);
+
+        let build = |input: Input| FfmpegContext::builder().input(input).output("output.mp4").build();
+        let result = build(Input::from("test.mp4").set_start_percent(100.5));
+        assert!(matches!(result, Err(Error::OpenInputStream(OpenInputError::InvalidStartPercent(_)))));
+        let result = build(Input::from("test.mp4").set_start_frame(u32::MAX as u64));
+        assert!(matches!(result, Err(Error::OpenInputStream(OpenInputError::StartFrameOutOfRange(_, _)))));
+    }
+
+    #[test]
+    fn test_invalid_output_limits() {
+        let build = |output: Output| FfmpegContext::builder().input("test.mp4").output(output).build();
+        let result = build(Output::from("output.mp4").set_max_audio_frames(0));
+        assert!(matches!(
+            result,
+            Err(Error::OpenOutput(OpenOutputError::OptionValueTooSmall(ref name, 0, 1))) if name == "max_audio_frames"
+        ));
+        let result = build(Output::from("output.mp4").set_max_video_frames(-5));
+        assert!(matches!(
+            result,
+            Err(Error::OpenOutput(OpenOutputError::OptionValueTooSmall(ref name, -5, 1))) if name == "max_video_frames"
+        ));
+        let result = build(Output::from("output.mp4").set_max_muxing_queue_size(0));
+        assert!(matches!(
+            result,
+            Err(Error::OpenOutput(OpenOutputError::OptionValueTooSmall(ref name, 0, 1))) if name == "max_muxing_queue_size"
+        ));
+        assert!(build(Output::from("output.mp4").set_max_audio_frames(1).set_max_muxing_queue_size(1)).is_ok());
+    }
+
+    #[test]
+    fn test_creation_time() {
+        let time_us = parse_creation_time("2024-05-01T14:00:00.25+02:00").unwrap();
+        assert_eq!(format_creation_time(time_us), "2024-05-01T12:00:00.250000Z");
+        assert_eq!(parse_creation_time("1970-01-01 00:00:00"), Some(0));
+        let time_us = parse_creation_time("2000-02-29T23:59:59Z").unwrap();
+        assert_eq!(format_creation_time(time_us), "2000-02-29T23:59:59.000000Z");
+        assert_eq!(format_creation_time(-1), "1969-12-31T23:59:59.999999Z");
+        for invalid in ["2023-02-29T00:00:00Z", "2024-05-01", "2024-05-01T12:00:00+2", "yesterday"] {
+            assert!(parse_creation_time(invalid).is_none(), "{invalid}");
+        }
+
+        let creation_time = |output: Output| unsafe {
+            let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+            let fmt_ctx = context.muxs[0].out_fmt_ctx;
+            let key = CString::new("creation_time").unwrap();
+            [(*fmt_ctx).metadata, (**(*fmt_ctx).streams).metadata].map(|metadata| {
+                let entry = ffmpeg_sys_next::av_dict_get(metadata, key.as_ptr(), null(), 0);
+                (!entry.is_null()).then(|| CStr::from_ptr((*entry).value).to_str().unwrap().to_string())
+            })
+        };
+        let time = Some("2024-05-01T12:00:00.000000Z".to_string());
+        for (url, stream_time) in [("output.mp4", time.clone()), ("output.mkv", None)] {
+            let output = Output::from(url)
+                .add_stream_map_with_copy("0:v")
+                .set_creation_time("2024-05-01T12:00:00Z");
+            assert_eq!(creation_time(output), [time.clone(), stream_time], "{url}");
+        }
+
+        // stripping the metadata keeps the creation time that was asked for
+        let output = Output::from("output.mp4")
+            .set_creation_time(String::from("2024-05-01T12:00:00Z"))
+            .strip_all_metadata(true);
+        assert_eq!(creation_time(output), [time.clone(), time.clone()]);
+        let output = Output::from("output.mp4").strip_all_metadata(true);
+        assert_eq!(creation_time(output), [None, None]);
+
+        #[cfg(feature = "chrono")]
+        {
+            let date = chrono::DateTime::parse_from_rfc3339("2024-05-01T14:00:00+02:00").unwrap();
+            let output = Output::from("output.mp4").set_creation_time(date);
+            assert_eq!(creation_time(output), [time.clone(), time.clone()]);
+        }
+
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output.mp4").set_creation_time("2024-13-01T00:00:00Z"))
+            .build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::InvalidCreationTime(_)))));
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let tags = Tags {
+            title: Some("Holiday".to_string()),
+            other: vec![
+                ("location".to_string(), "+48.8584+002.2945/".to_string()),
+                ("com.apple.quicktime.make".to_string(), "Apple".to_string()),
+            ],
+            ..Tags::default()
+        };
+        let metadata_keys = |output: Output| unsafe {
+            let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+            let fmt_ctx = context.muxs[0].out_fmt_ctx;
+            let mut keys = Vec::new();
+            for metadata in [(*fmt_ctx).metadata, (**(*fmt_ctx).streams).metadata] {
+                let mut entry = null();
+                loop {
+                    entry = av_dict_iterate(metadata, entry);
+                    if entry.is_null() {
+                        break;
+                    }
+                    keys.push(CStr::from_ptr((*entry).key).to_str().unwrap().to_string());
+                }
+            }
+            ((*fmt_ctx).flags & ffmpeg_sys_next::AVFMT_FLAG_BITEXACT as i32 != 0, keys)
+        };
+
+        let (bitexact, keys) = metadata_keys(
+            Output::from("output.mp4")
+                .add_stream_map_with_copy("0:v")
+                .set_tags(tags.clone())
+                .set_stream_tag(0, "Location", "+48.8584+002.2945/")
+                .strip_metadata_keys(vec!["LOCATION".to_string(), "com.apple.quicktime.make".to_string()]),
+        );
+        assert!(!bitexact);
+        assert_eq!(keys, vec!["title".to_string()]);
+
+        let (bitexact, keys) = metadata_keys(
+            Output::from("output.mp4")
+                .add_stream_map_with_copy("0:v")
+                .set_tags(tags)
+                .set_stream_tag(0, "title", "Main")
+                .strip_all_metadata(true),
+        );
+        assert!(bitexact);
+        assert!(keys.is_empty(), "{keys:?}");
+    }
+
+    #[test]
+    fn test_bitstream_filter() {
+        let output = Output::from("output_bsf.h264")
+            .add_stream_map_with_copy("0:v")
+            .set_bitstream_filter(0, "h264_mp4toannexb")
+            .set_bitstream_filter(0, "h264_metadata=aud=insert");
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        // start codes, and an access unit delimiter (NAL type 9) before each frame
+        let bytes = std::fs::read("output_bsf.h264").unwrap();
+        let _ = std::fs::remove_file("output_bsf.h264");
+        assert!(bytes.starts_with(&[0, 0, 0, 1, 9]));
+
+        let output = Output::from("output.ts").add_stream_map_with_copy("0:v").set_bitstream_filter(0, "no_such_filter");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::InvalidBitstreamFilter(_, 0)))));
+
+        // AAC is not H.264
+        let output = Output::from("output.ts").add_stream_map_with_copy("0:a").set_bitstream_filter(0, "h264_mp4toannexb");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::InvalidBitstreamFilter(_, 0)))));
+
+        let output = Output::from("output.ts").add_stream_map_with_copy("0:v").set_bitstream_filter(1, "h264_mp4toannexb");
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::StreamIndexOutOfRange(1, 1)))));
+    }
+
+    #[test]
+    fn test_playlist_entries() {
+        let list = "ffconcat version 1.0\n# intro\nfile 'intro.mp4'\nduration 5\nfile   part\\ 2.mp4 \nfile '/abs/it'\\''s.mp4'\n";
+        assert_eq!(playlist_entries("concat", list), vec!["intro.mp4", "part 2.mp4", "/abs/it's.mp4"]);
+
+        let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXTINF:4.0,\nseg0.ts\n\n#EXTINF:4.0,\nhttps://cdn.example.com/seg1.ts\n#EXT-X-ENDLIST\n";
+        assert_eq!(playlist_entries("hls", playlist), vec!["seg0.ts", "https://cdn.example.com/seg1.ts"]);
+
+        assert_eq!(local_path("file:/tmp/a.ts"), Some("/tmp/a.ts"));
+        assert_eq!(local_path("C:\\videos\\a.ts"), Some("C:\\videos\\a.ts"));
+        assert_eq!(local_path("segments/a.ts"), Some("segments/a.ts"));
+        assert_eq!(local_path("https://cdn.example.com/a.ts"), None);
+
+        assert!(is_hls_playlist("https://cdn.example.com/live/index.M3U8?token=1"));
+        assert!(!is_hls_playlist("list.txt"));
+    }
+
+    #[test]
+    fn test_concat_list() {
+        std::fs::create_dir_all("concat_list_test").unwrap();
+        std::fs::copy("test.mp4", "concat_list_test/part.mp4").unwrap();
+        // relative to the list, not the working directory
+        std::fs::write("concat_list_test/list.txt", "file 'part.mp4'\nfile 'part.mp4'\n").unwrap();
+        std::fs::write("concat_list_test/missing.txt", "file 'part.mp4'\nfile 'gone.mp4'\n").unwrap();
+
+        let output = Output::from("-").set_format("null");
+        let result = FfmpegContext::builder()
+            .input(Input::from_concat_list("concat_list_test/list.txt"))
+            .output(output)
+            .build();
+        assert!(result.is_ok(), "{:?}", result.err());
+        let result = result.unwrap().start().unwrap().wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let output = Output::from("-").set_format("null");
+        let result = FfmpegContext::builder()
+            .input(Input::from_concat_list("concat_list_test/missing.txt"))
+            .output(output)
+            .build();
+        assert!(
+            matches!(result, Err(Error::OpenInputStream(OpenInputError::PlaylistEntryNotFound(ref file, _))) if file.ends_with("gone.mp4")),
+            "{:?}",
+            result.err()
+        );
+
+        std::fs::remove_dir_all("concat_list_test").unwrap();
+    }
+
+    #[test]
+    fn test_overwrite() {
+        std::fs::write("output_exists.mp4", b"previous result").unwrap();
+
+        let output = Output::from("output_exists.mp4").set_overwrite(false);
+        let result = FfmpegContext::builder().input("test.mp4").output(output).build();
+        assert!(matches!(result, Err(Error::OpenOutput(OpenOutputError::OutputExists(ref url))) if url == "output_exists.mp4"));
+        assert_eq!(std::fs::read("output_exists.mp4").unwrap(), b"previous result");
+
+        // the default truncates the file
+        let result = FfmpegContext::builder().input("test.mp4").output("output_exists.mp4").build();
+        assert!(result.is_ok());
+        drop(result);
+        assert!(std::fs::read("output_exists.mp4").unwrap().is_empty());
+
+        std::fs::remove_file("output_exists.mp4").unwrap();
+    }
+
+    #[test]
+    fn test_open_timeout() {
+        // the connection is accepted by the kernel, but no data ever arrives
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+
+        let input = Input::from(url.as_str()).set_open_timeout(Duration::from_millis(500));
+        let start = Instant::now();
+        let result = FfmpegContext::builder().input(input).output("output.mp4").build();
+        assert!(matches!(result, Err(Error::InputTimeout(ref timed_out_url, _)) if *timed_out_url == url), "{:?}", result.err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_filter_inputs() {
+        let overlay = FilterComplex::from("[main][logo]overlay=10:10")
+            .add_input("0:v", "main")
+            .add_input("1:v", "logo");
+        let result = FfmpegContext::builder()
+            .inputs(vec!["test.mp4", "test.mp4"])
+            .filter_desc(overlay)
+            .output("output.mp4")
+            .build();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let overlay = FilterComplex::from("[main][logo]overlay=10:10").add_input("0:v", "main");
+        let result = FfmpegContext::builder().input("test.mp4").filter_desc(overlay).output("output.mp4").build();
+        assert!(matches!(result, Err(Error::FilterGraphParse(FilterGraphParseError::InputCountMismatch(2, 1)))));
+
+        let overlay = FilterComplex::from("[main][logo]overlay=10:10")
+            .add_input("0:v", "main")
+            .add_input("1:v", "watermark");
+        let result = FfmpegContext::builder()
+            .inputs(vec!["test.mp4", "test.mp4"])
+            .filter_desc(overlay)
+            .output("output.mp4")
+            .build();
+        assert!(
+            matches!(result, Err(Error::FilterGraphParse(FilterGraphParseError::InputPadNotFound(ref label))) if label == "watermark")
+        );
+    }
+
+    #[test]
+    fn test_validate_filters() {
+        assert!(FfmpegContext::builder().filter_desc("[0:v]hue=s=0,scale=640:-2[v]").validate_filters().is_ok());
+
+        let result = FfmpegContext::builder().filter_desc("hue=s=0,nosuchfilter").validate_filters();
+        assert!(
+            matches!(result, Err(Error::FilterGraphParse(FilterGraphParseError::FilterNotFound(ref name))) if name == "nosuchfilter"),
+            "{result:?}"
+        );
+
+        let result = FfmpegContext::builder().filter_desc("scale=nosuchoption=1").validate_filters();
+        assert!(
+            matches!(result, Err(Error::FilterGraphParse(FilterGraphParseError::InvalidFilterOption(ref name, ref key, _))) if name == "scale" && key == "nosuchoption"),
+            "{result:?}"
+        );
+    }
+
     #[test]
     fn test_filter() {
         let desc_cstr = CString::new("[1:v][2:v]concat=n=2:v=1:a=0[vout]").unwrap();
@@ -2737,6 +4421,54 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_subtitles_to_webvtt() {
+        std::fs::write(
+            "input_subtitles.srt",
+            "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i> world\n\n2\n00:00:03,000 --> 00:00:04,000\nSecond line\n",
+        )
+        .unwrap();
+
+        let result = FfmpegContext::builder()
+            .input("input_subtitles.srt")
+            .output(Output::from("output_subtitles.vtt").add_stream_map("0:s"))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let vtt = std::fs::read_to_string("output_subtitles.vtt").unwrap();
+        std::fs::remove_file("input_subtitles.srt").unwrap();
+        std::fs::remove_file("output_subtitles.vtt").unwrap();
+        assert!(vtt.starts_with("WEBVTT"), "{vtt}");
+        assert!(vtt.contains("00:01.000 --> 00:02.500"), "{vtt}");
+        assert!(vtt.contains("<i>Hello</i> world"), "{vtt}");
+        assert!(vtt.contains("00:03.000 --> 00:04.000"), "{vtt}");
+    }
+
+    #[test]
+    fn test_check_subtitle_conversion() {
+        use ffmpeg_sys_next::{avcodec_find_encoder_by_name, AVCodecID};
+
+        unsafe {
+            let (webvtt, dvdsub) = (CString::new("webvtt").unwrap(), CString::new("dvdsub").unwrap());
+            let webvtt = avcodec_find_encoder_by_name(webvtt.as_ptr());
+            let dvdsub = avcodec_find_encoder_by_name(dvdsub.as_ptr());
+            assert!(!webvtt.is_null() && !dvdsub.is_null());
+            assert!(super::check_subtitle_conversion(AVCodecID::AV_CODEC_ID_ASS, webvtt).is_ok());
+            assert!(matches!(
+                super::check_subtitle_conversion(AVCodecID::AV_CODEC_ID_HDMV_PGS_SUBTITLE, webvtt),
+                Err(Error::OpenOutput(OpenOutputError::BitmapSubtitleToText(_, _)))
+            ));
+            assert!(matches!(
+                super::check_subtitle_conversion(AVCodecID::AV_CODEC_ID_SUBRIP, dvdsub),
+                Err(Error::OpenOutput(OpenOutputError::TextSubtitleToBitmap(_, _)))
+            ));
+        }
+    }
+
     #[test]
     fn test_builder() {
         let _ = env_logger::builder()
@@ -2759,6 +4491,19 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_ignore_all_streams() {
+        let result = FfmpegContext::builder()
+            .input(Input::from("test.mp4").ignore_video().ignore_audio())
+            .output("output.mp4")
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(Error::OpenInputStream(OpenInputError::NoStreamsSelected(_)))
+        ));
+    }
+
     #[test]
     fn test_strtol() {
         let input = "-123---abc";
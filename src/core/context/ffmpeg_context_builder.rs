@@ -1,7 +1,10 @@
 use crate::core::context::input::Input;
 use crate::core::context::output::Output;
-use crate::core::context::ffmpeg_context::FfmpegContext;
+use crate::core::context::ffmpeg_context::{validate_filter_desc, FfmpegContext};
 use crate::core::context::filter_complex::FilterComplex;
+use crate::core::size_estimate::{estimate_builder_output_size, DEFAULT_CONTAINER_OVERHEAD};
+use crate::error::Error;
+use std::collections::HashMap;
 
 /// A builder for constructing [`FfmpegContext`] objects with customized inputs,
 /// outputs, and filter configurations. Typically, you will start by calling
@@ -32,6 +35,8 @@ pub struct FfmpegContextBuilder {
     filter_descs: Vec<FilterComplex>,
     outputs: Vec<Output>,
     copy_ts: bool,
+    deterministic: bool,
+    audio_sync_offset_us: Option<i64>,
 }
 
 impl FfmpegContextBuilder {
@@ -51,6 +56,8 @@ impl FfmpegContextBuilder {
             filter_descs: vec![],
             outputs: vec![],
             copy_ts: false,
+            deterministic: false,
+            audio_sync_offset_us: None,
         }
     }
 
@@ -287,6 +294,213 @@ impl FfmpegContextBuilder {
         self
     }
 
+    /// Makes the output byte-identical across runs with the same inputs and settings,
+    /// e.g. for snapshot tests of transcoded files.
+    ///
+    /// When enabled, every decoder and encoder runs with `threads=1` and `flags=+bitexact`,
+    /// and every muxer with `fflags=+bitexact`, like FFmpeg's `-threads 1 -bitexact`.
+    /// This keeps the result independent of thread scheduling and stops the muxers from
+    /// writing anything tied to the library version or the wall clock (such as the
+    /// `encoder` tag or a creation time). Options set explicitly on an input or output
+    /// are kept; the flags are added to any `flags`/`fflags` value already set.
+    ///
+    /// `bitexact` is honored by FFmpeg's native codecs (e.g. `mpeg4`, `mjpeg`, `aac`,
+    /// `flac`, `png`, `ffv1`) and muxers (e.g. `mp4`/`mov`, `matroska`, `ogg`, `avi`).
+    /// External encoders ignore it but are deterministic once single-threaded, e.g.
+    /// `libx264` and `libx265`; `libvpx` and `libaom` are only deterministic for the same
+    /// build and CPU, as they pick SIMD code paths at run time. Encoding is noticeably
+    /// slower without threads.
+    ///
+    /// # Example
+    /// ```rust
+    /// let context = FfmpegContextBuilder::new()
+    ///     .input("input.mp4")
+    ///     .output("snapshot.mp4")
+    ///     .set_deterministic(true)
+    ///     .build()
+    ///     .expect("Failed to build FfmpegContext");
+    /// ```
+    pub fn set_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Shifts the audio of every output by `offset_us` microseconds relative to the video,
+    /// to fix a constant lip-sync error, e.g. in a capture whose audio is late.
+    ///
+    /// A positive offset delays the audio, a negative offset plays it earlier. The audio
+    /// packet timestamps are shifted just before muxing, so it works for both encoded and
+    /// stream-copied audio, and the video timestamps are left untouched.
+    ///
+    /// With a negative offset the start of the audio would end up before the start of the
+    /// output: those audio packets are dropped rather than clamped to zero, so the audio
+    /// starts at most one packet after the video. With a positive offset the audio simply
+    /// starts later; no silence is inserted, players play the video without sound until then.
+    ///
+    /// # Example
+    /// ```rust
+    /// // the audio of this capture is 120 ms late
+    /// let context = FfmpegContextBuilder::new()
+    ///     .input("capture.mp4")
+    ///     .output("fixed.mp4")
+    ///     .set_audio_sync_offset_us(-120_000)
+    ///     .build()
+    ///     .expect("Failed to build FfmpegContext");
+    /// ```
+    pub fn set_audio_sync_offset_us(mut self, offset_us: i64) -> Self {
+        self.audio_sync_offset_us = Some(offset_us);
+        self
+    }
+
+    /// Replaces the URL (e.g. the file path) of the output at `index`, in the order the
+    /// outputs were added. Typically used to give each job built from a
+    /// [`template()`](FfmpegContextBuilder::template) its own output file.
+    ///
+    /// # Panics
+    /// Panics if there is no output at `index`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let builder = FfmpegContextBuilder::new()
+    ///     .input("input.mp4")
+    ///     .output(Output::from("output.mp4").set_video_codec("libx264"))
+    ///     .set_output_url(0, "renamed.mp4");
+    /// ```
+    pub fn set_output_url(mut self, index: usize, url: impl Into<String>) -> Self {
+        let output_count = self.outputs.len();
+        let output = self
+            .outputs
+            .get_mut(index)
+            .unwrap_or_else(|| panic!("output index {index} out of range, the builder has {output_count} outputs"));
+        output.url = Some(url.into());
+        self
+    }
+
+    /// Returns a copy of this builder's configuration **without its inputs**, to run the
+    /// same transcode settings over many files.
+    ///
+    /// The filter descriptions, outputs (codecs, options, stream maps, frame pipelines...)
+    /// and flags are copied; each job then adds its own input(s) and, usually, sets its
+    /// own output URL with [`set_output_url()`](FfmpegContextBuilder::set_output_url).
+    ///
+    /// Frame filters hold per-job state, so the frame pipelines are rebuilt with fresh
+    /// instances: their filters must have been added with
+    /// [`FramePipelineBuilder::filter_factory`](crate::filter::frame_pipeline_builder::FramePipelineBuilder::filter_factory).
+    ///
+    /// # Errors
+    /// Returns `Error::TemplateUnsupported` if the configuration cannot be copied: an
+    /// output writes through callbacks, a frame filter was added as an instance instead of
+    /// with a factory, or a frame pipeline has attributes set.
+    ///
+    /// # Example
+    /// ```rust
+    /// let template = FfmpegContext::builder()
+    ///     .filter_desc("scale=1280:-2")
+    ///     .output(Output::from("").set_video_codec("libx264"));
+    ///
+    /// for file in ["a.mp4", "b.mp4"] {
+    ///     template
+    ///         .template()?
+    ///         .input(file)
+    ///         .set_output_url(0, format!("scaled_{file}"))
+    ///         .build()?
+    ///         .start()?
+    ///         .wait()?;
+    /// }
+    /// ```
+    pub fn template(&self) -> crate::error::Result<FfmpegContextBuilder> {
+        let outputs = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(index, output)| {
+                output
+                    .try_clone()
+                    .map_err(|reason| Error::TemplateUnsupported(format!("output {index}: {reason}")))
+            })
+            .collect::<crate::error::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            independent_readrate: self.independent_readrate,
+            inputs: vec![],
+            filter_descs: self.filter_descs.clone(),
+            outputs,
+            copy_ts: self.copy_ts,
+            deterministic: self.deterministic,
+            audio_sync_offset_us: self.audio_sync_offset_us,
+        })
+    }
+
+    /// Checks the filter descriptions added so far without opening any input or output.
+    ///
+    /// Each description is parsed and its filters are created and initialized in a
+    /// throwaway graph, so syntax errors, unknown filters and invalid options are reported
+    /// before [`build()`](FfmpegContextBuilder::build) opens the inputs and outputs.
+    ///
+    /// # Errors
+    /// Returns the first [`FilterGraphParseError`](crate::error::FilterGraphParseError) found,
+    /// naming the offending filter (and option) when it is known, e.g.
+    /// `FilterNotFound("nosuchfilter")`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let builder = FfmpegContext::builder()
+    ///     .input("input.mp4")
+    ///     .filter_desc("hue=s=0,scale=640:-2")
+    ///     .output("output.mp4");
+    /// builder.validate_filters().expect("Invalid filter description");
+    /// let context = builder.build().unwrap();
+    /// ```
+    pub fn validate_filters(&self) -> crate::error::Result<()> {
+        for filter_complex in &self.filter_descs {
+            validate_filter_desc(&filter_complex.filter_descs)?;
+        }
+        Ok(())
+    }
+
+    /// Estimates the size in bytes of each output, in the order they were added, before
+    /// anything is encoded, e.g. to show "~45 MB" or check a quota.
+    ///
+    /// The estimate uses the bitrates set with the `b` option of the video and audio encoders
+    /// ([`Output::set_video_codec_opt`] / [`Output::set_audio_codec_opt`]) and the duration
+    /// of the output: its recording or stop time, or else the longest input, probed from the
+    /// files (respecting their start, recording and stop times). The
+    /// [default container overhead](crate::core::size_estimate::DEFAULT_CONTAINER_OVERHEAD)
+    /// is added on top.
+    ///
+    /// This is an **estimate**, see [`estimate_output_size`](crate::core::size_estimate::estimate_output_size).
+    /// Streams without a configured bitrate are not counted. An output gets `None` when no
+    /// bitrate is configured at all, or its duration cannot be told (an input read through a
+    /// callback, a live stream).
+    ///
+    /// # Errors
+    /// Returns an error if an input needed for the duration cannot be opened.
+    ///
+    /// # Example
+    /// ```rust
+    /// let builder = FfmpegContext::builder()
+    ///     .input("input.mp4")
+    ///     .output(Output::from("output.mp4")
+    ///         .set_video_codec_opt("b", "2M")
+    ///         .set_audio_codec_opt("b", "128k"));
+    /// if let Some(bytes) = builder.estimated_size()?[0] {
+    ///     println!("~{} MB", bytes / 1_000_000);
+    /// }
+    /// let context = builder.build()?;
+    /// ```
+    pub fn estimated_size(&self) -> crate::error::Result<Vec<Option<u64>>> {
+        self.estimated_size_with_overhead(DEFAULT_CONTAINER_OVERHEAD)
+    }
+
+    /// The same as [`estimated_size`](FfmpegContextBuilder::estimated_size), with the container
+    /// overhead given as a factor applied to the size of the streams, e.g. `1.08` for MPEG-TS.
+    pub fn estimated_size_with_overhead(&self, container_overhead: f64) -> crate::error::Result<Vec<Option<u64>>> {
+        self.outputs
+            .iter()
+            .map(|output| estimate_builder_output_size(&self.inputs, output, container_overhead))
+            .collect()
+    }
+
     /// Finalizes this builder, creating an [`FfmpegContext`] which can then be used
     /// to run FFmpeg jobs via [`FfmpegContext::start()`](FfmpegContext::start) or by constructing an
     /// [`FfmpegScheduler`](crate::FfmpegScheduler) yourself.
@@ -309,7 +523,40 @@ impl FfmpegContextBuilder {
     /// let scheduler = context.start().expect("Failed to start FFmpeg job");
     /// scheduler.wait().unwrap();
     /// ```
-    pub fn build(self) -> crate::error::Result<FfmpegContext> {
+    pub fn build(mut self) -> crate::error::Result<FfmpegContext> {
+        if self.deterministic {
+            for input in &mut self.inputs {
+                let decoder_opts = input.decoder_opts.get_or_insert_with(HashMap::new);
+                decoder_opts.entry("threads".to_string()).or_insert_with(|| "1".to_string());
+                add_flag(decoder_opts, "flags", "+bitexact");
+            }
+            for output in &mut self.outputs {
+                let encoder_opts = output.encoder_opts.get_or_insert_with(HashMap::new);
+                encoder_opts.entry("threads".to_string()).or_insert_with(|| "1".to_string());
+                add_flag(encoder_opts, "flags", "+bitexact");
+                // per-media-type options replace the output-wide ones, so their flags need it too
+                for codec_opts in [
+                    &mut output.video_codec_opts,
+                    &mut output.audio_codec_opts,
+                    &mut output.subtitle_codec_opts,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    if codec_opts.contains_key("flags") {
+                        add_flag(codec_opts, "flags", "+bitexact");
+                    }
+                }
+                add_flag(output.format_opts.get_or_insert_with(HashMap::new), "fflags", "+bitexact");
+            }
+        }
+
+        if let Some(audio_sync_offset_us) = self.audio_sync_offset_us {
+            for output in &mut self.outputs {
+                output.audio_sync_offset_us = Some(audio_sync_offset_us);
+            }
+        }
+
         FfmpegContext::new_with_options(
             self.independent_readrate,
             self.inputs,
@@ -319,3 +566,11 @@ impl FfmpegContextBuilder {
         )
     }
 }
+
+/// Adds `flag` (e.g. `+bitexact`) to the flags option `key`, keeping the flags already set.
+fn add_flag(opts: &mut HashMap<String, String>, key: &str, flag: &str) {
+    let flags = opts.entry(key.to_string()).or_default();
+    if !flags.contains(flag) {
+        flags.push_str(flag);
+    }
+}
@@ -1,7 +1,8 @@
+#[derive(Clone)]
 pub struct FilterComplex {
     pub(crate) filter_descs: String,
     pub(crate) hw_device: Option<String>,
-
+    pub(crate) inputs: Vec<(String, String)>,
 }
 
 impl FilterComplex {
@@ -28,16 +29,41 @@ impl FilterComplex {
         self.hw_device = Some(hw_device.into());
         self
     }
+
+    /// Feeds an input stream into a labeled input pad of this filter complex.
+    ///
+    /// Lets the filter description name its inputs by role (e.g. `[main]`, `[logo]`)
+    /// instead of hard-coding stream specifiers like `[0:v]` in the string. Once any
+    /// input is added, every input pad of the graph must be assigned exactly once,
+    /// otherwise building the context fails.
+    ///
+    /// # Parameters
+    /// * `stream_specifier` - The input stream, e.g. `"0:v"` or `"1:a:0"`.
+    /// * `pad_label` - The label of the input pad in the filter description, without brackets.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ez_ffmpeg::core::context::filter_complex::FilterComplex;
+    ///
+    /// // Overlay the video of the second input onto the first one
+    /// let fc = FilterComplex::from("[main][logo]overlay=10:10")
+    ///     .add_input("0:v", "main")
+    ///     .add_input("1:v", "logo");
+    /// ```
+    pub fn add_input(mut self, stream_specifier: impl Into<String>, pad_label: impl Into<String>) -> Self {
+        self.inputs.push((stream_specifier.into(), pad_label.into()));
+        self
+    }
 }
 
 impl From<String> for FilterComplex {
     fn from(filter_descs: String) -> Self {
-        Self { filter_descs, hw_device: None }
+        Self { filter_descs, hw_device: None, inputs: vec![] }
     }
 }
 
 impl From<&str> for FilterComplex {
     fn from(filter_descs: &str) -> Self {
-        Self { filter_descs: filter_descs.to_string(), hw_device: None }
+        Self { filter_descs: filter_descs.to_string(), hw_device: None, inputs: vec![] }
     }
 }
\ No newline at end of file
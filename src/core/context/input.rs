@@ -1,5 +1,7 @@
 use std::collections::HashMap;
+use std::time::Duration;
 use crate::filter::frame_pipeline::FramePipeline;
+use crate::util::ffmpeg_utils::duration_to_us;
 
 unsafe impl Send for Input {}
 
@@ -189,6 +191,9 @@ pub struct Input {
 
     pub(crate) exit_on_error: Option<bool>,
 
+    /// How the decoders of this input deal with data they cannot decode.
+    pub(crate) error_resilience: Option<ErrorResilience>,
+
     /// read input at specified rate.
     /// when set 1. read input at native frame rate.
     pub(crate) readrate: Option<f32>,
@@ -196,6 +201,47 @@ pub struct Input {
     pub(crate) recording_time_us: Option<i64>,
     pub(crate) stop_time_us: Option<i64>,
 
+    /// Whether frames between the seek keyframe and `start_time_us` are decoded and dropped.
+    /// Defaults to `true` (FFmpeg's `-accurate_seek`).
+    pub(crate) accurate_seek: Option<bool>,
+
+    /// Whether the video decoders only decode keyframes (`-skip_frame nokey`).
+    pub(crate) keyframes_only: Option<bool>,
+
+    /// Whether the streams of each type are left out of the pipeline (`-vn`, `-an`, `-sn`).
+    pub(crate) ignore_video: Option<bool>,
+    pub(crate) ignore_audio: Option<bool>,
+    pub(crate) ignore_subtitles: Option<bool>,
+
+    /// Whether anamorphic video is scaled to square pixels before the filter graph.
+    pub(crate) normalize_sar: Option<bool>,
+
+    /// Start position expressed as a percentage (0..=100) of the input duration.
+    /// Resolved into `start_time_us` once the input has been probed.
+    pub(crate) start_percent: Option<f32>,
+    /// Start position expressed as a video frame number.
+    /// Resolved into `start_time_us` using the frame rate of the best video stream.
+    pub(crate) start_frame: Option<u64>,
+
+    /// Maximum duration (in microseconds) FFmpeg spends analyzing the input
+    /// while probing streams (`analyzeduration`).
+    pub(crate) analyze_duration_us: Option<i64>,
+    /// Maximum number of bytes FFmpeg reads while probing the input (`probesize`).
+    pub(crate) probe_size: Option<i64>,
+
+    /// Maximum time opening the input and probing its streams may take.
+    pub(crate) open_timeout: Option<Duration>,
+    /// Maximum time reading the input may go without receiving a packet.
+    pub(crate) read_timeout: Option<Duration>,
+    /// How many times a transient read error is retried, and the delay before the first retry.
+    pub(crate) read_retry: Option<(u32, Duration)>,
+    /// Corrects packets whose dts does not increase, see [`Input::set_fix_timestamps`].
+    pub(crate) fix_timestamps: Option<bool>,
+
+    /// Size of the canvas bitmap subtitles are rendered on when they are fed
+    /// into a filter graph (sub2video), FFmpeg's `-canvas_size`.
+    pub(crate) canvas_size: Option<(u32, u32)>,
+
     /// set number of times input stream shall be looped
     pub(crate) stream_loop: Option<i32>,
 
@@ -206,6 +252,8 @@ pub struct Input {
     pub(crate) hwaccel_device: Option<String>,
     /// select output format used with HW accelerated decoding
     pub(crate) hwaccel_output_format: Option<String>,
+    /// Size the decoded video is scaled to, on the GPU when possible, see [`Input::set_hw_scale`].
+    pub(crate) hw_scale: Option<(i32, i32)>,
 
     /// The input format options for the demuxer.
     ///
@@ -219,6 +267,29 @@ pub struct Input {
     /// These options are used when initializing the FFmpeg input format, allowing you to
     /// fine-tune or override default demuxer behavior.
     pub(crate) format_opts: Option<HashMap<String, String>>,
+
+    /// Options passed to every decoder opened for this input, including the
+    /// decoder-private ones (e.g. `lowres=1`, `skip_loop_filter=all`).
+    pub(crate) decoder_opts: Option<HashMap<String, String>>,
+}
+
+/// How the decoders of an [`Input`] react to packets they cannot decode.
+///
+/// Only applies while `exit_on_error` is off: with [`Input::set_exit_on_error`] set to
+/// `true`, the first error stops the job whatever the level.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ErrorResilience {
+    /// The first decoding error or corrupt frame stops the job. Decoders also verify
+    /// CRCs and bitstream conformance where they can (`err_detect`).
+    Strict,
+    /// Undecodable packets are skipped and counted. The job fails once it ends if more
+    /// than two thirds of the packets of a stream could not be decoded, like FFmpeg's
+    /// default `-max_error_rate`. This is the default.
+    #[default]
+    Normal,
+    /// Undecodable packets are skipped and counted, decoders conceal damaged areas as
+    /// well as they can, and the error rate never fails the job.
+    Maximum,
 }
 
 impl Input {
@@ -226,6 +297,100 @@ impl Input {
         url.into().into()
     }
 
+    /// Creates an `Input` capturing from a device, e.g. a screen, a camera or a microphone.
+    ///
+    /// The input format (capture backend) is set explicitly, as FFmpeg cannot probe devices,
+    /// and `device` is passed to it as the URL. Typical combinations are:
+    /// - Linux: `("x11grab", ":0.0+0,0")` for the screen, `("v4l2", "/dev/video0")` for a camera,
+    ///   `("alsa", "default")` or `("pulse", "default")` for audio.
+    /// - macOS: `("avfoundation", "1:0")` (`"<video>:<audio>"` device indexes).
+    /// - Windows: `("dshow", "video=Integrated Camera")` or `("gdigrab", "desktop")`.
+    ///
+    /// Opening the input fails with `OpenInputError::DeviceFormatUnavailable`, listing the
+    /// available backends, when `format` is not compiled into FFmpeg. Use
+    /// [`list_devices`](crate::device::list_devices) to enumerate the devices of a backend.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let input = Input::from_device("x11grab", ":0.0")
+    ///     .set_device_framerate(30.0)
+    ///     .set_device_video_size(1920, 1080);
+    /// ```
+    pub fn from_device(format: &str, device: &str) -> Self {
+        Self::new(device).set_format(format)
+    }
+
+    /// Sets the capture frame rate of a device input (the `framerate` format option).
+    ///
+    /// ### Parameters:
+    /// - `framerate`: Frames per second, e.g. `30.0` or `29.97`.
+    ///
+    /// ### Return Value:
+    /// - Returns the modified `Input` instance for chaining.
+    pub fn set_device_framerate(self, framerate: f64) -> Self {
+        self.set_format_opt("framerate", framerate.to_string())
+    }
+
+    /// Sets the capture size of a device input (the `video_size` format option),
+    /// e.g. the grabbed area for `x11grab` or the camera resolution for `v4l2`.
+    ///
+    /// ### Parameters:
+    /// - `width`, `height`: The size in pixels.
+    ///
+    /// ### Return Value:
+    /// - Returns the modified `Input` instance for chaining.
+    pub fn set_device_video_size(self, width: u32, height: u32) -> Self {
+        self.set_format_opt("video_size", format!("{width}x{height}"))
+    }
+
+    /// Creates an `Input` reading the files of an FFmpeg concat list one after the other,
+    /// as a single input (the `concat` demuxer).
+    ///
+    /// The list names one file per `file` directive, e.g. `file 'intro.mp4'`, and may set
+    /// `duration`, `inpoint` and `outpoint` for each of them. Relative paths are resolved
+    /// against the directory of the list, not the working directory. All files should have
+    /// the same streams, with the same codecs, time bases and sizes.
+    ///
+    /// The `safe` option is turned off, so absolute paths and URLs are accepted in the list;
+    /// use [`set_concat_safe`](Input::set_concat_safe) to only allow plain relative paths.
+    /// Network protocols are added to the protocol whitelist, so the list may reference
+    /// remote files, unless `protocol_whitelist` is set with [`set_format_opt`](Input::set_format_opt).
+    ///
+    /// Opening the input fails with `OpenInputError::PlaylistEntryNotFound` when a local file
+    /// of the list does not exist, instead of stopping the job once the demuxer reaches it.
+    ///
+    /// `.m3u8` HLS playlists need no special constructor: `Input::from("playlist.m3u8")`
+    /// selects the `hls` demuxer, with the same protocol whitelist and missing segment check.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// // list.txt:
+    /// // file 'episode_part1.mp4'
+    /// // file 'episode_part2.mp4'
+    /// let input = Input::from_concat_list("list.txt");
+    /// ```
+    pub fn from_concat_list(list: impl Into<String>) -> Self {
+        Self::new(list).set_format("concat").set_concat_safe(false)
+    }
+
+    /// Sets the `safe` option of the `concat` demuxer.
+    ///
+    /// When `true`, the concat list may only reference relative paths made of plain
+    /// characters, and opening the input fails with an invalid data error on an absolute
+    /// path, a URL or a path with special characters. This is FFmpeg's default and is
+    /// useful when the list comes from an untrusted source.
+    ///
+    /// [`from_concat_list`](Input::from_concat_list) sets it to `false`.
+    ///
+    /// ### Parameters:
+    /// - `safe`: Whether to reject unsafe file names.
+    ///
+    /// ### Return Value:
+    /// - Returns the modified `Input` instance for chaining.
+    pub fn set_concat_safe(self, safe: bool) -> Self {
+        self.set_format_opt("safe", if safe { "1" } else { "0" })
+    }
+
     /// Creates a new `Input` instance with a custom read callback.
     ///
     /// This method initializes an `Input` object that uses a provided `read_callback` function
@@ -581,6 +746,26 @@ impl Input {
         self
     }
 
+    /// Sets how the decoders of this input react to packets they cannot decode.
+    ///
+    /// Defaults to [`ErrorResilience::Normal`]: undecodable packets are skipped and the
+    /// job only fails if most of a stream is undecodable. Use [`ErrorResilience::Maximum`]
+    /// to get as much as possible out of a damaged recording, or [`ErrorResilience::Strict`]
+    /// to stop at the first error.
+    ///
+    /// The number of packets skipped so far is available from
+    /// [`FfmpegScheduler::decode_errors`](crate::FfmpegScheduler::decode_errors).
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("damaged.mp4")
+    ///     .set_error_resilience(ErrorResilience::Maximum);
+    /// ```
+    pub fn set_error_resilience(mut self, level: ErrorResilience) -> Self {
+        self.error_resilience = Some(level);
+        self
+    }
+
     /// Sets a **read rate** for this input, controlling how quickly frames are read.
     ///
     /// - If set to `1.0`, frames are read at their native frame rate.
@@ -621,13 +806,30 @@ impl Input {
     /// ```
     pub fn set_start_time_us(mut self, start_time_us: i64) -> Self {
         self.start_time_us = Some(start_time_us);
+        self.start_percent = None;
+        self.start_frame = None;
         self
     }
 
+    /// Sets the **start time** from which to begin reading.
+    ///
+    /// The same as [`set_start_time_us`](Self::set_start_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_start_time(Duration::from_secs(2));
+    /// ```
+    pub fn set_start_time(self, duration: Duration) -> Self {
+        self.set_start_time_us(duration_to_us(duration))
+    }
+
     /// Sets the **recording time** (in microseconds) for this input.
     ///
     /// FFmpeg will only read for the specified duration, ignoring data past this
     /// limit. This can be used to trim or limit how much of the input is processed.
+    /// Reading stops once every audio and video stream used by the outputs is past it.
     ///
     /// # Parameters
     /// - `recording_time_us`: The number of microseconds to read from the input.
@@ -645,6 +847,20 @@ impl Input {
         self
     }
 
+    /// Sets the **recording time** for this input.
+    ///
+    /// The same as [`set_recording_time_us`](Self::set_recording_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_recording_time(Duration::from_secs(5));
+    /// ```
+    pub fn set_recording_time(self, duration: Duration) -> Self {
+        self.set_recording_time_us(duration_to_us(duration))
+    }
+
     /// Sets a **stop time** (in microseconds) beyond which input data will be ignored.
     ///
     /// This is similar to [`set_recording_time_us`](Self::set_recording_time_us) but
@@ -667,6 +883,206 @@ impl Input {
         self
     }
 
+    /// Sets a **stop time** beyond which input data will be ignored.
+    ///
+    /// The same as [`set_stop_time_us`](Self::set_stop_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_stop_time(Duration::from_secs(10));
+    /// ```
+    pub fn set_stop_time(self, duration: Duration) -> Self {
+        self.set_stop_time_us(duration_to_us(duration))
+    }
+
+    /// Controls frame-accurate seeking when a start time is set (FFmpeg's `-accurate_seek`).
+    ///
+    /// Seeking in the demuxer can only land on a keyframe, which is usually before the
+    /// requested start time. When `accurate_seek` is `true` (the default), the decoded
+    /// frames between that keyframe and the requested start time are discarded, so the
+    /// output starts exactly at `start_time_us`. Audio is trimmed to the same position,
+    /// keeping both streams in sync.
+    ///
+    /// Decoding and dropping those frames costs CPU time proportional to the distance
+    /// to the previous keyframe (up to a full GOP). Set it to `false` to start at the
+    /// keyframe instead, which is faster but less precise.
+    ///
+    /// This only applies to streams that are decoded; stream-copied streams always
+    /// start on a keyframe.
+    ///
+    /// # Parameters
+    /// - `accurate_seek`: `true` to decode and drop frames up to the exact start time.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_start_time_us(12_345_000)
+    ///     .set_accurate_seek(true);
+    /// ```
+    pub fn set_accurate_seek(mut self, accurate_seek: bool) -> Self {
+        self.accurate_seek = Some(accurate_seek);
+        self
+    }
+
+    /// Decodes only the keyframes of the video streams (FFmpeg's `-skip_frame nokey`).
+    ///
+    /// The video decoders skip every non-keyframe, which makes extracting thumbnails or
+    /// building preview grids and sprite sheets much cheaper than decoding the whole stream.
+    /// The decoded frames keep their original, increasing timestamps, with gaps where frames
+    /// were skipped; `filter_desc` filters such as `scale` work as usual on them.
+    ///
+    /// Outputs with a constant frame rate (the default for most containers) fill those gaps
+    /// by duplicating frames, use `VSyncMethod::VsyncVfr` or `VsyncPassthrough` on the output
+    /// to keep one output frame per keyframe.
+    ///
+    /// Audio, subtitles and stream-copied streams are not affected.
+    ///
+    /// # Parameters
+    /// - `keyframes_only`: `true` to decode keyframes only.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("movie.mp4")
+    ///     .set_keyframes_only(true);
+    /// let output = Output::from("preview_%04d.jpg")
+    ///     .set_vsync_method(VSyncMethod::VsyncVfr);
+    /// ```
+    pub fn set_keyframes_only(mut self, keyframes_only: bool) -> Self {
+        self.keyframes_only = Some(keyframes_only);
+        self
+    }
+
+    /// Ignores the **video** streams of this input (FFmpeg's input `-vn`).
+    ///
+    /// Ignored streams are discarded by the demuxer and never selected for an output or
+    /// a filter graph, so no video decoder is opened, e.g. when only the audio of a movie
+    /// is extracted. Mapping an ignored stream explicitly is an error.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("movie.mp4")
+    ///     .ignore_video();
+    /// let output = Output::from("audio.m4a");
+    /// ```
+    pub fn ignore_video(mut self) -> Self {
+        self.ignore_video = Some(true);
+        self
+    }
+
+    /// Ignores the **audio** streams of this input (FFmpeg's input `-an`).
+    ///
+    /// See [`ignore_video`](Self::ignore_video) for details.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    pub fn ignore_audio(mut self) -> Self {
+        self.ignore_audio = Some(true);
+        self
+    }
+
+    /// Ignores the **subtitle** streams of this input (FFmpeg's input `-sn`).
+    ///
+    /// See [`ignore_video`](Self::ignore_video) for details.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    pub fn ignore_subtitles(mut self) -> Self {
+        self.ignore_subtitles = Some(true);
+        self
+    }
+
+    /// Scales anamorphic video to **square pixels** before it enters the filter graph.
+    ///
+    /// Streams with a non-square sample aspect ratio (e.g. 16:9 DVDs stored as 720x576 with
+    /// a 64:45 SAR) are resized horizontally to their display width, and their SAR is set
+    /// to 1:1, so `filter_desc` filters and output [`FrameFilter`](crate::filter::frame_filter::FrameFilter)s
+    /// see correctly proportioned pixels. Input frame pipelines run before this step; they can
+    /// use `FrameFilterContext::sample_aspect_ratio` instead.
+    ///
+    /// Video with square pixels or an unknown SAR is left as is. Stream-copied streams are not affected.
+    ///
+    /// # Parameters
+    /// - `normalize_sar`: `true` to scale to square pixels.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("dvd.vob")
+    ///     .set_normalize_sar(true);
+    /// ```
+    pub fn set_normalize_sar(mut self, normalize_sar: bool) -> Self {
+        self.normalize_sar = Some(normalize_sar);
+        self
+    }
+
+    /// Sets the **start position** as a percentage of the input duration.
+    ///
+    /// The percentage is converted to `start_time_us` after the input has been opened,
+    /// using the same duration reported by [`get_duration_us`](crate::container_info::get_duration_us).
+    /// This is handy when you want to jump to e.g. the middle of a file without knowing
+    /// its duration upfront. Overrides any previously set start time or start frame.
+    ///
+    /// Opening the input fails if `percent` is outside `0..=100` or if the input
+    /// duration is unknown (e.g. live streams).
+    ///
+    /// # Parameters
+    /// - `percent`: The start position, from `0.0` (beginning) to `100.0` (end).
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_start_percent(50.0); // Start in the middle
+    /// ```
+    pub fn set_start_percent(mut self, percent: f32) -> Self {
+        self.start_percent = Some(percent);
+        self.start_frame = None;
+        self.start_time_us = None;
+        self
+    }
+
+    /// Sets the **start position** as a video frame number.
+    ///
+    /// The frame number is converted to `start_time_us` after the input has been opened,
+    /// using the average frame rate of the best video stream. Overrides any previously
+    /// set start time or start percentage.
+    ///
+    /// Opening the input fails if the input has no video stream with a known frame rate,
+    /// or if `frame` is beyond the estimated number of frames.
+    ///
+    /// # Parameters
+    /// - `frame`: The zero-based index of the frame to start from.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("long_clip.mp4")
+    ///     .set_start_frame(1200); // Start at frame 1200
+    /// ```
+    pub fn set_start_frame(mut self, frame: u64) -> Self {
+        self.start_frame = Some(frame);
+        self.start_percent = None;
+        self.start_time_us = None;
+        self
+    }
+
     /// Sets the number of **loops** to perform on this input stream.
     ///
     /// If FFmpeg reaches the end of the input, it can loop back and start from the
@@ -689,6 +1105,209 @@ impl Input {
         self
     }
 
+    /// Limits how long (in microseconds) FFmpeg analyzes the input while probing streams.
+    ///
+    /// This maps to FFmpeg's `analyzeduration` option and is applied to the format context
+    /// before `avformat_find_stream_info`. Lowering it speeds up startup for slow network
+    /// inputs (e.g. RTSP cameras).
+    ///
+    /// **Warning:** if set too low, FFmpeg may not see every stream or may fail to determine
+    /// stream parameters (frame rate, sample rate, ...), so some streams can be missing or
+    /// incompletely described. Negative values are rejected when the input is opened.
+    ///
+    /// # Parameters
+    /// - `analyze_duration_us`: The maximum analysis duration, in microseconds.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("rtsp://camera.local/stream")
+    ///     .set_analyze_duration_us(500_000); // Analyze at most 0.5 seconds
+    /// ```
+    pub fn set_analyze_duration_us(mut self, analyze_duration_us: i64) -> Self {
+        self.analyze_duration_us = Some(analyze_duration_us);
+        self
+    }
+
+    /// Limits how long FFmpeg analyzes the input to detect its streams.
+    ///
+    /// The same as [`set_analyze_duration_us`](Self::set_analyze_duration_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("rtsp://camera.local/stream")
+    ///     .set_analyze_duration(Duration::from_millis(500));
+    /// ```
+    pub fn set_analyze_duration(self, duration: Duration) -> Self {
+        self.set_analyze_duration_us(duration_to_us(duration))
+    }
+
+    /// Limits how many bytes FFmpeg reads from the input while probing streams.
+    ///
+    /// This maps to FFmpeg's `probesize` option and is applied to the format context
+    /// before `avformat_find_stream_info`. Lowering it speeds up startup for slow network
+    /// inputs.
+    ///
+    /// **Warning:** if set too low, FFmpeg may not detect every stream or may fail to
+    /// determine their parameters. Negative values are rejected when the input is opened.
+    ///
+    /// # Parameters
+    /// - `probe_size`: The maximum number of bytes to probe.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("rtsp://camera.local/stream")
+    ///     .set_probe_size(32 * 1024);
+    /// ```
+    pub fn set_probe_size(mut self, probe_size: i64) -> Self {
+        self.probe_size = Some(probe_size);
+        self
+    }
+
+    /// Limits how long opening the input may take, including probing its streams.
+    ///
+    /// Opening an unreachable network source (`rtmp://`, `rtsp://`, `http://`...) can
+    /// otherwise block forever. When the deadline passes, FFmpeg's blocking calls are
+    /// interrupted and building the context fails with [`Error::InputTimeout`](crate::error::Error::InputTimeout).
+    ///
+    /// Only applies to inputs opened from a URL, not to [`Input::new_by_read_callback`].
+    ///
+    /// # Parameters
+    /// - `timeout`: The maximum time to wait for the input to open.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("rtmp://camera.local/live/stream")
+    ///     .set_open_timeout(Duration::from_secs(5));
+    /// ```
+    pub fn set_open_timeout(mut self, timeout: Duration) -> Self {
+        self.open_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how long reading the input may go without receiving any data.
+    ///
+    /// Sets FFmpeg's `rw_timeout` protocol option (unless set with [`Input::set_format_opt`])
+    /// and interrupts a read that blocks longer than `timeout`, e.g. when a camera stops
+    /// sending. The job then fails with [`Error::InputTimeout`](crate::error::Error::InputTimeout).
+    ///
+    /// Only applies to inputs opened from a URL, not to [`Input::new_by_read_callback`].
+    ///
+    /// # Parameters
+    /// - `timeout`: The maximum time to wait for the next packet.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("rtsp://camera.local/stream")
+    ///     .set_open_timeout(Duration::from_secs(5))
+    ///     .set_read_timeout(Duration::from_secs(10));
+    /// ```
+    pub fn set_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Retries reading the input when it fails with a transient error, instead of giving up.
+    ///
+    /// Network inputs occasionally fail a read with an I/O error, a timeout, a reset
+    /// connection or an HTTP 5xx status. With a retry set, such a read is attempted again
+    /// up to `max_retries` times in a row, waiting `backoff` before the first retry and
+    /// twice as long before each following one. A successful read resets the count.
+    ///
+    /// Other errors, such as the end of the input or invalid data, are not retried and go
+    /// through the usual error handling (see [`Input::set_exit_on_error`]). Once the retries
+    /// are exhausted, the last error is handled the same way.
+    ///
+    /// # Parameters
+    /// - `max_retries`: The maximum number of consecutive retries.
+    /// - `backoff`: The delay before the first retry.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// // retry after 0.5, 1, 2, 4 and 8 seconds
+    /// let input = Input::from("https://media.example.com/live.ts")
+    ///     .set_read_retry(5, Duration::from_millis(500));
+    /// ```
+    pub fn set_read_retry(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.read_retry = Some((max_retries, backoff));
+        self
+    }
+
+    /// Corrects packets whose decoding timestamp (dts) does not increase, as found in
+    /// glitchy captures, instead of letting the muxer fail on them.
+    ///
+    /// A packet whose dts is not past the dts of the previous packet of its stream is moved
+    /// to just after it: its new dts is the previous one plus the packet duration, or one
+    /// frame at the stream frame rate, or one audio frame at its sample rate, and its pts is
+    /// moved by the same amount. A packet for which none of these is known is dropped.
+    ///
+    /// Only the offending packets are moved, the following ones keep their timestamps, so
+    /// the streams of the input do not drift apart: a short glitch costs at most the packets
+    /// it spans. Large jumps of formats with discontinuous timestamps (MPEG-TS) are already
+    /// corrected for all streams at once, with or without this option. The number of
+    /// corrected and dropped packets of each stream is logged when the input ends.
+    ///
+    /// # Parameters
+    /// - `fix_timestamps`: `true` to correct non-monotonic timestamps (default `false`).
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("capture.ts")
+    ///     .set_fix_timestamps(true);
+    /// ```
+    pub fn set_fix_timestamps(mut self, fix_timestamps: bool) -> Self {
+        self.fix_timestamps = Some(fix_timestamps);
+        self
+    }
+
+    /// Sets the canvas size used to render bitmap subtitles (PGS, DVB, DVD) as video.
+    ///
+    /// When a subtitle stream of this input is used as a video filter input, e.g.
+    /// `"[0:v][0:s]overlay"` or `"[0:v][0:s:1]overlay"` to pick the second subtitle stream,
+    /// each subtitle is drawn onto a transparent canvas (sub2video) and repeated on every
+    /// video frame until its end time. By default the canvas takes the size of the subtitle
+    /// stream, falling back to the largest video stream of the input and then to 720x576.
+    /// This maps to FFmpeg's `-canvas_size` option.
+    ///
+    /// # Parameters
+    /// - `width`: The canvas width in pixels.
+    /// - `height`: The canvas height in pixels.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let context = FfmpegContext::builder()
+    ///     .input(Input::from("movie.mkv").set_canvas_size(1920, 1080))
+    ///     .filter_desc("[0:v][0:s]overlay")
+    ///     .output("output.mp4")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn set_canvas_size(mut self, width: u32, height: u32) -> Self {
+        self.canvas_size = Some((width, height));
+        self
+    }
+
     /// Specifies a **hardware acceleration** name for decoding this input.
     ///
     /// Common values might include `"cuda"`, `"vaapi"`, `"dxva2"`, `"videotoolbox"`, etc.
@@ -758,15 +1377,70 @@ impl Input {
         self
     }
 
+    /// Scales the decoded video of this input to `width`x`height`, keeping hardware frames
+    /// in GPU memory.
+    ///
+    /// A `scale` filter in the filter graph only handles frames in system memory, so with
+    /// [`set_hwaccel_output_format`](Self::set_hwaccel_output_format) set to a hardware format
+    /// every frame would have to be downloaded first. With this option the frames are instead
+    /// scaled by the GPU scaler matching the output format, right after decoding:
+    ///
+    /// | `hwaccel_output_format` | scaler |
+    /// |---|---|
+    /// | `cuda` | `scale_cuda`, or `scale_npp` |
+    /// | `qsv` | `scale_qsv` |
+    /// | `vaapi` | `scale_vaapi` |
+    /// | `videotoolbox` | `scale_vt` |
+    /// | `vulkan` | `scale_vulkan` |
+    ///
+    /// Without a hardware output format, or when a frame reaches the filter graph in system
+    /// memory anyway (e.g. the decoder fell back to software decoding), the plain `scale`
+    /// filter is used. Opening the input fails with
+    /// [`OpenInputError::HwScalerUnavailable`](crate::error::OpenInputError::HwScalerUnavailable)
+    /// if the FFmpeg build has no scaler for the hardware format.
+    ///
+    /// `width` or `height` may be `-1` or `-2` to keep the aspect ratio, like for `scale`.
+    ///
+    /// # Parameters
+    /// - `width`: The width of the scaled video, in pixels.
+    /// - `height`: The height of the scaled video, in pixels.
+    ///
+    /// # Returns
+    /// * `Self` - allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let input = Input::from("video.mp4")
+    ///     .set_hwaccel("cuda")
+    ///     .set_hwaccel_output_format("cuda")
+    ///     .set_hw_scale(1280, 720);
+    /// ```
+    pub fn set_hw_scale(mut self, width: i32, height: i32) -> Self {
+        self.hw_scale = Some((width, height));
+        self
+    }
+
     /// Sets a single input format-specific option.
     ///
     /// This method allows you to configure a single key-value pair that will be passed
     /// to the FFmpeg demuxer. If the same key already exists, it will be overwritten.
     ///
+    /// The options are passed to `avformat_open_input`, so they reach the demuxer's private
+    /// options (e.g. `rtsp_transport`), the generic format context options (e.g. `fflags`,
+    /// `probesize`) and the protocol (e.g. `protocol_whitelist`, `timeout`, `user_agent`).
+    /// They only affect opening and reading the input: decoders and hardware acceleration are
+    /// configured separately, with [`set_decoder_option`](Input::set_decoder_option),
+    /// [`set_video_codec`](Input::set_video_codec) and [`set_hwaccel`](Input::set_hwaccel).
+    /// Options that nothing recognized (e.g. a typo) are logged as warnings once the input is opened.
+    ///
     /// **Example Usage:**
     /// ```rust
     /// let input = Input::new("avfoundation:0")
     ///     .set_format_opt("framerate", "30");
+    ///
+    /// // RTSP over TCP instead of UDP
+    /// let input = Input::new("rtsp://camera.local/stream")
+    ///     .set_format_opt("rtsp_transport", "tcp");
     /// ```
     ///
     /// ### Parameters:
@@ -786,6 +1460,20 @@ impl Input {
         self
     }
 
+    /// Sets a single option of the demuxer or protocol, like
+    /// [`set_format_opt`](Input::set_format_opt), e.g. `rtsp_transport`, `analyzeduration`
+    /// or the `video_size` of a raw input.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let input = Input::new("rtsp://camera.local/stream")
+    ///     .with_format_option("rtsp_transport", "tcp")
+    ///     .with_format_option("stimeout", "5000000");
+    /// ```
+    pub fn with_format_option(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set_format_opt(key, value)
+    }
+
     /// Sets multiple input format-specific options at once.
     ///
     /// This method allows setting multiple key-value pairs in a single call.
@@ -821,6 +1509,55 @@ impl Input {
         self
     }
 
+    /// Sets an option of the decoders of this input.
+    ///
+    /// The option is passed to every decoder opened for this input, before it is opened,
+    /// and can be a generic decoder option or a private option of the selected decoder.
+    /// Options not recognized by a decoder are reported with a warning.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let input = Input::new("video.mp4")
+    ///     .set_decoder_option("skip_loop_filter", "all")
+    ///     .set_decoder_option("threads", "4");
+    /// ```
+    ///
+    /// ### Parameters:
+    /// - `key`: The decoder option name (e.g., `"skip_loop_filter"`, `"threads"`).
+    /// - `value`: The value to set (e.g., `"all"`, `"4"`).
+    ///
+    /// ### Return Value:
+    /// - Returns the modified `Input` instance for chaining.
+    pub fn set_decoder_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.decoder_opts
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Decodes video at a reduced resolution (the decoder's `lowres` option).
+    ///
+    /// Each level halves the width and height: `1` decodes at 1/2, `2` at 1/4 and `3` at 1/8
+    /// of the original size. This is much faster than decoding at full size and scaling
+    /// down, but is only supported by a few decoders (e.g. MJPEG, JPEG 2000, MPEG-4 part 2).
+    ///
+    /// Opening a video decoder fails with an invalid argument error when `lowres` is above
+    /// the maximum level it supports.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let input = Input::new("camera.mjpeg")
+    ///     .set_lowres(2);
+    /// ```
+    ///
+    /// ### Parameters:
+    /// - `lowres`: The reduction level, `0` decodes at full resolution.
+    ///
+    /// ### Return Value:
+    /// - Returns the modified `Input` instance for chaining.
+    pub fn set_lowres(self, lowres: u8) -> Self {
+        self.set_decoder_option("lowres", lowres.to_string())
+    }
 }
 
 impl From<Box<dyn FnMut(&mut [u8]) -> i32>> for Input {
@@ -835,15 +1572,33 @@ impl From<Box<dyn FnMut(&mut [u8]) -> i32>> for Input {
             audio_codec: None,
             subtitle_codec: None,
             exit_on_error: None,
+            error_resilience: None,
             readrate: None,
             start_time_us: None,
             recording_time_us: None,
             stop_time_us: None,
+            accurate_seek: None,
+            keyframes_only: None,
+            ignore_video: None,
+            ignore_audio: None,
+            ignore_subtitles: None,
+            normalize_sar: None,
+            start_percent: None,
+            start_frame: None,
+            analyze_duration_us: None,
+            probe_size: None,
+            open_timeout: None,
+            read_timeout: None,
+            read_retry: None,
+            fix_timestamps: None,
+            canvas_size: None,
             stream_loop: None,
             hwaccel: None,
             hwaccel_device: None,
             hwaccel_output_format: None,
+            hw_scale: None,
             format_opts: None,
+            decoder_opts: None,
         }
     }
 }
@@ -860,15 +1615,33 @@ impl From<String> for Input {
             audio_codec: None,
             subtitle_codec: None,
             exit_on_error: None,
+            error_resilience: None,
             readrate: None,
             start_time_us: None,
             recording_time_us: None,
             stop_time_us: None,
+            accurate_seek: None,
+            keyframes_only: None,
+            ignore_video: None,
+            ignore_audio: None,
+            ignore_subtitles: None,
+            normalize_sar: None,
+            start_percent: None,
+            start_frame: None,
+            analyze_duration_us: None,
+            probe_size: None,
+            open_timeout: None,
+            read_timeout: None,
+            read_retry: None,
+            fix_timestamps: None,
+            canvas_size: None,
             stream_loop: None,
             hwaccel: None,
             hwaccel_device: None,
             hwaccel_output_format: None,
+            hw_scale: None,
             format_opts: None,
+            decoder_opts: None,
         }
     }
 }
@@ -882,7 +1655,145 @@ impl From<&str> for Input {
 
 #[cfg(test)]
 mod tests {
+    use crate::core::context::ffmpeg_context::FfmpegContext;
     use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_filter::FrameFilter;
+    use crate::core::filter::frame_filter_context::FrameFilterContext;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use ffmpeg_next::Frame;
+    use ffmpeg_sys_next::{AVMediaType, AVERROR, AVERROR_EOF, EIO};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Records the size of the video frames reaching the output.
+    struct FrameSizes(Arc<Mutex<Vec<(i32, i32)>>>);
+
+    impl FrameFilter for FrameSizes {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            if !frame.as_ptr().is_null() && !frame.is_empty() {
+                let size = unsafe { ((*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+                self.0.lock().unwrap().push(size);
+            }
+            Ok(Some(frame))
+        }
+    }
+
+    #[test]
+    fn test_hw_scale_software_fallback() {
+        // without a device, `auto` decodes in software, and `scale` scales the frames
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let result = FfmpegContext::builder()
+            .input(Input::from("test.mp4").set_hwaccel("auto").set_hw_scale(160, 90))
+            .output(
+                Output::from("-").set_format("null").add_frame_pipeline(
+                    FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                        .filter("sizes", Box::new(FrameSizes(sizes.clone()))),
+                ),
+            )
+            .build()
+            .and_then(|context| context.start())
+            .and_then(|scheduler| scheduler.wait());
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let sizes = sizes.lock().unwrap();
+        assert!(!sizes.is_empty());
+        assert!(sizes.iter().all(|&size| size == (160, 90)), "{sizes:?}");
+    }
+
+    #[test]
+    fn test_with_format_option() {
+        // a 32x16 gray frame, the raw demuxer cannot guess its size
+        std::fs::write("format_option.gray", vec![128u8; 32 * 16]).unwrap();
+        let raw_input = || Input::from("format_option.gray").set_format("rawvideo");
+
+        let result = FfmpegContext::builder()
+            .input(raw_input())
+            .output(Output::from("-").set_format("null"))
+            .build();
+        assert!(result.is_err());
+
+        let result = FfmpegContext::builder()
+            .input(
+                raw_input()
+                    .with_format_option("video_size", "32x16")
+                    .with_format_option("pixel_format", "gray"),
+            )
+            .output(Output::from("-").set_format("null"))
+            .build()
+            .and_then(|context| context.start())
+            .and_then(|scheduler| scheduler.wait());
+        std::fs::remove_file("format_option.gray").unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_read_retry() {
+        // 80 seconds of a 32x16 gray video in MPEG-TS, most of it read after the stream info
+        std::fs::write("read_retry.gray", vec![128u8; 32 * 16 * 2000]).unwrap();
+        let result = FfmpegContext::builder()
+            .input(
+                Input::from("read_retry.gray")
+                    .set_format("rawvideo")
+                    .with_format_option("video_size", "32x16")
+                    .with_format_option("pixel_format", "gray"),
+            )
+            .output("read_retry.ts")
+            .build()
+            .and_then(|context| context.start())
+            .and_then(|scheduler| scheduler.wait());
+        std::fs::remove_file("read_retry.gray").unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
+        let data = Arc::new(std::fs::read("read_retry.ts").unwrap());
+        std::fs::remove_file("read_retry.ts").unwrap();
+
+        // returns the number of failed reads, and whether the whole input was read
+        let read = |read_retry: Option<u32>| {
+            let (failures, position) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+            let (data, failed, read_to) = (data.clone(), failures.clone(), position.clone());
+            // fails 3 times in a row halfway through; reads whole TS packets, so that a failure
+            // never splits one
+            let mut input = Input::new_by_read_callback(move |buf| {
+                let pos = read_to.load(Ordering::Relaxed);
+                if pos >= data.len() / 2 && failed.load(Ordering::Relaxed) < 3 {
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    return AVERROR(EIO);
+                }
+                if pos >= data.len() {
+                    return AVERROR_EOF;
+                }
+                let len = (buf.len() / 188 * 188).min(data.len() - pos);
+                buf[..len].copy_from_slice(&data[pos..pos + len]);
+                read_to.store(pos + len, Ordering::Relaxed);
+                len as i32
+            });
+            if let Some(max_retries) = read_retry {
+                input = input.set_read_retry(max_retries, Duration::from_millis(1));
+            }
+            let result = FfmpegContext::builder()
+                .input(input)
+                .output(Output::from("-").set_format("null"))
+                .build()
+                .and_then(|context| context.start())
+                .and_then(|scheduler| scheduler.wait());
+            assert!(result.is_ok(), "{:?}", result.err());
+            (failures.load(Ordering::Relaxed), position.load(Ordering::Relaxed) == data.len())
+        };
+
+        // without retries, the input ends at the first failure
+        let (failures, read_all) = read(None);
+        assert!(failures >= 1 && !read_all, "{failures} failures");
+        // each failure is retried, then reading goes on to the end
+        assert_eq!(read(Some(8)), (3, true));
+        // the retries run out before the failures
+        let (failures, read_all) = read(Some(1));
+        assert!(failures < 3 && !read_all, "{failures} failures");
+    }
 
     #[test]
     fn test_new_by_read_callback() {
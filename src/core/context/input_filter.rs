@@ -5,6 +5,8 @@ use ffmpeg_next::Frame;
 pub(crate) struct InputFilter {
     pub(crate) linklabel: String,
     pub(crate) media_type: AVMediaType,
+    /// Type of the bound input stream, differs from `media_type` for sub2video.
+    pub(crate) type_src: AVMediaType,
     pub(crate) name: String,
     pub(crate) opts: InputFilterOptions,
 }
@@ -14,6 +16,7 @@ impl InputFilter {
         Self {
             linklabel,
             media_type,
+            type_src: media_type,
             name,
             opts: InputFilterOptions::new(fallback),
         }
@@ -27,6 +30,7 @@ pub(crate) const IFILTER_FLAG_REINIT: u32 = 1 << 1;
 pub(crate) const IFILTER_FLAG_CFR: u32 = 1 << 2;
 #[allow(dead_code)]
 pub(crate) const IFILTER_FLAG_CROP: u32 = 1 << 3;
+pub(crate) const IFILTER_FLAG_NORMALIZE_SAR: u32 = 1 << 4;
 
 pub(crate) struct InputFilterOptions {
     pub(crate) trim_start_us: Option<i64>,
@@ -48,6 +52,9 @@ pub(crate) struct InputFilterOptions {
 
     pub(crate) flags: u32,
 
+    /// Scaled size and scaler of the input video, see `Input::set_hw_scale`.
+    pub(crate) hw_scale: Option<(i32, i32, &'static str)>,
+
     pub(crate) fallback:Frame,
 }
 
@@ -65,6 +72,7 @@ impl InputFilterOptions {
             sub2video_width: 0,
             sub2video_height: 0,
             flags: 0,
+            hw_scale: None,
             fallback,
         }
     }
@@ -81,6 +89,7 @@ impl InputFilterOptions {
             sub2video_width: 0,
             sub2video_height: 0,
             flags: 0,
+            hw_scale: None,
             fallback:null_frame(),
         }
     }
@@ -4,8 +4,8 @@ use ffmpeg_sys_next::AVMediaType::{
     AVMEDIA_TYPE_VIDEO,
 };
 use ffmpeg_sys_next::{
-    av_freep, avcodec_free_context, avformat_close_input, avformat_free_context, avio_closep,
-    avio_context_free, AVCodecContext, AVCodecParameters, AVFormatContext, AVIOContext,
+    av_bsf_free, av_freep, avcodec_free_context, avformat_close_input, avformat_free_context, avio_closep,
+    avio_context_free, AVBSFContext, AVCodecContext, AVCodecParameters, AVFormatContext, AVIOContext,
     AVMediaType, AVRational, AVStream, AVFMT_NOFILE,
 };
 use std::ffi::c_void;
@@ -134,6 +134,18 @@ pub mod output;
 /// ```
 pub mod filter_complex;
 
+/// The **audio_mix** module provides [`AudioMix`](audio_mix::AudioMix), a typed builder for
+/// mixing two audio streams (e.g. narration over background music) with the `amix` filter,
+/// with per-stream weights, a [`MixDuration`](audio_mix::MixDuration) policy and optional
+/// normalization. It converts into a [`FilterComplex`](filter_complex::FilterComplex).
+///
+/// # Example
+///
+/// ```rust
+/// let mix = AudioMix::new("0:a", "1:a").set_weights(1.0, 0.3);
+/// ```
+pub mod audio_mix;
+
 
 pub(super) mod decoder_stream;
 pub(super) mod demuxer;
@@ -174,6 +186,23 @@ pub(super) mod output_filter;
 /// - Write operations assume individual buffers do not exceed `i32::MAX` bytes, which aligns with typical FFmpeg usage.
 pub mod null_output;
 
+/// The **ring_buffer_output** module provides the in-memory buffer behind
+/// [`Output::to_ring_buffer`](crate::Output::to_ring_buffer).
+///
+/// A [`RingBuffer`](ring_buffer_output::RingBuffer) keeps the most recent bytes written by the
+/// muxer up to a fixed capacity, dropping whole MPEG-TS packets or fragmented MP4 fragments so
+/// that a [`snapshot`](ring_buffer_output::RingBuffer::snapshot) can be saved and played as is.
+pub mod ring_buffer_output;
+
+/// The **packet_channel_output** module provides the channel behind
+/// [`Output::to_packet_channel`](crate::Output::to_packet_channel).
+///
+/// A [`PacketChannel`](packet_channel_output::PacketChannel) delivers the encoded packets of
+/// the output as [`EncodedPacket`](packet_channel_output::EncodedPacket)s instead of muxing
+/// them, and the codec configuration of each stream as an
+/// [`EncodedStream`](packet_channel_output::EncodedStream).
+pub mod packet_channel_output;
+
 pub(crate) struct CodecContext {
     inner: *mut AVCodecContext,
 }
@@ -257,6 +286,7 @@ pub(crate) struct FrameData {
     pub(crate) bits_per_raw_sample: i32,
     pub(crate) input_stream_width: i32,
     pub(crate) input_stream_height: i32,
+    pub(crate) sample_aspect_ratio: AVRational,
     pub(crate) subtitle_header_size: i32,
     pub(crate) subtitle_header: *mut u8,
 
@@ -289,6 +319,21 @@ pub(crate) struct PacketData {
 unsafe impl Send for PacketData {}
 unsafe impl Sync for PacketData {}
 
+/// Bitstream filter chain of a copied output stream, run by the muxer thread.
+pub(crate) struct BsfContextBox {
+    pub(crate) bsf_ctx: *mut AVBSFContext,
+}
+unsafe impl Send for BsfContextBox {}
+unsafe impl Sync for BsfContextBox {}
+
+impl Drop for BsfContextBox {
+    fn drop(&mut self) {
+        unsafe {
+            av_bsf_free(&mut self.bsf_ctx);
+        }
+    }
+}
+
 pub(crate) struct AVFormatContextBox {
     pub(crate) fmt_ctx: *mut AVFormatContext,
     pub(crate) is_input: bool,
@@ -1,16 +1,21 @@
 use std::collections::HashMap;
 use crate::core::context::encoder_stream::EncoderStream;
 use crate::core::filter::frame_pipeline::FramePipeline;
-use crate::core::context::output::{StreamMap, VSyncMethod};
-use crate::core::context::{FrameBox, PacketBox};
+use crate::core::context::output::{EvenDimensionsPolicy, ForceKeyframes, QualityRamp, StreamMap, VSyncMethod};
+use crate::core::context::{BsfContextBox, FrameBox, PacketBox};
+use crate::core::context::packet_channel_output::PacketSink;
 use crate::error::OpenOutputError;
 use crossbeam_channel::{Receiver, Sender};
-use ffmpeg_sys_next::{avformat_new_stream, AVCodec, AVFormatContext, AVMediaType, AVRational, AVSampleFormat, AVStream, AVFMT_NOTIMESTAMPS, AVFMT_VARIABLE_FPS};
+use ffmpeg_sys_next::{avformat_new_stream, AVCodec, AVFormatContext, AVMediaType, AVPixelFormat, AVRational, AVSampleFormat, AVStream, AVFMT_GLOBALHEADER, AVFMT_NOTIMESTAMPS, AVFMT_VARIABLE_FPS, AV_NOPTS_VALUE};
 use std::ffi::{CStr, CString};
 use std::ptr::null;
-use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use crate::core::scheduler::input_controller::SchNode;
+use crate::core::stream_info::StreamInfo;
+
+/// Number of packets an output stream may buffer while waiting for the muxer to start.
+pub(crate) const DEFAULT_MAX_MUXING_QUEUE_SIZE: usize = 65536;
 
 pub(crate) struct Muxer {
     pub(crate) url: String,
@@ -26,37 +31,68 @@ pub(crate) struct Muxer {
     pub(crate) subtitle_codec: Option<String>,
     pub(crate) start_time_us: Option<i64>,
     pub(crate) recording_time_us: Option<i64>,
+    pub(crate) output_ts_offset_us: Option<i64>,
+    pub(crate) audio_sync_offset_us: Option<i64>,
     pub(crate) framerate: Option<AVRational>,
     pub(crate) vsync_method: VSyncMethod,
+    pub(crate) even_dimensions_policy: EvenDimensionsPolicy,
+    pub(crate) pad_to_duration_us: Option<i64>,
     pub(crate) bits_per_raw_sample: Option<i32>,
     pub(crate) audio_sample_rate: Option<i32>,
     pub(crate) audio_channels: Option<i32>,
+    pub(crate) audio_channel_layout: Option<String>,
     pub(crate) audio_sample_fmt: Option<AVSampleFormat>,
+    pub(crate) video_pix_fmt: Option<AVPixelFormat>,
 
 
     pub(crate) video_qscale: Option<i32>,
     pub(crate) audio_qscale: Option<i32>,
 
+    pub(crate) gop_size: Option<i32>,
+    pub(crate) max_b_frames: Option<i32>,
+    pub(crate) keyint_min: Option<i32>,
+
     pub(crate) max_video_frames: Option<i64>,
     pub(crate) max_audio_frames: Option<i64>,
     pub(crate) max_subtitle_frames: Option<i64>,
+    max_muxing_queue_size: usize,
+    realtime_drop: bool,
+    pub(crate) interleaved: bool,
+    frame_interval: Option<u32>,
+    force_keyframes: Option<ForceKeyframes>,
+    video_quality_ramp: Option<QualityRamp>,
 
     pub(crate) video_codec_opts: Option<HashMap<CString, CString>>,
     pub(crate) audio_codec_opts: Option<HashMap<CString, CString>>,
     pub(crate) subtitle_codec_opts: Option<HashMap<CString, CString>>,
+    pub(crate) encoder_opts: Option<HashMap<CString, CString>>,
     pub(crate) format_opts: Option<HashMap<CString, CString>>,
 
     pub(crate) copy_ts: bool,
 
+    // receives the packets instead of the muxer, see `Output::to_packet_channel`
+    packet_sink: Option<PacketSink>,
+
     streams: Vec<EncoderStream>,
+    // bitstream filters of the copied streams, by output stream index
+    bitstream_filters: HashMap<i32, BsfContextBox>,
     queue: Option<(Sender<PacketBox>, Receiver<PacketBox>)>,
     src_pre_receivers: Vec<Receiver<PacketBox>>,
     is_started: Arc<AtomicBool>,
+    // streams of the output as written in the header, set once the header is written
+    output_streams: Arc<Mutex<Option<Vec<StreamInfo>>>>,
 
     pub(crate) nb_streams: usize,
     pub(crate) nb_streams_ready: Arc<AtomicUsize>,
 
     pub(crate) mux_stream_nodes: Vec<Arc<SchNode>>,
+    // frames dropped by the encoder of each stream because of `realtime_drop`, or by the
+    // frame rate conversion of its output filter
+    dropped_frames: Vec<Arc<AtomicU64>>,
+    // frames duplicated by the frame rate conversion of each stream
+    duplicated_frames: Vec<Arc<AtomicU64>>,
+    // what the muxer thread wrote to each stream, see `FfmpegScheduler::report`
+    written: Vec<Arc<StreamWritten>>,
 }
 
 unsafe impl Send for Muxer {}
@@ -74,58 +110,102 @@ impl Muxer {
         subtitle_codec: Option<String>,
         start_time_us: Option<i64>,
         recording_time_us: Option<i64>,
+        output_ts_offset_us: Option<i64>,
+        audio_sync_offset_us: Option<i64>,
         framerate: Option<AVRational>,
         vsync_method: VSyncMethod,
+        even_dimensions_policy: EvenDimensionsPolicy,
+        pad_to_duration_us: Option<i64>,
         bits_per_raw_sample: Option<i32>,
         audio_sample_rate: Option<i32>,
         audio_channels: Option<i32>,
+        audio_channel_layout: Option<String>,
         audio_sample_fmt: Option<AVSampleFormat>,
+        video_pix_fmt: Option<AVPixelFormat>,
         video_qscale: Option<i32>,
         audio_qscale: Option<i32>,
+        gop_size: Option<i32>,
+        max_b_frames: Option<i32>,
+        keyint_min: Option<i32>,
         max_video_frames: Option<i64>,
         max_audio_frames: Option<i64>,
         max_subtitle_frames: Option<i64>,
+        max_muxing_queue_size: usize,
+        realtime_drop: bool,
+        interleaved: bool,
+        frame_interval: Option<u32>,
+        force_keyframes: Option<ForceKeyframes>,
+        video_quality_ramp: Option<QualityRamp>,
         video_codec_opts: Option<HashMap<CString, CString>>,
         audio_codec_opts: Option<HashMap<CString, CString>>,
         subtitle_codec_opts: Option<HashMap<CString, CString>>,
+        encoder_opts: Option<HashMap<CString, CString>>,
         format_opts: Option<HashMap<CString, CString>>,
-        copy_ts: bool
+        copy_ts: bool,
+        packet_sink: Option<PacketSink>,
     ) -> Self {
+        let mut oformat_flags = unsafe { (*(*out_fmt_ctx).oformat).flags };
+        // without a container, the codec configuration can only be delivered out of band
+        if packet_sink.is_some() {
+            oformat_flags |= AVFMT_GLOBALHEADER;
+        }
         Self {
             url,
             frame_pipelines,
             out_fmt_ctx,
-            oformat_flags: unsafe { (*(*out_fmt_ctx).oformat).flags },
+            oformat_flags,
             stream_maps,
             video_codec,
             audio_codec,
             subtitle_codec,
             start_time_us,
             recording_time_us,
+            output_ts_offset_us,
+            audio_sync_offset_us,
             framerate,
             vsync_method,
+            even_dimensions_policy,
+            pad_to_duration_us,
             bits_per_raw_sample,
             audio_sample_rate,
             audio_channels,
+            audio_channel_layout,
             audio_sample_fmt,
+            video_pix_fmt,
             video_qscale,
             audio_qscale,
+            gop_size,
+            max_b_frames,
+            keyint_min,
             max_video_frames,
             max_audio_frames,
             max_subtitle_frames,
+            max_muxing_queue_size,
+            realtime_drop,
+            interleaved,
+            frame_interval,
+            force_keyframes,
+            video_quality_ramp,
             video_codec_opts,
             audio_codec_opts,
             subtitle_codec_opts,
+            encoder_opts,
             format_opts,
             copy_ts,
+            packet_sink,
             streams: vec![],
+            bitstream_filters: HashMap::new(),
             queue: None,
             src_pre_receivers: vec![],
             is_started: Arc::new(Default::default()),
+            output_streams: Arc::new(Mutex::new(None)),
             nb_streams: 0,
             nb_streams_ready: Arc::new(Default::default()),
             is_set_write_callback,
             mux_stream_nodes: vec![],
+            dropped_frames: vec![],
+            duplicated_frames: vec![],
+            written: vec![],
         }
     }
 
@@ -136,6 +216,8 @@ impl Muxer {
         src_node: Arc<SchNode>,
     ) -> crate::error::Result<(Sender<FrameBox>, usize)> {
         let (packet_sender, st, stream_index) = self.new_stream(src_node)?;
+        // the encoder fills in the parameters once it is opened, the media type is known now
+        unsafe { (*(*st).codecpar).codec_type = media_type };
         let (frame_sender, frame_receiver) = crossbeam_channel::bounded(8);
 
         let vsync_method = if media_type == AVMediaType::AVMEDIA_TYPE_VIDEO {
@@ -154,9 +236,29 @@ impl Muxer {
             None
         };
 
-        let (pre_packet_sender, pre_packet_receiver) = crossbeam_channel::bounded(65536);
+        let (gop_size, max_b_frames, keyint_min, frame_interval, force_keyframes, quality_ramp) =
+            if media_type == AVMediaType::AVMEDIA_TYPE_VIDEO {
+                (
+                    self.gop_size,
+                    self.max_b_frames,
+                    self.keyint_min,
+                    self.frame_interval,
+                    self.force_keyframes.clone(),
+                    self.video_quality_ramp.clone(),
+                )
+            } else {
+                (None, None, None, None, None, None)
+            };
+
+        let (pre_packet_sender, pre_packet_receiver) = crossbeam_channel::bounded(self.max_muxing_queue_size);
         self.src_pre_receivers.push(pre_packet_receiver);
 
+        let dropped_frames = if self.realtime_drop && media_type == AVMediaType::AVMEDIA_TYPE_VIDEO {
+            Some(self.dropped_frames[stream_index].clone())
+        } else {
+            None
+        };
+
         let stream = EncoderStream::new(
             stream_index,
             st,
@@ -164,6 +266,13 @@ impl Muxer {
             enc,
             vsync_method,
             qscale,
+            gop_size,
+            max_b_frames,
+            keyint_min,
+            dropped_frames,
+            frame_interval,
+            force_keyframes,
+            quality_ramp,
             frame_receiver,
             packet_sender,
             pre_packet_sender,
@@ -193,6 +302,10 @@ impl Muxer {
             source_finished: Arc::new(AtomicBool::new(false)),
         }));
 
+        self.dropped_frames.push(Arc::new(AtomicU64::new(0)));
+        self.duplicated_frames.push(Arc::new(AtomicU64::new(0)));
+        self.written.push(Arc::new(StreamWritten::new()));
+
         self.nb_streams += 1;
         unsafe {
             let st = avformat_new_stream(self.out_fmt_ctx, null());
@@ -239,9 +352,78 @@ impl Muxer {
         std::mem::take(&mut self.streams)
     }
 
+    pub(crate) fn set_bitstream_filter(&mut self, stream_index: usize, bsf: BsfContextBox) {
+        self.bitstream_filters.insert(stream_index as i32, bsf);
+    }
+
+    pub(crate) fn take_bitstream_filters(&mut self) -> HashMap<i32, BsfContextBox> {
+        std::mem::take(&mut self.bitstream_filters)
+    }
+
+    pub(crate) fn take_packet_sink(&mut self) -> Option<PacketSink> {
+        self.packet_sink.take()
+    }
+
     pub(crate) fn get_is_started(&self) -> Arc<AtomicBool> {
         self.is_started.clone()
     }
+
+    pub(crate) fn get_output_streams(&self) -> Arc<Mutex<Option<Vec<StreamInfo>>>> {
+        self.output_streams.clone()
+    }
+
+    pub(crate) fn get_dropped_frames(&self) -> Vec<Arc<AtomicU64>> {
+        self.dropped_frames.clone()
+    }
+
+    pub(crate) fn get_duplicated_frames(&self) -> Vec<Arc<AtomicU64>> {
+        self.duplicated_frames.clone()
+    }
+
+    pub(crate) fn get_written(&self) -> Vec<Arc<StreamWritten>> {
+        self.written.clone()
+    }
+}
+
+/// Totals of the packets written to one stream of an output, kept for the whole job
+/// (unlike the windows of the stream stats callback).
+pub(crate) struct StreamWritten {
+    pub(crate) packets: AtomicU64,
+    pub(crate) bytes: AtomicU64,
+    // dts of the first packet and end (dts + duration) of the last one, in microseconds
+    start_us: AtomicI64,
+    end_us: AtomicI64,
+}
+
+impl StreamWritten {
+    fn new() -> Self {
+        Self {
+            packets: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            start_us: AtomicI64::new(AV_NOPTS_VALUE),
+            end_us: AtomicI64::new(AV_NOPTS_VALUE),
+        }
+    }
+
+    /// Adds a written packet of `size` bytes, `dts_us` being `AV_NOPTS_VALUE` if unknown.
+    pub(crate) fn add(&self, size: i32, dts_us: i64, duration_us: i64) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size.max(0) as u64, Ordering::Relaxed);
+        if dts_us != AV_NOPTS_VALUE {
+            let _ = self.start_us.compare_exchange(AV_NOPTS_VALUE, dts_us, Ordering::Relaxed, Ordering::Relaxed);
+            self.end_us.fetch_max(dts_us + duration_us.max(0), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the media time covered by the written packets, in microseconds.
+    pub(crate) fn duration_us(&self) -> i64 {
+        let start_us = self.start_us.load(Ordering::Relaxed);
+        let end_us = self.end_us.load(Ordering::Relaxed);
+        if start_us == AV_NOPTS_VALUE || end_us == AV_NOPTS_VALUE {
+            return 0;
+        }
+        (end_us - start_us).max(0)
+    }
 }
 
 unsafe fn determine_vsync_method(
@@ -1,6 +1,12 @@
 use std::collections::HashMap;
-use ffmpeg_sys_next::{AVRational, AVSampleFormat};
+use std::sync::Arc;
+use std::time::Duration;
+use ffmpeg_sys_next::{AVPixelFormat, AVRational, AVSampleFormat};
+use crate::core::context::packet_channel_output::{PacketChannel, PacketSink};
+use crate::core::context::ring_buffer_output::RingBuffer;
+use crate::core::tags::Tags;
 use crate::filter::frame_pipeline::FramePipeline;
+use crate::util::ffmpeg_utils::duration_to_us;
 
 unsafe impl Send for Output {}
 
@@ -49,6 +55,14 @@ pub struct Output {
     /// correctly for the specified format.
     pub(crate) write_callback: Option<Box<dyn FnMut(&[u8]) -> i32>>,
 
+    /// The buffer written by the `write_callback` of an [`Output::to_ring_buffer`] output.
+    pub(crate) ring_buffer: Option<RingBuffer>,
+
+    /// The channel of an [`Output::to_packet_channel`] output, and its sending side handed
+    /// to the muxer.
+    pub(crate) packet_channel: Option<PacketChannel>,
+    pub(crate) packet_sink: Option<PacketSink>,
+
     /// A callback function for custom seeking within the output stream.
     ///
     /// The `seek_callback` function allows custom logic for adjusting the write position in
@@ -157,6 +171,28 @@ pub struct Output {
 
     pub(crate) stream_maps: Vec<StreamMap>,
 
+    /// Metadata tags of the output streams, as `(output stream index, key, value)`.
+    pub(crate) stream_tags: Vec<(usize, String, String)>,
+    /// Output streams marked as the default stream of their media type, see [`Output::set_default_stream`].
+    pub(crate) default_streams: Vec<usize>,
+    /// Time bases of the output streams, as `(output stream index, time base)`, see [`Output::set_time_base`].
+    pub(crate) stream_time_bases: Vec<(usize, AVRational)>,
+
+    /// Standard tags of the output file (title, artist, track...), see [`Output::set_tags`].
+    pub(crate) tags: Option<Tags>,
+
+    /// The `creation_time` of the output file, see [`Output::set_creation_time`].
+    pub(crate) creation_time: Option<String>,
+    pub(crate) copy_creation_time: bool,
+
+    /// Metadata keys removed from the file and its streams, see [`Output::strip_metadata_keys`].
+    pub(crate) strip_metadata_keys: Vec<String>,
+    pub(crate) strip_all_metadata: bool,
+
+    /// Bitstream filters of the copied output streams, as `(output stream index, filter)`,
+    /// applied in the order they were added.
+    pub(crate) bitstream_filters: Vec<(usize, String)>,
+
     /// The output format for the container.
     ///
     /// This field specifies the desired output format, such as `mp4`, `flv`, or `mkv`. If `None`, FFmpeg
@@ -192,11 +228,19 @@ pub struct Output {
     pub(crate) start_time_us: Option<i64>,
     pub(crate) recording_time_us: Option<i64>,
     pub(crate) stop_time_us: Option<i64>,
+    // padding of the encoded streams, see `set_pad_to_duration_us`
+    pub(crate) pad_to_duration_us: Option<i64>,
+    pub(crate) output_ts_offset_us: Option<i64>,
+    // set by `FfmpegContextBuilder::set_audio_sync_offset_us`
+    pub(crate) audio_sync_offset_us: Option<i64>,
     pub(crate) framerate: Option<AVRational>,
+    pub(crate) video_pix_fmt: Option<AVPixelFormat>,
     pub(crate) vsync_method: VSyncMethod,
+    pub(crate) even_dimensions_policy: EvenDimensionsPolicy,
     pub(crate) bits_per_raw_sample: Option<i32>,
     pub(crate) audio_sample_rate: Option<i32>,
     pub(crate) audio_channels: Option<i32>,
+    pub(crate) audio_channel_layout: Option<String>,
     pub(crate) audio_sample_fmt: Option<AVSampleFormat>,
 
     // -q:v
@@ -207,6 +251,18 @@ pub struct Output {
     // set audio quality (codec-specific)
     pub(crate) audio_qscale: Option<i32>,
 
+    // -g
+    // distance between keyframes (GOP size)
+    pub(crate) gop_size: Option<i32>,
+
+    // -bf
+    // maximum number of consecutive B-frames
+    pub(crate) max_b_frames: Option<i32>,
+
+    // -keyint_min
+    // minimum distance between keyframes
+    pub(crate) keyint_min: Option<i32>,
+
     /// Maximum number of **video** frames to encode (equivalent to `-frames:v` in FFmpeg).
     ///
     /// This option limits the number of **video** frames processed by the encoder.
@@ -255,6 +311,46 @@ pub struct Output {
     /// ```
     pub(crate) max_subtitle_frames: Option<i64>,
 
+    /// Maximum number of packets an output stream may buffer while the muxer is not
+    /// started yet (equivalent to `-max_muxing_queue_size` in FFmpeg).
+    ///
+    /// The muxer only writes the header once every stream is ready, packets produced before
+    /// that are queued. When a stream's queue is full, processing fails with a
+    /// "Too many packets buffered" error.
+    pub(crate) max_muxing_queue_size: Option<usize>,
+
+    /// Duration of each file when the output is split into segments, in microseconds.
+    ///
+    /// When set, the output is written through FFmpeg's `segment` muxer and `url` is the
+    /// file name pattern of the segments (e.g. `out_%03d.mp4`).
+    pub(crate) segment_duration_us: Option<i64>,
+
+    /// Whether the MP4/MOV index (`moov` atom) is moved to the front of the file once it is
+    /// written (equivalent to `-movflags +faststart` in FFmpeg).
+    pub(crate) faststart: bool,
+
+    /// Whether an existing output file is overwritten (FFmpeg's `-y`), or makes opening the
+    /// output fail (`-n`). Defaults to `true`.
+    pub(crate) overwrite: bool,
+
+    /// Whether the encoders drop video frames instead of blocking upstream when they fall
+    /// behind (see [`Output::set_realtime_drop`]).
+    pub(crate) realtime_drop: bool,
+
+    /// Whether packets go through FFmpeg's interleaving queue before being written
+    /// (see [`Output::set_interleaved`]). Defaults to `true`.
+    pub(crate) interleaved: bool,
+
+    /// Only every Nth video frame is encoded when set (see [`Output::set_frame_interval`]).
+    pub(crate) frame_interval: Option<u32>,
+
+    /// The video frames the encoders must turn into keyframes (see
+    /// [`Output::set_force_keyframes`] and [`Output::set_force_keyframes_expr`]).
+    pub(crate) force_keyframes: Option<ForceKeyframes>,
+
+    /// The quality of each video frame, by timestamp (see [`Output::set_video_quality_ramp`]).
+    pub(crate) video_quality_ramp: Option<QualityRamp>,
+
     /// Video encoder-specific options.
     ///
     /// This field stores key-value pairs for configuring the **video encoder**.
@@ -286,6 +382,17 @@ pub struct Output {
     /// - `srt` (for subtitle format)
     pub(crate) subtitle_codec_opts: Option<HashMap<String, String>>,
 
+    /// Encoder options applied to every encoder of this output.
+    ///
+    /// These are merged with the per-media-type options above (which take precedence)
+    /// and passed to the encoder, including its private options, before it is opened.
+    /// Options that no encoder recognizes are reported as warnings.
+    ///
+    /// **Common Examples:**
+    /// - `preset=veryslow`, `crf=18`, `tune=film` (x264/x265)
+    /// - `x264-params=keyint=60:no-scenecut=1`
+    pub(crate) encoder_opts: Option<HashMap<String, String>>,
+
     /// The output format options for the container.
     ///
     /// This field stores additional format-specific options that are passed to the FFmpeg muxer.
@@ -306,6 +413,64 @@ pub struct Output {
 
 }
 
+/// When the video encoders of an [`Output`] must emit a keyframe (`-force_key_frames`).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ForceKeyframes {
+    /// At the first frame at or after each of these times, in microseconds.
+    Times(Vec<i64>),
+    /// At the frames for which this FFmpeg expression is not zero.
+    Expr(String),
+}
+
+/// Returns the quality of the video frame at a timestamp in microseconds, see
+/// [`Output::set_video_quality_ramp`].
+pub(crate) type QualityRamp = Arc<dyn Fn(i64) -> f32 + Send + Sync>;
+
+/// What happens to video whose width or height does not fit the chroma subsampling of the
+/// encoder's pixel format, e.g. a 399 pixel wide picture encoded in `yuv420p`, which most
+/// encoders refuse.
+///
+/// Applies only to video encoded in a chroma subsampled format; `yuv444p` or RGB video
+/// keeps any size.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum EvenDimensionsPolicy {
+    /// Adds a black row or column at the bottom or right edge. This is the default.
+    #[default]
+    Pad,
+    /// Removes the last row or column.
+    Crop,
+    /// Fails the job once the size of the video is known.
+    Error,
+}
+
+/// The creation time of an output file, see [`Output::set_creation_time`]: an RFC 3339 date
+/// or `"now"`, or, with the `chrono` feature, a `chrono::DateTime`. Strings are checked when
+/// the context is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreationTime(String);
+
+impl From<&str> for CreationTime {
+    fn from(time: &str) -> Self {
+        Self(time.to_string())
+    }
+}
+
+impl From<String> for CreationTime {
+    fn from(time: String) -> Self {
+        Self(time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for CreationTime
+where
+    Tz::Offset: std::fmt::Display,
+{
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        Self(time.to_rfc3339())
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum VSyncMethod {
     VsyncAuto,
@@ -355,6 +520,103 @@ impl Output {
         (Box::new(write_callback) as Box<dyn FnMut(&[u8]) -> i32>).into()
     }
 
+    /// Creates an `Output` that keeps only the most recent `capacity_bytes` of muxed data in memory.
+    ///
+    /// This is meant for "save the last N seconds" features: the job keeps running, the oldest
+    /// data is dropped as new data arrives, and [`RingBuffer::snapshot`] returns a copy of the
+    /// current contents at any time. Get the handle with [`Output::ring_buffer`] before passing
+    /// the output to the context.
+    ///
+    /// The format defaults to `mpegts`. For the snapshot to be playable on its own, the data
+    /// must be made of independent pieces, so only these formats are trimmed on their boundaries:
+    /// - **MPEG-TS**: whole 188-byte packets are dropped. Players resynchronize on the next
+    ///   keyframe, so keep the GOP short (see [`Output::set_gop_size`]) to lose little at the start.
+    /// - **Fragmented MP4**: `set_format("mp4")` with
+    ///   `set_format_opt("movflags", "frag_keyframe+empty_moov+default_base_moof")`. The
+    ///   initialization segment is kept and whole fragments are dropped, each starting on a
+    ///   keyframe. A regular MP4 cannot be used, as its index is only written when the job ends.
+    ///
+    /// Any other format is trimmed byte by byte. `capacity_bytes` should hold several GOPs,
+    /// e.g. about 4 MB for 30 seconds at 1 Mbit/s.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::to_ring_buffer(8 * 1024 * 1024).set_gop_size(50);
+    /// let ring_buffer = output.ring_buffer().unwrap();
+    ///
+    /// let scheduler = FfmpegContext::builder()
+    ///     .input("rtmp://localhost/live/camera")
+    ///     .output(output)
+    ///     .build()?
+    ///     .start()?;
+    ///
+    /// // later, when something happened
+    /// std::fs::write("clip.ts", ring_buffer.snapshot())?;
+    /// ```
+    pub fn to_ring_buffer(capacity_bytes: usize) -> Self {
+        let ring_buffer = RingBuffer::new(capacity_bytes);
+        let write_callback = {
+            let ring_buffer = ring_buffer.clone();
+            move |buf: &[u8]| {
+                ring_buffer.write(buf);
+                buf.len() as i32
+            }
+        };
+        let mut output = Self::new_by_write_callback(write_callback).set_format("mpegts");
+        output.ring_buffer = Some(ring_buffer);
+        output
+    }
+
+    /// Returns the buffer of an output created with [`Output::to_ring_buffer`], `None` otherwise.
+    pub fn ring_buffer(&self) -> Option<RingBuffer> {
+        self.ring_buffer.clone()
+    }
+
+    /// Creates an `Output` that delivers the encoded packets through a channel instead of
+    /// muxing them, for callers that packetize the streams themselves (e.g. an RTP or
+    /// WebRTC sender). Get the handle with [`Output::packet_channel`] before passing the
+    /// output to the context.
+    ///
+    /// Each [`EncodedPacket`](crate::core::context::packet_channel_output::EncodedPacket)
+    /// carries the bytes, the output stream index, the timestamps in the time base of the
+    /// stream and the keyframe flag. There is no container to carry the codec configuration,
+    /// so the encoders are opened with global headers and the SPS/PPS (or their equivalent)
+    /// are delivered once, in the `extradata` of [`PacketChannel::streams`], rather than
+    /// in the packets. Copied streams keep the configuration and framing of their input.
+    ///
+    /// The channel is bounded: a receiver that falls behind slows the job down, and dropping
+    /// the receivers stops the output.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::to_packet_channel().set_video_codec("libx264");
+    /// let channel = output.packet_channel().unwrap();
+    ///
+    /// let scheduler = FfmpegContext::builder()
+    ///     .input("rtmp://localhost/live/camera")
+    ///     .output(output)
+    ///     .build()?
+    ///     .start()?;
+    ///
+    /// for packet in channel.receiver() {
+    ///     let streams = channel.streams().unwrap();
+    ///     // send streams[packet.stream_index].extradata before the first keyframe,
+    ///     // then packet.data
+    /// }
+    /// ```
+    pub fn to_packet_channel() -> Self {
+        let (packet_channel, packet_sink) = PacketChannel::new();
+        let mut output = Self::from("-").set_format("null");
+        output.packet_channel = Some(packet_channel);
+        output.packet_sink = Some(packet_sink);
+        output
+    }
+
+    /// Returns the channel of an output created with [`Output::to_packet_channel`], `None` otherwise.
+    pub fn packet_channel(&self) -> Option<PacketChannel> {
+        self.packet_channel.clone()
+    }
+
     /// Sets a custom seek callback for the output stream.
     ///
     /// This function assigns a user-defined function that handles seeking within the output stream.
@@ -527,6 +789,26 @@ impl Output {
     /// let output = Output::from("rtmp://localhost/live/stream")
     ///     .set_subtitle_codec("mov_text");
     /// ```
+    ///
+    /// # Sidecar subtitle files
+    /// A `.vtt` or `.srt` output selects the `webvtt` or `srt` (SubRip) encoder on its own, so
+    /// mapping a subtitle stream to it decodes the subtitles and re-encodes them as text rather
+    /// than copying the packets. Timings are kept, and styling is converted as far as the
+    /// target allows: bold, italic and underline survive in both, font colors in SRT only.
+    ///
+    /// Only text subtitles (ASS, SubRip, WebVTT, `mov_text`, ...) can be converted to text.
+    /// Bitmap subtitles (PGS, DVD, DVB) are pictures and would need OCR, so mapping one to a
+    /// text format fails with `OpenOutputError::BitmapSubtitleToText`.
+    ///
+    /// ```rust,ignore
+    /// // pull the subtitle track of an MKV out for the web
+    /// FfmpegContext::builder()
+    ///     .input("movie.mkv")
+    ///     .output(Output::from("captions.vtt").add_stream_map("0:s"))
+    ///     .build()?
+    ///     .start()?
+    ///     .wait()?;
+    /// ```
     pub fn set_subtitle_codec(mut self, subtitle_codec: impl Into<String>) -> Self {
         self.subtitle_codec = Some(subtitle_codec.into());
         self
@@ -594,6 +876,8 @@ impl Output {
     /// This string typically follows `"<input_index>:<media_type>"` syntax:
     /// - **`"0:v"`** – the video stream(s) from input #0.
     /// - **`"1:a?"`** – audio from input #1, **ignore** if none present (due to `?`).
+    /// - **`"0:2"`** – the stream with absolute index 2 of input #0 (`"0:2?"` to ignore if missing).
+    /// - **`"0:a:1"`** – the second audio stream of input #0 (`"0:a:1?"` to ignore if missing).
     /// - Other possibilities include `"0:s"`, `"0:d"`, etc. for subtitles/data, optionally with `?`.
     ///
     /// By calling `add_stream_map`, **you force re-encoding** of the chosen stream(s).
@@ -625,6 +909,7 @@ impl Output {
     /// Follows the same `"<input_index>:<media_type>"` pattern as [`add_stream_map`](Self::add_stream_map):
     /// - **`"0:a"`** – audio stream(s) from input #0.
     /// - **`"0:a?"`** – same, but ignore errors if no audio exists.
+    /// - **`"0:2"`** – the stream with absolute index 2 of input #0.
     /// - And so on for video (`v`), subtitles (`s`), attachments (`t`), etc.
     ///
     /// # Copy vs. Re-encode
@@ -652,10 +937,293 @@ impl Output {
         self.stream_maps.push(StreamMap {
             linklabel: linklabel.into(),
             copy: true,
+            language: None,
         });
         self
     }
 
+    /// Adds an **audio track** to the output: the stream `linklabel` refers to, re-encoded,
+    /// with its `language` tag set to `language` (an ISO 639-2 code such as `"eng"`).
+    ///
+    /// Call it once per track to build a multilingual file. `linklabel` follows
+    /// [`add_stream_map`](Self::add_stream_map): `"1:a"` for the first audio stream of input
+    /// #1, `"0:a:1"` for the second audio stream of input #0, or the label of a filter output
+    /// such as `"[mix]"` to add a track mixed from several sources. Each track gets its own
+    /// encoder.
+    ///
+    /// One audio track is marked as the default one, which players select first: the track
+    /// chosen with [`set_default_stream`](Self::set_default_stream), or else the first audio
+    /// stream of the output.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("multilingual.mp4")
+    ///     .add_stream_map("0:v")
+    ///     .add_audio_track("0:a", "eng")
+    ///     .add_audio_track("1:a", "spa");
+    /// ```
+    pub fn add_audio_track(mut self, linklabel: impl Into<String>, language: impl Into<String>) -> Self {
+        self.stream_maps.push(StreamMap {
+            linklabel: linklabel.into(),
+            copy: false,
+            language: Some(language.into()),
+        });
+        self
+    }
+
+    /// Marks the output stream `stream_index` as the **default** stream of its media type,
+    /// i.e. the track players select first, e.g. the Spanish audio track of a file whose
+    /// first audio track is English.
+    ///
+    /// Only one stream of each media type is the default one. Without this call, the first
+    /// video stream and the first audio stream of the output are, and no subtitle stream is
+    /// (players would show them without being asked to). `stream_index` is the index of the
+    /// stream in this output, see [`set_stream_language`](Self::set_stream_language).
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::StreamIndexOutOfRange` if the
+    /// output has no stream `stream_index`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mkv")
+    ///     .add_stream_map("0:v")
+    ///     .add_audio_track("0:a:0", "eng")
+    ///     .add_audio_track("0:a:1", "spa")
+    ///     .set_default_stream(2);
+    /// ```
+    pub fn set_default_stream(mut self, stream_index: usize) -> Self {
+        self.default_streams.push(stream_index);
+        self
+    }
+
+    /// Sets the **time base** (timescale) of the output stream `stream_index`, the unit its
+    /// timestamps are written in, e.g. `1/90000` for a 90 kHz clock expected by downstream
+    /// tooling.
+    ///
+    /// Without this call, encoded video streams use the time base of the encoder, i.e. the
+    /// inverse of the output frame rate (see [`set_framerate`](Self::set_framerate)) or the
+    /// time base of the filter output, encoded audio streams use `1/sample_rate`, and copied
+    /// streams use the inverse of their frame rate or their input time base. Setting it does
+    /// not change the encoder time base nor the frame rate: the packets are rescaled to it
+    /// when written, so a time base coarser than the frame rate loses precision.
+    ///
+    /// The time base is requested from the muxer when the header is written, and containers
+    /// with a fixed clock override it: MPEG-TS always uses `1/90000`, Matroska and FLV
+    /// `1/1000`, while MP4/MOV keep the requested value as the track timescale.
+    /// `stream_index` is the index of the stream in this output, see
+    /// [`set_stream_language`](Self::set_stream_language). Setting it twice keeps the last value.
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::InvalidTimeBase` if `num` or `den`
+    /// is not positive, and with `OpenOutputError::StreamIndexOutOfRange` if the output has
+    /// no stream `stream_index`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ffmpeg_sys_next::AVRational;
+    ///
+    /// let output = Output::from("output.mp4")
+    ///     .set_time_base(0, AVRational { num: 1, den: 90000 });
+    /// ```
+    pub fn set_time_base(mut self, stream_index: usize, time_base: AVRational) -> Self {
+        self.stream_time_bases.retain(|(index, _)| *index != stream_index);
+        self.stream_time_bases.push((stream_index, time_base));
+        self
+    }
+
+    /// Sets the **language** of an output stream, written as its `language` tag.
+    ///
+    /// Players and adaptive-streaming packagers (HLS, DASH) use it to label the audio
+    /// and subtitle tracks. Use an ISO 639-2 code such as `"eng"` or `"fra"`; other
+    /// values are written as given, with a warning when the context is built.
+    ///
+    /// `stream_index` is the index of the stream **in this output**, which differs from the
+    /// input stream index whenever streams are mapped, skipped or reordered: streams are
+    /// numbered in the order they are added, i.e. the order of the
+    /// [`add_stream_map`](Self::add_stream_map) calls, or video, audio, subtitle without them.
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::StreamIndexOutOfRange` if the
+    /// output has no stream `stream_index`.
+    ///
+    /// # Example
+    /// ```rust
+    /// // two audio tracks, labelled for the player
+    /// let output = Output::from("output.mp4")
+    ///     .add_stream_map("0:v")
+    ///     .add_stream_map("0:a")
+    ///     .add_stream_map("1:a")
+    ///     .set_stream_language(1, "eng")
+    ///     .set_stream_language(2, "fra");
+    /// ```
+    pub fn set_stream_language(self, stream_index: usize, language: impl Into<String>) -> Self {
+        self.set_stream_tag(stream_index, "language", language)
+    }
+
+    /// Sets a metadata **tag** of an output stream, e.g. `"title"` or `"handler_name"`.
+    ///
+    /// The tag is written by the muxer if the container supports it. `stream_index` is the
+    /// index of the stream in this output, see [`set_stream_language`](Self::set_stream_language).
+    /// Setting the same key twice keeps the last value.
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::StreamIndexOutOfRange` if the
+    /// output has no stream `stream_index`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_stream_tag(1, "handler_name", "Commentary");
+    /// ```
+    pub fn set_stream_tag(mut self, stream_index: usize, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.stream_tags.push((stream_index, key.into(), value.into()));
+        self
+    }
+
+    /// Sets the standard **tags** of the output file, e.g. the title, artist and track number
+    /// of a song.
+    ///
+    /// The fields are written under the names the container uses: ID3 frames for MP3
+    /// (`TIT2`, `TRCK`...), Vorbis comments for Ogg and FLAC (`TITLE`, `TRACKNUMBER` and
+    /// `TRACKTOTAL`...), atoms for MP4. The entries of [`Tags::other`] are written with their
+    /// key as is. Fields the container has no tag for are dropped by the muxer.
+    ///
+    /// The tags of the inputs are not copied to the output, so tags read with
+    /// [`read_tags`](crate::core::tags::read_tags) are typically edited and set back.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut tags = read_tags("song.mp3").unwrap();
+    /// tags.genre = Some("Jazz".to_string());
+    ///
+    /// let output = Output::from("song.flac").set_tags(tags);
+    /// ```
+    pub fn set_tags(mut self, tags: Tags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Sets the **creation time** of the output file, as an RFC 3339 date
+    /// (`"2024-05-01T12:00:00Z"`, `"2024-05-01T14:00:00.250+02:00"`), `"now"` for the time
+    /// the context is built, or, with the `chrono` feature, a `chrono::DateTime`.
+    ///
+    /// The time is converted to UTC and written as the `creation_time` tag of the file, which
+    /// the muxers store in their own way: MP4 and MOV in the movie and track headers, where it
+    /// is also set on every stream, Matroska and WebM as the `DateUTC` of the segment. Other
+    /// containers write it as a plain tag, if they write tags at all. This takes precedence
+    /// over [`copy_creation_time`](Self::copy_creation_time), and is kept by
+    /// [`strip_all_metadata`](Self::strip_all_metadata) and
+    /// [`strip_metadata_keys`](Self::strip_metadata_keys).
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::InvalidCreationTime` if `time` is
+    /// not a valid date.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4").set_creation_time("2024-05-01T12:00:00Z");
+    /// ```
+    pub fn set_creation_time(mut self, time: impl Into<CreationTime>) -> Self {
+        self.creation_time = Some(time.into().0);
+        self
+    }
+
+    /// Copies the **creation time** of the inputs to the output file when `copy` is `true`,
+    /// e.g. to keep the capture time of camera footage through a transcode.
+    ///
+    /// The time is taken from the first input having one, in its container or else in its
+    /// streams, and written as with [`set_creation_time`](Self::set_creation_time), so it is
+    /// kept by the metadata stripping as well. Without
+    /// it (the default), the output has no creation time and the muxers write their own,
+    /// usually none or the current time.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mkv").copy_creation_time(true);
+    /// ```
+    pub fn copy_creation_time(mut self, copy: bool) -> Self {
+        self.copy_creation_time = copy;
+        self
+    }
+
+    /// Removes metadata **keys** from the output file and from each of its streams, e.g.
+    /// the location or the camera model before publishing a video.
+    ///
+    /// Keys are matched without case. They are removed whatever set them, including
+    /// [`set_tags`](Self::set_tags) (whose [`Tags::other`] keeps every tag of
+    /// [`read_tags`](crate::core::tags::read_tags) it has no field for) and
+    /// [`set_stream_tag`](Self::set_stream_tag). This applies to encoded and copied streams
+    /// alike. Listing `"encoder"` also stops the muxer from writing its own `encoder` tag. The
+    /// creation time of [`set_creation_time`](Self::set_creation_time) and
+    /// [`copy_creation_time`](Self::copy_creation_time) is written after the keys are removed.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_tags(read_tags("input.mov").unwrap())
+    ///     .strip_metadata_keys(vec![
+    ///         "location".to_string(),
+    ///         "com.apple.quicktime.location.ISO6709".to_string(),
+    ///         "com.apple.quicktime.make".to_string(),
+    ///     ]);
+    /// ```
+    pub fn strip_metadata_keys(mut self, keys: Vec<String>) -> Self {
+        self.strip_metadata_keys = keys;
+        self
+    }
+
+    /// Removes all the metadata of the output file and of its streams when `strip` is `true`,
+    /// including the tags set on this output and the `encoder` tag the muxer adds. Only the
+    /// creation time asked for with [`set_creation_time`](Self::set_creation_time) or
+    /// [`copy_creation_time`](Self::copy_creation_time) is kept.
+    ///
+    /// Tags the container requires are still written by the muxer, e.g. the `handler_name`
+    /// of MP4 tracks, with their default value.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4").strip_all_metadata(true);
+    /// ```
+    pub fn strip_all_metadata(mut self, strip: bool) -> Self {
+        self.strip_all_metadata = strip;
+        self
+    }
+
+    /// Adds a **bitstream filter** to a stream copied into this output.
+    ///
+    /// Bitstream filters rewrite the packets of a stream without decoding them, e.g.
+    /// `"h264_mp4toannexb"` converts H.264 from the length-prefixed form used by MP4 to the
+    /// start-code form required by MPEG-TS, and `"aac_adtstoasc"` does the reverse for AAC.
+    /// Options follow the filter name, as in FFmpeg's `-bsf`: `"h264_metadata=aud=insert"`.
+    ///
+    /// Calling this several times for the same stream chains the filters in the order of the
+    /// calls; a comma-separated list such as `"h264_mp4toannexb,dump_extra"` does the same.
+    /// `stream_index` is the index of the stream in this output, see
+    /// [`set_stream_language`](Self::set_stream_language).
+    ///
+    /// Most muxers insert the filters they need on their own. When one does not, writing the
+    /// first packet fails, and the error names the filter the stream needs, if it is known.
+    ///
+    /// # Errors
+    /// Building the context fails with:
+    /// - `OpenOutputError::StreamIndexOutOfRange` if the output has no stream `stream_index`.
+    /// - `OpenOutputError::InvalidBitstreamFilter` if a filter is unknown, does not support
+    ///   the codec of the stream, or the stream is encoded rather than copied.
+    ///
+    /// # Example
+    /// ```rust
+    /// // remux MP4 to MPEG-TS without re-encoding
+    /// let output = Output::from("output.ts")
+    ///     .add_stream_map_with_copy("0:v")
+    ///     .add_stream_map_with_copy("0:a")
+    ///     .set_bitstream_filter(0, "h264_mp4toannexb");
+    /// ```
+    pub fn set_bitstream_filter(mut self, stream_index: usize, filter: impl Into<String>) -> Self {
+        self.bitstream_filters.push((stream_index, filter.into()));
+        self
+    }
+
     /// Sets the **start time** (in microseconds) for output encoding.
     ///
     /// If this is set, FFmpeg will attempt to start encoding from the specified
@@ -677,6 +1245,20 @@ impl Output {
         self
     }
 
+    /// Sets the **start time** for output encoding.
+    ///
+    /// The same as [`set_start_time_us`](Self::set_start_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_start_time(Duration::from_secs(2));
+    /// ```
+    pub fn set_start_time(self, duration: Duration) -> Self {
+        self.set_start_time_us(duration_to_us(duration))
+    }
+
     /// Sets the **recording time** (in microseconds) for output encoding.
     ///
     /// This indicates how many microseconds of data should be processed
@@ -699,6 +1281,20 @@ impl Output {
         self
     }
 
+    /// Sets the **recording time** for output encoding.
+    ///
+    /// The same as [`set_recording_time_us`](Self::set_recording_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_recording_time(Duration::from_secs(5));
+    /// ```
+    pub fn set_recording_time(self, duration: Duration) -> Self {
+        self.set_recording_time_us(duration_to_us(duration))
+    }
+
     /// Sets a **stop time** (in microseconds) for output encoding.
     ///
     /// If set, FFmpeg will stop encoding once the input’s timestamp
@@ -721,6 +1317,88 @@ impl Output {
         self
     }
 
+    /// Sets a **stop time** for output encoding.
+    ///
+    /// The same as [`set_stop_time_us`](Self::set_stop_time_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_stop_time(Duration::from_secs(10));
+    /// ```
+    pub fn set_stop_time(self, duration: Duration) -> Self {
+        self.set_stop_time_us(duration_to_us(duration))
+    }
+
+    /// Pads the encoded streams to a **fixed duration** (in microseconds), e.g. for
+    /// fixed-length ad slots.
+    ///
+    /// Once the source of a video stream ends, black frames are appended at the stream's
+    /// frame rate, and silence at the stream's sample rate and channel layout for an audio
+    /// stream, until the stream lasts `pad_to_duration_us`. The padding continues the
+    /// timestamps of the source, so they stay monotonic across the boundary. Streams longer
+    /// than the target are cut at it, so every encoded stream ends at the same time.
+    ///
+    /// The duration counts from the start of the output, after [`set_start_time_us`](Self::set_start_time_us)
+    /// is applied; when [`set_recording_time_us`](Self::set_recording_time_us) is also set, the
+    /// streams stop at the recording time instead. Streams copied without re-encoding are
+    /// neither padded nor cut.
+    ///
+    /// # Parameters
+    /// * `pad_to_duration_us` - The duration (in microseconds) of the streams.
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// // a 30 second slot, whatever the length of the clip
+    /// let output = Output::from("ad_slot.mp4")
+    ///     .set_pad_to_duration_us(30_000_000);
+    /// ```
+    pub fn set_pad_to_duration_us(mut self, pad_to_duration_us: i64) -> Self {
+        self.pad_to_duration_us = Some(pad_to_duration_us);
+        self
+    }
+
+    /// Pads the encoded streams to a **fixed duration**.
+    ///
+    /// The same as [`set_pad_to_duration_us`](Self::set_pad_to_duration_us), taking a [`Duration`]. It is
+    /// truncated to whole microseconds, and saturates at `i64::MAX` microseconds.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("ad_slot.mp4")
+    ///     .set_pad_to_duration(Duration::from_secs(30));
+    /// ```
+    pub fn set_pad_to_duration(self, duration: Duration) -> Self {
+        self.set_pad_to_duration_us(duration_to_us(duration))
+    }
+
+    /// Sets an **offset** (in microseconds) added to the timestamps of every packet
+    /// written to this output (equivalent to `-output_ts_offset` in FFmpeg).
+    ///
+    /// This is useful to align the output with other outputs or with a wall clock, e.g. a
+    /// segment that should start at 10 seconds in a streaming timeline. Negative offsets
+    /// are allowed, but timestamps that would become negative are clamped to zero.
+    ///
+    /// # Parameters
+    /// * `output_ts_offset_us` - The offset in microseconds.
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("segment.ts")
+    ///     .set_output_ts_offset_us(10_000_000); // First packet at 10 seconds
+    /// ```
+    pub fn set_output_ts_offset_us(mut self, output_ts_offset_us: i64) -> Self {
+        self.output_ts_offset_us = Some(output_ts_offset_us);
+        self
+    }
+
     /// Sets a **target frame rate** (`AVRational`) for output encoding.
     ///
     /// This can force the output to use a specific frame rate (e.g., 30/1 for 30 FPS).
@@ -734,6 +1412,13 @@ impl Output {
     /// # Returns
     /// * `Self` - The modified `Output`, allowing method chaining.
     ///
+    /// The conversion picks, for each output timestamp, the source frame covering it, so
+    /// frames are duplicated when the source is slower and dropped when it is faster. When
+    /// one rate is not an integer multiple of the other (e.g. 29.97 fps to 30 fps, or 24 to
+    /// 30), a warning is logged as this happens unevenly; the number of frames duplicated and
+    /// dropped is reported in `StreamStats::duplicated_frames` and `StreamStats::dropped_frames`
+    /// when a stream stats callback is registered on the scheduler.
+    ///
     /// # Example
     /// ```rust
     /// use ffmpeg_sys_next::AVRational;
@@ -745,6 +1430,37 @@ impl Output {
         self
     }
 
+    /// Sets the **pixel format** of the encoded video (equivalent to `-pix_fmt` in FFmpeg).
+    ///
+    /// The frames are converted to this format before they reach the encoder. By default the
+    /// format closest to the source among the ones the encoder supports is picked; opening the
+    /// output fails with `OpenOutputError::PixelFormatUnsupported`, listing the supported
+    /// formats, when the encoder cannot take `pix_fmt`.
+    ///
+    /// This matters most for **raw output**, which carries no description of its content:
+    /// with the `rawvideo` codec (e.g. a `.yuv` or `.rgb` file, or `set_format("rawvideo")`)
+    /// each packet is one frame, written as its planes one after the other (e.g. Y, U then V
+    /// for `yuv420p`), each plane as rows of `width` pixels without padding. The output is
+    /// then `frames * av_image_get_buffer_size(pix_fmt, width, height, 1)` bytes. Raw audio
+    /// works the same way with a PCM codec and format (e.g. `set_audio_codec("pcm_s16le")` and
+    /// `set_format("s16le")`): interleaved samples in the codec's format, at the rate and
+    /// channel count set with [`set_audio_sample_rate`](Output::set_audio_sample_rate) and
+    /// [`set_audio_channels`](Output::set_audio_channels). Raw output can also be written to a
+    /// callback with [`Output::new_by_write_callback`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+    ///
+    /// // 8-bit luma only, width * height bytes per frame
+    /// let output = Output::from("golden.yuv")
+    ///     .set_video_pix_fmt(AV_PIX_FMT_GRAY8);
+    /// ```
+    pub fn set_video_pix_fmt(mut self, pix_fmt: AVPixelFormat) -> Self {
+        self.video_pix_fmt = Some(pix_fmt);
+        self
+    }
+
     /// Sets the **video sync method** to be used during encoding.
     ///
     /// FFmpeg uses a variety of vsync policies to handle frame presentation times,
@@ -768,6 +1484,30 @@ impl Output {
         self
     }
 
+    /// Sets how video with odd dimensions is adjusted before it reaches the encoder.
+    ///
+    /// Encoding in a chroma subsampled format such as `yuv420p` requires an even width and
+    /// height, and a video resized to e.g. 399x300 makes most encoders fail to open. By
+    /// default ([`EvenDimensionsPolicy::Pad`]) such video is padded by one pixel, and the
+    /// adjustment is logged. The filters are added after any `filter_desc`.
+    ///
+    /// # Parameters
+    /// * `policy` - A variant of [`EvenDimensionsPolicy`].
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// // drop the last column of a 399 pixel wide video instead of padding it
+    /// let output = Output::from("output.mp4")
+    ///     .set_even_dimensions_policy(EvenDimensionsPolicy::Crop);
+    /// ```
+    pub fn set_even_dimensions_policy(mut self, policy: EvenDimensionsPolicy) -> Self {
+        self.even_dimensions_policy = policy;
+        self
+    }
+
     /// Sets the **bits per raw sample** for video encoding.
     ///
     /// This value can influence quality or color depth when dealing with
@@ -817,6 +1557,14 @@ impl Output {
     /// Common values include 1 (mono), 2 (stereo), 5.1 (6 channels), and 7.1 (8 channels).
     /// This setting affects the spatial audio characteristics of the output.
     ///
+    /// The audio is down- or upmixed to the first layout the encoder supports with this many
+    /// channels (the default layout if it accepts any, e.g. `mono`, `stereo`, `5.1`). Use
+    /// [`set_audio_channel_layout`](Output::set_audio_channel_layout) to pick the layout itself.
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::ChannelLayoutUnsupported`, listing
+    /// the supported layouts, if the encoder has no layout with this many channels.
+    ///
     /// # Parameters
     /// * `audio_channels` - The number of audio channels (e.g., 1 for mono, 2 for stereo).
     ///
@@ -833,6 +1581,32 @@ impl Output {
         self
     }
 
+    /// Sets the **audio channel layout** for output encoding, e.g. `"mono"`, `"stereo"`,
+    /// `"5.1"` or `"FL+FR+LFE"` (any layout FFmpeg's `-ch_layout` accepts).
+    ///
+    /// The audio is down- or upmixed to this layout by the filter graph before encoding.
+    /// It can be combined with [`set_audio_channels`](Output::set_audio_channels) only if
+    /// the channel counts agree, and with encoder options such as the bitrate, which are
+    /// applied independently.
+    ///
+    /// # Errors
+    /// Building the context fails with `OpenOutputError::ChannelLayoutUnsupported`, listing
+    /// the supported layouts, if the encoder does not support this layout, and with
+    /// `OpenOutputError::InvalidArgument` if the layout cannot be parsed.
+    ///
+    /// # Example
+    /// ```rust
+    /// // a mono voice memo
+    /// let output = Output::from("memo.m4a")
+    ///     .set_audio_codec("aac")
+    ///     .set_audio_channel_layout("mono")
+    ///     .set_audio_codec_opt("b", "64k");
+    /// ```
+    pub fn set_audio_channel_layout(mut self, layout: impl Into<String>) -> Self {
+        self.audio_channel_layout = Some(layout.into());
+        self
+    }
+
     /// Sets the **audio sample format** for output encoding.
     ///
     /// This method allows you to specify the audio sample format, which affects
@@ -907,6 +1681,41 @@ impl Output {
         self
     }
 
+    /// Varies the quality of the video encoding over time, e.g. to spend more bits on
+    /// high-motion segments.
+    ///
+    /// `ramp` is called for every encoded frame with its timestamp in microseconds, and
+    /// returns the quality of the frame on the scale of the encoder. How it is applied
+    /// depends on the encoder:
+    /// * **`libx264`, `libx264rgb`**: the value is the CRF (0-51, lower is better). The
+    ///   encoder is reconfigured whenever it changes.
+    /// * **FFmpeg's MPEG family** (`mpeg4`, `mpeg2video`, `mpeg1video`, `mjpeg`, `h263`,
+    ///   `msmpeg4`, `flv`...): the value is the qscale of the frame (2-31, lower is better),
+    ///   as with [`set_video_qscale`](Output::set_video_qscale).
+    /// * **Other encoders** cannot change their quality from one frame to the next: a
+    ///   warning is logged and the value at the start of the output is used as a constant
+    ///   qscale, unless [`set_video_qscale`](Output::set_video_qscale) sets one.
+    ///
+    /// # Parameters
+    /// * `ramp` - Returns the quality of the frame at the given time, in microseconds.
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// // better quality (CRF 18) for the action scene between 30s and 45s, CRF 26 elsewhere
+    /// let output = Output::from("output.mp4")
+    ///     .set_video_codec("libx264")
+    ///     .set_video_quality_ramp(|pts_us| {
+    ///         if (30_000_000..45_000_000).contains(&pts_us) { 18.0 } else { 26.0 }
+    ///     });
+    /// ```
+    pub fn set_video_quality_ramp(mut self, ramp: impl Fn(i64) -> f32 + Send + Sync + 'static) -> Self {
+        self.video_quality_ramp = Some(Arc::new(ramp));
+        self
+    }
+
     /// Sets the **audio quality scale** for encoding.
     ///
     /// This method configures codec-specific audio quality settings. The range, behavior,
@@ -955,8 +1764,93 @@ impl Output {
         self
     }
 
+    /// **Sets the GOP size, the maximum distance in frames between two keyframes (`-g`).**
+    ///
+    /// A fixed GOP is needed when the output is cut into segments (HLS, DASH or
+    /// [`set_segment`](Output::set_segment)): with a 2-second GOP at 30 fps (`gop_size = 60`) and
+    /// 2-second segments, every segment starts on a keyframe. To get keyframes at exactly that
+    /// interval, also set [`set_keyint_min`](Output::set_keyint_min) to the same value and disable
+    /// scene-cut keyframes of the encoder (e.g. `set_video_codec_opt("sc_threshold", "0")` for
+    /// libx264, whose keyframes are IDR frames by default).
+    ///
+    /// Only applies to encoded video streams. The value must not be negative, otherwise opening
+    /// the output fails with `OpenOutputError::OptionValueTooSmall`.
+    ///
+    /// # Parameters
+    /// * `gop_size` - The number of frames between keyframes (`0` makes every frame a keyframe for intra-only codecs).
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// // 2-second GOPs for a 30 fps HLS-ready MP4
+    /// let output = Output::from("output.mp4")
+    ///     .set_video_codec("libx264")
+    ///     .set_gop_size(60)
+    ///     .set_keyint_min(60)
+    ///     .set_video_codec_opt("sc_threshold", "0");
+    /// ```
+    pub fn set_gop_size(mut self, gop_size: i32) -> Self {
+        self.gop_size = Some(gop_size);
+        self
+    }
+
+    /// **Sets the maximum number of consecutive B-frames (`-bf`).**
+    ///
+    /// B-frames improve compression but add encoding and decoding delay, set it to `0` for
+    /// low-latency streaming, so frames are output in display order.
+    ///
+    /// Only applies to encoded video streams. The value must not be negative, otherwise opening
+    /// the output fails with `OpenOutputError::OptionValueTooSmall`.
+    ///
+    /// # Parameters
+    /// * `max_b_frames` - The maximum number of B-frames between two reference frames.
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("rtmp://localhost/live/stream")
+    ///     .set_format("flv")
+    ///     .set_max_b_frames(0);
+    /// ```
+    pub fn set_max_b_frames(mut self, max_b_frames: i32) -> Self {
+        self.max_b_frames = Some(max_b_frames);
+        self
+    }
+
+    /// **Sets the minimum distance in frames between two keyframes (`-keyint_min`).**
+    ///
+    /// Encoders may insert extra keyframes on scene changes, this sets how close they can be.
+    /// Use the same value as [`set_gop_size`](Output::set_gop_size) for a fixed keyframe interval.
+    ///
+    /// Only applies to encoded video streams. The value must not be negative, otherwise opening
+    /// the output fails with `OpenOutputError::OptionValueTooSmall`.
+    ///
+    /// # Parameters
+    /// * `keyint_min` - The minimum number of frames between keyframes.
+    ///
+    /// # Returns
+    /// * `Self` - The modified `Output`, allowing method chaining.
+    ///
+    /// # Example
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_gop_size(48)
+    ///     .set_keyint_min(48);
+    /// ```
+    pub fn set_keyint_min(mut self, keyint_min: i32) -> Self {
+        self.keyint_min = Some(keyint_min);
+        self
+    }
+
     /// **Sets the maximum number of video frames to encode (`-frames:v`).**
     ///
+    /// The value must be greater than 0, otherwise opening the output fails with
+    /// `OpenOutputError::OptionValueTooSmall`.
+    ///
     /// **Equivalent FFmpeg Command:**
     /// ```sh
     /// ffmpeg -i input.mp4 -frames:v 100 output.mp4
@@ -974,6 +1868,16 @@ impl Output {
 
     /// **Sets the maximum number of audio frames to encode (`-frames:a`).**
     ///
+    /// The limit counts frames as sent to the audio encoder, not samples: each frame holds
+    /// the encoder's frame size (e.g. 1024 samples for AAC), so 500 AAC frames at 48 kHz
+    /// are about 10.7 seconds. If a recording time is also set, whichever limit is reached
+    /// first ends the audio stream. Other streams are not truncated by this limit, so
+    /// combine it with `set_max_video_frames` or a recording time for an audio-only preview
+    /// of a video file.
+    ///
+    /// The value must be greater than 0, otherwise opening the output fails with
+    /// `OpenOutputError::OptionValueTooSmall`.
+    ///
     /// **Equivalent FFmpeg Command:**
     /// ```sh
     /// ffmpeg -i input.mp4 -frames:a 500 output.mp4
@@ -991,6 +1895,9 @@ impl Output {
 
     /// **Sets the maximum number of subtitle frames to encode (`-frames:s`).**
     ///
+    /// The value must be greater than 0, otherwise opening the output fails with
+    /// `OpenOutputError::OptionValueTooSmall`.
+    ///
     /// **Equivalent FFmpeg Command:**
     /// ```sh
     /// ffmpeg -i input.mp4 -frames:s 200 output.mp4
@@ -1006,6 +1913,335 @@ impl Output {
         self
     }
 
+    /// **Sets the maximum number of packets buffered per stream before the muxer starts (`-max_muxing_queue_size`).**
+    ///
+    /// The output header is only written once every stream has produced its first packet,
+    /// packets arriving earlier are queued. With many streams, or with a stream that starts
+    /// late (e.g. sparse subtitles), the queue of the other streams can fill up and processing
+    /// fails with a "Too many packets buffered" error; raise this limit in that case.
+    /// Defaults to 65536 packets.
+    ///
+    /// The value must be greater than 0, otherwise opening the output fails with
+    /// `OpenOutputError::OptionValueTooSmall`.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i input.mkv -map 0 -max_muxing_queue_size 1024 output.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_max_muxing_queue_size(1024);
+    /// ```
+    pub fn set_max_muxing_queue_size(mut self, max_muxing_queue_size: usize) -> Self {
+        self.max_muxing_queue_size = Some(max_muxing_queue_size);
+        self
+    }
+
+    /// **Splits the output into consecutive files of `duration_us` microseconds each.**
+    ///
+    /// The output is written through FFmpeg's `segment` muxer, the output URL is the file name
+    /// pattern of the segments and must contain a `printf`-style number (e.g. `out_%03d.mp4`
+    /// produces `out_000.mp4`, `out_001.mp4`, ...). The container of each segment is guessed from
+    /// the pattern's extension, or taken from [`set_format`](Output::set_format) when it is set.
+    ///
+    /// Segments are only cut on video keyframes, so every file starts with a keyframe and can
+    /// be played on its own, and timestamps restart at zero in each file. With stream copy a
+    /// segment therefore ends on the first source keyframe after the boundary; when encoding,
+    /// choose a GOP size ([`set_gop_size`](Output::set_gop_size)) that fits the segment duration.
+    ///
+    /// `duration_us` must be greater than 0, and the output must be a URL rather than a write
+    /// callback, otherwise opening the output fails with `OpenOutputError::InvalidArgument`.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i rtsp://camera -c copy -f segment -segment_time 3600 -reset_timestamps 1 out_%03d.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// // hourly files
+    /// let output = Output::from("camera_%05d.mp4")
+    ///     .add_stream_map_with_copy("0:v")
+    ///     .set_segment(3600 * 1_000_000);
+    /// ```
+    pub fn set_segment(mut self, duration_us: i64) -> Self {
+        self.segment_duration_us = Some(duration_us);
+        self
+    }
+
+    /// **Moves the index (`moov` atom) of an MP4/MOV output to the front of the file.**
+    ///
+    /// Players can then start a progressive download or byte-range playback (e.g. from a CDN)
+    /// without fetching the end of the file first. The muxer relocates the index in a second
+    /// pass over the file once every packet has been written; it is skipped when the job fails,
+    /// so a failed output is not rewritten.
+    ///
+    /// The second pass reads the file back, so the output must be a seekable file: opening
+    /// the output fails with `OpenOutputError::FaststartUnsupported` for write callbacks, pipes
+    /// (`"-"`) and containers other than the MP4/MOV family.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i input.mkv -movflags +faststart output.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("web.mp4")
+    ///     .set_faststart(true);
+    /// ```
+    pub fn set_faststart(mut self, faststart: bool) -> Self {
+        self.faststart = faststart;
+        self
+    }
+
+    /// **Sets whether an existing output file is overwritten.**
+    ///
+    /// By default an existing file is truncated, as with FFmpeg's `-y`. With `false` the
+    /// file is left untouched and building the context fails with
+    /// `OpenOutputError::OutputExists`, as with `-n`, so an automated pipeline does not
+    /// clobber earlier results.
+    ///
+    /// Only local files are checked: network outputs, write callbacks and outputs whose
+    /// muxer names the files itself (image sequences, segments) are always written.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -n -i input.mp4 output.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("result.mp4")
+    ///     .set_overwrite(false);
+    /// ```
+    pub fn set_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// **Drops video frames when an encoder cannot keep up, instead of stalling the input.**
+    ///
+    /// By default a slow encoder back-pressures the whole pipeline: its input queue fills up,
+    /// the decoder and then the demuxer wait, and a live source (camera, RTMP/RTSP stream)
+    /// falls behind. With this option, whenever a video encoder's input queue is full, the
+    /// oldest queued frame is dropped rather than encoded, so latency stays bounded during
+    /// CPU spikes at the cost of a lower frame rate.
+    ///
+    /// Frames flagged as keyframes are never dropped, nor are audio frames. The number of
+    /// dropped frames is reported in `StreamStats::dropped_frames` when a stream stats
+    /// callback is registered on the scheduler.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("rtmp://localhost/live/stream")
+    ///     .set_format("flv")
+    ///     .set_realtime_drop(true);
+    /// ```
+    pub fn set_realtime_drop(mut self, realtime_drop: bool) -> Self {
+        self.realtime_drop = realtime_drop;
+        self
+    }
+
+    /// **Chooses between interleaved (`av_interleaved_write_frame`) and direct (`av_write_frame`) writing.**
+    ///
+    /// By default (`true`) packets are written through FFmpeg's interleaving queue, which
+    /// holds each packet back until every other stream has produced a packet with a later
+    /// dts, so that the streams are written in dts order. With `false` every packet is
+    /// written as soon as it is encoded, which removes the latency that queue adds when a
+    /// stream lags behind the others, e.g. audio waiting on a slow video encoder in a live
+    /// stream.
+    ///
+    /// Direct writing leaves the order of the packets to the encoders: packets of different
+    /// streams are no longer sorted by dts, which some containers and players handle badly
+    /// (players may stall or drop data on MPEG-TS or FLV whose streams drift apart), and
+    /// muxers relying on their own interleaving, such as MXF or GXF, may write broken
+    /// files. Only use it for outputs consumed in real time whose streams stay close to
+    /// each other. An output with a single stream is written the same way either way, as
+    /// the queue never has another stream to wait for.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("udp://127.0.0.1:1234")
+    ///     .set_format("mpegts")
+    ///     .set_interleaved(false);
+    /// ```
+    pub fn set_interleaved(mut self, interleaved: bool) -> Self {
+        self.interleaved = interleaved;
+        self
+    }
+
+    /// **Encodes only every `frame_interval`th video frame** (the 1st, the (N+1)th, ...).
+    ///
+    /// Mostly useful to sample an image sequence: when the URL is a numbered pattern such as
+    /// `frame_%04d.png`, every encoded frame is written to its own file through FFmpeg's
+    /// `image2` muxer, numbered contiguously from 1 whatever the interval (set the
+    /// `start_number` format option to start elsewhere). The image codec is picked from the
+    /// extension; its quality follows [`set_video_qscale`](Output::set_video_qscale) (e.g. for
+    /// JPEG), and the pixel format is the one the encoder supports closest to the source,
+    /// which a `format` filter (e.g. `format=gray`) in a `filter_desc` can override.
+    ///
+    /// The interval counts the frames after the frame rate conversion, if any.
+    /// [`set_max_video_frames`](Output::set_max_video_frames) counts the frames actually encoded.
+    /// `frame_interval` must be greater than 0, otherwise opening the output fails with
+    /// `OpenOutputError::InvalidArgument`.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i input.mp4 -vf "select='not(mod(n,10))'" frame_%04d.png
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// // one image every 10 frames: frame_0001.png, frame_0002.png, ...
+    /// let output = Output::from("frame_%04d.png")
+    ///     .set_frame_interval(10);
+    /// ```
+    pub fn set_frame_interval(mut self, frame_interval: u32) -> Self {
+        self.frame_interval = Some(frame_interval);
+        self
+    }
+
+    /// **Forces a keyframe at each of the given output times (`-force_key_frames`).**
+    ///
+    /// For every time, in microseconds, the first video frame at or after it is sent to the
+    /// encoder flagged as an I frame (`AV_PICTURE_TYPE_I`), e.g. to align the keyframes with HLS
+    /// segment boundaries or ad insertion points. The times are on the output timeline, which
+    /// starts at 0 unless timestamps are copied.
+    ///
+    /// The encoder decides what to do with the hint. `libx264`, `libx265`, `libvpx`, `libaom-av1`,
+    /// `libsvtav1` and the FFmpeg native encoders start a new GOP (an IDR frame with the default
+    /// closed GOPs); NVENC emits an I frame that is an IDR frame only with its `forced-idr`
+    /// option set; some hardware encoders (e.g. `h264_v4l2m2m`, `h264_omx`) ignore it. The
+    /// encoder may still insert other keyframes on scene changes or at the end of its GOP, so
+    /// set [`set_gop_size`](Output::set_gop_size) (and `sc_threshold` to `0` for `libx264`) when
+    /// the keyframes must be exactly these.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i input.mp4 -force_key_frames 0,2,4,6 output.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// // a keyframe every 2 seconds for a 60 second video
+    /// let output = Output::from("output.mp4")
+    ///     .set_force_keyframes((0..30).map(|i| i * 2_000_000).collect());
+    /// ```
+    pub fn set_force_keyframes(mut self, times_us: Vec<i64>) -> Self {
+        self.force_keyframes = Some(ForceKeyframes::Times(times_us));
+        self
+    }
+
+    /// **Forces a keyframe at the video frames for which `expr` is not zero
+    /// (`-force_key_frames expr:...`).**
+    ///
+    /// The expression is evaluated for every frame with the variables of FFmpeg's
+    /// `force_key_frames`: `n` (the frame number, from 0), `n_forced` (the number of frames
+    /// forced so far), `prev_forced_n` and `prev_forced_t` (the number and time of the
+    /// previously forced frame, `NAN` before the first one) and `t` (the frame time in seconds).
+    /// What encoders do with the hint is described in
+    /// [`set_force_keyframes`](Output::set_force_keyframes). An invalid expression makes
+    /// opening the output fail with `OpenOutputError::InvalidArgument`.
+    ///
+    /// **Equivalent FFmpeg Command:**
+    /// ```sh
+    /// ffmpeg -i input.mp4 -force_key_frames "expr:gte(t,n_forced*2)" output.mp4
+    /// ```
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// // a keyframe every 2 seconds
+    /// let output = Output::from("output.m3u8")
+    ///     .set_force_keyframes_expr("gte(t,n_forced*2)");
+    /// ```
+    pub fn set_force_keyframes_expr(mut self, expr: impl Into<String>) -> Self {
+        self.force_keyframes = Some(ForceKeyframes::Expr(expr.into()));
+        self
+    }
+
+    /// Returns a copy of this output for another job, re-creating its frame pipelines.
+    ///
+    /// Fails if the output writes through callbacks, or if one of its frame pipelines
+    /// cannot be re-created (see [`FramePipeline`]).
+    pub(crate) fn try_clone(&self) -> Result<Output, String> {
+        if self.write_callback.is_some() || self.seek_callback.is_some() {
+            return Err("outputs with a write or seek callback cannot be copied".to_string());
+        }
+        let frame_pipelines = match &self.frame_pipelines {
+            Some(frame_pipelines) => Some(
+                frame_pipelines
+                    .iter()
+                    .map(FramePipeline::try_clone)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+
+        Ok(Output {
+            url: self.url.clone(),
+            write_callback: None,
+            seek_callback: None,
+            ring_buffer: None,
+            packet_channel: None,
+            packet_sink: None,
+            frame_pipelines,
+            stream_maps: self.stream_maps.clone(),
+            stream_tags: self.stream_tags.clone(),
+            default_streams: self.default_streams.clone(),
+            stream_time_bases: self.stream_time_bases.clone(),
+            tags: self.tags.clone(),
+            creation_time: self.creation_time.clone(),
+            copy_creation_time: self.copy_creation_time,
+            strip_metadata_keys: self.strip_metadata_keys.clone(),
+            strip_all_metadata: self.strip_all_metadata,
+            bitstream_filters: self.bitstream_filters.clone(),
+            format: self.format.clone(),
+            video_codec: self.video_codec.clone(),
+            audio_codec: self.audio_codec.clone(),
+            subtitle_codec: self.subtitle_codec.clone(),
+            start_time_us: self.start_time_us,
+            recording_time_us: self.recording_time_us,
+            stop_time_us: self.stop_time_us,
+            pad_to_duration_us: self.pad_to_duration_us,
+            output_ts_offset_us: self.output_ts_offset_us,
+            audio_sync_offset_us: self.audio_sync_offset_us,
+            framerate: self.framerate,
+            video_pix_fmt: self.video_pix_fmt,
+            vsync_method: self.vsync_method,
+            even_dimensions_policy: self.even_dimensions_policy,
+            bits_per_raw_sample: self.bits_per_raw_sample,
+            audio_sample_rate: self.audio_sample_rate,
+            audio_channels: self.audio_channels,
+            audio_channel_layout: self.audio_channel_layout.clone(),
+            audio_sample_fmt: self.audio_sample_fmt,
+            video_qscale: self.video_qscale,
+            audio_qscale: self.audio_qscale,
+            gop_size: self.gop_size,
+            max_b_frames: self.max_b_frames,
+            keyint_min: self.keyint_min,
+            max_video_frames: self.max_video_frames,
+            max_audio_frames: self.max_audio_frames,
+            max_subtitle_frames: self.max_subtitle_frames,
+            max_muxing_queue_size: self.max_muxing_queue_size,
+            segment_duration_us: self.segment_duration_us,
+            faststart: self.faststart,
+            overwrite: self.overwrite,
+            realtime_drop: self.realtime_drop,
+            interleaved: self.interleaved,
+            frame_interval: self.frame_interval,
+            force_keyframes: self.force_keyframes.clone(),
+            video_quality_ramp: self.video_quality_ramp.clone(),
+            video_codec_opts: self.video_codec_opts.clone(),
+            audio_codec_opts: self.audio_codec_opts.clone(),
+            subtitle_codec_opts: self.subtitle_codec_opts.clone(),
+            encoder_opts: self.encoder_opts.clone(),
+            format_opts: self.format_opts.clone(),
+        })
+    }
+
     /// Sets a **video codec-specific option**.
     ///
     /// These options control **video encoding parameters** such as compression, quality, and speed.
@@ -1145,6 +2381,54 @@ impl Output {
         self
     }
 
+    /// Sets an **encoder option** applied to every encoder of this output.
+    ///
+    /// The option is handed to the encoder (including its private options, such as the
+    /// x264/x265 `preset`, `crf`, `tune` or `x264-params`) before it is opened.
+    /// Options set with [`set_video_codec_opt`](Self::set_video_codec_opt),
+    /// [`set_audio_codec_opt`](Self::set_audio_codec_opt) or
+    /// [`set_subtitle_codec_opt`](Self::set_subtitle_codec_opt) take precedence for their stream type.
+    ///
+    /// An option that is not consumed by any encoder (e.g. a typo like `presett`) is
+    /// reported with a warning instead of being silently ignored.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let output = Output::from("output.mp4")
+    ///     .set_video_codec("libx264")
+    ///     .set_encoder_option("preset", "veryslow")
+    ///     .set_encoder_option("crf", "18")
+    ///     .set_encoder_option("tune", "film");
+    /// ```
+    pub fn set_encoder_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.encoder_opts
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// **Sets multiple encoder options at once.**
+    ///
+    /// See [`set_encoder_option`](Self::set_encoder_option) for how the options are applied.
+    ///
+    /// **Example Usage:**
+    /// ```rust
+    /// let mut opts = HashMap::new();
+    /// opts.insert("preset", "veryslow");
+    /// opts.insert("x264-params", "keyint=60:no-scenecut=1");
+    ///
+    /// let output = Output::from("output.mp4")
+    ///     .set_video_codec("libx264")
+    ///     .set_encoder_options(opts);
+    /// ```
+    pub fn set_encoder_options(mut self, opts: HashMap<impl Into<String>, impl Into<String>>) -> Self {
+        let encoder_opts = self.encoder_opts.get_or_insert_with(HashMap::new);
+        for (key, value) in opts {
+            encoder_opts.insert(key.into(), value.into());
+        }
+        self
+    }
+
     /// Sets a format-specific option for the output container.
     ///
     /// FFmpeg supports various format-specific options that can be passed to the muxer.
@@ -1218,8 +2502,20 @@ impl From<Box<dyn FnMut(&[u8]) -> i32>> for Output {
             url: None,
             write_callback: Some(write_callback_and_format),
             seek_callback: None,
+            ring_buffer: None,
+            packet_channel: None,
+            packet_sink: None,
             frame_pipelines: None,
             stream_maps: vec![],
+            stream_tags: vec![],
+            default_streams: vec![],
+            stream_time_bases: vec![],
+            tags: None,
+            creation_time: None,
+            copy_creation_time: false,
+            strip_metadata_keys: vec![],
+            strip_all_metadata: false,
+            bitstream_filters: vec![],
             format: None,
             video_codec: None,
             audio_codec: None,
@@ -1227,20 +2523,39 @@ impl From<Box<dyn FnMut(&[u8]) -> i32>> for Output {
             start_time_us: None,
             recording_time_us: None,
             stop_time_us: None,
+            pad_to_duration_us: None,
+            output_ts_offset_us: None,
+            audio_sync_offset_us: None,
             framerate: None,
+            video_pix_fmt: None,
             vsync_method: VSyncMethod::VsyncAuto,
+            even_dimensions_policy: EvenDimensionsPolicy::default(),
             bits_per_raw_sample: None,
             audio_sample_rate: None,
             audio_channels: None,
+            audio_channel_layout: None,
             audio_sample_fmt: None,
             video_qscale: None,
             audio_qscale: None,
+            gop_size: None,
+            max_b_frames: None,
+            keyint_min: None,
             max_video_frames: None,
             max_audio_frames: None,
             max_subtitle_frames: None,
+            max_muxing_queue_size: None,
+            segment_duration_us: None,
+            faststart: false,
+            overwrite: true,
+            realtime_drop: false,
+            interleaved: true,
+            frame_interval: None,
+            force_keyframes: None,
+            video_quality_ramp: None,
             video_codec_opts: None,
             audio_codec_opts: None,
             subtitle_codec_opts: None,
+            encoder_opts: None,
             format_opts: None,
         }
     }
@@ -1252,8 +2567,20 @@ impl From<String> for Output {
             url: Some(url),
             write_callback: None,
             seek_callback: None,
+            ring_buffer: None,
+            packet_channel: None,
+            packet_sink: None,
             frame_pipelines: None,
             stream_maps: vec![],
+            stream_tags: vec![],
+            default_streams: vec![],
+            stream_time_bases: vec![],
+            tags: None,
+            creation_time: None,
+            copy_creation_time: false,
+            strip_metadata_keys: vec![],
+            strip_all_metadata: false,
+            bitstream_filters: vec![],
             format: None,
             video_codec: None,
             audio_codec: None,
@@ -1261,20 +2588,39 @@ impl From<String> for Output {
             start_time_us: None,
             recording_time_us: None,
             stop_time_us: None,
+            pad_to_duration_us: None,
+            output_ts_offset_us: None,
+            audio_sync_offset_us: None,
             framerate: None,
+            video_pix_fmt: None,
             vsync_method: VSyncMethod::VsyncAuto,
+            even_dimensions_policy: EvenDimensionsPolicy::default(),
             bits_per_raw_sample: None,
             audio_sample_rate: None,
             audio_channels: None,
+            audio_channel_layout: None,
             audio_sample_fmt: None,
             video_qscale: None,
             audio_qscale: None,
+            gop_size: None,
+            max_b_frames: None,
+            keyint_min: None,
             max_video_frames: None,
             max_audio_frames: None,
             max_subtitle_frames: None,
+            max_muxing_queue_size: None,
+            segment_duration_us: None,
+            faststart: false,
+            overwrite: true,
+            realtime_drop: false,
+            interleaved: true,
+            frame_interval: None,
+            force_keyframes: None,
+            video_quality_ramp: None,
             video_codec_opts: None,
             audio_codec_opts: None,
             subtitle_codec_opts: None,
+            encoder_opts: None,
             format_opts: None,
         }
     }
@@ -1290,6 +2636,8 @@ impl From<&str> for Output {
 pub(crate) struct StreamMap {
     pub(crate) linklabel: String,
     pub(crate) copy: bool,
+    // the `language` tag of the stream the map creates, see `Output::add_audio_track`
+    pub(crate) language: Option<String>,
 }
 
 impl<T: Into<String>> From<T> for StreamMap {
@@ -1297,6 +2645,7 @@ impl<T: Into<String>> From<T> for StreamMap {
         Self {
             linklabel: linklabel.into(),
             copy: false,
+            language: None,
         }
     }
 }
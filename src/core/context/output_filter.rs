@@ -1,4 +1,4 @@
-use crate::core::context::output::VSyncMethod;
+use crate::core::context::output::{EvenDimensionsPolicy, VSyncMethod};
 use crate::core::context::FrameBox;
 use crossbeam_channel::Sender;
 #[cfg(not(feature = "docs-rs"))]
@@ -6,7 +6,7 @@ use ffmpeg_sys_next::{AVChannelLayout, AVChannelLayout__bindgen_ty_1, AVChannelO
 use ffmpeg_sys_next::{AVCodec, AVColorRange, AVColorSpace, AVMediaType, AVPixelFormat, AVRational, AVSampleFormat};
 use std::ptr::{null, null_mut};
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 
 #[derive(Clone)]
 pub(crate) struct OutputFilter {
@@ -53,7 +53,6 @@ pub(crate) const OFILTER_FLAG_AUTOSCALE: u32 = 1 << 2;
 pub(crate) struct OutputFilterOptions {
     pub(crate) name: String,
     pub(crate) enc: *const AVCodec,
-    #[allow(dead_code)]
     pub(crate) format: AVPixelFormat,
     pub(crate) formats: Option<Vec<AVPixelFormat>>,
     pub(crate) audio_format: AVSampleFormat,
@@ -67,6 +66,10 @@ pub(crate) struct OutputFilterOptions {
     pub(crate) color_range: AVColorRange,
     pub(crate) color_ranges: Option<Vec<AVColorRange>>,
     pub(crate) vsync_method: Option<VSyncMethod>,
+    pub(crate) even_dimensions_policy: EvenDimensionsPolicy,
+    // frames dropped and duplicated by the frame rate conversion, reported in the stream stats
+    pub(crate) dropped_frames: Option<Arc<AtomicU64>>,
+    pub(crate) duplicated_frames: Option<Arc<AtomicU64>>,
     pub(crate) sample_rate: i32,
     pub(crate) sample_rates: Option<Vec<i32>>,
     #[cfg(not(feature = "docs-rs"))]
@@ -75,6 +78,8 @@ pub(crate) struct OutputFilterOptions {
     pub(crate) ch_layouts: Option<Vec<AVChannelLayout>>,
     pub(crate) trim_start_us: Option<i64>,
     pub(crate) trim_duration_us: Option<i64>,
+    // pads the stream past the end of its source, see `Output::set_pad_to_duration_us`
+    pub(crate) pad: bool,
     pub(crate) ts_offset: Option<i64>,
     pub(crate) flags: u32,
 }
@@ -97,6 +102,9 @@ impl OutputFilterOptions {
             color_range: AVColorRange::AVCOL_RANGE_UNSPECIFIED,
             color_ranges: None,
             vsync_method: None,
+            even_dimensions_policy: EvenDimensionsPolicy::default(),
+            dropped_frames: None,
+            duplicated_frames: None,
             sample_rate: 0,
             sample_rates: None,
             #[cfg(not(feature = "docs-rs"))]
@@ -110,6 +118,7 @@ impl OutputFilterOptions {
             ch_layouts: None,
             trim_start_us: None,
             trim_duration_us: None,
+            pad: false,
             ts_offset: None,
             flags: 0,
         }
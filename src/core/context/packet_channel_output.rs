@@ -0,0 +1,178 @@
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_sys_next::{AVCodecID, AVFormatContext, AVMediaType, AVPacket, AVRational, AVERROR_EOF, AV_NOPTS_VALUE, AV_PKT_FLAG_KEY};
+use std::sync::{Arc, Mutex};
+
+/// Number of packets the channel of an [`Output::to_packet_channel`](crate::Output::to_packet_channel)
+/// output holds before the muxer thread waits for the receiver.
+const PACKET_CHANNEL_CAPACITY: usize = 64;
+
+/// An encoded packet delivered by an [`Output::to_packet_channel`](crate::Output::to_packet_channel)
+/// output, as it would have been given to the muxer.
+#[derive(Debug, Clone)]
+pub struct EncodedPacket {
+    /// The encoded bytes, in the framing of the encoder (e.g. Annex B for `libx264`).
+    pub data: Vec<u8>,
+    /// The index of the output stream, see [`PacketChannel::streams`].
+    pub stream_index: usize,
+    /// The presentation timestamp in `time_base` units, if known.
+    pub pts: Option<i64>,
+    /// The decoding timestamp in `time_base` units, if known.
+    pub dts: Option<i64>,
+    /// The duration in `time_base` units, `0` if unknown.
+    pub duration: i64,
+    /// The time base of the timestamps, the one of the stream.
+    pub time_base: AVRational,
+    /// Whether the packet is a keyframe, where a receiver can start decoding.
+    pub is_keyframe: bool,
+}
+
+/// An output stream of an [`Output::to_packet_channel`](crate::Output::to_packet_channel) output,
+/// with what a decoder of its packets needs to know.
+#[derive(Debug, Clone)]
+pub struct EncodedStream {
+    /// The index of the stream, as in [`EncodedPacket::stream_index`].
+    pub index: usize,
+    pub codec_type: AVMediaType,
+    pub codec_id: AVCodecID,
+    /// The time base of the timestamps of the packets of the stream.
+    pub time_base: AVRational,
+    /// The out-of-band codec configuration, e.g. the SPS and PPS of H.264 or the
+    /// AudioSpecificConfig of AAC. Empty if the codec has none.
+    pub extradata: Vec<u8>,
+    /// The size of the pictures, `0` for other streams.
+    pub width: i32,
+    pub height: i32,
+    /// The sample rate and the number of channels, `0` for other streams.
+    pub sample_rate: i32,
+    pub channels: i32,
+}
+
+/// Handle to the packets of an [`Output::to_packet_channel`](crate::Output::to_packet_channel) output.
+///
+/// Cloning the handle shares the same channel.
+#[derive(Clone)]
+pub struct PacketChannel {
+    receiver: Receiver<EncodedPacket>,
+    streams: Arc<Mutex<Option<Vec<EncodedStream>>>>,
+}
+
+impl PacketChannel {
+    pub(crate) fn new() -> (Self, PacketSink) {
+        let (sender, receiver) = crossbeam_channel::bounded(PACKET_CHANNEL_CAPACITY);
+        let streams = Arc::new(Mutex::new(None));
+        let sink = PacketSink {
+            sender,
+            streams: streams.clone(),
+        };
+        (Self { receiver, streams }, sink)
+    }
+
+    /// Returns the receiver of the packets. It disconnects once the job has delivered all
+    /// its packets, so iterating over it ends with the job.
+    ///
+    /// Dropping every receiver stops the output: the packets still to come are discarded.
+    pub fn receiver(&self) -> Receiver<EncodedPacket> {
+        self.receiver.clone()
+    }
+
+    /// Returns the streams of the output, with their codec configuration (`extradata`).
+    ///
+    /// `None` until the encoders are opened. The streams are always set before the first
+    /// packet is sent, so they are available once a packet has been received.
+    pub fn streams(&self) -> Option<Vec<EncodedStream>> {
+        self.streams.lock().unwrap().clone()
+    }
+}
+
+/// The sending side of a [`PacketChannel`], owned by the muxer thread.
+pub(crate) struct PacketSink {
+    sender: Sender<EncodedPacket>,
+    streams: Arc<Mutex<Option<Vec<EncodedStream>>>>,
+}
+
+impl PacketSink {
+    /// Publishes the streams of `fmt_ctx`, once its header is written.
+    pub(crate) unsafe fn set_streams(&self, fmt_ctx: *const AVFormatContext) {
+        let mut streams = Vec::new();
+        for i in 0..(*fmt_ctx).nb_streams as usize {
+            let stream = *(*fmt_ctx).streams.add(i);
+            let codecpar = (*stream).codecpar;
+            streams.push(EncodedStream {
+                index: i,
+                codec_type: (*codecpar).codec_type,
+                codec_id: (*codecpar).codec_id,
+                time_base: (*stream).time_base,
+                extradata: bytes((*codecpar).extradata, (*codecpar).extradata_size),
+                width: (*codecpar).width,
+                height: (*codecpar).height,
+                sample_rate: (*codecpar).sample_rate,
+                channels: (*codecpar).ch_layout.nb_channels,
+            });
+        }
+        *self.streams.lock().unwrap() = Some(streams);
+    }
+
+    /// Sends a copy of `pkt`, blocking while the channel is full. Returns `AVERROR_EOF`
+    /// once the receivers are gone, like a muxer that does not take more packets.
+    pub(crate) unsafe fn send(&self, pkt: *const AVPacket) -> i32 {
+        let timestamp = |ts: i64| if ts == AV_NOPTS_VALUE { None } else { Some(ts) };
+        let packet = EncodedPacket {
+            data: bytes((*pkt).data, (*pkt).size),
+            stream_index: (*pkt).stream_index as usize,
+            pts: timestamp((*pkt).pts),
+            dts: timestamp((*pkt).dts),
+            duration: (*pkt).duration,
+            time_base: (*pkt).time_base,
+            is_keyframe: (*pkt).flags & AV_PKT_FLAG_KEY != 0,
+        };
+        match self.sender.send(packet) {
+            Ok(()) => 0,
+            Err(_) => AVERROR_EOF,
+        }
+    }
+}
+
+unsafe fn bytes(data: *const u8, size: i32) -> Vec<u8> {
+    if data.is_null() || size <= 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(data, size as usize).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::output::Output;
+    use ffmpeg_sys_next::AVCodecID::AV_CODEC_ID_H264;
+
+    #[test]
+    fn test_packet_channel_output() {
+        let output = Output::to_packet_channel()
+            .add_stream_map("0:v")
+            .set_video_codec("libx264")
+            .set_recording_time_us(1_000_000);
+        let channel = output.packet_channel().unwrap();
+
+        let scheduler = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap();
+
+        let packets: Vec<_> = channel.receiver().iter().collect();
+        assert!(scheduler.wait().is_ok());
+
+        let streams = channel.streams().unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].codec_id, AV_CODEC_ID_H264);
+        // with no container to carry them, the SPS and PPS are delivered out of band
+        assert!(!streams[0].extradata.is_empty());
+
+        assert!(!packets.is_empty());
+        assert!(packets[0].is_keyframe);
+        assert!(packets.iter().all(|packet| packet.stream_index == 0 && !packet.data.is_empty()));
+        assert!(packets.windows(2).all(|pair| pair[0].dts <= pair[1].dts));
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Size of an MPEG-TS packet, the unit an MPEG-TS ring buffer is trimmed by.
+const TS_PACKET_SIZE: u64 = 188;
+
+/// Handle to the bytes kept by an [`Output::to_ring_buffer`](crate::Output::to_ring_buffer) output.
+///
+/// Cloning the handle shares the same buffer.
+#[derive(Clone)]
+pub struct RingBuffer {
+    state: Arc<Mutex<RingState>>,
+}
+
+impl RingBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RingState::new(capacity))),
+        }
+    }
+
+    /// Returns a copy of the most recent output that can be played on its own.
+    ///
+    /// For fragmented MP4 it is the initialization segment (`ftyp` + `moov`) followed by the
+    /// most recent complete fragments. For MPEG-TS it is the most recent whole TS packets.
+    /// Other formats are copied as they are, which is rarely playable.
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.state.lock().unwrap().snapshot()
+    }
+
+    /// Returns the number of bytes currently held.
+    pub fn len(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.header.len() + state.data.len()
+    }
+
+    /// Returns `true` when nothing has been written yet, or everything written was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of bytes held.
+    pub fn capacity(&self) -> usize {
+        self.state.lock().unwrap().capacity
+    }
+
+    /// Returns the number of bytes written by the muxer since the output was opened.
+    pub fn total_written(&self) -> u64 {
+        self.state.lock().unwrap().written
+    }
+
+    pub(crate) fn write(&self, buf: &[u8]) {
+        self.state.lock().unwrap().write(buf)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Layout {
+    /// ISO BMFF boxes: the boxes before the first `moof` are kept, fragments are dropped whole.
+    Mp4,
+    /// 188-byte MPEG-TS packets, dropped whole.
+    MpegTs,
+    /// Anything else, dropped byte by byte.
+    Raw,
+}
+
+struct RingState {
+    capacity: usize,
+    layout: Option<Layout>,
+    /// Initialization segment of a fragmented MP4, kept in every snapshot.
+    header: Vec<u8>,
+    data: VecDeque<u8>,
+    /// Absolute offset of `data[0]` in the output.
+    data_offset: u64,
+    /// Absolute offset of the next byte written.
+    written: u64,
+    /// Absolute offsets where a fragment starts.
+    fragment_starts: VecDeque<u64>,
+    /// Absolute offset where the last complete fragment ends.
+    fragment_end: u64,
+    /// Set while the bytes of a fragment larger than the capacity are discarded.
+    skipping: bool,
+
+    /// Bytes of a box header split across writes.
+    box_header: Vec<u8>,
+    /// Bytes left in the current top-level box.
+    box_remaining: u64,
+    box_is_mdat: bool,
+}
+
+impl RingState {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            layout: None,
+            header: Vec::new(),
+            data: VecDeque::new(),
+            data_offset: 0,
+            written: 0,
+            fragment_starts: VecDeque::new(),
+            fragment_end: 0,
+            skipping: false,
+            box_header: Vec::new(),
+            box_remaining: 0,
+            box_is_mdat: false,
+        }
+    }
+
+    fn write(&mut self, mut buf: &[u8]) {
+        let layout = *self.layout.get_or_insert_with(|| {
+            if buf.len() >= 8 && &buf[4..8] == b"ftyp" {
+                Layout::Mp4
+            } else if buf.first() == Some(&0x47) {
+                Layout::MpegTs
+            } else {
+                Layout::Raw
+            }
+        });
+        if layout != Layout::Mp4 {
+            self.store(buf);
+            return;
+        }
+
+        while !buf.is_empty() {
+            if self.box_remaining == 0 {
+                buf = self.read_box_header(buf);
+                continue;
+            }
+            let len = self.box_remaining.min(buf.len() as u64);
+            self.store(&buf[..len as usize]);
+            buf = &buf[len as usize..];
+            if self.box_remaining != u64::MAX {
+                self.box_remaining -= len;
+            }
+            if self.box_remaining == 0 && self.box_is_mdat {
+                self.fragment_end = self.written;
+            }
+        }
+    }
+
+    /// Collects the header of the next top-level box from `buf` and returns the bytes after it.
+    fn read_box_header<'a>(&mut self, buf: &'a [u8]) -> &'a [u8] {
+        let header_len = if self.box_header.len() >= 4 && self.box_header[..4] == [0, 0, 0, 1] {
+            16
+        } else {
+            8
+        };
+        let len = (header_len - self.box_header.len()).min(buf.len());
+        self.box_header.extend_from_slice(&buf[..len]);
+        if self.box_header.len() < header_len
+            || (header_len == 8 && self.box_header[..4] == [0, 0, 0, 1])
+        {
+            // incomplete, or a 64-bit size whose remaining 8 bytes are still to be read
+            return &buf[len..];
+        }
+
+        let box_header = std::mem::take(&mut self.box_header);
+        let box_type = &box_header[4..8];
+        let size = match header_len {
+            16 => u64::from_be_bytes(box_header[8..16].try_into().unwrap()),
+            _ => u32::from_be_bytes(box_header[..4].try_into().unwrap()) as u64,
+        };
+        if box_type == b"moof" {
+            self.fragment_starts.push_back(self.written);
+            if self.skipping {
+                self.skipping = false;
+                self.data_offset = self.written;
+            }
+        }
+        self.box_is_mdat = box_type == b"mdat";
+        self.store(&box_header);
+        self.box_remaining = match size {
+            // the box extends to the end of the output
+            0 => u64::MAX,
+            size => size.saturating_sub(header_len as u64),
+        };
+        if self.box_remaining == 0 && self.box_is_mdat {
+            self.fragment_end = self.written;
+        }
+        &buf[len..]
+    }
+
+    fn store(&mut self, buf: &[u8]) {
+        let start = self.written;
+        self.written += buf.len() as u64;
+        if self.layout == Some(Layout::Mp4) && self.fragment_starts.is_empty() {
+            self.header.extend_from_slice(buf);
+            self.data_offset = self.written;
+            return;
+        }
+        if self.skipping {
+            return;
+        }
+        // `data_offset` may be ahead of `start` when the rest of a TS packet was dropped in advance
+        let skip = self.data_offset.saturating_sub(start).min(buf.len() as u64) as usize;
+        self.data.extend(&buf[skip..]);
+        self.evict();
+    }
+
+    /// Drops the oldest data until the buffer fits its capacity again.
+    fn evict(&mut self) {
+        while !self.data.is_empty() && self.header.len() + self.data.len() > self.capacity {
+            let excess = (self.header.len() + self.data.len() - self.capacity) as u64;
+            let new_offset = match self.layout {
+                Some(Layout::Mp4) => {
+                    let next_start = self.fragment_starts.iter().copied().find(|start| *start > self.data_offset);
+                    match next_start {
+                        Some(next_start) => next_start,
+                        None => {
+                            // the fragment being written alone exceeds the capacity
+                            self.skipping = true;
+                            self.written
+                        }
+                    }
+                }
+                Some(Layout::MpegTs) => {
+                    let offset = self.data_offset + excess;
+                    offset.div_ceil(TS_PACKET_SIZE) * TS_PACKET_SIZE
+                }
+                _ => self.data_offset + excess,
+            };
+            let drop_len = (new_offset - self.data_offset).min(self.data.len() as u64) as usize;
+            self.data.drain(..drop_len);
+            self.data_offset = new_offset;
+            while self.fragment_starts.len() > 1 && self.fragment_starts[0] < self.data_offset {
+                self.fragment_starts.pop_front();
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        let data_len = match self.layout {
+            Some(Layout::Mp4) => self.fragment_end.saturating_sub(self.data_offset),
+            Some(Layout::MpegTs) => (self.written - self.written % TS_PACKET_SIZE).saturating_sub(self.data_offset),
+            _ => self.data.len() as u64,
+        }
+        .min(self.data.len() as u64) as usize;
+
+        let mut snapshot = Vec::with_capacity(self.header.len() + data_len);
+        snapshot.extend_from_slice(&self.header);
+        snapshot.extend(self.data.range(..data_len));
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FfmpegContext, Output};
+
+    fn mp4_box(box_type: &[u8; 4], payload_len: usize) -> Vec<u8> {
+        let mut bytes = ((payload_len + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(box_type);
+        bytes.resize(payload_len + 8, box_type[0]);
+        bytes
+    }
+
+    #[test]
+    fn test_mp4_fragments() {
+        let init = [mp4_box(b"ftyp", 16), mp4_box(b"moov", 40)].concat();
+        let fragment = [mp4_box(b"moof", 8), mp4_box(b"mdat", 92)].concat();
+        let ring = RingBuffer::new(init.len() + 2 * fragment.len() + 10);
+
+        ring.write(&init);
+        // writes do not follow box boundaries
+        let fragments = fragment.repeat(5);
+        for chunk in fragments.chunks(7) {
+            ring.write(chunk);
+        }
+        assert!(ring.len() <= ring.capacity());
+        assert_eq!(ring.snapshot(), [init.clone(), fragment.clone(), fragment.clone()].concat());
+
+        // the oldest fragment makes room for the next one, which is not complete yet
+        ring.write(&fragment[..50]);
+        assert_eq!(ring.snapshot(), [init, fragment].concat());
+    }
+
+    #[test]
+    fn test_mpegts_packets() {
+        let ring = RingBuffer::new(1000);
+        let packets: Vec<u8> = (0..10u8).flat_map(|i| {
+            let mut packet = vec![i; 188];
+            packet[0] = 0x47;
+            packet
+        }).collect();
+        for chunk in packets.chunks(100) {
+            ring.write(chunk);
+        }
+        let snapshot = ring.snapshot();
+        assert!(ring.len() <= 1000);
+        assert_eq!(snapshot.len() % 188, 0);
+        assert_eq!(snapshot, packets[packets.len() - snapshot.len()..]);
+        assert_eq!(ring.total_written(), packets.len() as u64);
+    }
+
+    #[test]
+    fn test_ring_buffer_output() {
+        let output = Output::to_ring_buffer(8 * 1024);
+        let ring_buffer = output.ring_buffer().unwrap();
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(ring_buffer.total_written() > ring_buffer.capacity() as u64);
+        assert!(ring_buffer.len() <= ring_buffer.capacity());
+        let snapshot = ring_buffer.snapshot();
+        assert!(!snapshot.is_empty());
+        assert_eq!(snapshot.len() % 188, 0);
+        assert!(snapshot.chunks(188).all(|packet| packet[0] == 0x47));
+    }
+}
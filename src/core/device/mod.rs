@@ -4,6 +4,30 @@ mod macos_avfoundation;
 #[cfg(not(target_os = "macos"))]
 mod avdevice;
 
+use crate::error::FindDevicesError;
+use ffmpeg_sys_next::{
+    av_find_input_format, av_input_audio_device_next, av_input_video_device_next,
+    avdevice_free_list_devices, avdevice_list_input_sources, AVInputFormat,
+};
+use std::ffi::{CStr, CString};
+use std::ptr::{null, null_mut};
+
+/// Input formats commonly used to capture from devices, used to report a missing
+/// device backend instead of an unknown format.
+pub(crate) const CAPTURE_FORMATS: &[&str] = &[
+    "x11grab", "kmsgrab", "fbdev", "v4l2", "video4linux2", "alsa", "pulse", "jack", "oss",
+    "avfoundation", "dshow", "gdigrab", "vfwcap", "decklink", "android_camera", "openal",
+];
+
+/// A device reported by an input device backend.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The name to pass as the device of [`Input::from_device`](crate::Input::from_device).
+    pub name: String,
+    /// A human-readable description of the device.
+    pub description: String,
+}
+
 /// Retrieves a list of available video input devices (e.g., cameras) on the system.
 ///
 /// This function attempts to query the available video devices that can be used as input,
@@ -80,6 +104,94 @@ pub fn get_input_audio_devices() -> crate::error::Result<Vec<String>> {
     avdevice::find_input_audio_device_list()
 }
 
+/// Returns the names of the input device formats (capture backends) compiled into FFmpeg,
+/// e.g. `["alsa", "lavfi", "v4l2", "x11grab"]` on Linux.
+///
+/// Any of these can be used as the `format` of [`Input::from_device`](crate::Input::from_device).
+pub fn get_input_device_formats() -> Vec<String> {
+    crate::core::initialize_ffmpeg();
+
+    let mut formats = Vec::new();
+    unsafe {
+        let mut format: *const AVInputFormat = null();
+        loop {
+            format = av_input_video_device_next(format);
+            if format.is_null() {
+                break;
+            }
+            formats.push(CStr::from_ptr((*format).name).to_string_lossy().into_owned());
+        }
+        loop {
+            format = av_input_audio_device_next(format);
+            if format.is_null() {
+                break;
+            }
+            formats.push(CStr::from_ptr((*format).name).to_string_lossy().into_owned());
+        }
+    }
+    formats.sort();
+    formats.dedup();
+    formats
+}
+
+/// Lists the devices of an input device format, like `ffmpeg -sources <format>`.
+///
+/// Not every backend can enumerate its devices: e.g. `dshow`, `v4l2`, `alsa` and `pulse`
+/// can, while `x11grab` and `avfoundation` cannot and return a
+/// [`FindDevicesError::NotImplemented`] error.
+///
+/// # Example
+///
+/// ```rust
+/// for device in list_devices("v4l2")? {
+///     println!("{}: {}", device.name, device.description);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns [`FindDevicesError::FormatNotFound`] if `format` is not compiled into FFmpeg,
+/// or the error reported by the backend.
+pub fn list_devices(format: &str) -> crate::error::Result<Vec<DeviceInfo>> {
+    crate::core::initialize_ffmpeg();
+
+    let format_cstr = CString::new(format)?;
+    unsafe {
+        let input_format = av_find_input_format(format_cstr.as_ptr());
+        if input_format.is_null() {
+            return Err(FindDevicesError::FormatNotFound(format.to_string()).into());
+        }
+
+        let mut device_list = null_mut();
+        let ret = avdevice_list_input_sources(input_format, null(), null_mut(), &mut device_list);
+        if ret < 0 {
+            avdevice_free_list_devices(&mut device_list);
+            return Err(FindDevicesError::from(ret).into());
+        }
+
+        let mut devices = Vec::new();
+        if !device_list.is_null() {
+            for i in 0..(*device_list).nb_devices {
+                let device = *(*device_list).devices.add(i as usize);
+                let to_string = |s: *const std::ffi::c_char| {
+                    if s.is_null() {
+                        String::new()
+                    } else {
+                        CStr::from_ptr(s).to_string_lossy().into_owned()
+                    }
+                };
+                devices.push(DeviceInfo {
+                    name: to_string((*device).device_name),
+                    description: to_string((*device).device_description),
+                });
+            }
+        }
+        avdevice_free_list_devices(&mut device_list);
+
+        Ok(devices)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +207,12 @@ mod tests {
         let audio_devices = get_input_audio_devices().unwrap();
         println!("{:?}", audio_devices);
     }
+
+    #[test]
+    fn test_list_devices_unknown_format() {
+        assert!(matches!(
+            list_devices("no_such_device"),
+            Err(crate::error::Error::FindDevices(FindDevicesError::FormatNotFound(_)))
+        ));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,821 @@
+//! A [`FrameFilter`] that turns the audio of a job into video: a scrolling waveform or a
+//! spectrogram rendered at a given size and frame rate, e.g. to turn a podcast into an
+//! audiogram.
+//!
+//! Frame pipelines are typed: an audio pipeline hands its frames to an audio encoder or filter
+//! graph input, so the frames it renders cannot leave through it. The visualizer is therefore
+//! split in two, joined by a channel:
+//! - the [`AudioVisualizerFilter`] itself goes into the audio pipeline. It passes the audio
+//!   through unchanged, and renders the video frames as the audio arrives;
+//! - the source returned by [`AudioVisualizerFilter::video_source`] goes into the video
+//!   pipeline of the same output and emits the rendered frames in place of the frames of that
+//!   stream, which only carries them to the encoder. Any video stream will do, e.g. a small
+//!   `nullsrc` input, whose frames are dropped; its length does not matter either, the video
+//!   ends with the audio.
+//!
+//! The video is on the audio timeline, whatever the audio and video rates: frame `n` has the
+//! timestamp `n / frame_rate`, and shows the [window](AudioVisualizerFilter::set_window) of audio
+//! ending where the frame ends. It is rendered as soon as the audio has reached that point, and
+//! the last frames when the audio ends. Neither side ever waits for the other: the frames are
+//! queued until the video pipeline takes them.
+//!
+//! # Example
+//! ```rust,ignore
+//! let mut visualizer = AudioVisualizerFilter::new(VisualizerMode::Waveform)
+//!     .set_size(1280, 720)
+//!     .set_frame_rate(AVRational { num: 30, den: 1 })
+//!     .set_area(0, 260, 1280, 200)
+//!     .set_color(255, 200, 0);
+//! let source = visualizer.video_source().unwrap();
+//!
+//! let context = FfmpegContext::builder()
+//!     .input("episode.mp3")
+//!     // carries the rendered frames
+//!     .input(Input::from("nullsrc=size=16x16:rate=1").set_format("lavfi"))
+//!     .output(
+//!         Output::from("audiogram.mp4")
+//!             .add_stream_map("0:a")
+//!             .add_stream_map("1:v")
+//!             .add_frame_pipeline(FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO).filter("visualizer", Box::new(visualizer)))
+//!             .add_frame_pipeline(FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("source", Box::new(source))),
+//!     )
+//!     .build()?;
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{AudioSample, FramePlanes};
+use crate::util::ffmpeg_utils::{av_err2str, sample_format};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_RGB24, AV_PIX_FMT_YUV420P};
+use ffmpeg_sys_next::AVSampleFormat::*;
+use ffmpeg_sys_next::{
+    av_frame_get_buffer, av_get_packed_sample_fmt, av_inv_q, av_rescale_q, av_sample_fmt_is_planar, AVMediaType,
+    AVPixelFormat, AVRational, AVSampleFormat, AV_NOPTS_VALUE,
+};
+use log::debug;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Audio kept for rendering, in seconds.
+const MAX_HISTORY_SECONDS: i64 = 30;
+/// Gap or overlap between consecutive audio frames, in seconds, above which the history is
+/// padded with silence or restarted instead of being appended to.
+const MAX_DRIFT_SECONDS: f64 = 0.1;
+/// Number of samples of each spectrogram column.
+const FFT_SIZE: usize = 1024;
+/// Dynamic range of the spectrogram, in dB.
+const SPECTRUM_RANGE_DB: f32 = 80.0;
+
+/// What [`AudioVisualizerFilter`] draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisualizerMode {
+    /// The waveform of the window, oldest sample on the left.
+    Waveform,
+    /// A spectrogram of the window, oldest column on the left and low frequencies at the bottom,
+    /// blended over the background by intensity.
+    Spectrum,
+}
+
+/// Renders video frames from the audio of a stream, see the [module documentation](self).
+pub struct AudioVisualizerFilter {
+    mode: VisualizerMode,
+    color: [u8; 3],
+    background: [u8; 3],
+    window: Duration,
+    area: Option<(usize, usize, usize, usize)>,
+    width: usize,
+    height: usize,
+    frame_rate: AVRational,
+
+    history: AudioHistory,
+    spectrum: SpectrumCache,
+    converter: FrameConverter,
+    // the `rgb24` picture the frames are drawn on
+    canvas: Option<Frame>,
+    // number of the next frame to render
+    next_frame: i64,
+    sender: Option<Sender<Frame>>,
+    receiver: Option<Receiver<Frame>>,
+}
+
+/// The video side of an [`AudioVisualizerFilter`], see the [module documentation](self).
+pub struct VisualizerSourceFilter {
+    receiver: Receiver<Frame>,
+    ended: bool,
+}
+
+/// Spectrogram columns already computed.
+#[derive(Default)]
+struct SpectrumCache {
+    /// Column number and intensity of each frequency bin, sorted by column.
+    columns: VecDeque<(i64, Vec<f32>)>,
+    /// `(width, samples per column)` the columns were computed for.
+    key: (usize, i64),
+}
+
+/// Mono samples of the most recent audio.
+#[derive(Default)]
+struct AudioHistory {
+    sample_rate: i32,
+    /// Index on the audio timeline, in samples, of `samples[0]`.
+    start: i64,
+    samples: VecDeque<f32>,
+}
+
+impl AudioVisualizerFilter {
+    /// Creates a visualizer rendering 1280x720 frames at 25 fps, drawing in `mode` over the
+    /// whole frame, in white on black, with a 2 second window.
+    pub fn new(mode: VisualizerMode) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        Self {
+            mode,
+            color: [255, 255, 255],
+            background: [0, 0, 0],
+            window: Duration::from_secs(2),
+            area: None,
+            width: 1280,
+            height: 720,
+            frame_rate: AVRational { num: 25, den: 1 },
+            history: AudioHistory::default(),
+            spectrum: SpectrumCache::default(),
+            converter: FrameConverter::new(),
+            canvas: None,
+            next_frame: 0,
+            sender: Some(sender),
+            receiver: Some(receiver),
+        }
+    }
+
+    /// Takes the filter emitting the rendered frames, to add to a video pipeline of the same
+    /// output. Returns `None` if it was already taken. Nothing is rendered when it was not
+    /// taken before the job starts.
+    pub fn video_source(&mut self) -> Option<VisualizerSourceFilter> {
+        self.receiver.take().map(|receiver| VisualizerSourceFilter { receiver, ended: false })
+    }
+
+    /// Sets the size of the rendered `yuv420p` frames. Defaults to 1280x720; most encoders
+    /// need an even width and height.
+    pub fn set_size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Sets the rate of the rendered frames. Defaults to 25 fps.
+    pub fn set_frame_rate(mut self, frame_rate: AVRational) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    /// Sets the color of the waveform, or of the loudest spectrogram bins.
+    pub fn set_color(mut self, r: u8, g: u8, b: u8) -> Self {
+        self.color = [r, g, b];
+        self
+    }
+
+    /// Sets the color of the frames around and behind the drawing. Defaults to black.
+    pub fn set_background(mut self, r: u8, g: u8, b: u8) -> Self {
+        self.background = [r, g, b];
+        self
+    }
+
+    /// Sets the duration of audio shown across the drawing area. Defaults to 2 seconds.
+    pub fn set_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Draws in the rectangle at (`x`, `y`) of `width` x `height` pixels instead of the whole
+    /// frame. The rectangle is clipped to the frame.
+    pub fn set_area(mut self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        self.area = Some((x, y, width, height));
+        self
+    }
+
+    /// Renders the frames whose audio has arrived, and at the end of the audio the frames
+    /// starting before it ends.
+    fn render(&mut self, at_end: bool) -> Result<(), String> {
+        let sample_rate = self.history.sample_rate;
+        if sample_rate <= 0 {
+            return Ok(());
+        }
+        let frame_base = unsafe { av_inv_q(self.frame_rate) };
+        let sample_base = AVRational { num: 1, den: sample_rate };
+        while self.sender.is_some() {
+            let (start, end) = unsafe {
+                (
+                    av_rescale_q(self.next_frame, frame_base, sample_base),
+                    av_rescale_q(self.next_frame + 1, frame_base, sample_base),
+                )
+            };
+            let available = self.history.end();
+            if end > available && !(at_end && start < available) {
+                break;
+            }
+            let frame = self.render_frame(end)?;
+            self.send(frame);
+            self.next_frame += 1;
+        }
+        Ok(())
+    }
+
+    /// Renders frame `next_frame`, showing the window ending at sample `end`.
+    fn render_frame(&mut self, end: i64) -> Result<Frame, String> {
+        let rate = self.history.sample_rate as f64;
+        let window = ((self.window.as_secs_f64() * rate) as i64).max(1);
+        // a spectrogram column also needs the samples before the start of the window
+        let lead = if self.mode == VisualizerMode::Spectrum { FFT_SIZE as i64 } else { 0 };
+        let samples = self.history.range(end - window - lead, end);
+
+        let mut canvas = match self.canvas.take() {
+            Some(canvas) => canvas,
+            None => new_frame(AV_PIX_FMT_RGB24, self.width, self.height)?,
+        };
+        {
+            let mut rows = canvas.plane_u8_mut(0)?;
+            for row in rows.iter_mut() {
+                row.chunks_exact_mut(3).for_each(|pixel| pixel.copy_from_slice(&self.background));
+            }
+            let area = self.area.unwrap_or((0, 0, self.width, self.height));
+            if let Some(area) = clip_area(area, self.width, self.height) {
+                match self.mode {
+                    VisualizerMode::Waveform => draw_waveform(&mut rows, area, &samples, self.color),
+                    VisualizerMode::Spectrum => {
+                        self.spectrum.draw(&mut rows, area, end, &samples, self.history.end(), self.color)
+                    }
+                }
+            }
+        }
+
+        // a new frame every time: the previous ones may still be queued
+        let mut frame = new_frame(AV_PIX_FMT_YUV420P, self.width, self.height)?;
+        let converted = self.converter.convert_into(&canvas, &mut frame);
+        self.canvas = Some(canvas);
+        converted?;
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).pts = self.next_frame;
+            (*f).duration = 1;
+            (*f).time_base = av_inv_q(self.frame_rate);
+            (*f).sample_aspect_ratio = AVRational { num: 1, den: 1 };
+        }
+        Ok(frame)
+    }
+
+    /// Queues a frame for the video source, forgetting the source once it is dropped.
+    fn send(&mut self, frame: Frame) {
+        if let Some(sender) = &self.sender {
+            if sender.send(frame).is_err() {
+                debug!("Audio visualizer source dropped, nothing is rendered anymore");
+                self.sender = None;
+            }
+        }
+    }
+
+    /// Renders the last frames and ends the video.
+    fn finish(&mut self) -> Result<(), String> {
+        self.render(true)?;
+        if self.sender.is_some() {
+            let mut eof = unsafe { Frame::empty() };
+            if !eof.as_ptr().is_null() {
+                unsafe {
+                    (*eof.as_mut_ptr()).pts = self.next_frame;
+                    (*eof.as_mut_ptr()).time_base = av_inv_q(self.frame_rate);
+                }
+            }
+            self.send(eof);
+        }
+        // disconnects the source
+        self.sender = None;
+        Ok(())
+    }
+}
+
+impl FrameFilter for AudioVisualizerFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_AUDIO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if self.window.is_zero() {
+            return Err("Audio visualizer window must not be zero".to_string());
+        }
+        if self.width == 0 || self.height == 0 || self.width > i32::MAX as usize || self.height > i32::MAX as usize {
+            return Err(format!("Invalid audio visualizer size {}x{}", self.width, self.height));
+        }
+        if self.frame_rate.num <= 0 || self.frame_rate.den <= 0 {
+            return Err(format!(
+                "Invalid audio visualizer frame rate {}/{}",
+                self.frame_rate.num, self.frame_rate.den
+            ));
+        }
+        // nobody reads the frames of a source that was not taken
+        if self.receiver.take().is_some() {
+            debug!("Audio visualizer has no video source, nothing is rendered");
+            self.sender = None;
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        if frame.as_ptr().is_null() || self.sender.is_none() {
+            return Ok(Some(frame));
+        }
+        if frame.is_empty() {
+            self.finish()?;
+            return Ok(Some(frame));
+        }
+
+        let (pts, time_base, sample_rate) = unsafe {
+            let f = frame.as_ptr();
+            ((*f).pts, (*f).time_base, (*f).sample_rate)
+        };
+        if sample_rate > 0 {
+            let start = if pts != AV_NOPTS_VALUE && time_base.num > 0 && time_base.den > 0 {
+                Some(unsafe { av_rescale_q(pts, time_base, AVRational { num: 1, den: sample_rate }) })
+            } else {
+                None
+            };
+            let samples = mono_samples(&frame)?;
+            self.history.append(sample_rate, start, &samples);
+            self.render(false)?;
+        }
+        Ok(Some(frame))
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        // the source ends the video on its own once disconnected
+        self.sender = None;
+    }
+}
+
+impl FrameFilter for VisualizerSourceFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, _frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        // the stream only carries the rendered frames, including its end
+        Ok(None)
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        if self.ended {
+            return Ok(None);
+        }
+        match self.receiver.try_recv() {
+            Ok(frame) => {
+                self.ended = frame.is_empty();
+                Ok(Some(frame))
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                // the audio side stopped without ending the video
+                self.ended = true;
+                Ok(Some(unsafe { Frame::empty() }))
+            }
+        }
+    }
+}
+
+impl SpectrumCache {
+    /// Draws the spectrogram of `samples` into the rows of an `rgb24` frame. The samples end at
+    /// sample `window_end` and start `FFT_SIZE` samples before the window. Columns after
+    /// `available_end` are not drawn.
+    fn draw(
+        &mut self,
+        rows: &mut [&mut [u8]],
+        area: (usize, usize, usize, usize),
+        window_end: i64,
+        samples: &[f32],
+        available_end: i64,
+        color: [u8; 3],
+    ) {
+        let (x0, y0, width, height) = area;
+        let window = samples.len() as i64 - FFT_SIZE as i64;
+        let column_len = (window / width as i64).max(1);
+        if self.key != (width, column_len) {
+            self.columns.clear();
+            self.key = (width, column_len);
+        }
+
+        // column `c` covers the samples up to `(c + 1) * column_len`
+        let last = window_end / column_len - 1;
+        let first = last - width as i64 + 1;
+        self.columns.retain(|(column, _)| (first..=last).contains(column));
+        let samples_start = window_end - samples.len() as i64;
+        for column in first..=last {
+            let column_end = (column + 1) * column_len;
+            if column_end > available_end {
+                break;
+            }
+            if let Err(position) = self.columns.binary_search_by_key(&column, |(c, _)| *c) {
+                let to = ((column_end - samples_start).max(0) as usize).min(samples.len());
+                let from = to.saturating_sub(FFT_SIZE);
+                self.columns.insert(position, (column, spectrum_column(&samples[from..to])));
+            }
+        }
+
+        let bins = FFT_SIZE / 2;
+        for y in 0..height {
+            let bin = (height - 1 - y) * bins / height;
+            let row = &mut rows[y0 + y];
+            for (column, intensities) in &self.columns {
+                let x = x0 + (column - first) as usize;
+                let intensity = intensities[bin];
+                let blend = spectrum_color(intensity, color);
+                for (channel, value) in row[x * 3..x * 3 + 3].iter_mut().enumerate() {
+                    *value = (*value as f32 * (1.0 - intensity) + blend[channel] * intensity).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+impl AudioHistory {
+    /// Index of the sample after the last one received.
+    fn end(&self) -> i64 {
+        self.start + self.samples.len() as i64
+    }
+
+    /// Appends the samples of a frame starting at sample `start`, or right after the previous
+    /// frame when its timestamp is unknown.
+    fn append(&mut self, sample_rate: i32, start: Option<i64>, samples: &[f32]) {
+        let drift = (MAX_DRIFT_SECONDS * sample_rate as f64) as i64;
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.samples.clear();
+            self.start = start.unwrap_or(0);
+        } else if let Some(start) = start {
+            let expected = self.end();
+            if start > expected + drift {
+                // a gap, filled with silence up to the history size
+                let gap = (start - expected).min(MAX_HISTORY_SECONDS * sample_rate as i64);
+                self.samples.extend(std::iter::repeat_n(0.0, gap as usize));
+                self.start = start - self.samples.len() as i64;
+            } else if start < expected - drift {
+                // the timeline restarted
+                self.samples.clear();
+                self.start = start;
+            }
+        }
+        self.samples.extend(samples);
+
+        let max_len = (MAX_HISTORY_SECONDS * sample_rate as i64) as usize;
+        if self.samples.len() > max_len {
+            let excess = self.samples.len() - max_len;
+            self.samples.drain(..excess);
+            self.start += excess as i64;
+        }
+    }
+
+    /// Returns the samples from `start` to `end`, silence where none were received.
+    fn range(&self, start: i64, end: i64) -> Vec<f32> {
+        (start..end)
+            .map(|index| {
+                let offset = index - self.start;
+                if offset < 0 {
+                    0.0
+                } else {
+                    self.samples.get(offset as usize).copied().unwrap_or(0.0)
+                }
+            })
+            .collect()
+    }
+}
+
+fn draw_waveform(rows: &mut [&mut [u8]], area: (usize, usize, usize, usize), samples: &[f32], color: [u8; 3]) {
+    let (x0, y0, width, height) = area;
+    let center = (height - 1) as f32 / 2.0;
+    let columns: Vec<(usize, usize)> = waveform_columns(samples, width)
+        .into_iter()
+        .map(|(min, max)| {
+            let top = (center - max.clamp(-1.0, 1.0) * center).round() as usize;
+            let bottom = (center - min.clamp(-1.0, 1.0) * center).round() as usize;
+            (top, bottom)
+        })
+        .collect();
+    for y in 0..height {
+        let row = &mut rows[y0 + y];
+        for (x, (top, bottom)) in columns.iter().enumerate() {
+            if (*top..=*bottom).contains(&y) {
+                row[(x0 + x) * 3..(x0 + x) * 3 + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Clips `area` to a `width` x `height` frame, `None` when nothing is left.
+fn clip_area(area: (usize, usize, usize, usize), width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+    let (x, y, w, h) = area;
+    let w = w.min(width.saturating_sub(x));
+    let h = h.min(height.saturating_sub(y));
+    (w > 0 && h > 0).then_some((x, y, w, h))
+}
+
+/// Returns the minimum and maximum sample of each of `width` columns over `samples`.
+fn waveform_columns(samples: &[f32], width: usize) -> Vec<(f32, f32)> {
+    (0..width)
+        .map(|x| {
+            let from = x * samples.len() / width;
+            let to = ((x + 1) * samples.len() / width).max(from + 1).min(samples.len());
+            samples[from.min(to)..to]
+                .iter()
+                .fold(None, |acc: Option<(f32, f32)>, s| match acc {
+                    Some((min, max)) => Some((min.min(*s), max.max(*s))),
+                    None => Some((*s, *s)),
+                })
+                .unwrap_or((0.0, 0.0))
+        })
+        .collect()
+}
+
+/// Returns the intensity in `[0, 1]` of the `FFT_SIZE / 2` frequency bins of the last
+/// `FFT_SIZE` samples, padded with silence in front.
+fn spectrum_column(samples: &[f32]) -> Vec<f32> {
+    let mut re = vec![0.0f32; FFT_SIZE];
+    let mut im = vec![0.0f32; FFT_SIZE];
+    let offset = FFT_SIZE - samples.len().min(FFT_SIZE);
+    for (i, sample) in samples[samples.len().saturating_sub(FFT_SIZE)..].iter().enumerate() {
+        let hann = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * (offset + i) as f32 / FFT_SIZE as f32).cos();
+        re[offset + i] = sample * hann;
+    }
+    fft(&mut re, &mut im);
+
+    // a full scale sine reaches FFT_SIZE / 4 with a Hann window
+    let full_scale = FFT_SIZE as f32 / 4.0;
+    (0..FFT_SIZE / 2)
+        .map(|bin| {
+            let magnitude = (re[bin] * re[bin] + im[bin] * im[bin]).sqrt() / full_scale;
+            let db = 20.0 * magnitude.max(1e-9).log10();
+            ((db + SPECTRUM_RANGE_DB) / SPECTRUM_RANGE_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// In-place radix-2 FFT, the length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Dark blue for quiet bins, through magenta, to `color` for the loudest ones.
+fn spectrum_color(intensity: f32, color: [u8; 3]) -> [f32; 3] {
+    let low = [40.0, 0.0, 160.0];
+    let mid = [200.0, 0.0, 120.0];
+    let high = color.map(|c| c as f32);
+    let (from, to, t) = if intensity < 0.5 { (low, mid, intensity * 2.0) } else { (mid, high, intensity * 2.0 - 1.0) };
+    std::array::from_fn(|c| from[c] + (to[c] - from[c]) * t)
+}
+
+/// Allocates a frame of `format` and `width` x `height` pixels.
+fn new_frame(format: AVPixelFormat, width: usize, height: usize) -> Result<Frame, String> {
+    unsafe {
+        let mut frame = Frame::empty();
+        if frame.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let f = frame.as_mut_ptr();
+        (*f).format = format as i32;
+        (*f).width = width as i32;
+        (*f).height = height as i32;
+        let ret = av_frame_get_buffer(f, 0);
+        if ret < 0 {
+            return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+        }
+        Ok(frame)
+    }
+}
+
+/// Returns the samples of an audio frame, averaged over its channels.
+fn mono_samples(frame: &Frame) -> Result<Vec<f32>, String> {
+    let raw_format = unsafe { (*frame.as_ptr()).format };
+    let format = sample_format(raw_format).ok_or_else(|| format!("Unknown sample format {raw_format}"))?;
+    match unsafe { av_get_packed_sample_fmt(format) } {
+        AV_SAMPLE_FMT_U8 => mix_channels::<u8>(frame, format),
+        AV_SAMPLE_FMT_S16 => mix_channels::<i16>(frame, format),
+        AV_SAMPLE_FMT_S32 => mix_channels::<i32>(frame, format),
+        AV_SAMPLE_FMT_S64 => mix_channels::<i64>(frame, format),
+        AV_SAMPLE_FMT_FLT => mix_channels::<f32>(frame, format),
+        AV_SAMPLE_FMT_DBL => mix_channels::<f64>(frame, format),
+        _ => Err(format!("Audio visualizer does not support sample format {format:?}")),
+    }
+}
+
+/// Averages the channels of `frame`, whose samples are of type `T`.
+fn mix_channels<T: AudioSample>(frame: &Frame, format: AVSampleFormat) -> Result<Vec<f32>, String> {
+    let (channels, nb_samples) = unsafe {
+        let f = frame.as_ptr();
+        ((*f).ch_layout.nb_channels.max(1) as usize, (*f).nb_samples.max(0) as usize)
+    };
+    let mut sums = vec![0.0f64; nb_samples];
+    if unsafe { av_sample_fmt_is_planar(format) } != 0 {
+        for plane in 0..channels {
+            sums.iter_mut().zip(frame.samples::<T>(plane)?).for_each(|(sum, sample)| *sum += sample.to_f64());
+        }
+    } else {
+        for (sum, samples) in sums.iter_mut().zip(frame.samples::<T>(0)?.chunks_exact(channels)) {
+            *sum = samples.iter().map(|sample| sample.to_f64()).sum();
+        }
+    }
+    Ok(sums.into_iter().map(|sum| (sum / channels as f64) as f32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::core::stream_info::{find_audio_stream_info, find_video_stream_info, StreamInfo};
+    use crate::FfmpegContext;
+    use ffmpeg_sys_next::av_channel_layout_default;
+    use std::collections::HashMap;
+
+    /// A mono `flt` frame at 1000 Hz starting at sample `pts`.
+    fn audio_frame(pts: i64, samples: &[f32]) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_SAMPLE_FMT_FLT as i32;
+            av_channel_layout_default(&mut (*f).ch_layout, 1);
+            (*f).nb_samples = samples.len() as i32;
+            (*f).sample_rate = 1000;
+            (*f).pts = pts;
+            (*f).time_base = AVRational { num: 1, den: 1000 };
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            frame.samples_mut::<f32>(0).unwrap().copy_from_slice(samples);
+            frame
+        }
+    }
+
+    /// Takes the frames the source has ready.
+    fn rendered(source: &mut VisualizerSourceFilter, ctx: &FrameFilterContext) -> Vec<Frame> {
+        std::iter::from_fn(|| source.request_frame(ctx).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_history_follows_timeline() {
+        let mut history = AudioHistory::default();
+        history.append(1000, Some(500), &[0.5; 100]);
+        history.append(1000, None, &[0.25; 100]);
+        assert_eq!((history.start, history.end()), (500, 700));
+        assert_eq!(history.range(498, 502), vec![0.0, 0.0, 0.5, 0.5]);
+        assert_eq!(history.range(598, 602), vec![0.5, 0.5, 0.25, 0.25]);
+
+        // a gap is filled with silence
+        history.append(1000, Some(1000), &[1.0; 10]);
+        assert_eq!(history.end(), 1010);
+        assert_eq!(history.range(999, 1001), vec![0.0, 1.0]);
+
+        // going back restarts the timeline
+        history.append(1000, Some(0), &[1.0; 10]);
+        assert_eq!((history.start, history.end()), (0, 10));
+    }
+
+    #[test]
+    fn test_waveform_columns() {
+        let samples = [0.0, 1.0, -0.5, 0.25, 0.0, 0.0];
+        assert_eq!(waveform_columns(&samples, 3), vec![(0.0, 1.0), (-0.5, 0.25), (0.0, 0.0)]);
+        // more columns than samples
+        assert_eq!(waveform_columns(&samples[..2], 4).len(), 4);
+    }
+
+    #[test]
+    fn test_spectrum_column() {
+        let bin = 64;
+        let sine: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| (2.0 * std::f32::consts::PI * bin as f32 * i as f32 / FFT_SIZE as f32).sin())
+            .collect();
+        let column = spectrum_column(&sine);
+        let loudest = (0..column.len()).max_by(|a, b| column[*a].total_cmp(&column[*b])).unwrap();
+        assert_eq!(loudest, bin);
+        assert!(column[bin] > 0.99);
+        assert!(column[bin * 4] < 0.5);
+    }
+
+    #[test]
+    fn test_render_on_audio_timeline() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("visualizer", &mut attributes);
+        let mut visualizer = AudioVisualizerFilter::new(VisualizerMode::Waveform)
+            .set_size(32, 16)
+            .set_frame_rate(AVRational { num: 10, den: 1 })
+            .set_window(Duration::from_millis(100));
+        let mut source = visualizer.video_source().unwrap();
+        assert!(visualizer.video_source().is_none());
+        visualizer.init(&ctx).unwrap();
+
+        // frame 0 ends at 100 ms
+        visualizer.filter_frame(audio_frame(0, &[0.5; 50]), &ctx).unwrap();
+        assert!(rendered(&mut source, &ctx).is_empty());
+        visualizer.filter_frame(audio_frame(50, &[0.5; 200]), &ctx).unwrap();
+        let frames = rendered(&mut source, &ctx);
+        assert_eq!(frames.len(), 2);
+        for (n, frame) in frames.iter().enumerate() {
+            unsafe {
+                let f = frame.as_ptr();
+                assert_eq!(((*f).width, (*f).height, (*f).format), (32, 16, AV_PIX_FMT_YUV420P as i32));
+                assert_eq!((*f).pts, n as i64);
+                assert_eq!(((*f).time_base.num, (*f).time_base.den), (1, 10));
+            }
+        }
+        // a line at half amplitude, above the center
+        let luma = frames[0].plane_u8(0).unwrap();
+        assert!(luma[4][16] > 200, "{}", luma[4][16]);
+        assert!(luma[12][16] < 40, "{}", luma[12][16]);
+
+        // the source drops the frames of the stream carrying it
+        let carrier = new_frame(AV_PIX_FMT_YUV420P, 16, 16).unwrap();
+        assert!(source.filter_frame(carrier, &ctx).unwrap().is_none());
+
+        // the audio ends in frame 2, which is rendered before the end of the video
+        let eof = unsafe { Frame::empty() };
+        assert!(visualizer.filter_frame(eof, &ctx).unwrap().unwrap().is_empty());
+        let frames = rendered(&mut source, &ctx);
+        assert_eq!(frames.len(), 2);
+        unsafe {
+            assert_eq!((*frames[0].as_ptr()).pts, 2);
+            assert!(frames[1].is_empty());
+            assert_eq!((*frames[1].as_ptr()).pts, 3);
+        }
+        assert!(source.request_frame(&ctx).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_audio_visualizer() {
+        for mode in [VisualizerMode::Waveform, VisualizerMode::Spectrum] {
+            let mut visualizer = AudioVisualizerFilter::new(mode)
+                .set_size(160, 90)
+                .set_frame_rate(AVRational { num: 10, den: 1 });
+            let source = visualizer.video_source().unwrap();
+            // the video of the input carries the rendered frames
+            let output = Output::from("output_visualizer.mp4")
+                .add_frame_pipeline(
+                    FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO)
+                        .filter("visualizer", Box::new(visualizer)),
+                )
+                .add_frame_pipeline(
+                    FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("source", Box::new(source)),
+                );
+
+            let result = FfmpegContext::builder()
+                .input("test.mp4")
+                .output(output)
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait();
+            assert!(result.is_ok(), "{mode:?}");
+
+            let Some(StreamInfo::Video { width, height, nb_frames, .. }) =
+                find_video_stream_info("output_visualizer.mp4").unwrap()
+            else {
+                panic!("{mode:?}: no video stream");
+            };
+            assert_eq!((width, height), (160, 90), "{mode:?}");
+            let Some(StreamInfo::Audio { duration, time_base, .. }) =
+                find_audio_stream_info("output_visualizer.mp4").unwrap()
+            else {
+                panic!("{mode:?}: no audio stream");
+            };
+            // one frame every 100 ms of audio
+            let expected = duration as f64 * time_base.num as f64 / time_base.den as f64 * 10.0;
+            assert!((nb_frames as f64 - expected).abs() <= 2.0, "{mode:?}: {nb_frames} frames, {expected} expected");
+        }
+        let _ = std::fs::remove_file("output_visualizer.mp4");
+    }
+}
@@ -0,0 +1,289 @@
+//! A [`FrameFilter`] that blurs video frames, or a rectangle of them, e.g. to hide faces or
+//! license plates, or to soften a background.
+//!
+//! The blur is a box or gaussian kernel of a given radius, applied as two separable passes
+//! (horizontal, then vertical) to every plane of the frame: luma, chroma and alpha alike.
+//! The radius is given in luma pixels and scaled down for subsampled chroma planes. Pixels past
+//! the frame edges repeat the edge pixels. With a [`Region`], only the pixels inside it change,
+//! but they are blurred with their neighbours outside of it, so the region blends in; a
+//! feathered region also fades the blur in over its edges.
+//!
+//! The filter works on planar, semi-planar and packed RGB formats in their own pixel format,
+//! at any bit depth from 8 to 16 bits (`yuv420p`, `nv12`, `yuv420p10le`, `p010le`, `rgb24`,
+//! `rgba`, ...). Packed YUV frames (`yuyv422`) are rejected, and hardware frames are passed
+//! through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! // blur a face at (600, 200)
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("blur", Box::new(
+//!         BlurFilter::new(12)
+//!             .set_kernel(BlurKernel::Gaussian)
+//!             .set_region(Region::new(600, 200, 160, 200).set_feather(10)),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{FramePlanes, PlaneInfo};
+use crate::core::filter::region::{ceil_rshift, plane_layouts, Region, RegionBounds, Sample};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVMediaType;
+
+/// The weights of the pixels averaged by a [`BlurFilter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlurKernel {
+    /// All pixels within the radius count the same. Cheap, with a slightly blocky look.
+    Box,
+    /// Pixels count less the farther they are, with a standard deviation of half the radius.
+    /// Smoother than a box blur of the same radius.
+    Gaussian,
+}
+
+pub struct BlurFilter {
+    kernel: BlurKernel,
+    radius: u32,
+    region: Option<Region>,
+}
+
+impl BlurFilter {
+    /// Creates a filter blurring whole frames with a gaussian kernel of `radius` pixels.
+    /// A radius of 0 leaves the frames untouched.
+    pub fn new(radius: u32) -> Self {
+        Self {
+            kernel: BlurKernel::Gaussian,
+            radius,
+            region: None,
+        }
+    }
+
+    /// Sets the kernel of the blur (gaussian by default).
+    pub fn set_kernel(mut self, kernel: BlurKernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Blurs only `region` instead of the whole frame. Frames the region lies outside of are
+    /// rejected.
+    pub fn set_region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+}
+
+impl FrameFilter for BlurFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+        if self.radius == 0 {
+            return Ok(Some(frame));
+        }
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        let bounds = match &self.region {
+            Some(region) => region.bounds(&frame)?,
+            None => RegionBounds::full(width, height),
+        };
+
+        for plane in plane_layouts(&frame)? {
+            let info = frame.plane_info(plane.index)?;
+            let (log2_w, log2_h) = (plane.log2_w, plane.log2_h);
+            let geometry = PlaneGeometry {
+                width: ceil_rshift(width, log2_w),
+                step: plane.step,
+                region: bounds.in_plane(log2_w, log2_h),
+                // only a region can fade in
+                mask: self.region.map(|_| bounds.plane_mask(log2_w, log2_h)),
+            };
+            let h_kernel = kernel(self.kernel, ceil_rshift(self.radius as usize, log2_w));
+            let v_kernel = kernel(self.kernel, ceil_rshift(self.radius as usize, log2_h));
+            if info.bytes_per_sample == 2 {
+                blur_plane(&mut frame.plane_u16_mut(plane.index)?, &geometry, &info, &h_kernel, &v_kernel);
+            } else {
+                blur_plane(&mut frame.plane_u8_mut(plane.index)?, &geometry, &info, &h_kernel, &v_kernel);
+            }
+        }
+
+        Ok(Some(frame))
+    }
+}
+
+/// Returns the normalized weights of the pixels from `-radius` to `radius`.
+fn kernel(kernel: BlurKernel, radius: usize) -> Vec<f32> {
+    let weights = match kernel {
+        BlurKernel::Box => vec![1.0; 2 * radius + 1],
+        BlurKernel::Gaussian => {
+            let sigma = (radius as f32 / 2.0).max(0.5);
+            (0..=2 * radius)
+                .map(|i| {
+                    let distance = i as f32 - radius as f32;
+                    (-distance * distance / (2.0 * sigma * sigma)).exp()
+                })
+                .collect()
+        }
+    };
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / sum).collect()
+}
+
+/// The size of a plane and the part of it to blur.
+struct PlaneGeometry {
+    // in pixels
+    width: usize,
+    // samples of a pixel, e.g. 2 in the interleaved chroma plane of `nv12`
+    step: usize,
+    // x0, y0, x1, y1, in pixels of the plane, the ends excluded
+    region: (usize, usize, usize, usize),
+    // how much of the blur applies to each pixel of the region, row after row
+    mask: Option<Vec<f32>>,
+}
+
+/// Blurs the region of a plane given as rows (each row its own slice, so the padding of the
+/// line size is never read), first horizontally into a buffer, then vertically back into
+/// the plane.
+fn blur_plane<T: Sample>(
+    rows: &mut [&mut [T]],
+    geometry: &PlaneGeometry,
+    info: &PlaneInfo,
+    h_kernel: &[f32],
+    v_kernel: &[f32],
+) {
+    let (x0, y0, x1, y1) = geometry.region;
+    let (h_radius, v_radius) = (h_kernel.len() / 2, v_kernel.len() / 2);
+    let (step, last_x, last_y) = (geometry.step, geometry.width.saturating_sub(1), rows.len().saturating_sub(1));
+    if x0 >= x1 || y0 >= y1.min(rows.len()) {
+        return;
+    }
+
+    // the rows the vertical pass reads: the region and `v_radius` rows around it
+    let (top, bottom) = (y0.saturating_sub(v_radius), (y1 + v_radius).min(rows.len()));
+    let row_len = (x1 - x0) * step;
+    let mut horizontal = vec![0f32; (bottom - top) * row_len];
+    for (row, out) in rows[top..bottom].iter().zip(horizontal.chunks_exact_mut(row_len)) {
+        for (i, out) in out.iter_mut().enumerate() {
+            let (x, c) = (x0 + i / step, i % step);
+            *out = h_kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sx = (x + k).saturating_sub(h_radius).min(last_x);
+                    weight * row[sx * step + c].value(info)
+                })
+                .sum();
+        }
+    }
+
+    for (y, row) in rows.iter_mut().enumerate().take(y1).skip(y0) {
+        for (i, sample) in row[x0 * step..x1 * step].iter_mut().enumerate() {
+            let value: f32 = v_kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    // clamped to the plane, which the buffer covers around the region
+                    let sy = (y + k).saturating_sub(v_radius).min(last_y);
+                    weight * horizontal[(sy - top) * row_len + i]
+                })
+                .sum();
+            let weight = geometry.mask.as_ref().map_or(1.0, |mask| mask[(y - y0) * (x1 - x0) + i / step]);
+            if weight >= 1.0 {
+                *sample = T::from_value(value, info);
+            } else if weight > 0.0 {
+                let original = sample.value(info);
+                *sample = T::from_value(original + (value - original) * weight, info);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::{av_frame_get_buffer, AVPixelFormat};
+    use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_NV12, AV_PIX_FMT_YUV420P10LE};
+    use std::collections::HashMap;
+
+    /// A frame whose left half is dark and right half bright, in every plane.
+    fn frame(format: AVPixelFormat, dark: u16, bright: u16) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = format as i32;
+            // not a multiple of the line size alignment, so the rows are padded
+            (*f).width = 30;
+            (*f).height = 20;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for plane in 0..frame.plane_count() {
+                if frame.plane_info(plane).unwrap().bytes_per_sample == 2 {
+                    for row in frame.plane_u16_mut(plane).unwrap() {
+                        let half = row.len() / 2;
+                        row[..half].fill(dark);
+                        row[half..].fill(bright);
+                    }
+                } else {
+                    for row in frame.plane_u8_mut(plane).unwrap() {
+                        let half = row.len() / 2;
+                        row[..half].fill(dark as u8);
+                        row[half..].fill(bright as u8);
+                    }
+                }
+            }
+            frame
+        }
+    }
+
+    fn luma(frame: &Frame, x: usize, y: usize) -> u16 {
+        if frame.plane_info(0).unwrap().bytes_per_sample == 2 {
+            frame.plane_u16(0).unwrap()[y][x]
+        } else {
+            frame.plane_u8(0).unwrap()[y][x] as u16
+        }
+    }
+
+    #[test]
+    fn test_kernel() {
+        for kind in [BlurKernel::Box, BlurKernel::Gaussian] {
+            let weights = kernel(kind, 3);
+            assert_eq!(weights.len(), 7);
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+            assert_eq!(weights[0], weights[6]);
+        }
+        assert!(kernel(BlurKernel::Gaussian, 3)[3] > kernel(BlurKernel::Gaussian, 3)[0]);
+    }
+
+    #[test]
+    fn test_blur() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("blur", &mut attributes);
+
+        for (format, dark, bright) in [(AV_PIX_FMT_NV12, 20, 220), (AV_PIX_FMT_YUV420P10LE, 80, 880)] {
+            let mut filter = BlurFilter::new(4).set_kernel(BlurKernel::Box);
+            let output = filter.filter_frame(frame(format, dark, bright), &ctx).unwrap().unwrap();
+            // the edge is softened, the flat areas far from it are unchanged
+            assert!(luma(&output, 14, 10) > dark && luma(&output, 15, 10) < bright, "{format:?}");
+            assert_eq!(luma(&output, 2, 10), dark);
+            assert_eq!(luma(&output, 27, 0), bright);
+
+            // only the region changes
+            let mut filter = BlurFilter::new(4).set_region(Region::new(12, 0, 6, 10));
+            let output = filter.filter_frame(frame(format, dark, bright), &ctx).unwrap().unwrap();
+            assert!(luma(&output, 14, 5) > dark, "{format:?}");
+            assert_eq!(luma(&output, 14, 15), dark);
+            assert_eq!(luma(&output, 15, 15), bright);
+
+            // fades in over the feather
+            let mut filter = BlurFilter::new(4).set_region(Region::new(0, 0, 30, 10).set_feather(8));
+            let output = filter.filter_frame(frame(format, dark, bright), &ctx).unwrap().unwrap();
+            assert!(luma(&output, 14, 2) > luma(&output, 14, 7), "{format:?}");
+            let mut filter = filter.set_region(Region::new(30, 0, 4, 4));
+            assert!(filter.filter_frame(frame(format, dark, bright), &ctx).is_err(), "{format:?}");
+        }
+    }
+}
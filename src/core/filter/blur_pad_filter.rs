@@ -0,0 +1,349 @@
+//! A [`FrameFilter`] that fits video frames into a fixed resolution over a blurred copy of
+//! themselves, instead of black bars, e.g. to repurpose portrait clips for a 16:9 player.
+//!
+//! Each frame is scaled to fill the whole target (cropping the overflow) and blurred for the
+//! background, then scaled to fit inside the target and centered on top. The work is done by a
+//! small private filter graph (`split`, `scale`, `crop`, `gblur`, `overlay`), created from the
+//! first frame and rebuilt when the frame size or pixel format changes. The sample aspect ratio
+//! of the source is taken into account and the output has square pixels; the pixel format is kept.
+//!
+//! The foreground size and position are rounded to even values so they line up with subsampled
+//! chroma, which may leave a one pixel strip of background at an edge. Use even target
+//! dimensions for subsampled formats such as `yuv420p`, as most encoders require them.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("blur_pad", Box::new(
+//!         BlurPadFilter::new(1920, 1080).set_blur_sigma(30.0),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_buffersink_get_frame, av_buffersink_get_time_base, av_buffersrc_add_frame_flags, av_get_pix_fmt_name,
+    avfilter_get_by_name, avfilter_graph_alloc, avfilter_graph_config, avfilter_graph_create_filter,
+    avfilter_graph_free, avfilter_link, AVFilterContext, AVFilterGraph, AVMediaType, AVRational,
+    AVERROR, AVERROR_EOF, AV_TIME_BASE, EAGAIN,
+};
+use std::ffi::{CStr, CString};
+use std::ptr::{null, null_mut};
+
+pub struct BlurPadFilter {
+    width: i32,
+    height: i32,
+    blur_sigma: f32,
+
+    graph: *mut AVFilterGraph,
+    src_ctx: *mut AVFilterContext,
+    sink_ctx: *mut AVFilterContext,
+    key: (i32, i32, i32, i32, i32),
+    // the end-of-stream frame, forwarded once the graph is drained
+    pending_eof: Option<Frame>,
+}
+
+unsafe impl Send for BlurPadFilter {}
+
+/// Sizes and position of the background and foreground of a [`BlurPadFilter`] output.
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+    background_width: i32,
+    background_height: i32,
+    foreground_width: i32,
+    foreground_height: i32,
+    x: i32,
+    y: i32,
+}
+
+impl Layout {
+    /// Computes the layout of a `src_width` x `src_height` source with sample aspect ratio `sar`
+    /// in a `width` x `height` target.
+    fn new(src_width: i32, src_height: i32, sar: AVRational, width: i32, height: i32) -> Self {
+        // display width of the source, in square pixels
+        let display_width = if sar.num > 0 && sar.den > 0 {
+            src_width as f64 * sar.num as f64 / sar.den as f64
+        } else {
+            src_width as f64
+        };
+        let display_height = src_height as f64;
+
+        let fill = (width as f64 / display_width).max(height as f64 / display_height);
+        let fit = (width as f64 / display_width).min(height as f64 / display_height);
+        let even = |value: f64, max: i32| ((value.round() as i32) & !1).clamp(2, max.max(2));
+
+        let foreground_width = even(display_width * fit, width);
+        let foreground_height = even(display_height * fit, height);
+        Self {
+            // at least the target size, so that the crop is always possible
+            background_width: ((display_width * fill).round() as i32).max(width),
+            background_height: ((display_height * fill).round() as i32).max(height),
+            foreground_width,
+            foreground_height,
+            x: ((width - foreground_width) / 2).max(0) & !1,
+            y: ((height - foreground_height) / 2).max(0) & !1,
+        }
+    }
+}
+
+impl BlurPadFilter {
+    /// Creates a filter producing `width` x `height` frames, with a blur sigma of 20.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width.max(1) as i32,
+            height: height.max(1) as i32,
+            blur_sigma: 20.0,
+            graph: null_mut(),
+            src_ctx: null_mut(),
+            sink_ctx: null_mut(),
+            key: (0, 0, 0, 0, 0),
+            pending_eof: None,
+        }
+    }
+
+    /// Sets the strength of the background blur, as the sigma of a gaussian blur in pixels
+    /// of the target resolution. `0.0` disables the blur (the background is only scaled).
+    pub fn set_blur_sigma(mut self, blur_sigma: f32) -> Self {
+        self.blur_sigma = blur_sigma.max(0.0);
+        self
+    }
+
+    fn configure(&mut self, frame: &Frame) -> Result<(), String> {
+        let (width, height, format, time_base, sar) = unsafe {
+            let f = frame.as_ptr();
+            ((*f).width, (*f).height, (*f).format, (*f).time_base, (*f).sample_aspect_ratio)
+        };
+        let key = (width, height, format, sar.num, sar.den);
+        if !self.graph.is_null() {
+            if self.key == key {
+                return Ok(());
+            }
+            log::debug!("Blur pad input changed to {width}x{height} (format {format}), reconfiguring.");
+            self.free_graph();
+        }
+
+        let pix_fmt = pixel_format(format).ok_or_else(|| format!("Unknown pixel format {format}"))?;
+        let pix_fmt_name = unsafe { av_get_pix_fmt_name(pix_fmt) };
+        if pix_fmt_name.is_null() {
+            return Err(format!("Unknown pixel format {format}"));
+        }
+        let pix_fmt_name = unsafe { CStr::from_ptr(pix_fmt_name) }.to_string_lossy().into_owned();
+
+        let (tb_num, tb_den) = if time_base.num > 0 && time_base.den > 0 {
+            (time_base.num, time_base.den)
+        } else {
+            (1, AV_TIME_BASE as i32)
+        };
+        let buffer_args = format!(
+            "video_size={width}x{height}:pix_fmt={format}:time_base={tb_num}/{tb_den}:pixel_aspect={}/{}",
+            sar.num,
+            sar.den.max(1)
+        );
+        let layout = Layout::new(width, height, sar, self.width, self.height);
+        log::debug!("Blur pad layout for {width}x{height} into {}x{}: {layout:?}", self.width, self.height);
+
+        unsafe {
+            self.graph = avfilter_graph_alloc();
+            if self.graph.is_null() {
+                return Err("Failed to allocate blur pad filter graph: Out of memory.".to_string());
+            }
+
+            self.src_ctx = self.create_filter("buffer", "in", Some(&buffer_args))?;
+            let split_ctx = self.create_filter("split", "split", None)?;
+            let bg_scale_ctx = self.create_filter(
+                "scale",
+                "background_scale",
+                Some(&format!("w={}:h={}", layout.background_width, layout.background_height)),
+            )?;
+            // centered by default
+            let crop_ctx = self.create_filter("crop", "crop", Some(&format!("w={}:h={}", self.width, self.height)))?;
+            let blur_ctx = self.create_filter("gblur", "blur", Some(&format!("sigma={}", self.blur_sigma)))?;
+            let fg_scale_ctx = self.create_filter(
+                "scale",
+                "foreground_scale",
+                Some(&format!("w={}:h={}", layout.foreground_width, layout.foreground_height)),
+            )?;
+            let overlay_ctx =
+                self.create_filter("overlay", "overlay", Some(&format!("x={}:y={}:format=auto", layout.x, layout.y)))?;
+            let setsar_ctx = self.create_filter("setsar", "setsar", Some("sar=1"))?;
+            let format_ctx = self.create_filter("format", "format", Some(&format!("pix_fmts={pix_fmt_name}")))?;
+            self.sink_ctx = self.create_filter("buffersink", "out", None)?;
+
+            let links = [
+                (self.src_ctx, 0, split_ctx, 0),
+                (split_ctx, 0, bg_scale_ctx, 0),
+                (bg_scale_ctx, 0, crop_ctx, 0),
+                (crop_ctx, 0, blur_ctx, 0),
+                (blur_ctx, 0, overlay_ctx, 0),
+                (split_ctx, 1, fg_scale_ctx, 0),
+                (fg_scale_ctx, 0, overlay_ctx, 1),
+                (overlay_ctx, 0, setsar_ctx, 0),
+                (setsar_ctx, 0, format_ctx, 0),
+                (format_ctx, 0, self.sink_ctx, 0),
+            ];
+            for (src, src_pad, dst, dst_pad) in links {
+                let ret = avfilter_link(src, src_pad, dst, dst_pad);
+                if ret < 0 {
+                    return Err(format!("Failed to link blur pad filters: {}", av_err2str(ret)));
+                }
+            }
+
+            let ret = avfilter_graph_config(self.graph, null_mut());
+            if ret < 0 {
+                return Err(format!("Failed to configure blur pad filter graph: {}", av_err2str(ret)));
+            }
+        }
+
+        self.key = key;
+        Ok(())
+    }
+
+    unsafe fn create_filter(&self, name: &str, instance: &str, args: Option<&str>) -> Result<*mut AVFilterContext, String> {
+        let name_cstr = CString::new(name).map_err(|e| e.to_string())?;
+        let filter = avfilter_get_by_name(name_cstr.as_ptr());
+        if filter.is_null() {
+            return Err(format!("Filter '{name}' is not available in this FFmpeg build."));
+        }
+
+        let instance_cstr = CString::new(instance).map_err(|e| e.to_string())?;
+        let args_cstr = args.map(CString::new).transpose().map_err(|e| e.to_string())?;
+        let mut ctx = null_mut();
+        let ret = avfilter_graph_create_filter(
+            &mut ctx,
+            filter,
+            instance_cstr.as_ptr(),
+            args_cstr.as_ref().map_or(null(), |args| args.as_ptr()),
+            null_mut(),
+            self.graph,
+        );
+        if ret < 0 {
+            return Err(format!("Failed to create filter '{name}': {}", av_err2str(ret)));
+        }
+        Ok(ctx)
+    }
+
+    /// Pulls the next padded frame, `None` when the graph needs more input or is drained.
+    fn receive(&mut self) -> Result<Option<Frame>, String> {
+        if self.graph.is_null() {
+            return Ok(None);
+        }
+        unsafe {
+            let mut frame = Frame::empty();
+            if frame.as_ptr().is_null() {
+                return Err("Failed to create frame: Out of memory.".to_string());
+            }
+            let ret = av_buffersink_get_frame(self.sink_ctx, frame.as_mut_ptr());
+            if ret == AVERROR(EAGAIN) || ret == AVERROR_EOF {
+                return Ok(None);
+            }
+            if ret < 0 {
+                return Err(format!("Failed to get padded frame: {}", av_err2str(ret)));
+            }
+            (*frame.as_mut_ptr()).time_base = av_buffersink_get_time_base(self.sink_ctx);
+            Ok(Some(frame))
+        }
+    }
+
+    /// Like `receive`, but forwards the pending end-of-stream frame once the graph is drained.
+    /// The graph is then released, so frames arriving afterwards (e.g. a looped input) start a new one.
+    fn drain(&mut self) -> Result<Option<Frame>, String> {
+        if let Some(frame) = self.receive()? {
+            return Ok(Some(frame));
+        }
+        if self.pending_eof.is_some() {
+            self.free_graph();
+        }
+        Ok(self.pending_eof.take())
+    }
+
+    fn free_graph(&mut self) {
+        if !self.graph.is_null() {
+            unsafe { avfilter_graph_free(&mut self.graph) };
+        }
+        self.src_ctx = null_mut();
+        self.sink_ctx = null_mut();
+    }
+}
+
+impl FrameFilter for BlurPadFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+
+            if frame.is_empty() {
+                if self.graph.is_null() {
+                    return Ok(Some(frame));
+                }
+                let ret = av_buffersrc_add_frame_flags(self.src_ctx, null_mut(), 0);
+                if ret < 0 {
+                    return Err(format!("Failed to flush blur pad filter: {}", av_err2str(ret)));
+                }
+                self.pending_eof = Some(frame);
+                return self.drain();
+            }
+        }
+
+        self.configure(&frame)?;
+        let ret = unsafe { av_buffersrc_add_frame_flags(self.src_ctx, frame.as_mut_ptr(), 0) };
+        if ret < 0 {
+            return Err(format!("Failed to feed blur pad filter: {}", av_err2str(ret)));
+        }
+        self.receive()
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        self.drain()
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        self.free_graph();
+    }
+}
+
+impl Drop for BlurPadFilter {
+    fn drop(&mut self) {
+        self.free_graph();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let square = AVRational { num: 1, den: 1 };
+
+        // portrait 1080x1920 into 1920x1080: pillarboxed
+        assert_eq!(
+            Layout::new(1080, 1920, square, 1920, 1080),
+            Layout {
+                background_width: 1920,
+                background_height: 3413,
+                foreground_width: 608,
+                foreground_height: 1080,
+                x: 656,
+                y: 0,
+            }
+        );
+
+        // wide 2.39:1 into 1920x1080: letterboxed
+        let layout = Layout::new(2390, 1000, square, 1920, 1080);
+        assert_eq!((layout.foreground_width, layout.foreground_height), (1920, 802));
+        assert_eq!((layout.x, layout.y), (0, 138));
+        assert!(layout.background_height >= 1080);
+
+        // anamorphic 720x576 with a 16:11 sample aspect ratio is 1047x576 on screen
+        let layout = Layout::new(720, 576, AVRational { num: 16, den: 11 }, 1920, 1080);
+        assert_eq!((layout.foreground_width, layout.foreground_height), (1920, 1056));
+        assert_eq!((layout.x, layout.y), (0, 12));
+    }
+}
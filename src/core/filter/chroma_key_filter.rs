@@ -0,0 +1,266 @@
+//! A [`FrameFilter`] that removes a key color from video frames, e.g. the green screen behind
+//! a presenter.
+//!
+//! Pixels are compared to the key color in the chroma plane: both are converted to BT.601
+//! Cb/Cr and their distance, normalized to 0-1, is compared to the similarity. Pixels closer
+//! than the similarity become fully transparent; with a non-zero blend, pixels up to
+//! `similarity + blend` away are partially transparent, which softens the edges of the
+//! subject. Comparing the chroma only keeps shadows and highlights on the screen keyed out.
+//!
+//! What the filter outputs depends on where the frames go next:
+//! - By default the transparency is kept in an alpha channel. Frames whose pixel format already
+//!   has one (`yuva420p`, `rgba`, ...) keep their format, with their alpha combined with the
+//!   key; other frames are replaced by `rgba` frames. This suits pipelines whose downstream
+//!   carries alpha, e.g. an input pipeline feeding an `overlay` filter, or an output encoded
+//!   with an alpha capable codec (`png`, `prores_ks` with `yuva444p10le`, `libvpx-vp9`, ...).
+//! - With a [`background`](ChromaKeyFilter::set_background), the keyed pixels are composited
+//!   over that color and the frame keeps its own pixel format and size, so the filter can run
+//!   right before an encoder that cannot carry alpha.
+//!
+//! Hardware frames are passed through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("chroma_key", Box::new(
+//!         ChromaKeyFilter::new([0, 177, 64])
+//!             .set_similarity(0.12)
+//!             .set_blend(0.05)
+//!             .set_background(Some([255, 255, 255])),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_RGB24, AV_PIX_FMT_RGBA};
+use ffmpeg_sys_next::{
+    av_frame_copy_props, av_frame_get_buffer, av_pix_fmt_desc_get, AVColorRange, AVColorSpace, AVMediaType,
+    AV_PIX_FMT_FLAG_ALPHA,
+};
+
+pub struct ChromaKeyFilter {
+    key: [u8; 3],
+    similarity: f32,
+    blend: f32,
+    background: Option<[u8; 3]>,
+
+    to_work: FrameConverter,
+    from_work: FrameConverter,
+}
+
+impl ChromaKeyFilter {
+    /// Creates a filter removing the `key` color (RGB), with a similarity of 0.1, no blend,
+    /// and the transparency kept in an alpha channel.
+    pub fn new(key: [u8; 3]) -> Self {
+        Self {
+            key,
+            similarity: 0.1,
+            blend: 0.0,
+            background: None,
+            to_work: FrameConverter::new(),
+            from_work: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the largest chroma distance (0-1) from the key color of a fully transparent
+    /// pixel. Higher values remove more shades of the key color, and eventually the subject.
+    pub fn set_similarity(mut self, similarity: f32) -> Self {
+        self.similarity = similarity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the distance (0-1) past the similarity over which pixels fade from transparent
+    /// to opaque. 0 makes every pixel either transparent or opaque.
+    pub fn set_blend(mut self, blend: f32) -> Self {
+        self.blend = blend.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the color (RGB) the keyed pixels are composited over, keeping the pixel format of
+    /// the frames. `None`, the default, keeps the transparency in an alpha channel instead.
+    pub fn set_background(mut self, background: Option<[u8; 3]>) -> Self {
+        self.background = background;
+        self
+    }
+}
+
+impl FrameFilter for ChromaKeyFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        let key = KeyParams::new(self.key, self.similarity, self.blend);
+
+        if let Some(background) = self.background {
+            let rgb = self.to_work.convert(&frame, AV_PIX_FMT_RGB24, width as i32, height as i32)?;
+            for row in rgb.plane_u8_mut(0)? {
+                for pixel in row.chunks_exact_mut(3) {
+                    let alpha = key.alpha([pixel[0], pixel[1], pixel[2]]) as u32;
+                    for (value, background) in pixel.iter_mut().zip(background) {
+                        *value = ((*value as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255) as u8;
+                    }
+                }
+            }
+            self.from_work.convert_into(rgb, &mut frame)?;
+            return Ok(Some(frame));
+        }
+
+        if has_alpha(unsafe { (*frame.as_ptr()).format }) {
+            let rgba = self.to_work.convert(&frame, AV_PIX_FMT_RGBA, width as i32, height as i32)?;
+            apply_key(rgba, &key)?;
+            self.from_work.convert_into(rgba, &mut frame)?;
+            Ok(Some(frame))
+        } else {
+            let mut rgba = new_rgba_frame(&frame)?;
+            self.to_work.convert_into(&frame, &mut rgba)?;
+            apply_key(&mut rgba, &key)?;
+            Ok(Some(rgba))
+        }
+    }
+}
+
+/// The key color and thresholds, in the units [`alpha`](KeyParams::alpha) computes with.
+struct KeyParams {
+    chroma: (f32, f32),
+    similarity: f32,
+    blend: f32,
+}
+
+impl KeyParams {
+    fn new(key: [u8; 3], similarity: f32, blend: f32) -> Self {
+        Self {
+            chroma: chroma(key),
+            similarity,
+            blend,
+        }
+    }
+
+    /// Returns the opacity (0-255) of an RGB `pixel`, from its chroma distance to the key.
+    fn alpha(&self, pixel: [u8; 3]) -> u8 {
+        let (cb, cr) = chroma(pixel);
+        let (du, dv) = (cb - self.chroma.0, cr - self.chroma.1);
+        // 0 for the key color itself, 1 at the largest possible distance
+        let distance = ((du * du + dv * dv) / (2.0 * 255.0 * 255.0)).sqrt();
+        if self.blend > 0.0001 {
+            (((distance - self.similarity) / self.blend).clamp(0.0, 1.0) * 255.0).round() as u8
+        } else if distance > self.similarity {
+            255
+        } else {
+            0
+        }
+    }
+}
+
+/// Returns the full range BT.601 Cb and Cr of an RGB color, centered on 0.
+fn chroma([r, g, b]: [u8; 3]) -> (f32, f32) {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    (
+        -0.168736 * r - 0.331264 * g + 0.5 * b,
+        0.5 * r - 0.418688 * g - 0.081312 * b,
+    )
+}
+
+/// Multiplies the alpha of every pixel of an `rgba` frame by the opacity the key gives it.
+fn apply_key(rgba: &mut Frame, key: &KeyParams) -> Result<(), String> {
+    for row in rgba.plane_u8_mut(0)? {
+        for pixel in row.chunks_exact_mut(4) {
+            let alpha = key.alpha([pixel[0], pixel[1], pixel[2]]) as u32;
+            pixel[3] = ((pixel[3] as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+    Ok(())
+}
+
+fn has_alpha(format: i32) -> bool {
+    let Some(format) = pixel_format(format) else {
+        return false;
+    };
+    unsafe {
+        let desc = av_pix_fmt_desc_get(format);
+        !desc.is_null() && (*desc).flags & AV_PIX_FMT_FLAG_ALPHA as u64 != 0
+    }
+}
+
+/// Returns a new `rgba` frame of the size of `src`, with its timestamps and properties.
+fn new_rgba_frame(src: &Frame) -> Result<Frame, String> {
+    unsafe {
+        let mut rgba = Frame::empty();
+        if rgba.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let dst = rgba.as_mut_ptr();
+        (*dst).format = AV_PIX_FMT_RGBA as i32;
+        (*dst).width = (*src.as_ptr()).width;
+        (*dst).height = (*src.as_ptr()).height;
+        let ret = av_frame_get_buffer(dst, 0);
+        if ret < 0 {
+            return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+        }
+        let ret = av_frame_copy_props(dst, src.as_ptr());
+        if ret < 0 {
+            return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+        }
+        // the YUV matrix and range of the source do not describe RGB samples
+        (*dst).colorspace = AVColorSpace::AVCOL_SPC_RGB;
+        (*dst).color_range = AVColorRange::AVCOL_RANGE_JPEG;
+        Ok(rgba)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+
+    #[test]
+    fn test_key_alpha() {
+        let green = [0, 255, 0];
+        let hard = KeyParams::new(green, 0.1, 0.0);
+        assert_eq!(hard.alpha(green), 0);
+        // a darker shade of the key is still keyed out
+        assert_eq!(hard.alpha([20, 230, 20]), 0);
+        assert_eq!(hard.alpha([255, 0, 255]), 255);
+        assert_eq!(hard.alpha([200, 150, 120]), 255);
+
+        // the blend fades in between
+        let soft = KeyParams::new(green, 0.1, 0.5);
+        let alpha = soft.alpha([100, 200, 100]);
+        assert!(alpha > 0 && alpha < 255, "{alpha}");
+        assert_eq!(soft.alpha(green), 0);
+    }
+
+    #[test]
+    fn test_chroma_key() {
+        for background in [None, Some([255, 255, 255])] {
+            let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter(
+                "chroma_key",
+                Box::new(ChromaKeyFilter::new([0, 255, 0]).set_blend(0.05).set_background(background)),
+            );
+            let result = FfmpegContext::builder()
+                .input(Input::from("test.mp4").add_frame_pipeline(pipeline))
+                .output(Output::from("output_chroma_key.mp4").set_recording_time_us(1_000_000))
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait();
+            assert!(result.is_ok(), "{background:?}: {:?}", result.err());
+        }
+        std::fs::remove_file("output_chroma_key.mp4").unwrap();
+    }
+}
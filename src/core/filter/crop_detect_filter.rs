@@ -0,0 +1,422 @@
+//! A [`FrameFilter`] that detects black borders (letterbox / pillarbox) and optionally crops them.
+//!
+//! The filter samples the luma of the first frames of the stream, one frame every
+//! [`set_sample_interval`](CropDetectFilter::set_sample_interval) frames until
+//! [`set_sample_frames`](CropDetectFilter::set_sample_frames) frames have been analyzed. A row or
+//! column is black when its average luma is at most the threshold. The detected area is the union
+//! of the non-black areas of all sampled frames, so a dark scene, whose bright part is smaller than
+//! the picture, cannot shrink the result; frames that are black as a whole are ignored.
+//!
+//! The area is rounded outwards to even coordinates and dimensions so it can be applied to
+//! chroma-subsampled frames. The luma of YUV and gray frames is read from the frame itself, at any
+//! bit depth, and compared in 8-bit units; frames of other formats (RGB, packed YUV) are converted
+//! to gray first. Once detected it is:
+//! - available from the handle returned by [`crop_area`](CropDetectFilter::crop_area), which can
+//!   also be shared with other filters of the pipeline through
+//!   [`FramePipeline::set_attribute`](crate::filter::frame_pipeline::FramePipeline::set_attribute);
+//! - sent as a [`PipelineEvent::Custom`] named `"crop"` whose value is `w:h:x:y`, the arguments
+//!   of FFmpeg's `crop` filter;
+//! - applied to every frame when [`set_apply`](CropDetectFilter::set_apply) is enabled. The frames
+//!   received during the detection are then held back and cropped as well, so all frames leaving
+//!   the filter have the same size. Keep the number of sampled frames reasonable in that case, as
+//!   `sample_frames * sample_interval` decoded frames are kept in memory.
+//!
+//! Hardware frames are passed through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let filter = CropDetectFilter::new().set_apply(true);
+//! let crop_area = filter.crop_area();
+//!
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("cropdetect", Box::new(filter));
+//!
+//! FfmpegContext::builder()
+//!     .input(Input::from("letterboxed.mp4").add_frame_pipeline(pipeline))
+//!     .output("cropped.mp4")
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//!
+//! println!("cropped to {:?}", crop_area.lock().unwrap());
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::core::filter::pipeline_event::PipelineEvent;
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+use ffmpeg_sys_next::{
+    av_frame_apply_cropping, av_pix_fmt_desc_get, AVMediaType, AV_PIX_FMT_FLAG_BAYER, AV_PIX_FMT_FLAG_RGB,
+};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// `AV_FRAME_CROP_UNALIGNED`: crop exactly, even if the data pointers end up unaligned.
+const AV_FRAME_CROP_UNALIGNED: i32 = 1;
+
+/// Area of the picture left once the black borders are removed, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropArea {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropArea {
+    /// Formats the area as `w:h:x:y`, the arguments of FFmpeg's `crop` filter.
+    pub fn to_crop_filter_args(&self) -> String {
+        format!("{}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Non-black area of a picture, as inclusive pixel bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl Bounds {
+    fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+pub struct CropDetectFilter {
+    threshold: u8,
+    sample_frames: usize,
+    sample_interval: usize,
+    apply: bool,
+    crop_area: Arc<Mutex<Option<CropArea>>>,
+
+    frame_size: Option<(i32, i32)>,
+    bounds: Option<Bounds>,
+    frames_seen: usize,
+    frames_sampled: usize,
+    detected: bool,
+    pending: VecDeque<Frame>,
+    ready: VecDeque<Frame>,
+    converter: FrameConverter,
+}
+
+impl CropDetectFilter {
+    /// Creates a filter sampling 25 frames, one every 2 frames, with a luma threshold of 24.
+    pub fn new() -> Self {
+        Self {
+            threshold: 24,
+            sample_frames: 25,
+            sample_interval: 2,
+            apply: false,
+            crop_area: Arc::new(Mutex::new(None)),
+            frame_size: None,
+            bounds: None,
+            frames_seen: 0,
+            frames_sampled: 0,
+            detected: false,
+            pending: VecDeque::new(),
+            ready: VecDeque::new(),
+            converter: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the highest average luma (0-255) of a row or column still considered black.
+    pub fn set_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets how many frames are analyzed before the crop area is decided.
+    pub fn set_sample_frames(mut self, sample_frames: usize) -> Self {
+        self.sample_frames = sample_frames;
+        self
+    }
+
+    /// Analyzes one frame out of `sample_interval`, to spread the samples over a longer time.
+    pub fn set_sample_interval(mut self, sample_interval: usize) -> Self {
+        self.sample_interval = sample_interval;
+        self
+    }
+
+    /// Crops every frame to the detected area instead of only reporting it.
+    pub fn set_apply(mut self, apply: bool) -> Self {
+        self.apply = apply;
+        self
+    }
+
+    /// Returns a handle to the detected area, `None` until the detection is over or when no
+    /// sampled frame had any content. Clone it before handing the filter to a pipeline.
+    pub fn crop_area(&self) -> Arc<Mutex<Option<CropArea>>> {
+        self.crop_area.clone()
+    }
+
+    fn sample(&mut self, frame: &Frame, width: i32, height: i32) -> Result<(), String> {
+        let luma = match native_luma(frame, width as usize) {
+            Some(luma) => luma,
+            None => self.converter.convert(frame, AV_PIX_FMT_GRAY8, width, height)?.plane_u8(0)?.concat(),
+        };
+        if let Some(bounds) = luma_bounds(&luma, width as usize, height as usize, self.threshold) {
+            self.bounds = Some(match self.bounds {
+                Some(previous) => previous.union(bounds),
+                None => bounds,
+            });
+        }
+        self.frames_sampled += 1;
+        Ok(())
+    }
+
+    fn finish_detection(&mut self, ctx: &FrameFilterContext) -> Result<(), String> {
+        self.detected = true;
+        let crop_area = match (self.bounds, self.frame_size) {
+            (Some(bounds), Some((width, height))) => Some(even_crop_area(bounds, width as usize, height as usize)),
+            _ => None,
+        };
+        match crop_area {
+            Some(crop_area) => {
+                info!(
+                    "[{}] detected crop area {} from {} frames",
+                    ctx.name(),
+                    crop_area.to_crop_filter_args(),
+                    self.frames_sampled
+                );
+                ctx.send_event(PipelineEvent::Custom {
+                    name: "crop".to_string(),
+                    value: crop_area.to_crop_filter_args(),
+                });
+            }
+            None => warn!("[{}] no content found in {} sampled frames, not cropping", ctx.name(), self.frames_sampled),
+        }
+        *self.crop_area.lock().unwrap() = crop_area;
+
+        while let Some(frame) = self.pending.pop_front() {
+            let frame = self.crop(frame)?;
+            self.ready.push_back(frame);
+        }
+        Ok(())
+    }
+
+    fn crop(&self, mut frame: Frame) -> Result<Frame, String> {
+        let Some(crop_area) = *self.crop_area.lock().unwrap() else {
+            return Ok(frame);
+        };
+        unsafe {
+            let (width, height) = ((*frame.as_ptr()).width, (*frame.as_ptr()).height);
+            if Some((width, height)) != self.frame_size {
+                // the stream changed size after the detection, the area does not apply
+                return Ok(frame);
+            }
+            let frame_ptr = frame.as_mut_ptr();
+            (*frame_ptr).crop_left = crop_area.x as usize;
+            (*frame_ptr).crop_top = crop_area.y as usize;
+            (*frame_ptr).crop_right = (width as u32 - crop_area.x - crop_area.width) as usize;
+            (*frame_ptr).crop_bottom = (height as u32 - crop_area.y - crop_area.height) as usize;
+            let ret = av_frame_apply_cropping(frame_ptr, AV_FRAME_CROP_UNALIGNED);
+            if ret < 0 {
+                return Err(format!("Failed to crop frame: {}", av_err2str(ret)));
+            }
+        }
+        Ok(frame)
+    }
+}
+
+impl Default for CropDetectFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for CropDetectFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if self.sample_frames == 0 || self.sample_interval == 0 {
+            return Err("Crop detection needs at least one sampled frame and a non-zero interval".to_string());
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        if frame.is_empty() {
+            // end of stream: decide with the frames seen so far, then forward it
+            if !self.detected {
+                self.finish_detection(ctx)?;
+            }
+            self.ready.push_back(frame);
+            return Ok(self.ready.pop_front());
+        }
+
+        if self.detected {
+            let frame = if self.apply { self.crop(frame)? } else { frame };
+            self.ready.push_back(frame);
+            return Ok(self.ready.pop_front());
+        }
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+        let frame_size = *self.frame_size.get_or_insert((width, height));
+        if self.frames_seen % self.sample_interval == 0 && frame_size == (width, height) {
+            self.sample(&frame, width, height)?;
+        }
+        self.frames_seen += 1;
+
+        if self.apply {
+            self.pending.push_back(frame);
+        } else {
+            self.ready.push_back(frame);
+        }
+        if self.frames_sampled >= self.sample_frames {
+            self.finish_detection(ctx)?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        Ok(self.ready.pop_front())
+    }
+}
+
+/// Returns the luma of `frame`, scaled to 8 bits, row after row, or `None` if the pixel format has
+/// no plane of luma samples alone (RGB, packed YUV like `yuyv422`, gray with alpha).
+fn native_luma(frame: &Frame, width: usize) -> Option<Vec<u8>> {
+    let info = frame.plane_info(0).ok()?;
+    let has_luma = unsafe {
+        let desc = av_pix_fmt_desc_get(pixel_format((*frame.as_ptr()).format)?);
+        !desc.is_null() && (*desc).flags & (AV_PIX_FMT_FLAG_RGB | AV_PIX_FMT_FLAG_BAYER) as u64 == 0
+    };
+    // packed formats interleave the luma with other components
+    if !has_luma || info.samples_per_row != width {
+        return None;
+    }
+    if info.bytes_per_sample == 2 {
+        let down = info.bit_depth.saturating_sub(8);
+        let rows = frame.plane_u16(0).ok()?;
+        Some(rows.iter().flat_map(|row| row.iter().map(|sample| (sample >> info.shift >> down) as u8)).collect())
+    } else {
+        Some(frame.plane_u8(0).ok()?.concat())
+    }
+}
+
+/// Returns the bounds of the rows and columns of `luma` whose average is above `threshold`,
+/// or `None` when the whole picture is black.
+fn luma_bounds(luma: &[u8], width: usize, height: usize, threshold: u8) -> Option<Bounds> {
+    let mut row_sums = vec![0u64; height];
+    let mut column_sums = vec![0u64; width];
+    for (y, row) in luma.chunks_exact(width).take(height).enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            row_sums[y] += *value as u64;
+            column_sums[x] += *value as u64;
+        }
+    }
+
+    let row_limit = threshold as u64 * width as u64;
+    let column_limit = threshold as u64 * height as u64;
+    let top = row_sums.iter().position(|sum| *sum > row_limit)?;
+    let bottom = row_sums.iter().rposition(|sum| *sum > row_limit)?;
+    let left = column_sums.iter().position(|sum| *sum > column_limit)?;
+    let right = column_sums.iter().rposition(|sum| *sum > column_limit)?;
+    Some(Bounds { left, top, right, bottom })
+}
+
+/// Turns `bounds` into a crop area with even coordinates and dimensions, growing it
+/// rather than cutting into the content, within a `width` x `height` picture.
+fn even_crop_area(bounds: Bounds, width: usize, height: usize) -> CropArea {
+    fn even_range(first: usize, last: usize, size: usize) -> (usize, usize) {
+        let start = first & !1;
+        let mut len = (last + 1 - start + 1) & !1;
+        if start + len > size {
+            len = (size - start) & !1;
+        }
+        (start, len)
+    }
+
+    let (x, crop_width) = even_range(bounds.left, bounds.right, width);
+    let (y, crop_height) = even_range(bounds.top, bounds.bottom, height);
+    CropArea {
+        x: x as u32,
+        y: y as u32,
+        width: crop_width as u32,
+        height: crop_height as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_YUV420P, AV_PIX_FMT_YUV420P10LE};
+
+    #[test]
+    fn test_luma_bounds() {
+        // 8x6 picture, content in columns 2..=5 and rows 1..=3, with noise in the bars
+        let mut luma = vec![16u8; 8 * 6];
+        for y in 1..=3 {
+            for x in 2..=5 {
+                luma[y * 8 + x] = 200;
+            }
+        }
+        luma[5 * 8] = 40;
+        let bounds = luma_bounds(&luma, 8, 6, 24).unwrap();
+        assert_eq!(bounds, Bounds { left: 2, top: 1, right: 5, bottom: 3 });
+
+        assert_eq!(luma_bounds(&[16u8; 8 * 6], 8, 6, 24), None);
+    }
+
+    #[test]
+    fn test_native_luma() {
+        for (format, black, white) in [(AV_PIX_FMT_YUV420P, 16, 235), (AV_PIX_FMT_YUV420P10LE, 64, 940)] {
+            let mut frame = unsafe { Frame::empty() };
+            unsafe {
+                let f = frame.as_mut_ptr();
+                (*f).format = format as i32;
+                (*f).width = 8;
+                (*f).height = 4;
+                assert!(av_frame_get_buffer(f, 0) >= 0);
+            }
+            // a black bar on the left, in the frame's own bit depth
+            if frame.plane_info(0).unwrap().bytes_per_sample == 2 {
+                for row in frame.plane_u16_mut(0).unwrap() {
+                    row[..2].fill(black);
+                    row[2..].fill(white);
+                }
+            } else {
+                for row in frame.plane_u8_mut(0).unwrap() {
+                    row[..2].fill(black as u8);
+                    row[2..].fill(white as u8);
+                }
+            }
+            let luma = native_luma(&frame, 8).unwrap();
+            assert_eq!(&luma[..8], [16, 16, 235, 235, 235, 235, 235, 235], "{format:?}");
+            assert_eq!(luma_bounds(&luma, 8, 4, 24), Some(Bounds { left: 2, top: 0, right: 7, bottom: 3 }));
+        }
+    }
+
+    #[test]
+    fn test_even_crop_area() {
+        let area = even_crop_area(Bounds { left: 2, top: 1, right: 5, bottom: 3 }, 8, 6);
+        assert_eq!(area, CropArea { x: 2, y: 0, width: 4, height: 4 });
+
+        // content reaching the last column of an odd-width picture
+        let area = even_crop_area(Bounds { left: 3, top: 0, right: 8, bottom: 5 }, 9, 6);
+        assert_eq!(area, CropArea { x: 2, y: 0, width: 6, height: 6 });
+        assert_eq!(area.to_crop_filter_args(), "6:6:2:0");
+    }
+}
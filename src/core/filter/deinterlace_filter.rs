@@ -0,0 +1,341 @@
+//! A [`FrameFilter`] that deinterlaces video frames with FFmpeg's `yadif` or `bwdif` algorithm.
+//!
+//! The filter runs a small private filter graph (`buffer -> yadif/bwdif -> buffersink`), so it
+//! can be used in a frame pipeline without a `filter_desc`. The graph is created from the first
+//! frame and rebuilt when the frame size or pixel format changes; the old graph is drained
+//! first, so the frames it held back are still emitted, ahead of the new ones.
+//!
+//! Both algorithms look one frame ahead, so the first input frame produces no output and the
+//! last one is emitted when the end of stream reaches the filter. In double-rate mode every
+//! input frame produces two output frames (one per field); the second one is returned by
+//! `request_frame`.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("deinterlace", Box::new(
+//!         DeinterlaceFilter::new(DeinterlaceAlgorithm::Bwdif)
+//!             .set_double_rate(true)
+//!             .set_interlaced_only(true),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::util::ffmpeg_utils::av_err2str;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_buffersink_get_frame, av_buffersink_get_time_base, av_buffersrc_add_frame_flags,
+    avfilter_get_by_name, avfilter_graph_alloc, avfilter_graph_config, avfilter_graph_create_filter,
+    avfilter_graph_free, avfilter_link, AVFilterContext, AVFilterGraph, AVMediaType, AVERROR,
+    AVERROR_EOF, AV_TIME_BASE, EAGAIN,
+};
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::ptr::{null, null_mut};
+
+/// The deinterlacing algorithm used by [`DeinterlaceFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeinterlaceAlgorithm {
+    /// "Yet Another DeInterlacing Filter", fast and widely used.
+    Yadif,
+    /// "Bob Weaver Deinterlacing Filter", slower but with better quality on motion.
+    Bwdif,
+}
+
+impl DeinterlaceAlgorithm {
+    fn filter_name(&self) -> &'static str {
+        match self {
+            DeinterlaceAlgorithm::Yadif => "yadif",
+            DeinterlaceAlgorithm::Bwdif => "bwdif",
+        }
+    }
+}
+
+pub struct DeinterlaceFilter {
+    algorithm: DeinterlaceAlgorithm,
+    double_rate: bool,
+    interlaced_only: bool,
+
+    graph: *mut AVFilterGraph,
+    src_ctx: *mut AVFilterContext,
+    sink_ctx: *mut AVFilterContext,
+    key: (i32, i32, i32),
+    // the frames drained from a graph replaced after a size or format change
+    drained: VecDeque<Frame>,
+    // the end-of-stream frame, forwarded once the graph is drained
+    pending_eof: Option<Frame>,
+}
+
+unsafe impl Send for DeinterlaceFilter {}
+
+impl DeinterlaceFilter {
+    /// Creates a single-rate filter deinterlacing every frame with the given `algorithm`.
+    pub fn new(algorithm: DeinterlaceAlgorithm) -> Self {
+        Self {
+            algorithm,
+            double_rate: false,
+            interlaced_only: false,
+            graph: null_mut(),
+            src_ctx: null_mut(),
+            sink_ctx: null_mut(),
+            key: (0, 0, 0),
+            drained: VecDeque::new(),
+            pending_eof: None,
+        }
+    }
+
+    /// Outputs one frame per field (`true`), doubling the frame rate, instead of one
+    /// frame per frame (`false`, the default). Double rate keeps the full temporal
+    /// resolution of interlaced footage, e.g. 50i becomes 50p.
+    pub fn set_double_rate(mut self, double_rate: bool) -> Self {
+        self.double_rate = double_rate;
+        self
+    }
+
+    /// Only deinterlaces frames flagged as interlaced by the decoder (`true`) and passes
+    /// progressive frames through, instead of deinterlacing every frame (`false`, the default).
+    pub fn set_interlaced_only(mut self, interlaced_only: bool) -> Self {
+        self.interlaced_only = interlaced_only;
+        self
+    }
+
+    fn configure(&mut self, frame: &Frame) -> Result<(), String> {
+        let (width, height, format, time_base, sar) = unsafe {
+            let f = frame.as_ptr();
+            ((*f).width, (*f).height, (*f).format, (*f).time_base, (*f).sample_aspect_ratio)
+        };
+        let key = (width, height, format);
+        if !self.graph.is_null() {
+            if self.key == key {
+                return Ok(());
+            }
+            log::debug!("Deinterlace input changed to {width}x{height} (format {format}), reconfiguring.");
+            self.flush()?;
+            while let Some(frame) = self.receive()? {
+                self.drained.push_back(frame);
+            }
+            self.free_graph();
+        }
+
+        let (tb_num, tb_den) = if time_base.num > 0 && time_base.den > 0 {
+            (time_base.num, time_base.den)
+        } else {
+            (1, AV_TIME_BASE as i32)
+        };
+        let buffer_args = format!(
+            "video_size={width}x{height}:pix_fmt={format}:time_base={tb_num}/{tb_den}:pixel_aspect={}/{}",
+            sar.num,
+            sar.den.max(1)
+        );
+        let deinterlace_args = format!(
+            "mode={}:parity=auto:deint={}",
+            if self.double_rate { "send_field" } else { "send_frame" },
+            if self.interlaced_only { "interlaced" } else { "all" }
+        );
+
+        unsafe {
+            self.graph = avfilter_graph_alloc();
+            if self.graph.is_null() {
+                return Err("Failed to allocate deinterlace filter graph: Out of memory.".to_string());
+            }
+
+            self.src_ctx = self.create_filter("buffer", "in", Some(&buffer_args))?;
+            let deinterlace_ctx =
+                self.create_filter(self.algorithm.filter_name(), "deinterlace", Some(&deinterlace_args))?;
+            self.sink_ctx = self.create_filter("buffersink", "out", None)?;
+
+            for (src, dst) in [(self.src_ctx, deinterlace_ctx), (deinterlace_ctx, self.sink_ctx)] {
+                let ret = avfilter_link(src, 0, dst, 0);
+                if ret < 0 {
+                    return Err(format!("Failed to link deinterlace filters: {}", av_err2str(ret)));
+                }
+            }
+
+            let ret = avfilter_graph_config(self.graph, null_mut());
+            if ret < 0 {
+                return Err(format!("Failed to configure deinterlace filter graph: {}", av_err2str(ret)));
+            }
+        }
+
+        self.key = key;
+        Ok(())
+    }
+
+    unsafe fn create_filter(&self, name: &str, instance: &str, args: Option<&str>) -> Result<*mut AVFilterContext, String> {
+        let name_cstr = CString::new(name).map_err(|e| e.to_string())?;
+        let filter = avfilter_get_by_name(name_cstr.as_ptr());
+        if filter.is_null() {
+            return Err(format!("Filter '{name}' is not available in this FFmpeg build."));
+        }
+
+        let instance_cstr = CString::new(instance).map_err(|e| e.to_string())?;
+        let args_cstr = args.map(CString::new).transpose().map_err(|e| e.to_string())?;
+        let mut ctx = null_mut();
+        let ret = avfilter_graph_create_filter(
+            &mut ctx,
+            filter,
+            instance_cstr.as_ptr(),
+            args_cstr.as_ref().map_or(null(), |args| args.as_ptr()),
+            null_mut(),
+            self.graph,
+        );
+        if ret < 0 {
+            return Err(format!("Failed to create filter '{name}': {}", av_err2str(ret)));
+        }
+        Ok(ctx)
+    }
+
+    /// Pulls the next deinterlaced frame, `None` when the graph needs more input or is drained.
+    fn receive(&mut self) -> Result<Option<Frame>, String> {
+        if self.graph.is_null() {
+            return Ok(None);
+        }
+        unsafe {
+            let mut frame = Frame::empty();
+            if frame.as_ptr().is_null() {
+                return Err("Failed to create frame: Out of memory.".to_string());
+            }
+            let ret = av_buffersink_get_frame(self.sink_ctx, frame.as_mut_ptr());
+            if ret == AVERROR(EAGAIN) || ret == AVERROR_EOF {
+                return Ok(None);
+            }
+            if ret < 0 {
+                return Err(format!("Failed to get deinterlaced frame: {}", av_err2str(ret)));
+            }
+            // double rate halves the time base
+            (*frame.as_mut_ptr()).time_base = av_buffersink_get_time_base(self.sink_ctx);
+            Ok(Some(frame))
+        }
+    }
+
+    /// Sends the end of stream to the graph, so it emits the frame held back for look-ahead.
+    fn flush(&mut self) -> Result<(), String> {
+        let ret = unsafe { av_buffersrc_add_frame_flags(self.src_ctx, null_mut(), 0) };
+        if ret < 0 {
+            return Err(format!("Failed to flush deinterlace filter: {}", av_err2str(ret)));
+        }
+        Ok(())
+    }
+
+    /// Like `receive`, but starts with the frames of a replaced graph and forwards the pending
+    /// end-of-stream frame once the graph is drained. The graph is then released, so frames
+    /// arriving afterwards (e.g. a looped input) start a new one.
+    fn drain(&mut self) -> Result<Option<Frame>, String> {
+        if let Some(frame) = self.drained.pop_front() {
+            return Ok(Some(frame));
+        }
+        if let Some(frame) = self.receive()? {
+            return Ok(Some(frame));
+        }
+        if self.pending_eof.is_some() {
+            self.free_graph();
+        }
+        Ok(self.pending_eof.take())
+    }
+
+    fn free_graph(&mut self) {
+        if !self.graph.is_null() {
+            unsafe { avfilter_graph_free(&mut self.graph) };
+        }
+        self.src_ctx = null_mut();
+        self.sink_ctx = null_mut();
+    }
+}
+
+impl FrameFilter for DeinterlaceFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+
+            if frame.is_empty() {
+                if self.graph.is_null() {
+                    return Ok(Some(frame));
+                }
+                // flush the frame held back for look-ahead
+                self.flush()?;
+                self.pending_eof = Some(frame);
+                return self.drain();
+            }
+        }
+
+        self.configure(&frame)?;
+        let ret = unsafe { av_buffersrc_add_frame_flags(self.src_ctx, frame.as_mut_ptr(), 0) };
+        if ret < 0 {
+            return Err(format!("Failed to feed deinterlace filter: {}", av_err2str(ret)));
+        }
+        match self.drained.pop_front() {
+            Some(frame) => Ok(Some(frame)),
+            None => self.receive(),
+        }
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        self.drain()
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        self.drained.clear();
+        self.free_graph();
+    }
+}
+
+impl Drop for DeinterlaceFilter {
+    fn drop(&mut self) {
+        self.free_graph();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use ffmpeg_sys_next::{av_frame_get_buffer, AVRational};
+    use std::collections::HashMap;
+
+    fn frame(width: i32, height: i32, pts: i64) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = width;
+            (*f).height = height;
+            (*f).pts = pts;
+            (*f).time_base = AVRational { num: 1, den: 25 };
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for plane in 0..3 {
+                let size = (*f).linesize[plane] as usize * (height as usize >> (plane > 0) as usize);
+                std::slice::from_raw_parts_mut((*f).data[plane], size).fill(128);
+            }
+            frame
+        }
+    }
+
+    #[test]
+    fn test_size_change_drains_the_old_graph() {
+        let ctx = FrameFilterContext::new("deinterlace", &mut HashMap::new());
+        let mut filter = DeinterlaceFilter::new(DeinterlaceAlgorithm::Yadif);
+
+        let mut widths = Vec::new();
+        let inputs = [frame(32, 16, 0), frame(32, 16, 1), frame(32, 16, 2), frame(64, 32, 3), Frame::empty()];
+        for input in inputs {
+            let mut output = filter.filter_frame(input, &ctx).unwrap();
+            while let Some(frame) = output {
+                if frame.is_empty() {
+                    break;
+                }
+                widths.push(unsafe { (*frame.as_ptr()).width });
+                output = filter.request_frame(&ctx).unwrap();
+            }
+        }
+
+        // the frame held back by the first graph is not lost, and comes before the new size
+        assert_eq!(widths, vec![32, 32, 32, 64]);
+    }
+}
@@ -0,0 +1,279 @@
+//! A [`FrameFilter`] that detects edges in video frames with a Sobel operator, a small worked
+//! example of a filter computing on pixels.
+//!
+//! The luma of each frame is obtained by converting it to `gray8` (or, in overlay mode, from
+//! its RGB conversion). The horizontal and vertical Sobel gradients are computed for every
+//! pixel, with the picture edges extended, and a pixel is an edge when the magnitude of the
+//! gradient, clamped to 0-255, is at least the threshold. Depending on the [`EdgeMode`], the
+//! frame is then replaced by the white-on-black edge map, or the edges are painted over it. In
+//! overlay mode, frames of more than 8 bits are painted in 16-bit RGB, so the rest of the
+//! picture keeps its precision.
+//!
+//! The result is written back in the frame's own pixel format and size, with its timestamps
+//! and properties untouched, so the filter can run right before an encoder. Hardware frames
+//! are passed through untouched. With a [`Region`], the rest of the frame keeps its original
+//! pixels (frames in packed YUV formats like `yuyv422` are then rejected).
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("edges", Box::new(
+//!         EdgeDetectFilter::new()
+//!             .set_threshold(60)
+//!             .set_mode(EdgeMode::Overlay { color: [0, 255, 0] }),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{bit_depth, reference_frame, FramePlanes};
+use crate::core::filter::region::{restore_outside, Region};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVMediaType;
+use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_GRAY8, AV_PIX_FMT_RGB24, AV_PIX_FMT_RGB48LE};
+
+/// What [`EdgeDetectFilter`] outputs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// White edges on a black picture. This is the default.
+    EdgesOnly,
+    /// The original picture with the edges painted in `color` (RGB).
+    Overlay { color: [u8; 3] },
+}
+
+pub struct EdgeDetectFilter {
+    threshold: u8,
+    mode: EdgeMode,
+    region: Option<Region>,
+
+    to_work: FrameConverter,
+    from_work: FrameConverter,
+}
+
+impl EdgeDetectFilter {
+    /// Creates a filter outputting the edges only, with a threshold of 80.
+    pub fn new() -> Self {
+        Self {
+            threshold: 80,
+            mode: EdgeMode::EdgesOnly,
+            region: None,
+            to_work: FrameConverter::new(),
+            from_work: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the lowest gradient magnitude (0-255) of an edge pixel. Lower values keep
+    /// fainter edges, and more noise.
+    pub fn set_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets what the filter outputs, see [`EdgeMode`].
+    pub fn set_mode(mut self, mode: EdgeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Detects edges only inside `region`, instead of the whole frame. Frames the region lies
+    /// outside of are rejected.
+    pub fn set_region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+}
+
+impl Default for EdgeDetectFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for EdgeDetectFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        // the converters make the frame writable, so this reference keeps the original pixels
+        let original = match &self.region {
+            Some(region) => Some((region.bounds(&frame)?, reference_frame(&frame)?)),
+            None => None,
+        };
+        match self.mode {
+            EdgeMode::EdgesOnly => {
+                let gray = self.to_work.convert(&frame, AV_PIX_FMT_GRAY8, width as i32, height as i32)?;
+                let edges = sobel_edges(&gray.plane_u8(0)?.concat(), width, height, self.threshold);
+                for (row, edges) in gray.plane_u8_mut(0)?.into_iter().zip(edges.chunks_exact(width)) {
+                    for (value, edge) in row.iter_mut().zip(edges) {
+                        *value = if *edge { 255 } else { 0 };
+                    }
+                }
+                self.from_work.convert_into(gray, &mut frame)?;
+            }
+            EdgeMode::Overlay { color } => {
+                let high_bit_depth = bit_depth(&frame).is_some_and(|depth| depth > 8);
+                let rgb_format = if high_bit_depth { AV_PIX_FMT_RGB48LE } else { AV_PIX_FMT_RGB24 };
+                let rgb = self.to_work.convert(&frame, rgb_format, width as i32, height as i32)?;
+                if rgb.plane_info(0)?.bytes_per_sample == 2 {
+                    let luma = rgb_luma(&rgb.plane_u16(0)?, |sample| (sample >> 8) as u32);
+                    let edges = sobel_edges(&luma, width, height, self.threshold);
+                    paint_edges(rgb.plane_u16_mut(0)?, &edges, width, color.map(|c| c as u16 * 257));
+                } else {
+                    let luma = rgb_luma(&rgb.plane_u8(0)?, |sample| sample as u32);
+                    let edges = sobel_edges(&luma, width, height, self.threshold);
+                    paint_edges(rgb.plane_u8_mut(0)?, &edges, width, color);
+                }
+                self.from_work.convert_into(rgb, &mut frame)?;
+            }
+        }
+        if let Some((bounds, original)) = &original {
+            restore_outside(bounds, original, &mut frame)?;
+        }
+        Ok(Some(frame))
+    }
+}
+
+/// Returns the 8-bit luma of the rows of a packed RGB plane, `to_8_bit` reducing its samples to
+/// 8 bits.
+fn rgb_luma<T: Copy>(rows: &[&[T]], to_8_bit: impl Fn(T) -> u32) -> Vec<u8> {
+    rows.iter()
+        .flat_map(|row| row.chunks_exact(3))
+        .map(|pixel| ((77 * to_8_bit(pixel[0]) + 150 * to_8_bit(pixel[1]) + 29 * to_8_bit(pixel[2])) >> 8) as u8)
+        .collect()
+}
+
+/// Paints the pixels of the rows of a packed RGB plane that are `edges` in `color`.
+fn paint_edges<T: Copy>(rows: Vec<&mut [T]>, edges: &[bool], width: usize, color: [T; 3]) {
+    for (row, edges) in rows.into_iter().zip(edges.chunks_exact(width)) {
+        for (pixel, edge) in row.chunks_exact_mut(3).zip(edges) {
+            if *edge {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Returns, for each pixel of the `width` x `height` `luma` plane, whether the magnitude of
+/// its Sobel gradient, clamped to 255, is at least `threshold`. Pixels outside the picture
+/// repeat the nearest edge pixel.
+fn sobel_edges(luma: &[u8], width: usize, height: usize, threshold: u8) -> Vec<bool> {
+    let at = |x: isize, y: isize| -> i32 {
+        let x = x.clamp(0, width as isize - 1) as usize;
+        let y = y.clamp(0, height as isize - 1) as usize;
+        luma[y * width + x] as i32
+    };
+    let threshold = threshold as i32;
+
+    let mut edges = Vec::with_capacity(width * height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            let magnitude = (gx * gx + gy * gy).min(255 * 255);
+            edges.push(magnitude >= threshold * threshold);
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_sobel_edges() {
+        // 6x4 picture, dark on the left half and bright on the right half
+        let luma: Vec<u8> = (0..4).flat_map(|_| [20, 20, 20, 200, 200, 200]).collect();
+        let edges = sobel_edges(&luma, 6, 4, 80);
+        for row in edges.chunks_exact(6) {
+            assert_eq!(row, [false, false, true, true, false, false]);
+        }
+
+        // a flat picture has no edges, even with a zero gradient allowed
+        assert!(sobel_edges(&[128; 16], 4, 4, 1).iter().all(|edge| !edge));
+    }
+
+    #[test]
+    fn test_edge_detect() {
+        for mode in [EdgeMode::EdgesOnly, EdgeMode::Overlay { color: [255, 0, 0] }] {
+            let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                .filter("edges", Box::new(EdgeDetectFilter::new().set_mode(mode)));
+            let output = Output::from("output_edges.mp4")
+                .set_recording_time_us(1_000_000)
+                .add_frame_pipeline(pipeline);
+            let result = FfmpegContext::builder()
+                .input(Input::from("test.mp4"))
+                .output(output)
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait();
+            assert!(result.is_ok(), "{mode:?}: {:?}", result.err());
+        }
+        std::fs::remove_file("output_edges.mp4").unwrap();
+    }
+
+    /// A 32x16 `yuv420p` frame whose luma is dark on the left half and bright on the right half.
+    fn split_frame() -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 32;
+            (*f).height = 16;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for row in frame.plane_u8_mut(0).unwrap() {
+                row[..16].fill(20);
+                row[16..].fill(200);
+            }
+            for plane in 1..3 {
+                for row in frame.plane_u8_mut(plane).unwrap() {
+                    row.fill(128);
+                }
+            }
+            frame
+        }
+    }
+
+    #[test]
+    fn test_edge_detect_region() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("edges", &mut attributes);
+
+        // the top half only
+        let mut filter = EdgeDetectFilter::new().set_region(Region::new(0, 0, 32, 8));
+        let input = split_frame();
+        let output = filter.filter_frame(split_frame(), &ctx).unwrap().unwrap();
+        let luma = output.plane_u8(0).unwrap();
+        assert!(luma[2][16] > 200 && luma[2][28] < 50, "{:?}", luma[2]);
+
+        // the pixels outside the region are those of the input
+        assert_eq!(luma[8..], input.plane_u8(0).unwrap()[8..]);
+        for plane in 1..3 {
+            assert_eq!(output.plane_u8(plane).unwrap()[4..], input.plane_u8(plane).unwrap()[4..], "plane {plane}");
+        }
+    }
+}
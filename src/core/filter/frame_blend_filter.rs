@@ -0,0 +1,281 @@
+//! A [`FrameFilter`] that blends each video frame with its neighbours, for motion-blur or
+//! "slow shutter" looks.
+//!
+//! The filter keeps the last N frames and outputs, for every input frame, a weighted average
+//! of the window ending on that frame. With [`FrameBlendFilter::set_centered`] the window is
+//! centered on the frame instead, so output is delayed by `N / 2` frames and the remaining
+//! frames are flushed when the end of stream reaches the filter.
+//!
+//! Near the start and the end of the stream the window is incomplete; the frames that are
+//! available are blended with their weights renormalized. The window is restarted when the
+//! frame size or pixel format changes, so frames of different formats are never mixed.
+//!
+//! Frames are blended in their own pixel format, which must use 8 or 9-16 bit (native endian)
+//! integer components, e.g. `yuv420p`, `nv12`, `rgb24` or `yuv420p10le`.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("blend", Box::new(
+//!         FrameBlendFilter::new(4).set_weights(vec![1.0, 2.0, 3.0, 4.0]),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::util::ffmpeg_utils::av_err2str;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{av_frame_copy_props, av_frame_get_buffer, AVMediaType};
+use std::collections::VecDeque;
+
+pub struct FrameBlendFilter {
+    frame_count: usize,
+    weights: Option<Vec<f32>>,
+    centered: bool,
+
+    // `frames[0]` is the frame received as number `first_index`
+    frames: VecDeque<Frame>,
+    first_index: usize,
+    received: usize,
+    next_output: usize,
+    ready: VecDeque<Frame>,
+    row_sum: Vec<f32>,
+}
+
+impl FrameBlendFilter {
+    /// Creates a filter averaging the last `frame_count` frames with equal weights.
+    pub fn new(frame_count: usize) -> Self {
+        Self {
+            frame_count,
+            weights: None,
+            centered: false,
+            frames: VecDeque::new(),
+            first_index: 0,
+            received: 0,
+            next_output: 0,
+            ready: VecDeque::new(),
+            row_sum: Vec::new(),
+        }
+    }
+
+    /// Sets the weight of each frame of the window, from the oldest to the newest.
+    /// Must contain `frame_count` non-negative values with a positive sum.
+    pub fn set_weights(mut self, weights: Vec<f32>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Centers the window on the output frame (`true`) instead of ending it on the
+    /// output frame (`false`, the default). Centering keeps the blur aligned with the
+    /// motion, at the cost of `frame_count / 2` frames of delay.
+    pub fn set_centered(mut self, centered: bool) -> Self {
+        self.centered = centered;
+        self
+    }
+
+    /// Number of frames after the output frame that are part of its window.
+    fn lookahead(&self) -> usize {
+        if self.centered {
+            self.frame_count / 2
+        } else {
+            0
+        }
+    }
+
+    fn push_frame(&mut self, frame: Frame) -> Result<(), String> {
+        if let Some(last) = self.frames.back() {
+            if frame_key(last) != frame_key(&frame) {
+                log::debug!("Frame blend input format changed, restarting the blend window.");
+                self.flush()?;
+            }
+        }
+
+        self.frames.push_back(frame);
+        self.received += 1;
+
+        let lookahead = self.lookahead();
+        while self.next_output + lookahead < self.received {
+            self.emit(self.next_output)?;
+            self.next_output += 1;
+        }
+
+        // keep the frames the window of the next output still needs
+        let keep_from = (self.next_output + lookahead + 1).saturating_sub(self.frame_count);
+        while self.first_index < keep_from {
+            self.frames.pop_front();
+            self.first_index += 1;
+        }
+        Ok(())
+    }
+
+    /// Emits the frames still waiting for look-ahead and restarts the window.
+    fn flush(&mut self) -> Result<(), String> {
+        while self.next_output < self.received {
+            self.emit(self.next_output)?;
+            self.next_output += 1;
+        }
+        self.frames.clear();
+        self.first_index = self.received;
+        Ok(())
+    }
+
+    fn emit(&mut self, output_index: usize) -> Result<(), String> {
+        let lookahead = self.lookahead();
+        // position of `frames[0]` within the full window of `output_index`
+        let window_start = (output_index + lookahead + 1) as isize - self.frame_count as isize;
+        let first = window_start.max(self.first_index as isize) as usize;
+        let last = (output_index + lookahead).min(self.received - 1);
+
+        let weights = match &self.weights {
+            Some(weights) => weights.clone(),
+            None => vec![1.0; self.frame_count],
+        };
+        let weights = blend_weights(&weights, (first as isize - window_start) as usize, last - first + 1)?;
+        let sources: Vec<&Frame> = (first..=last).map(|i| &self.frames[i - self.first_index]).collect();
+        let template = &self.frames[output_index - self.first_index];
+
+        let output = unsafe { blend_frames(&sources, &weights, template, &mut self.row_sum)? };
+        self.ready.push_back(output);
+        Ok(())
+    }
+}
+
+impl FrameFilter for FrameBlendFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if self.frame_count == 0 {
+            return Err("Frame blend needs at least one frame".to_string());
+        }
+        if let Some(weights) = &self.weights {
+            if weights.len() != self.frame_count {
+                return Err(format!(
+                    "Frame blend expects {} weights, got {}",
+                    self.frame_count,
+                    weights.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        if frame.is_empty() {
+            // end of stream: emit the partial windows, then forward it
+            self.flush()?;
+            self.ready.push_back(frame);
+        } else {
+            self.push_frame(frame)?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        Ok(self.ready.pop_front())
+    }
+}
+
+fn frame_key(frame: &Frame) -> (i32, i32, i32) {
+    unsafe { ((*frame.as_ptr()).format, (*frame.as_ptr()).width, (*frame.as_ptr()).height) }
+}
+
+/// Returns the normalized weights of `len` frames starting at `first_slot` of the full window.
+fn blend_weights(weights: &[f32], first_slot: usize, len: usize) -> Result<Vec<f32>, String> {
+    let weights = &weights[first_slot..first_slot + len];
+    if weights.iter().any(|weight| *weight < 0.0) {
+        return Err("Frame blend weights must not be negative".to_string());
+    }
+    let sum: f32 = weights.iter().sum();
+    if sum <= 0.0 {
+        // e.g. only zero-weight frames are available yet: show the newest one
+        let mut weights = vec![0.0; len];
+        weights[len - 1] = 1.0;
+        return Ok(weights);
+    }
+    Ok(weights.iter().map(|weight| weight / sum).collect())
+}
+
+/// Writes the weighted average of `sources` into a new frame with the properties of `template`.
+unsafe fn blend_frames(
+    sources: &[&Frame],
+    weights: &[f32],
+    template: &Frame,
+    row_sum: &mut Vec<f32>,
+) -> Result<Frame, String> {
+    let (format, width, height) = frame_key(template);
+    // checks the pixel format before allocating anything
+    let plane_infos = (0..template.plane_count())
+        .map(|plane| template.plane_info(plane))
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("Frame blend does not support pixel format {format}: {e}"))?;
+
+    let mut output = Frame::empty();
+    if output.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let dst = output.as_mut_ptr();
+    (*dst).format = format;
+    (*dst).width = width;
+    (*dst).height = height;
+    let ret = av_frame_get_buffer(dst, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+    let ret = av_frame_copy_props(dst, template.as_ptr());
+    if ret < 0 {
+        return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+    }
+
+    for (plane, info) in plane_infos.iter().enumerate() {
+        row_sum.resize(info.samples_per_row, 0.0);
+        if info.bytes_per_sample == 2 {
+            let source_rows = sources
+                .iter()
+                .map(|source| source.plane_u16(plane))
+                .collect::<Result<Vec<_>, String>>()?;
+            for (y, out) in output.plane_u16_mut(plane)?.into_iter().enumerate() {
+                row_sum.iter_mut().for_each(|sum| *sum = 0.0);
+                for (rows, weight) in source_rows.iter().zip(weights) {
+                    row_sum.iter_mut().zip(rows[y]).for_each(|(sum, v)| *sum += *v as f32 * weight);
+                }
+                out.iter_mut().zip(row_sum.iter()).for_each(|(o, sum)| *o = sum.round().min(u16::MAX as f32) as u16);
+            }
+        } else {
+            let source_rows = sources
+                .iter()
+                .map(|source| source.plane_u8(plane))
+                .collect::<Result<Vec<_>, String>>()?;
+            for (y, out) in output.plane_u8_mut(plane)?.into_iter().enumerate() {
+                row_sum.iter_mut().for_each(|sum| *sum = 0.0);
+                for (rows, weight) in source_rows.iter().zip(weights) {
+                    row_sum.iter_mut().zip(rows[y]).for_each(|(sum, v)| *sum += *v as f32 * weight);
+                }
+                out.iter_mut().zip(row_sum.iter()).for_each(|(o, sum)| *o = sum.round().min(u8::MAX as f32) as u8);
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_weights() {
+        assert_eq!(blend_weights(&[1.0, 1.0, 2.0], 0, 3).unwrap(), vec![0.25, 0.25, 0.5]);
+        // start of stream: only the newest two frames of the window exist
+        assert_eq!(blend_weights(&[1.0, 1.0, 2.0], 1, 2).unwrap(), vec![1.0 / 3.0, 2.0 / 3.0]);
+        assert_eq!(blend_weights(&[1.0, 0.0], 1, 1).unwrap(), vec![1.0]);
+        assert!(blend_weights(&[1.0, -1.0], 0, 2).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::core::filter::frame_planes::{make_writable, reference_frame};
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_frame_apply_cropping, av_frame_get_buffer, av_pix_fmt_desc_get, sws_getCoefficients, sws_scale,
+    sws_setColorspaceDetails, AVColorSpace, AVPixelFormat, AV_FRAME_CROP_UNALIGNED,
+};
+
+/// A rectangle of a frame: x, y, width and height, in pixels.
+pub(crate) type FrameRegion = (usize, usize, usize, usize);
+
+/// Converts video frames to a given pixel format and size, caching the scaler and
+/// the destination frame between calls. Shared by the built-in video `FrameFilter`s.
+pub(crate) struct FrameConverter {
+    scaler: Option<ffmpeg_next::software::scaling::Context>,
+    dst_frame: Option<Frame>,
+    key: (i32, i32, i32, i32, i32, i32),
+    /// YUV matrix and full-range flag of the source and destination, swscale's defaults when `None`.
+    colorspace: Option<((AVColorSpace, bool), (AVColorSpace, bool))>,
+}
+
+unsafe impl Send for FrameConverter {}
+
+impl FrameConverter {
+    pub(crate) fn new() -> Self {
+        Self {
+            scaler: None,
+            dst_frame: None,
+            key: (0, 0, 0, 0, 0, 0),
+            colorspace: None,
+        }
+    }
+
+    /// Sets the YUV matrix and full-range flag used for the source and the destination of the
+    /// next conversions, instead of swscale's BT.601 limited range default.
+    pub(crate) fn set_colorspace(&mut self, src: (AVColorSpace, bool), dst: (AVColorSpace, bool)) {
+        if self.colorspace != Some((src, dst)) {
+            self.colorspace = Some((src, dst));
+            // recreated with the new details on the next conversion
+            self.scaler = None;
+        }
+    }
+
+    fn apply_colorspace(&mut self) -> Result<(), String> {
+        let (Some(scaler), Some(((src_space, src_full), (dst_space, dst_full)))) =
+            (self.scaler.as_mut(), self.colorspace)
+        else {
+            return Ok(());
+        };
+        unsafe {
+            let ret = sws_setColorspaceDetails(
+                scaler.as_mut_ptr(),
+                sws_getCoefficients(src_space as i32),
+                src_full as i32,
+                sws_getCoefficients(dst_space as i32),
+                dst_full as i32,
+                0,
+                1 << 16,
+                1 << 16,
+            );
+            if ret < 0 {
+                return Err(format!("Failed to set scaler colorspace: {}", av_err2str(ret)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts `frame` into an internally owned frame of `dst_format` / `dst_width` x `dst_height`.
+    pub(crate) fn convert(
+        &mut self,
+        frame: &Frame,
+        dst_format: AVPixelFormat,
+        dst_width: i32,
+        dst_height: i32,
+    ) -> Result<&mut Frame, String> {
+        let (src_format, src_width, src_height) =
+            unsafe { ((*frame.as_ptr()).format, (*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+        let key = (src_format, src_width, src_height, dst_format as i32, dst_width, dst_height);
+
+        if self.scaler.is_none() || self.key != key {
+            let src_pixel = pixel_format(src_format).ok_or_else(|| format!("Unknown pixel format {src_format}"))?;
+            let scaler = ffmpeg_next::software::scaling::Context::get(
+                ffmpeg_next::format::Pixel::from(src_pixel),
+                src_width as u32,
+                src_height as u32,
+                ffmpeg_next::format::Pixel::from(dst_format),
+                dst_width as u32,
+                dst_height as u32,
+                ffmpeg_next::software::scaling::Flags::BILINEAR,
+            )
+            .map_err(|e| format!("Failed to create scaler: {e}"))?;
+
+            let mut dst_frame = unsafe { Frame::empty() };
+            unsafe {
+                if dst_frame.as_ptr().is_null() {
+                    return Err("Failed to create frame: Out of memory.".to_string());
+                }
+                (*dst_frame.as_mut_ptr()).width = dst_width;
+                (*dst_frame.as_mut_ptr()).height = dst_height;
+                (*dst_frame.as_mut_ptr()).format = dst_format as i32;
+                let ret = av_frame_get_buffer(dst_frame.as_mut_ptr(), 0);
+                if ret < 0 {
+                    return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+                }
+            }
+
+            self.scaler = Some(scaler);
+            self.dst_frame = Some(dst_frame);
+            self.key = key;
+            self.apply_colorspace()?;
+        }
+
+        let dst_frame = self.dst_frame.as_mut().unwrap();
+        unsafe {
+            (*dst_frame.as_mut_ptr()).pts = (*frame.as_ptr()).pts;
+            (*dst_frame.as_mut_ptr()).time_base = (*frame.as_ptr()).time_base;
+
+            let ret = sws_scale(
+                self.scaler.as_mut().unwrap().as_mut_ptr(),
+                (*frame.as_ptr()).data.as_ptr() as *const *const _,
+                (*frame.as_ptr()).linesize.as_ptr() as *const _,
+                0,
+                src_height,
+                (*dst_frame.as_mut_ptr()).data.as_ptr(),
+                (*dst_frame.as_mut_ptr()).linesize.as_ptr() as *mut _,
+            );
+            if ret <= 0 {
+                return Err(format!("Failed to scale frame: {}", av_err2str(ret)));
+            }
+        }
+
+        Ok(dst_frame)
+    }
+
+    /// Converts `region` of `frame`, which must be aligned with [`align_region`], into an
+    /// internally owned frame of `dst_format` and the size of the region.
+    pub(crate) fn convert_region(
+        &mut self,
+        frame: &Frame,
+        dst_format: AVPixelFormat,
+        region: FrameRegion,
+    ) -> Result<&mut Frame, String> {
+        let (_, _, width, height) = region;
+        let cropped = crop(frame, region)?;
+        self.convert(&cropped, dst_format, width as i32, height as i32)
+    }
+
+    /// Converts `src` into the region of `dst` of the size of `src` at (`x`, `y`), keeping the
+    /// format of `dst` and the pixels outside of the region. The region must be aligned with
+    /// [`align_region`].
+    pub(crate) fn convert_into_region(
+        &mut self,
+        src: &Frame,
+        dst: &mut Frame,
+        x: usize,
+        y: usize,
+    ) -> Result<(), String> {
+        let (width, height) = unsafe { ((*src.as_ptr()).width as usize, (*src.as_ptr()).height as usize) };
+        // the cropped reference writes into the buffers of `dst`, which must not be shared
+        make_writable(dst)?;
+        let mut cropped = crop(dst, (x, y, width, height))?;
+        self.scale_into(src, &mut cropped)
+    }
+
+    /// Converts `src` into the already allocated `dst` frame, keeping `dst`'s format and size.
+    pub(crate) fn convert_into(&mut self, src: &Frame, dst: &mut Frame) -> Result<(), String> {
+        make_writable(dst)?;
+        self.scale_into(src, dst)
+    }
+
+    /// Converts `src` into the writable `dst` frame.
+    fn scale_into(&mut self, src: &Frame, dst: &mut Frame) -> Result<(), String> {
+        let (src_format, src_width, src_height) =
+            unsafe { ((*src.as_ptr()).format, (*src.as_ptr()).width, (*src.as_ptr()).height) };
+        let (dst_format, dst_width, dst_height) =
+            unsafe { ((*dst.as_ptr()).format, (*dst.as_ptr()).width, (*dst.as_ptr()).height) };
+        let key = (src_format, src_width, src_height, dst_format, dst_width, dst_height);
+
+        if self.scaler.is_none() || self.key != key {
+            let src_pixel = pixel_format(src_format).ok_or_else(|| format!("Unknown pixel format {src_format}"))?;
+            let dst_pixel = pixel_format(dst_format).ok_or_else(|| format!("Unknown pixel format {dst_format}"))?;
+            let scaler = ffmpeg_next::software::scaling::Context::get(
+                ffmpeg_next::format::Pixel::from(src_pixel),
+                src_width as u32,
+                src_height as u32,
+                ffmpeg_next::format::Pixel::from(dst_pixel),
+                dst_width as u32,
+                dst_height as u32,
+                ffmpeg_next::software::scaling::Flags::BILINEAR,
+            )
+            .map_err(|e| format!("Failed to create scaler: {e}"))?;
+            self.scaler = Some(scaler);
+            self.dst_frame = None;
+            self.key = key;
+            self.apply_colorspace()?;
+        }
+
+        unsafe {
+            let ret = sws_scale(
+                self.scaler.as_mut().unwrap().as_mut_ptr(),
+                (*src.as_ptr()).data.as_ptr() as *const *const _,
+                (*src.as_ptr()).linesize.as_ptr() as *const _,
+                0,
+                src_height,
+                (*dst.as_mut_ptr()).data.as_ptr(),
+                (*dst.as_mut_ptr()).linesize.as_ptr() as *mut _,
+            );
+            if ret <= 0 {
+                return Err(format!("Failed to scale frame: {}", av_err2str(ret)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Grows `region` of `frame` to the chroma subsampling grid of its pixel format, so that it
+/// can be converted without shifting the chroma, and clips it to the frame.
+pub(crate) fn align_region(frame: &Frame, (x, y, width, height): FrameRegion) -> Result<FrameRegion, String> {
+    unsafe {
+        let format = (*frame.as_ptr()).format;
+        let pix_fmt = pixel_format(format).ok_or_else(|| format!("Unknown pixel format {format}"))?;
+        let desc = av_pix_fmt_desc_get(pix_fmt);
+        if desc.is_null() {
+            return Err(format!("Unknown pixel format {format}"));
+        }
+        let (frame_width, frame_height) = ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize);
+        let align = |start: usize, len: usize, log2: u8, limit: usize| {
+            let step = 1 << log2;
+            let aligned = start / step * step;
+            (aligned, ((start + len).div_ceil(step) * step).min(limit) - aligned)
+        };
+        let (x, width) = align(x.min(frame_width), width, (*desc).log2_chroma_w, frame_width);
+        let (y, height) = align(y.min(frame_height), height, (*desc).log2_chroma_h, frame_height);
+        Ok((x, y, width, height))
+    }
+}
+
+/// Returns a reference to `region` of `frame`, sharing its buffers.
+fn crop(frame: &Frame, (x, y, width, height): FrameRegion) -> Result<Frame, String> {
+    let mut cropped = reference_frame(frame)?;
+    unsafe {
+        let f = cropped.as_mut_ptr();
+        let (frame_width, frame_height) = ((*f).width as usize, (*f).height as usize);
+        if width == 0 || height == 0 || x + width > frame_width || y + height > frame_height {
+            return Err(format!(
+                "Region {width}x{height} at ({x}, {y}) is outside of the {frame_width}x{frame_height} frame"
+            ));
+        }
+        (*f).crop_left = x;
+        (*f).crop_top = y;
+        (*f).crop_right = frame_width - x - width;
+        (*f).crop_bottom = frame_height - y - height;
+        let ret = av_frame_apply_cropping(f, AV_FRAME_CROP_UNALIGNED as i32);
+        if ret < 0 {
+            return Err(format!("Failed to crop frame: {}", av_err2str(ret)));
+        }
+    }
+    Ok(cropped)
+}
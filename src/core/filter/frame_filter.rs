@@ -1,6 +1,7 @@
 use crate::core::filter::frame_filter_context::FrameFilterContext;
 use ffmpeg_sys_next::AVMediaType;
 use ffmpeg_next::Frame;
+use std::sync::Arc;
 
 pub trait FrameFilter: Send {
     /// Returns the media type this filter operates on.
@@ -35,6 +36,10 @@ pub trait FrameFilter: Send {
     /// pipeline modifications if needed. The pipeline allows filters to set or retrieve
     /// attributes, enabling the sharing of information across filters during processing.
     ///
+    /// The frame may carry no data, e.g. at the end of the stream; wrap it in a
+    /// [`FrameView`](crate::filter::frame_view::FrameView) to check this without `unsafe`
+    /// code, and read its data with [`FramePlanes`](crate::filter::frame_planes::FramePlanes).
+    ///
     /// # Parameters
     /// - `frame`: The input frame to be processed.
     /// - `ctx`: The context that provides metadata and dynamic modification capabilities.
@@ -83,6 +88,13 @@ pub trait FrameFilter: Send {
     }
 }
 
+/// Creates a fresh instance of a [`FrameFilter`].
+///
+/// Filters added with [`FramePipelineBuilder::filter_factory`](crate::filter::frame_pipeline_builder::FramePipelineBuilder::filter_factory)
+/// keep their factory, so the pipeline can be re-created for every job built from
+/// [`FfmpegContextBuilder::template`](crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder::template).
+pub type FrameFilterFactory = Arc<dyn Fn() -> Box<dyn FrameFilter> + Send + Sync>;
+
 
 
 pub struct NoopFilter {
@@ -2,8 +2,17 @@
 //! - A name string (for debugging/logging).
 //! - A reference to the pipeline's `attribute_map`, so they can read or modify shared data
 //!   without holding a full reference to the entire pipeline.
+//! - The channel of [`PipelineEvent`]s, so they can notify the application while running.
+//!
+//! Filter methods only get a shared reference to the context, so they publish attributes
+//! with [`FrameFilterContext::publish_attribute`]; these are stored in the `attribute_map`
+//! once the method returns, where the following filters can read them.
 
+use crate::core::filter::pipeline_event::PipelineEvent;
+use crossbeam_channel::Sender;
+use ffmpeg_sys_next::AVRational;
 use std::any::Any;
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 /// The context passed to each filter method (init, filter_frame, request_frame, uninit).
@@ -11,12 +20,39 @@ use std::collections::HashMap;
 pub struct FrameFilterContext<'a> {
     name: &'a str,
     attribute_map: &'a mut HashMap<String, Box<dyn Any + std::marker::Send>>,
+    sample_aspect_ratio: Option<AVRational>,
+    frame_rate: Option<AVRational>,
+    event_sender: Option<&'a Sender<PipelineEvent>>,
+    // attributes published through a shared reference, moved to `attribute_map` on drop
+    published: RefCell<Vec<(String, Box<dyn Any + std::marker::Send>)>>,
 }
 
 impl<'a> FrameFilterContext<'a> {
     /// Creates a new context for a specific filter name and attribute map.
     pub fn new(name: &'a str, attribute_map: &'a mut HashMap<String, Box<dyn Any + std::marker::Send>>) -> Self {
-        Self { name, attribute_map }
+        Self {
+            name,
+            attribute_map,
+            sample_aspect_ratio: None,
+            frame_rate: None,
+            event_sender: None,
+            published: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn with_sample_aspect_ratio(mut self, sample_aspect_ratio: Option<AVRational>) -> Self {
+        self.sample_aspect_ratio = sample_aspect_ratio;
+        self
+    }
+
+    pub(crate) fn with_frame_rate(mut self, frame_rate: Option<AVRational>) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    pub(crate) fn with_event_sender(mut self, event_sender: Option<&'a Sender<PipelineEvent>>) -> Self {
+        self.event_sender = event_sender;
+        self
     }
 
     /// Returns the filter's name, useful for logging or debugging.
@@ -24,6 +60,37 @@ impl<'a> FrameFilterContext<'a> {
         self.name
     }
 
+    /// Returns the sample (pixel) aspect ratio of the video frames, taken from the
+    /// decoder stream and updated from the frames themselves.
+    ///
+    /// A frame of `width` pixels is meant to be displayed `width * num / den` pixels wide,
+    /// e.g. `64:45` for 16:9 PAL DVDs stored as 720x576. `None` for audio, or when the
+    /// ratio is unknown, in which case pixels are usually square.
+    pub fn sample_aspect_ratio(&self) -> Option<AVRational> {
+        self.sample_aspect_ratio
+    }
+
+    /// Returns the frame rate of the video stream, taken from the decoder stream (input
+    /// pipelines) or the filter graph (output pipelines).
+    ///
+    /// `None` for audio, or when the rate is unknown, e.g. for variable frame rate streams
+    /// whose container does not tell an average.
+    pub fn frame_rate(&self) -> Option<AVRational> {
+        self.frame_rate
+    }
+
+    /// Sends `event` to the application, which receives it from
+    /// [`FfmpegScheduler::pipeline_events`](crate::FfmpegScheduler::pipeline_events).
+    ///
+    /// Never blocks: the event is dropped if the application did not ask for the events
+    /// or does not keep up with them. Returns whether the event was queued.
+    pub fn send_event(&self, event: PipelineEvent) -> bool {
+        match self.event_sender {
+            Some(sender) => sender.try_send(event).is_ok(),
+            None => false,
+        }
+    }
+
     /// Retrieves an attribute by `key`, downcasting it to `T`.
     pub fn get_attribute<T: 'static>(&self, key: &str) -> Option<&T> {
         self.attribute_map
@@ -43,8 +110,25 @@ impl<'a> FrameFilterContext<'a> {
         self.attribute_map.insert(key.to_string(), Box::new(value));
     }
 
+    /// Inserts or replaces an attribute under `key` from a filter method, which only gets a
+    /// shared reference to the context.
+    ///
+    /// The attribute is stored when the method returns, so it is visible to the filters after
+    /// this one for the same frame, and to this filter from its next call on.
+    pub fn publish_attribute<T: 'static + std::marker::Send>(&self, key: &str, value: T) {
+        self.published.borrow_mut().push((key.to_string(), Box::new(value)));
+    }
+
     /// Removes an attribute by `key` and returns it as `Box<dyn Any>` if found.
     pub fn remove_attribute(&mut self, key: &str) -> Option<Box<dyn Any + std::marker::Send>> {
         self.attribute_map.remove(key)
     }
 }
+
+impl Drop for FrameFilterContext<'_> {
+    fn drop(&mut self) {
+        for (key, value) in self.published.get_mut().drain(..) {
+            self.attribute_map.insert(key, value);
+        }
+    }
+}
@@ -1,14 +1,64 @@
-use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter::{FrameFilter, FrameFilterFactory};
 use crate::core::filter::frame_filter_context::FrameFilterContext;
-use ffmpeg_sys_next::AVMediaType;
+use crate::core::filter::pipeline_event::PipelineEvent;
+use crossbeam_channel::Sender;
+use ffmpeg_sys_next::{AVMediaType, AVRational};
 use std::any::Any;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use crate::filter::frame_pipeline_builder::FramePipelineBuilder;
 
 /// Internally, we store each filter along with its name in a holder.
 pub(crate) struct FilterHolder {
     name: String,
     filter: Box<dyn FrameFilter>,
+    // set when the filter can be re-created, see `FramePipeline::try_clone`
+    factory: Option<FrameFilterFactory>,
+}
+
+/// Attribute holding the number of frames that entered the pipeline so far, as a `u64`.
+pub const FRAMES_IN_ATTRIBUTE: &str = "frames_in";
+
+/// Attribute holding the number of frames the pipeline passed on so far, as a `u64`.
+pub const FRAMES_OUT_ATTRIBUTE: &str = "frames_out";
+
+/// Attribute holding the width of the decoded source video stream, before any scaling, as an
+/// `i32`. Set once the first frame reaches the pipeline; for pipelines after a filtergraph,
+/// it is the width of the first video input of the graph.
+pub const SOURCE_WIDTH_ATTRIBUTE: &str = "source_width";
+
+/// Attribute holding the height of the decoded source video stream, before any scaling, as
+/// an `i32`, see [`SOURCE_WIDTH_ATTRIBUTE`].
+pub const SOURCE_HEIGHT_ATTRIBUTE: &str = "source_height";
+
+/// Frame counts of a [`FramePipeline`], kept up to date by the scheduler while the job runs
+/// and readable once it is over.
+///
+/// `frames_in` counts the frames the decoder or filtergraph fed to the pipeline, and
+/// `frames_out` the frames that left its last filter, whether they came from
+/// `filter_frame` or from `request_frame`. A frame sent on to several destinations is
+/// counted once, and frames carrying no data (e.g. the end-of-stream timestamp) are not
+/// counted. The counts of the running pipeline are also available to its filters as the
+/// [`FRAMES_IN_ATTRIBUTE`] and [`FRAMES_OUT_ATTRIBUTE`] attributes.
+///
+/// The handle is cheap to clone; all clones read the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct FramePipelineStats {
+    frames_in: Arc<AtomicU64>,
+    frames_out: Arc<AtomicU64>,
+}
+
+impl FramePipelineStats {
+    /// Returns the number of frames that entered the pipeline.
+    pub fn frames_in(&self) -> u64 {
+        self.frames_in.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of frames that left the pipeline.
+    pub fn frames_out(&self) -> u64 {
+        self.frames_out.load(Ordering::Relaxed)
+    }
 }
 
 /// A pipeline that processes frames by passing them through all filters in order.
@@ -21,6 +71,21 @@ pub struct FramePipeline {
 
     // Shared data among all filters
     attribute_map: HashMap<String, Box<dyn Any + Send>>,
+
+    // Exposed to the filters through `FrameFilterContext::sample_aspect_ratio`
+    sample_aspect_ratio: Option<AVRational>,
+
+    // Exposed to the filters through `FrameFilterContext::frame_rate`
+    frame_rate: Option<AVRational>,
+
+    // Exposed to the filters as the `SOURCE_WIDTH_ATTRIBUTE` and `SOURCE_HEIGHT_ATTRIBUTE` attributes
+    source_size: Option<(i32, i32)>,
+
+    // Set by `FfmpegScheduler::pipeline_events`, used by `FrameFilterContext::send_event`
+    event_sender: Option<Sender<PipelineEvent>>,
+
+    // Updated by the scheduler, see `FramePipeline::stats`
+    stats: FramePipelineStats,
 }
 
 impl FramePipeline {
@@ -32,16 +97,45 @@ impl FramePipeline {
             stream_index,
             filters: Vec::new(),
             attribute_map: HashMap::new(),
+            sample_aspect_ratio: None,
+            frame_rate: None,
+            source_size: None,
+            event_sender: None,
+            stats: FramePipelineStats::default(),
         }
     }
 
     /// Adds a filter to the pipeline. No dynamic removal is provided in this simplified approach.
     pub fn add_filter(&mut self, name: impl Into<String>, filter: Box<dyn FrameFilter>) {
+        self.push_filter(name.into(), filter, None);
+    }
+
+    pub(crate) fn push_filter(&mut self, name: String, filter: Box<dyn FrameFilter>, factory: Option<FrameFilterFactory>) {
         assert_eq!(self.media_type, filter.media_type());
-        self.filters.push(FilterHolder {
-            name: name.into(),
-            filter,
-        });
+        self.filters.push(FilterHolder { name, filter, factory });
+    }
+
+    /// Returns a new pipeline with the same configuration and fresh filter instances.
+    ///
+    /// Fails if a filter was added without a factory, or if attributes were set: neither
+    /// can be copied.
+    pub(crate) fn try_clone(&self) -> Result<FramePipeline, String> {
+        if !self.attribute_map.is_empty() {
+            return Err("frame pipelines with attributes cannot be copied".to_string());
+        }
+        let mut pipeline = FramePipeline::new(self.media_type, self.stream_index);
+        for holder in &self.filters {
+            let Some(factory) = &holder.factory else {
+                return Err(format!(
+                    "frame filter '{}' was not added with a factory and cannot be re-created",
+                    holder.name
+                ));
+            };
+            pipeline.push_filter(holder.name.clone(), factory(), Some(factory.clone()));
+        }
+        // the copies count into the same handle, so it sums up every job of a template
+        pipeline.stats = self.stats.clone();
+        Ok(pipeline)
     }
 
     /// Allows external code to directly set an attribute. (Optional convenience)
@@ -56,10 +150,88 @@ impl FramePipeline {
             .and_then(|v| v.downcast_ref::<T>())
     }
 
+    /// Returns a handle to the frame counts of the pipeline, see [`FramePipelineStats`].
+    ///
+    /// Take it before handing the pipeline to an `Input` or `Output`; when the pipeline is
+    /// copied for the jobs of a template, the counts of all jobs add up.
+    pub fn stats(&self) -> FramePipelineStats {
+        self.stats.clone()
+    }
+
+    pub(crate) fn set_stats(&mut self, stats: FramePipelineStats) {
+        self.stats = stats;
+    }
+
+    /// Counts a frame that entered the pipeline.
+    pub(crate) fn count_frame_in(&mut self) {
+        self.stats.frames_in.fetch_add(1, Ordering::Relaxed);
+        self.increment_attribute(FRAMES_IN_ATTRIBUTE);
+    }
+
+    /// Counts a frame that left the pipeline.
+    pub(crate) fn count_frame_out(&mut self) {
+        self.stats.frames_out.fetch_add(1, Ordering::Relaxed);
+        self.increment_attribute(FRAMES_OUT_ATTRIBUTE);
+    }
+
+    // the attributes count the frames of this pipeline only, even when the stats are shared
+    fn increment_attribute(&mut self, key: &str) {
+        match self.attribute_map.get_mut(key).and_then(|value| value.downcast_mut::<u64>()) {
+            Some(count) => *count += 1,
+            None => {
+                self.attribute_map.insert(key.to_string(), Box::new(1u64));
+            }
+        }
+    }
+
+    pub(crate) fn set_event_sender(&mut self, event_sender: Sender<PipelineEvent>) {
+        self.event_sender = Some(event_sender);
+    }
+
+    /// Records the sample aspect ratio of the incoming video frames, ignoring unknown (`0/x`) values.
+    pub(crate) fn update_sample_aspect_ratio(&mut self, sample_aspect_ratio: AVRational) {
+        if self.media_type == AVMediaType::AVMEDIA_TYPE_VIDEO
+            && sample_aspect_ratio.num > 0
+            && sample_aspect_ratio.den > 0
+        {
+            self.sample_aspect_ratio = Some(sample_aspect_ratio);
+        }
+    }
+
+    /// Records the frame rate of the video stream, ignoring unknown (`None` or `0/x`) values.
+    pub(crate) fn update_frame_rate(&mut self, frame_rate: Option<AVRational>) {
+        if let Some(frame_rate) = frame_rate {
+            if self.media_type == AVMediaType::AVMEDIA_TYPE_VIDEO && frame_rate.num > 0 && frame_rate.den > 0 {
+                self.frame_rate = Some(frame_rate);
+            }
+        }
+    }
+
+    /// Records the size of the decoded source video stream, ignoring unknown (`0`) sizes.
+    pub(crate) fn update_source_size(&mut self, width: i32, height: i32) {
+        if self.media_type == AVMediaType::AVMEDIA_TYPE_VIDEO
+            && width > 0
+            && height > 0
+            && self.source_size != Some((width, height))
+        {
+            self.source_size = Some((width, height));
+            self.attribute_map.insert(SOURCE_WIDTH_ATTRIBUTE.to_string(), Box::new(width));
+            self.attribute_map.insert(SOURCE_HEIGHT_ATTRIBUTE.to_string(), Box::new(height));
+        }
+    }
+
+    /// Returns the size of the decoded source video stream, `(0, 0)` if unknown.
+    pub(crate) fn source_size(&self) -> (i32, i32) {
+        self.source_size.unwrap_or((0, 0))
+    }
+
     /// Initializes all filters in order.
     pub(crate) fn init_filters(&mut self) -> Result<(), String> {
         for holder in &mut self.filters {
-            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map);
+            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map)
+                .with_sample_aspect_ratio(self.sample_aspect_ratio)
+                .with_frame_rate(self.frame_rate)
+                .with_event_sender(self.event_sender.as_ref());
             holder.filter.init(&mut ctx)?;
         }
         Ok(())
@@ -69,7 +241,10 @@ impl FramePipeline {
     /// (You can reverse the order if needed, but typically it's not strict.)
     pub(crate) fn uninit_filters(&mut self) {
         for holder in &mut self.filters {
-            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map);
+            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map)
+                .with_sample_aspect_ratio(self.sample_aspect_ratio)
+                .with_frame_rate(self.frame_rate)
+                .with_event_sender(self.event_sender.as_ref());
             holder.filter.uninit(&mut ctx);
         }
     }
@@ -78,7 +253,10 @@ impl FramePipeline {
     /// the frame is dropped. Otherwise, the final `Some(frame)` is returned.
     pub(crate) fn run_filters(&mut self, mut frame: ffmpeg_next::Frame) -> Result<Option<ffmpeg_next::Frame>, String> {
         for holder in &mut self.filters {
-            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map);
+            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map)
+                .with_sample_aspect_ratio(self.sample_aspect_ratio)
+                .with_frame_rate(self.frame_rate)
+                .with_event_sender(self.event_sender.as_ref());
             match holder.filter.filter_frame(frame, &mut ctx)? {
                 Some(f) => {
                     frame = f;
@@ -98,7 +276,10 @@ impl FramePipeline {
     pub(crate) fn request_frame(&mut self, index: usize) -> Result<Option<ffmpeg_next::Frame>, String> {
         assert!(index < self.filters.len());
         let holder = &mut self.filters[index];
-        let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map);
+        let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map)
+            .with_sample_aspect_ratio(self.sample_aspect_ratio)
+            .with_frame_rate(self.frame_rate)
+            .with_event_sender(self.event_sender.as_ref());
         holder.filter.request_frame(&mut ctx)
     }
 
@@ -133,7 +314,10 @@ impl FramePipeline {
             let holder = &mut self.filters[i];
 
             // Build a temporary context, giving the filter its name and the attribute map.
-            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map);
+            let mut ctx = FrameFilterContext::new(&holder.name, &mut self.attribute_map)
+                .with_sample_aspect_ratio(self.sample_aspect_ratio)
+                .with_frame_rate(self.frame_rate)
+                .with_event_sender(self.event_sender.as_ref());
 
             // Call `filter_frame` on the filter. If `None`, discard the frame and stop.
             match holder.filter.filter_frame(frame, &mut ctx)? {
@@ -156,4 +340,23 @@ impl From<FramePipelineBuilder> for FramePipeline {
     fn from(pipeline: FramePipelineBuilder) -> Self {
         pipeline.build()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attributes_next_to_frame_counts() {
+        let mut pipeline = FramePipeline::new(AVMediaType::AVMEDIA_TYPE_VIDEO, None);
+        pipeline.set_attribute("threshold", 0.5f32);
+        pipeline.count_frame_in();
+        pipeline.count_frame_in();
+        pipeline.count_frame_out();
+
+        assert_eq!(pipeline.get_attribute::<f32>("threshold"), Some(&0.5));
+        assert_eq!(pipeline.get_attribute::<u64>("threshold"), None);
+        assert_eq!(pipeline.get_attribute::<u64>(FRAMES_IN_ATTRIBUTE), Some(&2));
+        assert_eq!(pipeline.get_attribute::<u64>(FRAMES_OUT_ATTRIBUTE), Some(&1));
+        assert_eq!((pipeline.stats().frames_in(), pipeline.stats().frames_out()), (2, 1));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::core::filter::frame_filter::FrameFilter;
-use crate::filter::frame_pipeline::FramePipeline;
+use crate::core::filter::frame_filter::{FrameFilter, FrameFilterFactory};
+use crate::filter::frame_pipeline::{FramePipeline, FramePipelineStats};
 use ffmpeg_sys_next::AVMediaType;
+use std::sync::Arc;
 
 /// A builder for constructing [`FramePipeline`] instances.
 ///
@@ -31,9 +32,13 @@ pub struct FramePipelineBuilder {
     /// Each filter is represented by a tuple containing:
     /// - A `String` name that identifies the filter.
     /// - A `Box<dyn FrameFilter>` that holds the filter implementation.
+    /// - The factory that created the filter, if it was added with [`filter_factory`](Self::filter_factory).
     ///
     /// These filters will be applied to the media frames in the order they are added.
-    pub(crate) filters: Vec<(String, Box<dyn FrameFilter>)>,
+    pub(crate) filters: Vec<(String, Box<dyn FrameFilter>, Option<FrameFilterFactory>)>,
+
+    /// The frame counts handed to the built pipeline, see [`stats`](Self::stats).
+    pub(crate) stats: FramePipelineStats,
 }
 
 impl FramePipelineBuilder {
@@ -56,6 +61,7 @@ impl FramePipelineBuilder {
             stream_index: None,
             media_type,
             filters: vec![],
+            stats: FramePipelineStats::default(),
         }
     }
 
@@ -92,6 +98,10 @@ impl FramePipelineBuilder {
     /// # Returns
     /// The modified `FramePipelineBuilder` instance, allowing method chaining.
     ///
+    /// # Panics
+    /// If the filter does not process the media type of the pipeline, or a filter is already
+    /// named `name`.
+    ///
     /// # Example
     /// ```rust
     /// let filter = Box::new(MyCustomFilter {});
@@ -100,10 +110,92 @@ impl FramePipelineBuilder {
     /// ```
     pub fn filter(mut self, name: &str, filter: Box<dyn FrameFilter>) -> Self {
         assert_eq!(self.media_type, filter.media_type());
-        self.filters.push((name.to_string(), filter));
+        self.assert_new_name(name);
+        self.filters.push((name.to_string(), filter, None));
         self
     }
 
+    /// Adds several filters to the pipeline, in order, e.g. an effect chain assembled from
+    /// a configuration file.
+    ///
+    /// This is the same as calling [`filter`](Self::filter) for each entry: every filter must
+    /// process the media type of the pipeline, and names are checked the same way.
+    ///
+    /// # Arguments
+    /// - `filters` - The `(name, filter)` pairs, in the order they are applied.
+    ///
+    /// # Returns
+    /// The modified `FramePipelineBuilder` instance, allowing method chaining.
+    ///
+    /// # Panics
+    /// If a filter does not process the media type of the pipeline, or two filters have the
+    /// same name.
+    ///
+    /// # Example
+    /// ```rust
+    /// let effects: Vec<(String, Box<dyn FrameFilter>)> = config
+    ///     .effects
+    ///     .iter()
+    ///     .map(|effect| (effect.name.clone(), effect.create_filter()))
+    ///     .collect();
+    /// let builder = FramePipelineBuilder::new(AVMEDIA_TYPE_VIDEO).filters(effects);
+    /// ```
+    pub fn filters(self, filters: Vec<(String, Box<dyn FrameFilter>)>) -> Self {
+        filters
+            .into_iter()
+            .fold(self, |builder, (name, filter)| builder.filter(&name, filter))
+    }
+
+    /// Adds a filter created by `factory` to the pipeline.
+    ///
+    /// Unlike [`filter`](Self::filter), the pipeline can then be re-created with a fresh
+    /// filter instance, which is required to reuse it in the jobs built from
+    /// [`FfmpegContextBuilder::template`](crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder::template).
+    ///
+    /// # Arguments
+    /// - `name` - The name of the filter, which serves as an identifier.
+    /// - `factory` - A closure returning a new instance of the filter on each call.
+    ///
+    /// # Returns
+    /// The modified `FramePipelineBuilder` instance, allowing method chaining.
+    ///
+    /// # Panics
+    /// If the filter does not process the media type of the pipeline, or a filter is already
+    /// named `name`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let builder = FramePipelineBuilder::new(AVMEDIA_TYPE_VIDEO)
+    ///     .filter_factory("custom", || Box::new(MyCustomFilter {}));
+    /// ```
+    pub fn filter_factory(
+        mut self,
+        name: &str,
+        factory: impl Fn() -> Box<dyn FrameFilter> + Send + Sync + 'static,
+    ) -> Self {
+        let factory: FrameFilterFactory = Arc::new(factory);
+        let filter = factory();
+        assert_eq!(self.media_type, filter.media_type());
+        self.assert_new_name(name);
+        self.filters.push((name.to_string(), filter, Some(factory)));
+        self
+    }
+
+    /// Returns a handle to the frame counts of the pipeline this builder builds, readable
+    /// once the job is over, e.g. to check how many frames a filter dropped.
+    ///
+    /// # Example
+    /// ```rust
+    /// let builder = FramePipelineBuilder::new(AVMEDIA_TYPE_VIDEO)
+    ///     .filter("drop_duplicates", Box::new(MyDedupFilter::new()));
+    /// let stats = builder.stats();
+    /// // ... run a job with the pipeline ...
+    /// println!("dropped {} frames", stats.frames_in() - stats.frames_out());
+    /// ```
+    pub fn stats(&self) -> FramePipelineStats {
+        self.stats.clone()
+    }
+
     /// Builds the `FramePipeline` instance.
     ///
     /// # Arguments
@@ -119,13 +211,22 @@ impl FramePipelineBuilder {
     ///
     pub fn build(self) -> FramePipeline {
         let mut frame_pipeline = FramePipeline::new(self.media_type, self.stream_index);
+        frame_pipeline.set_stats(self.stats);
 
-        for (name, filter) in self.filters.into_iter() {
-            frame_pipeline.add_filter(name, filter);
+        for (name, filter, factory) in self.filters.into_iter() {
+            frame_pipeline.push_filter(name, filter, factory);
         }
 
         frame_pipeline
     }
+
+    /// Panics if a filter is already named `name`, which names one filter of the pipeline.
+    fn assert_new_name(&self, name: &str) {
+        assert!(
+            self.filters.iter().all(|(existing, _, _)| existing != name),
+            "frame filter '{name}' already exists"
+        );
+    }
 }
 
 impl From<AVMediaType> for FramePipelineBuilder {
@@ -133,3 +234,49 @@ impl From<AVMediaType> for FramePipelineBuilder {
         Self::new(media_type)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::filter::frame_filter_context::FrameFilterContext;
+    use ffmpeg_next::Frame;
+
+    struct PassFilter;
+
+    impl FrameFilter for PassFilter {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            Ok(Some(frame))
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let builder = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filters(vec![
+            ("first".to_string(), Box::new(PassFilter) as Box<dyn FrameFilter>),
+            ("second".to_string(), Box::new(PassFilter)),
+        ]);
+        let names: Vec<&str> = builder.filters.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "frame filter 'pass' already exists")]
+    fn test_duplicate_filter_name() {
+        FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("pass", Box::new(PassFilter))
+            .filter_factory("pass", || Box::new(PassFilter));
+    }
+
+    #[test]
+    #[should_panic(expected = "frame filter 'pass' already exists")]
+    fn test_duplicate_name_in_filters() {
+        FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filters(vec![
+            ("pass".to_string(), Box::new(PassFilter) as Box<dyn FrameFilter>),
+            ("pass".to_string(), Box::new(PassFilter)),
+        ]);
+    }
+}
@@ -0,0 +1,533 @@
+//! Bit-depth-aware access to the pixel data of video frames, and to the samples of audio
+//! frames, for use in [`FrameFilter`](crate::filter::frame_filter::FrameFilter)s.
+//!
+//! A filter written for 8-bit `yuv420p` reads one byte per sample, which silently produces
+//! garbage on `yuv420p10le`, `p010le` or `rgb48le` frames, where every sample takes two
+//! bytes. [`FramePlanes::plane_info`] reports the layout of a plane (samples per row, rows,
+//! bytes per sample, bit depth), and the rows of the plane are exposed as `&[u8]` for 8-bit
+//! formats or `&[u16]` for 9 to 16-bit formats, with the padding at the end of the rows left
+//! out.
+//!
+//! Samples are stored in the low `bit_depth` bits, except in formats like `p010le` whose
+//! samples are shifted left, see [`PlaneInfo::shift`]. Big-endian, floating point, palette,
+//! bitstream and hardware formats are not supported.
+//!
+//! The samples of audio frames are exposed the same way, as slices of the type of their
+//! sample format ([`AudioSample`]): [`FramePlanes::samples`]`::<f32>` reads a `flt` or `fltp`
+//! frame, `::<i16>` an `s16` or `s16p` one. A planar frame has one plane per channel, a packed
+//! frame a single plane of interleaved channels.
+//!
+//! # Example
+//! ```rust,ignore
+//! use ez_ffmpeg::filter::frame_planes::FramePlanes;
+//!
+//! fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+//!     // invert the luma, whatever the bit depth
+//!     let info = frame.plane_info(0)?;
+//!     let max = (1u16 << info.bit_depth) - 1;
+//!     if info.bytes_per_sample == 2 {
+//!         for row in frame.plane_u16_mut(0)? {
+//!             row.iter_mut().for_each(|sample| *sample = (max - (*sample >> info.shift)) << info.shift);
+//!         }
+//!     } else {
+//!         for row in frame.plane_u8_mut(0)? {
+//!             row.iter_mut().for_each(|sample| *sample = 255 - *sample);
+//!         }
+//!     }
+//!     Ok(Some(frame))
+//! }
+//! ```
+
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format, sample_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_frame_make_writable, av_frame_ref, av_image_get_linesize, av_pix_fmt_count_planes, av_pix_fmt_desc_get,
+    AVPixelFormat, AVSampleFormat, AV_PIX_FMT_FLAG_BE, AV_PIX_FMT_FLAG_BITSTREAM, AV_PIX_FMT_FLAG_FLOAT,
+    AV_PIX_FMT_FLAG_HWACCEL, AV_PIX_FMT_FLAG_PAL,
+};
+
+/// The layout of one plane of a video frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneInfo {
+    /// Number of samples in a row, e.g. `width` for a luma plane, `width / 2` for the chroma
+    /// planes of `yuv420p`, or `width * 3` for `rgb24`.
+    pub samples_per_row: usize,
+    /// Number of rows.
+    pub rows: usize,
+    /// Size of a sample: 1 for 8-bit formats, 2 for 9 to 16-bit formats.
+    pub bytes_per_sample: usize,
+    /// Number of significant bits of a sample, e.g. 10 for `yuv420p10le`.
+    pub bit_depth: u8,
+    /// Number of low bits below the value of a sample, e.g. 6 for `p010le`, 0 for most formats.
+    pub shift: u8,
+}
+
+/// Accessors for the pixel data of a video frame, see the [module documentation](self).
+pub trait FramePlanes {
+    /// Returns the number of planes of the frame's pixel format, 0 for audio frames and unknown
+    /// formats.
+    fn plane_count(&self) -> usize;
+
+    /// Returns the layout of `plane`, or an error for audio frames, unsupported pixel formats
+    /// and planes the format does not have.
+    fn plane_info(&self, plane: usize) -> Result<PlaneInfo, String>;
+
+    /// Returns the rows of an 8-bit `plane`.
+    fn plane_u8(&self, plane: usize) -> Result<Vec<&[u8]>, String>;
+
+    /// Returns the rows of an 8-bit `plane` for writing, making the frame writable first.
+    fn plane_u8_mut(&mut self, plane: usize) -> Result<Vec<&mut [u8]>, String>;
+
+    /// Returns the rows of a 9 to 16-bit `plane`.
+    fn plane_u16(&self, plane: usize) -> Result<Vec<&[u16]>, String>;
+
+    /// Returns the rows of a 9 to 16-bit `plane` for writing, making the frame writable first.
+    fn plane_u16_mut(&mut self, plane: usize) -> Result<Vec<&mut [u16]>, String>;
+
+    /// Returns the samples of `plane` of an audio frame whose sample format has samples of
+    /// type `T`: the samples of channel `plane` of a planar frame, or the interleaved samples
+    /// of all channels in plane 0 of a packed frame.
+    fn samples<T: AudioSample>(&self, plane: usize) -> Result<&[T], String>;
+
+    /// Returns the samples of `plane` of an audio frame for writing, making the frame
+    /// writable first, see [`samples`](FramePlanes::samples).
+    fn samples_mut<T: AudioSample>(&mut self, plane: usize) -> Result<&mut [T], String>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The type of the samples of audio sample formats, implemented for `u8`, `i16`, `i32`,
+/// `i64`, `f32` and `f64`.
+pub trait AudioSample: Copy + sealed::Sealed {
+    /// The packed and planar sample formats whose samples are of this type.
+    const FORMATS: [AVSampleFormat; 2];
+
+    /// Returns the amplitude of the sample, in `[-1, 1]` for integer samples.
+    fn to_f64(self) -> f64;
+
+    /// Returns the sample of amplitude `value`, clipped to the range of integer samples.
+    fn from_f64(value: f64) -> Self;
+}
+
+// float to integer `as` casts saturate, which clips the integer samples to their range
+macro_rules! audio_sample {
+    ($type:ty, $packed:ident, $planar:ident, $scale:expr, $center:expr) => {
+        impl sealed::Sealed for $type {}
+        impl AudioSample for $type {
+            const FORMATS: [AVSampleFormat; 2] = [AVSampleFormat::$packed, AVSampleFormat::$planar];
+
+            fn to_f64(self) -> f64 {
+                (self as f64 - $center) / $scale
+            }
+
+            fn from_f64(value: f64) -> Self {
+                (value * $scale + $center).round() as $type
+            }
+        }
+    };
+}
+
+// unsigned 8-bit samples are centered on 128
+audio_sample!(u8, AV_SAMPLE_FMT_U8, AV_SAMPLE_FMT_U8P, 128.0, 128.0);
+audio_sample!(i16, AV_SAMPLE_FMT_S16, AV_SAMPLE_FMT_S16P, 32768.0, 0.0);
+audio_sample!(i32, AV_SAMPLE_FMT_S32, AV_SAMPLE_FMT_S32P, 2147483648.0, 0.0);
+audio_sample!(i64, AV_SAMPLE_FMT_S64, AV_SAMPLE_FMT_S64P, 9223372036854775808.0, 0.0);
+
+impl sealed::Sealed for f32 {}
+impl AudioSample for f32 {
+    const FORMATS: [AVSampleFormat; 2] = [AVSampleFormat::AV_SAMPLE_FMT_FLT, AVSampleFormat::AV_SAMPLE_FMT_FLTP];
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl sealed::Sealed for f64 {}
+impl AudioSample for f64 {
+    const FORMATS: [AVSampleFormat; 2] = [AVSampleFormat::AV_SAMPLE_FMT_DBL, AVSampleFormat::AV_SAMPLE_FMT_DBLP];
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl FramePlanes for Frame {
+    fn plane_count(&self) -> usize {
+        match video_pixel_format(self) {
+            Ok(format) => unsafe { av_pix_fmt_count_planes(format).max(0) as usize },
+            Err(_) => 0,
+        }
+    }
+
+    fn plane_info(&self, plane: usize) -> Result<PlaneInfo, String> {
+        let pix_fmt = video_pixel_format(self)?;
+        unsafe {
+            let frame = self.as_ptr();
+            let format = (*frame).format;
+            let desc = av_pix_fmt_desc_get(pix_fmt);
+            if desc.is_null() {
+                return Err(format!("Unknown pixel format {format}"));
+            }
+            let mut unsupported =
+                (AV_PIX_FMT_FLAG_PAL | AV_PIX_FMT_FLAG_BITSTREAM | AV_PIX_FMT_FLAG_HWACCEL | AV_PIX_FMT_FLAG_FLOAT) as u64;
+            if cfg!(target_endian = "little") {
+                unsupported |= AV_PIX_FMT_FLAG_BE as u64;
+            }
+            if (*desc).flags & unsupported != 0 || !(*frame).hw_frames_ctx.is_null() {
+                return Err(format!("Pixel format {format} has no integer samples in memory"));
+            }
+            if plane >= self.plane_count() {
+                return Err(format!("Pixel format {format} has no plane {plane}"));
+            }
+
+            let components = &(*desc).comp[..(*desc).nb_components as usize];
+            let Some(first) = components.iter().find(|comp| comp.plane as usize == plane) else {
+                return Err(format!("Pixel format {format} has no plane {plane}"));
+            };
+            let bits = components
+                .iter()
+                .filter(|comp| comp.plane as usize == plane)
+                .map(|comp| comp.depth + comp.shift)
+                .max()
+                .unwrap_or(0);
+            let bytes_per_sample = match bits {
+                1..=8 => 1,
+                9..=16 => 2,
+                _ => return Err(format!("Pixel format {format} has {bits}-bit samples")),
+            };
+
+            let row_bytes = av_image_get_linesize(pix_fmt, (*frame).width, plane as i32);
+            if row_bytes < 0 {
+                return Err(format!("Failed to get the row size of plane {plane}: {}", av_err2str(row_bytes)));
+            }
+            let rows = if plane == 1 || plane == 2 {
+                // AV_CEIL_RSHIFT
+                -((-(*frame).height) >> (*desc).log2_chroma_h) as usize
+            } else {
+                (*frame).height as usize
+            };
+
+            Ok(PlaneInfo {
+                samples_per_row: row_bytes as usize / bytes_per_sample,
+                rows,
+                bytes_per_sample,
+                bit_depth: first.depth as u8,
+                shift: first.shift as u8,
+            })
+        }
+    }
+
+    fn plane_u8(&self, plane: usize) -> Result<Vec<&[u8]>, String> {
+        let info = checked_info(self, plane, 1)?;
+        Ok((0..info.rows)
+            .map(|y| unsafe { std::slice::from_raw_parts(row_ptr(self, plane, y), info.samples_per_row) })
+            .collect())
+    }
+
+    fn plane_u8_mut(&mut self, plane: usize) -> Result<Vec<&mut [u8]>, String> {
+        let info = checked_info(self, plane, 1)?;
+        make_writable(self)?;
+        Ok((0..info.rows)
+            .map(|y| unsafe { std::slice::from_raw_parts_mut(row_ptr(self, plane, y), info.samples_per_row) })
+            .collect())
+    }
+
+    fn plane_u16(&self, plane: usize) -> Result<Vec<&[u16]>, String> {
+        let info = checked_info(self, plane, 2)?;
+        Ok((0..info.rows)
+            .map(|y| unsafe {
+                std::slice::from_raw_parts(row_ptr(self, plane, y) as *const u16, info.samples_per_row)
+            })
+            .collect())
+    }
+
+    fn plane_u16_mut(&mut self, plane: usize) -> Result<Vec<&mut [u16]>, String> {
+        let info = checked_info(self, plane, 2)?;
+        make_writable(self)?;
+        Ok((0..info.rows)
+            .map(|y| unsafe {
+                std::slice::from_raw_parts_mut(row_ptr(self, plane, y) as *mut u16, info.samples_per_row)
+            })
+            .collect())
+    }
+
+    fn samples<T: AudioSample>(&self, plane: usize) -> Result<&[T], String> {
+        let len = checked_samples::<T>(self, plane)?;
+        unsafe { Ok(std::slice::from_raw_parts(*(*self.as_ptr()).extended_data.add(plane) as *const T, len)) }
+    }
+
+    fn samples_mut<T: AudioSample>(&mut self, plane: usize) -> Result<&mut [T], String> {
+        checked_samples::<T>(self, plane)?;
+        make_writable(self)?;
+        // the samples may have moved to a new buffer
+        let len = checked_samples::<T>(self, plane)?;
+        unsafe { Ok(std::slice::from_raw_parts_mut(*(*self.as_mut_ptr()).extended_data.add(plane) as *mut T, len)) }
+    }
+}
+
+/// Returns the number of samples in `plane` of an audio frame, checking that they are of type
+/// `T` and can be read as such.
+fn checked_samples<T: AudioSample>(frame: &Frame, plane: usize) -> Result<usize, String> {
+    unsafe {
+        let f = frame.as_ptr();
+        if f.is_null() || (*f).buf[0].is_null() || (*f).extended_data.is_null() {
+            return Err("The frame has no data".to_string());
+        }
+        // video frames have a pixel format in the same field
+        if (*f).width > 0 || (*f).nb_samples <= 0 {
+            return Err("The frame is not an audio frame".to_string());
+        }
+        let format = sample_format((*f).format).ok_or_else(|| format!("Unknown sample format {}", (*f).format))?;
+        let (channels, nb_samples) = ((*f).ch_layout.nb_channels.max(0) as usize, (*f).nb_samples as usize);
+        let (planes, len) = match T::FORMATS {
+            [packed, _] if packed == format => (1, nb_samples * channels),
+            [_, planar] if planar == format => (channels, nb_samples),
+            _ => {
+                let name = std::any::type_name::<T>();
+                return Err(format!("Sample format {format:?} does not have {name} samples"));
+            }
+        };
+        if plane >= planes {
+            return Err(format!("The frame has no plane {plane}"));
+        }
+        let data = *(*f).extended_data.add(plane);
+        if data.is_null() || data as usize % std::mem::align_of::<T>() != 0 {
+            return Err(format!("Plane {plane} has no aligned samples"));
+        }
+        Ok(len)
+    }
+}
+
+/// Returns the pixel format of a video frame, or an error for audio frames, frames without a
+/// format and formats unknown to FFmpeg.
+fn video_pixel_format(frame: &Frame) -> Result<AVPixelFormat, String> {
+    unsafe {
+        let f = frame.as_ptr();
+        // audio frames have a sample format in the same field
+        if f.is_null() || (*f).width <= 0 || (*f).height <= 0 {
+            return Err("The frame is not a video frame".to_string());
+        }
+        pixel_format((*f).format).ok_or_else(|| format!("Unknown pixel format {}", (*f).format))
+    }
+}
+
+/// Returns the bit depth of the first component of a video frame (the luma, or red), e.g. to
+/// process frames of more than 8 bits in a 16-bit format. Unlike [`FramePlanes::plane_info`],
+/// this also works for formats whose samples cannot be read, like big-endian ones.
+pub(crate) fn bit_depth(frame: &Frame) -> Option<u8> {
+    let format = video_pixel_format(frame).ok()?;
+    unsafe {
+        let desc = av_pix_fmt_desc_get(format);
+        (!desc.is_null() && (*desc).nb_components > 0).then(|| (*desc).comp[0].depth as u8)
+    }
+}
+
+/// Returns the layout of `plane`, checking that its samples take `bytes_per_sample` bytes and
+/// that its rows can be read as such.
+fn checked_info(frame: &Frame, plane: usize, bytes_per_sample: usize) -> Result<PlaneInfo, String> {
+    let info = frame.plane_info(plane)?;
+    if info.bytes_per_sample != bytes_per_sample {
+        return Err(format!(
+            "Plane {plane} has {}-byte samples, not {bytes_per_sample}-byte samples",
+            info.bytes_per_sample
+        ));
+    }
+    unsafe {
+        let data = (*frame.as_ptr()).data[plane];
+        let linesize = (*frame.as_ptr()).linesize[plane];
+        if data.is_null() {
+            return Err(format!("Plane {plane} has no data"));
+        }
+        if data as usize % bytes_per_sample != 0 || linesize as usize % bytes_per_sample != 0 {
+            return Err(format!("Plane {plane} is not aligned to its {bytes_per_sample}-byte samples"));
+        }
+    }
+    Ok(info)
+}
+
+pub(crate) fn make_writable(frame: &mut Frame) -> Result<(), String> {
+    let ret = unsafe { av_frame_make_writable(frame.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(format!("Failed to make frame writable: {}", av_err2str(ret)));
+    }
+    Ok(())
+}
+
+/// A new reference to the buffers and properties of `frame`.
+pub(crate) fn reference_frame(frame: &Frame) -> Result<Frame, String> {
+    unsafe {
+        let mut copy = Frame::empty();
+        if copy.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let ret = av_frame_ref(copy.as_mut_ptr(), frame.as_ptr());
+        if ret < 0 {
+            return Err(format!("Failed to reference frame: {}", av_err2str(ret)));
+        }
+        Ok(copy)
+    }
+}
+
+/// Returns the start of row `y` of `plane`; the linesize may be negative for bottom-up pictures.
+unsafe fn row_ptr(frame: &Frame, plane: usize, y: usize) -> *mut u8 {
+    let linesize = (*frame.as_ptr()).linesize[plane] as isize;
+    (*frame.as_ptr()).data[plane].offset(y as isize * linesize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_filter::FrameFilter;
+    use crate::core::filter::frame_filter_context::FrameFilterContext;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::FfmpegContext;
+    use ffmpeg_sys_next::AVMediaType;
+    use ffmpeg_sys_next::{av_channel_layout_default, av_frame_get_buffer};
+    use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_P010LE, AV_PIX_FMT_YUV420P, AV_PIX_FMT_YUV420P10LE};
+    use std::sync::{Arc, Mutex};
+
+    fn new_frame(format: AVPixelFormat, width: i32, height: i32) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            (*frame.as_mut_ptr()).format = format as i32;
+            (*frame.as_mut_ptr()).width = width;
+            (*frame.as_mut_ptr()).height = height;
+            assert!(av_frame_get_buffer(frame.as_mut_ptr(), 0) >= 0);
+            frame
+        }
+    }
+
+    #[test]
+    fn test_plane_info() {
+        let frame = new_frame(AV_PIX_FMT_YUV420P10LE, 6, 5);
+        assert_eq!(frame.plane_count(), 3);
+        let luma = PlaneInfo { samples_per_row: 6, rows: 5, bytes_per_sample: 2, bit_depth: 10, shift: 0 };
+        assert_eq!(frame.plane_info(0).unwrap(), luma);
+        let chroma = PlaneInfo { samples_per_row: 3, rows: 3, ..luma };
+        assert_eq!(frame.plane_info(2).unwrap(), chroma);
+        assert!(frame.plane_info(3).is_err());
+        assert!(frame.plane_u8(0).is_err());
+
+        // interleaved, left-shifted chroma
+        let frame = new_frame(AV_PIX_FMT_P010LE, 6, 4);
+        let chroma = frame.plane_info(1).unwrap();
+        assert_eq!((chroma.samples_per_row, chroma.rows, chroma.bit_depth, chroma.shift), (6, 2, 10, 6));
+
+        let frame = new_frame(AV_PIX_FMT_YUV420P, 6, 4);
+        assert_eq!(frame.plane_info(0).unwrap().bytes_per_sample, 1);
+        assert!(frame.plane_u16(0).is_err());
+        assert_eq!(bit_depth(&frame), Some(8));
+        assert_eq!(bit_depth(&new_frame(AV_PIX_FMT_P010LE, 6, 4)), Some(10));
+
+        // not a pixel format
+        let mut frame = new_frame(AV_PIX_FMT_YUV420P, 6, 4);
+        unsafe { (*frame.as_mut_ptr()).format = i32::MAX };
+        assert_eq!(frame.plane_count(), 0);
+        assert!(frame.plane_info(0).is_err());
+        assert_eq!(bit_depth(&frame), None);
+    }
+
+    #[test]
+    fn test_plane_u16_round_trip() {
+        let mut frame = new_frame(AV_PIX_FMT_YUV420P10LE, 6, 5);
+        for (y, row) in frame.plane_u16_mut(0).unwrap().into_iter().enumerate() {
+            row.iter_mut().enumerate().for_each(|(x, sample)| *sample = (y * 100 + x) as u16);
+        }
+        let rows = frame.plane_u16(0).unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[4], [400, 401, 402, 403, 404, 405]);
+    }
+
+    fn new_audio_frame(format: AVSampleFormat, channels: i32, nb_samples: i32) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = format as i32;
+            (*f).nb_samples = nb_samples;
+            av_channel_layout_default(&mut (*f).ch_layout, channels);
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            frame
+        }
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut frame = new_audio_frame(AVSampleFormat::AV_SAMPLE_FMT_FLTP, 2, 4);
+        frame.samples_mut::<f32>(1).unwrap().fill(0.5);
+        assert_eq!(frame.samples::<f32>(1).unwrap(), &[0.5; 4]);
+        assert!(frame.samples::<f32>(2).is_err());
+        assert!(frame.samples::<i16>(0).is_err());
+        assert!(frame.plane_u8(0).is_err());
+
+        // packed: the channels are interleaved in plane 0
+        let frame = new_audio_frame(AVSampleFormat::AV_SAMPLE_FMT_S16, 2, 4);
+        assert_eq!(frame.samples::<i16>(0).unwrap().len(), 8);
+        assert!(frame.samples::<i16>(1).is_err());
+
+        assert!(new_frame(AV_PIX_FMT_YUV420P, 6, 4).samples::<u8>(0).is_err());
+    }
+
+    #[test]
+    fn test_audio_sample_amplitude() {
+        assert_eq!((128u8.to_f64(), 192u8.to_f64(), (-16384i16).to_f64()), (0.0, 0.5, -0.5));
+        assert_eq!((u8::from_f64(-2.0), i16::from_f64(0.5), i16::from_f64(1.0)), (0, 16384, i16::MAX));
+        assert_eq!((0.25f32.to_f64(), f32::from_f64(1.5)), (0.25, 1.5));
+    }
+
+    /// Records the largest luma sample of the 10-bit frames it sees.
+    struct MaxLumaFilter(Arc<Mutex<Option<u16>>>);
+
+    impl FrameFilter for MaxLumaFilter {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            if frame.is_empty() {
+                return Ok(Some(frame));
+            }
+            let max = frame.plane_u16(0)?.into_iter().flatten().copied().max();
+            let mut max_luma = self.0.lock().unwrap();
+            *max_luma = max_luma.max(max);
+            Ok(Some(frame))
+        }
+    }
+
+    #[test]
+    fn test_ten_bit_frames() {
+        let max_luma = Arc::new(Mutex::new(None));
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("max_luma", Box::new(MaxLumaFilter(max_luma.clone())));
+        let result = FfmpegContext::builder()
+            .input(Input::from("test.mp4"))
+            .filter_desc("format=yuv420p10le")
+            .output(
+                Output::from("-")
+                    .set_format("null")
+                    .set_recording_time_us(1_000_000)
+                    .add_frame_pipeline(pipeline),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // 8-bit content scaled to 10 bits uses more than the 8-bit range, within 10 bits
+        let max_luma = max_luma.lock().unwrap().unwrap();
+        assert!(max_luma > 255 && max_luma <= 1023, "{max_luma}");
+    }
+}
@@ -0,0 +1,304 @@
+//! Typed access to the side data carried by frames, for use in [`FrameFilter`](crate::filter::frame_filter::FrameFilter)s.
+//!
+//! Decoders attach side data to frames, e.g. the HDR10 mastering display and content light
+//! level metadata, the display matrix of rotated videos, or motion vectors when the decoder
+//! is opened with `flags2=+export_mvs` (see [`Input::set_decoder_option`](crate::Input::set_decoder_option)).
+//! Side data is kept when frames go through the pipeline and are handed to several outputs, and
+//! the global kinds (HDR metadata) are passed to the encoder when it is opened.
+//!
+//! # Example
+//! ```rust,ignore
+//! use ez_ffmpeg::filter::frame_side_data::FrameSideData;
+//!
+//! fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+//!     if frame.content_light_level().is_none() {
+//!         frame.set_content_light_level(ContentLightLevel { max_cll: 1000, max_fall: 400 })?;
+//!     }
+//!     Ok(Some(frame))
+//! }
+//! ```
+
+use crate::core::scheduler::filter_task::display_rotation_get;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVFrameSideDataType::{
+    AV_FRAME_DATA_CONTENT_LIGHT_LEVEL, AV_FRAME_DATA_DISPLAYMATRIX, AV_FRAME_DATA_MASTERING_DISPLAY_METADATA,
+    AV_FRAME_DATA_MOTION_VECTORS,
+};
+use ffmpeg_sys_next::{
+    av_content_light_metadata_create_side_data, av_frame_get_side_data, av_frame_remove_side_data,
+    av_mastering_display_metadata_create_side_data, av_q2d, AVContentLightMetadata, AVFrameSideDataType,
+    AVMasteringDisplayMetadata, AVMotionVector, AVRational,
+};
+
+/// Denominator of the chromaticity coordinates, in the 0.00002 units of HEVC/AV1 metadata.
+const CHROMA_DEN: i32 = 50000;
+/// Denominator of the luminance values, in the 0.0001 cd/m² units of HEVC/AV1 metadata.
+const LUMA_DEN: i32 = 10000;
+
+/// Mastering display color volume (SMPTE ST 2086), as carried by HDR10 content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasteringDisplay {
+    /// CIE 1931 xy chromaticity of the red, green and blue primaries, `None` if unknown.
+    pub primaries: Option<[(f64, f64); 3]>,
+    /// CIE 1931 xy chromaticity of the white point, `None` if unknown.
+    pub white_point: Option<(f64, f64)>,
+    /// Minimum and maximum luminance of the display in cd/m², `None` if unknown.
+    pub luminance: Option<(f64, f64)>,
+}
+
+/// Content light level (CTA-861.3), in cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// Maximum content light level of any pixel (MaxCLL).
+    pub max_cll: u32,
+    /// Maximum frame-average light level (MaxFALL).
+    pub max_fall: u32,
+}
+
+/// Accessors for the side data of a frame.
+pub trait FrameSideData {
+    /// Returns the kinds of side data attached to the frame.
+    fn side_data_types(&self) -> Vec<AVFrameSideDataType>;
+
+    /// Returns the HDR mastering display metadata.
+    fn mastering_display(&self) -> Option<MasteringDisplay>;
+
+    /// Attaches HDR mastering display metadata, replacing any previous one.
+    fn set_mastering_display(&mut self, mastering_display: MasteringDisplay) -> Result<(), String>;
+
+    /// Returns the HDR content light level.
+    fn content_light_level(&self) -> Option<ContentLightLevel>;
+
+    /// Attaches an HDR content light level, replacing any previous one.
+    fn set_content_light_level(&mut self, content_light_level: ContentLightLevel) -> Result<(), String>;
+
+    /// Returns the counter-clockwise rotation in degrees the display matrix applies to the frame.
+    fn display_rotation(&self) -> Option<f64>;
+
+    /// Returns the motion vectors exported by the decoder, empty unless it was opened with
+    /// `flags2=+export_mvs`.
+    fn motion_vectors(&self) -> &[AVMotionVector];
+}
+
+impl FrameSideData for Frame {
+    fn side_data_types(&self) -> Vec<AVFrameSideDataType> {
+        unsafe {
+            let frame = self.as_ptr();
+            if frame.is_null() {
+                return vec![];
+            }
+            (0..(*frame).nb_side_data as usize)
+                .map(|i| (**(*frame).side_data.add(i)).type_)
+                .collect()
+        }
+    }
+
+    fn mastering_display(&self) -> Option<MasteringDisplay> {
+        let data = side_data(self, AV_FRAME_DATA_MASTERING_DISPLAY_METADATA)?;
+        if data.len() < std::mem::size_of::<AVMasteringDisplayMetadata>() {
+            return None;
+        }
+        let metadata = unsafe { &*(data.as_ptr() as *const AVMasteringDisplayMetadata) };
+        let xy = |xy: &[AVRational; 2]| unsafe { (av_q2d(xy[0]), av_q2d(xy[1])) };
+        Some(MasteringDisplay {
+            primaries: (metadata.has_primaries != 0).then(|| {
+                [
+                    xy(&metadata.display_primaries[0]),
+                    xy(&metadata.display_primaries[1]),
+                    xy(&metadata.display_primaries[2]),
+                ]
+            }),
+            white_point: (metadata.has_primaries != 0).then(|| xy(&metadata.white_point)),
+            luminance: (metadata.has_luminance != 0)
+                .then(|| unsafe { (av_q2d(metadata.min_luminance), av_q2d(metadata.max_luminance)) }),
+        })
+    }
+
+    fn set_mastering_display(&mut self, mastering_display: MasteringDisplay) -> Result<(), String> {
+        if mastering_display.primaries.is_some() != mastering_display.white_point.is_some() {
+            return Err("Mastering display primaries and white point must be set together".to_string());
+        }
+        unsafe {
+            av_frame_remove_side_data(self.as_mut_ptr(), AV_FRAME_DATA_MASTERING_DISPLAY_METADATA);
+            let metadata = av_mastering_display_metadata_create_side_data(self.as_mut_ptr());
+            if metadata.is_null() {
+                return Err("Failed to allocate mastering display metadata: Out of memory.".to_string());
+            }
+            if let (Some(primaries), Some(white_point)) = (mastering_display.primaries, mastering_display.white_point) {
+                for (dst, (x, y)) in (*metadata).display_primaries.iter_mut().zip(primaries) {
+                    *dst = [to_q(x, CHROMA_DEN), to_q(y, CHROMA_DEN)];
+                }
+                (*metadata).white_point = [to_q(white_point.0, CHROMA_DEN), to_q(white_point.1, CHROMA_DEN)];
+                (*metadata).has_primaries = 1;
+            }
+            if let Some((min_luminance, max_luminance)) = mastering_display.luminance {
+                (*metadata).min_luminance = to_q(min_luminance, LUMA_DEN);
+                (*metadata).max_luminance = to_q(max_luminance, LUMA_DEN);
+                (*metadata).has_luminance = 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn content_light_level(&self) -> Option<ContentLightLevel> {
+        let data = side_data(self, AV_FRAME_DATA_CONTENT_LIGHT_LEVEL)?;
+        if data.len() < std::mem::size_of::<AVContentLightMetadata>() {
+            return None;
+        }
+        let metadata = unsafe { &*(data.as_ptr() as *const AVContentLightMetadata) };
+        Some(ContentLightLevel {
+            max_cll: metadata.MaxCLL,
+            max_fall: metadata.MaxFALL,
+        })
+    }
+
+    fn set_content_light_level(&mut self, content_light_level: ContentLightLevel) -> Result<(), String> {
+        unsafe {
+            av_frame_remove_side_data(self.as_mut_ptr(), AV_FRAME_DATA_CONTENT_LIGHT_LEVEL);
+            let metadata = av_content_light_metadata_create_side_data(self.as_mut_ptr());
+            if metadata.is_null() {
+                return Err("Failed to allocate content light level: Out of memory.".to_string());
+            }
+            (*metadata).MaxCLL = content_light_level.max_cll;
+            (*metadata).MaxFALL = content_light_level.max_fall;
+        }
+        Ok(())
+    }
+
+    fn display_rotation(&self) -> Option<f64> {
+        let data = side_data(self, AV_FRAME_DATA_DISPLAYMATRIX)?;
+        if data.len() < std::mem::size_of::<[i32; 9]>() {
+            return None;
+        }
+        let matrix = unsafe { &*(data.as_ptr() as *const [i32; 9]) };
+        let rotation = display_rotation_get(matrix);
+        (!rotation.is_nan()).then_some(rotation)
+    }
+
+    fn motion_vectors(&self) -> &[AVMotionVector] {
+        match side_data(self, AV_FRAME_DATA_MOTION_VECTORS) {
+            Some(data) => unsafe {
+                std::slice::from_raw_parts(
+                    data.as_ptr() as *const AVMotionVector,
+                    data.len() / std::mem::size_of::<AVMotionVector>(),
+                )
+            },
+            None => &[],
+        }
+    }
+}
+
+/// Returns the payload of the side data of kind `side_data_type`, if the frame has one.
+fn side_data(frame: &Frame, side_data_type: AVFrameSideDataType) -> Option<&[u8]> {
+    unsafe {
+        if frame.as_ptr().is_null() {
+            return None;
+        }
+        let sd = av_frame_get_side_data(frame.as_ptr(), side_data_type);
+        if sd.is_null() || (*sd).data.is_null() {
+            return None;
+        }
+        Some(std::slice::from_raw_parts((*sd).data, (*sd).size))
+    }
+}
+
+fn to_q(value: f64, den: i32) -> AVRational {
+    AVRational {
+        num: (value * den as f64).round() as i32,
+        den,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_filter::{FrameFilter, NoopFilter};
+    use crate::core::filter::frame_filter_context::FrameFilterContext;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::core::context::input::Input;
+    use crate::FfmpegContext;
+    use ffmpeg_sys_next::AVMediaType;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const HDR10_DISPLAY: MasteringDisplay = MasteringDisplay {
+        primaries: Some([(0.708, 0.292), (0.17, 0.797), (0.131, 0.046)]),
+        white_point: Some((0.3127, 0.329)),
+        luminance: Some((0.005, 1000.0)),
+    };
+
+    struct SetHdrFilter;
+
+    impl FrameFilter for SetHdrFilter {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            if !frame.is_empty() {
+                frame.set_mastering_display(HDR10_DISPLAY)?;
+                frame.set_content_light_level(ContentLightLevel { max_cll: 1000, max_fall: 400 })?;
+            }
+            Ok(Some(frame))
+        }
+    }
+
+    struct CheckHdrFilter {
+        frames_with_hdr: Arc<AtomicUsize>,
+    }
+
+    impl FrameFilter for CheckHdrFilter {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            if frame.mastering_display() == Some(HDR10_DISPLAY)
+                && frame.content_light_level() == Some(ContentLightLevel { max_cll: 1000, max_fall: 400 })
+            {
+                self.frames_with_hdr.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(Some(frame))
+        }
+    }
+
+    #[test]
+    fn test_mastering_display_round_trip() {
+        let mut frame = Frame::empty();
+        assert!(frame.side_data_types().is_empty());
+        frame.set_mastering_display(HDR10_DISPLAY).unwrap();
+        assert_eq!(frame.mastering_display(), Some(HDR10_DISPLAY));
+        assert_eq!(frame.side_data_types(), vec![AV_FRAME_DATA_MASTERING_DISPLAY_METADATA]);
+        assert!(frame.motion_vectors().is_empty());
+    }
+
+    #[test]
+    fn test_hdr_side_data_reaches_encoder() {
+        let frames_with_hdr = Arc::new(AtomicUsize::new(0));
+        let input = Input::from("test.mp4").add_frame_pipeline(
+            FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                .filter("set_hdr", Box::new(SetHdrFilter))
+                .filter("noop", Box::new(NoopFilter::new(AVMediaType::AVMEDIA_TYPE_VIDEO))),
+        );
+        // the output pipeline runs right before the encoder
+        let output = Output::from("output.mp4").add_frame_pipeline(
+            FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter(
+                "check_hdr",
+                Box::new(CheckHdrFilter { frames_with_hdr: frames_with_hdr.clone() }),
+            ),
+        );
+
+        FfmpegContext::builder()
+            .input(input)
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(frames_with_hdr.load(Ordering::Relaxed) > 0);
+    }
+}
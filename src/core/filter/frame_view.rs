@@ -0,0 +1,235 @@
+//! Safe access to the properties of the frames passed to [`FrameFilter`](crate::filter::frame_filter::FrameFilter)s.
+//!
+//! `ffmpeg_next::Frame` only exposes most of its fields through the raw `AVFrame` pointer,
+//! so even a simple filter has to check `frame.as_ptr().is_null()` or `buf[0].is_null()` in
+//! `unsafe` code. [`FrameView`] and [`FrameViewMut`] wrap a borrowed frame and expose what
+//! most filters need without `unsafe`: whether the frame carries data, its size and format,
+//! and its timestamp. The pixel and sample data itself is read and written with
+//! [`FramePlanes`](crate::filter::frame_planes::FramePlanes).
+//!
+//! The raw frame stays available through [`FrameView::frame`] and
+//! [`FrameViewMut::frame_mut`] for anything the views do not cover.
+//!
+//! # Example
+//! ```rust,ignore
+//! use ez_ffmpeg::filter::frame_planes::FramePlanes;
+//! use ez_ffmpeg::filter::frame_view::FrameView;
+//!
+//! fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+//!     let view = FrameView::new(&frame);
+//!     // end-of-stream and hardware frames go through untouched
+//!     if view.is_props_only() || view.is_hardware() || view.pixel_format() != Some(AV_PIX_FMT_YUV420P) {
+//!         return Ok(Some(frame));
+//!     }
+//!     // invert the luma
+//!     for row in frame.plane_u8_mut(0)? {
+//!         row.iter_mut().for_each(|sample| *sample = 255 - *sample);
+//!     }
+//!     Ok(Some(frame))
+//! }
+//! ```
+
+use crate::util::ffmpeg_utils;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{AVPixelFormat, AVRational, AVSampleFormat, AV_NOPTS_VALUE};
+
+/// A read-only view of a frame.
+#[derive(Clone, Copy)]
+pub struct FrameView<'a> {
+    frame: &'a Frame,
+}
+
+impl<'a> FrameView<'a> {
+    /// Wraps `frame`.
+    pub fn new(frame: &'a Frame) -> Self {
+        Self { frame }
+    }
+
+    /// Returns the wrapped frame, for raw (`unsafe`) access to the `AVFrame`.
+    pub fn frame(&self) -> &'a Frame {
+        self.frame
+    }
+
+    /// Returns whether the frame carries properties only and no data, like the frame
+    /// signalling the end of a stream (and its timestamp) at the end of the input. Filters
+    /// usually pass such frames on untouched.
+    pub fn is_props_only(&self) -> bool {
+        unsafe { self.frame.as_ptr().is_null() || (*self.frame.as_ptr()).buf[0].is_null() }
+    }
+
+    /// Returns whether the data of the frame lives in GPU memory (hardware decoding), in
+    /// which case it cannot be read as slices.
+    pub fn is_hardware(&self) -> bool {
+        unsafe { !self.frame.as_ptr().is_null() && !(*self.frame.as_ptr()).hw_frames_ctx.is_null() }
+    }
+
+    /// Returns whether this is a video frame.
+    pub fn is_video(&self) -> bool {
+        self.width() > 0 && self.height() > 0
+    }
+
+    /// Returns whether this is an audio frame.
+    pub fn is_audio(&self) -> bool {
+        !self.is_video() && self.nb_samples() > 0
+    }
+
+    /// Returns the width of a video frame, in pixels, 0 for audio frames.
+    pub fn width(&self) -> u32 {
+        self.field(0, |frame| frame.width.max(0) as u32)
+    }
+
+    /// Returns the height of a video frame, in pixels, 0 for audio frames.
+    pub fn height(&self) -> u32 {
+        self.field(0, |frame| frame.height.max(0) as u32)
+    }
+
+    /// Returns the pixel format of a video frame.
+    pub fn pixel_format(&self) -> Option<AVPixelFormat> {
+        if !self.is_video() {
+            return None;
+        }
+        ffmpeg_utils::pixel_format(self.field(-1, |frame| frame.format))
+    }
+
+    /// Returns the sample format of an audio frame.
+    pub fn sample_format(&self) -> Option<AVSampleFormat> {
+        if !self.is_audio() {
+            return None;
+        }
+        ffmpeg_utils::sample_format(self.field(-1, |frame| frame.format))
+    }
+
+    /// Returns the number of samples per channel of an audio frame, 0 for video frames.
+    pub fn nb_samples(&self) -> usize {
+        self.field(0, |frame| frame.nb_samples.max(0) as usize)
+    }
+
+    /// Returns the sample rate of an audio frame, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.field(0, |frame| frame.sample_rate.max(0) as u32)
+    }
+
+    /// Returns the number of channels of an audio frame.
+    pub fn channels(&self) -> usize {
+        self.field(0, |frame| frame.ch_layout.nb_channels.max(0) as usize)
+    }
+
+    /// Returns the presentation timestamp of the frame, in [`time_base`](Self::time_base) units.
+    pub fn pts(&self) -> Option<i64> {
+        Some(self.field(AV_NOPTS_VALUE, |frame| frame.pts)).filter(|pts| *pts != AV_NOPTS_VALUE)
+    }
+
+    /// Returns the time base of the timestamps of the frame.
+    pub fn time_base(&self) -> Option<AVRational> {
+        Some(self.field(AVRational { num: 0, den: 1 }, |frame| frame.time_base))
+            .filter(|time_base| time_base.num > 0 && time_base.den > 0)
+    }
+
+    /// Returns the presentation time of the frame, in seconds.
+    pub fn time_secs(&self) -> Option<f64> {
+        let (pts, time_base) = (self.pts()?, self.time_base()?);
+        Some(pts as f64 * time_base.num as f64 / time_base.den as f64)
+    }
+
+    /// Reads a field of the `AVFrame`, `default` for a null frame.
+    fn field<T>(&self, default: T, read: impl FnOnce(&ffmpeg_sys_next::AVFrame) -> T) -> T {
+        unsafe {
+            if self.frame.as_ptr().is_null() {
+                default
+            } else {
+                read(&*self.frame.as_ptr())
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a Frame> for FrameView<'a> {
+    fn from(frame: &'a Frame) -> Self {
+        Self::new(frame)
+    }
+}
+
+/// A view of a frame allowing to change its timestamp.
+pub struct FrameViewMut<'a> {
+    frame: &'a mut Frame,
+}
+
+impl<'a> FrameViewMut<'a> {
+    /// Wraps `frame`.
+    pub fn new(frame: &'a mut Frame) -> Self {
+        Self { frame }
+    }
+
+    /// Returns a read-only view of the frame, for its size, format and timestamp.
+    pub fn view(&self) -> FrameView<'_> {
+        FrameView::new(self.frame)
+    }
+
+    /// Returns the wrapped frame, for raw (`unsafe`) access to the `AVFrame`.
+    pub fn frame_mut(&mut self) -> &mut Frame {
+        self.frame
+    }
+
+    /// Sets the presentation timestamp of the frame, in the units of its time base, `None`
+    /// leaving it unset.
+    pub fn set_pts(&mut self, pts: Option<i64>) {
+        unsafe {
+            if !self.frame.as_ptr().is_null() {
+                (*self.frame.as_mut_ptr()).pts = pts.unwrap_or(AV_NOPTS_VALUE);
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a mut Frame> for FrameViewMut<'a> {
+    fn from(frame: &'a mut Frame) -> Self {
+        Self::new(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use ffmpeg_sys_next::AVSampleFormat::AV_SAMPLE_FMT_FLTP;
+    use ffmpeg_sys_next::{av_channel_layout_default, av_frame_get_buffer};
+
+    #[test]
+    fn test_frame_view() {
+        let mut frame = unsafe { Frame::empty() };
+        assert!(FrameView::new(&frame).is_props_only());
+
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_SAMPLE_FMT_FLTP as i32;
+            (*f).nb_samples = 4;
+            (*f).sample_rate = 48000;
+            av_channel_layout_default(&mut (*f).ch_layout, 2);
+            (*f).pts = 96000;
+            (*f).time_base = AVRational { num: 1, den: 48000 };
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+        }
+
+        let view = FrameView::new(&frame);
+        assert!(!view.is_props_only() && view.is_audio());
+        assert_eq!(view.sample_format(), Some(AV_SAMPLE_FMT_FLTP));
+        assert_eq!(view.pixel_format(), None);
+        assert_eq!((view.channels(), view.nb_samples()), (2, 4));
+        assert_eq!(view.time_secs(), Some(2.0));
+
+        let mut frame = unsafe { Frame::empty() };
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 4;
+            (*f).height = 2;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+        }
+        let mut view = FrameViewMut::new(&mut frame);
+        view.set_pts(None);
+        let view = view.view();
+        assert_eq!(view.pixel_format(), Some(AV_PIX_FMT_YUV420P));
+        assert_eq!(view.sample_format(), None);
+        assert_eq!((view.width(), view.height(), view.pts()), (4, 2, None));
+    }
+}
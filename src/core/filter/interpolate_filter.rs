@@ -0,0 +1,356 @@
+//! A [`FrameFilter`] that changes the frame rate of a video stream by generating frames in
+//! between the input frames, e.g. to make 60 fps video out of 30 fps footage.
+//!
+//! Output frames are placed on a regular grid of the target frame rate. Each output frame
+//! lying between two input frames is computed from both, according to the
+//! [`InterpolateMode`]; the filter therefore holds one input frame back until the next one
+//! arrives. When the end of stream reaches the filter, the last input frame is repeated until
+//! its duration is covered and the end of stream is forwarded.
+//!
+//! The output frames are timestamped in units of `1 / framerate`, with a duration of 1. The
+//! frame rate of the output should be set to the same value with
+//! [`Output::set_framerate`](crate::core::context::output::Output::set_framerate): otherwise
+//! the encoder keeps the frame rate of the input and drops the generated frames.
+//!
+//! Frames are interpolated in their own pixel format, which must use 8 or 9-16 bit (native
+//! endian) integer components, e.g. `yuv420p`, `nv12`, `rgb24` or `yuv420p10le`. When the
+//! frame size or pixel format changes, the last frame of the old format is repeated up to the
+//! first frame of the new one instead of being interpolated. Hardware frames are passed
+//! through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("interpolate", Box::new(
+//!         InterpolateFilter::new(AVRational { num: 60, den: 1 }).set_mode(InterpolateMode::Blend),
+//!     ));
+//! FfmpegContext::builder()
+//!     .input("input_30fps.mp4")
+//!     .output(Output::from("output_60fps.mp4")
+//!         .add_frame_pipeline(pipeline)
+//!         .set_framerate(AVRational { num: 60, den: 1 }))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{reference_frame, FramePlanes};
+use crate::util::ffmpeg_utils::av_err2str;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVRounding::AV_ROUND_UP;
+use ffmpeg_sys_next::{
+    av_compare_ts, av_frame_copy_props, av_frame_get_buffer, av_inv_q, av_q2d, av_rescale_q_rnd,
+    AVMediaType, AVRational, AV_NOPTS_VALUE,
+};
+use std::collections::VecDeque;
+
+/// How [`InterpolateFilter`] computes a frame lying between two input frames.
+///
+/// More modes, such as motion compensated interpolation, may be added in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum InterpolateMode {
+    /// Cross-fades the two frames, weighted by the distance of the output frame to each of
+    /// them. Cheap, but moving objects show as a double image on the generated frames.
+    #[default]
+    Blend,
+}
+
+pub struct InterpolateFilter {
+    framerate: AVRational,
+    mode: InterpolateMode,
+
+    // the last input frame, waiting for the next one to interpolate towards
+    previous: Option<Frame>,
+    // timestamp of the next output frame, in units of `1 / framerate`
+    next_pts: Option<i64>,
+    ready: VecDeque<Frame>,
+}
+
+impl InterpolateFilter {
+    /// Creates a filter producing `framerate` frames per second by blending the input frames.
+    pub fn new(framerate: AVRational) -> Self {
+        Self {
+            framerate,
+            mode: InterpolateMode::default(),
+            previous: None,
+            next_pts: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Sets how the frames in between the input frames are computed.
+    pub fn set_mode(mut self, mode: InterpolateMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    fn output_time_base(&self) -> AVRational {
+        unsafe { av_inv_q(self.framerate) }
+    }
+
+    fn push_frame(&mut self, frame: Frame) -> Result<(), String> {
+        let (pts, time_base) = unsafe { ((*frame.as_ptr()).pts, (*frame.as_ptr()).time_base) };
+        if pts == AV_NOPTS_VALUE || time_base.num <= 0 || time_base.den <= 0 {
+            return Err("Frame interpolation needs frames with timestamps".to_string());
+        }
+
+        let Some(previous) = self.previous.take() else {
+            self.next_pts = Some(unsafe { av_rescale_q_rnd(pts, time_base, self.output_time_base(), AV_ROUND_UP) });
+            self.previous = Some(frame);
+            return Ok(());
+        };
+
+        if frame_key(&previous) != frame_key(&frame) {
+            log::debug!("Interpolate input format changed, repeating the last frame up to the new one.");
+            self.emit_until(&previous, None, pts, time_base)?;
+        } else {
+            self.emit_until(&previous, Some(&frame), pts, time_base)?;
+        }
+        self.previous = Some(frame);
+        Ok(())
+    }
+
+    /// Repeats the last frame over its duration, or a single output frame if it has none.
+    fn flush(&mut self) -> Result<(), String> {
+        let Some(previous) = self.previous.take() else {
+            return Ok(());
+        };
+        let (pts, duration, time_base) =
+            unsafe { ((*previous.as_ptr()).pts, (*previous.as_ptr()).duration, (*previous.as_ptr()).time_base) };
+        let end = if duration > 0 {
+            pts + duration
+        } else {
+            pts + unsafe { av_rescale_q_rnd(1, self.output_time_base(), time_base, AV_ROUND_UP) }
+        };
+        self.emit_until(&previous, None, end, time_base)
+    }
+
+    /// Emits the output frames lying before `end_pts`, interpolated between `previous` and
+    /// `next`, or copies of `previous` without a `next` frame.
+    fn emit_until(
+        &mut self,
+        previous: &Frame,
+        next: Option<&Frame>,
+        end_pts: i64,
+        end_time_base: AVRational,
+    ) -> Result<(), String> {
+        let output_time_base = self.output_time_base();
+        let Some(mut next_pts) = self.next_pts else {
+            return Ok(());
+        };
+        let start = unsafe { (*previous.as_ptr()).pts as f64 * av_q2d((*previous.as_ptr()).time_base) };
+        let end = end_pts as f64 * unsafe { av_q2d(end_time_base) };
+
+        while unsafe { av_compare_ts(next_pts, output_time_base, end_pts, end_time_base) } < 0 {
+            let position = next_pts as f64 * unsafe { av_q2d(output_time_base) };
+            let weight = if end > start {
+                ((position - start) / (end - start)).clamp(0.0, 1.0) as f32
+            } else {
+                0.0
+            };
+
+            let mut output = match next {
+                Some(next) if weight > 0.0 => self.interpolate(previous, next, weight)?,
+                _ => reference_frame(previous)?,
+            };
+            unsafe {
+                let dst = output.as_mut_ptr();
+                (*dst).pts = next_pts;
+                (*dst).duration = 1;
+                (*dst).time_base = output_time_base;
+            }
+            self.ready.push_back(output);
+            next_pts += 1;
+        }
+        self.next_pts = Some(next_pts);
+        Ok(())
+    }
+
+    fn interpolate(&self, previous: &Frame, next: &Frame, weight: f32) -> Result<Frame, String> {
+        match self.mode {
+            InterpolateMode::Blend => unsafe { blend_frames(previous, next, weight) },
+        }
+    }
+}
+
+impl FrameFilter for InterpolateFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if self.framerate.num <= 0 || self.framerate.den <= 0 {
+            return Err(format!(
+                "Invalid interpolation frame rate {}/{}",
+                self.framerate.num, self.framerate.den
+            ));
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        if frame.is_empty() {
+            // end of stream: emit the last frame, then forward it
+            self.flush()?;
+            self.ready.push_back(frame);
+        } else {
+            self.push_frame(frame)?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        Ok(self.ready.pop_front())
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        self.previous = None;
+        self.next_pts = None;
+        self.ready.clear();
+    }
+}
+
+fn frame_key(frame: &Frame) -> (i32, i32, i32) {
+    unsafe { ((*frame.as_ptr()).format, (*frame.as_ptr()).width, (*frame.as_ptr()).height) }
+}
+
+/// Returns `previous * (1 - weight) + next * weight` in a new frame, with the properties of the
+/// closest of the two frames.
+unsafe fn blend_frames(previous: &Frame, next: &Frame, weight: f32) -> Result<Frame, String> {
+    let (format, width, height) = frame_key(previous);
+    // checks the pixel format before allocating anything
+    let plane_infos = (0..previous.plane_count())
+        .map(|plane| previous.plane_info(plane))
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("Frame interpolation does not support pixel format {format}: {e}"))?;
+
+    let mut output = Frame::empty();
+    if output.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let dst = output.as_mut_ptr();
+    (*dst).format = format;
+    (*dst).width = width;
+    (*dst).height = height;
+    let ret = av_frame_get_buffer(dst, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+    let template = if weight < 0.5 { previous } else { next };
+    let ret = av_frame_copy_props(dst, template.as_ptr());
+    if ret < 0 {
+        return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+    }
+
+    let mix = |a: f32, b: f32| a + (b - a) * weight;
+    for (plane, info) in plane_infos.iter().enumerate() {
+        if info.bytes_per_sample == 2 {
+            let (a_rows, b_rows) = (previous.plane_u16(plane)?, next.plane_u16(plane)?);
+            for (y, out) in output.plane_u16_mut(plane)?.into_iter().enumerate() {
+                for ((o, a), b) in out.iter_mut().zip(a_rows[y]).zip(b_rows[y]) {
+                    *o = mix(*a as f32, *b as f32).round() as u16;
+                }
+            }
+        } else {
+            let (a_rows, b_rows) = (previous.plane_u8(plane)?, next.plane_u8(plane)?);
+            for (y, out) in output.plane_u8_mut(plane)?.into_iter().enumerate() {
+                for ((o, a), b) in out.iter_mut().zip(a_rows[y]).zip(b_rows[y]) {
+                    *o = mix(*a as f32, *b as f32).round() as u8;
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+    use std::collections::HashMap;
+
+    fn gray_frame(value: u8, pts: i64) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_GRAY8 as i32;
+            (*f).width = 4;
+            (*f).height = 2;
+            (*f).pts = pts;
+            (*f).duration = 1;
+            (*f).time_base = AVRational { num: 1, den: 30 };
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for row in frame.plane_u8_mut(0).unwrap() {
+                row.fill(value);
+            }
+            frame
+        }
+    }
+
+    #[test]
+    fn test_interpolate_pts_and_weights() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("interpolate", &mut attributes);
+        let mut filter = InterpolateFilter::new(AVRational { num: 60, den: 1 });
+        filter.init(&ctx).unwrap();
+
+        let mut outputs = Vec::new();
+        for (i, value) in [0u8, 100, 200].into_iter().enumerate() {
+            outputs.extend(filter.filter_frame(gray_frame(value, i as i64), &ctx).unwrap());
+            while let Some(frame) = filter.request_frame(&ctx).unwrap() {
+                outputs.push(frame);
+            }
+        }
+        // nothing is emitted before the second frame arrives
+        outputs.extend(filter.filter_frame(unsafe { Frame::empty() }, &ctx).unwrap());
+        while let Some(frame) = filter.request_frame(&ctx).unwrap() {
+            outputs.push(frame);
+        }
+
+        let eof = outputs.pop().unwrap();
+        assert!(eof.is_empty());
+        let samples = outputs
+            .iter()
+            .map(|frame| unsafe {
+                assert_eq!((*frame.as_ptr()).time_base.den, 60);
+                ((*frame.as_ptr()).pts, frame.plane_u8(0).unwrap()[0][0])
+            })
+            .collect::<Vec<_>>();
+        // the last frame is repeated over its duration
+        assert_eq!(samples, vec![(0, 0), (1, 50), (2, 100), (3, 150), (4, 200), (5, 200)]);
+    }
+
+    #[test]
+    fn test_interpolate_filter() {
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("interpolate", Box::new(InterpolateFilter::new(AVRational { num: 60, den: 1 })));
+        let result = FfmpegContext::builder()
+            .input(Input::from("test.mp4").add_frame_pipeline(pipeline))
+            .output(
+                Output::from("output_interpolate.mp4")
+                    .add_stream_map("0:v")
+                    .set_framerate(AVRational { num: 60, den: 1 })
+                    .set_recording_time_us(1_000_000),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+        std::fs::remove_file("output_interpolate.mp4").unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+//! A [`FrameFilter`] that alpha-composites a (semi-transparent) PNG logo onto video frames.
+//!
+//! The image is decoded once in `init` and cached as RGBA. For each frame, only the area
+//! covered by the logo (rounded out to whole chroma samples) is converted to RGBA, blended
+//! with the logo, and written back in the frame's original pixel format; the rest of the
+//! frame is left untouched. No second input or `overlay` filter graph is needed.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("logo", Box::new(
+//!         LogoOverlayFilter::new("logo.png", Corner::BottomRight)
+//!             .set_margin(24)
+//!             .set_opacity(0.8)
+//!             .set_relative_width(0.15),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_converter::{align_region, FrameConverter};
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVMediaType;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGBA;
+
+/// Where the logo is anchored on the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+pub struct LogoOverlayFilter {
+    path: String,
+    corner: Corner,
+    margin: u32,
+    opacity: f32,
+    relative_width: Option<f32>,
+
+    logo: Option<RgbaImage>,
+    scaled_logo: Option<RgbaImage>,
+    to_rgba: FrameConverter,
+    from_rgba: FrameConverter,
+}
+
+#[derive(Clone)]
+struct RgbaImage {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl LogoOverlayFilter {
+    /// Creates a filter drawing the image at `path` (PNG with alpha, or any image FFmpeg can decode)
+    /// in the given `corner`, with a 16 pixel margin and full opacity.
+    pub fn new(path: impl Into<String>, corner: Corner) -> Self {
+        Self {
+            path: path.into(),
+            corner,
+            margin: 16,
+            opacity: 1.0,
+            relative_width: None,
+            logo: None,
+            scaled_logo: None,
+            to_rgba: FrameConverter::new(),
+            from_rgba: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the distance, in pixels, between the logo and the frame edges.
+    pub fn set_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Sets a global opacity multiplier (`0.0..=1.0`) applied on top of the logo's own alpha.
+    pub fn set_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Scales the logo to a fraction of the frame width (e.g. `0.1` for 10%), keeping its
+    /// aspect ratio, so it looks the same regardless of the video resolution.
+    /// By default the logo is drawn at its native pixel size.
+    pub fn set_relative_width(mut self, relative_width: f32) -> Self {
+        self.relative_width = Some(relative_width);
+        self
+    }
+
+    fn update_scaled_logo(&mut self, frame_width: usize) -> Result<(), String> {
+        let logo = self.logo.as_ref().ok_or("Logo is not loaded")?;
+        let target_width = match self.relative_width {
+            Some(relative_width) => ((frame_width as f32 * relative_width).round() as usize).max(1),
+            None => logo.width,
+        };
+
+        let up_to_date = matches!(&self.scaled_logo, Some(scaled) if scaled.width == target_width);
+        if !up_to_date {
+            let target_height = ((logo.height * target_width) as f32 / logo.width as f32).round().max(1.0) as usize;
+            self.scaled_logo = Some(if target_width == logo.width {
+                logo.clone()
+            } else {
+                scale_rgba(logo, target_width, target_height)?
+            });
+        }
+        Ok(())
+    }
+}
+
+impl FrameFilter for LogoOverlayFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        self.logo = Some(load_rgba_image(&self.path)?);
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        let margin = self.margin as usize;
+        let corner = self.corner;
+        let opacity = self.opacity;
+        self.update_scaled_logo(width)?;
+        let logo = self.scaled_logo.as_ref().unwrap();
+
+        let draw_width = logo.width.min(width.saturating_sub(margin));
+        let draw_height = logo.height.min(height.saturating_sub(margin));
+        if draw_width == 0 || draw_height == 0 || opacity <= 0.0 {
+            return Ok(Some(frame));
+        }
+        let x0 = match corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => width - margin - draw_width,
+        };
+        let y0 = match corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => height - margin - draw_height,
+        };
+
+        // only the pixels under the logo go through RGBA
+        let region = align_region(&frame, (x0, y0, draw_width, draw_height))?;
+        let (dx, dy) = (x0 - region.0, y0 - region.1);
+        let rgba = self.to_rgba.convert_region(&frame, AV_PIX_FMT_RGBA, region)?;
+        for (y, row) in rgba.plane_u8_mut(0)?[dy..dy + draw_height].iter_mut().enumerate() {
+            let logo_row = &logo.data[y * logo.width * 4..(y * logo.width + draw_width) * 4];
+            for x in 0..draw_width {
+                let src = &logo_row[x * 4..x * 4 + 4];
+                let alpha = src[3] as f32 / 255.0 * opacity;
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let dst = &mut row[(dx + x) * 4..(dx + x) * 4 + 3];
+                for c in 0..3 {
+                    dst[c] = (src[c] as f32 * alpha + dst[c] as f32 * (1.0 - alpha)).round() as u8;
+                }
+            }
+        }
+
+        self.from_rgba.convert_into_region(rgba, &mut frame, region.0, region.1)?;
+        Ok(Some(frame))
+    }
+}
+
+/// Decodes the first frame of an image file into RGBA.
+fn load_rgba_image(path: &str) -> Result<RgbaImage, String> {
+    let mut ictx = ffmpeg_next::format::input(&path).map_err(|e| format!("Failed to open image '{path}': {e}"))?;
+    let stream = ictx
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or(format!("No image found in '{path}'"))?;
+    let stream_index = stream.index();
+    let context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+        .map_err(|e| format!("Failed to create decoder for '{path}': {e}"))?;
+    let mut decoder = context
+        .decoder()
+        .video()
+        .map_err(|e| format!("Failed to open decoder for '{path}': {e}"))?;
+
+    let mut decoded = ffmpeg_next::frame::Video::empty();
+    let mut found = false;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet).map_err(|e| format!("Failed to decode '{path}': {e}"))?;
+        if decoder.receive_frame(&mut decoded).is_ok() {
+            found = true;
+            break;
+        }
+    }
+    if !found {
+        decoder.send_eof().map_err(|e| format!("Failed to decode '{path}': {e}"))?;
+        decoder
+            .receive_frame(&mut decoded)
+            .map_err(|e| format!("Failed to decode '{path}': {e}"))?;
+    }
+
+    let mut scaler = ffmpeg_next::software::scaling::Context::get(
+        decoded.format(),
+        decoded.width(),
+        decoded.height(),
+        ffmpeg_next::format::Pixel::RGBA,
+        decoded.width(),
+        decoded.height(),
+        ffmpeg_next::software::scaling::Flags::BILINEAR,
+    )
+    .map_err(|e| format!("Failed to create scaler for '{path}': {e}"))?;
+    let mut rgba = ffmpeg_next::frame::Video::empty();
+    scaler.run(&decoded, &mut rgba).map_err(|e| format!("Failed to convert '{path}' to RGBA: {e}"))?;
+
+    Ok(video_to_rgba_image(&rgba))
+}
+
+fn scale_rgba(image: &RgbaImage, width: usize, height: usize) -> Result<RgbaImage, String> {
+    let mut src = ffmpeg_next::frame::Video::new(ffmpeg_next::format::Pixel::RGBA, image.width as u32, image.height as u32);
+    let stride = src.stride(0);
+    for (y, row) in image.data.chunks(image.width * 4).enumerate() {
+        src.data_mut(0)[y * stride..y * stride + row.len()].copy_from_slice(row);
+    }
+
+    let mut scaler = ffmpeg_next::software::scaling::Context::get(
+        ffmpeg_next::format::Pixel::RGBA,
+        image.width as u32,
+        image.height as u32,
+        ffmpeg_next::format::Pixel::RGBA,
+        width as u32,
+        height as u32,
+        ffmpeg_next::software::scaling::Flags::BICUBIC,
+    )
+    .map_err(|e| format!("Failed to create logo scaler: {e}"))?;
+    let mut dst = ffmpeg_next::frame::Video::empty();
+    scaler.run(&src, &mut dst).map_err(|e| format!("Failed to scale logo: {e}"))?;
+    Ok(video_to_rgba_image(&dst))
+}
+
+fn video_to_rgba_image(frame: &ffmpeg_next::frame::Video) -> RgbaImage {
+    let width = frame.width() as usize;
+    let height = frame.height() as usize;
+    let stride = frame.stride(0);
+    let mut data = Vec::with_capacity(width * height * 4);
+    for row in frame.data(0).chunks(stride).take(height) {
+        data.extend_from_slice(&row[..width * 4]);
+    }
+    RgbaImage { width, height, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_overlay_leaves_the_rest_of_the_frame() {
+        let mut frame = unsafe { Frame::empty() };
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 32;
+            (*f).height = 24;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+        }
+        // a busy picture, which a round trip through RGBA would change
+        for plane in 0..3 {
+            for (y, row) in frame.plane_u8_mut(plane).unwrap().into_iter().enumerate() {
+                for (x, sample) in row.iter_mut().enumerate() {
+                    *sample = ((x * 37 + y * 101 + plane * 53) % 220 + 16) as u8;
+                }
+            }
+        }
+        let input: Vec<Vec<Vec<u8>>> =
+            (0..3).map(|plane| frame.plane_u8(plane).unwrap().iter().map(|row| row.to_vec()).collect()).collect();
+
+        let mut filter = LogoOverlayFilter::new("unused", Corner::BottomRight).set_margin(3);
+        // opaque white, 5x5 pixels at (24, 16)
+        filter.logo = Some(RgbaImage {
+            width: 5,
+            height: 5,
+            data: vec![255; 100],
+        });
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("logo", &mut attributes);
+        let output = filter.filter_frame(frame, &ctx).unwrap().unwrap();
+
+        let luma = output.plane_u8(0).unwrap();
+        for (y, row) in luma.iter().enumerate() {
+            for (x, &sample) in row.iter().enumerate() {
+                if (24..29).contains(&x) && (16..21).contains(&y) {
+                    assert!(sample > 230, "({x}, {y}): {sample}");
+                } else if !(24..30).contains(&x) || !(16..22).contains(&y) {
+                    // outside of the region rounded to the chroma samples
+                    assert_eq!(sample, input[0][y][x], "({x}, {y})");
+                }
+            }
+        }
+        for plane in 1..3 {
+            for (y, row) in output.plane_u8(plane).unwrap().iter().enumerate() {
+                for (x, &sample) in row.iter().enumerate() {
+                    if !(12..15).contains(&x) || !(8..11).contains(&y) {
+                        assert_eq!(sample, input[plane][y][x], "plane {plane} ({x}, {y})");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,643 @@
+//! [`FrameFilter`]s that measure the loudness of audio and normalize it to a target, e.g. a
+//! podcast episode to -16 LUFS.
+//!
+//! Loudness is measured as in EBU R 128 / ITU-R BS.1770: the samples are K-weighted, their
+//! power is taken over 400 ms blocks overlapping by 75%, and the integrated loudness is the
+//! average power of the blocks louder than -70 LUFS and no more than 10 LU below the average
+//! of those. Surround channels weigh 1.41 and the LFE channel is ignored.
+//!
+//! Normalization works in one of two ways:
+//!
+//! - **Two passes**, like FFmpeg's two-pass `loudnorm`: a first job runs
+//!   [`LoudnessMeterFilter`], which measures the whole program into a shared
+//!   [`LoudnessMeasurement`], then a second job over the same input runs
+//!   [`LoudnessNormFilter::with_measurement`], which applies a single gain bringing the
+//!   integrated loudness to the target. The dynamics are untouched. See
+//!   [running two passes](crate::filter::stabilize_filter#running-two-passes).
+//! - **Single pass**: [`LoudnessNormFilter::new`] measures the loudness of the last
+//!   10 seconds as the frames arrive and glides the gain towards the target. Nothing needs to
+//!   be prepared, but the gain follows the program: quiet passages are brought up and loud
+//!   ones down, which compresses the dynamics, and the first second keeps the original level
+//!   until enough audio has been measured. Prefer two passes when the file is at hand.
+//!
+//! In both modes the gained samples go through a limiter holding the sample peaks below a
+//! ceiling (-1 dBFS by default), so raising the level never clips. The limiter reacts
+//! instantly and has no lookahead: when it engages, e.g. on a program with a large
+//! peak-to-loudness ratio brought to a high target, the attack of the loud transients is
+//! audibly squashed.
+//!
+//! Every packed and planar sample format is supported (`u8`, `s16`, `s32`, `s64`, `flt`,
+//! `dbl`), in place: the frames keep their format, channel layout and timestamps.
+//!
+//! # Example
+//! ```rust,ignore
+//! // two passes: measure, then normalize
+//! let meter = LoudnessMeterFilter::new();
+//! let measurement = meter.measurement();
+//! FfmpegContext::builder()
+//!     .input("episode.wav")
+//!     .output(Output::from("-").set_format("null").add_frame_pipeline(
+//!         FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO).filter("meter", Box::new(meter)),
+//!     ))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//!
+//! FfmpegContext::builder()
+//!     .input("episode.wav")
+//!     .output(Output::from("episode.m4a").add_frame_pipeline(
+//!         FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO)
+//!             .filter("loudnorm", Box::new(LoudnessNormFilter::with_measurement(-16.0, measurement))),
+//!     ))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{AudioSample, FramePlanes};
+use crate::core::filter::frame_view::FrameView;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVChannel::*;
+use ffmpeg_sys_next::AVSampleFormat::*;
+use ffmpeg_sys_next::{
+    av_channel_layout_channel_from_index, av_get_packed_sample_fmt, av_sample_fmt_is_planar,
+    AVChannelLayout, AVFrame, AVMediaType, AVSampleFormat,
+};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Blocks below this loudness are silence, and do not count.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the loudness of the louder blocks do not count either.
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// The 400 ms blocks start every 100 ms.
+const SUBBLOCKS_PER_BLOCK: usize = 4;
+/// Blocks the single-pass normalization measures the loudness over (10 s).
+const DYNAMIC_WINDOW_BLOCKS: usize = 100;
+/// Time constant of the single-pass gain, in seconds.
+const DYNAMIC_GAIN_TIME: f64 = 1.0;
+/// Largest gain of the single-pass normalization, so that a quiet intro is not blown up.
+const DYNAMIC_MAX_GAIN_DB: f64 = 20.0;
+/// Time the limiter takes to recover, in seconds.
+const LIMITER_RELEASE_TIME: f64 = 0.1;
+
+/// Loudness of a program, measured by [`LoudnessMeterFilter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// The integrated loudness, in LUFS. `None` when the program is silent.
+    pub integrated_lufs: Option<f64>,
+    /// The largest absolute sample value, `1.0` being full scale.
+    pub sample_peak: f64,
+}
+
+impl Loudness {
+    /// Returns the sample peak in dBFS.
+    pub fn sample_peak_db(&self) -> f64 {
+        20.0 * self.sample_peak.log10()
+    }
+}
+
+/// Handle to the loudness measured by a [`LoudnessMeterFilter`], set once its job is over.
+pub type LoudnessMeasurement = Arc<Mutex<Option<Loudness>>>;
+
+/// First pass of the two-pass normalization: measures the loudness of the audio and passes
+/// the frames through unchanged.
+pub struct LoudnessMeterFilter {
+    meter: Option<LoudnessMeter>,
+    measurement: LoudnessMeasurement,
+}
+
+impl LoudnessMeterFilter {
+    /// Creates a filter measuring the loudness of the whole stream.
+    pub fn new() -> Self {
+        Self {
+            meter: None,
+            measurement: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a handle to the measured loudness, set once the job is over. Clone it before
+    /// handing the filter to a pipeline, and pass it to [`LoudnessNormFilter::with_measurement`].
+    pub fn measurement(&self) -> LoudnessMeasurement {
+        self.measurement.clone()
+    }
+}
+
+impl Default for LoudnessMeterFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for LoudnessMeterFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_AUDIO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        self.meter = None;
+        *self.measurement.lock().unwrap() = None;
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        if FrameView::new(&frame).is_props_only() {
+            return Ok(Some(frame));
+        }
+        let samples = Samples::read(&frame)?;
+        let meter = unsafe { LoudnessMeter::for_frame(&mut self.meter, frame.as_ptr()) };
+        for values in samples.values.chunks_exact(samples.channels) {
+            meter.add(values);
+        }
+        Ok(Some(frame))
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        if let Some(meter) = &self.meter {
+            let loudness = Loudness {
+                integrated_lufs: integrated_loudness(&meter.blocks),
+                sample_peak: meter.peak,
+            };
+            info!(
+                "Measured an integrated loudness of {:?} LUFS, sample peak {:.1} dBFS",
+                loudness.integrated_lufs,
+                loudness.sample_peak_db()
+            );
+            *self.measurement.lock().unwrap() = Some(loudness);
+        }
+    }
+}
+
+/// Normalizes the loudness of audio to a target, with a limiter keeping the peaks below a
+/// ceiling. See the [module documentation](self) for the single and two-pass modes.
+pub struct LoudnessNormFilter {
+    target_lufs: f64,
+    ceiling_db: f64,
+    measurement: Option<LoudnessMeasurement>,
+
+    /// The gain of the two-pass mode, fixed once the measurement is read.
+    fixed_gain: Option<f64>,
+    /// The meter and current gain of the single-pass mode.
+    meter: Option<LoudnessMeter>,
+    gain: f64,
+    limiter_gain: f64,
+}
+
+impl LoudnessNormFilter {
+    /// Creates a single-pass filter bringing the loudness to `target_lufs` (e.g. `-16.0` for
+    /// podcasts, `-23.0` for EBU R 128 broadcast) as the audio goes.
+    pub fn new(target_lufs: f64) -> Self {
+        Self {
+            target_lufs,
+            ceiling_db: -1.0,
+            measurement: None,
+            fixed_gain: None,
+            meter: None,
+            gain: 1.0,
+            limiter_gain: 1.0,
+        }
+    }
+
+    /// Creates a two-pass filter bringing the loudness measured by a [`LoudnessMeterFilter`]
+    /// over the same input to `target_lufs`.
+    ///
+    /// The job fails to start if the measurement is not set, i.e. the first job did not
+    /// run to the end. Silent programs are left as they are.
+    pub fn with_measurement(target_lufs: f64, measurement: LoudnessMeasurement) -> Self {
+        Self {
+            measurement: Some(measurement),
+            ..Self::new(target_lufs)
+        }
+    }
+
+    /// Sets the highest sample peak of the output, in dBFS. Defaults to `-1.0`, which leaves
+    /// room for the overshoot of lossy encoders.
+    pub fn set_ceiling_db(mut self, ceiling_db: f64) -> Self {
+        self.ceiling_db = ceiling_db;
+        self
+    }
+}
+
+/// The linear gain bringing `loudness_lufs` to `target_lufs`.
+fn gain_for(target_lufs: f64, loudness_lufs: f64) -> f64 {
+    10f64.powf((target_lufs - loudness_lufs) / 20.0)
+}
+
+impl FrameFilter for LoudnessNormFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_AUDIO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if !self.target_lufs.is_finite() || self.target_lufs > 0.0 {
+            return Err(format!("Invalid loudness target {} LUFS", self.target_lufs));
+        }
+        if !self.ceiling_db.is_finite() || self.ceiling_db > 0.0 {
+            return Err(format!("Invalid limiter ceiling {} dBFS", self.ceiling_db));
+        }
+
+        self.fixed_gain = None;
+        if let Some(measurement) = &self.measurement {
+            let Some(loudness) = *measurement.lock().unwrap() else {
+                return Err("The loudness has not been measured, run the LoudnessMeterFilter job first".to_string());
+            };
+            let gain = match loudness.integrated_lufs {
+                Some(integrated_lufs) => gain_for(self.target_lufs, integrated_lufs),
+                None => 1.0,
+            };
+            if loudness.sample_peak * gain > 10f64.powf(self.ceiling_db / 20.0) {
+                warn!(
+                    "Normalizing to {} LUFS raises the peaks above {} dBFS, the limiter will engage",
+                    self.target_lufs, self.ceiling_db
+                );
+            }
+            self.fixed_gain = Some(gain);
+        }
+        self.meter = None;
+        self.gain = 1.0;
+        self.limiter_gain = 1.0;
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        let view = FrameView::new(&frame);
+        if view.is_props_only() {
+            return Ok(Some(frame));
+        }
+
+        let mut samples = Samples::read(&frame)?;
+        let sample_rate = view.sample_rate().max(1) as f64;
+        let ceiling = 10f64.powf(self.ceiling_db / 20.0);
+        let gain_coeff = 1.0 - (-1.0 / (sample_rate * DYNAMIC_GAIN_TIME)).exp();
+        let release_coeff = 1.0 - (-1.0 / (sample_rate * LIMITER_RELEASE_TIME)).exp();
+        let max_gain = 10f64.powf(DYNAMIC_MAX_GAIN_DB / 20.0);
+
+        // the single-pass mode measures the audio as it comes
+        let mut meter = match self.fixed_gain {
+            Some(_) => None,
+            None => Some(unsafe { LoudnessMeter::for_frame(&mut self.meter, frame.as_ptr()) }),
+        };
+
+        for values in samples.values.chunks_exact_mut(samples.channels) {
+            let gain = match meter.as_mut() {
+                None => self.fixed_gain.unwrap_or(1.0),
+                Some(meter) => {
+                    if meter.add(values) {
+                        let start = meter.blocks.len().saturating_sub(DYNAMIC_WINDOW_BLOCKS);
+                        if let Some(loudness) = integrated_loudness(&meter.blocks[start..]) {
+                            meter.target_gain = Some(gain_for(self.target_lufs, loudness).min(max_gain));
+                        }
+                    }
+                    if let Some(target) = meter.target_gain {
+                        self.gain += (target - self.gain) * gain_coeff;
+                    }
+                    self.gain
+                }
+            };
+
+            let peak = values.iter().fold(0.0f64, |peak, value| peak.max(value.abs())) * gain;
+            self.limiter_gain += (1.0 - self.limiter_gain) * release_coeff;
+            if peak * self.limiter_gain > ceiling {
+                self.limiter_gain = ceiling / peak;
+            }
+
+            values.iter_mut().for_each(|value| *value *= gain * self.limiter_gain);
+        }
+
+        // the samples are made writable, as decoded frames may share their buffers
+        samples.write(&mut frame)?;
+        Ok(Some(frame))
+    }
+}
+
+/// Biquad filter, in transposed direct form II.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two stages of the K-weighting of BS.1770 at `sample_rate`: a high shelf modelling
+/// the head, then a high pass.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let k = (std::f64::consts::PI * 1681.974450955533 / sample_rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let k = (std::f64::consts::PI * 38.13547087602444 / sample_rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+/// Weight of a channel in the loudness: surround channels count more, the LFE not at all.
+unsafe fn channel_weight(ch_layout: *const AVChannelLayout, index: usize) -> f64 {
+    match av_channel_layout_channel_from_index(ch_layout, index as u32) {
+        AV_CHAN_LOW_FREQUENCY | AV_CHAN_LOW_FREQUENCY_2 => 0.0,
+        AV_CHAN_SIDE_LEFT | AV_CHAN_SIDE_RIGHT | AV_CHAN_BACK_LEFT | AV_CHAN_BACK_RIGHT => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Measures the K-weighted power of audio over 400 ms blocks.
+struct LoudnessMeter {
+    sample_rate: i32,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+
+    subblock_len: usize,
+    subblock_pos: usize,
+    subblock_power: f64,
+    /// Mean power of the last 100 ms subblocks.
+    subblocks: VecDeque<f64>,
+    /// Mean power of every 400 ms block so far.
+    blocks: Vec<f64>,
+    peak: f64,
+    /// Gain the single-pass normalization is heading to, once a block has been measured.
+    target_gain: Option<f64>,
+}
+
+impl LoudnessMeter {
+    unsafe fn new(frame: *const AVFrame) -> Self {
+        let sample_rate = (*frame).sample_rate.max(1);
+        let channels = (*frame).ch_layout.nb_channels.max(1) as usize;
+        Self {
+            sample_rate,
+            weights: (0..channels).map(|index| channel_weight(&(*frame).ch_layout, index)).collect(),
+            filters: vec![k_weighting(sample_rate as f64); channels],
+            subblock_len: (sample_rate as usize / 10).max(1),
+            subblock_pos: 0,
+            subblock_power: 0.0,
+            subblocks: VecDeque::with_capacity(SUBBLOCKS_PER_BLOCK),
+            blocks: Vec::new(),
+            peak: 0.0,
+            target_gain: None,
+        }
+    }
+
+    /// Returns the meter of the audio of `frame`, creating it for the first frame. A change
+    /// of sample rate or channels restarts the filters, but keeps the blocks measured so far.
+    unsafe fn for_frame(meter: &mut Option<LoudnessMeter>, frame: *const AVFrame) -> &mut LoudnessMeter {
+        let channels = (*frame).ch_layout.nb_channels.max(1) as usize;
+        let changed = meter
+            .as_ref()
+            .is_some_and(|meter| meter.sample_rate != (*frame).sample_rate || meter.weights.len() != channels);
+        if changed {
+            let previous = meter.take().unwrap();
+            let mut new = LoudnessMeter::new(frame);
+            new.blocks = previous.blocks;
+            new.peak = previous.peak;
+            new.target_gain = previous.target_gain;
+            *meter = Some(new);
+        }
+        meter.get_or_insert_with(|| LoudnessMeter::new(frame))
+    }
+
+    /// Adds a sample of every channel. Returns whether a block was completed.
+    fn add(&mut self, values: &[f64]) -> bool {
+        for (channel, &value) in values.iter().enumerate() {
+            self.peak = self.peak.max(value.abs());
+            let weight = self.weights[channel];
+            if weight == 0.0 {
+                continue;
+            }
+            let [shelf, high_pass] = &mut self.filters[channel];
+            let filtered = high_pass.process(shelf.process(value));
+            self.subblock_power += weight * filtered * filtered;
+        }
+
+        self.subblock_pos += 1;
+        if self.subblock_pos < self.subblock_len {
+            return false;
+        }
+        if self.subblocks.len() == SUBBLOCKS_PER_BLOCK {
+            self.subblocks.pop_front();
+        }
+        self.subblocks.push_back(self.subblock_power / self.subblock_len as f64);
+        self.subblock_pos = 0;
+        self.subblock_power = 0.0;
+        if self.subblocks.len() < SUBBLOCKS_PER_BLOCK {
+            return false;
+        }
+        self.blocks.push(self.subblocks.iter().sum::<f64>() / SUBBLOCKS_PER_BLOCK as f64);
+        true
+    }
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// The gated loudness of blocks of the given mean powers, `None` if they are all silent.
+fn integrated_loudness(blocks: &[f64]) -> Option<f64> {
+    let mean = |blocks: &mut dyn Iterator<Item = f64>| {
+        let (sum, count) = blocks.fold((0.0, 0usize), |(sum, count), power| (sum + power, count + 1));
+        (count > 0).then(|| sum / count as f64)
+    };
+
+    let audible = |power: &&f64| power_to_lufs(**power) > ABSOLUTE_GATE_LUFS;
+    let relative_gate = power_to_lufs(mean(&mut blocks.iter().filter(audible).copied())?) + RELATIVE_GATE_LU;
+    let gated = mean(&mut blocks.iter().filter(audible).filter(|power| power_to_lufs(**power) > relative_gate).copied())?;
+    Some(power_to_lufs(gated))
+}
+
+/// The samples of an audio frame, as values in `[-1, 1]` whatever their format, interleaved.
+struct Samples {
+    channels: usize,
+    values: Vec<f64>,
+}
+
+impl Samples {
+    fn read(frame: &Frame) -> Result<Self, String> {
+        let view = FrameView::new(frame);
+        let format = view.sample_format().ok_or("Loudness filters need audio frames")?;
+        let channels = view.channels().max(1);
+        let mut samples = Self {
+            channels,
+            values: vec![0.0; channels * view.nb_samples()],
+        };
+        match unsafe { av_get_packed_sample_fmt(format) } {
+            AV_SAMPLE_FMT_U8 => samples.read_planes::<u8>(frame, format)?,
+            AV_SAMPLE_FMT_S16 => samples.read_planes::<i16>(frame, format)?,
+            AV_SAMPLE_FMT_S32 => samples.read_planes::<i32>(frame, format)?,
+            AV_SAMPLE_FMT_S64 => samples.read_planes::<i64>(frame, format)?,
+            AV_SAMPLE_FMT_FLT => samples.read_planes::<f32>(frame, format)?,
+            AV_SAMPLE_FMT_DBL => samples.read_planes::<f64>(frame, format)?,
+            _ => return Err(format!("Loudness filters do not support sample format {format:?}")),
+        }
+        Ok(samples)
+    }
+
+    /// Writes the values back into `frame`, which they were read from.
+    fn write(&self, frame: &mut Frame) -> Result<(), String> {
+        let format = FrameView::new(frame).sample_format().ok_or("Loudness filters need audio frames")?;
+        match unsafe { av_get_packed_sample_fmt(format) } {
+            AV_SAMPLE_FMT_U8 => self.write_planes::<u8>(frame, format),
+            AV_SAMPLE_FMT_S16 => self.write_planes::<i16>(frame, format),
+            AV_SAMPLE_FMT_S32 => self.write_planes::<i32>(frame, format),
+            AV_SAMPLE_FMT_S64 => self.write_planes::<i64>(frame, format),
+            AV_SAMPLE_FMT_FLT => self.write_planes::<f32>(frame, format),
+            AV_SAMPLE_FMT_DBL => self.write_planes::<f64>(frame, format),
+            _ => Err(format!("Loudness filters do not support sample format {format:?}")),
+        }
+    }
+
+    /// The number of planes of `format`: one per channel if it is planar, else a single one.
+    fn planes(&self, format: AVSampleFormat) -> usize {
+        if unsafe { av_sample_fmt_is_planar(format) } != 0 {
+            self.channels
+        } else {
+            1
+        }
+    }
+
+    fn read_planes<T: AudioSample>(&mut self, frame: &Frame, format: AVSampleFormat) -> Result<(), String> {
+        let planes = self.planes(format);
+        for plane in 0..planes {
+            let values = self.values.iter_mut().skip(plane).step_by(planes);
+            values.zip(frame.samples::<T>(plane)?).for_each(|(value, sample)| *value = sample.to_f64());
+        }
+        Ok(())
+    }
+
+    fn write_planes<T: AudioSample>(&self, frame: &mut Frame, format: AVSampleFormat) -> Result<(), String> {
+        let planes = self.planes(format);
+        for plane in 0..planes {
+            let values = self.values.iter().skip(plane).step_by(planes);
+            let samples = frame.samples_mut::<T>(plane)?;
+            samples.iter_mut().zip(values).for_each(|(sample, value)| *sample = T::from_f64(*value));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::FfmpegContext;
+
+    /// Meter of `seconds` of a 997 Hz sine of peak `amplitude` on the first of `channels`
+    /// channels, at 48 kHz.
+    fn measure_sine(amplitude: f64, channels: usize, seconds: f64) -> LoudnessMeter {
+        let sample_rate = 48_000;
+        let mut meter = LoudnessMeter {
+            sample_rate,
+            weights: vec![1.0; channels],
+            filters: vec![k_weighting(sample_rate as f64); channels],
+            subblock_len: sample_rate as usize / 10,
+            subblock_pos: 0,
+            subblock_power: 0.0,
+            subblocks: VecDeque::new(),
+            blocks: Vec::new(),
+            peak: 0.0,
+            target_gain: None,
+        };
+        let mut values = vec![0.0; channels];
+        for i in 0..(seconds * sample_rate as f64) as usize {
+            values[0] = amplitude * (2.0 * std::f64::consts::PI * 997.0 * i as f64 / sample_rate as f64).sin();
+            meter.add(&values);
+        }
+        meter
+    }
+
+    #[test]
+    fn test_integrated_loudness() {
+        // a full scale 1 kHz sine on one channel measures -3.01 LUFS
+        let meter = measure_sine(1.0, 2, 5.0);
+        let loudness = integrated_loudness(&meter.blocks).unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "{loudness}");
+        assert!((meter.peak - 1.0).abs() < 1e-3);
+
+        let meter = measure_sine(0.1, 1, 5.0);
+        let loudness = integrated_loudness(&meter.blocks).unwrap();
+        assert!((loudness + 23.01).abs() < 0.05, "{loudness}");
+
+        // silence is gated out
+        assert_eq!(integrated_loudness(&measure_sine(0.0, 1, 2.0).blocks), None);
+        // a quiet tail more than 10 LU down does not lower the loudness
+        let blocks: Vec<f64> = [0.01; 20].into_iter().chain([0.00001; 40]).collect();
+        assert!((integrated_loudness(&blocks).unwrap() - power_to_lufs(0.01)).abs() < 1e-9);
+    }
+
+    fn loudness_after(pipeline: FramePipelineBuilder) -> Loudness {
+        let meter = LoudnessMeterFilter::new();
+        let measurement = meter.measurement();
+        let pipeline = pipeline.filter("meter", Box::new(meter));
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("-")
+                    .set_format("null")
+                    .add_stream_map("0:a")
+                    .set_recording_time_us(10_000_000)
+                    .add_frame_pipeline(pipeline),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+        let loudness = *measurement.lock().unwrap();
+        loudness.unwrap()
+    }
+
+    #[test]
+    fn test_loudness_norm() {
+        let audio = || FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO);
+        let original = loudness_after(audio());
+        assert!(original.integrated_lufs.is_some());
+
+        // two passes land on the target, without clipping
+        let measurement = Arc::new(Mutex::new(Some(original)));
+        let normalized = loudness_after(
+            audio().filter("loudnorm", Box::new(LoudnessNormFilter::with_measurement(-16.0, measurement))),
+        );
+        assert!((normalized.integrated_lufs.unwrap() + 16.0).abs() < 1.0, "{normalized:?}");
+        assert!(normalized.sample_peak_db() <= -0.9, "{normalized:?}");
+
+        let normalized = loudness_after(audio().filter("loudnorm", Box::new(LoudnessNormFilter::new(-16.0))));
+        assert!((normalized.integrated_lufs.unwrap() + 16.0).abs() < 2.0, "{normalized:?}");
+        assert!(normalized.sample_peak_db() <= -0.9, "{normalized:?}");
+
+        // the second pass needs the first one
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("-").set_format("null").add_stream_map("0:a").add_frame_pipeline(audio().filter(
+                "loudnorm",
+                Box::new(LoudnessNormFilter::with_measurement(-16.0, Arc::new(Mutex::new(None)))),
+            )))
+            .build()
+            .unwrap()
+            .start()
+            .and_then(|scheduler| scheduler.wait());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! A [`FrameFilter`] that color grades video frames with a 3D LUT loaded from a `.cube` file.
+//!
+//! The LUT is parsed once in `init`. Frames are converted to RGB (`rgb48le` for frames of more
+//! than 8 bits, so 10-bit footage keeps its precision), every pixel is looked up in the LUT with
+//! trilinear interpolation, and the result is written back in the frame's original pixel
+//! format, so no `lut3d` filter graph is needed. With a [`Region`], only the
+//! pixels inside it are graded.
+//!
+//! Supported `.cube` keywords are `TITLE`, `LUT_3D_SIZE` (2 to 64), `DOMAIN_MIN` and
+//! `DOMAIN_MAX`; 1D LUTs are rejected. Parse errors report the offending line number.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("grade", Box::new(Lut3DFilter::from_cube("teal_orange.cube")));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{bit_depth, reference_frame, FramePlanes, PlaneInfo};
+use crate::core::filter::region::{restore_outside, Region, Sample};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVMediaType;
+use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_RGB24, AV_PIX_FMT_RGB48LE};
+
+const MIN_LUT_SIZE: usize = 2;
+const MAX_LUT_SIZE: usize = 64;
+
+pub struct Lut3DFilter {
+    path: String,
+    region: Option<Region>,
+
+    lut: Option<Lut3D>,
+    to_rgb: FrameConverter,
+    from_rgb: FrameConverter,
+}
+
+/// A parsed 3D LUT: `size`^3 RGB entries, red changing fastest.
+#[derive(Debug)]
+struct Lut3D {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut3DFilter {
+    /// Creates a filter applying the 3D LUT of the `.cube` file at `path`.
+    pub fn from_cube(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            region: None,
+            lut: None,
+            to_rgb: FrameConverter::new(),
+            from_rgb: FrameConverter::new(),
+        }
+    }
+
+    /// Applies the LUT only inside `region`, instead of the whole frame. Frames the region
+    /// lies outside of, or in packed YUV formats like `yuyv422`, are rejected.
+    pub fn set_region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+}
+
+impl FrameFilter for Lut3DFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read LUT file '{}': {e}", self.path))?;
+        let lut = parse_cube(&content).map_err(|e| format!("Invalid LUT file '{}': {e}", self.path))?;
+        self.lut = Some(lut);
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+        let lut = self.lut.as_ref().ok_or("LUT is not loaded")?;
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        // the converters make the frame writable, so this reference keeps the original pixels
+        let original = match &self.region {
+            Some(region) => Some((region.bounds(&frame)?, reference_frame(&frame)?)),
+            None => None,
+        };
+        let high_bit_depth = bit_depth(&frame).is_some_and(|depth| depth > 8);
+        let rgb_format = if high_bit_depth { AV_PIX_FMT_RGB48LE } else { AV_PIX_FMT_RGB24 };
+        let rgb = self.to_rgb.convert(&frame, rgb_format, width as i32, height as i32)?;
+        let info = rgb.plane_info(0)?;
+        if info.bytes_per_sample == 2 {
+            grade(&mut rgb.plane_u16_mut(0)?, &info, lut);
+        } else {
+            grade(&mut rgb.plane_u8_mut(0)?, &info, lut);
+        }
+
+        self.from_rgb.convert_into(rgb, &mut frame)?;
+        if let Some((bounds, original)) = &original {
+            restore_outside(bounds, original, &mut frame)?;
+        }
+        Ok(Some(frame))
+    }
+}
+
+/// Maps every pixel of the rows of a packed RGB plane through `lut`.
+fn grade<T: Sample>(rows: &mut [&mut [T]], info: &PlaneInfo, lut: &Lut3D) {
+    let max = ((1u32 << info.bit_depth) - 1) as f32;
+    for row in rows {
+        for pixel in row.chunks_exact_mut(3) {
+            let input = [pixel[0].value(info) / max, pixel[1].value(info) / max, pixel[2].value(info) / max];
+            for (value, output) in pixel.iter_mut().zip(lut.apply(input)) {
+                *value = T::from_value(output * max, info);
+            }
+        }
+    }
+}
+
+impl Lut3D {
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[(b * self.size + g) * self.size + r]
+    }
+
+    /// Maps a normalized RGB color through the LUT with trilinear interpolation.
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max_index = (self.size - 1) as f32;
+        let position: [f32; 3] = std::array::from_fn(|c| {
+            let range = self.domain_max[c] - self.domain_min[c];
+            ((rgb[c] - self.domain_min[c]) / range * max_index).clamp(0.0, max_index)
+        });
+        let lower = position.map(|p| p.floor() as usize);
+        let upper = lower.map(|l| (l + 1).min(self.size - 1));
+        let fraction: [f32; 3] = std::array::from_fn(|c| position[c] - lower[c] as f32);
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| -> [f32; 3] {
+            [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+        };
+        let [r0, g0, b0] = lower;
+        let [r1, g1, b1] = upper;
+        let [tr, tg, tb] = fraction;
+
+        let c00 = lerp(self.entry(r0, g0, b0), self.entry(r1, g0, b0), tr);
+        let c10 = lerp(self.entry(r0, g1, b0), self.entry(r1, g1, b0), tr);
+        let c01 = lerp(self.entry(r0, g0, b1), self.entry(r1, g0, b1), tr);
+        let c11 = lerp(self.entry(r0, g1, b1), self.entry(r1, g1, b1), tr);
+        lerp(lerp(c00, c10, tg), lerp(c01, c11, tg), tb)
+    }
+}
+
+/// Parses the content of a `.cube` file.
+fn parse_cube(content: &str) -> Result<Lut3D, String> {
+    let mut size = None;
+    let mut domain_min = [0.0; 3];
+    let mut domain_max = [1.0; 3];
+    let mut table = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next().unwrap();
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                if !table.is_empty() {
+                    return Err(format!("line {line_number}: LUT_3D_SIZE must come before the table"));
+                }
+                let value = tokens.next().ok_or(format!("line {line_number}: missing LUT_3D_SIZE value"))?;
+                let value: usize = value
+                    .parse()
+                    .map_err(|_| format!("line {line_number}: invalid LUT_3D_SIZE '{value}'"))?;
+                if !(MIN_LUT_SIZE..=MAX_LUT_SIZE).contains(&value) {
+                    return Err(format!(
+                        "line {line_number}: LUT_3D_SIZE {value} is out of range, expected {MIN_LUT_SIZE}..={MAX_LUT_SIZE}"
+                    ));
+                }
+                size = Some(value);
+            }
+            "DOMAIN_MIN" => domain_min = parse_triplet(tokens, line_number)?,
+            "DOMAIN_MAX" => domain_max = parse_triplet(tokens, line_number)?,
+            "LUT_1D_SIZE" | "LUT_1D_INPUT_RANGE" => {
+                return Err(format!("line {line_number}: 1D LUTs are not supported"));
+            }
+            _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.') => {
+                let size = size.ok_or(format!("line {line_number}: table data before LUT_3D_SIZE"))?;
+                if table.len() == size * size * size {
+                    return Err(format!("line {line_number}: more than {} table entries", size * size * size));
+                }
+                table.push(parse_triplet(line.split_whitespace(), line_number)?);
+            }
+            _ => return Err(format!("line {line_number}: unknown keyword '{keyword}'")),
+        }
+    }
+
+    let size = size.ok_or("missing LUT_3D_SIZE")?;
+    if table.len() != size * size * size {
+        return Err(format!("expected {} table entries, found {}", size * size * size, table.len()));
+    }
+    if domain_max.iter().zip(&domain_min).any(|(max, min)| max <= min) {
+        return Err("DOMAIN_MAX must be greater than DOMAIN_MIN".to_string());
+    }
+
+    Ok(Lut3D { size, domain_min, domain_max, table })
+}
+
+fn parse_triplet<'a>(mut tokens: impl Iterator<Item = &'a str>, line_number: usize) -> Result<[f32; 3], String> {
+    let mut values = [0.0; 3];
+    for value in &mut values {
+        let token = tokens.next().ok_or(format!("line {line_number}: expected 3 values"))?;
+        *value = token
+            .parse()
+            .map_err(|_| format!("line {line_number}: invalid number '{token}'"))?;
+    }
+    if tokens.next().is_some() {
+        return Err(format!("line {line_number}: expected 3 values"));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_and_apply_cube() {
+        // identity LUT with red and blue swapped
+        let cube = "TITLE \"swap\"\n# comment\nLUT_3D_SIZE 2\n\
+            0 0 0\n0 0 1\n0 1 0\n0 1 1\n1 0 0\n1 0 1\n1 1 0\n1 1 1\n";
+        let lut = parse_cube(cube).unwrap();
+        assert_eq!(lut.apply([1.0, 0.0, 0.0]), [0.0, 0.0, 1.0]);
+        assert_eq!(lut.apply([0.5, 0.25, 0.0]), [0.0, 0.25, 0.5]);
+
+        let err = parse_cube("LUT_3D_SIZE 2\n0 0 0\n0 0\n").unwrap_err();
+        assert!(err.starts_with("line 3:"), "{err}");
+        assert!(parse_cube("LUT_3D_SIZE 65\n").is_err());
+        assert!(parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    }
+
+    #[test]
+    fn test_grade_16_bit() {
+        let cube = "LUT_3D_SIZE 2\n0 0 0\n0 0 1\n0 1 0\n0 1 1\n1 0 0\n1 0 1\n1 1 0\n1 1 1\n";
+        let lut = parse_cube(cube).unwrap();
+        let info = PlaneInfo { samples_per_row: 6, rows: 1, bytes_per_sample: 2, bit_depth: 16, shift: 0 };
+        // values an 8-bit conversion could not hold
+        let mut row = [65535u16, 1000, 3, 0, 0, 40001];
+        grade(&mut [&mut row[..]], &info, &lut);
+        assert_eq!(row, [3, 1000, 65535, 40001, 0, 0]);
+    }
+
+    #[test]
+    fn test_grade_region() {
+        // red and blue swapped
+        let cube = "LUT_3D_SIZE 2\n0 0 0\n0 0 1\n0 1 0\n0 1 1\n1 0 0\n1 0 1\n1 1 0\n1 1 1\n";
+        std::fs::write("region_swap.cube", cube).unwrap();
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("grade", &mut attributes);
+        let mut filter = Lut3DFilter::from_cube("region_swap.cube").set_region(Region::new(0, 0, 32, 8));
+        let result = filter.init(&ctx);
+        std::fs::remove_file("region_swap.cube").unwrap();
+        result.unwrap();
+
+        // a reddish 32x16 `yuv420p` frame
+        let frame = || unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 32;
+            (*f).height = 16;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for (plane, value) in [(0, 100), (1, 100), (2, 200)] {
+                for row in frame.plane_u8_mut(plane).unwrap() {
+                    row.fill(value);
+                }
+            }
+            frame
+        };
+        let input = frame();
+        let output = filter.filter_frame(frame(), &ctx).unwrap().unwrap();
+
+        // turned bluish in the top half only
+        let (u, v) = (output.plane_u8(1).unwrap(), output.plane_u8(2).unwrap());
+        assert!(u[0][0] > 128 && v[0][0] < 128, "{} {}", u[0][0], v[0][0]);
+        for plane in 0..3 {
+            let below = if plane == 0 { 8 } else { 4 };
+            let (output, input) = (output.plane_u8(plane).unwrap(), input.plane_u8(plane).unwrap());
+            assert_eq!(output[below..], input[below..], "plane {plane}");
+        }
+    }
+}
@@ -5,6 +5,36 @@ pub mod frame_filter;
 pub mod frame_pipeline;
 pub mod frame_filter_context;
 pub mod frame_pipeline_builder;
+pub mod pipeline_event;
+pub mod quality_metric_filter;
+pub mod logo_overlay_filter;
+pub mod timecode_filter;
+pub mod deinterlace_filter;
+pub mod frame_blend_filter;
+pub mod volume_filter;
+pub mod loudness_norm_filter;
+pub mod split_filter;
+pub mod lut3d_filter;
+pub mod resample_filter;
+pub mod transform_filter;
+pub mod blur_pad_filter;
+pub mod blur_filter;
+pub mod test_source_filter;
+pub mod crop_detect_filter;
+pub mod frame_side_data;
+pub mod frame_planes;
+pub mod frame_view;
+pub mod region;
+pub mod tone_map_filter;
+pub mod audio_visualizer_filter;
+pub mod stabilize_filter;
+pub mod edge_detect_filter;
+pub mod chroma_key_filter;
+pub mod interpolate_filter;
+pub mod vignette_filter;
+#[cfg(feature = "ndarray")]
+pub mod ndarray_filter;
+pub(crate) mod frame_converter;
 
 /// Retrieves a list of all filters recognized by FFmpeg.
 ///
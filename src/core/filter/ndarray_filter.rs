@@ -0,0 +1,212 @@
+//! A [`FrameFilter`] that hands video frames to a user callback as `ndarray` views.
+//!
+//! Each frame is presented as an `[height, width, channels]` [`ArrayViewMut3<u8>`] of packed
+//! RGB24 (or RGBA, see [`NdarrayFilter::set_alpha`]) pixels. Whatever the callback writes to
+//! the view is written back to the frame before it continues down the pipeline.
+//!
+//! Requires the `ndarray` feature.
+//!
+//! # Conversion cost
+//! The view is only zero-copy when the decoded frame already has the requested pixel format
+//! and its rows are tightly packed (`linesize == width * channels`). Otherwise, per frame:
+//! - a frame in another pixel format (e.g. YUV420P) is converted to RGB(A) with swscale, and
+//!   converted back after the callback returns;
+//! - padded rows are copied into a contiguous scratch buffer, and copied back afterwards.
+//!
+//! For 1080p that is roughly two full-frame colorspace conversions plus two 6 MB copies, so
+//! when the callback runs on every frame prefer producing RGB upstream, e.g. with
+//! `filter_desc("format=rgb24")` and the filter attached to an output pipeline.
+//!
+//! Hardware frames are passed through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("invert", Box::new(NdarrayFilter::new(|mut image| {
+//!         // image has shape [height, width, 3]
+//!         image.mapv_inplace(|v| 255 - v);
+//!     })));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::util::ffmpeg_utils::av_err2str;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_RGB24, AV_PIX_FMT_RGBA};
+use ffmpeg_sys_next::{av_frame_make_writable, AVMediaType, AVPixelFormat};
+use ndarray::ArrayViewMut3;
+
+pub struct NdarrayFilter {
+    callback: Box<dyn FnMut(ArrayViewMut3<u8>) + Send>,
+    alpha: bool,
+    scratch: Vec<u8>,
+    converter_in: FrameConverter,
+    converter_out: FrameConverter,
+}
+
+impl NdarrayFilter {
+    /// Creates a filter calling `f` with an `[height, width, 3]` RGB view of every video frame.
+    pub fn new(f: impl FnMut(ArrayViewMut3<u8>) + Send + 'static) -> Self {
+        Self {
+            callback: Box::new(f),
+            alpha: false,
+            scratch: Vec::new(),
+            converter_in: FrameConverter::new(),
+            converter_out: FrameConverter::new(),
+        }
+    }
+
+    /// Presents frames as RGBA (`[height, width, 4]`) instead of RGB.
+    pub fn set_alpha(mut self, alpha: bool) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    fn pixel_format(&self) -> AVPixelFormat {
+        if self.alpha {
+            AV_PIX_FMT_RGBA
+        } else {
+            AV_PIX_FMT_RGB24
+        }
+    }
+
+    fn channels(&self) -> usize {
+        if self.alpha {
+            4
+        } else {
+            3
+        }
+    }
+}
+
+/// Calls `callback` with a view of the packed `channels`-byte pixels in plane 0 of `frame`,
+/// going through `scratch` when the rows are padded.
+fn call_with_view(
+    callback: &mut (dyn FnMut(ArrayViewMut3<u8>) + Send),
+    scratch: &mut Vec<u8>,
+    frame: &mut Frame,
+    width: usize,
+    height: usize,
+    channels: usize,
+) -> Result<(), String> {
+    let row_bytes = width * channels;
+    let shape = (height, width, channels);
+    let linesize = unsafe { (*frame.as_ptr()).linesize[0] };
+
+    if linesize as usize == row_bytes {
+        let data = unsafe { std::slice::from_raw_parts_mut((*frame.as_mut_ptr()).data[0], row_bytes * height) };
+        let view = ArrayViewMut3::from_shape(shape, data).map_err(|e| format!("Failed to create view: {e}"))?;
+        callback(view);
+        return Ok(());
+    }
+
+    scratch.clear();
+    frame.plane_u8(0)?.into_iter().for_each(|row| scratch.extend_from_slice(row));
+    let view = ArrayViewMut3::from_shape(shape, &mut scratch[..]).map_err(|e| format!("Failed to create view: {e}"))?;
+    callback(view);
+    for (row, pixels) in frame.plane_u8_mut(0)?.into_iter().zip(scratch.chunks_exact(row_bytes)) {
+        row.copy_from_slice(pixels);
+    }
+    Ok(())
+}
+
+impl FrameFilter for NdarrayFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let (format, width, height) =
+            unsafe { ((*frame.as_ptr()).format, (*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+        if width <= 0 || height <= 0 {
+            return Ok(Some(frame));
+        }
+        let pixel_format = self.pixel_format();
+        let channels = self.channels();
+
+        if format == pixel_format as i32 {
+            unsafe {
+                let ret = av_frame_make_writable(frame.as_mut_ptr());
+                if ret < 0 {
+                    return Err(format!("Failed to make frame writable: {}", av_err2str(ret)));
+                }
+            }
+            call_with_view(&mut *self.callback, &mut self.scratch, &mut frame, width as usize, height as usize, channels)?;
+            return Ok(Some(frame));
+        }
+
+        let rgb = self.converter_in.convert(&frame, pixel_format, width, height)?;
+        call_with_view(&mut *self.callback, &mut self.scratch, rgb, width as usize, height as usize, channels)?;
+        self.converter_out.convert_into(rgb, &mut frame)?;
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn frame(format: AVPixelFormat, value: u8) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = format as i32;
+            // not a multiple of the line size alignment, so the rows are padded
+            (*f).width = 30;
+            (*f).height = 20;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for plane in 0..frame.plane_count() {
+                for row in frame.plane_u8_mut(plane).unwrap() {
+                    row.fill(value);
+                }
+            }
+            frame
+        }
+    }
+
+    #[test]
+    fn test_padded_rgb_frame() {
+        let ctx = FrameFilterContext::new("ndarray", &mut HashMap::new());
+        let shape = Arc::new(Mutex::new(None));
+        let seen_shape = shape.clone();
+        let mut filter = NdarrayFilter::new(move |mut image| {
+            *seen_shape.lock().unwrap() = Some(image.shape().to_vec());
+            image.mapv_inplace(|v| 255 - v);
+        });
+
+        let output = filter.filter_frame(frame(AV_PIX_FMT_RGB24, 40), &ctx).unwrap().unwrap();
+        assert_eq!(*shape.lock().unwrap(), Some(vec![20, 30, 3]));
+        // written back through the scratch buffer, row by row
+        assert!(output.plane_u8(0).unwrap().iter().all(|row| row.iter().all(|&v| v == 215)));
+    }
+
+    #[test]
+    fn test_converted_frame() {
+        let ctx = FrameFilterContext::new("ndarray", &mut HashMap::new());
+        let shape = Arc::new(Mutex::new(None));
+        let seen_shape = shape.clone();
+        let mut filter = NdarrayFilter::new(move |mut image| {
+            *seen_shape.lock().unwrap() = Some(image.shape().to_vec());
+            image.fill(255);
+        })
+        .set_alpha(true);
+
+        let output = filter.filter_frame(frame(AV_PIX_FMT_YUV420P, 16), &ctx).unwrap().unwrap();
+        assert_eq!(*shape.lock().unwrap(), Some(vec![20, 30, 4]));
+        // converted back to the frame's own format, now white
+        assert_eq!(unsafe { (*output.as_ptr()).format }, AV_PIX_FMT_YUV420P as i32);
+        assert!(output.plane_u8(0).unwrap().iter().all(|row| row.iter().all(|&luma| luma >= 230)));
+    }
+}
@@ -0,0 +1,52 @@
+//! Events sent by [`FrameFilter`](crate::filter::frame_filter::FrameFilter)s to the
+//! application while a job runs.
+//!
+//! A filter sends an event with [`FrameFilterContext::send_event`](crate::filter::frame_filter_context::FrameFilterContext::send_event),
+//! and the application receives it from the channel returned by
+//! [`FfmpegScheduler::pipeline_events`](crate::FfmpegScheduler::pipeline_events),
+//! instead of reading the pipeline attributes once the job is over.
+//!
+//! # Example
+//! ```rust,ignore
+//! // in a filter
+//! fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+//!     if self.is_scene_cut(&frame) {
+//!         let (pts, time_base) = unsafe { ((*frame.as_ptr()).pts, (*frame.as_ptr()).time_base) };
+//!         ctx.send_event(PipelineEvent::SceneCut { pts, time_base });
+//!     }
+//!     Ok(Some(frame))
+//! }
+//!
+//! // in the application
+//! let mut scheduler = FfmpegScheduler::new(context);
+//! let events = scheduler.pipeline_events();
+//! let scheduler = scheduler.start().unwrap();
+//! for event in events {
+//!     println!("{event:?}");
+//! }
+//! scheduler.wait().unwrap();
+//! ```
+
+use ffmpeg_sys_next::AVRational;
+
+/// An event sent by a frame filter, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub enum PipelineEvent {
+    /// A new scene starts at the frame with this PTS, expressed in `time_base`.
+    SceneCut { pts: i64, time_base: AVRational },
+    /// Any other notification, identified by the filter with `name`.
+    Custom { name: String, value: String },
+}
+
+impl PipelineEvent {
+    /// Returns the time of a [`SceneCut`](PipelineEvent::SceneCut) in seconds,
+    /// or `None` for other events and unknown time bases.
+    pub fn time_seconds(&self) -> Option<f64> {
+        match self {
+            PipelineEvent::SceneCut { pts, time_base } if time_base.den != 0 => {
+                Some(*pts as f64 * time_base.num as f64 / time_base.den as f64)
+            }
+            _ => None,
+        }
+    }
+}
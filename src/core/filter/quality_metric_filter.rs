@@ -0,0 +1,546 @@
+//! A [`FrameFilter`] that measures per-frame PSNR and SSIM against a reference video.
+//!
+//! The reference input is decoded on a background thread started in `init`, and its
+//! frames are fed to the filter through a bounded channel. Each processed frame is
+//! matched with a reference frame by PTS, both are converted to 8-bit luma at the
+//! reference resolution, and the scores are accumulated into a shared
+//! [`QualityMetrics`] that can be read once the job has finished.
+//!
+//! The results are available in two ways:
+//! - [`QualityMetricFilter::metrics`] returns the shared [`QualityMetrics`], holding the scores
+//!   of every frame; read it after `wait()` returns.
+//! - While the job runs, the scores of the latest matched frame and the running averages are
+//!   published as `f64` pipeline attributes ([`PSNR_ATTRIBUTE`], [`SSIM_ATTRIBUTE`],
+//!   [`AVERAGE_PSNR_ATTRIBUTE`] and [`AVERAGE_SSIM_ATTRIBUTE`]), so the filters after this one
+//!   can read them with [`FrameFilterContext::get_attribute`]. They are left unchanged for
+//!   frames that could not be matched with a reference frame.
+//!
+//! The timestamps of both sides are counted from their first frame, so inputs starting at a
+//! nonzero time (MPEG-TS, cut files) are matched frame for frame. Reference packets that
+//! fail to decode are skipped and counted in [`QualityMetrics::reference_errors`].
+//!
+//! # Example
+//! ```rust,ignore
+//! let filter = QualityMetricFilter::new("reference.mp4");
+//! let metrics = filter.metrics();
+//!
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("quality", Box::new(filter));
+//!
+//! FfmpegContext::builder()
+//!     .input("encoded.mp4")
+//!     .output(Output::from("output.mp4").add_frame_pipeline(pipeline))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//!
+//! let metrics = metrics.lock().unwrap();
+//! println!("PSNR: {:?}, SSIM: {:?}", metrics.average_psnr(), metrics.average_ssim());
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+use ffmpeg_sys_next::{av_rescale_q, AVMediaType, AVRational, AV_NOPTS_VALUE, AV_TIME_BASE_Q};
+use log::{error, warn};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Attribute holding the PSNR of the latest matched frame in dB, as an `f64`.
+pub const PSNR_ATTRIBUTE: &str = "quality_psnr";
+
+/// Attribute holding the SSIM of the latest matched frame, as an `f64`.
+pub const SSIM_ATTRIBUTE: &str = "quality_ssim";
+
+/// Attribute holding the average PSNR over the frames matched so far, as an `f64`, see
+/// [`QualityMetrics::average_psnr`].
+pub const AVERAGE_PSNR_ATTRIBUTE: &str = "quality_average_psnr";
+
+/// Attribute holding the average SSIM over the frames matched so far, as an `f64`.
+pub const AVERAGE_SSIM_ATTRIBUTE: &str = "quality_average_ssim";
+
+/// Quality scores of a single processed frame.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameQuality {
+    /// Presentation timestamp of the processed frame, in microseconds from the first frame.
+    pub pts_us: i64,
+    /// Mean squared error of the luma plane.
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in dB (`f64::INFINITY` for identical frames).
+    pub psnr: f64,
+    /// Structural similarity index, in the range `-1.0..=1.0`.
+    pub ssim: f64,
+}
+
+/// Per-frame and aggregate quality scores collected by [`QualityMetricFilter`].
+#[derive(Debug, Clone, Default)]
+pub struct QualityMetrics {
+    /// Scores for every processed frame that could be matched with a reference frame.
+    pub frames: Vec<FrameQuality>,
+    /// Number of processed frames that had no matching reference frame.
+    pub skipped_frames: u64,
+    /// Number of reference packets that failed to decode and were skipped.
+    pub reference_errors: u64,
+}
+
+impl QualityMetrics {
+    /// Average PSNR over all matched frames, computed from the mean MSE.
+    pub fn average_psnr(&self) -> Option<f64> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let mse = self.frames.iter().map(|f| f.mse).sum::<f64>() / self.frames.len() as f64;
+        Some(psnr_from_mse(mse))
+    }
+
+    /// Average SSIM over all matched frames.
+    pub fn average_ssim(&self) -> Option<f64> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        Some(self.frames.iter().map(|f| f.ssim).sum::<f64>() / self.frames.len() as f64)
+    }
+}
+
+struct ReferenceFrame {
+    pts_us: i64,
+    duration_us: i64,
+    width: i32,
+    height: i32,
+    luma: Vec<u8>,
+}
+
+pub struct QualityMetricFilter {
+    reference_url: String,
+    metrics: Arc<Mutex<QualityMetrics>>,
+    receiver: Option<Receiver<ReferenceFrame>>,
+    worker: Option<JoinHandle<()>>,
+    pending: Option<ReferenceFrame>,
+    reference_finished: bool,
+    // the PTS of the first processed frame, in microseconds
+    first_pts_us: Option<i64>,
+    converter: FrameConverter,
+}
+
+impl QualityMetricFilter {
+    /// Creates a filter comparing each frame against the best video stream of `reference_url`.
+    ///
+    /// Both sides are compared from their first frame on, so the processed input is expected
+    /// to start at the same picture as the reference, i.e. not to be cut with a start time.
+    pub fn new(reference_url: impl Into<String>) -> Self {
+        Self {
+            reference_url: reference_url.into(),
+            metrics: Arc::new(Mutex::new(QualityMetrics::default())),
+            receiver: None,
+            worker: None,
+            pending: None,
+            reference_finished: false,
+            first_pts_us: None,
+            converter: FrameConverter::new(),
+        }
+    }
+
+    /// Returns a handle to the collected metrics. Clone it before handing the filter to a pipeline.
+    ///
+    /// The running scores are also published as pipeline attributes, see the
+    /// [module documentation](crate::filter::quality_metric_filter).
+    pub fn metrics(&self) -> Arc<Mutex<QualityMetrics>> {
+        self.metrics.clone()
+    }
+
+    fn next_reference(&mut self) -> Option<ReferenceFrame> {
+        if let Some(reference) = self.pending.take() {
+            return Some(reference);
+        }
+        if self.reference_finished {
+            return None;
+        }
+        match self.receiver.as_ref().map(|receiver| receiver.recv()) {
+            Some(Ok(reference)) => Some(reference),
+            _ => {
+                self.reference_finished = true;
+                None
+            }
+        }
+    }
+
+    fn skip_frame(&mut self, pts_us: i64) {
+        warn!("No reference frame matches frame at {pts_us}us, skipping quality measurement.");
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.skipped_frames += 1;
+        }
+    }
+}
+
+impl FrameFilter for QualityMetricFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        let (sender, receiver) = crossbeam_channel::bounded(8);
+        let url = self.reference_url.clone();
+        let metrics = self.metrics.clone();
+        let worker = std::thread::Builder::new()
+            .name("quality-reference".to_string())
+            .spawn(move || {
+                if let Err(e) = decode_reference(&url, sender, &metrics) {
+                    error!("Failed to decode reference '{url}': {e}");
+                }
+            })
+            .map_err(|e| format!("Failed to spawn reference decoder: {e}"))?;
+
+        self.receiver = Some(receiver);
+        self.worker = Some(worker);
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let (pts, time_base) = unsafe { ((*frame.as_ptr()).pts, (*frame.as_ptr()).time_base) };
+        if pts == AV_NOPTS_VALUE || time_base.den == 0 {
+            return Ok(Some(frame));
+        }
+        let pts_us = unsafe { av_rescale_q(pts, time_base, AV_TIME_BASE_Q) };
+        let pts_us = pts_us - *self.first_pts_us.get_or_insert(pts_us);
+
+        loop {
+            let Some(reference) = self.next_reference() else {
+                self.skip_frame(pts_us);
+                break;
+            };
+
+            let tolerance = reference.duration_us / 2;
+            if reference.pts_us + tolerance < pts_us {
+                warn!("Reference frame at {}us has no matching frame, dropping it.", reference.pts_us);
+                continue;
+            }
+            if reference.pts_us - tolerance > pts_us {
+                self.pending = Some(reference);
+                self.skip_frame(pts_us);
+                break;
+            }
+
+            let converted = self
+                .converter
+                .convert(&frame, AV_PIX_FMT_GRAY8, reference.width, reference.height)?;
+            let luma = converted.plane_u8(0)?.concat();
+
+            let width = reference.width as usize;
+            let height = reference.height as usize;
+            let mse = compute_mse(&luma, &reference.luma);
+            let quality = FrameQuality {
+                pts_us,
+                mse,
+                psnr: psnr_from_mse(mse),
+                ssim: compute_ssim(&luma, &reference.luma, width, height),
+            };
+            ctx.publish_attribute(PSNR_ATTRIBUTE, quality.psnr);
+            ctx.publish_attribute(SSIM_ATTRIBUTE, quality.ssim);
+            if let Ok(mut metrics) = self.metrics.lock() {
+                metrics.frames.push(quality);
+                if let (Some(psnr), Some(ssim)) = (metrics.average_psnr(), metrics.average_ssim()) {
+                    ctx.publish_attribute(AVERAGE_PSNR_ATTRIBUTE, psnr);
+                    ctx.publish_attribute(AVERAGE_SSIM_ATTRIBUTE, ssim);
+                }
+            }
+            break;
+        }
+
+        Ok(Some(frame))
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        // Dropping the receiver unblocks the decoder thread if it is waiting to send.
+        self.pending = None;
+        self.receiver = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn decode_reference(
+    url: &str,
+    sender: Sender<ReferenceFrame>,
+    metrics: &Mutex<QualityMetrics>,
+) -> Result<(), ffmpeg_next::Error> {
+    let mut ictx = ffmpeg_next::format::input(&url)?;
+    let stream = ictx
+        .streams()
+        .best(ffmpeg_next::media::Type::Video)
+        .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+    let stream_index = stream.index();
+    let time_base: AVRational = stream.time_base().into();
+    let frame_rate = stream.avg_frame_rate();
+    let duration_us = if frame_rate.numerator() > 0 && frame_rate.denominator() > 0 {
+        1_000_000 * frame_rate.denominator() as i64 / frame_rate.numerator() as i64
+    } else {
+        40_000
+    };
+
+    let context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())?;
+    let mut decoder = context.decoder().video()?;
+    let mut scaler: Option<ffmpeg_next::software::scaling::Context> = None;
+    // counted from the first frame, as the processed frames
+    let mut first_pts: Option<i64> = None;
+
+    let mut receive_frames = |decoder: &mut ffmpeg_next::decoder::Video| -> Result<bool, ffmpeg_next::Error> {
+        let mut decoded = ffmpeg_next::frame::Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let Some(pts) = decoded.timestamp() else {
+                continue;
+            };
+            if scaler.is_none() {
+                scaler = Some(ffmpeg_next::software::scaling::Context::get(
+                    decoded.format(),
+                    decoded.width(),
+                    decoded.height(),
+                    ffmpeg_next::format::Pixel::GRAY8,
+                    decoded.width(),
+                    decoded.height(),
+                    ffmpeg_next::software::scaling::Flags::BILINEAR,
+                )?);
+            }
+            let mut gray = ffmpeg_next::frame::Video::empty();
+            scaler.as_mut().unwrap().run(&decoded, &mut gray)?;
+
+            let width = gray.width() as usize;
+            let height = gray.height() as usize;
+            let stride = gray.stride(0);
+            let mut luma = Vec::with_capacity(width * height);
+            for row in gray.data(0).chunks(stride).take(height) {
+                luma.extend_from_slice(&row[..width]);
+            }
+
+            let reference = ReferenceFrame {
+                pts_us: unsafe { av_rescale_q(pts - *first_pts.get_or_insert(pts), time_base, AV_TIME_BASE_Q) },
+                duration_us,
+                width: width as i32,
+                height: height as i32,
+                luma,
+            };
+            if sender.send(reference).is_err() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        if let Err(e) = decoder.send_packet(&packet) {
+            warn!("Skipping reference packet at {:?} that failed to decode: {e}", packet.pts());
+            if let Ok(mut metrics) = metrics.lock() {
+                metrics.reference_errors += 1;
+            }
+            continue;
+        }
+        if !receive_frames(&mut decoder)? {
+            return Ok(());
+        }
+    }
+    decoder.send_eof()?;
+    receive_frames(&mut decoder)?;
+    Ok(())
+}
+
+fn compute_mse(a: &[u8], b: &[u8]) -> f64 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    let sum: u64 = a[..len]
+        .iter()
+        .zip(&b[..len])
+        .map(|(&x, &y)| {
+            let d = x as i64 - y as i64;
+            (d * d) as u64
+        })
+        .sum();
+    sum as f64 / len as f64
+}
+
+fn psnr_from_mse(mse: f64) -> f64 {
+    if mse <= 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+/// SSIM over 8x8 windows with a stride of 4, averaged across the plane.
+fn compute_ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    const WINDOW: usize = 8;
+    const STRIDE: usize = 4;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    if width < WINDOW || height < WINDOW {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0usize;
+    let n = (WINDOW * WINDOW) as f64;
+    for y in (0..=height - WINDOW).step_by(STRIDE) {
+        for x in (0..=width - WINDOW).step_by(STRIDE) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for wy in 0..WINDOW {
+                let offset = (y + wy) * width + x;
+                for wx in 0..WINDOW {
+                    let pa = a[offset + wx] as f64;
+                    let pb = b[offset + wx] as f64;
+                    sum_a += pa;
+                    sum_b += pb;
+                    sum_aa += pa * pa;
+                    sum_bb += pb * pb;
+                    sum_ab += pa * pb;
+                }
+            }
+            let mean_a = sum_a / n;
+            let mean_b = sum_b / n;
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let cov = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_planes() {
+        let plane: Vec<u8> = (0..64 * 64).map(|i| (i % 251) as u8).collect();
+        assert_eq!(compute_mse(&plane, &plane), 0.0);
+        assert!(psnr_from_mse(0.0).is_infinite());
+        assert!((compute_ssim(&plane, &plane, 64, 64) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_nonzero_start_time() {
+        use crate::core::context::ffmpeg_context::FfmpegContext;
+        use crate::core::context::output::Output;
+        use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+
+        // a copy of the input starting at 1.4s
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("quality_offset.mp4")
+                    .add_stream_map_with_copy("0:v")
+                    .set_output_ts_offset_us(1_400_000)
+                    .set_recording_time_us(1_000_000),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        let filter = QualityMetricFilter::new("test.mp4");
+        let metrics = filter.metrics();
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("quality", Box::new(filter));
+        FfmpegContext::builder()
+            .input("quality_offset.mp4")
+            .output(Output::from("quality_output.mp4").add_frame_pipeline(pipeline))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+        std::fs::remove_file("quality_offset.mp4").unwrap();
+        std::fs::remove_file("quality_output.mp4").unwrap();
+
+        let metrics = metrics.lock().unwrap();
+        assert!(!metrics.frames.is_empty());
+        assert_eq!(metrics.reference_errors, 0);
+        // the same pictures are paired, not pictures 1.4s apart
+        assert!(metrics.average_psnr().unwrap() > 40.0, "{:?}", metrics.average_psnr());
+    }
+
+    #[test]
+    fn test_published_attributes() {
+        use crate::core::context::ffmpeg_context::FfmpegContext;
+        use crate::core::context::output::Output;
+        use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+
+        type Scores = Arc<Mutex<Vec<[Option<f64>; 4]>>>;
+
+        /// Records the quality attributes it sees for every frame.
+        struct Reader(Scores);
+
+        impl FrameFilter for Reader {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                let scores = [PSNR_ATTRIBUTE, SSIM_ATTRIBUTE, AVERAGE_PSNR_ATTRIBUTE, AVERAGE_SSIM_ATTRIBUTE]
+                    .map(|key| ctx.get_attribute::<f64>(key).copied());
+                self.0.lock().unwrap().push(scores);
+                Ok(Some(frame))
+            }
+        }
+
+        let filter = QualityMetricFilter::new("test.mp4");
+        let metrics = filter.metrics();
+        let scores: Scores = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("quality", Box::new(filter))
+            .filter("reader", Box::new(Reader(scores.clone())));
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("quality_attributes.mp4")
+                    .add_frame_pipeline(pipeline)
+                    .set_recording_time_us(1_000_000),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+        std::fs::remove_file("quality_attributes.mp4").unwrap();
+
+        let metrics = metrics.lock().unwrap();
+        let scores = scores.lock().unwrap();
+        assert!(!metrics.frames.is_empty());
+        // the filter after the quality filter sees the scores of the frame it was given
+        let last = metrics.frames.last().unwrap();
+        assert_eq!(
+            scores.iter().rev().find(|scores| scores[0].is_some()).copied(),
+            Some([Some(last.psnr), Some(last.ssim), metrics.average_psnr(), metrics.average_ssim()])
+        );
+    }
+
+    #[test]
+    fn test_different_planes() {
+        let a = vec![100u8; 32 * 32];
+        let b = vec![110u8; 32 * 32];
+        assert_eq!(compute_mse(&a, &b), 100.0);
+        assert!((psnr_from_mse(100.0) - 28.13).abs() < 0.01);
+        assert!(compute_ssim(&a, &b, 32, 32) < 1.0);
+    }
+}
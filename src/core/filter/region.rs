@@ -0,0 +1,292 @@
+//! Rectangles of video frames that pixel-processing filters apply to, e.g. to blur a license
+//! plate without touching the rest of the picture.
+//!
+//! A [`Region`] is given in pixels of the frame. Before it is used on a frame it is clipped to
+//! the frame, and widened to whole chroma samples: with `yuv420p` frames its edges move out to
+//! even coordinates, so that every chroma sample is either inside or outside of it. A region
+//! lying entirely outside the frame, or empty, is an error.
+//!
+//! With a feather, the effect fades in over that many pixels inside the edges of the region,
+//! instead of stopping sharply at them. Edges lying on the border of the frame are not
+//! feathered.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("plate", Box::new(
+//!         BlurFilter::new(16).set_region(Region::new(820, 610, 240, 60).set_feather(8)),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_planes::{FramePlanes, PlaneInfo};
+use ffmpeg_next::Frame;
+use crate::util::ffmpeg_utils::pixel_format;
+use ffmpeg_sys_next::{av_pix_fmt_desc_get, AV_PIX_FMT_FLAG_RGB};
+
+/// A `w` x `h` rectangle whose top left corner is at `(x, y)`, in pixels of the frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    /// Width, in pixels, of the band inside the edges over which the effect fades in.
+    pub feather: u32,
+}
+
+impl Region {
+    /// Creates a region with sharp edges.
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h, feather: 0 }
+    }
+
+    /// Sets the width of the band inside the edges over which the effect fades in (0, sharp
+    /// edges, by default).
+    pub fn set_feather(mut self, feather: u32) -> Self {
+        self.feather = feather;
+        self
+    }
+
+    /// Returns the bounds of the region in `frame`, see the [module documentation](self).
+    pub(crate) fn bounds(&self, frame: &Frame) -> Result<RegionBounds, String> {
+        let (log2_chroma_w, log2_chroma_h) = unsafe {
+            let format = (*frame.as_ptr()).format;
+            let desc = pixel_format(format).map_or(std::ptr::null(), |format| av_pix_fmt_desc_get(format));
+            if desc.is_null() {
+                return Err(format!("Unknown pixel format {format}"));
+            }
+            ((*desc).log2_chroma_w, (*desc).log2_chroma_h)
+        };
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        self.bounds_in(width, height, log2_chroma_w, log2_chroma_h)
+    }
+
+    fn bounds_in(
+        &self,
+        width: usize,
+        height: usize,
+        log2_chroma_w: u8,
+        log2_chroma_h: u8,
+    ) -> Result<RegionBounds, String> {
+        let (x, y, w, h) = (self.x as usize, self.y as usize, self.w as usize, self.h as usize);
+        if w == 0 || h == 0 {
+            return Err(format!("Region {self:?} is empty"));
+        }
+        if x >= width || y >= height {
+            return Err(format!("Region {self:?} is outside the {width}x{height} frame"));
+        }
+        // out to whole chroma samples, then clipped to the frame
+        let floor = |value: usize, shift: u8| value >> shift << shift;
+        let ceil = |value: usize, shift: u8| ceil_rshift(value, shift) << shift;
+        Ok(RegionBounds {
+            x0: floor(x, log2_chroma_w),
+            y0: floor(y, log2_chroma_h),
+            x1: ceil(x.saturating_add(w), log2_chroma_w).min(width),
+            y1: ceil(y.saturating_add(h), log2_chroma_h).min(height),
+            width,
+            height,
+            feather: self.feather as f32,
+        })
+    }
+}
+
+/// A [`Region`] clipped to a frame and aligned to its chroma samples, in pixels of the frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RegionBounds {
+    x0: usize,
+    y0: usize,
+    // the ends excluded
+    x1: usize,
+    y1: usize,
+    // the size of the frame
+    width: usize,
+    height: usize,
+    feather: f32,
+}
+
+impl RegionBounds {
+    /// The bounds of the whole `width` x `height` frame.
+    pub(crate) fn full(width: usize, height: usize) -> Self {
+        Self { x0: 0, y0: 0, x1: width, y1: height, width, height, feather: 0.0 }
+    }
+
+    /// Returns the bounds in a plane subsampled by `log2_w` and `log2_h`.
+    pub(crate) fn in_plane(&self, log2_w: u8, log2_h: u8) -> (usize, usize, usize, usize) {
+        (self.x0 >> log2_w, self.y0 >> log2_h, ceil_rshift(self.x1, log2_w), ceil_rshift(self.y1, log2_h))
+    }
+
+    /// Returns how much of the effect applies at `(x, y)` inside the bounds, in pixels of the
+    /// frame: 1, fading to 0 over the feather at the edges.
+    fn weight(&self, x: f32, y: f32) -> f32 {
+        // the edges on the border of the frame are not feathered
+        let distance = |inside: f32, on_border: bool| if on_border { f32::INFINITY } else { inside };
+        let d = distance(x - self.x0 as f32, self.x0 == 0)
+            .min(distance(self.x1 as f32 - x, self.x1 == self.width))
+            .min(distance(y - self.y0 as f32, self.y0 == 0))
+            .min(distance(self.y1 as f32 - y, self.y1 == self.height));
+        if self.feather <= 0.0 {
+            1.0
+        } else {
+            (d / self.feather).clamp(0.0, 1.0)
+        }
+    }
+
+    /// Returns the weight of every pixel of the bounds in a plane subsampled by `log2_w` and
+    /// `log2_h`, row after row.
+    pub(crate) fn plane_mask(&self, log2_w: u8, log2_h: u8) -> Vec<f32> {
+        let (x0, y0, x1, y1) = self.in_plane(log2_w, log2_h);
+        let (scale_x, scale_y) = ((1 << log2_w) as f32, (1 << log2_h) as f32);
+        (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+            // at the center of the pixel, in pixels of the frame
+            .map(|(x, y)| self.weight((x as f32 + 0.5) * scale_x, (y as f32 + 0.5) * scale_y))
+            .collect()
+    }
+}
+
+/// `AV_CEIL_RSHIFT`: the size of a subsampled plane.
+pub(crate) fn ceil_rshift(size: usize, shift: u8) -> usize {
+    (size + (1 << shift) - 1) >> shift
+}
+
+/// A plane of a pixel format, with its subsampling and the samples of a pixel in it.
+pub(crate) struct PlaneLayout {
+    pub(crate) index: usize,
+    pub(crate) log2_w: u8,
+    pub(crate) log2_h: u8,
+    // e.g. 2 in the interleaved chroma plane of `nv12`, 3 in `rgb24`
+    pub(crate) step: usize,
+}
+
+/// Returns the planes of the pixel format of `frame`. Packed YUV formats (`yuyv422`), whose
+/// luma and chroma samples of different sizes share a plane, are rejected.
+pub(crate) fn plane_layouts(frame: &Frame) -> Result<Vec<PlaneLayout>, String> {
+    unsafe {
+        let format = (*frame.as_ptr()).format;
+        let desc = pixel_format(format).map_or(std::ptr::null(), |format| av_pix_fmt_desc_get(format));
+        if desc.is_null() {
+            return Err(format!("Unknown pixel format {format}"));
+        }
+        let components = &(*desc).comp[..(*desc).nb_components as usize];
+        let rgb = (*desc).flags & AV_PIX_FMT_FLAG_RGB as u64 != 0;
+        if !rgb && components.len() >= 3 && components[0].plane == components[1].plane {
+            return Err(format!("Pixel format {format} is packed YUV, planar YUV, gray or RGB is needed"));
+        }
+
+        let mut planes: Vec<PlaneLayout> = Vec::new();
+        for (i, comp) in components.iter().enumerate() {
+            let index = comp.plane as usize;
+            if planes.iter().any(|plane| plane.index == index) {
+                continue;
+            }
+            let info = frame.plane_info(index)?;
+            // the chroma components of YUV formats are subsampled
+            let subsampled = !rgb && (i == 1 || i == 2);
+            planes.push(PlaneLayout {
+                index,
+                log2_w: if subsampled { (*desc).log2_chroma_w } else { 0 },
+                log2_h: if subsampled { (*desc).log2_chroma_h } else { 0 },
+                step: (comp.step as usize / info.bytes_per_sample).max(1),
+            });
+        }
+        Ok(planes)
+    }
+}
+
+/// An 8 or 16-bit sample.
+pub(crate) trait Sample: Copy {
+    /// Returns the value of the sample, without the shift of formats like `p010le`.
+    fn value(self, info: &PlaneInfo) -> f32;
+    /// Returns the sample of `value`, rounded and clamped to the bit depth.
+    fn from_value(value: f32, info: &PlaneInfo) -> Self;
+}
+
+impl Sample for u8 {
+    fn value(self, _info: &PlaneInfo) -> f32 {
+        self as f32
+    }
+
+    fn from_value(value: f32, _info: &PlaneInfo) -> Self {
+        value.round().clamp(0.0, 255.0) as u8
+    }
+}
+
+impl Sample for u16 {
+    fn value(self, info: &PlaneInfo) -> f32 {
+        (self >> info.shift) as f32
+    }
+
+    fn from_value(value: f32, info: &PlaneInfo) -> Self {
+        let max = ((1u32 << info.bit_depth) - 1) as f32;
+        (value.round().clamp(0.0, max) as u16) << info.shift
+    }
+}
+
+/// Puts back the samples of `original` wherever `frame`, processed as a whole, is outside of
+/// `bounds`, blending the two over the feather. Both frames have the same format and size.
+pub(crate) fn restore_outside(bounds: &RegionBounds, original: &Frame, frame: &mut Frame) -> Result<(), String> {
+    for plane in plane_layouts(frame)? {
+        let info = frame.plane_info(plane.index)?;
+        if info.bytes_per_sample == 2 {
+            let original = original.plane_u16(plane.index)?;
+            blend_plane(&original, &mut frame.plane_u16_mut(plane.index)?, bounds, &plane, &info);
+        } else {
+            let original = original.plane_u8(plane.index)?;
+            blend_plane(&original, &mut frame.plane_u8_mut(plane.index)?, bounds, &plane, &info);
+        }
+    }
+    Ok(())
+}
+
+fn blend_plane<T: Sample>(
+    original: &[&[T]],
+    rows: &mut [&mut [T]],
+    bounds: &RegionBounds,
+    plane: &PlaneLayout,
+    info: &PlaneInfo,
+) {
+    let (x0, y0, x1, y1) = bounds.in_plane(plane.log2_w, plane.log2_h);
+    let mask = bounds.plane_mask(plane.log2_w, plane.log2_h);
+    let step = plane.step;
+    for (y, (row, original)) in rows.iter_mut().zip(original).enumerate() {
+        if y < y0 || y >= y1 {
+            row.copy_from_slice(original);
+            continue;
+        }
+        let row_mask = &mask[(y - y0) * (x1 - x0)..(y - y0 + 1) * (x1 - x0)];
+        for (x, (sample, original)) in row.iter_mut().zip(original.iter()).enumerate() {
+            let weight = match (x / step).checked_sub(x0) {
+                Some(i) if i < x1 - x0 => row_mask[i],
+                _ => 0.0,
+            };
+            if weight <= 0.0 {
+                *sample = *original;
+            } else if weight < 1.0 {
+                let value = original.value(info) + (sample.value(info) - original.value(info)) * weight;
+                *sample = T::from_value(value, info);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        // out to even coordinates for 4:2:0, clipped to the frame
+        let bounds = Region::new(3, 5, 10, 100).bounds_in(64, 36, 1, 1).unwrap();
+        assert_eq!((bounds.x0, bounds.y0, bounds.x1, bounds.y1), (2, 4, 14, 36));
+        assert_eq!(bounds.in_plane(1, 1), (1, 2, 7, 18));
+        assert!(Region::new(64, 0, 10, 10).bounds_in(64, 36, 1, 1).is_err());
+        assert!(Region::new(0, 0, 0, 10).bounds_in(64, 36, 1, 1).is_err());
+
+        // fades in over the feather, except at the border of the frame
+        let bounds = Region::new(10, 0, 20, 36).set_feather(4).bounds_in(64, 36, 0, 0).unwrap();
+        assert_eq!(bounds.weight(10.5, 10.0), 0.125);
+        assert_eq!(bounds.weight(12.0, 10.0), 0.5);
+        assert_eq!(bounds.weight(20.0, 0.5), 1.0);
+        assert_eq!(RegionBounds::full(64, 36).plane_mask(1, 1), vec![1.0; 32 * 18]);
+    }
+}
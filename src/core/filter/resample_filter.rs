@@ -0,0 +1,316 @@
+//! A [`FrameFilter`] that converts audio frames to a fixed sample rate, channel layout and
+//! sample format with libswresample.
+//!
+//! Use it upstream of filters that assume a fixed audio format. The resampler is created from
+//! the first frame and rebuilt when the input format changes. It buffers a few samples
+//! internally, so output frames may be shorter or longer than the input frames. The buffered
+//! samples are flushed when the end of stream reaches the filter.
+//!
+//! Output timestamps are in `1 / sample_rate` units. They start at the first input frame's
+//! timestamp and then advance by the number of samples emitted, so they stay continuous
+//! across the rate change.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO)
+//!     .filter("resample", Box::new(
+//!         ResampleFilter::new(16000)
+//!             .set_channels(1)
+//!             .set_sample_format(AVSampleFormat::AV_SAMPLE_FMT_FLT),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::util::ffmpeg_utils::{av_err2str, sample_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_channel_layout_copy, av_channel_layout_default, av_channel_layout_uninit, av_frame_get_buffer,
+    av_rescale_q, swr_alloc_set_opts2, swr_convert, swr_free, swr_get_out_samples, swr_init, AVChannelLayout,
+    AVMediaType, AVRational, AVSampleFormat, SwrContext, AV_NOPTS_VALUE,
+};
+use std::collections::VecDeque;
+use std::ptr::{null, null_mut};
+
+pub struct ResampleFilter {
+    sample_rate: i32,
+    channels: Option<i32>,
+    sample_format: Option<AVSampleFormat>,
+
+    swr: *mut SwrContext,
+    // input format, sample rate and channel count the resampler was created for
+    key: (i32, i32, i32),
+    out_layout: AVChannelLayout,
+    out_format: AVSampleFormat,
+    next_pts: Option<i64>,
+    ready: VecDeque<Frame>,
+}
+
+unsafe impl Send for ResampleFilter {}
+
+impl ResampleFilter {
+    /// Creates a filter resampling the audio to `sample_rate` Hz, keeping the channel layout
+    /// and sample format of the input unless [`set_channels`](Self::set_channels) or
+    /// [`set_sample_format`](Self::set_sample_format) are used.
+    pub fn new(sample_rate: i32) -> Self {
+        Self {
+            sample_rate,
+            channels: None,
+            sample_format: None,
+            swr: null_mut(),
+            key: (0, 0, 0),
+            out_layout: unsafe { std::mem::zeroed() },
+            out_format: AVSampleFormat::AV_SAMPLE_FMT_NONE,
+            next_pts: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Converts to the default channel layout with `channels` channels, e.g. `1` for mono
+    /// or `2` for stereo.
+    pub fn set_channels(mut self, channels: i32) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Converts to `sample_format`, e.g. `AV_SAMPLE_FMT_FLTP`.
+    pub fn set_sample_format(mut self, sample_format: AVSampleFormat) -> Self {
+        self.sample_format = Some(sample_format);
+        self
+    }
+
+    fn configure(&mut self, frame: &Frame) -> Result<(), String> {
+        let (format, sample_rate, channels) = unsafe {
+            let f = frame.as_ptr();
+            ((*f).format, (*f).sample_rate, (*f).ch_layout.nb_channels)
+        };
+        let key = (format, sample_rate, channels);
+        if !self.swr.is_null() {
+            if self.key == key {
+                return Ok(());
+            }
+            log::debug!("Resample input changed to {sample_rate} Hz, {channels} channels (format {format}), reconfiguring.");
+            self.flush()?;
+            self.free_resampler();
+        }
+        if sample_rate <= 0 {
+            return Err(format!("Invalid input sample rate {sample_rate}"));
+        }
+        let in_format = sample_format(format).ok_or_else(|| format!("Unknown sample format {format}"))?;
+
+        unsafe {
+            let f = frame.as_ptr();
+            av_channel_layout_uninit(&mut self.out_layout);
+            match self.channels {
+                Some(channels) => av_channel_layout_default(&mut self.out_layout, channels),
+                None => {
+                    let ret = av_channel_layout_copy(&mut self.out_layout, &(*f).ch_layout);
+                    if ret < 0 {
+                        return Err(format!("Failed to copy channel layout: {}", av_err2str(ret)));
+                    }
+                }
+            }
+            self.out_format = self.sample_format.unwrap_or(in_format);
+
+            let ret = swr_alloc_set_opts2(
+                &mut self.swr,
+                &self.out_layout,
+                self.out_format,
+                self.sample_rate,
+                &(*f).ch_layout,
+                in_format,
+                sample_rate,
+                0,
+                null_mut(),
+            );
+            if ret < 0 {
+                return Err(format!("Failed to allocate resampler: {}", av_err2str(ret)));
+            }
+            let ret = swr_init(self.swr);
+            if ret < 0 {
+                return Err(format!("Failed to initialize resampler: {}", av_err2str(ret)));
+            }
+        }
+
+        self.key = key;
+        Ok(())
+    }
+
+    /// Feeds `frame` (or nothing when flushing) to the resampler and queues the converted samples.
+    fn convert(&mut self, frame: Option<&Frame>) -> Result<(), String> {
+        unsafe {
+            let (in_data, in_samples): (*const *const u8, i32) = match frame {
+                Some(frame) => ((*frame.as_ptr()).extended_data as *const *const u8, (*frame.as_ptr()).nb_samples),
+                None => (null(), 0),
+            };
+            let out_samples = swr_get_out_samples(self.swr, in_samples);
+            if out_samples < 0 {
+                return Err(format!("Failed to get resampler output size: {}", av_err2str(out_samples)));
+            }
+
+            let mut output = Frame::empty();
+            if output.as_ptr().is_null() {
+                return Err("Failed to create frame: Out of memory.".to_string());
+            }
+            let dst = output.as_mut_ptr();
+            (*dst).format = self.out_format as i32;
+            (*dst).sample_rate = self.sample_rate;
+            (*dst).nb_samples = out_samples.max(1);
+            let ret = av_channel_layout_copy(&mut (*dst).ch_layout, &self.out_layout);
+            if ret < 0 {
+                return Err(format!("Failed to copy channel layout: {}", av_err2str(ret)));
+            }
+            let ret = av_frame_get_buffer(dst, 0);
+            if ret < 0 {
+                return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+            }
+
+            let ret = swr_convert(self.swr, (*dst).extended_data, (*dst).nb_samples, in_data, in_samples);
+            if ret < 0 {
+                return Err(format!("Failed to resample audio: {}", av_err2str(ret)));
+            }
+            if ret == 0 {
+                return Ok(());
+            }
+
+            let pts = self.next_pts.unwrap_or(0);
+            (*dst).nb_samples = ret;
+            (*dst).pts = pts;
+            (*dst).time_base = AVRational { num: 1, den: self.sample_rate };
+            self.next_pts = Some(pts + ret as i64);
+            self.ready.push_back(output);
+        }
+        Ok(())
+    }
+
+    /// Queues the samples still buffered in the resampler.
+    fn flush(&mut self) -> Result<(), String> {
+        if self.swr.is_null() {
+            return Ok(());
+        }
+        loop {
+            let queued = self.ready.len();
+            self.convert(None)?;
+            if self.ready.len() == queued {
+                return Ok(());
+            }
+        }
+    }
+
+    fn free_resampler(&mut self) {
+        if !self.swr.is_null() {
+            unsafe { swr_free(&mut self.swr) };
+        }
+        self.next_pts = None;
+    }
+}
+
+impl FrameFilter for ResampleFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_AUDIO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if self.sample_rate <= 0 {
+            return Err(format!("Invalid resample rate {}", self.sample_rate));
+        }
+        if let Some(channels) = self.channels {
+            if channels <= 0 {
+                return Err(format!("Invalid resample channel count {channels}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        if unsafe { frame.as_ptr().is_null() } {
+            return Ok(Some(frame));
+        }
+
+        if frame.is_empty() {
+            // end of stream: emit the buffered samples, then forward it. The resampler is
+            // released, so frames arriving afterwards (e.g. a looped input) start a new one.
+            self.flush()?;
+            self.free_resampler();
+            self.ready.push_back(frame);
+        } else {
+            self.configure(&frame)?;
+            if self.next_pts.is_none() {
+                // the output timeline starts at the first input frame
+                let pts = unsafe { (*frame.as_ptr()).pts };
+                if pts != AV_NOPTS_VALUE {
+                    let time_base = AVRational { num: 1, den: self.sample_rate };
+                    self.next_pts = Some(unsafe { av_rescale_q(pts, (*frame.as_ptr()).time_base, time_base) });
+                }
+            }
+            self.convert(Some(&frame))?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        Ok(self.ready.pop_front())
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        self.free_resampler();
+    }
+}
+
+impl Drop for ResampleFilter {
+    fn drop(&mut self) {
+        self.free_resampler();
+        unsafe { av_channel_layout_uninit(&mut self.out_layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_resample_keeps_pts_continuous() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("resample", &mut attributes);
+        let mut filter = ResampleFilter::new(16000).set_sample_format(AVSampleFormat::AV_SAMPLE_FMT_FLT);
+        filter.init(&ctx).unwrap();
+
+        let mut outputs = Vec::new();
+        for i in 0..10 {
+            let mut frame = unsafe { Frame::empty() };
+            unsafe {
+                let f = frame.as_mut_ptr();
+                (*f).format = AVSampleFormat::AV_SAMPLE_FMT_S16 as i32;
+                (*f).sample_rate = 48000;
+                (*f).nb_samples = 1024;
+                (*f).pts = 1024 * i;
+                (*f).time_base = AVRational { num: 1, den: 48000 };
+                av_channel_layout_default(&mut (*f).ch_layout, 2);
+                assert!(av_frame_get_buffer(f, 0) >= 0);
+            }
+            outputs.extend(filter.filter_frame(frame, &ctx).unwrap());
+            while let Some(frame) = filter.request_frame(&ctx).unwrap() {
+                outputs.push(frame);
+            }
+        }
+        outputs.extend(filter.filter_frame(unsafe { Frame::empty() }, &ctx).unwrap());
+        while let Some(frame) = filter.request_frame(&ctx).unwrap() {
+            outputs.push(frame);
+        }
+
+        let eof = outputs.pop().unwrap();
+        assert!(eof.is_empty());
+        let mut expected_pts = 0;
+        for frame in &outputs {
+            unsafe {
+                assert_eq!((*frame.as_ptr()).sample_rate, 16000);
+                assert_eq!((*frame.as_ptr()).format, AVSampleFormat::AV_SAMPLE_FMT_FLT as i32);
+                assert_eq!((*frame.as_ptr()).pts, expected_pts);
+                expected_pts += (*frame.as_ptr()).nb_samples as i64;
+            }
+        }
+        // 10240 samples at 48 kHz are 3413 samples at 16 kHz
+        assert!((expected_pts - 3413).abs() <= 1, "{expected_pts}");
+    }
+}
@@ -0,0 +1,281 @@
+//! A [`FrameFilter`] that duplicates a stream into named outputs, each with a chain of filters
+//! of its own, e.g. a low resolution preview next to the full resolution path.
+//!
+//! Every frame reaching the filter is passed on unchanged to the next filters of the pipeline
+//! (the main path), and a reference to it is run through the [`FramePipeline`] of every
+//! output, whose frames are delivered to the receiver of that output. The frames are not
+//! copied: like the scheduler when a decoded stream feeds several destinations, the outputs
+//! take a new reference to the same buffers (`av_frame_ref`), or a copy of the properties of
+//! frames without data, so the timestamps and side data are the same on every path. Filters
+//! of the outputs must therefore not write into the buffers they receive in place without
+//! making them writable first (`av_frame_make_writable`).
+//!
+//! The channel of each output holds a few frames; when it is full the filter waits for the
+//! receiver, so a slow consumer slows the whole stream down. Outputs whose receiver was not
+//! taken with [`output`](SplitFilter::output) before the job starts, or was dropped since, are
+//! skipped. The receivers disconnect once the stream ends.
+//!
+//! # Example
+//! ```rust,ignore
+//! let mut split = SplitFilter::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .add_output("preview", FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!         .filter("thumbnail", Box::new(ThumbnailFilter::new(160, 90))));
+//! let preview = split.output("preview").unwrap();
+//! std::thread::spawn(move || {
+//!     for frame in preview {
+//!         // show or store the thumbnail
+//!     }
+//! });
+//!
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("split", Box::new(split));
+//! FfmpegContext::builder()
+//!     .input(Input::from("input.mp4").add_frame_pipeline(pipeline))
+//!     .output("output.mp4")
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::filter::frame_pipeline::FramePipeline;
+use crate::util::ffmpeg_utils::av_err2str;
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{av_frame_copy_props, av_frame_ref, AVMediaType};
+use log::debug;
+
+/// Number of frames the channel of an output holds before the filter waits for its receiver.
+const SPLIT_OUTPUT_QUEUE_SIZE: usize = 8;
+
+/// An output of a [`SplitFilter`]: a pipeline and the channel its frames are sent to.
+struct SplitOutput {
+    name: String,
+    pipeline: FramePipeline,
+    sender: Option<Sender<Frame>>,
+    receiver: Option<Receiver<Frame>>,
+    initialized: bool,
+}
+
+/// Duplicates a stream into named outputs, see the [module documentation](self).
+pub struct SplitFilter {
+    media_type: AVMediaType,
+    outputs: Vec<SplitOutput>,
+}
+
+impl SplitFilter {
+    /// Creates a filter for a stream of `media_type`, with no output yet.
+    pub fn new(media_type: AVMediaType) -> Self {
+        Self {
+            media_type,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Adds an output named `name`, whose frames go through `pipeline`. An empty pipeline
+    /// delivers the frames as they are.
+    ///
+    /// # Panics
+    /// If the pipeline does not process the media type of the filter, or an output is
+    /// already named `name`.
+    pub fn add_output(mut self, name: &str, pipeline: impl Into<FramePipeline>) -> Self {
+        let pipeline = pipeline.into();
+        assert_eq!(self.media_type, pipeline.media_type);
+        assert!(
+            self.outputs.iter().all(|output| output.name != name),
+            "split output '{name}' already exists"
+        );
+        let (sender, receiver) = crossbeam_channel::bounded(SPLIT_OUTPUT_QUEUE_SIZE);
+        self.outputs.push(SplitOutput {
+            name: name.to_string(),
+            pipeline,
+            sender: Some(sender),
+            receiver: Some(receiver),
+            initialized: false,
+        });
+        self
+    }
+
+    /// Takes the receiver of the frames of the output `name`. Returns `None` if there is no
+    /// such output, or if its receiver was already taken.
+    pub fn output(&mut self, name: &str) -> Option<Receiver<Frame>> {
+        self.outputs
+            .iter_mut()
+            .find(|output| output.name == name)
+            .and_then(|output| output.receiver.take())
+    }
+}
+
+impl FrameFilter for SplitFilter {
+    fn media_type(&self) -> AVMediaType {
+        self.media_type
+    }
+
+    fn init(&mut self, ctx: &FrameFilterContext) -> Result<(), String> {
+        for output in &mut self.outputs {
+            // nobody reads an output whose receiver was not taken
+            if output.receiver.take().is_some() {
+                debug!("Split output '{}' has no receiver, skipping it", output.name);
+                output.sender = None;
+                continue;
+            }
+            if let Some(sample_aspect_ratio) = ctx.sample_aspect_ratio() {
+                output.pipeline.update_sample_aspect_ratio(sample_aspect_ratio);
+            }
+            output.pipeline.update_frame_rate(ctx.frame_rate());
+            output
+                .pipeline
+                .init_filters()
+                .map_err(|e| format!("Split output '{}': {e}", output.name))?;
+            output.initialized = true;
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        if frame.as_ptr().is_null() {
+            return Ok(Some(frame));
+        }
+
+        for output in &mut self.outputs {
+            if output.sender.is_none() {
+                continue;
+            }
+            if let Some(sample_aspect_ratio) = ctx.sample_aspect_ratio() {
+                output.pipeline.update_sample_aspect_ratio(sample_aspect_ratio);
+            }
+            output.pipeline.update_frame_rate(ctx.frame_rate());
+
+            let copy = reference_frame(&frame)?;
+            let filtered = output
+                .pipeline
+                .run_filters(copy)
+                .map_err(|e| format!("Split output '{}': {e}", output.name))?;
+            output.send(filtered);
+
+            // frames the filters of the output release on their own
+            for i in 0..output.pipeline.filter_len() {
+                while output.sender.is_some() {
+                    let requested = output
+                        .pipeline
+                        .request_frame(i)
+                        .map_err(|e| format!("Split output '{}': {e}", output.name))?;
+                    let Some(requested) = requested else {
+                        break;
+                    };
+                    let filtered = output
+                        .pipeline
+                        .run_filters_from(i + 1, requested)
+                        .map_err(|e| format!("Split output '{}': {e}", output.name))?;
+                    output.send(filtered);
+                }
+            }
+        }
+        Ok(Some(frame))
+    }
+
+    fn uninit(&mut self, _ctx: &FrameFilterContext) {
+        for output in &mut self.outputs {
+            if output.initialized {
+                output.pipeline.uninit_filters();
+                output.initialized = false;
+            }
+            // disconnects the receiver
+            output.sender = None;
+        }
+    }
+}
+
+impl SplitOutput {
+    /// Sends a frame to the receiver, forgetting the receiver once it is dropped.
+    fn send(&mut self, frame: Option<Frame>) {
+        let (Some(sender), Some(frame)) = (&self.sender, frame) else {
+            return;
+        };
+        if sender.send(frame).is_err() {
+            debug!("Split output '{}' receiver dropped, skipping it", self.name);
+            self.sender = None;
+        }
+    }
+}
+
+/// A new reference to `frame`, or a copy of its properties if it has no data, e.g. a frame
+/// signaling the timestamp of the end of the stream.
+fn reference_frame(frame: &Frame) -> Result<Frame, String> {
+    unsafe {
+        let mut copy = Frame::empty();
+        if copy.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let ret = if !(*frame.as_ptr()).buf[0].is_null() {
+            av_frame_ref(copy.as_mut_ptr(), frame.as_ptr())
+        } else {
+            av_frame_copy_props(copy.as_mut_ptr(), frame.as_ptr())
+        };
+        if ret < 0 {
+            return Err(format!("Failed to reference frame: {}", av_err2str(ret)));
+        }
+        Ok(copy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::input::Input;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_filter::NoopFilter;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::FfmpegContext;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the timestamps of the frames of the main path.
+    struct PtsRecorder(Arc<Mutex<Vec<i64>>>);
+
+    impl FrameFilter for PtsRecorder {
+        fn media_type(&self) -> AVMediaType {
+            AVMediaType::AVMEDIA_TYPE_VIDEO
+        }
+
+        fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+            self.0.lock().unwrap().push(unsafe { (*frame.as_ptr()).pts });
+            Ok(Some(frame))
+        }
+    }
+
+    #[test]
+    fn test_split_filter() {
+        let video = || FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO);
+        let mut split = SplitFilter::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .add_output("preview", video().filter("noop", Box::new(NoopFilter::new(AVMediaType::AVMEDIA_TYPE_VIDEO))))
+            // never read, must not hold the stream back
+            .add_output("unused", video());
+        let preview = split.output("preview").unwrap();
+        assert!(split.output("preview").is_none());
+        assert!(split.output("missing").is_none());
+
+        let consumer = std::thread::spawn(move || {
+            preview.iter().map(|frame| unsafe { (*frame.as_ptr()).pts }).collect::<Vec<_>>()
+        });
+
+        let main_pts = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = video()
+            .filter("split", Box::new(split))
+            .filter("recorder", Box::new(PtsRecorder(main_pts.clone())));
+        let result = FfmpegContext::builder()
+            .input(Input::from("test.mp4").add_frame_pipeline(pipeline))
+            .output(Output::from("-").set_format("null").add_stream_map("0:v").set_recording_time_us(1_000_000))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let preview_pts = consumer.join().unwrap();
+        let main_pts = main_pts.lock().unwrap();
+        assert!(!main_pts.is_empty());
+        assert_eq!(preview_pts, *main_pts);
+    }
+}
@@ -0,0 +1,934 @@
+//! [`FrameFilter`]s that stabilize shaky video, e.g. handheld or action camera footage.
+//!
+//! Stabilizing a frame needs the camera motion around it: the path of the camera is smoothed
+//! over the frames before and after it, and every frame is moved by the difference between the
+//! smoothed and the actual path. The filters offer two ways to get there:
+//!
+//! - **Single pass**: [`StabilizeFilter`] estimates the motion as the frames arrive and holds
+//!   [`smoothing`](StabilizeFilter::set_smoothing) frames back as a lookahead window before it
+//!   warps and releases a frame. Nothing needs to be prepared, but that many decoded frames are
+//!   kept in memory and the output lags the input by as many frames.
+//! - **Two passes**, like FFmpeg's `vidstabdetect` / `vidstabtransform`: a first job runs
+//!   [`VidStabDetectFilter`], which records the motion of every frame in a shared
+//!   [`MotionTransforms`] list, then a second job over the same input runs
+//!   [`VidStabTransformFilter`] with that list. The whole camera path is known before the first
+//!   frame is warped, so no frame is held back, see [running two passes](#running-two-passes).
+//!   The list can also be shared with other filters of the pipeline through
+//!   [`FramePipeline::set_attribute`](crate::filter::frame_pipeline::FramePipeline::set_attribute).
+//!
+//! Motion is estimated on a luma copy of the frames scaled down to at most 320 pixels wide, by
+//! matching a grid of textured blocks between consecutive frames, and is modelled as a
+//! translation plus a rotation around the center of the picture. Frames are warped on their own
+//! planes with bilinear interpolation, for planar formats of 8 to 16 bits per component
+//! (`yuv420p`, `yuv422p10le`, `gbrp`, ...). The edges uncovered by the warp repeat the
+//! outermost pixels; a few percent of [`zoom`](StabilizeFilter::set_zoom) hide them.
+//!
+//! Hardware frames are passed through untouched.
+//!
+//! # Running two passes
+//!
+//! Two-pass filters, here and in [`loudness_norm_filter`](crate::filter::loudness_norm_filter),
+//! measure the whole input in a first job and hand the result to a second one. The first job
+//! only decodes: its output can be discarded with the `"null"` format. Both jobs must see the
+//! same frames in the same order, so give them the same input options (start time, duration,
+//! frame rate).
+//!
+//! # Example
+//! ```rust,ignore
+//! // single pass
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("stabilize", Box::new(StabilizeFilter::new().set_smoothing(15).set_zoom(5.0)));
+//!
+//! // two passes: detect, then transform
+//! let detect = VidStabDetectFilter::new().set_shakiness(8);
+//! let transforms = detect.transforms();
+//! FfmpegContext::builder()
+//!     .input("gopro.mp4")
+//!     .output(Output::from("-").set_format("null").add_frame_pipeline(
+//!         FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("detect", Box::new(detect)),
+//!     ))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//!
+//! FfmpegContext::builder()
+//!     .input("gopro.mp4")
+//!     .output(Output::from("stable.mp4").add_frame_pipeline(
+//!         FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter(
+//!             "transform",
+//!             Box::new(VidStabTransformFilter::new(transforms).set_smoothing(30).set_zoom(5.0)),
+//!         ),
+//!     ))
+//!     .build()?
+//!     .start()?
+//!     .wait()?;
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_GRAY8;
+use ffmpeg_sys_next::{
+    av_frame_copy_props, av_frame_get_buffer, av_pix_fmt_count_planes, av_pix_fmt_desc_get, AVMediaType,
+    AV_PIX_FMT_FLAG_BAYER, AV_PIX_FMT_FLAG_BE, AV_PIX_FMT_FLAG_BITSTREAM, AV_PIX_FMT_FLAG_HWACCEL, AV_PIX_FMT_FLAG_PAL,
+};
+use log::warn;
+use std::collections::VecDeque;
+use std::ops::{Add, Sub};
+use std::sync::{Arc, Mutex};
+
+/// Largest width of the luma copy the motion is estimated on.
+const ANALYSIS_WIDTH: i32 = 320;
+const BLOCK_SIZE: usize = 16;
+const GRID_COLUMNS: usize = 8;
+const GRID_ROWS: usize = 6;
+/// Lowest mean absolute deviation of a block for it to be matched: flat blocks (sky, walls)
+/// match anywhere.
+const MIN_BLOCK_CONTRAST: f64 = 3.0;
+/// Frames of the camera path kept behind the oldest one still needed.
+const TRAJECTORY_SLACK: usize = 1024;
+
+/// Motion of the picture content from one frame to the next, or a position on the camera path
+/// when accumulated.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameMotion {
+    /// Horizontal shift in pixels, positive to the right.
+    pub dx: f64,
+    /// Vertical shift in pixels, positive downwards.
+    pub dy: f64,
+    /// Rotation around the center of the picture in radians, positive clockwise.
+    pub angle: f64,
+}
+
+impl FrameMotion {
+    fn scale(self, factor: f64) -> FrameMotion {
+        FrameMotion {
+            dx: self.dx * factor,
+            dy: self.dy * factor,
+            angle: self.angle * factor,
+        }
+    }
+}
+
+impl Add for FrameMotion {
+    type Output = FrameMotion;
+
+    fn add(self, other: FrameMotion) -> FrameMotion {
+        FrameMotion {
+            dx: self.dx + other.dx,
+            dy: self.dy + other.dy,
+            angle: self.angle + other.angle,
+        }
+    }
+}
+
+impl Sub for FrameMotion {
+    type Output = FrameMotion;
+
+    fn sub(self, other: FrameMotion) -> FrameMotion {
+        FrameMotion {
+            dx: self.dx - other.dx,
+            dy: self.dy - other.dy,
+            angle: self.angle - other.angle,
+        }
+    }
+}
+
+/// Motion of every frame relative to the previous one, in frame order, as recorded by
+/// [`VidStabDetectFilter`]. The first frame has no motion.
+pub type MotionTransforms = Arc<Mutex<Vec<FrameMotion>>>;
+
+/// Stabilizes video in a single pass, holding back a lookahead window of frames.
+pub struct StabilizeFilter {
+    smoothing: usize,
+    zoom: f64,
+    detector: MotionDetector,
+
+    /// Camera path, `trajectory[0]` being the position of frame `trajectory_start`.
+    trajectory: Vec<FrameMotion>,
+    trajectory_start: usize,
+    /// Frames waiting for their lookahead window, the first one is frame `next_index`.
+    pending: VecDeque<Frame>,
+    next_index: usize,
+    ready: VecDeque<Frame>,
+}
+
+impl StabilizeFilter {
+    /// Creates a filter smoothing the camera path over 10 frames on each side, with a
+    /// shakiness of 5 and no zoom.
+    pub fn new() -> Self {
+        Self {
+            smoothing: 10,
+            zoom: 0.0,
+            detector: MotionDetector::new(5),
+            trajectory: Vec::new(),
+            trajectory_start: 0,
+            pending: VecDeque::new(),
+            next_index: 0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Sets how shaky the footage is, from 1 (little) to 10 (very). Motion between two
+    /// consecutive frames is searched up to `shakiness` percent of the width.
+    pub fn set_shakiness(mut self, shakiness: u8) -> Self {
+        self.detector.shakiness = shakiness;
+        self
+    }
+
+    /// Sets the number of frames on each side the camera path is smoothed over, which is also
+    /// the number of frames held back. Larger values give a steadier picture.
+    pub fn set_smoothing(mut self, smoothing: usize) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Enlarges the picture by `zoom` percent, to hide the edges uncovered by the warp.
+    pub fn set_zoom(mut self, zoom: f64) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    fn warp_next(&mut self) -> Result<(), String> {
+        let Some(frame) = self.pending.pop_front() else {
+            return Ok(());
+        };
+        let index = self.next_index - self.trajectory_start;
+        let offset = correction(&self.trajectory, index, self.smoothing);
+        self.ready.push_back(warp(frame, offset, self.zoom)?);
+        self.next_index += 1;
+
+        // the frames before the smoothing window of the next frame are not needed anymore
+        let needed_from = (self.next_index - self.trajectory_start).saturating_sub(self.smoothing);
+        if needed_from > TRAJECTORY_SLACK {
+            self.trajectory.drain(..needed_from);
+            self.trajectory_start += needed_from;
+        }
+        Ok(())
+    }
+}
+
+impl Default for StabilizeFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for StabilizeFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        check_shakiness(self.detector.shakiness)?;
+        check_zoom(self.zoom)?;
+        if self.smoothing == 0 {
+            return Err("Stabilization needs a smoothing window of at least one frame".to_string());
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        if frame.is_empty() {
+            // end of stream: the last frames are smoothed with what comes before them
+            while !self.pending.is_empty() {
+                self.warp_next()?;
+            }
+            self.ready.push_back(frame);
+            return Ok(self.ready.pop_front());
+        }
+
+        let motion = self.detector.detect(&frame)?;
+        let position = self.trajectory.last().copied().unwrap_or_default() + motion;
+        self.trajectory.push(position);
+        self.pending.push_back(frame);
+        if self.pending.len() > self.smoothing {
+            self.warp_next()?;
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        Ok(self.ready.pop_front())
+    }
+}
+
+/// First pass of the two-pass stabilization: records the motion of every frame and passes
+/// the frames through unchanged.
+pub struct VidStabDetectFilter {
+    detector: MotionDetector,
+    transforms: MotionTransforms,
+}
+
+impl VidStabDetectFilter {
+    /// Creates a filter with a shakiness of 5.
+    pub fn new() -> Self {
+        Self {
+            detector: MotionDetector::new(5),
+            transforms: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Sets how shaky the footage is, from 1 (little) to 10 (very). Motion between two
+    /// consecutive frames is searched up to `shakiness` percent of the width.
+    pub fn set_shakiness(mut self, shakiness: u8) -> Self {
+        self.detector.shakiness = shakiness;
+        self
+    }
+
+    /// Returns a handle to the recorded motion, complete once the job is over. Clone it before
+    /// handing the filter to a pipeline, and pass it to [`VidStabTransformFilter::new`].
+    pub fn transforms(&self) -> MotionTransforms {
+        self.transforms.clone()
+    }
+}
+
+impl Default for VidStabDetectFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for VidStabDetectFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        check_shakiness(self.detector.shakiness)?;
+        self.transforms.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() {
+                return Ok(Some(frame));
+            }
+            if !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                // keep the list aligned with the frame numbers
+                self.transforms.lock().unwrap().push(FrameMotion::default());
+                return Ok(Some(frame));
+            }
+        }
+
+        let motion = self.detector.detect(&frame)?;
+        self.transforms.lock().unwrap().push(motion);
+        Ok(Some(frame))
+    }
+}
+
+/// Second pass of the two-pass stabilization: warps every frame using the motion recorded by
+/// [`VidStabDetectFilter`] in the first pass.
+pub struct VidStabTransformFilter {
+    transforms: MotionTransforms,
+    smoothing: usize,
+    zoom: f64,
+
+    trajectory: Vec<FrameMotion>,
+    index: usize,
+}
+
+impl VidStabTransformFilter {
+    /// Creates a filter warping the frames with `transforms`, smoothing the camera path over
+    /// 10 frames on each side, with no zoom.
+    pub fn new(transforms: MotionTransforms) -> Self {
+        Self {
+            transforms,
+            smoothing: 10,
+            zoom: 0.0,
+            trajectory: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// Sets the number of frames on each side the camera path is smoothed over. No frame is
+    /// held back, so large values (e.g. 30) cost nothing but a steadier picture.
+    pub fn set_smoothing(mut self, smoothing: usize) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Enlarges the picture by `zoom` percent, to hide the edges uncovered by the warp.
+    pub fn set_zoom(mut self, zoom: f64) -> Self {
+        self.zoom = zoom;
+        self
+    }
+}
+
+impl FrameFilter for VidStabTransformFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, ctx: &FrameFilterContext) -> Result<(), String> {
+        check_zoom(self.zoom)?;
+        let transforms = self.transforms.lock().unwrap();
+        if transforms.is_empty() {
+            warn!("[{}] no motion was recorded, run the detection pass first; frames are not stabilized", ctx.name());
+        }
+        self.trajectory = transforms
+            .iter()
+            .scan(FrameMotion::default(), |position, motion| {
+                *position = *position + *motion;
+                Some(*position)
+            })
+            .collect();
+        self.index = 0;
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let index = self.index;
+        self.index += 1;
+        if index >= self.trajectory.len() {
+            if index == self.trajectory.len() && !self.trajectory.is_empty() {
+                warn!(
+                    "[{}] the detection pass recorded {} frames, the following frames are not stabilized",
+                    ctx.name(),
+                    self.trajectory.len()
+                );
+            }
+            return Ok(Some(frame));
+        }
+        unsafe {
+            if !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let offset = correction(&self.trajectory, index, self.smoothing);
+        warp(frame, offset, self.zoom).map(Some)
+    }
+}
+
+fn check_shakiness(shakiness: u8) -> Result<(), String> {
+    if !(1..=10).contains(&shakiness) {
+        return Err(format!("Stabilization shakiness must be between 1 and 10, got {shakiness}"));
+    }
+    Ok(())
+}
+
+fn check_zoom(zoom: f64) -> Result<(), String> {
+    if !(0.0..100.0).contains(&zoom) {
+        return Err(format!("Stabilization zoom must be between 0 and 100 percent, got {zoom}"));
+    }
+    Ok(())
+}
+
+/// Luma copy of a frame, as analyzed.
+struct Luma {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+    /// Size of the frame it was made from.
+    frame_size: (i32, i32),
+}
+
+/// Estimates the motion between consecutive frames.
+struct MotionDetector {
+    shakiness: u8,
+    converter: FrameConverter,
+    previous: Option<Luma>,
+}
+
+impl MotionDetector {
+    fn new(shakiness: u8) -> Self {
+        Self {
+            shakiness,
+            converter: FrameConverter::new(),
+            previous: None,
+        }
+    }
+
+    /// Returns the motion from the previous frame to `frame`, none for the first frame or
+    /// after a change of size.
+    fn detect(&mut self, frame: &Frame) -> Result<FrameMotion, String> {
+        let (width, height) = unsafe { ((*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+        let analysis_width = width.min(ANALYSIS_WIDTH);
+        let analysis_height = ((height as i64 * analysis_width as i64 / width as i64) as i32).max(1);
+        let gray = self.converter.convert(frame, AV_PIX_FMT_GRAY8, analysis_width, analysis_height)?;
+        let luma = Luma {
+            data: gray.plane_u8(0)?.concat(),
+            width: analysis_width as usize,
+            height: analysis_height as usize,
+            frame_size: (width, height),
+        };
+
+        let motion = match self.previous.take() {
+            Some(previous) if previous.frame_size == luma.frame_size => {
+                let range = (luma.width * self.shakiness as usize / 100).max(4);
+                let motion = estimate_motion(&previous.data, &luma.data, luma.width, luma.height, range);
+                FrameMotion {
+                    dx: motion.dx * width as f64 / luma.width as f64,
+                    dy: motion.dy * height as f64 / luma.height as f64,
+                    angle: motion.angle,
+                }
+            }
+            _ => FrameMotion::default(),
+        };
+        self.previous = Some(luma);
+        Ok(motion)
+    }
+}
+
+/// Displacement of a block between two frames, its position relative to the picture center.
+#[derive(Debug, Clone, Copy)]
+struct BlockVector {
+    x: f64,
+    y: f64,
+    dx: f64,
+    dy: f64,
+}
+
+/// Estimates the motion of the content of `previous` to `current`, two `width` x `height` luma
+/// pictures, searching shifts of up to `range` pixels.
+fn estimate_motion(previous: &[u8], current: &[u8], width: usize, height: usize, range: usize) -> FrameMotion {
+    // room around the blocks for the search and the sub-pixel interpolation
+    let margin = range + 1;
+    if width < BLOCK_SIZE + 2 * margin || height < BLOCK_SIZE + 2 * margin {
+        return FrameMotion::default();
+    }
+    let (center_x, center_y) = ((width - 1) as f64 / 2.0, (height - 1) as f64 / 2.0);
+
+    let mut vectors = Vec::with_capacity(GRID_COLUMNS * GRID_ROWS);
+    for row in 0..GRID_ROWS {
+        for column in 0..GRID_COLUMNS {
+            let x = margin + (width - BLOCK_SIZE - 2 * margin) * column / (GRID_COLUMNS - 1);
+            let y = margin + (height - BLOCK_SIZE - 2 * margin) * row / (GRID_ROWS - 1);
+            if block_contrast(previous, width, x, y) < MIN_BLOCK_CONTRAST {
+                continue;
+            }
+            let (dx, dy) = match_block(previous, current, width, x, y, range as isize);
+            vectors.push(BlockVector {
+                x: x as f64 + (BLOCK_SIZE - 1) as f64 / 2.0 - center_x,
+                y: y as f64 + (BLOCK_SIZE - 1) as f64 / 2.0 - center_y,
+                dx,
+                dy,
+            });
+        }
+    }
+    fit_motion(&vectors)
+}
+
+/// Mean absolute deviation of the block at (`x`, `y`).
+fn block_contrast(luma: &[u8], width: usize, x: usize, y: usize) -> f64 {
+    let rows = || (0..BLOCK_SIZE).map(|j| &luma[(y + j) * width + x..][..BLOCK_SIZE]);
+    let count = (BLOCK_SIZE * BLOCK_SIZE) as f64;
+    let mean = rows().flatten().map(|v| *v as f64).sum::<f64>() / count;
+    rows().flatten().map(|v| (*v as f64 - mean).abs()).sum::<f64>() / count
+}
+
+/// Sum of absolute differences between the block of `previous` at (`x`, `y`) and the block of
+/// `current` shifted by (`dx`, `dy`), or any value of at least `limit` once it is reached.
+fn sad(previous: &[u8], current: &[u8], width: usize, (x, y): (usize, usize), dx: isize, dy: isize, limit: u32) -> u32 {
+    let (cx, cy) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+    let mut sum = 0;
+    for j in 0..BLOCK_SIZE {
+        let a = &previous[(y + j) * width + x..][..BLOCK_SIZE];
+        let b = &current[(cy + j) * width + cx..][..BLOCK_SIZE];
+        sum += a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u32).sum::<u32>();
+        if sum >= limit {
+            break;
+        }
+    }
+    sum
+}
+
+/// Finds where the block of `previous` at (`x`, `y`) moved to in `current`, searching every
+/// shift of up to `range` pixels, then interpolating to a fraction of a pixel.
+fn match_block(previous: &[u8], current: &[u8], width: usize, x: usize, y: usize, range: isize) -> (f64, f64) {
+    let cost = |dx: isize, dy: isize, limit: u32| sad(previous, current, width, (x, y), dx, dy, limit);
+
+    let mut best = (0, 0, cost(0, 0, u32::MAX));
+    for dy in -range..=range {
+        for dx in -range..=range {
+            let c = cost(dx, dy, best.2);
+            if c < best.2 {
+                best = (dx, dy, c);
+            }
+        }
+    }
+
+    let (dx, dy, c) = best;
+    let parabola = |before: u32, after: u32| {
+        let curvature = before as f64 - 2.0 * c as f64 + after as f64;
+        if curvature > 0.0 {
+            ((before as f64 - after as f64) / (2.0 * curvature)).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+    (
+        dx as f64 + parabola(cost(dx - 1, dy, u32::MAX), cost(dx + 1, dy, u32::MAX)),
+        dy as f64 + parabola(cost(dx, dy - 1, u32::MAX), cost(dx, dy + 1, u32::MAX)),
+    )
+}
+
+/// Fits a translation plus a small rotation around the center to the block `vectors`,
+/// leaving out the blocks that move on their own (e.g. over a moving subject).
+fn fit_motion(vectors: &[BlockVector]) -> FrameMotion {
+    fn median(mut values: Vec<f64>) -> f64 {
+        values.sort_by(f64::total_cmp);
+        values[values.len() / 2]
+    }
+
+    if vectors.is_empty() {
+        return FrameMotion::default();
+    }
+    // derivative of the displacement with the angle, for a block at (x, y)
+    let turn = |v: &BlockVector| (-v.y, v.x);
+
+    let mut inliers: Vec<BlockVector> = vectors.to_vec();
+    let mut motion = FrameMotion::default();
+    for _ in 0..3 {
+        motion.dx = median(inliers.iter().map(|v| v.dx - motion.angle * turn(v).0).collect());
+        motion.dy = median(inliers.iter().map(|v| v.dy - motion.angle * turn(v).1).collect());
+        let (numerator, denominator) = inliers.iter().fold((0.0, 0.0), |(n, d), v| {
+            let (tx, ty) = turn(v);
+            (n + (v.dx - motion.dx) * tx + (v.dy - motion.dy) * ty, d + tx * tx + ty * ty)
+        });
+        motion.angle = if denominator > 0.0 { numerator / denominator } else { 0.0 };
+
+        let error = |v: &BlockVector| {
+            let (tx, ty) = turn(v);
+            (v.dx - motion.dx - motion.angle * tx).hypot(v.dy - motion.dy - motion.angle * ty)
+        };
+        let threshold = (2.5 * median(inliers.iter().map(error).collect())).max(1.0);
+        let kept: Vec<BlockVector> = vectors.iter().filter(|v| error(*v) <= threshold).copied().collect();
+        if kept.len() < 3 {
+            break;
+        }
+        inliers = kept;
+    }
+    motion
+}
+
+/// Returns how far frame `index` is from the camera path `trajectory` smoothed over `radius`
+/// frames on each side: the frame is stabilized by sampling it that far away.
+///
+/// The path is mirrored past its ends, so that a steady pan is left alone at the start and
+/// the end of the stream too.
+fn correction(trajectory: &[FrameMotion], index: usize, radius: usize) -> FrameMotion {
+    let last = trajectory.len() as isize - 1;
+    let position = |k: isize| {
+        if k < 0 {
+            trajectory[0].scale(2.0) - trajectory[(-k).min(last) as usize]
+        } else if k > last {
+            trajectory[last as usize].scale(2.0) - trajectory[(2 * last - k).max(0) as usize]
+        } else {
+            trajectory[k as usize]
+        }
+    };
+
+    let sigma = (radius as f64 / 2.0).max(0.5);
+    let mut smoothed = FrameMotion::default();
+    let mut total = 0.0;
+    for offset in -(radius as isize)..=radius as isize {
+        let weight = (-(offset * offset) as f64 / (2.0 * sigma * sigma)).exp();
+        smoothed = smoothed + position(index as isize + offset).scale(weight);
+        total += weight;
+    }
+    trajectory[index] - smoothed.scale(1.0 / total)
+}
+
+/// Moves `frame` by `offset` and enlarges it by `zoom` percent.
+fn warp(frame: Frame, offset: FrameMotion, zoom: f64) -> Result<Frame, String> {
+    if offset == FrameMotion::default() && zoom == 0.0 {
+        return Ok(frame);
+    }
+    unsafe { warp_frame(&frame, offset, 1.0 + zoom / 100.0) }
+}
+
+/// Returns a copy of `src` whose pixel at `p` is the pixel of `src` at
+/// `center + R(offset.angle) (p - center) / zoom + (offset.dx, offset.dy)`.
+unsafe fn warp_frame(src: &Frame, offset: FrameMotion, zoom: f64) -> Result<Frame, String> {
+    let s = src.as_ptr();
+    let (format, width, height) = ((*s).format, (*s).width, (*s).height);
+    let pix_fmt = pixel_format(format).ok_or_else(|| format!("Unknown pixel format {format}"))?;
+    let desc = av_pix_fmt_desc_get(pix_fmt);
+    if desc.is_null() {
+        return Err(format!("Unknown pixel format {format}"));
+    }
+    let unsupported = (AV_PIX_FMT_FLAG_PAL
+        | AV_PIX_FMT_FLAG_BITSTREAM
+        | AV_PIX_FMT_FLAG_HWACCEL
+        | AV_PIX_FMT_FLAG_BAYER
+        | AV_PIX_FMT_FLAG_BE) as u64;
+    let nb_planes = av_pix_fmt_count_planes(pix_fmt);
+    let components = &(*desc).comp[..(*desc).nb_components as usize];
+    // one component per plane, stored in one or two bytes
+    let planar = components.iter().all(|comp| {
+        comp.step == (comp.depth + 7) / 8
+            && comp.step <= 2
+            && components.iter().filter(|other| other.plane == comp.plane).count() == 1
+    });
+    if (*desc).flags & unsupported != 0 || nb_planes <= 0 || !planar {
+        return Err(format!(
+            "Stabilization does not support pixel format {format}, convert the frames to a planar format such as yuv420p"
+        ));
+    }
+    let (log2_chroma_w, log2_chroma_h) = ((*desc).log2_chroma_w, (*desc).log2_chroma_h);
+
+    let mut output = Frame::empty();
+    if output.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let dst = output.as_mut_ptr();
+    (*dst).format = format;
+    (*dst).width = width;
+    (*dst).height = height;
+    let ret = av_frame_get_buffer(dst, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+    let ret = av_frame_copy_props(dst, s);
+    if ret < 0 {
+        return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+    }
+
+    for comp in components {
+        let plane = comp.plane as usize;
+        let is_chroma = components.len() >= 3
+            && (components[1].plane as usize == plane || components[2].plane as usize == plane);
+        let (shift_w, shift_h) = if is_chroma { (log2_chroma_w, log2_chroma_h) } else { (0, 0) };
+        let src_plane = Plane {
+            data: (*s).data[plane],
+            linesize: (*s).linesize[plane] as isize,
+            // AV_CEIL_RSHIFT
+            width: (-((-width) >> shift_w)) as usize,
+            height: (-((-height) >> shift_h)) as usize,
+        };
+        let dst_plane = Plane {
+            data: (*dst).data[plane],
+            linesize: (*dst).linesize[plane] as isize,
+            ..src_plane
+        };
+        let affine = Affine::new(offset, zoom, width, height, (1 << shift_w) as f64, (1 << shift_h) as f64);
+        if comp.step == 1 {
+            warp_plane::<u8>(&src_plane, &dst_plane, &affine);
+        } else {
+            warp_plane::<u16>(&src_plane, &dst_plane, &affine);
+        }
+    }
+    Ok(output)
+}
+
+struct Plane {
+    data: *mut u8,
+    linesize: isize,
+    width: usize,
+    height: usize,
+}
+
+/// Maps a pixel of a plane to the position it is sampled from: `a * (x, y) + b`.
+#[derive(Debug)]
+struct Affine {
+    a: [f64; 4],
+    b: [f64; 2],
+}
+
+impl Affine {
+    /// Mapping of a plane subsampled by (`scale_x`, `scale_y`) of a `width` x `height` picture.
+    fn new(offset: FrameMotion, zoom: f64, width: i32, height: i32, scale_x: f64, scale_y: f64) -> Affine {
+        let (sin, cos) = offset.angle.sin_cos();
+        let (m00, m01, m10, m11) = (cos / zoom, -sin / zoom, sin / zoom, cos / zoom);
+        let (center_x, center_y) = ((width - 1) as f64 / 2.0, (height - 1) as f64 / 2.0);
+        let b_x = center_x + offset.dx - (m00 * center_x + m01 * center_y);
+        let b_y = center_y + offset.dy - (m10 * center_x + m11 * center_y);
+        Affine {
+            a: [m00, m01 * scale_y / scale_x, m10 * scale_x / scale_y, m11],
+            b: [b_x / scale_x, b_y / scale_y],
+        }
+    }
+}
+
+trait Sample: Copy {
+    fn to_f32(self) -> f32;
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Sample for u8 {
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round() as u8
+    }
+}
+
+impl Sample for u16 {
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round() as u16
+    }
+}
+
+/// Fills `dst` with `src` sampled through `affine`, bilinearly, repeating the edges of `src`.
+unsafe fn warp_plane<T: Sample>(src: &Plane, dst: &Plane, affine: &Affine) {
+    let pixel = |x: usize, y: usize| (src.data.offset(y as isize * src.linesize) as *const T).add(x).read_unaligned();
+    let (max_x, max_y) = ((src.width - 1) as f64, (src.height - 1) as f64);
+    for y in 0..dst.height {
+        let row = dst.data.offset(y as isize * dst.linesize) as *mut T;
+        for x in 0..dst.width {
+            let sx = (affine.a[0] * x as f64 + affine.a[1] * y as f64 + affine.b[0]).clamp(0.0, max_x);
+            let sy = (affine.a[2] * x as f64 + affine.a[3] * y as f64 + affine.b[1]).clamp(0.0, max_y);
+            let (x0, y0) = (sx as usize, sy as usize);
+            let (x1, y1) = ((x0 + 1).min(src.width - 1), (y0 + 1).min(src.height - 1));
+            let (fx, fy) = ((sx - x0 as f64) as f32, (sy - y0 as f64) as f32);
+            let top = pixel(x0, y0).to_f32() * (1.0 - fx) + pixel(x1, y0).to_f32() * fx;
+            let bottom = pixel(x0, y1).to_f32() * (1.0 - fx) + pixel(x1, y1).to_f32() * fx;
+            row.add(x).write_unaligned(T::from_f32(top * (1.0 - fy) + bottom * fy));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::output::Output;
+    use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+    use crate::FfmpegContext;
+
+    /// Deterministic texture, defined for any integer position.
+    fn texture(x: i64, y: i64) -> u8 {
+        let mut h = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)) as u64;
+        h ^= h >> 13;
+        h = h.wrapping_mul(0x5bd1_e995);
+        (h >> 24) as u8
+    }
+
+    #[test]
+    fn test_estimate_motion() {
+        let (width, height) = (160, 120);
+        let picture = |shift_x: i64, shift_y: i64| {
+            (0..height as i64)
+                .flat_map(|y| (0..width as i64).map(move |x| texture(x - shift_x, y - shift_y)))
+                .collect::<Vec<u8>>()
+        };
+        let motion = estimate_motion(&picture(0, 0), &picture(3, -2), width, height, 8);
+        assert!((motion.dx - 3.0).abs() < 0.25, "{motion:?}");
+        assert!((motion.dy + 2.0).abs() < 0.25, "{motion:?}");
+        assert!(motion.angle.abs() < 0.002, "{motion:?}");
+
+        // a flat picture has nothing to match
+        let flat = vec![128u8; width * height];
+        assert_eq!(estimate_motion(&flat, &flat, width, height, 8), FrameMotion::default());
+    }
+
+    #[test]
+    fn test_fit_motion() {
+        let (dx, dy, angle) = (2.0, 1.0, 0.02);
+        let mut vectors: Vec<BlockVector> = (-3..=3)
+            .flat_map(|j| (-3..=3).map(move |i| (i as f64 * 20.0, j as f64 * 15.0)))
+            .map(|(x, y)| BlockVector { x, y, dx: dx - angle * y, dy: dy + angle * x })
+            .collect();
+        // a subject moving on its own
+        vectors[10].dx = 20.0;
+        vectors[11].dy = -15.0;
+
+        let motion = fit_motion(&vectors);
+        assert!((motion.dx - dx).abs() < 1e-6, "{motion:?}");
+        assert!((motion.dy - dy).abs() < 1e-6, "{motion:?}");
+        assert!((motion.angle - angle).abs() < 1e-6, "{motion:?}");
+    }
+
+    #[test]
+    fn test_correction() {
+        // a steady pan is left alone, up to both ends
+        let pan: Vec<FrameMotion> = (0..30).map(|i| FrameMotion { dx: i as f64 * 2.0, ..Default::default() }).collect();
+        for index in 0..pan.len() {
+            assert!(correction(&pan, index, 5).dx.abs() < 1e-9);
+        }
+
+        // shaking around the pan is removed
+        let shaky: Vec<FrameMotion> = pan
+            .iter()
+            .enumerate()
+            .map(|(i, p)| FrameMotion { dy: if i % 2 == 0 { 3.0 } else { -3.0 }, ..*p })
+            .collect();
+        let offset = correction(&shaky, 15, 8);
+        assert!(offset.dx.abs() < 1e-9);
+        assert!((offset.dy + 3.0).abs() < 0.1, "{offset:?}");
+    }
+
+    #[test]
+    fn test_warp_plane() {
+        // 4x2 source, sampled one pixel to the right: the last column repeats
+        let mut src = vec![1u8, 2, 3, 4, 5, 6, 7, 8];
+        let mut dst = vec![0u8; 8];
+        let src_plane = Plane { data: src.as_mut_ptr(), linesize: 4, width: 4, height: 2 };
+        let dst_plane = Plane { data: dst.as_mut_ptr(), linesize: 4, width: 4, height: 2 };
+        let offset = FrameMotion { dx: 1.0, ..Default::default() };
+        let affine = Affine::new(offset, 1.0, 4, 2, 1.0, 1.0);
+        unsafe { warp_plane::<u8>(&src_plane, &dst_plane, &affine) };
+        assert_eq!(dst, vec![2, 3, 4, 4, 6, 7, 8, 8]);
+
+        // half a pixel down, on a half-height chroma plane of a 4x4 picture
+        let offset = FrameMotion { dy: 1.0, ..Default::default() };
+        let affine = Affine::new(offset, 1.0, 4, 4, 1.0, 2.0);
+        unsafe { warp_plane::<u8>(&src_plane, &dst_plane, &affine) };
+        assert_eq!(dst, vec![3, 4, 5, 6, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_stabilize() {
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("stabilize", Box::new(StabilizeFilter::new().set_smoothing(5).set_zoom(4.0)));
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output_stabilize.mp4").set_recording_time_us(2_000_000).add_frame_pipeline(pipeline))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok());
+
+        let detect = VidStabDetectFilter::new();
+        let transforms = detect.transforms();
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("detect", Box::new(detect));
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("-").set_format("null").set_recording_time_us(2_000_000).add_frame_pipeline(pipeline))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok());
+        assert!(!transforms.lock().unwrap().is_empty());
+
+        let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("transform", Box::new(VidStabTransformFilter::new(transforms).set_smoothing(20)));
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output_stabilize.mp4").set_recording_time_us(2_000_000).add_frame_pipeline(pipeline))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok());
+        let _ = std::fs::remove_file("output_stabilize.mp4");
+    }
+}
@@ -0,0 +1,472 @@
+//! A [`FrameFilter`] that fills gaps in a live source with a test pattern or a tone, e.g. a
+//! "technical difficulties" slate while a camera or an encoder feed is down.
+//!
+//! Real frames pass through unchanged. When no frame has arrived for the configured timeout,
+//! [`request_frame`](FrameFilter::request_frame) starts generating frames in real time: SMPTE
+//! color bars (or another [`TestPattern`]) for video, a sine tone for audio. Generated frames
+//! have the format, size, sample rate and channel layout of the last real frame, and their
+//! timestamps continue from it, so the encoder sees one uninterrupted stream. When the source
+//! comes back with timestamps that would go backwards, its frames are shifted after the last
+//! generated frame.
+//!
+//! Nothing is generated before the first real frame (the output format is unknown yet), for
+//! hardware frames, or after the end of the stream.
+//!
+//! # Example
+//! ```rust,ignore
+//! let video = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("slate", Box::new(
+//!         TestSourceFilter::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!             .set_timeout(Duration::from_millis(500)),
+//!     ));
+//! let audio = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO)
+//!     .filter("tone", Box::new(
+//!         TestSourceFilter::new(AVMediaType::AVMEDIA_TYPE_AUDIO).set_tone_frequency(1000.0),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{AudioSample, FramePlanes};
+use crate::util::ffmpeg_utils::{av_err2str, sample_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGB24;
+use ffmpeg_sys_next::AVSampleFormat::*;
+use ffmpeg_sys_next::{
+    av_channel_layout_copy, av_frame_copy_props, av_frame_get_buffer, av_frame_ref, av_get_packed_sample_fmt,
+    av_inv_q, av_mul_q, av_q2d, av_rescale_q, av_sample_fmt_is_planar, AVMediaType, AVRational, AV_NOPTS_VALUE,
+};
+use std::time::{Duration, Instant};
+
+/// Peak amplitude of the generated tone, -20 dBFS.
+const TONE_AMPLITUDE: f64 = 0.1;
+
+/// The picture generated by a video [`TestSourceFilter`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestPattern {
+    /// SMPTE color bars: seven 75% bars, the reverse blue bars, and the `-I`/white/`+Q`
+    /// and PLUGE row at the bottom.
+    SmpteBars,
+    /// Seven full-height 75% color bars.
+    ColorBars,
+    /// A single RGB color, e.g. `Solid(0, 0, 0)` for black.
+    Solid(u8, u8, u8),
+}
+
+pub struct TestSourceFilter {
+    media_type: AVMediaType,
+    pattern: TestPattern,
+    tone_frequency: f64,
+    frame_rate: Option<AVRational>,
+    timeout: Duration,
+
+    // properties (no data) of the last real frame
+    template: Option<Frame>,
+    last_arrival: Option<Instant>,
+    // timestamp following the last real frame, where generated frames start
+    gap_pts: i64,
+    // frames (video) or samples (audio) generated since the last real frame
+    generated: u64,
+    next_pts: i64,
+    pts_offset: i64,
+    eof: bool,
+    warned: bool,
+
+    slate: Option<Frame>,
+    slate_key: (i32, i32, i32),
+    converter: FrameConverter,
+}
+
+impl TestSourceFilter {
+    /// Creates a filter for `media_type` (video or audio) generating SMPTE bars or a 1 kHz
+    /// tone after 500 ms without source frames.
+    pub fn new(media_type: AVMediaType) -> Self {
+        Self {
+            media_type,
+            pattern: TestPattern::SmpteBars,
+            tone_frequency: 1000.0,
+            frame_rate: None,
+            timeout: Duration::from_millis(500),
+            template: None,
+            last_arrival: None,
+            gap_pts: 0,
+            generated: 0,
+            next_pts: AV_NOPTS_VALUE,
+            pts_offset: 0,
+            eof: false,
+            warned: false,
+            slate: None,
+            slate_key: (0, 0, 0),
+            converter: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the picture generated for video (default [`TestPattern::SmpteBars`]).
+    pub fn set_pattern(mut self, pattern: TestPattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets the frequency in Hz of the generated audio tone (default `1000.0`).
+    pub fn set_tone_frequency(mut self, frequency: f64) -> Self {
+        self.tone_frequency = frequency;
+        self
+    }
+
+    /// Sets the rate of the generated video frames. By default the duration of the last real
+    /// frame is used, or 25 fps if it has none. Audio frames always have the size of the last
+    /// real audio frame.
+    pub fn set_frame_rate(mut self, frame_rate: AVRational) -> Self {
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    /// Sets how long the source may stay silent before frames are generated (default 500 ms).
+    /// The generated frames also cover the timeout, so the stream has no gap.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Records the properties of a real frame and where generated frames would start.
+    unsafe fn update_template(&mut self, frame: &Frame) -> Result<(), String> {
+        let src = frame.as_ptr();
+        let mut template = Frame::empty();
+        if template.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let dst = template.as_mut_ptr();
+        let ret = av_frame_copy_props(dst, src);
+        if ret < 0 {
+            return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+        }
+        (*dst).format = (*src).format;
+        (*dst).width = (*src).width;
+        (*dst).height = (*src).height;
+        (*dst).sample_rate = (*src).sample_rate;
+        (*dst).nb_samples = (*src).nb_samples;
+        let ret = av_channel_layout_copy(&mut (*dst).ch_layout, &(*src).ch_layout);
+        if ret < 0 {
+            return Err(format!("Failed to copy channel layout: {}", av_err2str(ret)));
+        }
+
+        let time_base = (*src).time_base;
+        if (*src).pts != AV_NOPTS_VALUE && time_base.num > 0 && time_base.den > 0 {
+            let duration = if self.media_type == AVMediaType::AVMEDIA_TYPE_AUDIO && (*src).sample_rate > 0 {
+                av_rescale_q((*src).nb_samples as i64, AVRational { num: 1, den: (*src).sample_rate }, time_base)
+            } else if (*src).duration > 0 {
+                (*src).duration
+            } else {
+                av_rescale_q(1, self.frame_period(&template), time_base).max(1)
+            };
+            self.gap_pts = (*src).pts + duration;
+            self.next_pts = self.gap_pts;
+        }
+        self.template = Some(template);
+        Ok(())
+    }
+
+    /// Seconds of media per generated video frame.
+    unsafe fn frame_period(&self, template: &Frame) -> AVRational {
+        let (duration, time_base) = ((*template.as_ptr()).duration, (*template.as_ptr()).time_base);
+        match self.frame_rate {
+            Some(frame_rate) if frame_rate.num > 0 && frame_rate.den > 0 => av_inv_q(frame_rate),
+            _ if duration > 0 && duration <= i32::MAX as i64 => av_mul_q(AVRational { num: duration as i32, den: 1 }, time_base),
+            _ => AVRational { num: 1, den: 25 },
+        }
+    }
+
+    /// Generates the next frame if the source has been silent long enough and the generated
+    /// frames have not caught up with the wall clock yet.
+    unsafe fn generate(&mut self) -> Result<Option<Frame>, String> {
+        let (Some(template), Some(last_arrival)) = (&self.template, self.last_arrival) else {
+            return Ok(None);
+        };
+        let elapsed = last_arrival.elapsed();
+        if self.eof || elapsed < self.timeout {
+            return Ok(None);
+        }
+
+        let t = template.as_ptr();
+        let time_base = (*t).time_base;
+        if self.next_pts == AV_NOPTS_VALUE || time_base.num <= 0 || time_base.den <= 0 || !(*t).hw_frames_ctx.is_null() {
+            if !self.warned {
+                log::warn!("Test source can not continue frames without timestamps or in hardware memory.");
+                self.warned = true;
+            }
+            return Ok(None);
+        }
+
+        let first = self.generated == 0;
+        let (mut frame, pts, duration) = if self.media_type == AVMediaType::AVMEDIA_TYPE_AUDIO {
+            let sample_rate = (*t).sample_rate;
+            let nb_samples = if (*t).nb_samples > 0 { (*t).nb_samples } else { 1024 };
+            if sample_rate <= 0 {
+                return Ok(None);
+            }
+            let samples_tb = AVRational { num: 1, den: sample_rate };
+            let media_time = self.generated as f64 / sample_rate as f64;
+            if media_time > elapsed.as_secs_f64() {
+                return Ok(None);
+            }
+            let frame = tone_frame(template, nb_samples, self.generated, self.tone_frequency)?;
+            let pts = self.gap_pts + av_rescale_q(self.generated as i64, samples_tb, time_base);
+            self.generated += nb_samples as u64;
+            (frame, pts, av_rescale_q(nb_samples as i64, samples_tb, time_base))
+        } else {
+            let period = self.frame_period(template);
+            let media_time = self.generated as f64 * av_q2d(period);
+            if media_time > elapsed.as_secs_f64() {
+                return Ok(None);
+            }
+            let pts = self.gap_pts + av_rescale_q(self.generated as i64, period, time_base);
+            let frame = self.slate_frame()?;
+            self.generated += 1;
+            (frame, pts, av_rescale_q(1, period, time_base))
+        };
+
+        if first {
+            log::warn!("No frames from the source for {elapsed:?}, generating test frames.");
+        }
+
+        let f = frame.as_mut_ptr();
+        // a previous generated frame may have been longer than its successor's offset
+        (*f).pts = pts.max(self.next_pts);
+        (*f).duration = duration;
+        (*f).time_base = time_base;
+        self.next_pts = (*f).pts + duration.max(1);
+        Ok(Some(frame))
+    }
+
+    /// Returns a new reference to the slate in the format and size of the last real frame,
+    /// rendering it again when they changed.
+    unsafe fn slate_frame(&mut self) -> Result<Frame, String> {
+        let template = self.template.as_ref().unwrap();
+        let t = template.as_ptr();
+        let key = ((*t).format, (*t).width, (*t).height);
+        if self.slate.is_none() || self.slate_key != key {
+            let (width, height) = (key.1.max(1) as usize, key.2.max(1) as usize);
+            let mut rgb = new_video_frame(AV_PIX_FMT_RGB24 as i32, width as i32, height as i32)?;
+            for (y, row) in rgb.plane_u8_mut(0)?.into_iter().enumerate() {
+                for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                    pixel.copy_from_slice(&pattern_color(self.pattern, x, y, width, height));
+                }
+            }
+
+            let mut slate = new_video_frame(key.0, key.1, key.2)?;
+            self.converter.convert_into(&rgb, &mut slate)?;
+            let ret = av_frame_copy_props(slate.as_mut_ptr(), t);
+            if ret < 0 {
+                return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+            }
+            self.slate = Some(slate);
+            self.slate_key = key;
+        }
+
+        let mut frame = Frame::empty();
+        if frame.as_ptr().is_null() {
+            return Err("Failed to create frame: Out of memory.".to_string());
+        }
+        let ret = av_frame_ref(frame.as_mut_ptr(), self.slate.as_ref().unwrap().as_ptr());
+        if ret < 0 {
+            return Err(format!("Failed to reference slate frame: {}", av_err2str(ret)));
+        }
+        Ok(frame)
+    }
+}
+
+impl FrameFilter for TestSourceFilter {
+    fn media_type(&self) -> AVMediaType {
+        self.media_type
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        match self.media_type {
+            AVMediaType::AVMEDIA_TYPE_VIDEO => Ok(()),
+            AVMediaType::AVMEDIA_TYPE_AUDIO if self.tone_frequency.is_finite() && self.tone_frequency >= 0.0 => Ok(()),
+            AVMediaType::AVMEDIA_TYPE_AUDIO => Err(format!("Invalid tone frequency {}", self.tone_frequency)),
+            media_type => Err(format!("Test source does not support media type {media_type:?}")),
+        }
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() {
+                return Ok(Some(frame));
+            }
+            if frame.is_empty() {
+                self.eof = true;
+                return Ok(Some(frame));
+            }
+
+            let f = frame.as_mut_ptr();
+            if (*f).pts != AV_NOPTS_VALUE {
+                // the source came back with timestamps behind the generated frames
+                if self.generated > 0 && self.next_pts != AV_NOPTS_VALUE && (*f).pts + self.pts_offset < self.next_pts {
+                    self.pts_offset = self.next_pts - (*f).pts;
+                    log::info!("Source resumed, shifting its timestamps by {}.", self.pts_offset);
+                }
+                (*f).pts += self.pts_offset;
+            }
+            if self.generated > 0 {
+                log::info!("Source resumed after {} generated {}.", self.generated,
+                    if self.media_type == AVMediaType::AVMEDIA_TYPE_AUDIO { "samples" } else { "frames" });
+            }
+
+            self.update_template(&frame)?;
+            self.last_arrival = Some(Instant::now());
+            self.generated = 0;
+        }
+        Ok(Some(frame))
+    }
+
+    fn request_frame(&mut self, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe { self.generate() }
+    }
+}
+
+unsafe fn new_video_frame(format: i32, width: i32, height: i32) -> Result<Frame, String> {
+    let mut frame = Frame::empty();
+    if frame.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let f = frame.as_mut_ptr();
+    (*f).format = format;
+    (*f).width = width;
+    (*f).height = height;
+    let ret = av_frame_get_buffer(f, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+    Ok(frame)
+}
+
+/// Creates an audio frame like `template` holding `nb_samples` samples of the tone, starting
+/// at sample number `first_sample` so consecutive frames continue the same sine wave.
+unsafe fn tone_frame(template: &Frame, nb_samples: i32, first_sample: u64, frequency: f64) -> Result<Frame, String> {
+    let t = template.as_ptr();
+    let mut frame = Frame::empty();
+    if frame.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let f = frame.as_mut_ptr();
+    let ret = av_frame_copy_props(f, t);
+    if ret < 0 {
+        return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+    }
+    (*f).format = (*t).format;
+    (*f).sample_rate = (*t).sample_rate;
+    (*f).nb_samples = nb_samples;
+    let ret = av_channel_layout_copy(&mut (*f).ch_layout, &(*t).ch_layout);
+    if ret < 0 {
+        return Err(format!("Failed to copy channel layout: {}", av_err2str(ret)));
+    }
+    let ret = av_frame_get_buffer(f, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+
+    let format = sample_format((*f).format).ok_or_else(|| format!("Unknown sample format {}", (*f).format))?;
+    let channels = (*f).ch_layout.nb_channels.max(1) as usize;
+    // a planar frame has a plane per channel, a packed frame the channels interleaved in one
+    let (planes, interleaved) = if av_sample_fmt_is_planar(format) != 0 { (channels, 1) } else { (1, channels) };
+    let sample_rate = (*f).sample_rate;
+    let tone = |i: usize| tone_value(first_sample + i as u64, sample_rate, frequency);
+    for plane in 0..planes {
+        match av_get_packed_sample_fmt(format) {
+            AV_SAMPLE_FMT_U8 => store_tone(frame.samples_mut::<u8>(plane)?, interleaved, &tone),
+            AV_SAMPLE_FMT_S16 => store_tone(frame.samples_mut::<i16>(plane)?, interleaved, &tone),
+            AV_SAMPLE_FMT_S32 => store_tone(frame.samples_mut::<i32>(plane)?, interleaved, &tone),
+            AV_SAMPLE_FMT_S64 => store_tone(frame.samples_mut::<i64>(plane)?, interleaved, &tone),
+            AV_SAMPLE_FMT_FLT => store_tone(frame.samples_mut::<f32>(plane)?, interleaved, &tone),
+            AV_SAMPLE_FMT_DBL => store_tone(frame.samples_mut::<f64>(plane)?, interleaved, &tone),
+            _ => return Err(format!("Test source does not support sample format {format:?}")),
+        }
+    }
+    Ok(frame)
+}
+
+fn tone_value(sample: u64, sample_rate: i32, frequency: f64) -> f64 {
+    let phase = (sample as f64 * frequency / sample_rate as f64).fract();
+    TONE_AMPLITUDE * (2.0 * std::f64::consts::PI * phase).sin()
+}
+
+/// Writes sample number `i` of the tone, `tone(i)`, into every channel of `samples`, which
+/// interleave `channels` channels.
+fn store_tone<T: AudioSample>(samples: &mut [T], channels: usize, tone: &dyn Fn(usize) -> f64) {
+    for (i, sample) in samples.chunks_mut(channels).enumerate() {
+        sample.fill(T::from_f64(tone(i)));
+    }
+}
+
+/// Returns the RGB color of pixel (`x`, `y`) of `pattern` in a `width` x `height` picture.
+fn pattern_color(pattern: TestPattern, x: usize, y: usize, width: usize, height: usize) -> [u8; 3] {
+    // 75% bars: gray, yellow, cyan, green, magenta, red, blue
+    const BARS: [[u8; 3]; 7] = [
+        [191, 191, 191], [191, 191, 0], [0, 191, 191], [0, 191, 0], [191, 0, 191], [191, 0, 0], [0, 0, 191],
+    ];
+    const BLACK: [u8; 3] = [19, 19, 19];
+    let bar = (x * 7 / width).min(6);
+
+    match pattern {
+        TestPattern::Solid(r, g, b) => [r, g, b],
+        TestPattern::ColorBars => BARS[bar],
+        TestPattern::SmpteBars if y * 3 < height * 2 => BARS[bar],
+        // reverse bars: blue, black, magenta, black, cyan, black, gray
+        TestPattern::SmpteBars if y * 4 < height * 3 => {
+            if bar % 2 == 0 {
+                BARS[6 - bar]
+            } else {
+                BLACK
+            }
+        }
+        // -I, white, +Q and black blocks 5/4 of a bar wide, then the PLUGE
+        // (super black, black, +4% gray) in the sixth bar
+        TestPattern::SmpteBars => match x * 28 / width {
+            0..=4 => [0, 33, 76],
+            5..=9 => [255, 255, 255],
+            10..=14 => [50, 0, 106],
+            15..=19 => BLACK,
+            _ => match x * 21 / width {
+                15 => [9, 9, 9],
+                16 => BLACK,
+                17 => [29, 29, 29],
+                _ => BLACK,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_color() {
+        // top bars
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 0, 0, 700, 480), [191, 191, 191]);
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 650, 0, 700, 480), [0, 0, 191]);
+        // reverse bars
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 0, 330, 700, 480), [0, 0, 191]);
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 150, 330, 700, 480), [19, 19, 19]);
+        // bottom row
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 0, 470, 700, 480), [0, 33, 76]);
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 150, 470, 700, 480), [255, 255, 255]);
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 510, 470, 700, 480), [9, 9, 9]);
+        assert_eq!(pattern_color(TestPattern::SmpteBars, 580, 470, 700, 480), [29, 29, 29]);
+        assert_eq!(pattern_color(TestPattern::ColorBars, 350, 470, 700, 480), [0, 191, 0]);
+    }
+
+    #[test]
+    fn test_store_tone_samples() {
+        // 1 kHz at 8 kHz: a period is 8 samples, with the peak at sample 2
+        assert!((tone_value(2, 8000, 1000.0) - TONE_AMPLITUDE).abs() < 1e-9);
+        assert!((tone_value(8002, 8000, 1000.0) - TONE_AMPLITUDE).abs() < 1e-9);
+
+        // two interleaved channels
+        let mut s16: Vec<i16> = vec![0; 8];
+        store_tone(&mut s16, 2, &|i| tone_value(i as u64 + 4, 8000, 1000.0));
+        assert_eq!(s16, vec![0, 0, -2317, -2317, -3277, -3277, -2317, -2317]);
+    }
+}
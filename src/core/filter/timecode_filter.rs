@@ -0,0 +1,336 @@
+//! A [`FrameFilter`] that burns a SMPTE style `HH:MM:SS:FF` timecode into video frames.
+//!
+//! The timecode is derived from each frame's PTS and the video frame rate, and is drawn
+//! with a small bundled bitmap font, so no fontconfig/freetype (`drawtext`) is required.
+//!
+//! The frame rate is the one of the stream, see
+//! [`FrameFilterContext::frame_rate`], or else is derived from the frame duration. Only the
+//! pixels of the timecode box are converted to RGBA and back, the rest of the frame is left
+//! untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("timecode", Box::new(
+//!         TimecodeFilter::new(Corner::TopLeft)
+//!             .set_start_timecode("01:00:00:00"),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_converter::{align_region, FrameConverter};
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::core::filter::logo_overlay_filter::Corner;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGBA;
+use ffmpeg_sys_next::{AVMediaType, AVRational, AV_NOPTS_VALUE};
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// 5x7 glyphs for `0`-`9`, `:` and `;`, one byte per row, most significant bit on the left.
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 12] = [
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+];
+
+pub struct TimecodeFilter {
+    corner: Corner,
+    margin: u32,
+    start_timecode: Option<String>,
+    drop_frame: Option<bool>,
+
+    start_frames: u64,
+    converter_in: FrameConverter,
+    converter_out: FrameConverter,
+}
+
+impl TimecodeFilter {
+    /// Creates a filter drawing the timecode in the given `corner`, starting at `00:00:00:00`.
+    pub fn new(corner: Corner) -> Self {
+        Self {
+            corner,
+            margin: 16,
+            start_timecode: None,
+            drop_frame: None,
+            start_frames: 0,
+            converter_in: FrameConverter::new(),
+            converter_out: FrameConverter::new(),
+        }
+    }
+
+    /// Sets the distance, in pixels, between the timecode box and the frame edges.
+    pub fn set_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Sets the timecode of the first frame (PTS 0), e.g. `"01:00:00:00"`.
+    /// Both `:` and `;` are accepted as the frame separator.
+    pub fn set_start_timecode(mut self, timecode: impl Into<String>) -> Self {
+        self.start_timecode = Some(timecode.into());
+        self
+    }
+
+    /// Forces drop-frame (`true`) or non-drop-frame (`false`) counting.
+    ///
+    /// By default drop-frame is used for the NTSC rates 29.97 and 59.94 fps, and
+    /// non-drop-frame for every other rate.
+    pub fn set_drop_frame(mut self, drop_frame: bool) -> Self {
+        self.drop_frame = Some(drop_frame);
+        self
+    }
+}
+
+impl FrameFilter for TimecodeFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+            if (*frame.as_ptr()).pts == AV_NOPTS_VALUE {
+                return Ok(Some(frame));
+            }
+        }
+
+        let framerate = match ctx.frame_rate() {
+            Some(framerate) => framerate,
+            None => frame_rate_from_duration(&frame).ok_or("Unable to determine the frame rate of the stream")?,
+        };
+        let fps = framerate.num as f64 / framerate.den as f64;
+        let drop_frame = self.drop_frame.unwrap_or(framerate.den == 1001 && is_drop_frame_rate(fps));
+
+        if let Some(timecode) = self.start_timecode.take() {
+            self.start_frames = parse_timecode(&timecode, fps, drop_frame)?;
+        }
+
+        let (pts, time_base) = unsafe { ((*frame.as_ptr()).pts, (*frame.as_ptr()).time_base) };
+        if time_base.num <= 0 || time_base.den <= 0 {
+            return Ok(Some(frame));
+        }
+        let seconds = pts as f64 * time_base.num as f64 / time_base.den as f64;
+        let frame_number = (seconds * fps).round().max(0.0) as u64 + self.start_frames;
+        let text = format_timecode(frame_number, fps, drop_frame);
+
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        let scale = (height / 180).max(1);
+        let padding = 2 * scale;
+        let box_width = text.len() * (GLYPH_WIDTH + 1) * scale - scale + 2 * padding;
+        let box_height = GLYPH_HEIGHT * scale + 2 * padding;
+        let margin = self.margin as usize;
+        if box_width + margin > width || box_height + margin > height {
+            return Ok(Some(frame));
+        }
+        let x0 = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => margin,
+            Corner::TopRight | Corner::BottomRight => width - margin - box_width,
+        };
+        let y0 = match self.corner {
+            Corner::TopLeft | Corner::TopRight => margin,
+            Corner::BottomLeft | Corner::BottomRight => height - margin - box_height,
+        };
+
+        // only the pixels of the box go through RGBA
+        let region = align_region(&frame, (x0, y0, box_width, box_height))?;
+        let (dx, dy) = (x0 - region.0, y0 - region.1);
+        let rgba = self.converter_in.convert_region(&frame, AV_PIX_FMT_RGBA, region)?;
+        for (y, row) in rgba.plane_u8_mut(0)?[dy..dy + box_height].iter_mut().enumerate() {
+            for x in 0..box_width {
+                let lit = glyph_pixel(&text, scale, x as isize - padding as isize, y as isize - padding as isize);
+                let dst = &mut row[(dx + x) * 4..(dx + x) * 4 + 3];
+                for c in dst.iter_mut() {
+                    // White text on a 60% black box.
+                    *c = if lit { 255 } else { (*c as f32 * 0.4).round() as u8 };
+                }
+            }
+        }
+
+        self.converter_out.convert_into_region(rgba, &mut frame, region.0, region.1)?;
+        Ok(Some(frame))
+    }
+}
+
+/// Returns whether the text pixel at (`x`, `y`), relative to the top-left of the text, is set.
+fn glyph_pixel(text: &str, scale: usize, x: isize, y: isize) -> bool {
+    if x < 0 || y < 0 {
+        return false;
+    }
+    let (x, y) = (x as usize / scale, y as usize / scale);
+    let (index, column) = (x / (GLYPH_WIDTH + 1), x % (GLYPH_WIDTH + 1));
+    if y >= GLYPH_HEIGHT || column >= GLYPH_WIDTH {
+        return false;
+    }
+    let glyph = match text.as_bytes().get(index) {
+        Some(c @ b'0'..=b'9') => &GLYPHS[(c - b'0') as usize],
+        Some(b':') => &GLYPHS[10],
+        Some(b';') => &GLYPHS[11],
+        _ => return false,
+    };
+    glyph[y] & (0x10 >> column) != 0
+}
+
+fn frame_rate_from_duration(frame: &Frame) -> Option<AVRational> {
+    let (duration, time_base) = unsafe { ((*frame.as_ptr()).duration, (*frame.as_ptr()).time_base) };
+    if duration <= 0 || time_base.num <= 0 || time_base.den <= 0 {
+        return None;
+    }
+    let fps = time_base.den as f64 / (duration as f64 * time_base.num as f64);
+    // Snap 29.97-like rates back to their exact NTSC form.
+    let rounded = fps.round();
+    if (fps - rounded * 1000.0 / 1001.0).abs() < 0.005 && (fps - rounded).abs() > 0.005 {
+        Some(AVRational { num: rounded as i32 * 1000, den: 1001 })
+    } else {
+        Some(AVRational { num: (fps * 1000.0).round() as i32, den: 1000 })
+    }
+}
+
+/// Drop-frame timecode is only defined for 29.97 and 59.94 fps.
+fn is_drop_frame_rate(fps: f64) -> bool {
+    let rounded = fps.round() as u64;
+    rounded == 30 || rounded == 60
+}
+
+/// Number of frame numbers skipped every minute (except every tenth minute) in drop-frame mode.
+fn dropped_frames_per_minute(fps: f64) -> u64 {
+    fps.round() as u64 / 15
+}
+
+/// Formats a zero-based frame count as `HH:MM:SS:FF`, or `HH:MM:SS;FF` in drop-frame mode.
+fn format_timecode(frame_number: u64, fps: f64, drop_frame: bool) -> String {
+    let nominal_fps = (fps.round() as u64).max(1);
+    let mut frame_number = frame_number;
+
+    if drop_frame && is_drop_frame_rate(fps) {
+        let drop = dropped_frames_per_minute(fps);
+        let frames_per_minute = nominal_fps * 60 - drop;
+        let frames_per_10_minutes = frames_per_minute * 10 + drop;
+        let tens = frame_number / frames_per_10_minutes;
+        let remainder = frame_number % frames_per_10_minutes;
+        frame_number += 9 * drop * tens;
+        if remainder > drop {
+            frame_number += drop * ((remainder - drop) / frames_per_minute);
+        }
+    }
+
+    let frames = frame_number % nominal_fps;
+    let total_seconds = frame_number / nominal_fps;
+    let separator = if drop_frame && is_drop_frame_rate(fps) { ';' } else { ':' };
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:02}",
+        (total_seconds / 3600) % 24,
+        (total_seconds / 60) % 60,
+        total_seconds % 60,
+        frames
+    )
+}
+
+/// Parses `HH:MM:SS:FF` (or `HH:MM:SS;FF`) into a zero-based frame count.
+fn parse_timecode(timecode: &str, fps: f64, drop_frame: bool) -> Result<u64, String> {
+    let parts = timecode
+        .split([':', ';'])
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Invalid timecode '{timecode}', expected HH:MM:SS:FF"))?;
+    let nominal_fps = (fps.round() as u64).max(1);
+    let [hours, minutes, seconds, frames] = parts[..] else {
+        return Err(format!("Invalid timecode '{timecode}', expected HH:MM:SS:FF"));
+    };
+    if minutes >= 60 || seconds >= 60 || frames >= nominal_fps {
+        return Err(format!("Invalid timecode '{timecode}' for {fps:.2} fps"));
+    }
+
+    let total_minutes = hours * 60 + minutes;
+    let mut frame_number = (total_minutes * 60 + seconds) * nominal_fps + frames;
+    if drop_frame && is_drop_frame_rate(fps) {
+        frame_number -= dropped_frames_per_minute(fps) * (total_minutes - total_minutes / 10);
+    }
+    Ok(frame_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::av_frame_get_buffer;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+    use std::collections::HashMap;
+
+    const NTSC: f64 = 30000.0 / 1001.0;
+
+    #[test]
+    fn test_format_timecode() {
+        assert_eq!(format_timecode(0, 25.0, false), "00:00:00:00");
+        assert_eq!(format_timecode(25 * 3661 + 3, 25.0, false), "01:01:01:03");
+        assert_eq!(format_timecode(1799, NTSC, true), "00:00:59;29");
+        assert_eq!(format_timecode(1800, NTSC, true), "00:01:00;02");
+        assert_eq!(format_timecode(17982, NTSC, true), "00:10:00;00");
+        assert_eq!(format_timecode(1800, NTSC, false), "00:01:00:00");
+    }
+
+    #[test]
+    fn test_parse_timecode() {
+        assert_eq!(parse_timecode("01:00:00:00", 25.0, false).unwrap(), 90000);
+        assert_eq!(parse_timecode("00:01:00;02", NTSC, true).unwrap(), 1800);
+        assert_eq!(parse_timecode("00:10:00;00", NTSC, true).unwrap(), 17982);
+        assert!(parse_timecode("00:00:00:30", 25.0, false).is_err());
+        assert!(parse_timecode("00:00:00", 25.0, false).is_err());
+    }
+
+    #[test]
+    fn test_timecode_box() {
+        let new_frame = || unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 320;
+            (*f).height = 180;
+            (*f).pts = 1;
+            (*f).time_base = AVRational { num: 1, den: 25 };
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for (y, row) in frame.plane_u8_mut(0).unwrap().into_iter().enumerate() {
+                for (x, sample) in row.iter_mut().enumerate() {
+                    *sample = ((x * 37 + y * 101) % 220 + 16) as u8;
+                }
+            }
+            for plane in 1..3 {
+                frame.plane_u8_mut(plane).unwrap().into_iter().for_each(|row| row.fill(128));
+            }
+            frame
+        };
+        let input = new_frame();
+        let mut attributes = HashMap::new();
+
+        // no duration to derive the rate from
+        let ctx = FrameFilterContext::new("timecode", &mut attributes);
+        assert!(TimecodeFilter::new(Corner::TopLeft).filter_frame(new_frame(), &ctx).is_err());
+        drop(ctx);
+
+        let ctx = FrameFilterContext::new("timecode", &mut attributes)
+            .with_frame_rate(Some(AVRational { num: 25, den: 1 }));
+        let output = TimecodeFilter::new(Corner::TopLeft).filter_frame(new_frame(), &ctx).unwrap().unwrap();
+        // the 69x11 box at (16, 16) is drawn, the rest is left untouched
+        let (input, output) = (input.plane_u8(0).unwrap(), output.plane_u8(0).unwrap());
+        assert_ne!(output[17][17], input[17][17]);
+        for (y, (input, output)) in input.iter().zip(&output).enumerate() {
+            for (x, (input, output)) in input.iter().zip(output.iter()).enumerate() {
+                if !(16..86).contains(&x) || !(16..28).contains(&y) {
+                    assert_eq!(input, output, "({x}, {y})");
+                }
+            }
+        }
+    }
+}
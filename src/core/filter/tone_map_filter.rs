@@ -0,0 +1,404 @@
+//! A [`FrameFilter`] that tone maps HDR video frames (PQ or HLG, BT.2020) to SDR BT.709.
+//!
+//! Frames whose transfer characteristic is neither PQ (SMPTE ST 2084) nor HLG (ARIB STD-B67)
+//! are passed through untouched, so the filter can be added unconditionally. HDR frames are
+//! converted to 16-bit RGB, decoded row by row into a linear-light `f32` buffer, tone mapped
+//! with the chosen [`ToneMapOperator`], converted from BT.2020 to BT.709 primaries, encoded
+//! with the BT.709 transfer function and written back in the frame's original pixel format.
+//! The output is tagged BT.709 and the HDR side data is removed.
+//!
+//! The source peak comes from [`ToneMapFilter::set_source_peak`], else from the frame's content
+//! light level or mastering display metadata, else defaults to 1000 cd/m².
+//!
+//! # Performance
+//! The transfer functions and the tone curve are precomputed into lookup tables, so the
+//! per-pixel work is a few table lookups and a 3x3 matrix, plus one `powf` for the HLG
+//! system gamma. The swscale round trip through RGB costs about as much again, and all of
+//! it runs on a single thread, so the cost grows linearly with the pixel count. Downscale
+//! before this filter when the target resolution is lower, or use a hardware tone mapping
+//! filter graph when 4K has to be processed in real time.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("tonemap", Box::new(ToneMapFilter::new(ToneMapOperator::Hable).set_target_peak(100.0)));
+//! ```
+
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::core::filter::frame_side_data::FrameSideData;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVColorPrimaries::{AVCOL_PRI_BT709, AVCOL_PRI_UNSPECIFIED};
+use ffmpeg_sys_next::AVColorRange::AVCOL_RANGE_JPEG;
+use ffmpeg_sys_next::AVColorSpace::{AVCOL_SPC_BT2020_NCL, AVCOL_SPC_BT709, AVCOL_SPC_UNSPECIFIED};
+use ffmpeg_sys_next::AVColorTransferCharacteristic::{AVCOL_TRC_ARIB_STD_B67, AVCOL_TRC_BT709, AVCOL_TRC_SMPTE2084};
+use ffmpeg_sys_next::AVFrameSideDataType::{AV_FRAME_DATA_CONTENT_LIGHT_LEVEL, AV_FRAME_DATA_MASTERING_DISPLAY_METADATA};
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGB48LE;
+use ffmpeg_sys_next::{av_frame_remove_side_data, AVMediaType};
+
+/// Peak luminance assumed for HDR sources without metadata, in cd/m².
+const DEFAULT_SOURCE_PEAK: f64 = 1000.0;
+/// Peak luminance of a reference SDR display, in cd/m².
+const DEFAULT_TARGET_PEAK: f64 = 100.0;
+/// Peak luminance the PQ signal range is defined over, in cd/m².
+const PQ_MAX_LUMINANCE: f64 = 10000.0;
+/// Number of intervals the tone curve is sampled with.
+const CURVE_SIZE: usize = 4096;
+
+/// Linear BT.2020 RGB to linear BT.709 RGB (ITU-R BT.2087).
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+/// The curve compressing the source luminance range into the target one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// The filmic curve of Uncharted 2, with a soft shoulder and slightly crushed shadows.
+    Hable,
+    /// Extended Reinhard, mapping the source peak to the target peak. Simple and low contrast.
+    Reinhard,
+    /// The EETF of ITU-R BT.2390: leaves the range the target can show untouched and rolls off
+    /// the highlights with a spline in the PQ domain.
+    Bt2390,
+}
+
+pub struct ToneMapFilter {
+    operator: ToneMapOperator,
+    source_peak: Option<f64>,
+    target_peak: f64,
+
+    luts: Option<ToneMapLuts>,
+    to_rgb: FrameConverter,
+    from_rgb: FrameConverter,
+    linear: Vec<f32>,
+}
+
+impl ToneMapFilter {
+    /// Creates a filter tone mapping HDR frames to a 100 cd/m² SDR display with `operator`.
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            source_peak: None,
+            target_peak: DEFAULT_TARGET_PEAK,
+            luts: None,
+            to_rgb: FrameConverter::new(),
+            from_rgb: FrameConverter::new(),
+            linear: Vec::new(),
+        }
+    }
+
+    /// Overrides the peak luminance of the source in cd/m², instead of reading it from the
+    /// frames' HDR metadata.
+    pub fn set_source_peak(mut self, nits: f64) -> Self {
+        self.source_peak = Some(nits);
+        self
+    }
+
+    /// Sets the peak luminance of the target display in cd/m². Defaults to 100.
+    pub fn set_target_peak(mut self, nits: f64) -> Self {
+        self.target_peak = nits;
+        self
+    }
+}
+
+impl FrameFilter for ToneMapFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if !is_valid_peak(self.target_peak) {
+            return Err(format!("Invalid target peak {} cd/m²", self.target_peak));
+        }
+        if let Some(source_peak) = self.source_peak.filter(|peak| !is_valid_peak(*peak)) {
+            return Err(format!("Invalid source peak {source_peak} cd/m²"));
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+        let (width, height, transfer, primaries, colorspace, full_range) = unsafe {
+            let f = frame.as_ptr();
+            (
+                (*f).width as usize,
+                (*f).height as usize,
+                (*f).color_trc,
+                (*f).color_primaries,
+                (*f).colorspace,
+                (*f).color_range == AVCOL_RANGE_JPEG,
+            )
+        };
+        let transfer = match transfer {
+            AVCOL_TRC_SMPTE2084 => Transfer::Pq,
+            AVCOL_TRC_ARIB_STD_B67 => Transfer::Hlg,
+            _ => return Ok(Some(frame)),
+        };
+
+        let source_peak = self
+            .source_peak
+            .or_else(|| metadata_peak(&frame))
+            .unwrap_or(DEFAULT_SOURCE_PEAK)
+            .max(self.target_peak);
+        let key = (transfer, self.operator, source_peak.to_bits(), self.target_peak.to_bits());
+        if self.luts.as_ref().is_none_or(|luts| luts.key != key) {
+            self.luts = Some(ToneMapLuts::new(transfer, self.operator, source_peak, self.target_peak));
+        }
+        let luts = self.luts.as_ref().unwrap();
+        let gamut = (primaries != AVCOL_PRI_BT709 && primaries != AVCOL_PRI_UNSPECIFIED).then_some(&BT2020_TO_BT709);
+
+        let colorspace = if colorspace == AVCOL_SPC_UNSPECIFIED { AVCOL_SPC_BT2020_NCL } else { colorspace };
+        self.to_rgb.set_colorspace((colorspace, full_range), (AVCOL_SPC_BT709, true));
+        self.from_rgb.set_colorspace((AVCOL_SPC_BT709, true), (AVCOL_SPC_BT709, full_range));
+
+        let rgb = self.to_rgb.convert(&frame, AV_PIX_FMT_RGB48LE, width as i32, height as i32)?;
+        self.linear.resize(width * 3, 0.0);
+        for row in rgb.plane_u16_mut(0)? {
+            for (value, code) in self.linear.iter_mut().zip(row.iter()) {
+                *value = luts.to_linear[*code as usize];
+            }
+            for pixel in self.linear.chunks_exact_mut(3) {
+                let mapped = luts.tone_map([pixel[0], pixel[1], pixel[2]], gamut);
+                pixel.copy_from_slice(&mapped);
+            }
+            for (code, value) in row.iter_mut().zip(&self.linear) {
+                *code = luts.encode(*value);
+            }
+        }
+
+        self.from_rgb.convert_into(rgb, &mut frame)?;
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).color_primaries = AVCOL_PRI_BT709;
+            (*f).color_trc = AVCOL_TRC_BT709;
+            (*f).colorspace = AVCOL_SPC_BT709;
+            av_frame_remove_side_data(f, AV_FRAME_DATA_MASTERING_DISPLAY_METADATA);
+            av_frame_remove_side_data(f, AV_FRAME_DATA_CONTENT_LIGHT_LEVEL);
+        }
+        Ok(Some(frame))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    Pq,
+    Hlg,
+}
+
+/// Lookup tables for one combination of transfer, operator and peaks.
+struct ToneMapLuts {
+    key: (Transfer, ToneMapOperator, u64, u64),
+    transfer: Transfer,
+    /// 16-bit code value to linear light: relative to the target peak for PQ, scene light in
+    /// `[0, 1]` for HLG.
+    to_linear: Vec<f32>,
+    /// Source peak relative to the target peak.
+    peak: f32,
+    /// HLG system gamma minus one.
+    hlg_gamma: f32,
+    /// Tone curve sampled over `[0, peak]`, mapping to `[0, 1]`.
+    curve: Vec<f32>,
+    /// Linear light in `[0, 1]` scaled to `0..=65535`, to the BT.709 16-bit code value.
+    to_bt709: Vec<u16>,
+}
+
+impl ToneMapLuts {
+    fn new(transfer: Transfer, operator: ToneMapOperator, source_peak: f64, target_peak: f64) -> Self {
+        let to_linear = (0..=u16::MAX)
+            .map(|code| {
+                let signal = code as f64 / u16::MAX as f64;
+                match transfer {
+                    Transfer::Pq => (pq_eotf(signal) * PQ_MAX_LUMINANCE / target_peak) as f32,
+                    Transfer::Hlg => hlg_inverse_oetf(signal) as f32,
+                }
+            })
+            .collect();
+        let peak = source_peak / target_peak;
+        let curve = (0..=CURVE_SIZE)
+            .map(|i| tone_curve(operator, i as f64 / CURVE_SIZE as f64 * peak, source_peak, target_peak) as f32)
+            .collect();
+        let to_bt709 = (0..=u16::MAX)
+            .map(|i| (bt709_oetf(i as f64 / u16::MAX as f64) * u16::MAX as f64).round() as u16)
+            .collect();
+
+        Self {
+            key: (transfer, operator, source_peak.to_bits(), target_peak.to_bits()),
+            transfer,
+            to_linear,
+            peak: peak as f32,
+            // the nominal system gamma of BT.2100 for a display of `source_peak` cd/m²
+            hlg_gamma: (1.2 + 0.42 * (source_peak / 1000.0).log10()) as f32 - 1.0,
+            curve,
+            to_bt709,
+        }
+    }
+
+    /// Tone maps a linear pixel, scaling its channels by the same factor to keep the hue.
+    fn tone_map(&self, mut rgb: [f32; 3], gamut: Option<&[[f32; 3]; 3]>) -> [f32; 3] {
+        if self.transfer == Transfer::Hlg {
+            // HLG OOTF, scene light to display light relative to the target peak
+            let luma = 0.2627 * rgb[0] + 0.6780 * rgb[1] + 0.0593 * rgb[2];
+            let gain = self.peak * luma.max(1e-6).powf(self.hlg_gamma);
+            rgb = rgb.map(|c| c * gain);
+        }
+
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+        if max > 0.0 {
+            let position = (max / self.peak * CURVE_SIZE as f32).min(CURVE_SIZE as f32);
+            let index = (position as usize).min(CURVE_SIZE - 1);
+            let fraction = position - index as f32;
+            let mapped = self.curve[index] + (self.curve[index + 1] - self.curve[index]) * fraction;
+            let scale = mapped / max;
+            rgb = rgb.map(|c| c * scale);
+        }
+
+        if let Some(matrix) = gamut {
+            rgb = matrix.map(|row| row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]);
+        }
+        rgb.map(|c| c.clamp(0.0, 1.0))
+    }
+
+    /// Encodes linear light in `[0, 1]` to a BT.709 16-bit code value.
+    fn encode(&self, value: f32) -> u16 {
+        self.to_bt709[(value * u16::MAX as f32).round() as usize]
+    }
+}
+
+fn is_valid_peak(nits: f64) -> bool {
+    nits.is_finite() && nits > 0.0
+}
+
+/// Returns the peak luminance in cd/m² announced by the frame's HDR metadata.
+fn metadata_peak(frame: &Frame) -> Option<f64> {
+    frame
+        .content_light_level()
+        .map(|level| level.max_cll as f64)
+        .filter(|peak| *peak > 0.0)
+        .or_else(|| frame.mastering_display()?.luminance.map(|(_, max)| max))
+        .filter(|peak| *peak > 0.0)
+}
+
+/// Maps `x`, linear light relative to the target peak, to `[0, 1]`.
+fn tone_curve(operator: ToneMapOperator, x: f64, source_peak: f64, target_peak: f64) -> f64 {
+    let peak = source_peak / target_peak;
+    let y = match operator {
+        ToneMapOperator::Hable => hable(x) / hable(peak),
+        ToneMapOperator::Reinhard => x * (1.0 + x / (peak * peak)) / (1.0 + x),
+        ToneMapOperator::Bt2390 => bt2390(x * target_peak, source_peak, target_peak) / target_peak,
+    };
+    y.clamp(0.0, 1.0)
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+/// The BT.2390 EETF, mapping `nits` of a `source_peak` master to a `target_peak` display.
+fn bt2390(nits: f64, source_peak: f64, target_peak: f64) -> f64 {
+    let source_max = pq_inverse_eotf(source_peak / PQ_MAX_LUMINANCE);
+    let signal = pq_inverse_eotf(nits / PQ_MAX_LUMINANCE) / source_max;
+    let max_lum = pq_inverse_eotf(target_peak / PQ_MAX_LUMINANCE) / source_max;
+    if max_lum >= 1.0 {
+        return nits;
+    }
+
+    let knee = 1.5 * max_lum - 0.5;
+    let mapped = if signal < knee {
+        signal
+    } else {
+        let t = (signal.min(1.0) - knee) / (1.0 - knee);
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * knee + (t3 - 2.0 * t2 + t) * (1.0 - knee) + (-2.0 * t3 + 3.0 * t2) * max_lum
+    };
+    pq_eotf(mapped * source_max) * PQ_MAX_LUMINANCE
+}
+
+const PQ_M1: f64 = 2610.0 / 16384.0;
+const PQ_M2: f64 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f64 = 3424.0 / 4096.0;
+const PQ_C2: f64 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f64 = 2392.0 / 4096.0 * 32.0;
+
+/// PQ signal in `[0, 1]` to linear light relative to 10000 cd/m².
+fn pq_eotf(signal: f64) -> f64 {
+    let p = signal.max(0.0).powf(1.0 / PQ_M2);
+    ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1)
+}
+
+/// Linear light relative to 10000 cd/m² to a PQ signal in `[0, 1]`.
+fn pq_inverse_eotf(light: f64) -> f64 {
+    let y = light.max(0.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
+/// HLG signal in `[0, 1]` to scene light in `[0, 1]`.
+fn hlg_inverse_oetf(signal: f64) -> f64 {
+    const A: f64 = 0.17883277;
+    const B: f64 = 0.28466892;
+    const C: f64 = 0.55991073;
+    if signal <= 0.5 {
+        signal * signal / 3.0
+    } else {
+        (((signal - C) / A).exp() + B) / 12.0
+    }
+}
+
+/// Linear light in `[0, 1]` to a BT.709 signal.
+fn bt709_oetf(light: f64) -> f64 {
+    if light < 0.018 {
+        4.5 * light
+    } else {
+        1.099 * light.powf(0.45) - 0.099
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pq_round_trip() {
+        for light in [0.0, 0.0001, 0.01, 0.1, 1.0] {
+            assert!((pq_eotf(pq_inverse_eotf(light)) - light).abs() < 1e-9, "{light}");
+        }
+        // 100 cd/m² is at about half of the PQ signal range
+        assert!((pq_inverse_eotf(0.01) - 0.508).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_tone_curves() {
+        for operator in [ToneMapOperator::Hable, ToneMapOperator::Reinhard, ToneMapOperator::Bt2390] {
+            assert!(tone_curve(operator, 0.0, 1000.0, 100.0) < 1e-6, "{operator:?}");
+            assert!((tone_curve(operator, 10.0, 1000.0, 100.0) - 1.0).abs() < 1e-6, "{operator:?}");
+            let samples: Vec<f64> = (0..=100).map(|i| tone_curve(operator, i as f64 / 10.0, 1000.0, 100.0)).collect();
+            assert!(samples.windows(2).all(|w| w[0] <= w[1]), "{operator:?} is not monotonic");
+        }
+        // BT.2390 leaves the shadows untouched
+        assert!((tone_curve(ToneMapOperator::Bt2390, 0.05, 1000.0, 100.0) - 0.05).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tone_map_pixel() {
+        let luts = ToneMapLuts::new(Transfer::Pq, ToneMapOperator::Bt2390, 1000.0, 100.0);
+        let white = luts.tone_map([10.0, 10.0, 10.0], Some(&BT2020_TO_BT709));
+        assert!(white.iter().all(|c| (c - 1.0).abs() < 1e-3), "{white:?}");
+        // the hue of a saturated highlight is kept while its level is compressed
+        let [r, g, b] = luts.tone_map([8.0, 4.0, 0.0], None);
+        assert!(r <= 1.0 && (g / r - 0.5).abs() < 1e-3 && b == 0.0);
+        assert_eq!(luts.encode(1.0), u16::MAX);
+    }
+}
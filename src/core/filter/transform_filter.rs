@@ -0,0 +1,282 @@
+//! A [`FrameFilter`] that rotates video frames by multiples of 90 degrees and/or flips them,
+//! e.g. to fix sideways phone footage.
+//!
+//! Pixels are moved directly on the raw planes, so the result is lossless and no filter graph
+//! is needed. Chroma planes are transformed with their own (subsampled) size, and the width,
+//! height and sample aspect ratio (when known) are swapped for 90 and 270 degree rotations.
+//!
+//! Only right angles are supported: any other angle needs resampling (and a background fill)
+//! and is rejected by [`Rotation::from_degrees`]; use FFmpeg's `rotate` filter in a
+//! `filter_desc` for those. Formats whose chroma is subsampled differently horizontally and
+//! vertically (e.g. `yuv422p`) cannot be rotated by 90 or 270 degrees, since the rotated
+//! frame would need the opposite subsampling; they can still be rotated by 180 degrees and flipped.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("transform", Box::new(
+//!         TransformFilter::new(Rotation::Rotate90).set_hflip(true),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::util::ffmpeg_utils::{av_err2str, pixel_format};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{
+    av_frame_copy_props, av_frame_get_buffer, av_pix_fmt_count_planes, av_pix_fmt_desc_get, AVMediaType,
+    AVRational, AV_PIX_FMT_FLAG_BAYER, AV_PIX_FMT_FLAG_BITSTREAM, AV_PIX_FMT_FLAG_HWACCEL,
+    AV_PIX_FMT_FLAG_PAL,
+};
+
+/// A clockwise rotation by a right angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// Returns the clockwise rotation by `degrees` (negative values rotate counter-clockwise),
+    /// or `None` if `degrees` is not a multiple of 90.
+    pub fn from_degrees(degrees: i32) -> Option<Rotation> {
+        if degrees % 90 != 0 {
+            return None;
+        }
+        Some(match degrees.rem_euclid(360) {
+            0 => Rotation::None,
+            90 => Rotation::Rotate90,
+            180 => Rotation::Rotate180,
+            _ => Rotation::Rotate270,
+        })
+    }
+
+    fn swaps_dimensions(&self) -> bool {
+        matches!(self, Rotation::Rotate90 | Rotation::Rotate270)
+    }
+}
+
+pub struct TransformFilter {
+    rotation: Rotation,
+    hflip: bool,
+    vflip: bool,
+}
+
+impl TransformFilter {
+    /// Creates a filter rotating the frames clockwise by `rotation`.
+    pub fn new(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            hflip: false,
+            vflip: false,
+        }
+    }
+
+    /// Mirrors the frames left to right, after the rotation.
+    pub fn set_hflip(mut self, hflip: bool) -> Self {
+        self.hflip = hflip;
+        self
+    }
+
+    /// Mirrors the frames top to bottom, after the rotation.
+    pub fn set_vflip(mut self, vflip: bool) -> Self {
+        self.vflip = vflip;
+        self
+    }
+
+    fn is_identity(&self) -> bool {
+        self.rotation == Rotation::None && !self.hflip && !self.vflip
+    }
+}
+
+impl FrameFilter for TransformFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null()
+                || frame.is_empty()
+                || !(*frame.as_ptr()).hw_frames_ctx.is_null()
+                || self.is_identity()
+            {
+                return Ok(Some(frame));
+            }
+            transform_frame(&frame, self.rotation, self.hflip, self.vflip).map(Some)
+        }
+    }
+}
+
+/// Returns a transformed copy of `src`.
+unsafe fn transform_frame(src: &Frame, rotation: Rotation, hflip: bool, vflip: bool) -> Result<Frame, String> {
+    let s = src.as_ptr();
+    let (format, width, height) = ((*s).format, (*s).width, (*s).height);
+    let pix_fmt = pixel_format(format).ok_or_else(|| format!("Unknown pixel format {format}"))?;
+    let desc = av_pix_fmt_desc_get(pix_fmt);
+    if desc.is_null() {
+        return Err(format!("Unknown pixel format {format}"));
+    }
+    let unsupported =
+        (AV_PIX_FMT_FLAG_PAL | AV_PIX_FMT_FLAG_BITSTREAM | AV_PIX_FMT_FLAG_HWACCEL | AV_PIX_FMT_FLAG_BAYER) as u64;
+    let nb_planes = av_pix_fmt_count_planes(pix_fmt);
+    let (log2_chroma_w, log2_chroma_h) = ((*desc).log2_chroma_w, (*desc).log2_chroma_h);
+    // packed formats sharing chroma between pixels (e.g. yuyv422) have no per-pixel element
+    if (*desc).flags & unsupported != 0 || nb_planes <= 0 || (nb_planes == 1 && log2_chroma_w != 0) {
+        return Err(format!("Transform filter does not support pixel format {format}"));
+    }
+    if rotation.swaps_dimensions() && log2_chroma_w != log2_chroma_h {
+        return Err(format!(
+            "Transform filter cannot rotate pixel format {format} by 90 or 270 degrees: its chroma subsampling is not square"
+        ));
+    }
+
+    let mut output = Frame::empty();
+    if output.as_ptr().is_null() {
+        return Err("Failed to create frame: Out of memory.".to_string());
+    }
+    let dst = output.as_mut_ptr();
+    let (dst_width, dst_height) = if rotation.swaps_dimensions() { (height, width) } else { (width, height) };
+    (*dst).format = format;
+    (*dst).width = dst_width;
+    (*dst).height = dst_height;
+    let ret = av_frame_get_buffer(dst, 0);
+    if ret < 0 {
+        return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+    }
+    let ret = av_frame_copy_props(dst, s);
+    if ret < 0 {
+        return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+    }
+    let sar = (*s).sample_aspect_ratio;
+    // an unknown ratio (0/1) stays unknown
+    if rotation.swaps_dimensions() && sar.num > 0 && sar.den > 0 {
+        (*dst).sample_aspect_ratio = AVRational { num: sar.den, den: sar.num };
+    }
+
+    let components = &(*desc).comp[..(*desc).nb_components as usize];
+    for plane in 0..nb_planes as usize {
+        // bytes of one pixel of the plane, e.g. 2 for the interleaved chroma of nv12
+        let element = components
+            .iter()
+            .filter(|comp| comp.plane as usize == plane)
+            .map(|comp| comp.step as usize)
+            .max()
+            .unwrap_or(1);
+        let is_chroma = components.len() >= 3
+            && (components[1].plane as usize == plane || components[2].plane as usize == plane);
+        let (plane_width, plane_height) = if is_chroma {
+            // AV_CEIL_RSHIFT
+            (-((-width) >> log2_chroma_w), -((-height) >> log2_chroma_h))
+        } else {
+            (width, height)
+        };
+
+        let src_plane = Plane {
+            data: (*s).data[plane],
+            linesize: (*s).linesize[plane] as isize,
+            width: plane_width as usize,
+            height: plane_height as usize,
+        };
+        let (dst_plane_width, dst_plane_height) = if rotation.swaps_dimensions() {
+            (plane_height, plane_width)
+        } else {
+            (plane_width, plane_height)
+        };
+        let dst_plane = Plane {
+            data: (*dst).data[plane],
+            linesize: (*dst).linesize[plane] as isize,
+            width: dst_plane_width as usize,
+            height: dst_plane_height as usize,
+        };
+        transform_plane(&src_plane, &dst_plane, element, rotation, hflip, vflip);
+    }
+    Ok(output)
+}
+
+struct Plane {
+    data: *mut u8,
+    linesize: isize,
+    width: usize,
+    height: usize,
+}
+
+/// Copies every `element`-byte pixel of `src` to its transformed position in `dst`.
+unsafe fn transform_plane(src: &Plane, dst: &Plane, element: usize, rotation: Rotation, hflip: bool, vflip: bool) {
+    for y in 0..dst.height {
+        let row = dst.data.offset(y as isize * dst.linesize);
+        for x in 0..dst.width {
+            let (rx, ry) = (
+                if hflip { dst.width - 1 - x } else { x },
+                if vflip { dst.height - 1 - y } else { y },
+            );
+            // position in the source of the pixel rotated to (rx, ry)
+            let (sx, sy) = match rotation {
+                Rotation::None => (rx, ry),
+                Rotation::Rotate90 => (ry, src.height - 1 - rx),
+                Rotation::Rotate180 => (src.width - 1 - rx, src.height - 1 - ry),
+                Rotation::Rotate270 => (src.width - 1 - ry, rx),
+            };
+            let pixel = src.data.offset(sy as isize * src.linesize).add(sx * element);
+            std::ptr::copy_nonoverlapping(pixel, row.add(x * element), element);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_from_degrees() {
+        assert_eq!(Rotation::from_degrees(90), Some(Rotation::Rotate90));
+        assert_eq!(Rotation::from_degrees(-90), Some(Rotation::Rotate270));
+        assert_eq!(Rotation::from_degrees(540), Some(Rotation::Rotate180));
+        assert_eq!(Rotation::from_degrees(45), None);
+    }
+
+    #[test]
+    fn test_transform_plane() {
+        // 3x2 source:
+        // 1 2 3
+        // 4 5 6
+        let mut src = vec![1u8, 2, 3, 4, 5, 6];
+        let src_plane = Plane { data: src.as_mut_ptr(), linesize: 3, width: 3, height: 2 };
+
+        let transform = |rotation: Rotation, hflip: bool, vflip: bool| {
+            let (width, height) = if rotation.swaps_dimensions() { (2, 3) } else { (3, 2) };
+            let mut dst = vec![0u8; 6];
+            let dst_plane = Plane { data: dst.as_mut_ptr(), linesize: width as isize, width, height };
+            unsafe { transform_plane(&src_plane, &dst_plane, 1, rotation, hflip, vflip) };
+            dst
+        };
+
+        assert_eq!(transform(Rotation::Rotate90, false, false), vec![4, 1, 5, 2, 6, 3]);
+        assert_eq!(transform(Rotation::Rotate180, false, false), vec![6, 5, 4, 3, 2, 1]);
+        assert_eq!(transform(Rotation::Rotate270, false, false), vec![3, 6, 2, 5, 1, 4]);
+        assert_eq!(transform(Rotation::None, true, false), vec![3, 2, 1, 6, 5, 4]);
+        assert_eq!(transform(Rotation::None, false, true), vec![4, 5, 6, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_rotated_sample_aspect_ratio() {
+        let rotated_sar = |sar: AVRational| unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 4;
+            (*f).height = 2;
+            (*f).sample_aspect_ratio = sar;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            let rotated = transform_frame(&frame, Rotation::Rotate90, false, false).unwrap();
+            let sar = (*rotated.as_ptr()).sample_aspect_ratio;
+            (sar.num, sar.den)
+        };
+
+        assert_eq!(rotated_sar(AVRational { num: 4, den: 3 }), (3, 4));
+        // unknown, not turned into an invalid 1/0
+        assert_eq!(rotated_sar(AVRational { num: 0, den: 1 }), (0, 1));
+    }
+}
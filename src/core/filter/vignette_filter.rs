@@ -0,0 +1,320 @@
+//! A [`FrameFilter`] that darkens the edges of video frames, for a cinematic look.
+//!
+//! The darkening follows the shape of the frame: the distance of a pixel to the center is
+//! measured relative to the half width and half height, so that the corners are at distance
+//! 1. Pixels closer than the radius are left untouched; past it, the gain falls smoothly
+//! (smoothstep) to `1 - strength` in the corners. The luma is scaled towards black, and, when
+//! enabled, the chroma towards neutral, which also desaturates the edges.
+//!
+//! The filter works on the planes of YUV and gray frames in their own pixel format, at any
+//! bit depth from 8 to 16 bits (`yuv420p`, `nv12`, `yuv420p10le`, `p010le`, ...). The gain of
+//! each pixel is computed once per frame size and cached, so the per-frame cost is one
+//! multiplication per sample. RGB and packed YUV frames (`yuyv422`) are rejected, and hardware
+//! frames are passed through untouched.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+//!     .filter("vignette", Box::new(
+//!         VignetteFilter::new()
+//!             .set_radius(0.5)
+//!             .set_strength(0.6)
+//!             .set_chroma(true),
+//!     ));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{FramePlanes, PlaneInfo};
+use crate::util::ffmpeg_utils::pixel_format;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::{av_pix_fmt_desc_get, AVColorRange, AVMediaType, AV_PIX_FMT_FLAG_RGB};
+
+pub struct VignetteFilter {
+    radius: f32,
+    strength: f32,
+    chroma: bool,
+
+    // the gains of the last frame size
+    falloff: Option<Falloff>,
+}
+
+impl VignetteFilter {
+    /// Creates a filter darkening the luma past 60% of the distance to the corners, down to
+    /// half its level in the corners.
+    pub fn new() -> Self {
+        Self {
+            radius: 0.6,
+            strength: 0.5,
+            chroma: false,
+            falloff: None,
+        }
+    }
+
+    /// Sets the distance to the center (0-1, 1 being the corners) where the darkening starts.
+    pub fn set_radius(mut self, radius: f32) -> Self {
+        self.radius = radius.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets how much the corners are darkened (0-1): 0 leaves the frame untouched, 1 turns
+    /// the corners black.
+    pub fn set_strength(mut self, strength: f32) -> Self {
+        self.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Also fades the chroma towards neutral (`true`), desaturating the edges, instead of
+    /// only darkening the luma (`false`, the default).
+    pub fn set_chroma(mut self, chroma: bool) -> Self {
+        self.chroma = chroma;
+        self
+    }
+}
+
+impl Default for VignetteFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameFilter for VignetteFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_VIDEO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        // the gains depend on the frame size, computed with the first frame
+        self.falloff = None;
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() || !(*frame.as_ptr()).hw_frames_ctx.is_null() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let layout = YuvLayout::of(&frame)?;
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+        let full_range = unsafe { (*frame.as_ptr()).color_range == AVColorRange::AVCOL_RANGE_JPEG };
+
+        let key = (width, height, layout.log2_chroma_w, layout.log2_chroma_h);
+        if !matches!(&self.falloff, Some(falloff) if falloff.key == key) {
+            self.falloff = Some(Falloff::new(key, self.radius, self.strength));
+        }
+        let Some(falloff) = &self.falloff else {
+            unreachable!()
+        };
+
+        let luma_info = frame.plane_info(layout.luma_plane)?;
+        // scaled towards black, 16 (at 8 bits) in limited range
+        let black = if full_range || layout.gray { 0.0 } else { (16u32 << (luma_info.bit_depth - 8)) as f32 };
+        apply_gains(&mut frame, layout.luma_plane, &luma_info, 1, &falloff.luma, black)?;
+
+        if self.chroma && !layout.gray {
+            for (plane, components) in layout.chroma_planes() {
+                let info = frame.plane_info(plane)?;
+                let neutral = (1u32 << (info.bit_depth - 1)) as f32;
+                apply_gains(&mut frame, plane, &info, components, &falloff.chroma, neutral)?;
+            }
+        }
+
+        Ok(Some(frame))
+    }
+}
+
+/// The gain of every pixel of a frame size, for the luma plane and the subsampled chroma
+/// planes.
+struct Falloff {
+    // width, height, log2_chroma_w, log2_chroma_h
+    key: (usize, usize, u8, u8),
+    // the gain of each pixel, row after row
+    luma: Vec<f32>,
+    chroma: Vec<f32>,
+}
+
+impl Falloff {
+    fn new(key: (usize, usize, u8, u8), radius: f32, strength: f32) -> Self {
+        let (width, height, log2_chroma_w, log2_chroma_h) = key;
+        let gains = |plane_width: usize, plane_height: usize| -> Vec<f32> {
+            let scale_x = width as f32 / plane_width as f32;
+            let scale_y = height as f32 / plane_height as f32;
+            (0..plane_height)
+                .flat_map(|y| (0..plane_width).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    // the center of the pixel, in frame coordinates
+                    let (fx, fy) = ((x as f32 + 0.5) * scale_x, (y as f32 + 0.5) * scale_y);
+                    gain(fx, fy, width as f32, height as f32, radius, strength)
+                })
+                .collect()
+        };
+        Self {
+            key,
+            luma: gains(width, height),
+            chroma: gains(ceil_rshift(width, log2_chroma_w), ceil_rshift(height, log2_chroma_h)),
+        }
+    }
+}
+
+/// `AV_CEIL_RSHIFT`: the size of a subsampled plane.
+fn ceil_rshift(size: usize, shift: u8) -> usize {
+    (size + (1 << shift) - 1) >> shift
+}
+
+/// The gain at `(x, y)` of a `width` x `height` frame.
+fn gain(x: f32, y: f32, width: f32, height: f32, radius: f32, strength: f32) -> f32 {
+    let dx = (x - width / 2.0) / (width / 2.0);
+    let dy = (y - height / 2.0) / (height / 2.0);
+    // 0 at the center, 1 in the corners
+    let distance = ((dx * dx + dy * dy) / 2.0).sqrt();
+    if distance <= radius {
+        return 1.0;
+    }
+    let t = ((distance - radius) / (1.0 - radius).max(f32::EPSILON)).min(1.0);
+    1.0 - strength * t * t * (3.0 - 2.0 * t)
+}
+
+/// Scales every sample of `plane` towards `target` by the gain of its pixel. A pixel holds
+/// `components` samples, e.g. 2 in the interleaved chroma plane of `nv12`.
+fn apply_gains(
+    frame: &mut Frame,
+    plane: usize,
+    info: &PlaneInfo,
+    components: usize,
+    gains: &[f32],
+    target: f32,
+) -> Result<(), String> {
+    let plane_width = gains.len() / info.rows.max(1);
+    let scale = |value: f32, gain: f32| target + (value - target) * gain;
+    if info.bytes_per_sample == 2 {
+        let max = ((1u32 << info.bit_depth) - 1) as f32;
+        for (row, row_gains) in frame.plane_u16_mut(plane)?.into_iter().zip(gains.chunks_exact(plane_width)) {
+            for (pixel, gain) in row.chunks_exact_mut(components).zip(row_gains) {
+                for sample in pixel {
+                    let value = scale((*sample >> info.shift) as f32, *gain).round().clamp(0.0, max);
+                    *sample = (value as u16) << info.shift;
+                }
+            }
+        }
+    } else {
+        for (row, row_gains) in frame.plane_u8_mut(plane)?.into_iter().zip(gains.chunks_exact(plane_width)) {
+            for (pixel, gain) in row.chunks_exact_mut(components).zip(row_gains) {
+                for sample in pixel {
+                    *sample = scale(*sample as f32, *gain).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Where the luma and chroma samples of a planar or semi-planar YUV (or gray) format are.
+struct YuvLayout {
+    gray: bool,
+    luma_plane: usize,
+    // the planes of the Cb and Cr components, the same one for semi-planar formats
+    chroma: [usize; 2],
+    log2_chroma_w: u8,
+    log2_chroma_h: u8,
+}
+
+impl YuvLayout {
+    fn of(frame: &Frame) -> Result<Self, String> {
+        unsafe {
+            let format = (*frame.as_ptr()).format;
+            let pix_fmt = pixel_format(format).ok_or_else(|| format!("Unknown pixel format {format}"))?;
+            let desc = av_pix_fmt_desc_get(pix_fmt);
+            if desc.is_null() {
+                return Err(format!("Unknown pixel format {format}"));
+            }
+            let components = &(*desc).comp[..(*desc).nb_components as usize];
+            let luma_plane = components[0].plane as usize;
+            let packed = components[1..].iter().any(|comp| comp.plane as usize == luma_plane);
+            if (*desc).flags & AV_PIX_FMT_FLAG_RGB as u64 != 0 || packed {
+                return Err(format!("Vignette needs planar YUV or gray frames, not pixel format {format}"));
+            }
+            let gray = components.len() < 3;
+            Ok(Self {
+                gray,
+                luma_plane,
+                chroma: if gray { [0, 0] } else { [components[1].plane as usize, components[2].plane as usize] },
+                log2_chroma_w: (*desc).log2_chroma_w,
+                log2_chroma_h: (*desc).log2_chroma_h,
+            })
+        }
+    }
+
+    /// The chroma planes, with the number of samples of a pixel in each.
+    fn chroma_planes(&self) -> Vec<(usize, usize)> {
+        if self.chroma[0] == self.chroma[1] {
+            vec![(self.chroma[0], 2)]
+        } else {
+            vec![(self.chroma[0], 1), (self.chroma[1], 1)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::{av_frame_get_buffer, AVPixelFormat};
+    use ffmpeg_sys_next::AVPixelFormat::{AV_PIX_FMT_YUV420P, AV_PIX_FMT_YUV420P10LE};
+    use std::collections::HashMap;
+
+    fn frame(format: AVPixelFormat, luma: u16, chroma: u16) -> Frame {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = format as i32;
+            (*f).width = 64;
+            (*f).height = 36;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+            for plane in 0..3 {
+                let value = if plane == 0 { luma } else { chroma };
+                if frame.plane_info(plane).unwrap().bytes_per_sample == 2 {
+                    frame.plane_u16_mut(plane).unwrap().into_iter().for_each(|row| row.fill(value));
+                } else {
+                    frame.plane_u8_mut(plane).unwrap().into_iter().for_each(|row| row.fill(value as u8));
+                }
+            }
+            frame
+        }
+    }
+
+    #[test]
+    fn test_gain() {
+        assert_eq!(gain(50.0, 50.0, 100.0, 100.0, 0.5, 0.8), 1.0);
+        assert!((gain(0.0, 0.0, 100.0, 100.0, 0.5, 0.8) - 0.2).abs() < 1e-6);
+        // the falloff follows the aspect ratio: the middle of each edge is as far as the other
+        assert_eq!(gain(0.0, 50.0, 200.0, 100.0, 0.5, 0.8), gain(100.0, 0.0, 200.0, 100.0, 0.5, 0.8));
+    }
+
+    #[test]
+    fn test_vignette_bit_depths() {
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("vignette", &mut attributes);
+        let mut filter = VignetteFilter::new().set_radius(0.3).set_strength(0.8).set_chroma(true);
+        filter.init(&ctx).unwrap();
+
+        for (format, luma, chroma) in [(AV_PIX_FMT_YUV420P, 200, 200), (AV_PIX_FMT_YUV420P10LE, 800, 800)] {
+            let output = filter.filter_frame(frame(format, luma, chroma), &ctx).unwrap().unwrap();
+            let sample = |plane: usize, x: usize, y: usize| -> u16 {
+                if output.plane_info(plane).unwrap().bytes_per_sample == 2 {
+                    output.plane_u16(plane).unwrap()[y][x]
+                } else {
+                    output.plane_u8(plane).unwrap()[y][x] as u16
+                }
+            };
+            // the center is untouched, the corners are darker but not below black
+            assert_eq!(sample(0, 32, 18), luma);
+            let black = if luma > 255 { 64 } else { 16 };
+            assert!(sample(0, 0, 0) < luma / 2 && sample(0, 0, 0) >= black, "{format:?}: {}", sample(0, 0, 0));
+            // the chroma moves towards neutral
+            let neutral = if luma > 255 { 512 } else { 128 };
+            assert!(sample(1, 0, 0) < chroma && sample(1, 0, 0) > neutral, "{format:?}: {}", sample(1, 0, 0));
+            assert_eq!(sample(1, 16, 9), chroma);
+        }
+    }
+}
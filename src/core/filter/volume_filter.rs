@@ -0,0 +1,131 @@
+//! A [`FrameFilter`] that changes the volume of audio frames, without a libavfilter `volume` graph.
+//!
+//! Every sample is multiplied by a linear gain, in place, so the frame keeps its format,
+//! channel layout, timestamps and other properties. Integer formats (`u8`, `s16`, `s32`,
+//! `s64`, packed or planar) are clipped to their range; floating-point formats are not,
+//! like FFmpeg's own `volume` filter.
+//!
+//! # Example
+//! ```rust,ignore
+//! let pipeline = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_AUDIO)
+//!     .filter("vol", Box::new(VolumeFilter::from_db(6.0)));
+//! ```
+
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_planes::{AudioSample, FramePlanes};
+use crate::core::filter::frame_view::FrameView;
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVSampleFormat::*;
+use ffmpeg_sys_next::{av_get_packed_sample_fmt, av_sample_fmt_is_planar, AVMediaType};
+
+pub struct VolumeFilter {
+    gain: f64,
+}
+
+impl VolumeFilter {
+    /// Creates a filter multiplying the samples by `gain` (`1.0` keeps the volume, `0.5` halves
+    /// the amplitude, `2.0` doubles it).
+    pub fn new(gain: f64) -> Self {
+        Self { gain }
+    }
+
+    /// Creates a filter changing the volume by `db` decibels, e.g. `6.0` roughly doubles
+    /// the amplitude and `-6.0` roughly halves it.
+    pub fn from_db(db: f64) -> Self {
+        Self::new(10f64.powf(db / 20.0))
+    }
+
+    /// Returns the linear gain applied to the samples.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+}
+
+impl FrameFilter for VolumeFilter {
+    fn media_type(&self) -> AVMediaType {
+        AVMediaType::AVMEDIA_TYPE_AUDIO
+    }
+
+    fn init(&mut self, _ctx: &FrameFilterContext) -> Result<(), String> {
+        if !self.gain.is_finite() || self.gain < 0.0 {
+            return Err(format!("Invalid volume gain {}", self.gain));
+        }
+        Ok(())
+    }
+
+    fn filter_frame(&mut self, mut frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+        let view = FrameView::new(&frame);
+        if view.is_props_only() || self.gain == 1.0 {
+            return Ok(Some(frame));
+        }
+        let format = view.sample_format().ok_or("Volume filter needs audio frames")?;
+        let planes = if unsafe { av_sample_fmt_is_planar(format) } != 0 { view.channels() } else { 1 };
+
+        // the samples are made writable, as decoded frames may share their buffers
+        for plane in 0..planes {
+            match unsafe { av_get_packed_sample_fmt(format) } {
+                AV_SAMPLE_FMT_U8 => apply_gain(frame.samples_mut::<u8>(plane)?, self.gain),
+                AV_SAMPLE_FMT_S16 => apply_gain(frame.samples_mut::<i16>(plane)?, self.gain),
+                AV_SAMPLE_FMT_S32 => apply_gain(frame.samples_mut::<i32>(plane)?, self.gain),
+                AV_SAMPLE_FMT_S64 => apply_gain(frame.samples_mut::<i64>(plane)?, self.gain),
+                AV_SAMPLE_FMT_FLT => apply_gain(frame.samples_mut::<f32>(plane)?, self.gain),
+                AV_SAMPLE_FMT_DBL => apply_gain(frame.samples_mut::<f64>(plane)?, self.gain),
+                _ => return Err(format!("Volume filter does not support sample format {format:?}")),
+            }
+        }
+        Ok(Some(frame))
+    }
+}
+
+fn apply_gain<T: AudioSample>(samples: &mut [T], gain: f64) {
+    samples.iter_mut().for_each(|sample| *sample = T::from_f64(sample.to_f64() * gain));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::{av_channel_layout_default, av_frame_get_buffer};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_apply_gain_clips_integer_samples() {
+        let mut s16: Vec<i16> = vec![1000, -1000, 20000, -20000];
+        apply_gain(&mut s16, 2.0);
+        assert_eq!(s16, vec![2000, -2000, i16::MAX, i16::MIN]);
+
+        let mut u8s: Vec<u8> = vec![128, 138, 118, 250];
+        apply_gain(&mut u8s, 2.0);
+        assert_eq!(u8s, vec![128, 148, 108, 255]);
+
+        let mut flt: Vec<f32> = vec![0.75, -0.25];
+        apply_gain(&mut flt, 2.0);
+        assert_eq!(flt, vec![1.5, -0.5]);
+    }
+
+    #[test]
+    fn test_volume_planar_frame() {
+        let mut frame = unsafe { Frame::empty() };
+        unsafe {
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_SAMPLE_FMT_S16P as i32;
+            (*f).nb_samples = 3;
+            av_channel_layout_default(&mut (*f).ch_layout, 2);
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+        }
+        frame.samples_mut::<i16>(0).unwrap().copy_from_slice(&[100, -100, 0]);
+        frame.samples_mut::<i16>(1).unwrap().copy_from_slice(&[300, 30000, -7]);
+
+        let mut attributes = HashMap::new();
+        let ctx = FrameFilterContext::new("vol", &mut attributes);
+        let frame = VolumeFilter::new(2.0).filter_frame(frame, &ctx).unwrap().unwrap();
+        assert_eq!(frame.samples::<i16>(0).unwrap(), &[200, -200, 0]);
+        assert_eq!(frame.samples::<i16>(1).unwrap(), &[600, i16::MAX, -14]);
+    }
+
+    #[test]
+    fn test_from_db() {
+        assert!((VolumeFilter::from_db(0.0).gain() - 1.0).abs() < 1e-9);
+        assert!((VolumeFilter::from_db(20.0).gain() - 10.0).abs() < 1e-9);
+    }
+}
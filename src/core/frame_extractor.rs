@@ -0,0 +1,267 @@
+use crate::core::context::CodecContext;
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_planes::FramePlanes;
+use crate::core::stream_info::init_format_context;
+use crate::error::Error::{
+    Decoding, Demuxing, FrameConversion, FrameIndexOutOfRange, OpenDecoder, VideoStreamNotFound,
+};
+use crate::error::{
+    DecodingError, DecodingOperationError, DemuxingError, DemuxingOperationError, OpenDecoderError,
+    OpenDecoderOperationError, Result,
+};
+use ffmpeg_next::{Frame, Packet};
+use ffmpeg_sys_next::AVDiscard::AVDISCARD_ALL;
+use ffmpeg_sys_next::AVMediaType::AVMEDIA_TYPE_VIDEO;
+use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_RGB24;
+use ffmpeg_sys_next::{
+    av_frame_unref, av_packet_unref, av_read_frame, av_seek_frame, avcodec_alloc_context3, avcodec_find_decoder,
+    avcodec_flush_buffers, avcodec_open2, avcodec_parameters_to_context, avcodec_receive_frame, avcodec_send_packet,
+    AVFormatContext, AVStream, AVERROR, AVERROR_EOF, AVSEEK_FLAG_BACKWARD, AV_NOPTS_VALUE, AV_PKT_FLAG_DISCARD, EAGAIN,
+};
+use image::RgbImage;
+use log::error;
+use std::ptr::{null, null_mut};
+use std::time::Duration;
+
+/// Extracts the frame with index `frame_number` (counted from 0) of a video stream as an RGB image.
+///
+/// Frames are counted in presentation order, so B-frames are numbered where they are shown,
+/// not where they are stored. The packets of the stream are read first, without decoding, to
+/// find the timestamp of the requested frame, then the input is seeked to the keyframe before
+/// it and decoded forward to that exact frame. This stays exact with a variable frame rate,
+/// unlike converting the frame number to a timestamp.
+///
+/// Requires the `image` feature.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"video.mp4"`).
+/// - `stream_index`: The index of the video stream in the input.
+/// - `frame_number`: The index of the frame in the stream.
+///
+/// # Returns
+/// - `Ok(RgbImage)`: The decoded frame, converted to RGB at its original size.
+/// - `Err(Error::VideoStreamNotFound)` if the input has no video stream `stream_index`.
+/// - `Err(Error::FrameIndexOutOfRange)` if the stream has `frame_number` frames or less.
+/// - Any other error raised while opening, reading or decoding the input.
+///
+/// # Example
+/// ```rust
+/// // reproduce the frame an analysis tool flagged
+/// let image = extract_frame_by_index("video.mp4", 0, 1234).unwrap();
+/// image.save("frame_1234.png").unwrap();
+/// ```
+pub fn extract_frame_by_index(input: &str, stream_index: usize, frame_number: u64) -> Result<RgbImage> {
+    let in_fmt_ctx_box = init_format_context(input)?;
+    let fmt_ctx = in_fmt_ctx_box.fmt_ctx;
+
+    unsafe {
+        let nb_streams = (*fmt_ctx).nb_streams as usize;
+        if stream_index >= nb_streams
+            || (*(**(*fmt_ctx).streams.add(stream_index)).codecpar).codec_type != AVMEDIA_TYPE_VIDEO
+        {
+            error!("Input '{input}' has no video stream {stream_index}.");
+            return Err(VideoStreamNotFound(stream_index));
+        }
+        for i in (0..nb_streams).filter(|i| *i != stream_index) {
+            (**(*fmt_ctx).streams.add(i)).discard = AVDISCARD_ALL;
+        }
+
+        let mut timestamps = frame_timestamps(fmt_ctx, stream_index)?;
+        timestamps.sort_unstable();
+        let Some(&target) = usize::try_from(frame_number).ok().and_then(|index| timestamps.get(index)) else {
+            error!("Frame {frame_number} is beyond the {} frames of stream {stream_index} of '{input}'.", timestamps.len());
+            return Err(FrameIndexOutOfRange(frame_number, timestamps.len() as u64));
+        };
+
+        let mut decoder = FrameDecoder::open(*(*fmt_ctx).streams.add(stream_index))?;
+        // an open GOP can start before the keyframe the seek lands on, in which case the first
+        // decoded frame is already past the target: decode from the first frame instead
+        let frame = match decoder.decode_to(fmt_ctx, target, target, false)? {
+            Some(frame) => frame,
+            None => match decoder.decode_to(fmt_ctx, timestamps[0], target, true)? {
+                Some(frame) => frame,
+                None => {
+                    error!("Frame {frame_number} of stream {stream_index} of '{input}' could not be decoded.");
+                    return Err(FrameIndexOutOfRange(frame_number, timestamps.len() as u64));
+                }
+            },
+        };
+        to_rgb_image(&frame)
+    }
+}
+
+/// Reads the whole input and returns the presentation timestamps of the frames of `stream_index`,
+/// in decoding order. Packets without a `pts` use their `dts`.
+unsafe fn frame_timestamps(fmt_ctx: *mut AVFormatContext, stream_index: usize) -> Result<Vec<i64>> {
+    let mut timestamps = Vec::new();
+    let mut packet = Packet::empty();
+    loop {
+        let ret = av_read_frame(fmt_ctx, packet.as_mut_ptr());
+        if ret == AVERROR(EAGAIN) {
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+        if ret == AVERROR_EOF {
+            return Ok(timestamps);
+        }
+        if ret < 0 {
+            return Err(Demuxing(DemuxingOperationError::ReadFrameError(DemuxingError::from(ret))));
+        }
+
+        let pkt = packet.as_ptr();
+        // discarded packets (e.g. before the start of an edit list) are decoded but never shown
+        if (*pkt).stream_index as usize == stream_index && (*pkt).flags & AV_PKT_FLAG_DISCARD == 0 {
+            let ts = if (*pkt).pts != AV_NOPTS_VALUE { (*pkt).pts } else { (*pkt).dts };
+            if ts != AV_NOPTS_VALUE {
+                timestamps.push(ts);
+            }
+        }
+        av_packet_unref(packet.as_mut_ptr());
+    }
+}
+
+struct FrameDecoder {
+    dec_ctx: CodecContext,
+    stream_index: i32,
+}
+
+impl FrameDecoder {
+    unsafe fn open(stream: *mut AVStream) -> Result<Self> {
+        let codecpar = (*stream).codecpar;
+        let dec = avcodec_find_decoder((*codecpar).codec_id);
+        if dec.is_null() {
+            error!("No decoder for stream {}.", (*stream).index);
+            return Err(OpenDecoder(OpenDecoderOperationError::ContextAllocationError(OpenDecoderError::NotImplemented)));
+        }
+
+        let dec_ctx = avcodec_alloc_context3(dec);
+        if dec_ctx.is_null() {
+            return Err(OpenDecoder(OpenDecoderOperationError::ContextAllocationError(OpenDecoderError::OutOfMemory)));
+        }
+        let decoder = Self {
+            dec_ctx: CodecContext::new(dec_ctx),
+            stream_index: (*stream).index,
+        };
+
+        let ret = avcodec_parameters_to_context(dec_ctx, codecpar);
+        if ret < 0 {
+            return Err(OpenDecoder(OpenDecoderOperationError::ParameterApplicationError(OpenDecoderError::from(ret))));
+        }
+        (*dec_ctx).pkt_timebase = (*stream).time_base;
+
+        let ret = avcodec_open2(dec_ctx, dec, null_mut());
+        if ret < 0 {
+            return Err(OpenDecoder(OpenDecoderOperationError::DecoderOpenError(OpenDecoderError::from(ret))));
+        }
+        Ok(decoder)
+    }
+
+    /// Seeks to the keyframe at or before `seek_ts` and decodes forward to the frame shown at
+    /// `target`. Returns `None` if the first decoded frame is already past `target` (unless
+    /// `accept_later` is set), or if the stream ends first.
+    unsafe fn decode_to(
+        &mut self,
+        fmt_ctx: *mut AVFormatContext,
+        seek_ts: i64,
+        target: i64,
+        accept_later: bool,
+    ) -> Result<Option<Frame>> {
+        let ret = av_seek_frame(fmt_ctx, self.stream_index, seek_ts, AVSEEK_FLAG_BACKWARD);
+        if ret < 0 {
+            return Err(Demuxing(DemuxingOperationError::SeekFileError(DemuxingError::from(ret))));
+        }
+        let dec_ctx = self.dec_ctx.as_mut_ptr();
+        avcodec_flush_buffers(dec_ctx);
+
+        let mut packet = Packet::empty();
+        let mut frame = Frame::empty();
+        let mut before_target = false;
+        let mut draining = false;
+        loop {
+            if !draining {
+                let ret = av_read_frame(fmt_ctx, packet.as_mut_ptr());
+                if ret == AVERROR(EAGAIN) {
+                    std::thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                if ret < 0 && ret != AVERROR_EOF {
+                    return Err(Demuxing(DemuxingOperationError::ReadFrameError(DemuxingError::from(ret))));
+                }
+                draining = ret == AVERROR_EOF;
+                if !draining && (*packet.as_ptr()).stream_index != self.stream_index {
+                    av_packet_unref(packet.as_mut_ptr());
+                    continue;
+                }
+
+                let pkt = if draining { null() } else { packet.as_ptr() };
+                let ret = avcodec_send_packet(dec_ctx, pkt);
+                av_packet_unref(packet.as_mut_ptr());
+                if ret < 0 && ret != AVERROR_EOF {
+                    return Err(Decoding(DecodingOperationError::SendPacketError(DecodingError::from(ret))));
+                }
+            }
+
+            loop {
+                let ret = avcodec_receive_frame(dec_ctx, frame.as_mut_ptr());
+                if ret == AVERROR_EOF || (ret == AVERROR(EAGAIN) && draining) {
+                    return Ok(None);
+                }
+                if ret == AVERROR(EAGAIN) {
+                    break;
+                }
+                if ret < 0 {
+                    return Err(Decoding(DecodingOperationError::ReceiveFrameError(DecodingError::from(ret))));
+                }
+
+                let ts = match (*frame.as_ptr()).best_effort_timestamp {
+                    AV_NOPTS_VALUE => (*frame.as_ptr()).pts,
+                    ts => ts,
+                };
+                if ts == AV_NOPTS_VALUE || ts < target {
+                    before_target |= ts != AV_NOPTS_VALUE;
+                    av_frame_unref(frame.as_mut_ptr());
+                    continue;
+                }
+                if ts > target && !before_target && !accept_later {
+                    return Ok(None);
+                }
+                return Ok(Some(frame));
+            }
+        }
+    }
+}
+
+fn to_rgb_image(frame: &Frame) -> Result<RgbImage> {
+    let (width, height) = unsafe { ((*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+    let mut converter = FrameConverter::new();
+    let rgb = converter.convert(frame, AV_PIX_FMT_RGB24, width, height).map_err(FrameConversion)?;
+    let data = rgb.plane_u8(0).map_err(FrameConversion)?.concat();
+    RgbImage::from_raw(width as u32, height as u32, data)
+        .ok_or_else(|| FrameConversion(format!("Invalid {width}x{height} RGB frame")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::frame_reader::FrameReader;
+    use crate::error::Error;
+    use ffmpeg_sys_next::AVPixelFormat;
+
+    #[test]
+    fn test_extract_frame_by_index() {
+        // the same frame as decoding from the start and counting
+        let frame = FrameReader::new("test.mp4", AVMEDIA_TYPE_VIDEO)
+            .set_pixel_format(AVPixelFormat::AV_PIX_FMT_RGB24)
+            .nth(40)
+            .unwrap()
+            .unwrap();
+        let (width, height) = unsafe { ((*frame.as_ptr()).width as usize, (*frame.as_ptr()).height as usize) };
+
+        let image = extract_frame_by_index("test.mp4", 0, 40).unwrap();
+        assert_eq!((image.width() as usize, image.height() as usize), (width, height));
+        assert_eq!(image.into_raw(), frame.plane_u8(0).unwrap().concat());
+
+        assert!(matches!(extract_frame_by_index("test.mp4", 0, u64::MAX), Err(Error::FrameIndexOutOfRange(u64::MAX, _))));
+        assert!(matches!(extract_frame_by_index("test.mp4", 1, 0), Err(Error::VideoStreamNotFound(1))));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::core::context::ffmpeg_context::FfmpegContext;
+use crate::core::context::input::Input;
+use crate::core::context::output::Output;
+use crate::core::filter::frame_converter::FrameConverter;
+use crate::core::filter::frame_filter::FrameFilter;
+use crate::core::filter::frame_filter_context::FrameFilterContext;
+use crate::core::filter::frame_pipeline_builder::FramePipelineBuilder;
+use crate::core::scheduler::ffmpeg_scheduler::{FfmpegScheduler, Running};
+use crate::error::{Error, Result};
+use crate::util::ffmpeg_utils::av_err2str;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use ffmpeg_next::Frame;
+use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_VIDEO};
+use ffmpeg_sys_next::{av_frame_clone, av_frame_copy_props, av_frame_get_buffer, AVMediaType, AVPixelFormat};
+use std::time::Duration;
+
+/// Number of decoded frames buffered ahead of the consumer.
+const FRAME_QUEUE_SIZE: usize = 8;
+
+/// Reads the decoded frames of one stream of an input into Rust, e.g. to feed them to a
+/// computer vision model.
+///
+/// `FrameReader` runs the regular decoding pipeline in the background (so input options such
+/// as [`Input::set_start_time_us`] and [`Input::set_recording_time_us`] apply) and yields the
+/// frames through [`Iterator`]. Decoding starts on the first call to `next()` and is throttled
+/// to the speed of the consumer. Dropping the reader stops decoding and frees its resources.
+///
+/// The iterator yields `Err` if the input cannot be opened or decoding fails, then ends.
+///
+/// # Example
+/// ```rust,ignore
+/// let reader = FrameReader::new("test.mp4", AVMediaType::AVMEDIA_TYPE_VIDEO)
+///     .set_pixel_format(AVPixelFormat::AV_PIX_FMT_RGB24);
+/// for frame in reader {
+///     let frame = frame?;
+///     // run the model on the RGB frame
+/// }
+/// ```
+pub struct FrameReader {
+    input: Option<Input>,
+    media_type: AVMediaType,
+    stream_index: usize,
+    pixel_format: Option<AVPixelFormat>,
+
+    receiver: Option<Receiver<Frame>>,
+    scheduler: Option<FfmpegScheduler<Running>>,
+}
+
+impl FrameReader {
+    /// Creates a reader for the first stream of `media_type` (video or audio) of `input`.
+    pub fn new(input: impl Into<Input>, media_type: AVMediaType) -> Self {
+        Self {
+            input: Some(input.into()),
+            media_type,
+            stream_index: 0,
+            pixel_format: None,
+            receiver: None,
+            scheduler: None,
+        }
+    }
+
+    /// Reads the `stream_index`-th stream of the media type instead of the first one,
+    /// e.g. `1` for the second audio track.
+    pub fn set_stream_index(mut self, stream_index: usize) -> Self {
+        self.stream_index = stream_index;
+        self
+    }
+
+    /// Converts the video frames to `pixel_format` (e.g. `AV_PIX_FMT_RGB24`) instead of
+    /// yielding them in the decoder's format. Ignored for audio.
+    pub fn set_pixel_format(mut self, pixel_format: AVPixelFormat) -> Self {
+        self.pixel_format = Some(pixel_format);
+        self
+    }
+
+    fn start(&mut self, input: Input) -> Result<()> {
+        let specifier = match self.media_type {
+            AVMEDIA_TYPE_VIDEO => 'v',
+            AVMEDIA_TYPE_AUDIO => 'a',
+            media_type => {
+                return Err(Error::FrameFilterInit(format!(
+                    "FrameReader only reads video or audio streams, not {media_type:?}"
+                )))
+            }
+        };
+
+        let (sender, receiver) = crossbeam_channel::bounded(FRAME_QUEUE_SIZE);
+        let capture = CaptureFilter {
+            media_type: self.media_type,
+            pixel_format: self.pixel_format.filter(|_| self.media_type == AVMEDIA_TYPE_VIDEO),
+            sender,
+            converter: FrameConverter::new(),
+        };
+        let pipeline = FramePipelineBuilder::new(self.media_type).filter("frame_reader", Box::new(capture));
+
+        // the null muxer wraps the frames without encoding them
+        let output = Output::from("-")
+            .set_format("null")
+            .add_stream_map(format!("0:{specifier}:{}", self.stream_index))
+            .add_frame_pipeline(pipeline);
+
+        let scheduler = FfmpegContext::builder().input(input).output(output).build()?.start()?;
+        self.receiver = Some(receiver);
+        self.scheduler = Some(scheduler);
+        Ok(())
+    }
+
+    /// Waits for the pipeline to finish, returning its error if it failed.
+    fn finish(&mut self) -> Option<Result<Frame>> {
+        self.receiver = None;
+        self.scheduler.take()?.wait().err().map(Err)
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(input) = self.input.take() {
+            if let Err(e) = self.start(input) {
+                return Some(Err(e));
+            }
+        }
+
+        let receiver = self.receiver.as_ref()?;
+        loop {
+            match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(frame) => return Some(Ok(frame)),
+                Err(RecvTimeoutError::Timeout) => {
+                    if !self.scheduler.as_ref().is_some_and(|scheduler| scheduler.is_ended()) {
+                        continue;
+                    }
+                    // the pipeline is done: hand out what it queued before ending
+                    if let Ok(frame) = receiver.try_recv() {
+                        return Some(Ok(frame));
+                    }
+                    return self.finish();
+                }
+                Err(RecvTimeoutError::Disconnected) => return self.finish(),
+            }
+        }
+    }
+}
+
+impl Drop for FrameReader {
+    fn drop(&mut self) {
+        // unblock the pipeline if it waits for room in the queue, then stop it
+        self.receiver = None;
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.abort();
+        }
+    }
+}
+
+/// Sends a reference to every frame to the [`FrameReader`] and passes the frame on.
+struct CaptureFilter {
+    media_type: AVMediaType,
+    pixel_format: Option<AVPixelFormat>,
+    sender: Sender<Frame>,
+    converter: FrameConverter,
+}
+
+impl CaptureFilter {
+    fn capture(&mut self, frame: &Frame) -> std::result::Result<Frame, String> {
+        unsafe {
+            let Some(pixel_format) = self.pixel_format else {
+                let clone = av_frame_clone(frame.as_ptr());
+                if clone.is_null() {
+                    return Err("Failed to reference frame: Out of memory.".to_string());
+                }
+                return Ok(Frame::wrap(clone));
+            };
+
+            let mut converted = Frame::empty();
+            if converted.as_ptr().is_null() {
+                return Err("Failed to create frame: Out of memory.".to_string());
+            }
+            let dst = converted.as_mut_ptr();
+            (*dst).format = pixel_format as i32;
+            (*dst).width = (*frame.as_ptr()).width;
+            (*dst).height = (*frame.as_ptr()).height;
+            let ret = av_frame_get_buffer(dst, 0);
+            if ret < 0 {
+                return Err(format!("Failed to allocate frame buffer: {}", av_err2str(ret)));
+            }
+            let ret = av_frame_copy_props(dst, frame.as_ptr());
+            if ret < 0 {
+                return Err(format!("Failed to copy frame properties: {}", av_err2str(ret)));
+            }
+            self.converter.convert_into(frame, &mut converted)?;
+            Ok(converted)
+        }
+    }
+}
+
+impl FrameFilter for CaptureFilter {
+    fn media_type(&self) -> AVMediaType {
+        self.media_type
+    }
+
+    fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> std::result::Result<Option<Frame>, String> {
+        unsafe {
+            if frame.as_ptr().is_null() || frame.is_empty() {
+                return Ok(Some(frame));
+            }
+        }
+
+        let captured = self.capture(&frame)?;
+        // fails once the reader is dropped, which also stops the pipeline
+        let _ = self.sender.send(captured);
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_frames() {
+        let frames = FrameReader::new("test.mp4", AVMEDIA_TYPE_VIDEO)
+            .set_pixel_format(AVPixelFormat::AV_PIX_FMT_RGB24)
+            .take(5)
+            .collect::<Result<Vec<Frame>>>()
+            .unwrap();
+
+        assert_eq!(frames.len(), 5);
+        for frame in &frames {
+            assert_eq!(unsafe { (*frame.as_ptr()).format }, AVPixelFormat::AV_PIX_FMT_RGB24 as i32);
+        }
+    }
+}
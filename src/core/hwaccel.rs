@@ -1,8 +1,9 @@
+use crate::error::OpenInputError;
 use ffmpeg_sys_next::{
-    av_buffer_unref, av_dict_parse_string, av_hwdevice_ctx_create, av_hwdevice_ctx_create_derived,
-    av_hwdevice_find_type_by_name, av_hwdevice_get_type_name, av_hwdevice_iterate_types,
-    avcodec_get_hw_config, AVBufferRef, AVCodec, AVHWDeviceType, AVERROR,
-    AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, EINVAL, ENOMEM,
+    av_buffer_unref, av_dict_parse_string, av_get_pix_fmt, av_hwdevice_ctx_create, av_hwdevice_ctx_create_derived,
+    av_hwdevice_find_type_by_name, av_hwdevice_get_type_name, av_hwdevice_iterate_types, av_pix_fmt_desc_get,
+    avcodec_get_hw_config, avfilter_get_by_name, AVBufferRef, AVCodec, AVHWDeviceType, AVERROR,
+    AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, AV_PIX_FMT_FLAG_HWACCEL, EINVAL, ENOMEM,
 };
 use log::{error, warn};
 use std::ffi::{CStr, CString};
@@ -444,6 +445,49 @@ pub(crate) fn hw_device_get_by_name(name: &str) -> Option<HWDevice> {
     None
 }
 
+/// The GPU scalers able to scale frames in the hardware pixel format `format`, in order of
+/// preference.
+fn hw_scalers(format: &str) -> &'static [&'static str] {
+    match format {
+        "cuda" => &["scale_cuda", "scale_npp"],
+        "qsv" => &["scale_qsv"],
+        "vaapi" => &["scale_vaapi"],
+        "videotoolbox" => &["scale_vt"],
+        "vulkan" => &["scale_vulkan"],
+        _ => &[],
+    }
+}
+
+/// Returns the filter scaling the frames decoded in `hwaccel_output_format`: the first GPU
+/// scaler of this FFmpeg build matching the hardware format, or `"scale"` for frames in
+/// system memory (no output format, or a software one like `"nv12"`).
+pub(crate) fn hw_scaler_for_format(hwaccel_output_format: Option<&str>) -> crate::error::Result<&'static str> {
+    let Some(format) = hwaccel_output_format else {
+        return Ok("scale");
+    };
+    let format_cstr = CString::new(format)?;
+    let pix_fmt = unsafe { av_get_pix_fmt(format_cstr.as_ptr()) };
+    let desc = unsafe { av_pix_fmt_desc_get(pix_fmt) };
+    if desc.is_null() || unsafe { (*desc).flags } & AV_PIX_FMT_FLAG_HWACCEL as u64 == 0 {
+        return Ok("scale");
+    }
+
+    let scalers = hw_scalers(format);
+    let available = scalers.iter().find(|scaler| {
+        let Ok(name) = CString::new(**scaler) else {
+            return false;
+        };
+        unsafe { !avfilter_get_by_name(name.as_ptr()).is_null() }
+    });
+    match available {
+        Some(scaler) => Ok(scaler),
+        None => {
+            error!("No GPU scaler for '{format}' frames in this FFmpeg build, tried: {scalers:?}");
+            Err(OpenInputError::HwScalerUnavailable(format.to_string(), scalers.join(", ")).into())
+        }
+    }
+}
+
 fn add_hw_device(device: HWDevice) {
     let devices = HW_DEVICES.get_or_init(|| new_hw_devices());
     let mut devices = devices.lock().unwrap();
@@ -459,4 +503,16 @@ mod tests {
         let hwaccels = get_hwaccels();
         println!("{:?}", hwaccels);
     }
+
+    #[test]
+    fn test_hw_scaler_for_format() {
+        // frames in system memory are scaled on the CPU
+        assert_eq!(hw_scaler_for_format(None).unwrap(), "scale");
+        assert_eq!(hw_scaler_for_format(Some("nv12")).unwrap(), "scale");
+        // a hardware format without a GPU scaler
+        assert!(matches!(
+            hw_scaler_for_format(Some("mediacodec")),
+            Err(crate::error::Error::OpenInputStream(OpenInputError::HwScalerUnavailable(..)))
+        ));
+    }
 }
@@ -155,6 +155,19 @@ pub mod scheduler;
 /// (e.g., if the file can't be opened or if there is an issue reading the data).
 pub mod container_info;
 
+/// The **tags** module reads and describes the standard tags of a media file (title, artist,
+/// album, track number...) with [`Tags`](tags::Tags), independently of how the container
+/// names them (ID3 frames, Vorbis comments, MP4 atoms). Tags are written back with
+/// [`Output::set_tags`](crate::Output::set_tags).
+///
+/// # Example
+///
+/// ```rust
+/// let tags = read_tags("song.mp3").unwrap();
+/// println!("{:?} by {:?}", tags.title, tags.artist);
+/// ```
+pub mod tags;
+
 /// The **stream_info** module provides utilities to retrieve detailed information
 /// about media streams (video, audio, and more) from an input source (e.g., a local file
 /// path, an RTMP URL, etc.). It queries FFmpeg for metadata regarding stream types, codec
@@ -185,6 +198,10 @@ pub mod container_info;
 /// for info in all_infos {
 ///     println!("{:?}", info);
 /// }
+///
+/// // Retrieve the profile, level and chroma subsampling of the video stream 0 of "test.mp4"
+/// let details = get_video_codec_details("test.mp4", 0).unwrap();
+/// println!("{:?} level {:?}", details.profile, details.level);
 /// ```
 ///
 /// These helper functions return `Result<Option<StreamInfo>, Error>` or `Result<Vec<StreamInfo>, Error>`
@@ -203,6 +220,10 @@ pub mod stream_info;
 /// These functions can be used to programmatically discover devices before choosing one
 /// for capture or recording in an FFmpeg-based pipeline.
 ///
+/// [`device::get_input_device_formats`] lists the capture backends compiled into FFmpeg, and
+/// [`device::list_devices`] the devices of a given backend, to be opened with
+/// [`Input::from_device`](crate::Input::from_device).
+///
 /// # Examples
 ///
 /// ```rust
@@ -272,6 +293,11 @@ pub mod hwaccel;
 ///   encoders (e.g., H.264, AAC) recognized by FFmpeg.
 /// - [`get_decoders()`](codec::get_decoders): Returns a list of [`CodecInfo`](codec::CodecInfo) representing all
 ///   decoders (e.g., H.264, AAC) recognized by FFmpeg.
+/// - [`list_encoders()`](codec::list_encoders) / [`list_decoders()`](codec::list_decoders): The same lists,
+///   optionally restricted to one media type (e.g. only video encoders).
+/// - [`encoder_pixel_formats()`](codec::encoder_pixel_formats) /
+///   [`encoder_sample_formats()`](codec::encoder_sample_formats): The pixel or sample formats
+///   an encoder accepts.
 ///
 /// # Example
 ///
@@ -297,6 +323,7 @@ pub mod hwaccel;
 ///   - `media_type` (audio/video/subtitle, etc.)
 ///   - `codec_id` (internal FFmpeg ID)
 ///   - `codec_capabilities` (bitmask indicating codec features)
+///   - `hw_accelerated()` (hardware codec or hwaccel-capable)
 ///
 /// # Notes
 ///
@@ -307,6 +334,85 @@ pub mod hwaccel;
 ///   and any external libraries installed on the system.
 pub mod codec;
 
+/// The **remux** module provides a one-call helper to change the container of a media
+/// file without re-encoding its streams (the equivalent of `ffmpeg -i in -map 0 -c copy out`).
+///
+/// # Example
+///
+/// ```rust
+/// // Convert MKV to MP4, copying every stream as-is
+/// remux("input.mkv", "output.mp4").unwrap();
+/// ```
+///
+/// If one of the streams uses a codec the target container cannot store
+/// (e.g. Opus in AVI), an error is returned before anything is written.
+pub mod remux;
+
+/// The **clip** module transcodes a time range of a media file, seeking to the keyframe
+/// before the start and reading no further than the end, so short clips of long files are
+/// fast to cut.
+///
+/// # Example
+///
+/// ```rust
+/// // 10 seconds starting at 1h23m20s, starting exactly on that frame
+/// clip("movie.mkv", 5_000_000_000, 10_000_000, "clip.mp4").unwrap();
+/// ```
+pub mod clip;
+
+/// The **size_estimate** module estimates the size of an output file from its bitrates and
+/// duration before anything is encoded, e.g. to show the expected size in a UI.
+///
+/// # Example
+///
+/// ```rust
+/// let bytes = estimate_output_size(180_000_000, 2_000_000, 128_000);
+/// println!("~{} MB", bytes / 1_000_000);
+/// ```
+///
+/// [`FfmpegContextBuilder::estimated_size`](crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder::estimated_size)
+/// does the same from the configuration of each output.
+pub mod size_estimate;
+
+/// The **frame_reader** module streams the decoded frames of a media stream into Rust through
+/// an [`Iterator`], e.g. for ML preprocessing, optionally converted to a given pixel format.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let reader = FrameReader::new("test.mp4", AVMediaType::AVMEDIA_TYPE_VIDEO)
+///     .set_pixel_format(AVPixelFormat::AV_PIX_FMT_RGB24);
+/// for frame in reader.take(100) {
+///     let frame = frame.unwrap();
+///     // use the RGB frame
+/// }
+/// ```
+pub mod frame_reader;
+
+/// The **frame_extractor** module decodes a single frame of a video stream, selected by its
+/// index in presentation order, into an [`image::RgbImage`]. Requires the `image` feature.
+///
+/// # Example
+///
+/// ```rust
+/// let image = extract_frame_by_index("video.mp4", 0, 250).unwrap();
+/// image.save("frame_250.png").unwrap();
+/// ```
+#[cfg(feature = "image")]
+pub mod frame_extractor;
+
+/// The **verify** module checks the integrity of a media file by reading and decoding all of
+/// its packets, and reports the decode errors, corrupt frames and timestamp anomalies found
+/// in each stream.
+///
+/// # Example
+///
+/// ```rust
+/// let report = verify("archive.mkv").unwrap();
+/// println!("clean: {}", report.is_clean());
+/// ```
+pub mod verify;
+
 /// The **filter** module provides a flexible framework for custom frame processing
 /// within the FFmpeg pipeline, along with the ability to query FFmpeg's built-in filters.
 /// It introduces the [`FrameFilter`](filter::frame_filter::FrameFilter) trait, which defines how to apply transformations
@@ -419,6 +525,7 @@ pub mod filter;
 static INIT_FFMPEG: std::sync::Once = std::sync::Once::new();
 
 extern "C" fn cleanup() {
+    flush_ffmpeg_log();
     unsafe {
         hwaccel::hw_device_free_all();
         ffmpeg_sys_next::avformat_network_deinit();
@@ -462,17 +569,147 @@ pub type VaListType = *mut ffmpeg_sys_next::__va_list_tag_powerpc;
 #[cfg(target_arch = "s390x")]
 pub type VaListType = *mut ffmpeg_sys_next::__va_list_tag_s390x;
 
+/// Most verbose FFmpeg log level forwarded to the `log` crate, see [`init_logging`].
+static FFMPEG_LOG_LEVEL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(ffmpeg_sys_next::AV_LOG_INFO);
+
+static FFMPEG_LOG_STATE: std::sync::Mutex<FfmpegLogState> = std::sync::Mutex::new(FfmpegLogState::new());
+
+/// FFmpeg logs a line in several calls at times, and often repeats the same line.
+struct FfmpegLogState {
+    print_prefix: libc::c_int,
+    // the line being assembled
+    line: String,
+    last_line: String,
+    last_level: libc::c_int,
+    repeated: usize,
+}
+
+impl FfmpegLogState {
+    const fn new() -> Self {
+        Self { print_prefix: 1, line: String::new(), last_line: String::new(), last_level: 0, repeated: 0 }
+    }
+
+    /// Adds a `fragment` of a line logged at `level`, returning the lines to log: none until
+    /// the line ends or while it repeats the last one, else the pending repeat count and the
+    /// line.
+    fn push(&mut self, level: libc::c_int, fragment: &str) -> Vec<(log::Level, String)> {
+        self.line.push_str(fragment);
+        // wait for the end of the line
+        if !self.line.ends_with(['\n', '\r']) {
+            return vec![];
+        }
+        let line = std::mem::take(&mut self.line);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.is_empty() {
+            return vec![];
+        }
+
+        if line == self.last_line && level == self.last_level {
+            self.repeated += 1;
+            return vec![];
+        }
+        let mut lines: Vec<_> = self.take_repeated().into_iter().collect();
+        lines.push((ffmpeg_level_to_log_level(level), format!("FFmpeg: {line}")));
+        self.last_line = line.to_string();
+        self.last_level = level;
+        lines
+    }
+
+    /// Returns the line reporting how many times the last line was repeated, if it was.
+    fn take_repeated(&mut self) -> Option<(log::Level, String)> {
+        if self.repeated == 0 {
+            return None;
+        }
+        let repeated = std::mem::take(&mut self.repeated);
+        let line = format!("FFmpeg: Last message repeated {repeated} times");
+        Some((ffmpeg_level_to_log_level(self.last_level), line))
+    }
+
+    /// Returns the pending repeat count line, and forgets the last line, so that it is
+    /// reported again if it comes back.
+    fn flush(&mut self) -> Option<(log::Level, String)> {
+        let repeated = self.take_repeated();
+        self.last_line.clear();
+        repeated
+    }
+}
+
+/// Routes FFmpeg's own diagnostics up to `level` to the [`log`] crate.
+///
+/// FFmpeg messages are always forwarded to `log` (prefixed with `FFmpeg:`), by default up to
+/// the `Info` level. This function changes that threshold, e.g. `LevelFilter::Debug` also
+/// forwards FFmpeg's verbose messages and `LevelFilter::Off` silences FFmpeg. Levels are
+/// mapped as follows: panic, fatal and error to `Error`, warning to `Warn`, info to `Info`,
+/// verbose to `Debug`, debug and trace to `Trace`.
+///
+/// Repeated identical lines are reported once, followed by a "Last message repeated N times"
+/// line, like FFmpeg's own logger does.
+///
+/// # Example
+/// ```rust,ignore
+/// env_logger::init();
+/// ez_ffmpeg::core::init_logging(log::LevelFilter::Warn);
+/// ```
+pub fn init_logging(level: log::LevelFilter) {
+    initialize_ffmpeg();
+    let av_level = match level {
+        log::LevelFilter::Off => ffmpeg_sys_next::AV_LOG_QUIET,
+        log::LevelFilter::Error => ffmpeg_sys_next::AV_LOG_ERROR,
+        log::LevelFilter::Warn => ffmpeg_sys_next::AV_LOG_WARNING,
+        log::LevelFilter::Info => ffmpeg_sys_next::AV_LOG_INFO,
+        log::LevelFilter::Debug => ffmpeg_sys_next::AV_LOG_VERBOSE,
+        log::LevelFilter::Trace => ffmpeg_sys_next::AV_LOG_TRACE,
+    };
+    FFMPEG_LOG_LEVEL.store(av_level, std::sync::atomic::Ordering::Relaxed);
+    // lets FFmpeg skip formatting messages nobody will see
+    unsafe { ffmpeg_sys_next::av_log_set_level(av_level) };
+}
+
+fn ffmpeg_level_to_log_level(level: libc::c_int) -> log::Level {
+    if level <= ffmpeg_sys_next::AV_LOG_ERROR {
+        log::Level::Error
+    } else if level <= ffmpeg_sys_next::AV_LOG_WARNING {
+        log::Level::Warn
+    } else if level <= ffmpeg_sys_next::AV_LOG_INFO {
+        log::Level::Info
+    } else if level <= ffmpeg_sys_next::AV_LOG_VERBOSE {
+        log::Level::Debug
+    } else {
+        log::Level::Trace
+    }
+}
+
+/// Returns whether FFmpeg messages of `level` are forwarded, see [`init_logging`].
+fn ffmpeg_level_forwarded(level: libc::c_int) -> bool {
+    level <= FFMPEG_LOG_LEVEL.load(std::sync::atomic::Ordering::Relaxed)
+}
+
 unsafe extern "C" fn ffmpeg_log_callback(
     ptr: *mut libc::c_void,
     level: libc::c_int,
     fmt: *const libc::c_char,
     args: VaListType,
 ) {
+    if !ffmpeg_level_forwarded(level) {
+        return;
+    }
+    // a panic (e.g. in the logger implementation) must not unwind into FFmpeg
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        forward_ffmpeg_log(ptr, level, fmt, args)
+    }));
+}
+
+unsafe fn forward_ffmpeg_log(
+    ptr: *mut libc::c_void,
+    level: libc::c_int,
+    fmt: *const libc::c_char,
+    args: VaListType,
+) {
+    // the state is consistent between statements, so a poisoned lock can be reused
+    let mut state = FFMPEG_LOG_STATE.lock().unwrap_or_else(|e| e.into_inner());
+
     // Create a fixed-size buffer to hold the formatted log message.
     let mut buffer = [0u8; 1024];
-    // 'print_prefix' is used internally by av_log_format_line to decide whether to print a prefix.
-    let mut print_prefix = 1;
-
     // Call FFmpeg's av_log_format_line to format the variable arguments into the buffer.
     ffmpeg_sys_next::av_log_format_line(
         ptr,
@@ -481,22 +718,20 @@ unsafe extern "C" fn ffmpeg_log_callback(
         args,
         buffer.as_mut_ptr() as *mut libc::c_char,
         buffer.len() as libc::c_int,
-        &mut print_prefix,
+        &mut state.print_prefix,
     );
+    let fragment = std::ffi::CStr::from_ptr(buffer.as_ptr() as *const libc::c_char).to_string_lossy();
+    for (level, line) in state.push(level, &fragment) {
+        log::log!(level, "{line}");
+    }
+}
 
-    // Convert the C string in the buffer to a Rust &str.
-    if let Ok(msg) = std::ffi::CStr::from_ptr(buffer.as_ptr() as *const libc::c_char).to_str() {
-        // Trim any trailing newline characters (\n or \r).
-        let trimmed_msg = msg.trim_end_matches(|c| c == '\n' || c == '\r');
-
-        // Map FFmpeg log levels to the corresponding Rust log levels.
-        if level <= ffmpeg_sys_next::AV_LOG_ERROR {
-            log::error!("FFmpeg: {}", trimmed_msg);
-        } else if level <= ffmpeg_sys_next::AV_LOG_WARNING {
-            log::warn!("FFmpeg: {}", trimmed_msg);
-        } else if level <= ffmpeg_sys_next::AV_LOG_INFO {
-            log::info!("FFmpeg: {}", trimmed_msg);
-        }
+/// Reports a pending "Last message repeated N times" line, called when a job ends and at
+/// exit, since no further FFmpeg line may come to report it.
+pub(crate) fn flush_ffmpeg_log() {
+    let mut state = FFMPEG_LOG_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((level, line)) = state.flush() {
+        log::log!(level, "{line}");
     }
 }
 
@@ -511,3 +746,59 @@ fn initialize_ffmpeg() {
         log::info!("FFmpeg initialized.");
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::{AV_LOG_DEBUG, AV_LOG_ERROR, AV_LOG_INFO, AV_LOG_VERBOSE, AV_LOG_WARNING};
+    use log::Level;
+
+    fn line(level: Level, line: &str) -> Vec<(Level, String)> {
+        vec![(level, format!("FFmpeg: {line}"))]
+    }
+
+    #[test]
+    fn test_ffmpeg_log_lines() {
+        let mut state = FfmpegLogState::new();
+        // a line logged in several calls
+        assert!(state.push(AV_LOG_INFO, "Stream #0:0: ").is_empty());
+        assert!(state.push(AV_LOG_INFO, "Video: h264").is_empty());
+        assert_eq!(state.push(AV_LOG_INFO, "\n"), line(Level::Info, "Stream #0:0: Video: h264"));
+        assert!(state.push(AV_LOG_INFO, "\n").is_empty());
+
+        // a line repeated 3 more times, then another line
+        assert_eq!(state.push(AV_LOG_WARNING, "late frame\n"), line(Level::Warn, "late frame"));
+        for _ in 0..3 {
+            assert!(state.push(AV_LOG_WARNING, "late frame\n").is_empty());
+        }
+        let mut lines = line(Level::Warn, "Last message repeated 3 times");
+        lines.extend(line(Level::Info, "done"));
+        assert_eq!(state.push(AV_LOG_INFO, "done\n"), lines);
+
+        // the same text at another level is another line
+        assert_eq!(state.push(AV_LOG_ERROR, "done\n"), line(Level::Error, "done"));
+        assert!(state.push(AV_LOG_ERROR, "done\n").is_empty());
+
+        // flushing reports the pending repeat, and the line again after it
+        assert_eq!(state.flush(), line(Level::Error, "Last message repeated 1 times").pop());
+        assert_eq!(state.flush(), None);
+        assert_eq!(state.push(AV_LOG_ERROR, "done\r"), line(Level::Error, "done"));
+    }
+
+    #[test]
+    fn test_ffmpeg_log_levels() {
+        assert_eq!(ffmpeg_level_to_log_level(AV_LOG_ERROR), Level::Error);
+        assert_eq!(ffmpeg_level_to_log_level(AV_LOG_WARNING), Level::Warn);
+        assert_eq!(ffmpeg_level_to_log_level(AV_LOG_INFO), Level::Info);
+        assert_eq!(ffmpeg_level_to_log_level(AV_LOG_VERBOSE), Level::Debug);
+        assert_eq!(ffmpeg_level_to_log_level(AV_LOG_DEBUG), Level::Trace);
+
+        init_logging(log::LevelFilter::Off);
+        let silenced = !ffmpeg_level_forwarded(ffmpeg_sys_next::AV_LOG_PANIC);
+        init_logging(log::LevelFilter::Warn);
+        let (warning, info) = (ffmpeg_level_forwarded(AV_LOG_WARNING), ffmpeg_level_forwarded(AV_LOG_INFO));
+        init_logging(log::LevelFilter::Info);
+        assert!(silenced);
+        assert!(warning && !info);
+    }
+}
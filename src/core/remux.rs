@@ -0,0 +1,165 @@
+use crate::core::context::ffmpeg_context::FfmpegContext;
+use crate::core::context::output::Output;
+use crate::core::stream_info::init_format_context;
+use crate::error::{OpenOutputError, Result};
+use ffmpeg_sys_next::AVMediaType::{
+    AVMEDIA_TYPE_ATTACHMENT, AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_DATA, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO,
+};
+use ffmpeg_sys_next::{av_guess_format, avcodec_get_name, avformat_query_codec, FF_COMPLIANCE_NORMAL};
+use log::warn;
+use std::ffi::{CStr, CString};
+use std::ptr::null;
+
+/// Remuxes `input` into `output` without re-encoding (stream copy).
+///
+/// All video, audio and subtitle streams of the input are copied as-is, the
+/// output container is chosen from the extension of `output` (e.g. `.mp4`, `.mkv`).
+/// Data and attachment streams are copied unless the target container reports it cannot
+/// store their codec, in which case they are skipped with a warning. Timecode (`tmcd`)
+/// tracks have no codec and are skipped by containers with a fixed list of codecs, like
+/// MP4 and MOV.
+///
+/// # Arguments
+/// - `input`: The path or URL of the media to read (e.g., `"video.mkv"`).
+/// - `output`: The path of the file to write (e.g., `"video.mp4"`).
+///
+/// # Returns
+/// - `Ok(())` once the output has been fully written.
+/// - `Err(OpenOutputError::CodecNotSupportedByContainer)` if a video, audio or subtitle
+///   codec cannot be stored in the target container (e.g. Opus in AVI).
+/// - Any other error raised while opening, muxing or writing the files.
+///
+/// # Example
+/// ```rust
+/// // Convert MKV to MP4 without re-encoding
+/// remux("video.mkv", "video.mp4").unwrap();
+/// ```
+pub fn remux(input: &str, output: &str) -> Result<()> {
+    let streams = {
+        let in_fmt_ctx_box = init_format_context(input)?;
+        unsafe {
+            let fmt_ctx = in_fmt_ctx_box.fmt_ctx;
+            (0..(*fmt_ctx).nb_streams as usize)
+                .map(|i| {
+                    let codecpar = (**(*fmt_ctx).streams.add(i)).codecpar;
+                    ((*codecpar).codec_type, (*codecpar).codec_id)
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let output_cstr = CString::new(output)?;
+    let oformat = unsafe { av_guess_format(null(), output_cstr.as_ptr(), null()) };
+    if oformat.is_null() {
+        return Err(OpenOutputError::FormatUnsupported(output.to_string()).into());
+    }
+    let format_name = unsafe { CStr::from_ptr((*oformat).name).to_string_lossy().into_owned() };
+
+    let mut remux_output = Output::from(output);
+    for (index, (codec_type, codec_id)) in streams.into_iter().enumerate() {
+        let required = match codec_type {
+            AVMEDIA_TYPE_VIDEO | AVMEDIA_TYPE_AUDIO | AVMEDIA_TYPE_SUBTITLE => true,
+            AVMEDIA_TYPE_DATA | AVMEDIA_TYPE_ATTACHMENT => false,
+            _ => continue,
+        };
+
+        // 1: supported, 0: not supported, < 0: the container cannot tell
+        let supported = unsafe { avformat_query_codec(oformat, codec_id, FF_COMPLIANCE_NORMAL as i32) };
+        if supported == 0 {
+            let codec_name = unsafe { CStr::from_ptr(avcodec_get_name(codec_id)).to_string_lossy().into_owned() };
+            if required {
+                return Err(OpenOutputError::CodecNotSupportedByContainer(codec_name, format_name).into());
+            }
+            warn!("Stream {index} ({codec_name}) is not supported by the '{format_name}' container, skipping.");
+            continue;
+        }
+
+        remux_output = remux_output.add_stream_map_with_copy(format!("0:{index}"));
+    }
+
+    FfmpegContext::builder()
+        .input(input)
+        .output(remux_output)
+        .build()?
+        .start()?
+        .wait()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_remux() {
+        remux("test.mp4", "output.mkv").unwrap();
+        std::fs::remove_file("output.mkv").unwrap();
+    }
+
+    #[test]
+    fn test_remux_unsupported_codec() {
+        // Opus audio, which AVI has no tag for (the native encoder is experimental)
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("remux_opus.mkv")
+                    .add_stream_map("0:a")
+                    .set_audio_codec("opus")
+                    .set_audio_codec_opt("strict", "experimental")
+                    .set_audio_sample_rate(48000)
+                    .set_recording_time_us(1_000_000),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        let result = remux("remux_opus.mkv", "remux_opus.avi");
+        std::fs::remove_file("remux_opus.mkv").unwrap();
+        assert!(
+            matches!(
+                result,
+                Err(Error::OpenOutput(OpenOutputError::CodecNotSupportedByContainer(ref codec, ref format)))
+                    if codec == "opus" && format == "avi"
+            ),
+            "{result:?}"
+        );
+        assert!(!std::path::Path::new("remux_opus.avi").exists());
+    }
+
+    #[test]
+    fn test_remux_data_track() {
+        use crate::core::stream_info::{find_all_stream_infos, StreamInfo};
+
+        // the MOV muxer writes a timecode (tmcd) data track for a video stream with a timecode
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("remux_tmcd.mov")
+                    .add_stream_map_with_copy("0:v")
+                    .add_stream_map_with_copy("0:a")
+                    .set_stream_tag(0, "timecode", "01:00:00:00")
+                    .set_recording_time_us(1_000_000),
+            )
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+        let infos = find_all_stream_infos("remux_tmcd.mov").unwrap();
+        assert!(infos.iter().any(|info| matches!(info, StreamInfo::Data { .. })), "{infos:?}");
+
+        // the tmcd track has no codec, the MOV muxer has no tag for it: it is skipped, while
+        // the video and audio are copied
+        remux("remux_tmcd.mov", "remux_tmcd_copy.mov").unwrap();
+        let copied = find_all_stream_infos("remux_tmcd_copy.mov").unwrap();
+        assert!(matches!(copied[0], StreamInfo::Video { .. }), "{copied:?}");
+        assert!(matches!(copied[1], StreamInfo::Audio { .. }), "{copied:?}");
+
+        std::fs::remove_file("remux_tmcd.mov").unwrap();
+        std::fs::remove_file("remux_tmcd_copy.mov").unwrap();
+    }
+}
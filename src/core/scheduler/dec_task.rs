@@ -1,8 +1,9 @@
 use crate::core::context::decoder_stream::DecoderStream;
+use crate::core::context::input::ErrorResilience;
 use crate::core::context::obj_pool::ObjPool;
 use crate::core::context::{null_frame, CodecContext, FrameBox, FrameData, PacketBox};
 use crate::core::scheduler::ffmpeg_scheduler::{
-    packet_is_null, set_scheduler_error, wait_until_not_paused, STATUS_END,
+    packet_is_null, set_scheduler_error, set_scheduler_error_deferred, wait_until_not_paused, STATUS_END,
 };
 use crate::error::DecodingOperationError::DecodeSubtitleError;
 use crate::error::Error::{Bug, Decoding, OpenDecoder};
@@ -19,15 +20,16 @@ use ffmpeg_next::packet::{Mut, Ref};
 use ffmpeg_next::{Frame, Packet};
 use ffmpeg_sys_next::AVHWDeviceType::AV_HWDEVICE_TYPE_QSV;
 use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO};
+use ffmpeg_sys_next::AVDiscard;
 use ffmpeg_sys_next::AVRounding::AV_ROUND_UP;
 use ffmpeg_sys_next::AVSubtitleType::SUBTITLE_BITMAP;
 #[cfg(not(feature = "docs-rs"))]
 use ffmpeg_sys_next::{av_channel_layout_copy, AV_CODEC_FLAG_COPY_OPAQUE};
-use ffmpeg_sys_next::{av_buffer_create, av_buffer_ref, av_calloc, av_dict_set, av_frame_apply_cropping, av_frame_copy_props, av_frame_move_ref, av_frame_ref, av_frame_unref, av_free, av_freep, av_gcd, av_hwdevice_get_type_name, av_hwframe_transfer_data, av_inv_q, av_mallocz, av_memdup, av_mul_q, av_opt_set_dict2, av_pix_fmt_desc_get, av_rescale_delta, av_rescale_q, av_rescale_q_rnd, av_strdup, avcodec_alloc_context3, avcodec_decode_subtitle2, avcodec_default_get_buffer2, avcodec_flush_buffers, avcodec_free_context, avcodec_get_hw_config, avcodec_open2, avcodec_parameters_to_context, avcodec_receive_frame, avcodec_send_packet, avsubtitle_free, AVCodec, AVCodecContext, AVDictionary, AVFrame, AVHWDeviceType, AVMediaType, AVPixelFormat, AVRational, AVSubtitle, AVSubtitleRect, AVERROR, AVERROR_EOF, AVPALETTE_SIZE, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, AV_FRAME_CROP_UNALIGNED, AV_FRAME_FLAG_CORRUPT, AV_NOPTS_VALUE, AV_PIX_FMT_FLAG_HWACCEL, AV_TIME_BASE_Q, EAGAIN, EINVAL, ENOMEM};
+use ffmpeg_sys_next::{av_buffer_create, av_buffer_ref, av_calloc, av_dict_free, av_dict_get, av_dict_set, av_frame_apply_cropping, av_frame_copy_props, av_frame_move_ref, av_frame_ref, av_frame_unref, av_free, av_freep, av_gcd, av_hwdevice_get_type_name, av_hwframe_transfer_data, av_inv_q, av_mallocz, av_memdup, av_mul_q, av_opt_set_dict2, av_pix_fmt_desc_get, av_rescale_delta, av_rescale_q, av_rescale_q_rnd, av_strdup, avcodec_alloc_context3, avcodec_decode_subtitle2, avcodec_default_get_buffer2, avcodec_flush_buffers, avcodec_free_context, avcodec_get_hw_config, avcodec_open2, avcodec_parameters_to_context, avcodec_receive_frame, avcodec_send_packet, avsubtitle_free, AVCodec, AVCodecContext, AVDictionary, AVDictionaryEntry, AVFrame, AVHWDeviceType, AVMediaType, AVPixelFormat, AVRational, AVSubtitle, AVSubtitleRect, AVERROR, AVERROR_EOF, AVPALETTE_SIZE, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, AV_DICT_IGNORE_SUFFIX, AV_FRAME_CROP_UNALIGNED, AV_FRAME_FLAG_CORRUPT, AV_NOPTS_VALUE, AV_PIX_FMT_FLAG_HWACCEL, AV_TIME_BASE_Q, EAGAIN, EINVAL, ENOMEM};
 use log::{debug, error, info, trace, warn};
 use std::ffi::{c_void, CStr, CString};
 use std::ptr::{null, null_mut};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 use crate::util::ffmpeg_utils::av_err2str;
@@ -70,7 +72,9 @@ pub(crate) fn dec_init(
     dec_open(dp_arc.clone(), dec_stream, null_mut())?;
 
     let senders = dec_stream.take_dsts();
-    let exit_on_error = exit_on_error.unwrap_or(false);
+    let error_resilience = dec_stream.error_resilience;
+    // Strict turns every decoding error into a fatal one, like exit_on_error does.
+    let exit_on_error = exit_on_error.unwrap_or(false) || error_resilience == ErrorResilience::Strict;
 
     let decoder_name = unsafe {std::str::from_utf8_unchecked(CStr::from_ptr((*dec_stream.codec.as_ptr()).name).to_bytes())};
 
@@ -201,11 +205,20 @@ pub(crate) fn dec_init(
                         0.0
                     };
                     let max_error_rate = 2.0 / 3.0;
-                    if err_rate > max_error_rate {
+                    if err_rate > max_error_rate && error_resilience == ErrorResilience::Normal {
                         error!("Decoder error rate {err_rate} exceeds maximum {max_error_rate}");
-                        // ret = FFMPEG_ERROR_RATE_EXCEEDED;
+                        set_scheduler_error_deferred(
+                            &scheduler_result,
+                            Decoding(DecodingOperationError::ErrorRateExceeded(
+                                dp.dec.decode_errors,
+                                dp.dec.frames_decoded,
+                            )),
+                        );
                     } else if err_rate != 0.0 {
-                        debug!("Decoder error rate {err_rate}");
+                        warn!(
+                            "{} packets could not be decoded and were skipped, error rate {err_rate:.3}",
+                            dp.dec.decode_errors
+                        );
                     }
                 }
             }
@@ -256,7 +269,7 @@ unsafe fn transcode_subtitles(
         };
         (*frame.as_mut_ptr()).pts = (*packet_box.packet.as_ptr()).pts;
         (*frame.as_mut_ptr()).time_base = (*packet_box.packet.as_ptr()).time_base;
-        (*frame.as_mut_ptr()).opaque = PacketOpaque::PktOpaqueSubHeartbeat as i32 as *mut c_void;
+        (*frame.as_mut_ptr()).opaque = FrameOpaque::FrameOpaqueSubHeartbeat as i32 as *mut c_void;
 
         let frame_box = dec_frame_to_box(dp_arc, frame);
 
@@ -292,7 +305,7 @@ unsafe fn transcode_subtitles(
     }
 
     let mut packet_is_eof = false;
-    if packet_is_null(&packet_box.packet) || (*packet_box.packet.as_ptr()).stream_index >= 0 {
+    if packet_is_null(&packet_box.packet) || (*packet_box.packet.as_ptr()).stream_index < 0 {
         let Ok(packet) = packet_pool.get() else {
             return Err(Decoding(DecodingOperationError::PacketAllocationError(
                 DecodingError::OutOfMemory,
@@ -304,18 +317,19 @@ unsafe fn transcode_subtitles(
 
     let dp = dp_arc.clone();
     let mut dp = dp.lock().unwrap();
-    let subtitle = null_mut();
-    let got_output = null_mut();
+    let mut decoded: AVSubtitle = std::mem::zeroed();
+    let subtitle: *mut AVSubtitle = &mut decoded;
+    let mut got_output = 0;
     let ret = avcodec_decode_subtitle2(
         dp.dec_ctx.as_mut_ptr(),
         subtitle,
-        got_output,
+        &mut got_output,
         packet_box.packet.as_mut_ptr(),
     );
     packet_pool.release(packet_box.packet);
     if ret < 0 {
         error!("Error decoding subtitles: {}", av_err2str(ret));
-        dp.dec.decode_errors = dp.dec.decode_errors + 1;
+        dp.count_decode_error();
         return if exit_on_error {
             Err(Decoding(DecodeSubtitleError(DecodingError::from(ret))))
         } else {
@@ -323,7 +337,7 @@ unsafe fn transcode_subtitles(
         };
     }
 
-    if *got_output == 0 {
+    if got_output == 0 {
         return if !packet_is_eof {
             Ok(())
         } else {
@@ -615,6 +629,7 @@ unsafe fn dec_frame_to_box(dp_arc: Arc<Mutex<DecoderParameter>>, frame: Frame) -
             bits_per_raw_sample: (*dec_ctx).bits_per_raw_sample,
             input_stream_width: (*dec_ctx).width,
             input_stream_height: (*dec_ctx).height,
+            sample_aspect_ratio: (*dec_ctx).sample_aspect_ratio,
             subtitle_header_size: (*dec_ctx).subtitle_header_size,
             subtitle_header: (*dec_ctx).subtitle_header,
             fg_input_index: usize::MAX,
@@ -624,7 +639,7 @@ unsafe fn dec_frame_to_box(dp_arc: Arc<Mutex<DecoderParameter>>, frame: Frame) -
 }
 
 #[repr(i32)]
-enum PacketOpaque {
+pub(crate) enum PacketOpaque {
     PktOpaqueSubHeartbeat = 1,
     PktOpaqueFixSubDuration,
 }
@@ -671,17 +686,59 @@ fn dec_open(
         (*dec_ctx).get_format = Some(get_format_callback);
         (*dec_ctx).get_buffer2 = Some(get_buffer_callback);
         (*dec_ctx).pkt_timebase = dec_stream.time_base;
+        if dec_stream.keyframes_only {
+            (*dec_ctx).skip_frame = AVDiscard::AVDISCARD_NONKEY;
+        }
 
         let mut dec_opts: *mut AVDictionary = std::ptr::null_mut();
         let opt_key = CString::new("threads".to_string()).unwrap();
         let opt_val = CString::new("auto".to_string()).unwrap();
         av_dict_set(&mut dec_opts, opt_key.as_ptr(), opt_val.as_ptr(), 0);
 
+        // set before the user's decoder options, which can still override them
+        let resilience_opts: &[(&str, &str)] = match dec_stream.error_resilience {
+            ErrorResilience::Strict => &[("err_detect", "crccheck+bitstream+buffer+explode")],
+            ErrorResilience::Normal => &[],
+            ErrorResilience::Maximum => &[("err_detect", "ignore_err"), ("ec", "guess_mvs+deblock+favor_inter")],
+        };
+        for (key, value) in resilience_opts {
+            let opt_key = CString::new(*key).unwrap();
+            let opt_val = CString::new(*value).unwrap();
+            av_dict_set(&mut dec_opts, opt_key.as_ptr(), opt_val.as_ptr(), 0);
+        }
+
+        if let Some(decoder_opts) = &dec_stream.decoder_opts {
+            for (key, value) in decoder_opts {
+                if key.as_bytes() == b"lowres" {
+                    // only meaningful for video, audio decoders would just clamp it to 0
+                    if dec_stream.codec_type != AVMEDIA_TYPE_VIDEO {
+                        continue;
+                    }
+                    let max_lowres = (*dec_stream.codec.as_ptr()).max_lowres as i32;
+                    let lowres = value.to_str().ok().and_then(|value| value.parse::<i32>().ok());
+                    if !matches!(lowres, Some(lowres) if (0..=max_lowres).contains(&lowres)) {
+                        av_dict_free(&mut dec_opts);
+                        avcodec_free_context(&mut dec_ctx);
+                        error!(
+                            "Invalid lowres '{}' for decoder '{}', the maximum supported value is {max_lowres}.",
+                            value.to_string_lossy(),
+                            CStr::from_ptr((*dec_stream.codec.as_ptr()).name).to_string_lossy()
+                        );
+                        return Err(OpenDecoder(OpenDecoderOperationError::ParameterApplicationError(
+                            OpenDecoderError::InvalidArgument,
+                        )));
+                    }
+                }
+                av_dict_set(&mut dec_opts, key.as_ptr(), value.as_ptr(), 0);
+            }
+        }
+
         {
             let dp_arc_clone = dp_arc.clone();
             let mut dp = dp_arc_clone.lock().unwrap();
             ret = hw_device_setup_for_decode(&mut dp, dec_stream.codec.as_ptr(), dec_ctx);
             if ret < 0 {
+                av_dict_free(&mut dec_opts);
                 avcodec_free_context(&mut dec_ctx);
                 error!("Hardware device setup failed for decoder: {}", av_err2str(ret));
                 return Err(OpenDecoder(OpenDecoderOperationError::HwSetupError(
@@ -691,6 +748,20 @@ fn dec_open(
         }
 
         ret = av_opt_set_dict2(dec_ctx as *mut c_void, &mut dec_opts, ffmpeg_sys_next::AV_OPT_SEARCH_CHILDREN);
+        // the options left in the dictionary were not recognized by the decoder
+        let mut entry: *mut AVDictionaryEntry = null_mut();
+        loop {
+            entry = av_dict_get(dec_opts, null(), entry, AV_DICT_IGNORE_SUFFIX);
+            if entry.is_null() {
+                break;
+            }
+            warn!(
+                "Decoder option '{}' is not supported by decoder '{}', ignoring it.",
+                CStr::from_ptr((*entry).key).to_string_lossy(),
+                CStr::from_ptr((*dec_stream.codec.as_ptr()).name).to_string_lossy()
+            );
+        }
+        av_dict_free(&mut dec_opts);
         if ret < 0 {
             avcodec_free_context(&mut dec_ctx);
             error!("Error applying decoder options: {}", av_err2str(ret));
@@ -1067,6 +1138,8 @@ struct DecoderParameter {
     last_frame_sample_rate: i32,
     // view_map: Vec<ViewMap>,
 
+    // shared with the other decoders of the input, read by the scheduler
+    decode_error_count: Arc<AtomicU64>,
 }
 
 unsafe impl Send for DecoderParameter {}
@@ -1117,8 +1190,14 @@ impl DecoderParameter {
             last_frame_sample_rate: 0,
 
             // view_map: vec![],
+            decode_error_count: dec_stream.decode_errors.clone(),
         }
     }
+
+    fn count_decode_error(&mut self) {
+        self.dec.decode_errors += 1;
+        self.decode_error_count.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
 struct Decoder {
@@ -1150,18 +1229,12 @@ fn dec_done(dp_arc: &Arc<Mutex<DecoderParameter>>, senders: &Vec<(Sender<FrameBo
     }
 }
 
-/*const fn fferrtag(a: u8, b: u8, c: u8, d: u8) -> i32 {
-    -(((a as i32) & 0xFF) << 24
-        | ((b as i32) & 0xFF) << 16
-        | ((c as i32) & 0xFF) << 8
-        | ((d as i32) & 0xFF))
-}
-
-const FFMPEG_ERROR_RATE_EXCEEDED: i32 = fferrtag(b'E', b'R', b'E', b'D');*/
+/// Consecutive `avcodec_receive_frame` failures after which the packet is given up.
+#[cfg(not(feature = "docs-rs"))]
+const MAX_CONSECUTIVE_RECEIVE_ERRORS: usize = 16;
 
 #[repr(i32)]
-enum FrameOpaque {
-    #[allow(dead_code)]
+pub(crate) enum FrameOpaque {
     FrameOpaqueSubHeartbeat = 1,
     FrameOpaqueEof,
     #[allow(dead_code)]
@@ -1233,7 +1306,7 @@ unsafe fn packet_decode(
         if ret != AVERROR_EOF {
             let dp = dp_arc.clone();
             let mut dp = dp.lock().unwrap();
-            dp.dec.decode_errors += 1;
+            dp.count_decode_error();
             if !exit_on_error {
                 return Ok(());
             };
@@ -1246,6 +1319,7 @@ unsafe fn packet_decode(
 
     packet_pool.release(packet_box.packet);
 
+    let mut receive_errors = 0;
     loop {
         let mut outputs_mask = 1;
 
@@ -1264,7 +1338,7 @@ unsafe fn packet_decode(
             error!("Decoding error: {}", av_err2str(ret));
             let dp = dp_arc.clone();
             let mut dp = dp.lock().unwrap();
-            dp.dec.decode_errors += 1;
+            dp.count_decode_error();
 
             if exit_on_error {
                 return Err(Decoding(DecodingOperationError::ReceiveFrameError(
@@ -1272,8 +1346,15 @@ unsafe fn packet_decode(
                 )));
             };
 
+            // a decoder stuck in an error state would otherwise be polled forever
+            receive_errors += 1;
+            if receive_errors >= MAX_CONSECUTIVE_RECEIVE_ERRORS {
+                warn!("Decoder keeps failing, skipping to the next packet");
+                return Ok(());
+            }
             continue;
         }
+        receive_errors = 0;
 
         if (*frame.as_ptr()).decode_error_flags != 0
             || ((*frame.as_ptr()).flags & AV_FRAME_FLAG_CORRUPT != 0)
@@ -1,17 +1,18 @@
-use std::ffi::CStr;
+use std::ffi::{c_void, CStr};
 use crate::core::context::decoder_stream::DecoderStream;
 use crate::core::context::demuxer::Demuxer;
 use crate::core::context::obj_pool::ObjPool;
 use crate::core::context::{AVFormatContextBox, PacketBox, PacketData};
+use crate::core::scheduler::dec_task::PacketOpaque;
 use crate::core::scheduler::ffmpeg_scheduler::{
     packet_is_null, set_scheduler_error, wait_until_not_paused, STATUS_END,
 };
-use crate::error::Error::Demuxing;
+use crate::error::Error::{Demuxing, InputTimeout};
 use crate::error::{DemuxingError, DemuxingOperationError};
 use crossbeam_channel::Sender;
 use ffmpeg_next::packet::{Mut, Ref};
 use ffmpeg_next::Packet;
-use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_VIDEO};
+use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO};
 use ffmpeg_sys_next::AVRounding::AV_ROUND_NEAR_INF;
 #[cfg(not(feature = "docs-rs"))]
 use ffmpeg_sys_next::AV_CODEC_PROP_FIELDS;
@@ -19,7 +20,7 @@ use ffmpeg_sys_next::{
     av_compare_ts, av_gettime_relative, av_inv_q, av_mul_q, av_packet_ref, av_q2d, av_read_frame,
     av_rescale, av_rescale_q, av_rescale_q_rnd, av_stream_get_parser, av_usleep,
     avformat_seek_file, AVCodecDescriptor, AVCodecParameters, AVFormatContext, AVMediaType,
-    AVPacket, AVRational, AVStream, AVERROR, AVERROR_EOF, AVFMT_TS_DISCONT,
+    AVPacket, AVRational, AVStream, AVERROR, AVERROR_EOF, AVERROR_HTTP_SERVER_ERROR, AVFMT_TS_DISCONT,
     AV_NOPTS_VALUE, AV_PKT_FLAG_CORRUPT, AV_TIME_BASE, AV_TIME_BASE_Q,
     EAGAIN,
 };
@@ -28,6 +29,7 @@ use log::{debug, error, info, warn};
 use std::ptr::null_mut;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use crate::core::scheduler::input_controller::SchNode;
 use crate::util::ffmpeg_utils::av_err2str;
 
@@ -73,12 +75,19 @@ pub(crate) fn demux_init(
     let hwaccel = { demux.hwaccel.take() };
 
     let format_name = unsafe {std::str::from_utf8_unchecked(CStr::from_ptr((*(*in_fmt_ctx).iformat).name).to_bytes())};
+    let url = demux.url.clone();
+    let interrupt = demux.interrupt.clone();
 
     let result = std::thread::Builder::new()
         .name(format!("demuxer{demux_idx}:{format_name}"))
         .spawn(move || {
+            // declared first so that it outlives the format context using it
+            let interrupt = interrupt;
             let in_fmt_ctx_box = in_fmt_ctx_box;
             let mut is_started = false;
+            let mut read_pending = false;
+            // consecutive retries of transient read errors
+            let mut read_retries = 0;
             demux_paramter.wallclock_start = unsafe { av_gettime_relative() };
 
             loop {
@@ -92,7 +101,32 @@ pub(crate) fn demux_init(
                 };
 
                 unsafe {
+                    if let Some(interrupt) = &interrupt {
+                        // the read timeout counts from the first attempt, not from each EAGAIN retry
+                        if !read_pending {
+                            interrupt.arm(interrupt.read_timeout);
+                        }
+                    }
                     let mut ret = av_read_frame(in_fmt_ctx_box.fmt_ctx, packet.as_mut_ptr());
+                    let timed_out = interrupt.as_ref().is_some_and(|interrupt| {
+                        if ret == AVERROR(EAGAIN) {
+                            interrupt.expired()
+                        } else {
+                            ret < 0 && interrupt.timed_out()
+                        }
+                    });
+                    if timed_out {
+                        let read_timeout = interrupt.as_ref().and_then(|interrupt| interrupt.read_timeout).unwrap_or_default();
+                        error!("No data received from input '{url}' for {read_timeout:?}");
+                        packet_pool.release(packet);
+                        set_scheduler_error(
+                            &scheduler_status,
+                            &scheduler_result,
+                            InputTimeout(url.clone(), read_timeout),
+                        );
+                        break;
+                    }
+                    read_pending = ret == AVERROR(EAGAIN);
                     if ret == AVERROR(EAGAIN) {
                         if wait_until_not_paused(&scheduler_status) == STATUS_END {
                             info!("Demuxer receiver end command, finishing.");
@@ -108,6 +142,24 @@ pub(crate) fn demux_init(
                         break;
                     }
 
+                    if ret >= 0 {
+                        read_retries = 0;
+                    } else if let Some((max_retries, backoff)) = demux_paramter.read_retry.filter(|_| is_transient_read_error(ret)) {
+                        if read_retries < max_retries {
+                            let delay = backoff.saturating_mul(1 << read_retries.min(16));
+                            read_retries += 1;
+                            warn!("Error reading input '{url}': {}, retry {read_retries}/{max_retries} in {delay:?}", av_err2str(ret));
+                            packet_pool.release(packet);
+                            if !sleep_until_end(delay, &scheduler_status) {
+                                info!("Demuxer receiver end command, finishing.");
+                                break;
+                            }
+                            clear_io_error(in_fmt_ctx_box.fmt_ctx);
+                            continue;
+                        }
+                        error!("Giving up reading input '{url}' after {max_retries} retries");
+                    }
+
                     if ret < 0 {
                         if ret == AVERROR_EOF {
                             debug!("EOF while reading input");
@@ -191,7 +243,13 @@ pub(crate) fn demux_init(
                                 (*packet.as_ptr()).stream_index
                             );
                             packet_pool.release(packet);
-                            // ret = AVERROR_INVALIDDATA;
+                            set_scheduler_error(
+                                &scheduler_status,
+                                &scheduler_result,
+                                Demuxing(DemuxingOperationError::ReadFrameError(
+                                    DemuxingError::InvalidData,
+                                )),
+                            );
                             break;
                         } else {
                             warn!(
@@ -219,6 +277,10 @@ pub(crate) fn demux_init(
                     if ret < 0 {
                         break;
                     }
+                    if ret == PACKET_DROPPED {
+                        packet_pool.release(packet);
+                        continue;
+                    }
 
                     if let Some(readrate) = demux_paramter.readrate {
                         if readrate != 0.0 {
@@ -231,6 +293,8 @@ pub(crate) fn demux_init(
                     }
 
                     {
+                        let heartbeat_pts = (*packet.as_ptr()).pts;
+                        let heartbeat_tb = (*packet.as_ptr()).time_base;
                         let ds = demux_paramter
                             .demux_streams
                             .get_mut((*packet.as_ptr()).stream_index as usize)
@@ -250,6 +314,11 @@ pub(crate) fn demux_init(
                         if ret < 0 {
                             break;
                         }
+
+                        ret = sub2video_heartbeat_send(&mut demux_paramter, heartbeat_pts, heartbeat_tb, &packet_pool, &scheduler_status);
+                        if ret < 0 {
+                            break;
+                        }
                     }
                 }
             }
@@ -257,6 +326,9 @@ pub(crate) fn demux_init(
             if is_started {
                 demux_done(&mut demux_paramter, &packet_pool, &scheduler_status);
             }
+            if demux_paramter.fix_timestamps {
+                log_fixed_timestamps(&demux_paramter, &url);
+            }
 
             let node = demux_node.as_ref();
             let SchNode::Demux {
@@ -342,6 +414,27 @@ unsafe fn readrate_sleep(demux_paramter: &DemuxerParamter, nb_streams: c_uint, r
     }
 }
 
+/// Returns whether every audio and video stream sent to a destination is past the recording
+/// time, so that the rest of the input would only be read to be thrown away. Sparse streams
+/// (subtitles, data) do not hold the input open, as their packets are interleaved by dts.
+fn recording_time_reached(demux_paramter: &DemuxerParamter) -> bool {
+    let mut streams = demux_paramter
+        .demux_streams
+        .iter()
+        .filter(|ds| {
+            (ds.codec_type == AVMEDIA_TYPE_VIDEO || ds.codec_type == AVMEDIA_TYPE_AUDIO)
+                && demux_paramter
+                    .dsts
+                    .iter()
+                    .any(|(_, input_stream_index, _)| *input_stream_index == ds.stream_index)
+        })
+        .peekable();
+    streams.peek().is_some() && streams.all(|ds| ds.past_recording_time)
+}
+
+/// Returned by `input_packet_process` for a packet that must not be sent.
+const PACKET_DROPPED: c_int = 1;
+
 unsafe fn input_packet_process(
     demux_paramter: &mut DemuxerParamter,
     in_fmt_ctx: *mut AVFormatContext,
@@ -349,7 +442,9 @@ unsafe fn input_packet_process(
     send_flags: &mut usize,
     copy_ts: bool,
 ) -> c_int {
-    ts_fixup(demux_paramter, in_fmt_ctx, pkt, copy_ts);
+    if !ts_fixup(demux_paramter, in_fmt_ctx, pkt, copy_ts) {
+        return PACKET_DROPPED;
+    }
 
     if let Some(recording_time_us) = demux_paramter.recording_time_us {
         if recording_time_us != i64::MAX {
@@ -363,6 +458,11 @@ unsafe fn input_packet_process(
                 .unwrap();
             if ds.dts >= recording_time_us + start_time {
                 *send_flags |= DEMUX_SEND_STREAMCOPY_EOF;
+                ds.past_recording_time = true;
+                if recording_time_reached(demux_paramter) {
+                    debug!("All streams reached the recording time, stopping reading the input.");
+                    return AVERROR_EOF;
+                }
             }
         }
     }
@@ -378,15 +478,18 @@ unsafe fn ts_fixup(
     demux_paramter: &mut DemuxerParamter,
     in_fmt_ctx: *mut AVFormatContext,
     pkt: *mut AVPacket,
-) {}
+) -> bool {
+    true
+}
 
+/// Fixes up the timestamps of `pkt`, returning `false` if it must be dropped.
 #[cfg(not(feature = "docs-rs"))]
 unsafe fn ts_fixup(
     demux_paramter: &mut DemuxerParamter,
     in_fmt_ctx: *mut AVFormatContext,
     pkt: *mut AVPacket,
     copy_ts: bool,
-) {
+) -> bool {
     let streams = (*in_fmt_ctx).streams;
     let ist = *streams.offset((*pkt).stream_index as isize);
     let start_time = demux_paramter.start_time_effective;
@@ -489,8 +592,82 @@ unsafe fn ts_fixup(
     // detect and try to correct for timestamp discontinuities
     ts_discontinuity_process(demux_paramter, in_fmt_ctx, ist, pkt, copy_ts);
 
+    if demux_paramter.fix_timestamps && !fix_non_monotonic_dts(demux_paramter, ist, pkt) {
+        return false;
+    }
+
     // update estimated/predicted dts
     ist_dts_update(demux_paramter, ist, pkt);
+    true
+}
+
+/// Moves `pkt` to just after the previous packet of its stream if its dts does not increase,
+/// returning `false` if it must be dropped as its duration is unknown. See
+/// `Input::set_fix_timestamps`.
+unsafe fn fix_non_monotonic_dts(demux_paramter: &mut DemuxerParamter, ist: *mut AVStream, pkt: *mut AVPacket) -> bool {
+    let ds = demux_paramter
+        .demux_streams
+        .get_mut((*pkt).stream_index as usize)
+        .unwrap();
+
+    // missing timestamps are estimated later on
+    if (*pkt).dts == AV_NOPTS_VALUE {
+        return true;
+    }
+    if ds.last_dts == AV_NOPTS_VALUE || (*pkt).dts > ds.last_dts {
+        ds.last_dts = (*pkt).dts;
+        return true;
+    }
+
+    let par = (*ist).codecpar;
+    let step = if (*pkt).duration > 0 {
+        Some((*pkt).duration)
+    } else if (*par).codec_type == AVMEDIA_TYPE_VIDEO && (*ist).avg_frame_rate.num > 0 {
+        Some(av_rescale_q(1, av_inv_q((*ist).avg_frame_rate), (*pkt).time_base))
+    } else if (*par).codec_type == AVMEDIA_TYPE_AUDIO && (*par).frame_size > 0 && (*par).sample_rate > 0 {
+        let sample_time_base = AVRational { num: 1, den: (*par).sample_rate };
+        Some(av_rescale_q((*par).frame_size as i64, sample_time_base, (*pkt).time_base))
+    } else {
+        None
+    };
+    let Some(step) = step else {
+        debug!("Dropping packet with non-monotonic dts {} (previous {}) of stream {}", (*pkt).dts, ds.last_dts, ds.stream_index);
+        ds.dropped_dts += 1;
+        return false;
+    };
+
+    let fixed_dts = ds.last_dts + step.max(1);
+    debug!("Non-monotonic dts {} (previous {}) of stream {}, corrected to {fixed_dts}", (*pkt).dts, ds.last_dts, ds.stream_index);
+    if (*pkt).pts != AV_NOPTS_VALUE {
+        (*pkt).pts += fixed_dts - (*pkt).dts;
+    }
+    (*pkt).dts = fixed_dts;
+    ds.last_dts = fixed_dts;
+    ds.fixed_dts += 1;
+    true
+}
+
+/// Logs how many packets of each stream of the input `url` had their dts corrected or were
+/// dropped by `Input::set_fix_timestamps`.
+fn log_fixed_timestamps(demux_paramter: &DemuxerParamter, url: &str) {
+    for report in fixed_timestamps_reports(demux_paramter, url) {
+        warn!("{report}");
+    }
+}
+
+/// The lines `log_fixed_timestamps` logs, one per stream with corrected or dropped packets.
+fn fixed_timestamps_reports(demux_paramter: &DemuxerParamter, url: &str) -> Vec<String> {
+    demux_paramter
+        .demux_streams
+        .iter()
+        .filter(|ds| ds.fixed_dts > 0 || ds.dropped_dts > 0)
+        .map(|ds| {
+            format!(
+                "Input '{url}' stream {}: corrected {} non-monotonic timestamps, dropped {} packets",
+                ds.stream_index, ds.fixed_dts, ds.dropped_dts
+            )
+        })
+        .collect()
 }
 
 #[cfg(feature = "docs-rs")]
@@ -734,6 +911,7 @@ struct DemuxStreamParamter {
     stream_index: usize,
     codecpar: *mut AVCodecParameters,
     codec_desc: *const AVCodecDescriptor,
+    have_sub2video: bool,
 
     wrap_correction_done: bool,
     saw_first_ts: bool,
@@ -743,6 +921,15 @@ struct DemuxStreamParamter {
     next_dts: i64,
     ///< dts of the last packet read for this stream (in AV_TIME_BASE units)
     dts: i64,
+    ///< a packet past the recording time of the input was read for this stream
+    past_recording_time: bool,
+
+    ///< dts of the last packet sent for this stream (in its time base), when fixing timestamps
+    last_dts: i64,
+    ///< number of packets whose non-monotonic dts was corrected
+    fixed_dts: u64,
+    ///< number of packets dropped for a non-monotonic dts that could not be corrected
+    dropped_dts: u64,
 }
 
 unsafe impl Send for DemuxStreamParamter {}
@@ -754,14 +941,61 @@ impl DemuxStreamParamter {
             stream_index: ds.stream_index,
             codecpar: ds.codec_parameters,
             codec_desc: ds.codec_desc,
+            have_sub2video: ds.have_sub2video,
             wrap_correction_done: false,
             saw_first_ts: false,
             first_dts: AV_NOPTS_VALUE,
             next_dts: AV_NOPTS_VALUE,
             dts: 0,
+            past_recording_time: false,
+            last_dts: AV_NOPTS_VALUE,
+            fixed_dts: 0,
+            dropped_dts: 0,
         }
     }
 }
+
+/// Returns whether the read error `ret` may go away by reading again, e.g. a dropped
+/// connection, as opposed to the end of the input or invalid data.
+fn is_transient_read_error(ret: i32) -> bool {
+    [
+        AVERROR(libc::EIO),
+        AVERROR(libc::ETIMEDOUT),
+        AVERROR(libc::ECONNRESET),
+        AVERROR(libc::ECONNABORTED),
+        AVERROR(libc::EPIPE),
+        AVERROR(libc::ENETUNREACH),
+        AVERROR(libc::EHOSTUNREACH),
+        AVERROR_HTTP_SERVER_ERROR,
+    ]
+    .contains(&ret)
+}
+
+/// Sleeps for `delay`, waking up early to return `false` if the job ends meanwhile.
+fn sleep_until_end(delay: Duration, scheduler_status: &Arc<AtomicUsize>) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if scheduler_status.load(Ordering::Acquire) == STATUS_END {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(10)));
+    }
+}
+
+/// Clears the error and end-of-file state a failed read leaves on the I/O context of
+/// `fmt_ctx`, which would otherwise fail the next read right away.
+unsafe fn clear_io_error(fmt_ctx: *mut AVFormatContext) {
+    let pb = (*fmt_ctx).pb;
+    if !pb.is_null() {
+        (*pb).error = 0;
+        (*pb).eof_reached = 0;
+    }
+}
+
 struct DemuxerParamter {
     dsts_finished: Vec<bool>,
     have_audio_dec: bool,
@@ -780,6 +1014,8 @@ struct DemuxerParamter {
     start_time_us: Option<i64>,
     recording_time_us: Option<i64>,
     exit_on_error: bool,
+    read_retry: Option<(u32, Duration)>,
+    fix_timestamps: bool,
     stream_loop: i32,
 
     end_pts: Timestamp,
@@ -829,6 +1065,8 @@ impl DemuxerParamter {
             start_time_us: demux.start_time_us,
             recording_time_us: demux.recording_time_us,
             exit_on_error: demux.exit_on_error.unwrap_or(false),
+            read_retry: demux.read_retry,
+            fix_timestamps: demux.fix_timestamps,
             stream_loop: demux.stream_loop.unwrap_or(0),
 
             end_pts: Default::default(),
@@ -1030,6 +1268,65 @@ unsafe fn demux_send_for_stream(
     }
 }
 
+/// Sends a heartbeat with the timestamp of the packet just demuxed to the decoders of
+/// subtitle streams rendered as video (sub2video), so that the current subtitle keeps
+/// being pushed into the filter graph (or gets cleared) while the other streams advance.
+unsafe fn sub2video_heartbeat_send(
+    demux_paramter: &mut DemuxerParamter,
+    pts: i64,
+    time_base: AVRational,
+    packet_pool: &ObjPool<Packet>,
+    scheduler_status: &Arc<AtomicUsize>,
+) -> i32 {
+    if pts == AV_NOPTS_VALUE {
+        return 0;
+    }
+
+    for ds in &demux_paramter.demux_streams {
+        if !ds.have_sub2video {
+            continue;
+        }
+
+        for (dst_i, (packet_dst, input_stream_index, output_stream_index)) in demux_paramter.dsts.iter().enumerate() {
+            // only decoders understand heartbeats, never forward them to stream copy outputs
+            if *input_stream_index != ds.stream_index || output_stream_index.is_some() {
+                continue;
+            }
+
+            let Ok(mut packet) = packet_pool.get() else {
+                return AVERROR(ffmpeg_sys_next::ENOMEM);
+            };
+            (*packet.as_mut_ptr()).pts = pts;
+            (*packet.as_mut_ptr()).time_base = time_base;
+            (*packet.as_mut_ptr()).stream_index = ds.stream_index as i32;
+            (*packet.as_mut_ptr()).opaque = PacketOpaque::PktOpaqueSubHeartbeat as i32 as usize as *mut c_void;
+
+            let packet_box = PacketBox {
+                packet,
+                packet_data: PacketData {
+                    dts_est: 0,
+                    codec_type: AVMEDIA_TYPE_SUBTITLE,
+                    output_stream_index: 0,
+                    is_copy: false,
+                    codecpar: ds.codecpar,
+                },
+            };
+            let ret = demux_stream_send_to_dst(
+                packet_box,
+                packet_dst,
+                output_stream_index,
+                &mut demux_paramter.dsts_finished[dst_i],
+                0,
+                scheduler_status,
+            );
+            if ret < 0 && ret != AVERROR_EOF {
+                return ret;
+            }
+        }
+    }
+    0
+}
+
 const DEMUX_SEND_STREAMCOPY_EOF: usize = 1 << 0;
 
 unsafe fn demux_stream_send_to_dst(
@@ -1122,3 +1419,167 @@ unsafe fn demux_flush(
 
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::{av_find_input_format, avformat_alloc_context, avformat_free_context, avformat_new_stream};
+    use std::ffi::CString;
+    use std::ptr::null;
+
+    /// An input of a 25 fps video stream in 1/90000, an audio stream of 1024 sample frames at
+    /// 48 kHz, and a video stream without a frame rate.
+    struct TestInput {
+        fmt_ctx: *mut AVFormatContext,
+        demux_paramter: DemuxerParamter,
+    }
+
+    impl TestInput {
+        unsafe fn new(fix_timestamps: bool) -> Self {
+            let fmt_ctx = avformat_alloc_context();
+            let mp4 = CString::new("mp4").unwrap();
+            (*fmt_ctx).iformat = av_find_input_format(mp4.as_ptr()) as _;
+
+            let mut demux_streams = Vec::new();
+            for (stream_index, codec_type) in [AVMEDIA_TYPE_VIDEO, AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_VIDEO]
+                .into_iter()
+                .enumerate()
+            {
+                let st = avformat_new_stream(fmt_ctx, null());
+                let par = (*st).codecpar;
+                (*par).codec_type = codec_type;
+                match stream_index {
+                    0 => {
+                        (*st).time_base = AVRational { num: 1, den: 90000 };
+                        (*st).avg_frame_rate = AVRational { num: 25, den: 1 };
+                    }
+                    1 => {
+                        (*st).time_base = AVRational { num: 1, den: 48000 };
+                        (*par).sample_rate = 48000;
+                        (*par).frame_size = 1024;
+                    }
+                    _ => (*st).time_base = AVRational { num: 1, den: 1000 },
+                }
+                demux_streams.push(DemuxStreamParamter {
+                    codec_type,
+                    stream_index,
+                    codecpar: par,
+                    codec_desc: null(),
+                    have_sub2video: false,
+                    wrap_correction_done: false,
+                    saw_first_ts: false,
+                    first_dts: AV_NOPTS_VALUE,
+                    next_dts: AV_NOPTS_VALUE,
+                    dts: 0,
+                    past_recording_time: false,
+                    last_dts: AV_NOPTS_VALUE,
+                    fixed_dts: 0,
+                    dropped_dts: 0,
+                });
+            }
+
+            let demux_paramter = DemuxerParamter {
+                dsts_finished: vec![],
+                have_audio_dec: true,
+                wallclock_start: 0,
+                ts_offset_discont: 0,
+                last_ts: 0,
+                start_time_effective: AV_NOPTS_VALUE,
+                ts_offset: 0,
+                readrate: None,
+                start_time_us: None,
+                recording_time_us: None,
+                exit_on_error: false,
+                read_retry: None,
+                fix_timestamps,
+                stream_loop: 0,
+                end_pts: Default::default(),
+                duration: Timestamp { ts: 0, tb: AVRational { num: 1, den: 1 } },
+                min_pts: Default::default(),
+                max_pts: Default::default(),
+                demux_streams,
+                dsts: vec![],
+            };
+            Self { fmt_ctx, demux_paramter }
+        }
+
+        /// Processes a packet of `stream_index` with the timestamp `dts` (also its pts), and
+        /// returns the result and the dts and pts it leaves with.
+        unsafe fn process(&mut self, stream_index: i32, dts: i64, duration: i64) -> (c_int, i64, i64) {
+            let mut packet = Packet::empty();
+            let pkt = packet.as_mut_ptr();
+            (*pkt).stream_index = stream_index;
+            (*pkt).dts = dts;
+            (*pkt).pts = dts;
+            (*pkt).duration = duration;
+            let mut send_flags = 0;
+            let ret = input_packet_process(&mut self.demux_paramter, self.fmt_ctx, pkt, &mut send_flags, false);
+            (ret, (*pkt).dts, (*pkt).pts)
+        }
+    }
+
+    impl Drop for TestInput {
+        fn drop(&mut self) {
+            unsafe { avformat_free_context(self.fmt_ctx) };
+        }
+    }
+
+    #[test]
+    fn test_fix_non_monotonic_dts() {
+        unsafe {
+            let mut input = TestInput::new(true);
+            // (stream, dts, duration) -> dts after the fix
+            let packets = [
+                ((0, 0, 0), 0),
+                ((1, 0, 1024), 0),
+                ((0, 3600, 0), 3600),
+                ((1, 1024, 1024), 1024),
+                // repeated: one frame of the frame rate later
+                ((0, 3600, 0), 7200),
+                ((1, 2048, 1024), 2048),
+                // going back: one packet duration later
+                ((1, 1024, 1024), 3072),
+                // the following packets keep their timestamps
+                ((0, 10800, 0), 10800),
+                ((1, 4096, 1024), 4096),
+            ];
+            for ((stream_index, dts, duration), expected) in packets {
+                assert_eq!(input.process(stream_index, dts, duration), (0, expected, expected), "{stream_index} {dts}");
+            }
+
+            // without a duration or a frame rate, the packet cannot be placed
+            assert_eq!(input.process(2, 100, 0).0, 0);
+            assert_eq!(input.process(2, 100, 0).0, PACKET_DROPPED);
+            // a missing timestamp is left to be estimated
+            assert_eq!(input.process(2, AV_NOPTS_VALUE, 0).0, 0);
+
+            let streams = &input.demux_paramter.demux_streams;
+            let counts: Vec<(u64, u64)> = streams.iter().map(|ds| (ds.fixed_dts, ds.dropped_dts)).collect();
+            assert_eq!(counts, [(1, 0), (1, 0), (0, 1)]);
+            // audio and video are still in sync: both are at the time of their last packet
+            assert_eq!(streams[0].dts, 10800 * AV_TIME_BASE as i64 / 90000);
+            assert_eq!(streams[1].dts, 4096 * AV_TIME_BASE as i64 / 48000);
+
+            assert_eq!(
+                fixed_timestamps_reports(&input.demux_paramter, "input.ts"),
+                [
+                    "Input 'input.ts' stream 0: corrected 1 non-monotonic timestamps, dropped 0 packets",
+                    "Input 'input.ts' stream 1: corrected 1 non-monotonic timestamps, dropped 0 packets",
+                    "Input 'input.ts' stream 2: corrected 0 non-monotonic timestamps, dropped 1 packets",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_monotonic_dts_without_fix() {
+        unsafe {
+            let mut input = TestInput::new(false);
+            assert_eq!(input.process(0, 3600, 0), (0, 3600, 3600));
+            assert_eq!(input.process(0, 3600, 0), (0, 3600, 3600));
+            assert_eq!(input.process(2, 100, 0), (0, 100, 100));
+            assert_eq!(input.process(2, 50, 0), (0, 50, 50));
+            assert!(fixed_timestamps_reports(&input.demux_paramter, "input.ts").is_empty());
+        }
+    }
+}
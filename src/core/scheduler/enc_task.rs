@@ -1,4 +1,5 @@
 use crate::core::context::encoder_stream::EncoderStream;
+use crate::core::context::output::{ForceKeyframes, QualityRamp};
 use crate::core::context::obj_pool::ObjPool;
 use crate::core::context::{CodecContext, FrameBox, PacketBox, PacketData};
 use crate::error::Error::{Encoding, OpenEncoder};
@@ -8,7 +9,7 @@ use crate::core::scheduler::ffmpeg_scheduler::{
 };
 use crate::hwaccel::hw_device_get_by_type;
 use crate::util::ffmpeg_utils::{av_err2str, hashmap_to_avdictionary};
-use crossbeam_channel::{Receiver, RecvTimeoutError, SendError, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
 use ffmpeg_next::packet::Mut;
 use ffmpeg_next::{Frame, Packet};
 use ffmpeg_sys_next::AVCodecID::{
@@ -18,17 +19,18 @@ use ffmpeg_sys_next::AVFieldOrder::{
     AV_FIELD_BB, AV_FIELD_BT, AV_FIELD_PROGRESSIVE, AV_FIELD_TB, AV_FIELD_TT,
 };
 use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO};
-use ffmpeg_sys_next::AVPictureType::AV_PICTURE_TYPE_NONE;
+use ffmpeg_sys_next::AVPictureType::{AV_PICTURE_TYPE_I, AV_PICTURE_TYPE_NONE};
 use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_NONE;
 use ffmpeg_sys_next::AVSampleFormat::AV_SAMPLE_FMT_NONE;
 #[cfg(not(feature = "docs-rs"))]
 use ffmpeg_sys_next::AVSideDataProps::AV_SIDE_DATA_PROP_GLOBAL;
 #[cfg(not(feature = "docs-rs"))]
 use ffmpeg_sys_next::{av_channel_layout_copy, av_frame_side_data_clone, av_frame_side_data_desc, AV_CODEC_FLAG_COPY_OPAQUE, AV_CODEC_FLAG_FRAME_DURATION, AV_FRAME_FLAG_INTERLACED, AV_FRAME_FLAG_TOP_FIELD_FIRST, AV_FRAME_SIDE_DATA_FLAG_UNIQUE};
-use ffmpeg_sys_next::{av_add_q, av_buffer_ref, av_compare_ts, av_cpu_max_align, av_frame_copy_props, av_frame_get_buffer, av_frame_ref, av_get_bytes_per_sample, av_get_pix_fmt_name, av_opt_set_dict2, av_pix_fmt_desc_get, av_rescale_q, av_sample_fmt_is_planar, av_samples_copy, av_shrink_packet, avcodec_alloc_context3, avcodec_encode_subtitle, avcodec_get_hw_config, avcodec_open2, avcodec_parameters_from_context, avcodec_receive_packet, avcodec_send_frame, AVBufferRef, AVCodecContext, AVFrame, AVHWFramesContext, AVMediaType, AVRational, AVStream, AVSubtitle, AVERROR, AVERROR_EOF, AVERROR_EXPERIMENTAL, AV_CODEC_CAP_ENCODER_REORDERED_OPAQUE, AV_CODEC_CAP_PARAM_CHANGE, AV_CODEC_FLAG_INTERLACED_DCT, AV_CODEC_FLAG_INTERLACED_ME, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, AV_CODEC_HW_CONFIG_METHOD_HW_FRAMES_CTX, AV_NOPTS_VALUE, AV_OPT_SEARCH_CHILDREN, AV_PKT_FLAG_TRUSTED, AV_TIME_BASE_Q, EAGAIN};
+use ffmpeg_sys_next::{av_expr_eval, av_expr_free, av_expr_parse, av_opt_set_double, av_q2d, AVExpr, EINVAL};
+use ffmpeg_sys_next::{av_add_q, av_buffer_ref, av_compare_ts, av_cpu_max_align, av_dict_free, av_dict_get, av_frame_copy_props, av_frame_get_buffer, av_frame_ref, av_get_bytes_per_sample, av_get_pix_fmt_name, av_opt_set_dict2, av_pix_fmt_desc_get, av_rescale_q, av_sample_fmt_is_planar, av_samples_copy, av_shrink_packet, avcodec_alloc_context3, avcodec_encode_subtitle, avcodec_get_hw_config, avcodec_open2, avcodec_parameters_from_context, avcodec_receive_packet, avcodec_send_frame, AVBufferRef, AVCodecContext, AVDictionaryEntry, AVFrame, AVHWFramesContext, AVMediaType, AVRational, AVStream, AVSubtitle, AVERROR, AVERROR_EOF, AVERROR_EXPERIMENTAL, AV_CODEC_CAP_ENCODER_REORDERED_OPAQUE, AV_CODEC_CAP_PARAM_CHANGE, AV_CODEC_FLAG_INTERLACED_DCT, AV_CODEC_FLAG_INTERLACED_ME, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, AV_CODEC_HW_CONFIG_METHOD_HW_FRAMES_CTX, AV_DICT_IGNORE_SUFFIX, AV_FRAME_FLAG_KEY, AV_NOPTS_VALUE, AV_OPT_SEARCH_CHILDREN, AV_PKT_FLAG_TRUSTED, AV_TIME_BASE_Q, EAGAIN};
 use log::{debug, error, info, trace, warn};
-use std::collections::{HashMap, VecDeque};
-use std::ffi::{CStr, CString};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{c_char, CStr, CString};
 use std::ptr::{null, null_mut};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
@@ -49,6 +51,8 @@ pub(crate) fn enc_init(
     video_codec_opts: &Option<HashMap<CString, CString>>,
     audio_codec_opts: &Option<HashMap<CString, CString>>,
     subtitle_codec_opts: &Option<HashMap<CString, CString>>,
+    encoder_opts: &Option<HashMap<CString, CString>>,
+    used_encoder_opts: &mut HashSet<CString>,
     oformat_flags: i32,
     frame_pool: ObjPool<Frame>,
     packet_pool: ObjPool<Packet>,
@@ -72,6 +76,8 @@ pub(crate) fn enc_init(
     video_codec_opts: &Option<HashMap<CString, CString>>,
     audio_codec_opts: &Option<HashMap<CString, CString>>,
     subtitle_codec_opts: &Option<HashMap<CString, CString>>,
+    encoder_opts: &Option<HashMap<CString, CString>>,
+    used_encoder_opts: &mut HashSet<CString>,
     oformat_flags: i32,
     frame_pool: ObjPool<Frame>,
     packet_pool: ObjPool<Packet>,
@@ -94,14 +100,30 @@ pub(crate) fn enc_init(
         }
     }
 
+    unsafe {
+        if let Some(gop_size) = enc_stream.gop_size {
+            (*enc_ctx).gop_size = gop_size;
+        }
+        if let Some(max_b_frames) = enc_stream.max_b_frames {
+            (*enc_ctx).max_b_frames = max_b_frames;
+        }
+        if let Some(keyint_min) = enc_stream.keyint_min {
+            (*enc_ctx).keyint_min = keyint_min;
+        }
+    }
+
     if oformat_flags & ffmpeg_sys_next::AVFMT_GLOBALHEADER != 0 {
        unsafe { (*enc_ctx).flags |= ffmpeg_sys_next::AV_CODEC_FLAG_GLOBAL_HEADER as i32; }
     }
+    let mut frame_quality = enc_stream
+        .quality_ramp
+        .clone()
+        .and_then(|ramp| unsafe { FrameQuality::init(ramp, enc_ctx, enc_stream.qscale.is_some()) });
     let enc_ctx_box = CodecContext::new(enc_ctx);
 
     let max_frames = get_max_frames(enc_stream.codec_type, max_video_frames, max_audio_frames, max_subtitle_frames);
 
-    set_encoder_opts(&enc_stream, video_codec_opts, audio_codec_opts, subtitle_codec_opts, &enc_ctx_box)?;
+    set_encoder_opts(mux_idx, &enc_stream, video_codec_opts, audio_codec_opts, subtitle_codec_opts, encoder_opts, used_encoder_opts, &enc_ctx_box)?;
 
     let receiver = enc_stream.take_src();
     let pkt_sender = enc_stream.take_dst();
@@ -110,6 +132,14 @@ pub(crate) fn enc_init(
 
     let stream_box = enc_stream.stream;
     let stream_index = enc_stream.stream_index;
+    let dropped_frames = enc_stream.dropped_frames.clone();
+    let frame_interval = enc_stream.frame_interval;
+    let mut keyframe_forcer = match enc_stream.force_keyframes.as_ref().map(KeyframeForcer::new).transpose() {
+        Ok(keyframe_forcer) => keyframe_forcer,
+        Err(ret) => {
+            return Err(OpenEncoder(OpenEncoderOperationError::CodecParametersError(OpenEncoderError::from(ret))))
+        }
+    };
 
     let encoder_name = unsafe {std::str::from_utf8_unchecked(CStr::from_ptr((*enc_stream.encoder).name).to_bytes())};
 
@@ -121,6 +151,8 @@ pub(crate) fn enc_init(
         let mut finished = false;
         let mut frames_sent = 0;
         let mut samples_sent = 0;
+        // frames received, to pick every `frame_interval`th one
+        let mut frame_index: u64 = 0;
 
         // audio
         let mut frame_samples = 0;
@@ -141,6 +173,29 @@ pub(crate) fn enc_init(
                 SyncFrame::Break => break
             };
 
+            if let Some(frame_interval) = frame_interval {
+                let frame = &receive_frame_box.frame;
+                if !frame_is_null(frame) && !frame.is_empty() {
+                    let skip = frame_index % frame_interval as u64 != 0;
+                    frame_index += 1;
+                    if skip {
+                        frames_sent -= 1;
+                        frame_pool.release(receive_frame_box.frame);
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(dropped_frames) = dropped_frames.as_ref() {
+                if can_drop_frame(&receiver, &receive_frame_box.frame) {
+                    trace!("Encoder input queue is full, dropping a frame");
+                    dropped_frames.fetch_add(1, Ordering::Relaxed);
+                    frames_sent -= 1;
+                    frame_pool.release(receive_frame_box.frame);
+                    continue;
+                }
+            }
+
             let result = frame_encode(
                 enc_ctx_box.as_mut_ptr(),
                 receive_frame_box.frame.as_mut_ptr(),
@@ -151,6 +206,8 @@ pub(crate) fn enc_init(
                 &mux_started,
                 stream_box.inner,
                 &packet_pool,
+                &mut keyframe_forcer,
+                &mut frame_quality,
             );
             frame_pool.release(receive_frame_box.frame);
             if let Err(e) = result {
@@ -195,7 +252,7 @@ pub(crate) fn enc_init(
                 set_scheduler_error(
                     &scheduler_status,
                     &scheduler_result,
-                    Encoding(EncodingOperationError::MuxerFinished),
+                    Encoding(e),
                 );
             }
         }
@@ -210,6 +267,19 @@ pub(crate) fn enc_init(
     Ok(())
 }
 
+/// Whether the encoder may skip `frame` to catch up with its source (realtime drop): the
+/// input queue was full when the frame was taken, and the frame is neither a keyframe nor
+/// the end of stream.
+unsafe fn can_drop_frame(receiver: &Receiver<FrameBox>, frame: &Frame) -> bool {
+    let queue_full = receiver
+        .capacity()
+        .is_some_and(|capacity| receiver.len() + 1 >= capacity);
+    queue_full
+        && !frame_is_null(frame)
+        && !frame.is_empty()
+        && (*frame.as_ptr()).flags & AV_FRAME_FLAG_KEY == 0
+}
+
 enum SyncFrame {
     FrameBox(FrameBox),
     Continue,
@@ -411,29 +481,77 @@ fn receive_frame(
     }
 }
 
-fn set_encoder_opts(enc_stream: &EncoderStream, video_codec_opts: &Option<HashMap<CString, CString>>, audio_codec_opts: &Option<HashMap<CString, CString>>, subtitle_codec_opts: &Option<HashMap<CString, CString>>, enc_ctx_box: &CodecContext) -> crate::error::Result<()> {
-    let mut encoder_opts = if enc_stream.codec_type == AVMEDIA_TYPE_VIDEO {
-        hashmap_to_avdictionary(video_codec_opts)
+fn set_encoder_opts(
+    mux_idx: usize,
+    enc_stream: &EncoderStream,
+    video_codec_opts: &Option<HashMap<CString, CString>>,
+    audio_codec_opts: &Option<HashMap<CString, CString>>,
+    subtitle_codec_opts: &Option<HashMap<CString, CString>>,
+    encoder_opts: &Option<HashMap<CString, CString>>,
+    used_encoder_opts: &mut HashSet<CString>,
+    enc_ctx_box: &CodecContext,
+) -> crate::error::Result<()> {
+    let codec_opts = if enc_stream.codec_type == AVMEDIA_TYPE_VIDEO {
+        video_codec_opts
     } else if enc_stream.codec_type == AVMEDIA_TYPE_AUDIO {
-        hashmap_to_avdictionary(audio_codec_opts)
+        audio_codec_opts
     } else if enc_stream.codec_type == AVMEDIA_TYPE_SUBTITLE {
-        hashmap_to_avdictionary(subtitle_codec_opts)
+        subtitle_codec_opts
     } else {
-        null_mut()
+        return Ok(());
     };
-    if !encoder_opts.is_null() {
-        let ret = unsafe {
-            av_opt_set_dict2(
-                enc_ctx_box.as_mut_ptr() as *mut libc::c_void,
-                &mut encoder_opts,
-                AV_OPT_SEARCH_CHILDREN,
-            )
-        };
-        if ret < 0 {
-            error!("Error applying encoder options: {}", av_err2str(ret));
-            return Err(OpenEncoder(
-                OpenEncoderOperationError::ContextAllocationError(OpenEncoderError::from(ret)),
-            ));
+
+    // Output-wide options first, so that the per-media-type ones override them.
+    let mut opts = encoder_opts.clone().unwrap_or_default();
+    if let Some(codec_opts) = codec_opts {
+        opts.extend(codec_opts.clone());
+    }
+    if opts.is_empty() {
+        return Ok(());
+    }
+
+    let mut encoder_dict = hashmap_to_avdictionary(&Some(opts.clone()));
+    let ret = unsafe {
+        av_opt_set_dict2(
+            enc_ctx_box.as_mut_ptr() as *mut libc::c_void,
+            &mut encoder_dict,
+            AV_OPT_SEARCH_CHILDREN,
+        )
+    };
+    if ret < 0 {
+        unsafe { av_dict_free(&mut encoder_dict) };
+        error!("Error applying encoder options: {}", av_err2str(ret));
+        return Err(OpenEncoder(
+            OpenEncoderOperationError::ContextAllocationError(OpenEncoderError::from(ret)),
+        ));
+    }
+
+    // av_opt_set_dict2 leaves the options it could not apply in the dictionary.
+    let mut unused = HashSet::new();
+    unsafe {
+        let mut entry: *mut AVDictionaryEntry = null_mut();
+        loop {
+            entry = av_dict_get(encoder_dict, null(), entry, AV_DICT_IGNORE_SUFFIX);
+            if entry.is_null() {
+                break;
+            }
+            unused.insert(CStr::from_ptr((*entry).key).to_owned());
+        }
+        av_dict_free(&mut encoder_dict);
+    }
+
+    for key in opts.keys() {
+        let is_codec_opt = codec_opts.as_ref().is_some_and(|codec_opts| codec_opts.contains_key(key));
+        if !unused.contains(key) {
+            if !is_codec_opt {
+                used_encoder_opts.insert(key.clone());
+            }
+        } else if is_codec_opt {
+            warn!(
+                "Codec option '{}' was not used by the encoder of output stream #{mux_idx}:{}, it may be misspelled or not supported by this encoder.",
+                key.to_string_lossy(),
+                enc_stream.stream_index
+            );
         }
     }
     Ok(())
@@ -923,6 +1041,187 @@ unsafe fn frame_is_aligned(align_mask: usize, frame: *const AVFrame) -> bool {
     false
 }
 
+/// Variables of a `force_keyframes` expression, in the order of their values.
+const FORCE_KEYFRAMES_VARS: [&str; 5] = ["n", "n_forced", "prev_forced_n", "prev_forced_t", "t"];
+
+/// Picks the video frames sent to the encoder as keyframes, like `-force_key_frames`.
+pub(crate) struct KeyframeForcer {
+    /// Times not reached yet, in microseconds, in increasing order.
+    times: VecDeque<i64>,
+    expr: *mut AVExpr,
+    frame_number: u64,
+    forced: u64,
+    prev_forced_n: f64,
+    prev_forced_t: f64,
+}
+
+unsafe impl Send for KeyframeForcer {}
+
+impl KeyframeForcer {
+    /// Fails with the FFmpeg error code when the expression cannot be parsed.
+    pub(crate) fn new(force_keyframes: &ForceKeyframes) -> Result<Self, i32> {
+        let mut keyframe_forcer = Self {
+            times: VecDeque::new(),
+            expr: null_mut(),
+            frame_number: 0,
+            forced: 0,
+            prev_forced_n: f64::NAN,
+            prev_forced_t: f64::NAN,
+        };
+        match force_keyframes {
+            ForceKeyframes::Times(times) => {
+                let mut times = times.clone();
+                times.sort_unstable();
+                times.dedup();
+                keyframe_forcer.times = times.into();
+            }
+            ForceKeyframes::Expr(expr) => {
+                let expr = CString::new(expr.as_str()).map_err(|_| AVERROR(EINVAL))?;
+                let names = FORCE_KEYFRAMES_VARS.map(|name| CString::new(name).unwrap());
+                let mut name_ptrs: Vec<*const c_char> = names.iter().map(|name| name.as_ptr()).collect();
+                name_ptrs.push(null());
+                let ret = unsafe {
+                    av_expr_parse(
+                        &mut keyframe_forcer.expr,
+                        expr.as_ptr(),
+                        name_ptrs.as_ptr(),
+                        null(),
+                        null(),
+                        null(),
+                        null(),
+                        0,
+                        null_mut(),
+                    )
+                };
+                if ret < 0 {
+                    return Err(ret);
+                }
+            }
+        }
+        Ok(keyframe_forcer)
+    }
+
+    /// Returns whether the next frame, at `pts` in `time_base`, must be a keyframe.
+    pub(crate) fn force(&mut self, pts: i64, time_base: AVRational) -> bool {
+        let frame_number = self.frame_number;
+        self.frame_number += 1;
+        if pts == AV_NOPTS_VALUE {
+            return false;
+        }
+
+        let mut force = false;
+        // times between the previous frame and this one all map to this frame
+        while self
+            .times
+            .front()
+            .is_some_and(|time| unsafe { av_compare_ts(pts, time_base, *time, AV_TIME_BASE_Q) } >= 0)
+        {
+            self.times.pop_front();
+            force = true;
+        }
+
+        if !self.expr.is_null() {
+            let t = pts as f64 * unsafe { av_q2d(time_base) };
+            let values = [frame_number as f64, self.forced as f64, self.prev_forced_n, self.prev_forced_t, t];
+            if unsafe { av_expr_eval(self.expr, values.as_ptr(), null_mut()) } != 0.0 {
+                force = true;
+            }
+            if force {
+                self.forced += 1;
+                self.prev_forced_n = frame_number as f64;
+                self.prev_forced_t = t;
+            }
+        }
+        force
+    }
+}
+
+impl Drop for KeyframeForcer {
+    fn drop(&mut self) {
+        unsafe { av_expr_free(self.expr) }
+    }
+}
+
+/// FFmpeg's MPEG-family encoders, which take the qscale of each frame from `AVFrame::quality`.
+const FRAME_QSCALE_ENCODERS: [&str; 14] = [
+    "mpeg1video", "mpeg2video", "mpeg4", "msmpeg4v2", "msmpeg4", "wmv1", "wmv2", "h261", "h263", "h263p", "flv",
+    "rv10", "rv20", "mjpeg",
+];
+
+/// Encoders that reconfigure their rate control when the `crf` option changes between frames.
+const FRAME_CRF_ENCODERS: [&str; 2] = ["libx264", "libx264rgb"];
+
+/// How an encoder takes the quality of each frame.
+enum QualityControl {
+    /// Through `AVFrame::quality`.
+    Qscale,
+    /// Through the `crf` option, holding the value currently set.
+    Crf(f32),
+}
+
+/// Applies the quality of [`Output::set_video_quality_ramp`](crate::Output::set_video_quality_ramp)
+/// to the frames sent to a video encoder.
+struct FrameQuality {
+    ramp: QualityRamp,
+    control: QualityControl,
+}
+
+impl FrameQuality {
+    /// Sets the quality of the start of the output on `enc_ctx`, which is not opened yet.
+    ///
+    /// Encoders that cannot change their quality between frames get it as a constant qscale,
+    /// unless `has_qscale` (one was set already), and `None` is returned.
+    unsafe fn init(ramp: QualityRamp, enc_ctx: *mut AVCodecContext, has_qscale: bool) -> Option<Self> {
+        let name = CStr::from_ptr((*(*enc_ctx).codec).name).to_string_lossy();
+        let quality = ramp(0);
+        if FRAME_CRF_ENCODERS.contains(&name.as_ref()) {
+            set_crf(enc_ctx, quality);
+            return Some(Self { ramp, control: QualityControl::Crf(quality) });
+        }
+
+        let per_frame = FRAME_QSCALE_ENCODERS.contains(&name.as_ref());
+        if !per_frame {
+            warn!("Encoder {name} does not support a per-frame quality, the quality ramp is replaced by a constant quality");
+            if has_qscale {
+                return None;
+            }
+        }
+        (*enc_ctx).flags |= ffmpeg_sys_next::AV_CODEC_FLAG_QSCALE as i32;
+        (*enc_ctx).global_quality = qscale_to_lambda(quality);
+        per_frame.then_some(Self { ramp, control: QualityControl::Qscale })
+    }
+
+    /// Sets the quality of `frame`, by its timestamp.
+    unsafe fn apply(&mut self, enc_ctx: *mut AVCodecContext, frame: *mut AVFrame) {
+        if (*frame).pts == AV_NOPTS_VALUE {
+            return;
+        }
+        let time_base = if (*frame).time_base.num > 0 { (*frame).time_base } else { (*enc_ctx).time_base };
+        let quality = (self.ramp)(av_rescale_q((*frame).pts, time_base, AV_TIME_BASE_Q));
+        match &mut self.control {
+            QualityControl::Qscale => (*frame).quality = qscale_to_lambda(quality),
+            QualityControl::Crf(current) => {
+                if *current != quality {
+                    set_crf(enc_ctx, quality);
+                    *current = quality;
+                }
+            }
+        }
+    }
+}
+
+unsafe fn set_crf(enc_ctx: *mut AVCodecContext, crf: f32) {
+    let name = CString::new("crf").unwrap();
+    let ret = av_opt_set_double((*enc_ctx).priv_data, name.as_ptr(), crf as f64, 0);
+    if ret < 0 {
+        warn!("Could not set the CRF to {crf}: {}", av_err2str(ret));
+    }
+}
+
+fn qscale_to_lambda(qscale: f32) -> i32 {
+    (qscale.max(1.0) * ffmpeg_sys_next::FF_QP2LAMBDA as f32).round() as i32
+}
+
 #[cfg(not(feature = "docs-rs"))]
 fn frame_encode(
     enc_ctx: *mut AVCodecContext,
@@ -934,6 +1233,8 @@ fn frame_encode(
     mux_started: &Arc<AtomicBool>,
     stream: *mut AVStream,
     packet_pool: &ObjPool<Packet>,
+    keyframe_forcer: &mut Option<KeyframeForcer>,
+    frame_quality: &mut Option<FrameQuality>,
 ) -> crate::error::Result<bool> {
     unsafe {
         if (*enc_ctx).codec_type == AVMEDIA_TYPE_SUBTITLE {
@@ -975,7 +1276,16 @@ fn frame_encode(
 
             if (*enc_ctx).codec_type == AVMEDIA_TYPE_VIDEO {
                 (*frame).quality = (*enc_ctx).global_quality;
+                if let Some(frame_quality) = frame_quality {
+                    frame_quality.apply(enc_ctx, frame);
+                }
                 (*frame).pict_type = AV_PICTURE_TYPE_NONE;
+                if let Some(keyframe_forcer) = keyframe_forcer {
+                    if keyframe_forcer.force((*frame).pts, (*frame).time_base) {
+                        trace!("Forcing a keyframe at pts {}", (*frame).pts);
+                        (*frame).pict_type = AV_PICTURE_TYPE_I;
+                    }
+                }
             } else {
                 if (*(*enc_ctx).codec).capabilities & AV_CODEC_CAP_PARAM_CHANGE as i32 == 0
                     && (*enc_ctx).ch_layout.nb_channels != (*frame).ch_layout.nb_channels
@@ -1092,7 +1402,7 @@ unsafe fn do_subtitle_out(
         }
         (*pkt).dts = (*pkt).pts;
 
-        if let Err(_) = send_to_mux(PacketBox {
+        if let Err(e) = send_to_mux(PacketBox {
             packet,
             packet_data: PacketData {
                 dts_est: 0,
@@ -1102,8 +1412,8 @@ unsafe fn do_subtitle_out(
                 codecpar: (*stream).codecpar,
             },
         }, pkt_sender, pre_pkt_sender, mux_started) {
-            error!("send subtitle packet failed, mux already finished");
-            return Err(Encoding(EncodingOperationError::MuxerFinished));
+            error!("send subtitle packet failed: {e}");
+            return Err(Encoding(e));
         }
     }
 
@@ -1164,7 +1474,7 @@ unsafe fn encode_frame(
 
         (*pkt).flags |= AV_PKT_FLAG_TRUSTED;
 
-        if let Err(_) = send_to_mux(PacketBox {
+        if let Err(e) = send_to_mux(PacketBox {
             packet,
             packet_data: PacketData {
                 dts_est: 0,
@@ -1174,26 +1484,62 @@ unsafe fn encode_frame(
                 codecpar: (*stream).codecpar,
             },
         }, pkt_sender, pre_pkt_sender, mux_started) {
-            error!("send packet failed, mux already finished");
-            return Err(Encoding(EncodingOperationError::MuxerFinished));
+            error!("send packet failed: {e}");
+            return Err(Encoding(e));
         }
     }
 }
 
 
-fn send_to_mux(packet_box: PacketBox, pkt_sender: &Sender<PacketBox>, pre_pkt_sender: &Sender<PacketBox>, mux_started:&Arc<AtomicBool>) -> Result<(), SendError<PacketBox>> {
+fn send_to_mux(packet_box: PacketBox, pkt_sender: &Sender<PacketBox>, pre_pkt_sender: &Sender<PacketBox>, mux_started:&Arc<AtomicBool>) -> Result<(), EncodingOperationError> {
     if mux_started.load(Ordering::Acquire) {
-        pkt_sender.send(packet_box)
-    } else {
-        if let Err(e) = pre_pkt_sender.send(packet_box) {
-            for _ in 0..64 {
-                if mux_started.load(Ordering::Acquire) {
-                    return pkt_sender.send(e.0);
-                }
-                sleep(Duration::from_millis(100));
-            }
-            return Err(e);
+        return pkt_sender.send(packet_box).map_err(|_| EncodingOperationError::MuxerFinished);
+    }
+
+    let (packet_box, queue_full) = match pre_pkt_sender.try_send(packet_box) {
+        Ok(()) => return Ok(()),
+        Err(TrySendError::Full(packet_box)) => (packet_box, true),
+        Err(TrySendError::Disconnected(packet_box)) => (packet_box, false),
+    };
+    for _ in 0..64 {
+        if mux_started.load(Ordering::Acquire) {
+            return pkt_sender.send(packet_box).map_err(|_| EncodingOperationError::MuxerFinished);
         }
-        Ok(())
+        sleep(Duration::from_millis(100));
+    }
+    if queue_full {
+        error!("Too many packets buffered for output stream {}.", packet_box.packet_data.output_stream_index);
+        return Err(EncodingOperationError::MuxingQueueFull);
     }
-}
\ No newline at end of file
+    Err(EncodingOperationError::MuxerFinished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_YUV420P;
+
+    #[test]
+    fn test_can_drop_frame() {
+        unsafe {
+            let mut frame = Frame::empty();
+            let f = frame.as_mut_ptr();
+            (*f).format = AV_PIX_FMT_YUV420P as i32;
+            (*f).width = 16;
+            (*f).height = 16;
+            assert!(av_frame_get_buffer(f, 0) >= 0);
+
+            // a queue of one frame is full as soon as a frame is received
+            let (_sender, full) = crossbeam_channel::bounded::<FrameBox>(1);
+            let (_sender, roomy) = crossbeam_channel::bounded::<FrameBox>(8);
+            assert!(can_drop_frame(&full, &frame));
+            assert!(!can_drop_frame(&roomy, &frame));
+
+            // keyframes are kept
+            (*f).flags |= AV_FRAME_FLAG_KEY;
+            assert!(!can_drop_frame(&full, &frame));
+            // as is the end of the stream
+            assert!(!can_drop_frame(&full, &Frame::empty()));
+        }
+    }
+}
@@ -3,22 +3,28 @@ use crate::core::context::ffmpeg_context::FfmpegContext;
 use crate::core::context::muxer::Muxer;
 use crate::core::context::obj_pool::ObjPool;
 use crate::core::context::{in_fmt_ctx_free, out_fmt_ctx_free};
+use crate::core::filter::pipeline_event::PipelineEvent;
 use crate::core::scheduler::dec_task::dec_init;
 use crate::core::scheduler::demux_task::demux_init;
 use crate::core::scheduler::enc_task::enc_init;
 use crate::core::scheduler::filter_task::filter_graph_init;
 use crate::core::scheduler::frame_filter_pipeline::{input_pipeline_init, output_pipeline_init};
 use crate::core::scheduler::input_controller::InputController;
-use crate::core::scheduler::mux_task::{mux_init, ready_to_init_mux};
+use crate::core::scheduler::job_report::{InputReport, JobReport, OutputReport, StreamReport};
+use crate::core::scheduler::mux_task::{mux_init, ready_to_init_mux, StreamStatsReporter};
+use crate::core::stream_info::{stream_infos_from_format_context, StreamInfo};
 use crate::error::{AllocFrameError, AllocPacketError};
 use crate::util::thread_synchronizer::ThreadSynchronizer;
+use crossbeam_channel::Receiver;
 use ffmpeg_next::packet::{Mut, Ref};
 use ffmpeg_next::{Frame, Packet};
 use ffmpeg_sys_next::{av_frame_alloc, av_frame_unref, av_packet_unref};
+use log::warn;
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub struct Initialization;
 pub struct Running;
@@ -30,11 +36,63 @@ pub struct FfmpegScheduler<S> {
     status: Arc<AtomicUsize>,
     thread_sync: ThreadSynchronizer,
     result: Arc<Mutex<Option<crate::error::Result<()>>>>,
+    stream_stats_callback: Option<Box<dyn FnMut(StreamStats) + Send>>,
+    stream_stats_window: Duration,
+    /// Created by the first `start` and kept across [`reset`](FfmpegScheduler::reset)s.
+    packet_pool: Option<ObjPool<Packet>>,
+    frame_pool: Option<ObjPool<Frame>>,
+    /// Streams of each input, probed before the job starts, for [`report`](FfmpegScheduler::report).
+    input_streams: Vec<Vec<StreamReport>>,
+    started_at: Option<Instant>,
+    /// When the job was seen ending, and the error it ended with.
+    ended: Mutex<Option<(Instant, Option<String>)>>,
     state: PhantomData<S>,
 }
 unsafe impl<S> Send for FfmpegScheduler<S> {}
 unsafe impl<S> Sync for FfmpegScheduler<S> {}
 
+/// Number of [`PipelineEvent`]s that can wait in the channel returned by
+/// [`FfmpegScheduler::pipeline_events`] before new events are dropped.
+pub const PIPELINE_EVENT_QUEUE_SIZE: usize = 1024;
+
+/// Bitrate statistics of one output stream, reported over a time window.
+///
+/// Delivered to the callback registered with
+/// [`FfmpegScheduler::with_stream_stats_callback`] each time a stream has muxed
+/// packets spanning the configured window, and once more for the last partial
+/// window when the output finishes.
+#[derive(Debug, Clone, Copy)]
+pub struct StreamStats {
+    /// Index of the output (in the order outputs were added to the context).
+    pub output_index: usize,
+    /// Index of the stream within the output.
+    pub stream_index: usize,
+    /// Number of bytes written during the window.
+    pub bytes: u64,
+    /// Number of packets written during the window.
+    pub packets: u64,
+    /// Timestamp (in microseconds) of the first packet of the window.
+    pub start_time_us: i64,
+    /// Duration (in microseconds) covered by the window.
+    pub duration_us: i64,
+    /// Number of frames dropped since the previous report, by the frame rate conversion
+    /// (see [`Output::set_framerate`](crate::Output::set_framerate)) or because the encoder
+    /// could not keep up (see [`Output::set_realtime_drop`](crate::Output::set_realtime_drop)).
+    pub dropped_frames: u64,
+    /// Number of frames duplicated by the frame rate conversion since the previous report.
+    pub duplicated_frames: u64,
+}
+
+impl StreamStats {
+    /// Returns the achieved bitrate over the window, in bits per second.
+    pub fn bitrate(&self) -> f64 {
+        if self.duration_us <= 0 {
+            return 0.0;
+        }
+        self.bytes as f64 * 8.0 * 1_000_000.0 / self.duration_us as f64
+    }
+}
+
 pub(crate) const STATUS_INIT: usize = 0;
 pub(crate) const STATUS_RUN: usize = 1;
 pub(crate) const STATUS_PAUSE: usize = 2;
@@ -64,6 +122,13 @@ impl<S: 'static> FfmpegScheduler<S> {
             status: self.status,
             thread_sync: self.thread_sync,
             result: self.result,
+            stream_stats_callback: self.stream_stats_callback,
+            stream_stats_window: self.stream_stats_window,
+            packet_pool: self.packet_pool,
+            frame_pool: self.frame_pool,
+            input_streams: self.input_streams,
+            started_at: self.started_at,
+            ended: self.ended,
             state: Default::default(),
         }
     }
@@ -88,6 +153,185 @@ impl<S: 'static> FfmpegScheduler<S> {
     pub fn is_ended(&self) -> bool {
         self.status.load(Ordering::Acquire) == STATUS_END
     }
+
+    /// Returns the parameters of the output streams as they were negotiated and written
+    /// to the output headers: codec, resolution, pixel/sample format, time base, etc.
+    ///
+    /// These can differ from the requested settings, e.g. when an encoder only supports
+    /// some pixel formats. The streams of all outputs are returned, in the order the
+    /// outputs were added to the context; the values no longer change once available.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<StreamInfo>)` once every output has written its header.
+    /// - `Err(Error::NotStarted)` before that: the scheduler has not been started, or an
+    ///   output is still waiting for the first frame of each of its streams.
+    ///
+    /// # Example
+    /// ```rust
+    /// let scheduler = context.start().unwrap();
+    /// // ... once the outputs are running
+    /// for stream in scheduler.output_streams().unwrap() {
+    ///     println!("{stream:?}");
+    /// }
+    /// ```
+    pub fn output_streams(&self) -> crate::error::Result<Vec<StreamInfo>> {
+        let mut stream_infos = Vec::new();
+        for mux in &self.ffmpeg_context.muxs {
+            match mux.get_output_streams().lock().unwrap().as_ref() {
+                Some(streams) => stream_infos.extend(streams.iter().cloned()),
+                None => return Err(crate::error::Error::NotStarted),
+            }
+        }
+        Ok(stream_infos)
+    }
+
+    /// Returns the number of packets the decoders could not decode so far, over all inputs.
+    ///
+    /// Unless an input stops at the first error (see
+    /// [`Input::set_error_resilience`](crate::Input::set_error_resilience)), these packets
+    /// are skipped and the job goes on, so a successful job may still have lost some
+    /// content. The count is final once [`is_ended`](Self::is_ended) returns `true`.
+    ///
+    /// # Example
+    /// ```rust
+    /// let scheduler = context.start().unwrap();
+    /// while !scheduler.is_ended() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(100));
+    /// }
+    /// let decode_errors = scheduler.decode_errors();
+    /// scheduler.wait().unwrap();
+    /// ```
+    pub fn decode_errors(&self) -> u64 {
+        self.ffmpeg_context
+            .demuxs
+            .iter()
+            .map(|demux| demux.decode_errors.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns a summary of the job: the streams of its inputs and outputs, the frames and
+    /// media time written, the average speed and the recoverable errors met so far.
+    ///
+    /// The report can be taken in any state. While the job runs it holds the values so far;
+    /// once the job ended it is final and stays valid when the job failed partway, describing
+    /// what was written until the error, which is reported in [`JobReport::error`]. Take it
+    /// from the scheduler returned by [`finish`](FfmpegScheduler::finish) to report a job
+    /// after it ended. With the `serde` feature, the report can be serialized, e.g. to JSON.
+    ///
+    /// # Example
+    /// ```rust
+    /// let (result, scheduler) = FfmpegScheduler::new(context).start().unwrap().finish();
+    /// let report = scheduler.report();
+    /// println!(
+    ///     "{} frames, {:.1}s of media in {:.1}s ({:.1} fps), error: {:?}",
+    ///     report.frames, report.duration_secs, report.elapsed_secs, report.average_fps, report.error
+    /// );
+    /// ```
+    pub fn report(&self) -> JobReport {
+        let finished = self.is_ended();
+        let (ended_at, error) = {
+            let mut ended = self.ended.lock().unwrap();
+            if finished && ended.is_none() {
+                *ended = Some((Instant::now(), None));
+            }
+            match ended.as_ref() {
+                Some((ended_at, error)) => (Some(*ended_at), error.clone()),
+                None => (None, None),
+            }
+        };
+        // the error of a job that has not been waited for yet
+        let error = error.or_else(|| match self.result.lock().unwrap().as_ref() {
+            Some(Err(e)) => Some(e.to_string()),
+            _ => None,
+        });
+
+        let inputs = self
+            .ffmpeg_context
+            .demuxs
+            .iter()
+            .zip(&self.input_streams)
+            .map(|(demux, streams)| InputReport {
+                url: demux.url.clone(),
+                streams: streams.clone(),
+                decode_errors: demux.decode_errors.load(Ordering::Relaxed),
+            })
+            .collect::<Vec<_>>();
+
+        let mut frames = 0;
+        let mut duration_us = 0;
+        let mut outputs = Vec::new();
+        for mux in &self.ffmpeg_context.muxs {
+            let written = mux.get_written();
+            let stream_infos = mux.get_output_streams().lock().unwrap().clone().unwrap_or_default();
+            let mut streams = Vec::new();
+            for stream_info in &stream_infos {
+                let mut stream = StreamReport::new(stream_info);
+                if let Some(written) = written.get(stream.index) {
+                    stream.packets = written.packets.load(Ordering::Relaxed);
+                    stream.bytes = written.bytes.load(Ordering::Relaxed);
+                    stream.duration_secs = written.duration_us() as f64 / 1_000_000.0;
+                }
+                if matches!(stream_info, StreamInfo::Video { .. }) {
+                    frames += stream.packets;
+                }
+                streams.push(stream);
+            }
+            duration_us = written.iter().map(|written| written.duration_us()).fold(duration_us, i64::max);
+            outputs.push(OutputReport {
+                url: mux.url.clone(),
+                streams,
+                bytes: written.iter().map(|written| written.bytes.load(Ordering::Relaxed)).sum(),
+            });
+        }
+
+        let elapsed_secs = match (self.started_at, ended_at) {
+            (Some(started_at), Some(ended_at)) => ended_at.saturating_duration_since(started_at).as_secs_f64(),
+            (Some(started_at), None) => started_at.elapsed().as_secs_f64(),
+            (None, _) => 0.0,
+        };
+        JobReport {
+            decode_errors: inputs.iter().map(|input| input.decode_errors).sum(),
+            inputs,
+            outputs,
+            frames,
+            duration_secs: duration_us as f64 / 1_000_000.0,
+            elapsed_secs,
+            average_fps: if elapsed_secs > 0.0 { frames as f64 / elapsed_secs } else { 0.0 },
+            finished,
+            error,
+        }
+    }
+
+    /// Records the end of the job for [`report`](Self::report), keeping the time it was first
+    /// seen ending.
+    fn set_ended(&self, result: &Option<crate::error::Result<()>>) {
+        crate::core::flush_ffmpeg_log();
+        let error = match result {
+            Some(Err(e)) => Some(e.to_string()),
+            _ => None,
+        };
+        let mut ended = self.ended.lock().unwrap();
+        let ended_at = ended.as_ref().map_or_else(Instant::now, |(ended_at, _)| *ended_at);
+        *ended = Some((ended_at, error));
+    }
+}
+
+/// Returns the streams of each input of `ffmpeg_context`, whose format contexts are only
+/// guaranteed to be open before the job starts.
+fn input_streams(ffmpeg_context: &FfmpegContext) -> Vec<Vec<StreamReport>> {
+    ffmpeg_context
+        .demuxs
+        .iter()
+        .map(|demux| {
+            if demux.in_fmt_ctx.is_null() {
+                return vec![];
+            }
+            unsafe { stream_infos_from_format_context(demux.in_fmt_ctx) }
+                .iter()
+                .map(StreamReport::new)
+                .collect()
+        })
+        .collect()
 }
 
 impl FfmpegScheduler<Initialization> {
@@ -109,12 +353,85 @@ impl FfmpegScheduler<Initialization> {
     /// ```
     pub fn new(ffmpeg_context: FfmpegContext) -> FfmpegScheduler<Initialization> {
         FfmpegScheduler {
+            input_streams: input_streams(&ffmpeg_context),
+            started_at: None,
+            ended: Mutex::new(None),
             ffmpeg_context,
             state: Default::default(),
             thread_sync: ThreadSynchronizer::new(),
             status: Arc::new(AtomicUsize::new(STATUS_INIT)),
             result: Arc::new(Mutex::new(None)),
+            stream_stats_callback: None,
+            stream_stats_window: Duration::from_secs(1),
+            packet_pool: None,
+            frame_pool: None,
+        }
+    }
+
+    /// Registers a callback receiving per-stream bitrate statistics while muxing.
+    ///
+    /// The callback is invoked from the muxer threads with a [`StreamStats`] each time
+    /// an output stream has written packets covering the window set by
+    /// [`with_stream_stats_window`](Self::with_stream_stats_window) (1 second by default).
+    /// This is useful to verify the achieved bitrate of each rendition, e.g. when
+    /// generating HLS ladders or checking a CBR target.
+    ///
+    /// When an input restarts because of `set_stream_loop`, its timestamps continue after the
+    /// previous pass, so the windows go on across the loop. If the timestamps of a stream do
+    /// jump backwards, e.g. on a reset in a live input, the current window is reported up to
+    /// its last packet and a new one starts.
+    ///
+    /// # Example
+    /// ```rust
+    /// let scheduler = FfmpegScheduler::new(context)
+    ///     .with_stream_stats_callback(Box::new(|stats| {
+    ///         println!("output {} stream {}: {:.0} bps", stats.output_index, stats.stream_index, stats.bitrate());
+    ///     }))
+    ///     .start()
+    ///     .unwrap();
+    /// ```
+    pub fn with_stream_stats_callback(mut self, callback: Box<dyn FnMut(StreamStats) + Send>) -> Self {
+        self.stream_stats_callback = Some(callback);
+        self
+    }
+
+    /// Returns a channel receiving the [`PipelineEvent`]s that the frame filters of this job
+    /// send with [`FrameFilterContext::send_event`](crate::filter::frame_filter_context::FrameFilterContext::send_event),
+    /// e.g. scene cuts found by a detection filter, while the job runs.
+    ///
+    /// Filters never wait for the application: when it does not keep up and
+    /// [`PIPELINE_EVENT_QUEUE_SIZE`] events are pending, new events are dropped. The channel
+    /// disconnects once every frame pipeline has finished, which ends iterating over it.
+    /// Calling this again disconnects the previously returned channel.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut scheduler = FfmpegScheduler::new(context);
+    /// let events = scheduler.pipeline_events();
+    /// let scheduler = scheduler.start().unwrap();
+    /// std::thread::spawn(move || {
+    ///     for event in events {
+    ///         println!("{event:?}");
+    ///     }
+    /// });
+    /// scheduler.wait().unwrap();
+    /// ```
+    pub fn pipeline_events(&mut self) -> Receiver<PipelineEvent> {
+        let (sender, receiver) = crossbeam_channel::bounded(PIPELINE_EVENT_QUEUE_SIZE);
+        let demux_pipelines = self.ffmpeg_context.demuxs.iter_mut().filter_map(|demux| demux.frame_pipelines.as_mut());
+        let mux_pipelines = self.ffmpeg_context.muxs.iter_mut().filter_map(|mux| mux.frame_pipelines.as_mut());
+        for frame_pipeline in demux_pipelines.chain(mux_pipelines).flatten() {
+            frame_pipeline.set_event_sender(sender.clone());
         }
+        receiver
+    }
+
+    /// Sets the window over which [`StreamStats`] are accumulated before being reported.
+    ///
+    /// The window is measured in stream time, not wall-clock time. Defaults to 1 second.
+    pub fn with_stream_stats_window(mut self, window: Duration) -> Self {
+        self.stream_stats_window = window;
+        self
     }
 
     /// Initializes all FFmpeg components (demuxers, encoders, filters, muxers)
@@ -134,10 +451,17 @@ impl FfmpegScheduler<Initialization> {
     /// // Now it's in Running state, you can wait or pause/abort, etc.
     /// ```
     pub fn start(mut self) -> crate::error::Result<FfmpegScheduler<Running>> {
-        let packet_pool = ObjPool::new(64, new_packet, unref_packet, packet_is_null)?;
-        let frame_pool = ObjPool::new(64, new_frame, unref_frame, frame_is_null)?;
+        if self.packet_pool.is_none() {
+            self.packet_pool = Some(ObjPool::new(64, new_packet, unref_packet, packet_is_null)?);
+        }
+        if self.frame_pool.is_none() {
+            self.frame_pool = Some(ObjPool::new(64, new_frame, unref_frame, frame_is_null)?);
+        }
+        let packet_pool = self.packet_pool.clone().unwrap();
+        let frame_pool = self.frame_pool.clone().unwrap();
         let scheduler_status = self.status.clone();
         scheduler_status.store(STATUS_RUN, Ordering::Release);
+        self.started_at = Some(Instant::now());
         let thread_sync = self.thread_sync.clone();
         let scheduler_result = self.result.clone();
 
@@ -145,18 +469,23 @@ impl FfmpegScheduler<Initialization> {
         let mux_stream_nodes = self.ffmpeg_context.muxs.iter().flat_map(|mux| mux.mux_stream_nodes.clone()).map(|mux_stream| mux_stream.clone()).collect::<Vec<_>>();
         let input_controller = InputController::new(demux_nodes, mux_stream_nodes);
         let input_controller = Arc::new(input_controller);
+        let stream_stats = self.stream_stats_callback.take().map(|callback| {
+            StreamStatsReporter::new(callback, self.stream_stats_window.as_micros() as i64)
+        });
 
         // Muxer
         for (mux_idx, mux) in self.ffmpeg_context.muxs.iter_mut().enumerate() {
             // Even if it's not ready here, it's going to be ready later, so it locks first
             thread_sync.thread_start();
             if mux.is_ready() {
+                let mux_stream_stats = stream_stats.as_ref().map(|reporter| reporter.for_muxer(mux));
                 if let Err(e) = mux_init(
                     mux_idx,
                     mux,
                     packet_pool.clone(),
                     input_controller.clone(),
                     mux.mux_stream_nodes.clone(),
+                    mux_stream_stats,
                     scheduler_status.clone(),
                     thread_sync.clone(),
                     scheduler_result.clone(),
@@ -190,17 +519,23 @@ impl FfmpegScheduler<Initialization> {
         // Encoder
         let ffmpeg_context = &mut self.ffmpeg_context;
         for (mux_idx, mux) in &mut ffmpeg_context.muxs.iter_mut().enumerate() {
+            let mux_stream_stats = stream_stats.as_ref().map(|reporter| reporter.for_muxer(mux));
             let ready_sender = ready_to_init_mux(
                 mux_idx,
                 mux,
                 packet_pool.clone(),
                 input_controller.clone(),
+                mux_stream_stats,
                 scheduler_status.clone(),
                 thread_sync.clone(),
                 scheduler_result.clone(),
             );
 
-            for enc_stream in mux.take_streams_mut() {
+            let mut used_encoder_opts = HashSet::new();
+            let enc_streams = mux.take_streams_mut();
+            // an output whose streams are all copied has no encoder to use the options
+            let has_encoders = !enc_streams.is_empty();
+            for enc_stream in enc_streams {
                 if let Err(e) = enc_init(
                     mux_idx,
                     enc_stream,
@@ -214,6 +549,8 @@ impl FfmpegScheduler<Initialization> {
                     &mux.video_codec_opts,
                     &mux.audio_codec_opts,
                     &mux.subtitle_codec_opts,
+                    &mux.encoder_opts,
+                    &mut used_encoder_opts,
                     mux.oformat_flags,
                     frame_pool.clone(),
                     packet_pool.clone(),
@@ -224,6 +561,15 @@ impl FfmpegScheduler<Initialization> {
                     return Err(e);
                 }
             }
+
+            if let Some(encoder_opts) = mux.encoder_opts.as_ref().filter(|_| has_encoders) {
+                for key in encoder_opts.keys().filter(|key| !used_encoder_opts.contains(*key)) {
+                    warn!(
+                        "Encoder option '{}' has not been used for any stream of output #{mux_idx}, it may be misspelled or not supported by the selected encoders.",
+                        key.to_string_lossy()
+                    );
+                }
+            }
         }
 
         // Filter graph
@@ -349,12 +695,34 @@ impl FfmpegScheduler<Running> {
     /// assert!(result.is_ok());
     /// ```
     pub fn wait(self) -> crate::error::Result<()> {
+        self.wait_result()
+    }
+
+    /// Blocks until the FFmpeg job finishes like [`wait`](Self::wait), but keeps the scheduler
+    /// so that it can run another job with [`reset`](FfmpegScheduler::reset).
+    ///
+    /// # Returns
+    /// The result of the job, and the ended scheduler.
+    ///
+    /// # Example
+    /// ```rust
+    /// let (result, scheduler) = FfmpegScheduler::new(context).start().unwrap().finish();
+    /// result.unwrap();
+    /// let result = scheduler.reset(next_context).start().unwrap().wait();
+    /// ```
+    pub fn finish(self) -> (crate::error::Result<()>, FfmpegScheduler<Ended>) {
+        let result = self.wait_result();
+        (result, self.into_state())
+    }
+
+    fn wait_result(&self) -> crate::error::Result<()> {
         if self.status.load(Ordering::Acquire) != STATUS_END {
             self.thread_sync.wait_for_all_threads();
             self.status.store(STATUS_END, Ordering::Release);
         }
 
         let option = self.result.lock().unwrap().take();
+        self.set_ended(&option);
         match option {
             None => {
                 log::info!("FFmpeg task succeeded.");
@@ -410,6 +778,7 @@ impl std::future::Future for FfmpegScheduler<Running> {
 
         if this.status.load(Ordering::Acquire) == STATUS_END {
             let option = this.result.lock().unwrap().take();
+            this.set_ended(&option);
             std::task::Poll::Ready(match option {
                 None => {
                     log::info!("FFmpeg task succeeded.");
@@ -459,6 +828,59 @@ impl FfmpegScheduler<Paused> {
     }
 }
 
+impl FfmpegScheduler<Ended> {
+
+    /// Prepares the scheduler of a finished job to run `ffmpeg_context`, cheaper than creating a
+    /// new scheduler for each of many short jobs (e.g. thumbnails of a batch of files).
+    ///
+    /// What is reused and what is rebuilt:
+    /// - The frame and packet pools are reused. Frames and packets are unreferenced when they
+    ///   return to a pool, so no data of the previous job reaches the next one.
+    /// - The stream stats window is kept.
+    /// - The status and the result are replaced by new ones rather than reset in place, so
+    ///   that a thread of the previous job still winding down cannot see or change the state of
+    ///   the next job. The thread synchronizer is replaced for the same reason.
+    /// - The stream stats callback is consumed by each job; register it again with
+    ///   [`with_stream_stats_callback`](FfmpegScheduler::with_stream_stats_callback).
+    /// - The worker threads are not pooled: each stage thread owns the codec, filter graph or
+    ///   format context it drives and exits once its stream ends, so every job spawns its own.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut scheduler: Option<FfmpegScheduler<Ended>> = None;
+    /// for (i, file) in files.iter().enumerate() {
+    ///     let context = FfmpegContext::builder()
+    ///         .input(file.as_str())
+    ///         .filter_desc("scale=160:-1")
+    ///         .output(Output::from(format!("thumb_{i}.jpg")).set_max_video_frames(1))
+    ///         .build()
+    ///         .unwrap();
+    ///     let next = match scheduler.take() {
+    ///         Some(scheduler) => scheduler.reset(context),
+    ///         None => FfmpegScheduler::new(context),
+    ///     };
+    ///     let (result, ended) = next.start().unwrap().finish();
+    ///     result.unwrap();
+    ///     scheduler = Some(ended);
+    /// }
+    /// ```
+    pub fn reset(self, ffmpeg_context: FfmpegContext) -> FfmpegScheduler<Initialization> {
+        FfmpegScheduler {
+            input_streams: input_streams(&ffmpeg_context),
+            started_at: None,
+            ended: Mutex::new(None),
+            ffmpeg_context,
+            status: Arc::new(AtomicUsize::new(STATUS_INIT)),
+            thread_sync: ThreadSynchronizer::new(),
+            result: Arc::new(Mutex::new(None)),
+            stream_stats_callback: None,
+            stream_stats_window: self.stream_stats_window,
+            packet_pool: self.packet_pool,
+            frame_pool: self.frame_pool,
+            state: Default::default(),
+        }
+    }
+}
 
 
 fn new_frame() -> crate::error::Result<Frame> {
@@ -524,20 +946,32 @@ pub(crate) fn set_scheduler_error(
     let mut scheduler_result = scheduler_result.lock().unwrap();
     if scheduler_result.is_none() {
         scheduler_result.replace(Err(error.into()));
-        scheduler_status.store(STATUS_END, Ordering::Release);
+    }
+    scheduler_status.store(STATUS_END, Ordering::Release);
+}
+
+/// Records `error` as the result of the job without stopping it, for errors that should
+/// fail the job but still let every output be finished (e.g. a too high decoding error rate).
+pub(crate) fn set_scheduler_error_deferred(
+    scheduler_result: &Arc<Mutex<Option<crate::error::Result<()>>>>,
+    error: impl Into<crate::error::Error>,
+) {
+    let mut scheduler_result = scheduler_result.lock().unwrap();
+    if scheduler_result.is_none() {
+        scheduler_result.replace(Err(error.into()));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::core::context::ffmpeg_context::FfmpegContext;
-    use crate::core::context::input::Input;
-    use crate::core::context::output::Output;
+    use crate::core::context::input::{ErrorResilience, Input};
+    use crate::core::context::output::{EvenDimensionsPolicy, Output};
     use crate::core::filter::frame_filter::NoopFilter;
     use crate::core::scheduler::ffmpeg_scheduler::{
-        FfmpegScheduler, Initialization, Paused, Running, STATUS_INIT, STATUS_PAUSE, STATUS_RUN,
+        Ended, FfmpegScheduler, Initialization, Paused, Running, STATUS_INIT, STATUS_PAUSE, STATUS_RUN,
     };
-    use ffmpeg_sys_next::AVMediaType;
+    use ffmpeg_sys_next::{AVMediaType, AVPixelFormat, AVRational};
     use log::{info, warn};
     use std::sync::atomic::Ordering;
     use std::sync::{Arc, Mutex};
@@ -633,6 +1067,517 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_write_failure_is_reported() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let output = Output::new_by_write_callback(|_buf: &[u8]| -> i32 {
+            ffmpeg_sys_next::AVERROR(ffmpeg_sys_next::EIO)
+        })
+        .set_format("mpegts");
+
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .and_then(|scheduler| scheduler.wait());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .output("output_report.mp4")
+            .build()
+            .unwrap();
+        let (result, scheduler) = FfmpegScheduler::new(context).start().unwrap().finish();
+        assert!(result.is_ok());
+        let report = scheduler.report();
+        info!("{report:?}");
+        assert!(report.finished && report.error.is_none());
+        assert_eq!(report.inputs[0].streams.len(), 2);
+        assert_eq!(report.outputs[0].streams[0].media_type, "video");
+        assert_eq!(report.frames, report.outputs[0].streams[0].packets);
+        assert!(report.frames > 0 && report.duration_secs > 0.0 && report.average_fps > 0.0);
+        std::fs::remove_file("output_report.mp4").unwrap();
+
+        // a failed job still reports what it did
+        let output = Output::new_by_write_callback(|_buf: &[u8]| -> i32 {
+            ffmpeg_sys_next::AVERROR(ffmpeg_sys_next::EIO)
+        })
+        .set_format("mpegts");
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+        let (result, scheduler) = FfmpegScheduler::new(context).start().unwrap().finish();
+        assert!(result.is_err());
+        let report = scheduler.report();
+        assert!(report.finished && report.error.is_some());
+        assert_eq!(report.inputs.len(), 1);
+    }
+
+    #[test]
+    fn test_error_resilience() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        // wipe a slice of the media data in the middle of the file
+        let mut data = std::fs::read("test.mp4").unwrap();
+        let len = data.len();
+        data[len / 2..len / 2 + len / 20].fill(0);
+        let damaged = std::env::temp_dir().join("ez_ffmpeg_damaged.mp4");
+        std::fs::write(&damaged, data).unwrap();
+
+        let context = FfmpegContext::builder()
+            .input(Input::from(damaged.to_str().unwrap()).set_error_resilience(ErrorResilience::Maximum))
+            .output("output.mp4")
+            .build()
+            .unwrap();
+        let scheduler = FfmpegScheduler::new(context).start().unwrap();
+        while !scheduler.is_ended() {
+            sleep(Duration::from_millis(10));
+        }
+        let decode_errors = scheduler.decode_errors();
+        info!("{decode_errors} packets could not be decoded");
+        let result = scheduler.wait();
+        std::fs::remove_file(&damaged).unwrap();
+
+        // the damaged packets are skipped, the job still succeeds
+        assert!(decode_errors > 0);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_direct_write() {
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output_direct.ts").set_interleaved(false))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let streams = crate::core::stream_info::find_all_stream_infos("output_direct.ts").unwrap();
+        assert_eq!(streams.len(), 2);
+        std::fs::remove_file("output_direct.ts").unwrap();
+    }
+
+    #[test]
+    fn test_output_streams() {
+        use crate::core::stream_info::StreamInfo;
+
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .filter_desc("scale=160:90")
+            .output(Output::from("output_streams.mp4").set_video_codec("libx264"))
+            .build()
+            .unwrap();
+        let scheduler = FfmpegScheduler::new(context);
+        assert!(matches!(scheduler.output_streams(), Err(crate::error::Error::NotStarted)));
+
+        let scheduler = scheduler.start().unwrap();
+        while !scheduler.is_ended() {
+            sleep(Duration::from_millis(10));
+        }
+        let streams = scheduler.output_streams().unwrap();
+        assert!(scheduler.wait().is_ok());
+        std::fs::remove_file("output_streams.mp4").unwrap();
+
+        // the streams as the encoders negotiated them
+        assert_eq!(streams.len(), 2);
+        let Some(StreamInfo::Video { width, height, codec_name, pixel_format, .. }) = streams
+            .iter()
+            .find(|stream| matches!(stream, StreamInfo::Video { .. }))
+        else {
+            panic!("no video output stream in {streams:?}");
+        };
+        assert_eq!((*width, *height), (160, 90));
+        assert_eq!(codec_name, "h264");
+        assert_eq!(*pixel_format, AVPixelFormat::AV_PIX_FMT_YUV420P as i32);
+        assert!(streams.iter().any(|stream| matches!(stream, StreamInfo::Audio { .. })));
+    }
+
+    #[test]
+    fn test_stream_stats_stream_loop() {
+        let duration_us = crate::core::container_info::get_duration_us("test.mp4").unwrap();
+        let stats = Arc::new(Mutex::new(Vec::new()));
+        let reported = stats.clone();
+        let context = FfmpegContext::builder()
+            .input(Input::from("test.mp4").set_stream_loop(1))
+            .output("output_stats_loop.mp4")
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context)
+            .with_stream_stats_window(Duration::from_millis(200))
+            .with_stream_stats_callback(Box::new(move |stats| reported.lock().unwrap().push(stats)))
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // (packets, bytes) of each stream of the output file
+        let mut written = [(0u64, 0u64); 2];
+        let mut input = ffmpeg_next::format::input(&"output_stats_loop.mp4").unwrap();
+        for (stream, packet) in input.packets() {
+            written[stream.index()].0 += 1;
+            written[stream.index()].1 += packet.size() as u64;
+        }
+        let _ = std::fs::remove_file("output_stats_loop.mp4");
+
+        let stats = stats.lock().unwrap();
+        for (stream_index, written) in written.into_iter().enumerate() {
+            let windows: Vec<_> = stats.iter().filter(|stats| stats.stream_index == stream_index).collect();
+            let packets = windows.iter().map(|stats| stats.packets).sum::<u64>();
+            let bytes = windows.iter().map(|stats| stats.bytes).sum::<u64>();
+            assert_eq!((packets, bytes), written, "stream {stream_index}");
+            // the second pass continues the timestamps of the first one
+            let starts: Vec<i64> = windows.iter().map(|stats| stats.start_time_us).collect();
+            assert!(starts.windows(2).all(|pair| pair[0] < pair[1]), "stream {stream_index}: {starts:?}");
+            assert!(starts.last().is_some_and(|&start| start > duration_us), "stream {stream_index}: {starts:?}");
+        }
+    }
+
+    #[test]
+    fn test_gop_without_b_frames() {
+        let gop_size = 10;
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("output_gop.mp4")
+                    .set_video_codec("libx264")
+                    .set_max_b_frames(0)
+                    .set_gop_size(gop_size)
+                    .set_keyint_min(gop_size)
+                    .set_video_codec_opt("sc_threshold", "0"),
+            )
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context).start().unwrap().wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let mut input = ffmpeg_next::format::input(&"output_gop.mp4").unwrap();
+        let video_index = input.streams().best(ffmpeg_next::media::Type::Video).unwrap().index();
+        let packets: Vec<_> = input
+            .packets()
+            .filter(|(stream, _)| stream.index() == video_index)
+            .map(|(_, packet)| (packet.pts(), packet.dts(), packet.is_key()))
+            .collect();
+        let _ = std::fs::remove_file("output_gop.mp4");
+
+        assert!(packets.len() > gop_size as usize, "{} packets", packets.len());
+        // no B-frames: the frames are not reordered
+        assert!(packets.iter().all(|(pts, dts, _)| pts == dts), "{packets:?}");
+        let keyframes: Vec<usize> = packets.iter().enumerate().filter(|(_, packet)| packet.2).map(|(i, _)| i).collect();
+        let expected: Vec<usize> = (0..packets.len()).step_by(gop_size as usize).collect();
+        assert_eq!(keyframes, expected);
+
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output_gop.mp4").set_gop_size(-1))
+            .build();
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::OpenOutput(crate::error::OpenOutputError::OptionValueTooSmall(ref name, -1, 0)))
+                if name == "gop_size"
+        ));
+        let _ = std::fs::remove_file("output_gop.mp4");
+    }
+
+    #[test]
+    fn test_realtime_drop() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let video_packets = |url: &str| {
+            let mut input = ffmpeg_next::format::input(&url).unwrap();
+            let video_index = input.streams().best(ffmpeg_next::media::Type::Video).unwrap().index();
+            input.packets().filter(|(stream, _)| stream.index() == video_index).count()
+        };
+
+        let stats = Arc::new(Mutex::new(Vec::new()));
+        let reported = stats.clone();
+        // the file is decoded much faster than a slow preset encodes it, so the encoder
+        // queue fills up
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("output_realtime_drop.mp4")
+                    .set_video_codec("libx264")
+                    .set_video_codec_opt("preset", "veryslow")
+                    .set_realtime_drop(true),
+            )
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context)
+            .with_stream_stats_callback(Box::new(move |stats| reported.lock().unwrap().push(stats)))
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok());
+        let written = video_packets("output_realtime_drop.mp4");
+        let _ = std::fs::remove_file("output_realtime_drop.mp4");
+
+        let stats = stats.lock().unwrap();
+        let video = stats.iter().filter(|stats| stats.stream_index == 0);
+        assert!(video.map(|stats| stats.dropped_frames).sum::<u64>() > 0);
+        assert!(written > 0 && written < video_packets("test.mp4"), "{written} packets");
+        assert!(stats.iter().filter(|stats| stats.stream_index == 1).all(|stats| stats.dropped_frames == 0));
+    }
+
+    #[test]
+    fn test_framerate_conversion_stats() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let stats = Arc::new(Mutex::new(Vec::new()));
+        let reported = stats.clone();
+        // doubling the frame rate duplicates every frame
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output.mp4").set_framerate(AVRational { num: 120, den: 1 }))
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context)
+            .with_stream_stats_callback(Box::new(move |stats| reported.lock().unwrap().push(stats)))
+            .start()
+            .unwrap()
+            .wait();
+
+        assert!(result.is_ok());
+        let stats = stats.lock().unwrap();
+        assert!(stats.iter().filter(|stats| stats.stream_index == 0).map(|stats| stats.duplicated_frames).sum::<u64>() > 0);
+        assert!(stats.iter().filter(|stats| stats.stream_index == 1).all(|stats| stats.duplicated_frames == 0));
+    }
+
+    #[test]
+    fn test_deterministic() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let transcode = || {
+            let context = FfmpegContext::builder()
+                .input("test.mp4")
+                .output("output.mp4")
+                .set_deterministic(true)
+                .build()
+                .unwrap();
+            let result = FfmpegScheduler::new(context).start().unwrap().wait();
+            assert!(result.is_ok());
+
+            let mut hasher = DefaultHasher::new();
+            std::fs::read("output.mp4").unwrap().hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(transcode(), transcode());
+    }
+
+    #[test]
+    fn test_image_sequence() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(
+                Output::from("frame_%04d.jpg")
+                    .set_frame_interval(10)
+                    .set_max_video_frames(3)
+                    .set_video_qscale(2),
+            )
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context).start().unwrap().wait();
+
+        let files = (1..=4).map(|number| format!("frame_{number:04}.jpg")).collect::<Vec<_>>();
+        let exists = files.iter().map(|file| std::path::Path::new(file).exists()).collect::<Vec<_>>();
+        for file in &files {
+            let _ = std::fs::remove_file(file);
+        }
+        assert!(result.is_ok());
+        assert_eq!(exists, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn test_force_keyframes() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let keyframe_times = |output: Output| {
+            let context = FfmpegContext::builder()
+                .input("test.mp4")
+                .output(
+                    output
+                        .set_recording_time_us(3_000_000)
+                        .set_gop_size(1000)
+                        .set_video_codec_opt("sc_threshold", "0"),
+                )
+                .build()
+                .unwrap();
+            let result = FfmpegScheduler::new(context).start().unwrap().wait();
+            assert!(result.is_ok());
+
+            let mut input = ffmpeg_next::format::input(&"output_keyframes.mp4").unwrap();
+            let video_index = input.streams().best(ffmpeg_next::media::Type::Video).unwrap().index();
+            let times = input
+                .packets()
+                .filter(|(stream, packet)| stream.index() == video_index && packet.is_key())
+                .map(|(stream, packet)| packet.pts().unwrap() as f64 * f64::from(stream.time_base()))
+                .collect::<Vec<_>>();
+            let _ = std::fs::remove_file("output_keyframes.mp4");
+            times
+        };
+        let near = |times: &[f64], expected: &[f64]| {
+            times.len() == expected.len() && times.iter().zip(expected).all(|(t, e)| (t - e).abs() < 0.1)
+        };
+
+        let times = keyframe_times(
+            Output::from("output_keyframes.mp4").set_force_keyframes(vec![2_000_000, 0, 1_000_000]),
+        );
+        assert!(near(&times, &[0.0, 1.0, 2.0]), "{times:?}");
+
+        let times = keyframe_times(Output::from("output_keyframes.mp4").set_force_keyframes_expr("gte(t,n_forced*1.5)"));
+        assert!(near(&times, &[0.0, 1.5]), "{times:?}");
+
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .output(Output::from("output_keyframes.mp4").set_force_keyframes_expr("gte(t,"))
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_video_quality_ramp() {
+        // average packet size of the video before and after 1s
+        let packet_sizes = |video_codec: &str, ramp: fn(i64) -> f32| {
+            let context = FfmpegContext::builder()
+                .input("test.mp4")
+                .output(
+                    Output::from("output_quality_ramp.mkv")
+                        .add_stream_map("0:v")
+                        .set_video_codec(video_codec)
+                        .set_recording_time_us(2_000_000)
+                        .set_video_quality_ramp(ramp),
+                )
+                .build()
+                .unwrap();
+            let result = FfmpegScheduler::new(context).start().unwrap().wait();
+            assert!(result.is_ok(), "{video_codec}: {:?}", result.err());
+
+            let mut input = ffmpeg_next::format::input(&"output_quality_ramp.mkv").unwrap();
+            let (mut before, mut after) = ((0, 0), (0, 0));
+            for (stream, packet) in input.packets() {
+                let time = packet.pts().unwrap() as f64 * f64::from(stream.time_base());
+                let total = if time < 1.0 { &mut before } else { &mut after };
+                *total = (total.0 + packet.size(), total.1 + 1);
+            }
+            let _ = std::fs::remove_file("output_quality_ramp.mkv");
+            (before.0 / before.1, after.0 / after.1)
+        };
+
+        // high quality for the first second only
+        let (before, after) = packet_sizes("mpeg4", |pts_us| if pts_us < 1_000_000 { 2.0 } else { 31.0 });
+        assert!(before > 2 * after, "mpeg4: {before} {after}");
+        let (before, after) = packet_sizes("libx264", |pts_us| if pts_us < 1_000_000 { 15.0 } else { 45.0 });
+        assert!(before > 2 * after, "libx264: {before} {after}");
+    }
+
+    #[test]
+    fn test_even_dimensions_policy() {
+        let encode = |policy: EvenDimensionsPolicy| {
+            let context = FfmpegContext::builder()
+                .input("test.mp4")
+                .filter_desc("scale=399:301")
+                .output(
+                    Output::from("output_even_dimensions.mp4")
+                        .set_video_codec("libx264")
+                        .set_recording_time_us(500_000)
+                        .set_even_dimensions_policy(policy),
+                )
+                .build()
+                .unwrap();
+            let result = FfmpegScheduler::new(context).start().unwrap().wait();
+            let size = result.map(|_| {
+                let input = ffmpeg_next::format::input(&"output_even_dimensions.mp4").unwrap();
+                let stream = input.streams().best(ffmpeg_next::media::Type::Video).unwrap();
+                let parameters = stream.parameters();
+                unsafe { ((*parameters.as_ptr()).width, (*parameters.as_ptr()).height) }
+            });
+            let _ = std::fs::remove_file("output_even_dimensions.mp4");
+            size
+        };
+
+        assert_eq!(encode(EvenDimensionsPolicy::Pad).unwrap(), (400, 302));
+        assert_eq!(encode(EvenDimensionsPolicy::Crop).unwrap(), (398, 300));
+        assert!(encode(EvenDimensionsPolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_pad_to_duration() {
+        let context = FfmpegContext::builder()
+            .input(Input::from("test.mp4").set_recording_time_us(1_000_000))
+            .output(Output::from("output_padded.mp4").set_pad_to_duration_us(2_500_000))
+            .build()
+            .unwrap();
+        FfmpegScheduler::new(context).start().unwrap().wait().unwrap();
+
+        let input = ffmpeg_next::format::input(&"output_padded.mp4").unwrap();
+        for stream in input.streams() {
+            let duration = stream.duration() as f64 * f64::from(stream.time_base());
+            assert!((duration - 2.5).abs() < 0.1, "stream {}: {duration}s", stream.index());
+        }
+        drop(input);
+        std::fs::remove_file("output_padded.mp4").unwrap();
+    }
+
+    #[test]
+    fn test_raw_video_output() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let context = FfmpegContext::builder()
+            .input("test.mp4")
+            .filter_desc("scale=64:48")
+            .output(
+                Output::from("output.yuv")
+                    .set_video_pix_fmt(AVPixelFormat::AV_PIX_FMT_GRAY8)
+                    .set_max_video_frames(2),
+            )
+            .build()
+            .unwrap();
+        let result = FfmpegScheduler::new(context).start().unwrap().wait();
+
+        let size = std::fs::metadata("output.yuv").map(|metadata| metadata.len());
+        let _ = std::fs::remove_file("output.yuv");
+        assert!(result.is_ok());
+        // two frames of 64x48 luma bytes
+        assert_eq!(size.unwrap(), 2 * 64 * 48);
+    }
+
     #[test]
     fn test_thumbnail() {
         let _ = env_logger::builder()
@@ -815,6 +1760,396 @@ mod tests {
         scheduler.wait().unwrap();
     }
 
+    #[test]
+    fn test_pipeline_events() {
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use crate::core::filter::pipeline_event::PipelineEvent;
+        use ffmpeg_next::Frame;
+
+        struct PtsEventFilter;
+
+        impl FrameFilter for PtsEventFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                let pts = unsafe { (*frame.as_ptr()).pts };
+                ctx.send_event(PipelineEvent::Custom { name: "pts".to_string(), value: pts.to_string() });
+                Ok(Some(frame))
+            }
+        }
+
+        let output = Output::from("output.mp4").add_frame_pipeline(
+            FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO).filter("pts", Box::new(PtsEventFilter)),
+        );
+        let context = FfmpegContext::builder().input("test.mp4").output(output).build().unwrap();
+
+        let mut scheduler = FfmpegScheduler::new(context);
+        let events = scheduler.pipeline_events();
+        scheduler.start().unwrap().wait().unwrap();
+
+        // the pipeline is done, so the iteration ends after the queued events
+        assert!(events.iter().count() > 0);
+    }
+
+    #[test]
+    fn test_pipeline_stats() {
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use crate::core::filter::frame_pipeline::FRAMES_IN_ATTRIBUTE;
+        use ffmpeg_next::Frame;
+
+        /// Drops every other frame, and records the frames_in attribute it sees last.
+        struct HalveFilter(u64, Arc<Mutex<Option<u64>>>);
+
+        impl FrameFilter for HalveFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                if unsafe { (*frame.as_ptr()).buf[0].is_null() } {
+                    return Ok(Some(frame));
+                }
+                self.0 += 1;
+                Ok((self.0 % 2 == 1).then_some(frame))
+            }
+
+            fn uninit(&mut self, ctx: &FrameFilterContext) {
+                *self.1.lock().unwrap() = ctx.get_attribute::<u64>(FRAMES_IN_ATTRIBUTE).copied();
+            }
+        }
+
+        let seen_frames_in = Arc::new(Mutex::new(None));
+        let builder = FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+            .filter("halve", Box::new(HalveFilter(0, seen_frames_in.clone())));
+        let stats = builder.stats();
+        let output = Output::from("-")
+            .set_format("null")
+            .set_recording_time_us(1_000_000)
+            .add_frame_pipeline(builder);
+        FfmpegContext::builder().input("test.mp4").output(output).build().unwrap().start().unwrap().wait().unwrap();
+
+        assert!(stats.frames_in() > 0);
+        assert_eq!(stats.frames_out(), stats.frames_in().div_ceil(2));
+        assert_eq!(*seen_frames_in.lock().unwrap(), Some(stats.frames_in()));
+    }
+
+    #[test]
+    fn test_pipeline_source_size() {
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use crate::core::filter::frame_pipeline::{SOURCE_HEIGHT_ATTRIBUTE, SOURCE_WIDTH_ATTRIBUTE};
+        use crate::core::stream_info::{find_video_stream_info, StreamInfo};
+        use ffmpeg_next::Frame;
+
+        /// Records the source size and the size of the frames it sees.
+        struct SizeFilter(Arc<Mutex<Option<((i32, i32), (i32, i32))>>>);
+
+        impl FrameFilter for SizeFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                if let (Some(width), Some(height)) = (
+                    ctx.get_attribute::<i32>(SOURCE_WIDTH_ATTRIBUTE),
+                    ctx.get_attribute::<i32>(SOURCE_HEIGHT_ATTRIBUTE),
+                ) {
+                    let frame_size = unsafe { ((*frame.as_ptr()).width, (*frame.as_ptr()).height) };
+                    *self.0.lock().unwrap() = Some(((*width, *height), frame_size));
+                }
+                Ok(Some(frame))
+            }
+        }
+
+        let Some(StreamInfo::Video { width, height, .. }) = find_video_stream_info("test.mp4").unwrap() else {
+            panic!("test.mp4 has no video stream");
+        };
+
+        let sizes = Arc::new(Mutex::new(None));
+        let output = Output::from("-")
+            .set_format("null")
+            .set_recording_time_us(500_000)
+            .add_frame_pipeline(
+                FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                    .filter("size", Box::new(SizeFilter(sizes.clone()))),
+            );
+        FfmpegContext::builder()
+            .input("test.mp4")
+            .filter_desc("scale=160:90")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        // the pipeline after the filtergraph still sees the size of the decoded stream
+        assert_eq!(*sizes.lock().unwrap(), Some(((width, height), (160, 90))));
+    }
+
+    #[test]
+    fn test_burn_in_bitmap_subtitle() {
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use crate::core::stream_info::{find_video_stream_info, StreamInfo};
+        use ffmpeg_next::Frame;
+        use ffmpeg_sys_next::AVCodecID::AV_CODEC_ID_DVD_SUBTITLE;
+        use ffmpeg_sys_next::AVSubtitleType::SUBTITLE_BITMAP;
+        use ffmpeg_sys_next::{
+            av_interleaved_write_frame, av_new_packet, av_packet_alloc, av_packet_free, av_packet_rescale_ts,
+            av_write_trailer, avcodec_alloc_context3, avcodec_encode_subtitle, avcodec_find_encoder,
+            avcodec_free_context, avcodec_open2, avcodec_parameters_from_context, avformat_alloc_output_context2,
+            avformat_free_context, avformat_new_stream, avformat_write_header, avio_closep, avio_open, AVSubtitle,
+            AVSubtitleRect, AVIO_FLAG_WRITE,
+        };
+        use std::ffi::CString;
+        use std::ptr::{null, null_mut};
+
+        // the subtitle: a white box at (16, 16), shown for the first 5 seconds
+        const BOX: (usize, usize, usize, usize) = (16, 16, 32, 16);
+
+        /// Writes a Matroska file with a single DVD subtitle of an opaque white box.
+        unsafe fn write_dvd_subtitle(path: &str, width: i32, height: i32) {
+            let encoder = avcodec_find_encoder(AV_CODEC_ID_DVD_SUBTITLE);
+            assert!(!encoder.is_null());
+            let mut enc_ctx = avcodec_alloc_context3(encoder);
+            (*enc_ctx).width = width;
+            (*enc_ctx).height = height;
+            (*enc_ctx).time_base = AVRational { num: 1, den: 1000 };
+            assert!(avcodec_open2(enc_ctx, encoder, null_mut()) >= 0);
+
+            // color 0 is transparent, color 1 opaque white
+            let (x, y, w, h) = BOX;
+            let mut bitmap = vec![1u8; w * h];
+            let mut palette = vec![0u32; 256];
+            palette[1] = 0xffffffff;
+            let mut rect: AVSubtitleRect = std::mem::zeroed();
+            (rect.x, rect.y, rect.w, rect.h) = (x as i32, y as i32, w as i32, h as i32);
+            rect.nb_colors = 2;
+            rect.data[0] = bitmap.as_mut_ptr();
+            rect.linesize[0] = w as i32;
+            rect.data[1] = palette.as_mut_ptr() as *mut u8;
+            rect.type_ = SUBTITLE_BITMAP;
+            let mut rects = [&mut rect as *mut AVSubtitleRect];
+            let mut subtitle: AVSubtitle = std::mem::zeroed();
+            subtitle.end_display_time = 5000;
+            subtitle.num_rects = 1;
+            subtitle.rects = rects.as_mut_ptr();
+
+            let mut data = vec![0u8; 64 * 1024];
+            let size = avcodec_encode_subtitle(enc_ctx, data.as_mut_ptr(), data.len() as i32, &subtitle);
+            assert!(size > 0);
+
+            let path = CString::new(path).unwrap();
+            let format = CString::new("matroska").unwrap();
+            let mut fmt_ctx = null_mut();
+            assert!(avformat_alloc_output_context2(&mut fmt_ctx, null(), format.as_ptr(), path.as_ptr()) >= 0);
+            let stream = avformat_new_stream(fmt_ctx, null());
+            assert!(avcodec_parameters_from_context((*stream).codecpar, enc_ctx) >= 0);
+            (*stream).time_base = (*enc_ctx).time_base;
+            assert!(avio_open(&mut (*fmt_ctx).pb, path.as_ptr(), AVIO_FLAG_WRITE) >= 0);
+            assert!(avformat_write_header(fmt_ctx, null_mut()) >= 0);
+
+            let mut packet = av_packet_alloc();
+            assert!(av_new_packet(packet, size) >= 0);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), (*packet).data, size as usize);
+            ((*packet).pts, (*packet).dts, (*packet).duration) = (0, 0, 5000);
+            av_packet_rescale_ts(packet, (*enc_ctx).time_base, (*stream).time_base);
+            assert!(av_interleaved_write_frame(fmt_ctx, packet) >= 0);
+            assert!(av_write_trailer(fmt_ctx) >= 0);
+
+            av_packet_free(&mut packet);
+            avio_closep(&mut (*fmt_ctx).pb);
+            avformat_free_context(fmt_ctx);
+            avcodec_free_context(&mut enc_ctx);
+        }
+
+        /// Counts the frames the white box shows on.
+        struct BoxFilter(Arc<Mutex<(usize, usize)>>);
+
+        impl FrameFilter for BoxFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                unsafe {
+                    let f = frame.as_ptr();
+                    if (*f).buf[0].is_null() || (*f).format != AVPixelFormat::AV_PIX_FMT_YUV420P as i32 {
+                        return Ok(Some(frame));
+                    }
+                    let (x, y, w, h) = BOX;
+                    let white = (y..y + h).all(|row| {
+                        let line = (*f).data[0].offset(row as isize * (*f).linesize[0] as isize);
+                        std::slice::from_raw_parts(line.add(x), w).iter().all(|&luma| luma >= 220)
+                    });
+                    let mut counts = self.0.lock().unwrap();
+                    counts.0 += 1;
+                    counts.1 += white as usize;
+                }
+                Ok(Some(frame))
+            }
+        }
+
+        let Some(StreamInfo::Video { width, height, .. }) = find_video_stream_info("test.mp4").unwrap() else {
+            panic!("test.mp4 has no video stream");
+        };
+        unsafe { write_dvd_subtitle("dvd_subtitle.mkv", width, height) };
+
+        let counts = Arc::new(Mutex::new((0, 0)));
+        let output = Output::from("-")
+            .set_format("null")
+            .set_recording_time_us(1_000_000)
+            .add_frame_pipeline(
+                FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                    .filter("box", Box::new(BoxFilter(counts.clone()))),
+            );
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .input("dvd_subtitle.mkv")
+            .filter_desc("[0:v][1:s]overlay,format=yuv420p")
+            .output(output)
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        std::fs::remove_file("dvd_subtitle.mkv").unwrap();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        // the subtitle is decoded and drawn over the video
+        let (frames, white) = *counts.lock().unwrap();
+        assert!(frames > 0);
+        assert!(white > 0, "the subtitle shows on none of the {frames} frames");
+    }
+
+    #[test]
+    fn test_normalize_anamorphic_sar() {
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use ffmpeg_next::Frame;
+
+        /// Records the size and sample aspect ratio of the frames it sees.
+        struct SarFilter(Arc<Mutex<Option<(i32, i32, AVRational)>>>);
+
+        impl FrameFilter for SarFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                unsafe {
+                    let f = frame.as_ptr();
+                    if !(*f).buf[0].is_null() {
+                        *self.0.lock().unwrap() = Some(((*f).width, (*f).height, (*f).sample_aspect_ratio));
+                    }
+                }
+                Ok(Some(frame))
+            }
+        }
+
+        // a 16:9 anamorphic PAL DVD: 720x576 with a 64:45 SAR
+        let result = FfmpegContext::builder()
+            .input("test.mp4")
+            .filter_desc("scale=720:576,setsar=64/45")
+            .output(Output::from("anamorphic.mkv").set_recording_time_us(500_000))
+            .build()
+            .unwrap()
+            .start()
+            .unwrap()
+            .wait();
+        assert!(result.is_ok(), "{:?}", result.err());
+
+        let frame_of = |normalize_sar: bool| {
+            let seen = Arc::new(Mutex::new(None));
+            let output = Output::from("-").set_format("null").add_frame_pipeline(
+                FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                    .filter("sar", Box::new(SarFilter(seen.clone()))),
+            );
+            FfmpegContext::builder()
+                .input(Input::from("anamorphic.mkv").set_normalize_sar(normalize_sar))
+                .output(output)
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait()
+                .unwrap();
+            let seen = seen.lock().unwrap().take().unwrap();
+            seen
+        };
+
+        let (width, height, sar) = frame_of(false);
+        assert_eq!((width, height, sar.num, sar.den), (720, 576, 64, 45));
+        // resized to the display width, with square pixels
+        let (width, height, sar) = frame_of(true);
+        std::fs::remove_file("anamorphic.mkv").unwrap();
+        assert_eq!((width, height, sar.num, sar.den), (1024, 576, 1, 1));
+    }
+
+    #[test]
+    fn test_keyframes_only() {
+        use crate::core::context::output::VSyncMethod;
+        use crate::core::filter::frame_filter::FrameFilter;
+        use crate::core::filter::frame_filter_context::FrameFilterContext;
+        use ffmpeg_next::Frame;
+        use ffmpeg_sys_next::AV_FRAME_FLAG_KEY;
+
+        /// Counts the decoded frames and the keyframes among them.
+        struct KeyFilter(Arc<Mutex<(usize, usize)>>);
+
+        impl FrameFilter for KeyFilter {
+            fn media_type(&self) -> AVMediaType {
+                AVMediaType::AVMEDIA_TYPE_VIDEO
+            }
+
+            fn filter_frame(&mut self, frame: Frame, _ctx: &FrameFilterContext) -> Result<Option<Frame>, String> {
+                unsafe {
+                    if !(*frame.as_ptr()).buf[0].is_null() {
+                        let mut counts = self.0.lock().unwrap();
+                        counts.0 += 1;
+                        counts.1 += ((*frame.as_ptr()).flags & AV_FRAME_FLAG_KEY as i32 != 0) as usize;
+                    }
+                }
+                Ok(Some(frame))
+            }
+        }
+
+        let count_frames = |keyframes_only: bool| {
+            let counts = Arc::new(Mutex::new((0, 0)));
+            let output = Output::from("-")
+                .set_format("null")
+                .set_vsync_method(VSyncMethod::VsyncVfr)
+                .add_frame_pipeline(
+                    FramePipelineBuilder::new(AVMediaType::AVMEDIA_TYPE_VIDEO)
+                        .filter("key", Box::new(KeyFilter(counts.clone()))),
+                );
+            FfmpegContext::builder()
+                .input(Input::from("test.mp4").set_keyframes_only(keyframes_only))
+                .output(output)
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait()
+                .unwrap();
+            let counts = *counts.lock().unwrap();
+            counts
+        };
+
+        let (all_frames, _) = count_frames(false);
+        let (frames, keyframes) = count_frames(true);
+        // only the keyframes are decoded, one output frame each
+        assert!(frames > 0);
+        assert_eq!(frames, keyframes);
+        assert!(frames < all_frames, "{frames} of {all_frames} frames decoded");
+    }
+
     #[test]
     fn test_is_ended() {
         let _ = env_logger::builder()
@@ -948,6 +2283,40 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_reset() {
+        let _ = env_logger::builder()
+            .filter_level(log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+
+        let mut scheduler: Option<FfmpegScheduler<Ended>> = None;
+        for i in 0..3 {
+            let context = FfmpegContext::builder()
+                .input("test.mp4")
+                .filter_desc("scale='min(160,iw)':-1")
+                .output(Output::from(format!("output_reset_{i}.jpg")).set_max_video_frames(1))
+                .build()
+                .unwrap();
+            let next = match scheduler.take() {
+                Some(scheduler) => scheduler.reset(context),
+                None => FfmpegScheduler::new(context),
+            };
+            assert_eq!(next.status.load(Ordering::Acquire), STATUS_INIT);
+            assert!(next.result.lock().unwrap().is_none());
+
+            let (result, ended) = next.start().unwrap().finish();
+            assert!(result.is_ok());
+            assert!(ended.is_ended());
+            assert!(ended.frame_pool.is_some() && ended.packet_pool.is_some());
+            assert!(std::fs::metadata(format!("output_reset_{i}.jpg")).unwrap().len() > 0);
+            scheduler = Some(ended);
+        }
+        for i in 0..3 {
+            let _ = std::fs::remove_file(format!("output_reset_{i}.jpg"));
+        }
+    }
+
     #[test]
     fn test_status() {
         let _ = env_logger::builder()
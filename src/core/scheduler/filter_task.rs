@@ -1,13 +1,14 @@
 use crate::core::context::filter_graph::FilterGraph;
-use crate::core::context::input_filter::{InputFilterOptions, IFILTER_FLAG_AUTOROTATE};
+use crate::core::context::input_filter::{InputFilterOptions, IFILTER_FLAG_AUTOROTATE, IFILTER_FLAG_NORMALIZE_SAR};
 use crate::core::context::obj_pool::ObjPool;
-use crate::core::context::output::VSyncMethod;
+use crate::core::context::output::{EvenDimensionsPolicy, VSyncMethod};
 use crate::core::context::output::VSyncMethod::{VsyncCfr, VsyncVscfr};
 use crate::core::context::output_filter::OutputFilterOptions;
 use crate::core::context::{null_frame, FrameBox, FrameData};
 use crate::core::scheduler::ffmpeg_scheduler::{
     frame_is_null, set_scheduler_error, wait_until_not_paused, STATUS_END,
 };
+use crate::core::scheduler::dec_task::FrameOpaque;
 use crate::core::scheduler::input_controller::{InputController, SchNode};
 use crate::error::{Error, FilterGraphError, FilterGraphOperationError, FilterGraphParseError};
 use crate::hwaccel::{hw_device_for_filter, init_filter_hw_device, HWDevice};
@@ -23,6 +24,7 @@ use ffmpeg_sys_next::AVOptionType::AV_OPT_TYPE_BINARY;
 use ffmpeg_sys_next::AVPixelFormat::AV_PIX_FMT_NONE;
 use ffmpeg_sys_next::AVRounding::{AV_ROUND_NEAR_INF, AV_ROUND_PASS_MINMAX};
 use ffmpeg_sys_next::AVSampleFormat::AV_SAMPLE_FMT_NONE;
+use ffmpeg_sys_next::AVSubtitleType::SUBTITLE_BITMAP;
 use ffmpeg_sys_next::{
     av_bprint_chars, av_bprint_finalize, av_bprint_init, av_bprintf, av_buffer_ref,
     av_buffer_unref, av_buffersink_get_frame_flags, av_buffersink_get_frame_rate,
@@ -31,16 +33,17 @@ use ffmpeg_sys_next::{
     av_buffersrc_add_frame_flags, av_buffersrc_close, av_buffersrc_get_nb_failed_requests,
     av_buffersrc_parameters_alloc, av_buffersrc_parameters_set, av_color_range_name,
     av_color_space_name, av_dict_free, av_frame_alloc, av_frame_free, av_frame_get_side_data,
-    av_frame_ref, av_frame_remove_side_data, av_freep, av_get_pix_fmt_name, av_get_sample_fmt_name,
+    av_frame_get_buffer, av_frame_ref, av_frame_remove_side_data, av_frame_unref, av_freep, av_get_pix_fmt_name, av_get_sample_fmt_name,
     av_inv_q, av_log2, av_malloc, av_opt_find, av_opt_set, av_opt_set_bin, av_opt_set_int,
     av_pix_fmt_desc_get, av_q2d, av_rescale_q, av_rescale_q_rnd, avfilter_get_by_name,
     avfilter_graph_alloc, avfilter_graph_config, avfilter_graph_create_filter, avfilter_graph_free,
     avfilter_graph_request_oldest, avfilter_inout_free, avfilter_link, avfilter_pad_get_type,
     avio_close, avio_closep, avio_open, avio_open2, avio_read, avio_read_to_bprint, avio_size,
     AVBPrint, AVBufferRef, AVColorRange, AVColorSpace, AVFilterContext, AVFilterGraph,
-    AVFilterInOut, AVFrame, AVMediaType, AVPixelFormat, AVRational, AVSampleFormat, AVERROR,
+    AVFilterInOut, AVFrame, AVMediaType, AVPixelFormat, AVRational, AVSampleFormat, AVSubtitle,
+    AVSubtitleRect, AVERROR,
     AVERROR_BUG, AVERROR_EOF, AVERROR_OPTION_NOT_FOUND, AVIO_FLAG_READ, AV_BPRINT_SIZE_AUTOMATIC,
-    AV_BUFFERSINK_FLAG_NO_REQUEST, AV_BUFFERSRC_FLAG_PUSH, AV_NOPTS_VALUE, AV_OPT_SEARCH_CHILDREN,
+    AV_BUFFERSINK_FLAG_NO_REQUEST, AV_BUFFERSRC_FLAG_KEEP_REF, AV_BUFFERSRC_FLAG_PUSH, AV_NOPTS_VALUE, AV_OPT_SEARCH_CHILDREN,
     AV_PIX_FMT_FLAG_HWACCEL, AV_TIME_BASE_Q, EAGAIN, EIO, ENOMEM,
 };
 #[cfg(not(feature = "docs-rs"))]
@@ -98,6 +101,7 @@ pub(crate) fn filter_graph_init(
             i,
             filter_graph.inputs[i].name.clone(),
             filter_graph.inputs[i].media_type,
+            filter_graph.inputs[i].type_src,
             opts,
         );
         ifps.push(input_filter_parameter);
@@ -157,6 +161,18 @@ pub(crate) fn filter_graph_init(
                 let frame_box = result.unwrap();
                 let input_index = frame_box.frame_data.fg_input_index;
 
+                if ifps.get(input_index).is_some_and(|ifp| ifp.type_src == AVMEDIA_TYPE_VIDEO)
+                    && frame_box.frame_data.input_stream_width > 0
+                    && frame_box.frame_data.input_stream_height > 0
+                    && !fgp.source_size.is_some_and(|(index, _, _)| index < input_index)
+                {
+                    fgp.source_size = Some((
+                        input_index,
+                        frame_box.frame_data.input_stream_width,
+                        frame_box.frame_data.input_stream_height,
+                    ));
+                }
+
                 if input_index < finished_flag_list.len() {
                     if finished_flag_list[input_index].load(Ordering::Acquire) {
                        continue;
@@ -166,9 +182,13 @@ pub(crate) fn filter_graph_init(
                 }
 
                 unsafe {
-                    if ifps[input_index].media_type == AVMEDIA_TYPE_SUBTITLE {
-                        //TODO
-                        // sub2video_frame
+                    if ifps[input_index].type_src == AVMEDIA_TYPE_SUBTITLE
+                        && !frame_is_null(&frame_box.frame)
+                        && (!(*frame_box.frame.as_ptr()).buf[0].is_null()
+                            || (*frame_box.frame.as_ptr()).opaque as usize as i32
+                                == FrameOpaque::FrameOpaqueSubHeartbeat as i32)
+                    {
+                        sub2video_frame(&mut ifps[input_index], frame_box, graph.is_null());
                     } else if !frame_is_null(&frame_box.frame)
                         && !(*frame_box.frame.as_ptr()).buf[0].is_null()
                     {
@@ -196,6 +216,9 @@ pub(crate) fn filter_graph_init(
                             }
                         }
                     } else {
+                        if ifps[input_index].type_src == AVMEDIA_TYPE_SUBTITLE && !graph.is_null() {
+                            sub2video_flush(&mut ifps[input_index]);
+                        }
                         if let Err(e) = fg_send_eof(
                             fg_index,
                             &mut graph,
@@ -295,6 +318,8 @@ struct InputFilterParameter {
     input_filter_index: usize,
     name: String,
     media_type: AVMediaType,
+    // type of the bound input stream, AVMEDIA_TYPE_SUBTITLE for sub2video
+    type_src: AVMediaType,
     opts: InputFilterOptions,
     hw_frames_ctx: *mut AVBufferRef,
     time_base: AVRational,
@@ -314,20 +339,32 @@ struct InputFilterParameter {
 
     frame_queue: VecDeque<FrameBox>,
 
+    sub2video: Sub2Video,
+
     eof: bool,
 }
 
+struct Sub2Video {
+    last_pts: i64,
+    end_pts: i64,
+    // the canvas currently displayed, pushed again on every heartbeat
+    frame: Frame,
+    initialize: bool,
+}
+
 impl InputFilterParameter {
     fn new(
         input_filter_index: usize,
         name: String,
         media_type: AVMediaType,
+        type_src: AVMediaType,
         opts: InputFilterOptions,
     ) -> Self {
-        Self {
+        let mut ifp = Self {
             input_filter_index,
             name,
             media_type,
+            type_src,
             opts,
             hw_frames_ctx: null_mut(),
             time_base: AVRational { num: 0, den: 1 },
@@ -350,11 +387,34 @@ impl InputFilterParameter {
             displaymatrix: [0; 9],
             filter: null_mut(),
             frame_queue: Default::default(),
+            sub2video: Sub2Video {
+                last_pts: i64::MIN,
+                end_pts: i64::MIN,
+                frame: unsafe { Frame::empty() },
+                initialize: false,
+            },
             eof: false,
+        };
+
+        if type_src == AVMEDIA_TYPE_SUBTITLE {
+            ifp.width = ifp.opts.sub2video_width;
+            ifp.height = ifp.opts.sub2video_height;
+            // rectangles are AV_PIX_FMT_PAL8, but we have no guarantee that the
+            // palettes for all rectangles are identical or compatible
+            ifp.format = SUB2VIDEO_PIX_FMT as i32;
+            ifp.time_base = AV_TIME_BASE_Q;
+            debug!("sub2video: using {}x{} canvas", ifp.width, ifp.height);
         }
+        ifp
     }
 }
 
+// AV_PIX_FMT_RGB32: native endian 0xAARRGGBB, the layout of the subtitle palettes
+#[cfg(target_endian = "little")]
+const SUB2VIDEO_PIX_FMT: AVPixelFormat = AVPixelFormat::AV_PIX_FMT_BGRA;
+#[cfg(target_endian = "big")]
+const SUB2VIDEO_PIX_FMT: AVPixelFormat = AVPixelFormat::AV_PIX_FMT_ARGB;
+
 unsafe impl Send for InputFilterParameter {}
 unsafe impl Sync for InputFilterParameter {}
 
@@ -365,6 +425,15 @@ struct FilterGraphParameter {
     got_frame: bool,
     nb_outputs_done: usize,
     is_meta: bool,
+    // size of the decoded stream of the first video input fed by a decoder, as
+    // (input index, width, height), passed on with the filtered frames
+    source_size: Option<(usize, i32, i32)>,
+}
+
+impl FilterGraphParameter {
+    fn source_size(&self) -> (i32, i32) {
+        self.source_size.map_or((0, 0), |(_, width, height)| (width, height))
+    }
 }
 
 struct OutputFilterParameter {
@@ -376,6 +445,8 @@ struct OutputFilterParameter {
     finished_flag_list: Arc<[AtomicBool]>,
 
     filter: *mut AVFilterContext,
+    // the pad or crop filter fitting the size to the chroma subsampling, if inserted
+    even_dimensions_filter: *mut AVFilterContext,
     name: String,
     fpsconv_context: FPSConvContext,
     eof: bool,
@@ -409,6 +480,7 @@ impl OutputFilterParameter {
             fg_input_index,
             finished_flag_list,
             filter: null_mut(),
+            even_dimensions_filter: null_mut(),
             name: "".to_string(),
             opts,
             fpsconv_context,
@@ -722,6 +794,165 @@ unsafe fn fg_send_frame(
     Ok(())
 }
 
+fn sub2video_prepare(ifp: &mut InputFilterParameter) {
+    ifp.sub2video.last_pts = i64::MIN;
+    ifp.sub2video.end_pts = i64::MIN;
+    /* sub2video structure has been (re-)initialized.
+       Mark it as such so that the system will be
+       initialized with the first received heartbeat. */
+    ifp.sub2video.initialize = true;
+}
+
+/// Handles a decoded subtitle (or a heartbeat) for a sub2video input.
+/// Subtitles received before the graph is configured are queued when `buffer` is set.
+unsafe fn sub2video_frame(ifp: &mut InputFilterParameter, frame_box: FrameBox, buffer: bool) {
+    let frame = frame_box.frame.as_ptr();
+
+    // heartbeat frame
+    if (*frame).buf[0].is_null() {
+        if !ifp.filter.is_null() {
+            sub2video_heartbeat(ifp, (*frame).pts, (*frame).time_base);
+        }
+        return;
+    }
+
+    if buffer {
+        ifp.frame_queue.push_back(frame_box);
+        return;
+    }
+
+    let sub = (*(*frame).buf[0]).data as *const AVSubtitle;
+    sub2video_update(ifp, i64::MIN, sub);
+}
+
+/// Clears the displayed subtitle at the end of the stream.
+unsafe fn sub2video_flush(ifp: &mut InputFilterParameter) {
+    if !ifp.filter.is_null() && ifp.sub2video.end_pts < i64::MAX {
+        sub2video_update(ifp, i64::MAX, null());
+    }
+}
+
+unsafe fn sub2video_heartbeat(ifp: &mut InputFilterParameter, pts: i64, tb: AVRational) {
+    /* subtitles seem to be usually muxed ahead of other streams;
+       if not, subtracting a larger time here is necessary */
+    let pts2 = av_rescale_q(pts, tb, ifp.time_base) - 1;
+
+    /* do not send the heartbeat frame if the subtitle is already ahead */
+    if pts2 <= ifp.sub2video.last_pts {
+        return;
+    }
+
+    if pts2 >= ifp.sub2video.end_pts || ifp.sub2video.initialize {
+        /* if we have hit the end of the current displayed subpicture,
+           or if we need to initialize the system, update the
+           overlayed subpicture and its start/end times */
+        sub2video_update(ifp, pts2 + 1, null());
+    } else {
+        // keep showing the current subpicture on the new video frames
+        sub2video_push_ref(ifp, pts2);
+    }
+}
+
+unsafe fn sub2video_update(ifp: &mut InputFilterParameter, heartbeat_pts: i64, sub: *const AVSubtitle) {
+    let (pts, end_pts, num_rects) = if !sub.is_null() {
+        (
+            av_rescale_q(
+                (*sub).pts + (*sub).start_display_time as i64 * 1000,
+                AV_TIME_BASE_Q,
+                ifp.time_base,
+            ),
+            av_rescale_q(
+                (*sub).pts + (*sub).end_display_time as i64 * 1000,
+                AV_TIME_BASE_Q,
+                ifp.time_base,
+            ),
+            (*sub).num_rects as usize,
+        )
+    } else {
+        /* If we are initializing the system, utilize current heartbeat
+           PTS as the start time, and show until the following subpicture
+           is received. Otherwise, utilize the previous subpicture's end time
+           as the fall-back value. */
+        let pts = if ifp.sub2video.initialize {
+            heartbeat_pts
+        } else {
+            ifp.sub2video.end_pts
+        };
+        (pts, i64::MAX, 0)
+    };
+
+    if let Err(e) = sub2video_get_blank_frame(ifp) {
+        error!("Impossible to get a blank canvas: {}", av_err2str(e));
+        return;
+    }
+
+    let frame = ifp.sub2video.frame.as_mut_ptr();
+    for i in 0..num_rects {
+        sub2video_copy_rect(frame, *(*sub).rects.add(i));
+    }
+    sub2video_push_ref(ifp, pts);
+    ifp.sub2video.end_pts = end_pts;
+    ifp.sub2video.initialize = false;
+}
+
+unsafe fn sub2video_get_blank_frame(ifp: &mut InputFilterParameter) -> Result<(), i32> {
+    let frame = ifp.sub2video.frame.as_mut_ptr();
+    av_frame_unref(frame);
+    (*frame).width = ifp.width;
+    (*frame).height = ifp.height;
+    (*frame).format = ifp.format;
+    (*frame).colorspace = ifp.color_space;
+    (*frame).color_range = ifp.color_range;
+
+    let ret = av_frame_get_buffer(frame, 0);
+    if ret < 0 {
+        return Err(ret);
+    }
+    std::ptr::write_bytes((*frame).data[0], 0, ((*frame).height * (*frame).linesize[0]) as usize);
+    Ok(())
+}
+
+unsafe fn sub2video_copy_rect(frame: *mut AVFrame, r: *const AVSubtitleRect) {
+    if (*r).type_ != SUBTITLE_BITMAP {
+        warn!("sub2video: non-bitmap subtitle");
+        return;
+    }
+    if (*r).x < 0 || (*r).x + (*r).w > (*frame).width || (*r).y < 0 || (*r).y + (*r).h > (*frame).height {
+        warn!(
+            "sub2video: rectangle ({} {} {} {}) overflowing {} {}",
+            (*r).x, (*r).y, (*r).w, (*r).h, (*frame).width, (*frame).height
+        );
+        return;
+    }
+
+    let dst_linesize = (*frame).linesize[0] as usize;
+    let mut dst = (*frame).data[0].add((*r).y as usize * dst_linesize + (*r).x as usize * 4);
+    let mut src = (*r).data[0] as *const u8;
+    let pal = (*r).data[1] as *const u32;
+    for _ in 0..(*r).h {
+        let dst2 = dst as *mut u32;
+        for x in 0..(*r).w as usize {
+            dst2.add(x).write_unaligned(*pal.add(*src.add(x) as usize));
+        }
+        dst = dst.add(dst_linesize);
+        src = src.add((*r).linesize[0] as usize);
+    }
+}
+
+unsafe fn sub2video_push_ref(ifp: &mut InputFilterParameter, pts: i64) {
+    let frame = ifp.sub2video.frame.as_mut_ptr();
+    (*frame).pts = pts;
+    ifp.sub2video.last_pts = pts;
+    let ret = av_buffersrc_add_frame_flags(
+        ifp.filter,
+        frame,
+        (AV_BUFFERSRC_FLAG_KEEP_REF | AV_BUFFERSRC_FLAG_PUSH) as i32,
+    );
+    if ret != AVERROR_EOF && ret < 0 {
+        warn!("Error while add the frame to buffer source({}).", av_err2str(ret));
+    }
+}
+
 #[cfg(feature = "docs-rs")]
 unsafe fn configure_filtergraph(
     fg_index: usize,
@@ -841,6 +1072,23 @@ unsafe fn configure_filtergraph(
 
         ofp.sample_rate = av_buffersink_get_sample_rate(ofp.filter);
 
+        if ofp.media_type == AVMEDIA_TYPE_VIDEO {
+            report_even_dimensions(ofp);
+            if ofp.opts.even_dimensions_policy == EvenDimensionsPolicy::Error
+                && !fits_chroma_subsampling(ofp.format, ofp.width, ofp.height)
+            {
+                error!(
+                    "The {}x{} video of output stream {} has odd dimensions",
+                    ofp.width, ofp.height, ofp.opts.name
+                );
+                let (width, height, name) = (ofp.width, ofp.height, ofp.opts.name.clone());
+                cleanup_filtergraph(graph, ifps, ofps);
+                return Err(Error::FilterGraph(FilterGraphOperationError::OddDimensions(
+                    width, height, name,
+                )));
+            }
+        }
+
         av_channel_layout_uninit(&mut ofp.opts.ch_layout);
         ret = av_buffersink_get_ch_layout(ofp.filter, &mut ofp.opts.ch_layout);
         if ret < 0 {
@@ -858,9 +1106,8 @@ unsafe fn configure_filtergraph(
                 break;
             }
             let tmp_frame_box = option.unwrap();
-            if ifp.media_type == AVMEDIA_TYPE_SUBTITLE {
-                //TODO
-                // sub2video_frame(ifp, tmp_frame_box);
+            if ifp.type_src == AVMEDIA_TYPE_SUBTITLE {
+                sub2video_frame(ifp, tmp_frame_box, false);
             } else {
                 let mut tmp_frame = unsafe { av_frame_alloc() };
                 if tmp_frame.is_null() {
@@ -1079,7 +1326,14 @@ unsafe fn configure_output_audio_filter(
         pad_idx = 0;
     }
 
-    //TODO auto apad
+    if ofp.opts.pad {
+        // endless silence, cut by the trim below
+        ret = insert_filter(&mut last_filter, &mut pad_idx, "apad", None);
+        if ret < 0 {
+            av_bprint_finalize(&mut bprint, null_mut());
+            return ret;
+        }
+    }
 
     let name = format!("trim_out_{}", ofp.name);
     ret = insert_trim(
@@ -1384,6 +1638,27 @@ unsafe fn choose_out_timebase(ofp: &mut OutputFilterParameter, frame: &Frame) {
             {
                 fr = ofp.fpsconv_context.framerate_max;
             }
+
+            // only on the first frame, before the output time base is set
+            let fr_sink = av_buffersink_get_frame_rate(ofp.filter);
+            if ofp.tb_out.num == 0
+                && ofp.opts.framerate.num > 0
+                && fr_sink.num > 0
+                && fr_sink.den > 0
+                && !is_integer_ratio(fr_sink, fr)
+            {
+                warn!(
+                    "Output {}: the source frame rate {}/{} ({:.3} fps) is not a multiple nor a divisor \
+                    of the requested {}/{} ({:.3} fps), frames will be unevenly duplicated or dropped.",
+                    ofp.opts.name,
+                    fr_sink.num,
+                    fr_sink.den,
+                    av_q2d(fr_sink),
+                    fr.num,
+                    fr.den,
+                    av_q2d(fr)
+                );
+            }
         }
     }
 
@@ -1399,6 +1674,14 @@ unsafe fn choose_out_timebase(ofp: &mut OutputFilterParameter, frame: &Frame) {
     ofp.tb_out = tb;
 }
 
+/// Whether one of the frame rates `a` and `b` is an integer multiple of the other, i.e.
+/// converting between them duplicates or drops frames at a regular pace.
+fn is_integer_ratio(a: AVRational, b: AVRational) -> bool {
+    let num = a.num as i64 * b.den as i64;
+    let den = a.den as i64 * b.num as i64;
+    num > 0 && den > 0 && (num % den == 0 || den % num == 0)
+}
+
 #[cfg(feature = "docs-rs")]
 unsafe fn fg_output_frame(
     fgp: &mut FilterGraphParameter,
@@ -1429,6 +1712,17 @@ unsafe fn fg_output_frame(
 
     if ofp.media_type == AVMEDIA_TYPE_VIDEO && (!frame_is_null(&frame) || fgp.got_frame) {
         unsafe { video_sync_process(ofp, frame.as_mut_ptr(), &mut nb_frames, &mut nb_frames_prev) };
+        if !frame_is_null(&frame) {
+            if nb_frames == 0 {
+                if let Some(dropped_frames) = &ofp.opts.dropped_frames {
+                    dropped_frames.fetch_add(1, Ordering::Relaxed);
+                }
+            } else if nb_frames > 1 {
+                if let Some(duplicated_frames) = &ofp.opts.duplicated_frames {
+                    duplicated_frames.fetch_add(nb_frames as u64 - 1, Ordering::Relaxed);
+                }
+            }
+        }
     }
 
     let frame_prev = &ofp.fpsconv_context.last_frame;
@@ -1501,13 +1795,16 @@ unsafe fn fg_output_frame(
                 Some(ofp.opts.framerate)
             };
 
+            let sample_aspect_ratio = (*frame_out.as_ptr()).sample_aspect_ratio;
+            let (input_stream_width, input_stream_height) = fgp.source_size();
             let frame_box = FrameBox {
                 frame: frame_out,
                 frame_data: FrameData {
                     framerate,
                     bits_per_raw_sample: 0,
-                    input_stream_width: 0,
-                    input_stream_height: 0,
+                    input_stream_width,
+                    input_stream_height,
+                    sample_aspect_ratio,
                     subtitle_header_size: 0,
                     subtitle_header: null_mut(),
                     fg_input_index: ofp.fg_input_index,
@@ -1601,13 +1898,15 @@ unsafe fn close_output(
                 Some(ofp.opts.framerate)
             };
 
+            let (input_stream_width, input_stream_height) = fgp.source_size();
             let frame_box = FrameBox {
                 frame,
                 frame_data: FrameData {
                     framerate,
                     bits_per_raw_sample: 0,
-                    input_stream_width: 0,
-                    input_stream_height: 0,
+                    input_stream_width,
+                    input_stream_height,
+                    sample_aspect_ratio: ofp.sample_aspect_ratio,
                     subtitle_header_size: 0,
                     subtitle_header: null_mut(),
                     fg_input_index: ofp.fg_input_index,
@@ -1630,6 +1929,7 @@ unsafe fn close_output(
                 bits_per_raw_sample: 0,
                 input_stream_width: 0,
                 input_stream_height: 0,
+                sample_aspect_ratio: AVRational { num: 0, den: 1 },
                 subtitle_header_size: 0,
                 subtitle_header: null_mut(),
                 fg_input_index: ofp.fg_input_index,
@@ -2121,7 +2421,7 @@ unsafe fn configure_output_video_filter(
     av_bprint_init(&mut bprint, 0, u32::MAX);
 
     //TODO To support specifying the following parameters
-    choose_pix_fmts(&mut bprint, AV_PIX_FMT_NONE, ofp.opts.formats.clone());
+    choose_pix_fmts(&mut bprint, ofp.opts.format, ofp.opts.formats.clone());
     choose_color_spaces(
         &mut bprint,
         AVCOL_SPC_UNSPECIFIED,
@@ -2167,6 +2467,32 @@ unsafe fn configure_output_video_filter(
         pad_idx = 0;
     }
 
+    ofp.even_dimensions_filter = null_mut();
+    if needs_even_dimensions(&ofp.opts) {
+        let filter = match ofp.opts.even_dimensions_policy {
+            EvenDimensionsPolicy::Pad => Some(("pad", "w=ceil(iw/hsub)*hsub:h=ceil(ih/vsub)*vsub")),
+            EvenDimensionsPolicy::Crop => Some(("crop", "w=trunc(iw/hsub)*hsub:h=trunc(ih/vsub)*vsub")),
+            EvenDimensionsPolicy::Error => None,
+        };
+        if let Some((filter_name, args)) = filter {
+            ret = insert_filter(&mut last_filter, &mut pad_idx, filter_name, Some(args));
+            if ret < 0 {
+                av_bprint_finalize(&mut bprint, null_mut());
+                return ret;
+            }
+            ofp.even_dimensions_filter = last_filter;
+        }
+    }
+
+    if ofp.opts.pad {
+        // endless black frames at the stream's frame rate, cut by the trim below
+        ret = insert_filter(&mut last_filter, &mut pad_idx, "tpad", Some("stop=-1:stop_mode=add:color=black"));
+        if ret < 0 {
+            av_bprint_finalize(&mut bprint, null_mut());
+            return ret;
+        }
+    }
+
     let name = format!("trim_out_{}", ofp.name);
     ret = insert_trim(
         ofp.opts.trim_start_us,
@@ -2435,10 +2761,9 @@ unsafe fn configure_input_video_filter(
         return AVERROR(ENOMEM);
     }
 
-    //TODO
-    /*if (ifp.type_src == AVMEDIA_TYPE_SUBTITLE){
-    sub2video_prepare(ifp);
-    }*/
+    if ifp.type_src == AVMEDIA_TYPE_SUBTITLE {
+        sub2video_prepare(ifp);
+    }
 
     let mut sar = ifp.sample_aspect_ratio;
     if sar.den == 0 {
@@ -2509,6 +2834,26 @@ unsafe fn configure_input_video_filter(
         return ret;
     }*/
 
+    // scale anamorphic video to square pixels, before it is rotated
+    if ifp.opts.flags & IFILTER_FLAG_NORMALIZE_SAR != 0
+        && (*desc).flags & AV_PIX_FMT_FLAG_HWACCEL as u64 == 0
+    {
+        if let Some((width, height)) = square_pixel_size(ifp.width, ifp.height, sar) {
+            debug!(
+                "Scaling {}x{} video with SAR {}:{} to {width}x{height} square pixels",
+                ifp.width, ifp.height, sar.num, sar.den
+            );
+            ret = insert_filter(&mut last_filter, &mut pad_idx, "scale", Some(&format!("{width}:{height}")));
+            if ret < 0 {
+                return ret;
+            }
+            ret = insert_filter(&mut last_filter, &mut pad_idx, "setsar", Some("1"));
+            if ret < 0 {
+                return ret;
+            }
+        }
+    }
+
     ifp.displaymatrix_applied = false;
     if ifp.opts.flags & IFILTER_FLAG_AUTOROTATE != 0
         && (*desc).flags & AV_PIX_FMT_FLAG_HWACCEL as u64 == 0
@@ -2556,6 +2901,22 @@ unsafe fn configure_input_video_filter(
         ifp.displaymatrix_applied = true;
     }
 
+    // scale on the GPU while the frames are still in its memory
+    if let Some((width, height, hw_scaler)) = ifp.opts.hw_scale {
+        let scaler = if (*desc).flags & AV_PIX_FMT_FLAG_HWACCEL as u64 != 0 {
+            hw_scaler
+        } else {
+            if hw_scaler != "scale" {
+                debug!("Input {} has frames in system memory, scaling them with scale instead of {hw_scaler}", ifp.name);
+            }
+            "scale"
+        };
+        ret = insert_filter(&mut last_filter, &mut pad_idx, scaler, Some(&format!("{width}:{height}")));
+        if ret < 0 {
+            return ret;
+        }
+    }
+
     let name = format!("trim_in_{}", ifp.name);
     ret = insert_trim(
         ifp.opts.trim_start_us,
@@ -2575,6 +2936,57 @@ unsafe fn configure_input_video_filter(
     0
 }
 
+/// Whether the encoder of the output takes chroma subsampled software frames, whose size
+/// must then be a multiple of the subsampling.
+unsafe fn needs_even_dimensions(opts: &OutputFilterOptions) -> bool {
+    let formats = if opts.format != AV_PIX_FMT_NONE {
+        vec![opts.format]
+    } else {
+        opts.formats.clone().unwrap_or_default()
+    };
+    let mut subsampled = false;
+    for format in formats {
+        let desc = av_pix_fmt_desc_get(format);
+        if desc.is_null() || (*desc).flags & AV_PIX_FMT_FLAG_HWACCEL as u64 != 0 {
+            return false;
+        }
+        subsampled |= (*desc).log2_chroma_w != 0 || (*desc).log2_chroma_h != 0;
+    }
+    subsampled
+}
+
+unsafe fn fits_chroma_subsampling(format: AVPixelFormat, width: i32, height: i32) -> bool {
+    let desc = av_pix_fmt_desc_get(format);
+    if desc.is_null() || (*desc).flags & AV_PIX_FMT_FLAG_HWACCEL as u64 != 0 {
+        return true;
+    }
+    width % (1 << (*desc).log2_chroma_w) == 0 && height % (1 << (*desc).log2_chroma_h) == 0
+}
+
+/// Logs the adjustment made by the pad or crop filter of an output, if the size changed.
+unsafe fn report_even_dimensions(ofp: &OutputFilterParameter) {
+    let filter = ofp.even_dimensions_filter;
+    if filter.is_null() {
+        return;
+    }
+    let (input, output) = (*(*filter).inputs, *(*filter).outputs);
+    if (*input).w == (*output).w && (*input).h == (*output).h {
+        return;
+    }
+    let action = match ofp.opts.even_dimensions_policy {
+        EvenDimensionsPolicy::Crop => "Cropped",
+        _ => "Padded",
+    };
+    info!(
+        "{action} the {}x{} video of output stream {} to {}x{} for the encoder",
+        (*input).w,
+        (*input).h,
+        ofp.opts.name,
+        (*output).w,
+        (*output).h
+    );
+}
+
 fn insert_filter(
     last_filter: &mut *mut AVFilterContext,
     pad_idx: &mut i32,
@@ -2619,6 +3031,18 @@ fn insert_filter(
     0
 }
 
+/// The size of a `width`x`height` frame with the given sample aspect ratio once resized to
+/// square pixels, `None` when the pixels already are square or the ratio is unknown.
+/// Only the width changes, always rounded to an even value, whatever the pixel format.
+fn square_pixel_size(width: i32, height: i32, sar: AVRational) -> Option<(i32, i32)> {
+    if sar.num <= 0 || sar.den <= 0 || sar.num == sar.den || width <= 0 {
+        return None;
+    }
+    let display_width = (width as i64 * sar.num as i64 + sar.den as i64 / 2) / sar.den as i64;
+    let display_width = ((display_width + 1) & !1).max(2);
+    Some((display_width as i32, height))
+}
+
 fn get_rotation(displaymatrix: &[i32; 9]) -> f64 {
     let mut theta = -round(display_rotation_get(displaymatrix));
 
@@ -2636,7 +3060,7 @@ fn get_rotation(displaymatrix: &[i32; 9]) -> f64 {
     theta
 }
 
-fn display_rotation_get(matrix: &[i32; 9]) -> f64 {
+pub(crate) fn display_rotation_get(matrix: &[i32; 9]) -> f64 {
     let mut scale = [0.0; 2];
 
     scale[0] = hypot(conv_fp(matrix[0]), conv_fp(matrix[3]));
@@ -2755,7 +3179,7 @@ pub(crate) unsafe fn graph_opts_apply(seg: *mut AVFilterGraphSegment) -> i32 {
     0
 }
 
-unsafe fn filter_opt_apply(f: *mut AVFilterContext, mut key: *mut c_char, val: *mut c_char) -> i32 {
+pub(crate) unsafe fn filter_opt_apply(f: *mut AVFilterContext, mut key: *mut c_char, val: *mut c_char) -> i32 {
     let mut ret = av_opt_set(f as *mut libc::c_void, key, val, AV_OPT_SEARCH_CHILDREN);
     if ret >= 0 {
         return 0;
@@ -2913,3 +3337,30 @@ unsafe fn read_binary(path: *mut c_char) -> crate::error::Result<(*mut c_void, i
 
     Ok((data, fsize))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_pixel_size() {
+        // 16:9 anamorphic PAL DVD
+        assert_eq!(square_pixel_size(720, 576, AVRational { num: 64, den: 45 }), Some((1024, 576)));
+        // 4:3 NTSC DVD
+        assert_eq!(square_pixel_size(720, 480, AVRational { num: 8, den: 9 }), Some((640, 480)));
+        // 16:9 HDV, rounded to an even width
+        assert_eq!(square_pixel_size(1440, 1080, AVRational { num: 4, den: 3 }), Some((1920, 1080)));
+        assert_eq!(square_pixel_size(1920, 1080, AVRational { num: 1, den: 1 }), None);
+        assert_eq!(square_pixel_size(720, 576, AVRational { num: 0, den: 1 }), None);
+    }
+
+    #[test]
+    fn test_is_integer_ratio() {
+        let fps = |num, den| AVRational { num, den };
+        assert!(is_integer_ratio(fps(60, 1), fps(30, 1)));
+        assert!(is_integer_ratio(fps(15, 1), fps(30, 1)));
+        assert!(is_integer_ratio(fps(60000, 1001), fps(30000, 1001)));
+        assert!(!is_integer_ratio(fps(30000, 1001), fps(30, 1)));
+        assert!(!is_integer_ratio(fps(24, 1), fps(30, 1)));
+    }
+}
@@ -10,7 +10,7 @@ use crate::error::Error::{
 use crate::filter::frame_pipeline::FramePipeline;
 use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use ffmpeg_next::Frame;
-use ffmpeg_sys_next::{av_frame_copy_props, av_frame_ref};
+use ffmpeg_sys_next::{av_frame_copy_props, av_frame_ref, AVRational};
 use log::{debug, error, info, warn};
 use std::ptr::null_mut;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
@@ -20,7 +20,7 @@ use std::time::Duration;
 
 pub(crate) fn input_pipeline_init(
     demux_idx: usize,
-    pipeline: FramePipeline,
+    mut pipeline: FramePipeline,
     decoder_streams: &mut Vec<DecoderStream>,
     frame_pool: ObjPool<Frame>,
     scheduler_status: Arc<AtomicUsize>,
@@ -35,6 +35,11 @@ pub(crate) fn input_pipeline_init(
     let (stream_index, encoder_frame_receiver, pipeline_frame_senders) =
         match_decoder_stream(&pipeline, decoder_streams)?;
 
+    if let Some(decoder_stream) = decoder_streams.iter().find(|s| s.stream_index == stream_index) {
+        pipeline.update_sample_aspect_ratio(unsafe { (*decoder_stream.codec_parameters).sample_aspect_ratio });
+        pipeline.update_frame_rate(Some(decoder_stream.avg_framerate));
+    }
+
     pipeline_init(
         true,
         demux_idx,
@@ -286,6 +291,22 @@ fn run_pipeline(
                     }
                 }
                 Ok(frame_box) => {
+                    pipeline.update_sample_aspect_ratio(frame_box.frame_data.sample_aspect_ratio);
+                    pipeline.update_frame_rate(frame_box.frame_data.framerate);
+                    pipeline.update_source_size(
+                        frame_box.frame_data.input_stream_width,
+                        frame_box.frame_data.input_stream_height,
+                    );
+                    unsafe {
+                        if !frame_box.frame.as_ptr().is_null() {
+                            pipeline.update_sample_aspect_ratio((*frame_box.frame.as_ptr()).sample_aspect_ratio);
+                        }
+                    }
+
+                    if has_data(&frame_box.frame) {
+                        pipeline.count_frame_in();
+                    }
+
                     // filter frame
                     match pipeline.run_filters(frame_box.frame) {
                         Ok(tmp_frame) => send_frame(
@@ -362,13 +383,27 @@ fn send_frame(
     tmp_frame: Option<Frame>,
 ) -> crate::error::Result<()> {
     if let Some(frame) = tmp_frame {
+        // counted once, however many destinations it is sent to
+        if has_data(&frame) {
+            pipeline.count_frame_out();
+        }
+
+        let sample_aspect_ratio = unsafe {
+            if frame.as_ptr().is_null() {
+                AVRational { num: 0, den: 1 }
+            } else {
+                (*frame.as_ptr()).sample_aspect_ratio
+            }
+        };
+        let (input_stream_width, input_stream_height) = pipeline.source_size();
         let mut frame_box = FrameBox {
             frame,
             frame_data: FrameData {
                 framerate: None,
                 bits_per_raw_sample: 0,
-                input_stream_width: 0,
-                input_stream_height: 0,
+                input_stream_width,
+                input_stream_height,
+                sample_aspect_ratio,
                 subtitle_header_size: 0,
                 subtitle_header: null_mut(),
                 fg_input_index: usize::MAX,
@@ -436,6 +471,11 @@ fn send_frame(
     Ok(())
 }
 
+/// Whether `frame` carries data, rather than the properties only, e.g. to signal the EOF timestamp.
+fn has_data(frame: &Frame) -> bool {
+    unsafe { !frame.as_ptr().is_null() && !(*frame.as_ptr()).buf[0].is_null() }
+}
+
 fn pipeline_uninit(pipeline: &mut FramePipeline) {
     pipeline.uninit_filters()
 }
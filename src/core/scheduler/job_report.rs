@@ -0,0 +1,136 @@
+//! The summary of a job returned by [`FfmpegScheduler::report`](crate::FfmpegScheduler::report).
+//!
+//! With the `serde` feature, every type of this module implements `serde::Serialize`, so the
+//! report of a job can be logged as JSON:
+//!
+//! ```rust,ignore
+//! let (result, scheduler) = FfmpegScheduler::new(context).start()?.finish();
+//! let report = scheduler.report();
+//! println!("{}", serde_json::to_string(&report)?);
+//! ```
+
+use crate::core::stream_info::StreamInfo;
+
+/// What a job read, wrote and ran into, as of the moment it is taken.
+///
+/// A report can be taken at any time: while the job runs, the counters are the values so far.
+/// Once the job ended, even with an error, they describe what was processed until then and
+/// [`error`](Self::error) holds the error.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JobReport {
+    /// The inputs of the job, in the order they were added to the context.
+    pub inputs: Vec<InputReport>,
+    /// The outputs of the job, in the order they were added to the context.
+    pub outputs: Vec<OutputReport>,
+    /// Video frames written, summed over the video streams of all outputs.
+    pub frames: u64,
+    /// Media time written, in seconds: the longest of the output streams.
+    pub duration_secs: f64,
+    /// Wall-clock time the job has been running, or ran for, in seconds.
+    pub elapsed_secs: f64,
+    /// [`frames`](Self::frames) per second of [`elapsed_secs`](Self::elapsed_secs).
+    pub average_fps: f64,
+    /// Packets the decoders could not decode and skipped, over all inputs.
+    pub decode_errors: u64,
+    /// Whether the job has ended (completed, failed or aborted).
+    pub finished: bool,
+    /// The error that ended the job, if it failed.
+    pub error: Option<String>,
+}
+
+/// An input of a [`JobReport`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InputReport {
+    /// The URL of the input, or `read_callback[<index>]` for an input read by a callback.
+    pub url: String,
+    /// The streams of the input, as probed when the input was opened.
+    pub streams: Vec<StreamReport>,
+    /// Packets of this input the decoders could not decode and skipped.
+    pub decode_errors: u64,
+}
+
+/// An output of a [`JobReport`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OutputReport {
+    /// The URL of the output.
+    pub url: String,
+    /// The streams of the output, as written in its header. Empty as long as the header is not
+    /// written, e.g. when the job failed before the first frame of each stream.
+    pub streams: Vec<StreamReport>,
+    /// Bytes of packets written to the output, over all of its streams.
+    pub bytes: u64,
+}
+
+/// A stream of an input or output of a [`JobReport`]. Fields not applying to the type of the
+/// stream are 0.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StreamReport {
+    /// The index of the stream in its input or output.
+    pub index: usize,
+    /// The type of the stream: `"video"`, `"audio"`, `"subtitle"`, `"data"`, `"attachment"`
+    /// or `"unknown"`.
+    pub media_type: String,
+    /// The codec of the stream, e.g. `"h264"`.
+    pub codec_name: String,
+    /// The width of a video stream, in pixels.
+    pub width: i32,
+    /// The height of a video stream, in pixels.
+    pub height: i32,
+    /// The average frame rate of a video stream.
+    pub frame_rate: f64,
+    /// The sample rate of an audio stream, in Hz.
+    pub sample_rate: i32,
+    /// The number of channels of an audio stream.
+    pub channels: i32,
+    /// The bitrate of the stream, in bits per second, 0 if unknown.
+    pub bit_rate: i64,
+    /// Packets written to an output stream (frames for audio and video), 0 for input streams.
+    pub packets: u64,
+    /// Bytes written to an output stream, 0 for input streams.
+    pub bytes: u64,
+    /// Media time written to an output stream, in seconds, 0 for input streams.
+    pub duration_secs: f64,
+}
+
+impl StreamReport {
+    pub(crate) fn new(stream_info: &StreamInfo) -> Self {
+        match stream_info {
+            StreamInfo::Video { index, codec_name, width, height, fps, bit_rate, .. } => Self {
+                index: *index as usize,
+                media_type: "video".to_string(),
+                codec_name: codec_name.clone(),
+                width: *width,
+                height: *height,
+                frame_rate: *fps,
+                bit_rate: *bit_rate,
+                ..Default::default()
+            },
+            StreamInfo::Audio { index, codec_name, sample_rate, nb_channels, bit_rate, .. } => Self {
+                index: *index as usize,
+                media_type: "audio".to_string(),
+                codec_name: codec_name.clone(),
+                sample_rate: *sample_rate,
+                channels: *nb_channels,
+                bit_rate: *bit_rate,
+                ..Default::default()
+            },
+            StreamInfo::Subtitle { index, codec_name, .. } => Self::other(*index, "subtitle", codec_name),
+            StreamInfo::Data { index, .. } => Self::other(*index, "data", ""),
+            StreamInfo::Attachment { index, codec_name, .. } => Self::other(*index, "attachment", codec_name),
+            StreamInfo::Unknown { index, .. } => Self::other(*index, "unknown", ""),
+        }
+    }
+
+    fn other(index: i32, media_type: &str, codec_name: &str) -> Self {
+        Self {
+            index: index as usize,
+            media_type: media_type.to_string(),
+            codec_name: codec_name.to_string(),
+            ..Default::default()
+        }
+    }
+}
@@ -52,9 +52,10 @@ use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_ATTACHMENT, AVMEDIA_TYPE_AUDIO,
 /// }
 /// ```
 pub mod ffmpeg_scheduler;
+pub mod job_report;
 mod frame_filter_pipeline;
 mod mux_task;
-mod enc_task;
+pub(crate) mod enc_task;
 pub(crate) mod filter_task;
 mod dec_task;
 mod demux_task;
@@ -1,8 +1,10 @@
-use crate::core::context::muxer::Muxer;
+use crate::core::context::muxer::{Muxer, StreamWritten};
 use crate::core::context::obj_pool::ObjPool;
-use crate::core::context::{AVFormatContextBox, PacketBox, PacketData};
-use crate::core::scheduler::ffmpeg_scheduler::{packet_is_null, set_scheduler_error, wait_until_not_paused, STATUS_END};
+use crate::core::context::packet_channel_output::PacketSink;
+use crate::core::context::{AVFormatContextBox, BsfContextBox, PacketBox, PacketData};
+use crate::core::scheduler::ffmpeg_scheduler::{packet_is_null, set_scheduler_error, wait_until_not_paused, StreamStats, STATUS_END};
 use crate::core::scheduler::input_controller::{InputController, SchNode};
+use crate::core::stream_info::{stream_infos_from_format_context, StreamInfo};
 use crate::error::Error::Muxing;
 use crate::error::{MuxingError, MuxingOperationError, WriteHeaderError};
 use crate::util::ffmpeg_utils::{av_err2str, hashmap_to_avdictionary};
@@ -10,13 +12,14 @@ use crate::util::thread_synchronizer::ThreadSynchronizer;
 use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use ffmpeg_next::packet::{Mut, Ref};
 use ffmpeg_next::Packet;
+use ffmpeg_sys_next::AVCodecID::{AV_CODEC_ID_AAC, AV_CODEC_ID_H264, AV_CODEC_ID_HEVC};
 use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO};
-use ffmpeg_sys_next::{av_get_audio_frame_duration2, av_interleaved_write_frame, av_packet_rescale_ts, av_rescale_delta, av_rescale_q, av_write_trailer, avformat_write_header, AVFormatContext, AVPacket, AVRational, AVERROR, AVERROR_EOF, AVFMT_NOTIMESTAMPS, AVFMT_TS_NONSTRICT, AV_LOG_DEBUG, AV_LOG_WARNING, AV_NOPTS_VALUE, AV_PKT_FLAG_KEY, AV_TIME_BASE_Q, EAGAIN};
+use ffmpeg_sys_next::{av_bsf_receive_packet, av_bsf_send_packet, av_get_audio_frame_duration2, av_interleaved_write_frame, av_opt_set, av_write_frame, av_packet_rescale_ts, av_rescale_delta, av_rescale_q, av_write_trailer, avformat_write_header, avio_closep, AVCodecID, AVFormatContext, AVPacket, AVRational, AVERROR, AVERROR_EOF, AVERROR_INVALIDDATA, AVFMT_NOFILE, AVFMT_NOTIMESTAMPS, AVFMT_TS_NONSTRICT, AV_LOG_DEBUG, AV_LOG_WARNING, AV_NOPTS_VALUE, AV_PKT_FLAG_KEY, AV_TIME_BASE_Q, EAGAIN, ENOMEM};
 use log::{debug, error, info, trace, warn};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::ptr::null_mut;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -26,6 +29,7 @@ pub(crate) fn mux_init(
     packet_pool: ObjPool<Packet>,
     input_controller: Arc<InputController>,
     mux_stream_nodes: Vec<Arc<SchNode>>,
+    stream_stats: Option<StreamStatsReporter>,
     scheduler_status: Arc<AtomicUsize>,
     thread_sync: ThreadSynchronizer,
     scheduler_result: Arc<Mutex<Option<crate::error::Result<()>>>>,
@@ -40,13 +44,21 @@ pub(crate) fn mux_init(
         mux.take_queue(),
         mux.start_time_us,
         mux.recording_time_us,
+        mux.output_ts_offset_us,
+        mux.audio_sync_offset_us,
+        mux.interleaved,
         mux.stream_count(),
         mux.format_opts.clone(),
+        mux.take_bitstream_filters(),
+        mux.take_packet_sink(),
         mux.take_src_pre_recvs(),
         mux.get_is_started(),
+        mux.get_output_streams(),
+        mux.get_written(),
         packet_pool,
         input_controller,
         mux_stream_nodes,
+        stream_stats,
         scheduler_status,
         thread_sync,
         scheduler_result,
@@ -58,6 +70,7 @@ pub(crate) fn ready_to_init_mux(
     mux: &mut Muxer,
     packet_pool: ObjPool<Packet>,
     input_controller: Arc<InputController>,
+    stream_stats: Option<StreamStatsReporter>,
     scheduler_status: Arc<AtomicUsize>,
     thread_sync: ThreadSynchronizer,
     scheduler_result: Arc<Mutex<Option<crate::error::Result<()>>>>,
@@ -72,11 +85,18 @@ pub(crate) fn ready_to_init_mux(
         let queue = mux.take_queue();
         let src_pre_recvs = mux.take_src_pre_recvs();
         let is_started = mux.get_is_started();
+        let output_streams = mux.get_output_streams();
+        let written = mux.get_written();
         let start_time_us = mux.start_time_us;
         let recording_time_us = mux.recording_time_us;
+        let output_ts_offset_us = mux.output_ts_offset_us;
+        let audio_sync_offset_us = mux.audio_sync_offset_us;
+        let interleaved = mux.interleaved;
         let stream_count = mux.stream_count();
         let nb_streams_ready = mux.nb_streams_ready.clone();
         let format_opts = mux.format_opts.clone();
+        let bitstream_filters = mux.take_bitstream_filters();
+        let packet_sink = mux.take_packet_sink();
 
         let out_fmt_ctx_box =
             AVFormatContextBox::new(out_fmt_ctx, false, is_set_write_callback);
@@ -110,6 +130,9 @@ pub(crate) fn ready_to_init_mux(
                 if nb_streams_ready + 1 == stream_count {
                     let out_fmt_ctx = out_fmt_ctx_box.fmt_ctx;
                     out_fmt_ctx_box.fmt_ctx = null_mut();
+                    // nobody waits on this thread, so report the failure through the scheduler
+                    let status = scheduler_status.clone();
+                    let result = scheduler_result.clone();
                     if let Err(e) = mux_task_start(
                         mux_idx,
                         out_fmt_ctx,
@@ -117,18 +140,27 @@ pub(crate) fn ready_to_init_mux(
                         queue,
                         start_time_us,
                         recording_time_us,
+                        output_ts_offset_us,
+                        audio_sync_offset_us,
+                        interleaved,
                         stream_count,
                         format_opts,
+                        bitstream_filters,
+                        packet_sink,
                         src_pre_recvs,
                         is_started,
+                        output_streams,
+                        written,
                         packet_pool,
                         input_controller,
                         mux_stream_nodes,
+                        stream_stats,
                         scheduler_status,
                         thread_sync,
                         scheduler_result,
                     ) {
                         error!("Muxer init error: {e}");
+                        set_scheduler_error(&status, &result, e);
                     }
                     break;
                 }
@@ -146,13 +178,21 @@ fn mux_task_start(mux_idx: usize,
                   queue: Option<(Sender<PacketBox>, Receiver<PacketBox>)>,
                   start_time_us: Option<i64>,
                   recording_time_us: Option<i64>,
+                  output_ts_offset_us: Option<i64>,
+                  audio_sync_offset_us: Option<i64>,
+                  interleaved: bool,
                   stream_count: usize,
                   format_opts: Option<HashMap<CString, CString>>,
+                  bitstream_filters: HashMap<i32, BsfContextBox>,
+                  packet_sink: Option<PacketSink>,
                   src_pre_receivers: Vec<Receiver<PacketBox>>,
                   is_started: Arc<AtomicBool>,
+                  output_streams: Arc<Mutex<Option<Vec<StreamInfo>>>>,
+                  written: Vec<Arc<StreamWritten>>,
                   packet_pool: ObjPool<Packet>,
                   input_controller: Arc<InputController>,
                   mux_stream_nodes: Vec<Arc<SchNode>>,
+                  stream_stats: Option<StreamStatsReporter>,
                   scheduler_status: Arc<AtomicUsize>,
                   thread_sync: ThreadSynchronizer,
                   scheduler_result: Arc<Mutex<Option<crate::error::Result<()>>>>,) -> crate::error::Result<()> {
@@ -163,7 +203,7 @@ fn mux_task_start(mux_idx: usize,
 
     let (queue_sender, queue_receiver) = queue.unwrap();
 
-    _mux_init(mux_idx, out_fmt_ctx, is_set_write_callback, queue_receiver, start_time_us, recording_time_us, stream_count, format_opts, packet_pool,input_controller, mux_stream_nodes, scheduler_status, thread_sync, scheduler_result)?;
+    _mux_init(mux_idx, out_fmt_ctx, is_set_write_callback, queue_receiver, start_time_us, recording_time_us, output_ts_offset_us, audio_sync_offset_us, interleaved, stream_count, format_opts, bitstream_filters, packet_sink, output_streams, written, packet_pool,input_controller, mux_stream_nodes, stream_stats, scheduler_status, thread_sync, scheduler_result)?;
 
     for src_pre_receiver in src_pre_receivers {
         {
@@ -185,11 +225,19 @@ fn _mux_init(
     pkt_receiver: Receiver<PacketBox>,
     start_time_us: Option<i64>,
     recording_time_us: Option<i64>,
+    output_ts_offset_us: Option<i64>,
+    audio_sync_offset_us: Option<i64>,
+    interleaved: bool,
     stream_count: usize,
     format_opts: Option<HashMap<CString, CString>>,
+    bitstream_filters: HashMap<i32, BsfContextBox>,
+    packet_sink: Option<PacketSink>,
+    output_streams: Arc<Mutex<Option<Vec<StreamInfo>>>>,
+    written: Vec<Arc<StreamWritten>>,
     packet_pool: ObjPool<Packet>,
     input_controller: Arc<InputController>,
     mux_stream_nodes: Vec<Arc<SchNode>>,
+    stream_stats: Option<StreamStatsReporter>,
     scheduler_status: Arc<AtomicUsize>,
     thread_sync: ThreadSynchronizer,
     scheduler_result: Arc<Mutex<Option<crate::error::Result<()>>>>,
@@ -210,6 +258,13 @@ fn _mux_init(
         )));
     }
 
+    // the header may update the stream parameters (e.g. the time base), snapshot them now
+    *output_streams.lock().unwrap() = Some(unsafe { stream_infos_from_format_context(out_fmt_ctx) });
+    // before any packet, so that a receiver always finds the streams of its packets
+    if let Some(packet_sink) = &packet_sink {
+        unsafe { packet_sink.set_streams(out_fmt_ctx) };
+    }
+
     let oformat_flags = unsafe {
         let oformat = (*out_fmt_ctx).oformat;
         (*oformat).flags
@@ -222,10 +277,34 @@ fn _mux_init(
         let mut started = false;
         let mut st_rescale_delta_last_map = HashMap::new();
         let mut st_last_dts_map = HashMap::new();
+        let mut stream_stats = stream_stats;
+        let mut st_stats_map: HashMap<i32, StreamStatsWindow> = HashMap::new();
 
         let mut nb_done = 0;
 
         let mut ret = 0;
+        // set when the failure is better described than by the return code alone
+        let mut failure = None;
+
+        let mut write = |packet_box: PacketBox| -> (i32, Option<&'static str>) {
+            write_and_report(
+                packet_box,
+                &mut st_rescale_delta_last_map,
+                oformat_flags,
+                &mut st_last_dts_map,
+                &out_fmt_ctx_box,
+                output_ts_offset_us,
+                interleaved,
+                &bitstream_filters,
+                packet_sink.as_ref(),
+                format_name,
+                &packet_pool,
+                &written,
+                &mut stream_stats,
+                &mut st_stats_map,
+                mux_idx,
+            )
+        };
 
         loop {
             let result = pkt_receiver.recv_timeout(Duration::from_millis(100));
@@ -251,6 +330,8 @@ fn _mux_init(
             unsafe {
                 if packet_is_null(&packet_box.packet) || packet_box.packet.is_empty() {
                     nb_done += 1;
+                    let stream_index = (*pkt).stream_index;
+                    let packet_data = packet_box.packet_data.clone();
                     packet_pool.release(packet_box.packet);
 
                     let mux_stream_node = mux_stream_node.as_ref();
@@ -258,6 +339,28 @@ fn _mux_init(
                     source_finished.store(true, Ordering::Release);
                     input_controller.update_locked(&scheduler_status);
 
+                    // write what the bitstream filter of the stream still holds
+                    if let Some(bsf) = bitstream_filters.get(&stream_index) {
+                        match bitstream_filter(bsf, None, &packet_data, &packet_pool) {
+                            Ok(packets) => {
+                                for packet_box in packets {
+                                    (ret, _) = write(packet_box);
+                                    if ret < 0 {
+                                        break;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                ret = e;
+                                failure = Some(MuxingOperationError::BitstreamFilterError(MuxingError::from(e)));
+                            }
+                        }
+                        if ret < 0 {
+                            error!("Error flushing the bitstream filter of stream {stream_index}");
+                            break;
+                        }
+                    }
+
                     if nb_done == stream_count {
                         trace!("All streams finished");
                         break;
@@ -269,55 +372,95 @@ fn _mux_init(
                 update_last_dts(mux_stream_node, &input_controller, &scheduler_status, pkt);
 
                 if !packet_is_null(&packet_box.packet) && packet_data.is_copy {
-                    ret = streamcopy_rescale(
+                    let copy_ret = streamcopy_rescale(
                         packet_box.packet.as_mut_ptr(),
                         &packet_data,
                         &start_time_us,
                         &recording_time_us,
                         &mut started,
                     );
-                    if ret == AVERROR(EAGAIN) {
+                    // a dropped packet is not a write error
+                    if copy_ret == AVERROR(EAGAIN) {
+                        packet_pool.release(packet_box.packet);
                         continue;
-                    } else if ret == AVERROR_EOF {
                     }
                 }
 
-                // write
-                if !packet_is_null(&packet_box.packet)
-                    && (*packet_box.packet.as_ptr()).stream_index >= 0
-                {
-                    ret = write_packet(
-                        &mut st_rescale_delta_last_map,
-                        oformat_flags,
-                        &mut st_last_dts_map,
-                        &out_fmt_ctx_box,
-                        &mut packet_box,
-                    );
+                if packet_data.codec_type == AVMEDIA_TYPE_AUDIO {
+                    if let Some(audio_sync_offset_us) = audio_sync_offset_us {
+                        // audio moved before the start of the output is dropped
+                        if !apply_audio_sync_offset(packet_box.packet.as_mut_ptr(), audio_sync_offset_us) {
+                            packet_pool.release(packet_box.packet);
+                            continue;
+                        }
+                    }
+                }
+
+                if packet_is_null(&packet_box.packet) || (*packet_box.packet.as_ptr()).stream_index < 0 {
                     packet_pool.release(packet_box.packet);
+                    continue;
+                }
 
-                    if ret == AVERROR_EOF {
-                        trace!("Muxer returned EOF");
-                        break;
-                    } else if ret < 0 {
-                        error!("Error muxing a packet");
+                let packets = match bitstream_filters.get(&(*pkt).stream_index) {
+                    Some(bsf) if packet_data.is_copy => {
+                        let packet_data = packet_data.clone();
+                        match bitstream_filter(bsf, Some(packet_box), &packet_data, &packet_pool) {
+                            Ok(packets) => packets,
+                            Err(e) => {
+                                error!("Error filtering a packet: {}", av_err2str(e));
+                                ret = e;
+                                failure = Some(MuxingOperationError::BitstreamFilterError(MuxingError::from(e)));
+                                break;
+                            }
+                        }
+                    }
+                    _ => vec![packet_box],
+                };
+
+                // write
+                for packet_box in packets {
+                    let suggested_bsf;
+                    (ret, suggested_bsf) = write(packet_box);
+                    if ret < 0 {
+                        if let Some(bsf) = suggested_bsf.filter(|_| ret == AVERROR_INVALIDDATA) {
+                            error!("The muxer rejected a copied packet, the stream probably needs the '{bsf}' bitstream filter");
+                            failure = Some(MuxingOperationError::MissingBitstreamFilter(MuxingError::from(ret), bsf.to_string()));
+                        }
                         break;
                     }
                 }
+
+                if ret == AVERROR_EOF {
+                    trace!("Muxer returned EOF");
+                    break;
+                } else if ret < 0 {
+                    error!("Error muxing a packet");
+                    break;
+                }
             }
         }
 
         if ret < 0 && ret != AVERROR_EOF {
-            set_scheduler_error(
-                &scheduler_status,
-                &scheduler_result,
-                Muxing(MuxingOperationError::InterleavedWriteError(
-                    MuxingError::from(ret),
-                )),
-            );
+            let error = failure.unwrap_or_else(|| MuxingOperationError::InterleavedWriteError(MuxingError::from(ret)));
+            set_scheduler_error(&scheduler_status, &scheduler_result, Muxing(error));
+        }
+
+        if let Some(stream_stats) = stream_stats.as_mut() {
+            stream_stats.flush(&mut st_stats_map, mux_idx);
         }
 
         // write_trailer
         unsafe {
+            // don't rewrite the file of a failed job to move its index to the front (faststart)
+            let failed = matches!(*scheduler_result.lock().unwrap(), Some(Err(_)));
+            let priv_data = (*out_fmt_ctx_box.fmt_ctx).priv_data;
+            if failed && !priv_data.is_null() && !(*(*out_fmt_ctx_box.fmt_ctx).oformat).priv_class.is_null() {
+                let movflags = CString::new("movflags").unwrap();
+                let no_faststart = CString::new("-faststart").unwrap();
+                // fails harmlessly for muxers without the option
+                av_opt_set(priv_data, movflags.as_ptr(), no_faststart.as_ptr(), 0);
+            }
+
             let ret = av_write_trailer(out_fmt_ctx_box.fmt_ctx);
             if ret < 0 {
                 error!("Error writing trailer: {}", av_err2str(ret));
@@ -329,6 +472,23 @@ fn _mux_init(
                     ))),
                 );
             }
+
+            // close the file here rather than on drop, so that a failure to flush it is reported
+            let fmt_ctx = out_fmt_ctx_box.fmt_ctx;
+            if !is_set_write_callback
+                && oformat_flags & AVFMT_NOFILE == 0
+                && !(*fmt_ctx).pb.is_null()
+            {
+                let ret = avio_closep(&mut (*fmt_ctx).pb);
+                if ret < 0 {
+                    error!("Error closing output file: {}", av_err2str(ret));
+                    set_scheduler_error(
+                        &scheduler_status,
+                        &scheduler_result,
+                        Muxing(MuxingOperationError::IOCloseError(MuxingError::from(ret))),
+                    );
+                }
+            }
         }
 
         debug!("Muxer finished.");
@@ -346,6 +506,152 @@ fn _mux_init(
     Ok(())
 }
 
+/// Forwards per-stream bitrate statistics from the muxer threads to the user callback.
+#[derive(Clone)]
+pub(crate) struct StreamStatsReporter {
+    callback: Arc<Mutex<Box<dyn FnMut(StreamStats) + Send>>>,
+    window_us: i64,
+    // frames dropped and duplicated on each stream of the muxer, see `Muxer::get_dropped_frames`
+    dropped_frames: Vec<Arc<AtomicU64>>,
+    duplicated_frames: Vec<Arc<AtomicU64>>,
+}
+
+#[derive(Default)]
+struct StreamStatsWindow {
+    start_us: i64,
+    last_dts_us: i64,
+    // the end of the last packet: its DTS plus its duration
+    end_us: i64,
+    bytes: u64,
+    packets: u64,
+}
+
+impl StreamStatsReporter {
+    pub(crate) fn new(callback: Box<dyn FnMut(StreamStats) + Send>, window_us: i64) -> Self {
+        Self {
+            callback: Arc::new(Mutex::new(callback)),
+            window_us: window_us.max(1),
+            dropped_frames: vec![],
+            duplicated_frames: vec![],
+        }
+    }
+
+    /// Returns a reporter for the streams of `mux`.
+    pub(crate) fn for_muxer(&self, mux: &Muxer) -> Self {
+        Self {
+            callback: self.callback.clone(),
+            window_us: self.window_us,
+            dropped_frames: mux.get_dropped_frames(),
+            duplicated_frames: mux.get_duplicated_frames(),
+        }
+    }
+
+    /// Returns the frames dropped on `stream_index` since the last call.
+    fn take_dropped_frames(&self, stream_index: i32) -> u64 {
+        take_count(&self.dropped_frames, stream_index)
+    }
+
+    /// Returns the frames duplicated on `stream_index` since the last call.
+    fn take_duplicated_frames(&self, stream_index: i32) -> u64 {
+        take_count(&self.duplicated_frames, stream_index)
+    }
+
+    fn update(
+        &mut self,
+        st_stats_map: &mut HashMap<i32, StreamStatsWindow>,
+        mux_idx: usize,
+        stream_index: i32,
+        size: i32,
+        dts_us: i64,
+        duration_us: i64,
+    ) {
+        if dts_us == AV_NOPTS_VALUE {
+            return;
+        }
+        let window = st_stats_map.entry(stream_index).or_insert_with(|| StreamStatsWindow {
+            start_us: dts_us,
+            last_dts_us: dts_us,
+            end_us: dts_us,
+            ..Default::default()
+        });
+
+        // Timestamps went backwards (e.g. a reset in a live input; the restarts of
+        // stream_loop are shifted after the previous pass instead), the current window no
+        // longer describes a contiguous range: report it up to its last packet and start over.
+        if dts_us < window.last_dts_us {
+            debug!("Stream {stream_index} timestamps went backwards, restarting stream stats.");
+            let stats = StreamStats {
+                output_index: mux_idx,
+                stream_index: stream_index as usize,
+                bytes: window.bytes,
+                packets: window.packets,
+                start_time_us: window.start_us,
+                duration_us: window.end_us - window.start_us,
+                dropped_frames: self.take_dropped_frames(stream_index),
+                duplicated_frames: self.take_duplicated_frames(stream_index),
+            };
+            self.report(stats);
+            *window = StreamStatsWindow {
+                start_us: dts_us,
+                last_dts_us: dts_us,
+                end_us: dts_us,
+                ..Default::default()
+            };
+        }
+
+        if dts_us - window.start_us >= self.window_us && window.packets > 0 {
+            let stats = StreamStats {
+                output_index: mux_idx,
+                stream_index: stream_index as usize,
+                bytes: window.bytes,
+                packets: window.packets,
+                start_time_us: window.start_us,
+                duration_us: dts_us - window.start_us,
+                dropped_frames: self.take_dropped_frames(stream_index),
+                duplicated_frames: self.take_duplicated_frames(stream_index),
+            };
+            self.report(stats);
+            *window = StreamStatsWindow {
+                start_us: dts_us,
+                last_dts_us: dts_us,
+                end_us: dts_us,
+                ..Default::default()
+            };
+        }
+
+        window.bytes += size.max(0) as u64;
+        window.packets += 1;
+        window.last_dts_us = dts_us;
+        window.end_us = window.end_us.max(dts_us + duration_us.max(0));
+    }
+
+    fn flush(&mut self, st_stats_map: &mut HashMap<i32, StreamStatsWindow>, mux_idx: usize) {
+        for (stream_index, window) in st_stats_map.drain() {
+            if window.packets == 0 {
+                continue;
+            }
+            let stats = StreamStats {
+                output_index: mux_idx,
+                stream_index: stream_index as usize,
+                bytes: window.bytes,
+                packets: window.packets,
+                start_time_us: window.start_us,
+                // up to the end of the last packet, not its start
+                duration_us: window.end_us - window.start_us,
+                dropped_frames: self.take_dropped_frames(stream_index),
+                duplicated_frames: self.take_duplicated_frames(stream_index),
+            };
+            self.report(stats);
+        }
+    }
+
+    fn report(&self, stats: StreamStats) {
+        if let Ok(mut callback) = self.callback.lock() {
+            callback(stats);
+        }
+    }
+}
+
 unsafe fn update_last_dts(mux_stream_node: &Arc<SchNode>, input_controller: &Arc<InputController>, scheduler_status: &Arc<AtomicUsize>, pkt: *const AVPacket) {
     if (*pkt).dts != AV_NOPTS_VALUE {
         let dts = av_rescale_q((*pkt).dts + (*pkt).duration, (*pkt).time_base, AV_TIME_BASE_Q);
@@ -412,12 +718,154 @@ unsafe fn streamcopy_rescale(
     0
 }
 
+/// Writes `packet_box` and adds it to the statistics of its stream. Also returns the bitstream
+/// filter a copied packet looks like it needs, in case the muxer rejects it.
+unsafe fn write_and_report(
+    mut packet_box: PacketBox,
+    st_rescale_delta_last_map: &mut HashMap<i32, i64>,
+    oformat_flags: i32,
+    st_last_dts_map: &mut HashMap<i32, i64>,
+    out_fmt_ctx_box: &AVFormatContextBox,
+    output_ts_offset_us: Option<i64>,
+    interleaved: bool,
+    bitstream_filters: &HashMap<i32, BsfContextBox>,
+    packet_sink: Option<&PacketSink>,
+    format_name: &str,
+    packet_pool: &ObjPool<Packet>,
+    written: &[Arc<StreamWritten>],
+    stream_stats: &mut Option<StreamStatsReporter>,
+    st_stats_map: &mut HashMap<i32, StreamStatsWindow>,
+    mux_idx: usize,
+) -> (i32, Option<&'static str>) {
+    let pkt = packet_box.packet.as_ptr();
+    let packet_data = &packet_box.packet_data;
+    let output_stream_index = packet_data.output_stream_index;
+    let packet_size = (*pkt).size;
+    let packet_dts_us = if (*pkt).dts == AV_NOPTS_VALUE {
+        AV_NOPTS_VALUE
+    } else {
+        av_rescale_q((*pkt).dts, (*pkt).time_base, AV_TIME_BASE_Q)
+    };
+    let packet_duration_us = av_rescale_q((*pkt).duration, (*pkt).time_base, AV_TIME_BASE_Q);
+
+    // the muxer takes the packet data, so look at it beforehand
+    let suggested_bsf = if packet_data.is_copy
+        && !packet_data.codecpar.is_null()
+        && !bitstream_filters.contains_key(&output_stream_index)
+    {
+        let data = if (*pkt).data.is_null() || (*pkt).size <= 0 {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts((*pkt).data, (*pkt).size as usize)
+        };
+        suggest_bitstream_filter((*packet_data.codecpar).codec_id, data, format_name)
+    } else {
+        None
+    };
+
+    let ret = write_packet(
+        st_rescale_delta_last_map,
+        oformat_flags,
+        st_last_dts_map,
+        out_fmt_ctx_box,
+        &mut packet_box,
+        output_ts_offset_us,
+        interleaved,
+        packet_sink,
+    );
+    packet_pool.release(packet_box.packet);
+
+    if ret >= 0 {
+        if let Some(written) = written.get(output_stream_index as usize) {
+            written.add(packet_size, packet_dts_us, packet_duration_us);
+        }
+        if let Some(stream_stats) = stream_stats.as_mut() {
+            stream_stats.update(
+                st_stats_map,
+                mux_idx,
+                output_stream_index,
+                packet_size,
+                packet_dts_us,
+                packet_duration_us,
+            );
+        }
+    }
+    (ret, suggested_bsf)
+}
+
+/// Sends `packet_box` through the bitstream filter `bsf`, or drains the filter when it is
+/// `None`, and returns the packets the filter outputs.
+unsafe fn bitstream_filter(
+    bsf: &BsfContextBox,
+    packet_box: Option<PacketBox>,
+    packet_data: &PacketData,
+    packet_pool: &ObjPool<Packet>,
+) -> Result<Vec<PacketBox>, i32> {
+    let bsf_ctx = bsf.bsf_ctx;
+    let ret = match packet_box {
+        Some(mut packet_box) => {
+            let pkt = packet_box.packet.as_mut_ptr();
+            av_packet_rescale_ts(pkt, (*pkt).time_base, (*bsf_ctx).time_base_in);
+            (*pkt).time_base = (*bsf_ctx).time_base_in;
+            let ret = av_bsf_send_packet(bsf_ctx, pkt);
+            packet_pool.release(packet_box.packet);
+            ret
+        }
+        None => av_bsf_send_packet(bsf_ctx, null_mut()),
+    };
+    if ret < 0 {
+        return Err(ret);
+    }
+
+    let mut packets = Vec::new();
+    loop {
+        let Ok(mut packet) = packet_pool.get() else {
+            return Err(AVERROR(ENOMEM));
+        };
+        let ret = av_bsf_receive_packet(bsf_ctx, packet.as_mut_ptr());
+        if ret == AVERROR(EAGAIN) || ret == AVERROR_EOF {
+            packet_pool.release(packet);
+            return Ok(packets);
+        } else if ret < 0 {
+            packet_pool.release(packet);
+            return Err(ret);
+        }
+        (*packet.as_mut_ptr()).time_base = (*bsf_ctx).time_base_out;
+        (*packet.as_mut_ptr()).stream_index = packet_data.output_stream_index;
+        packets.push(PacketBox {
+            packet,
+            packet_data: packet_data.clone(),
+        });
+    }
+}
+
+/// Returns the bitstream filter a copied packet of `codec_id` most likely needs to be written
+/// by the muxer `format_name`, judging by the framing of its `data`.
+fn suggest_bitstream_filter(codec_id: AVCodecID, data: &[u8], format_name: &str) -> Option<&'static str> {
+    const ANNEXB_FORMATS: [&str; 8] = ["mpegts", "hls", "rtp_mpegts", "h264", "hevc", "mpeg", "vob", "dvd"];
+    const ASC_FORMATS: [&str; 10] = ["mp4", "mov", "ipod", "ismv", "3gp", "3g2", "psp", "f4v", "flv", "matroska"];
+
+    let is_format = |formats: &[&str]| format_name.split(',').any(|name| formats.contains(&name));
+    let has_start_code = data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1]);
+    let is_adts = data.len() >= 2 && data[0] == 0xff && data[1] & 0xf0 == 0xf0;
+
+    match codec_id {
+        AV_CODEC_ID_H264 if !has_start_code && is_format(&ANNEXB_FORMATS) => Some("h264_mp4toannexb"),
+        AV_CODEC_ID_HEVC if !has_start_code && is_format(&ANNEXB_FORMATS) => Some("hevc_mp4toannexb"),
+        AV_CODEC_ID_AAC if is_adts && is_format(&ASC_FORMATS) => Some("aac_adtstoasc"),
+        _ => None,
+    }
+}
+
 unsafe fn write_packet(
     st_rescale_delta_last_map: &mut HashMap<i32, i64>,
     oformat_flags: i32,
     st_last_dts_map: &mut HashMap<i32, i64>,
     out_fmt_ctx_box: &AVFormatContextBox,
     mut sq_packet_box: &mut PacketBox,
+    output_ts_offset_us: Option<i64>,
+    interleaved: bool,
+    packet_sink: Option<&PacketSink>,
 ) -> i32 {
     mux_fixup_ts(
         st_rescale_delta_last_map,
@@ -425,13 +873,19 @@ unsafe fn write_packet(
         st_last_dts_map,
         &mut sq_packet_box,
         out_fmt_ctx_box.fmt_ctx,
+        output_ts_offset_us,
     );
 
     (*sq_packet_box.packet.as_mut_ptr()).stream_index =
         sq_packet_box.packet_data.output_stream_index;
-    let ret =
-        av_interleaved_write_frame(out_fmt_ctx_box.fmt_ctx, sq_packet_box.packet.as_mut_ptr());
-    ret
+    if let Some(packet_sink) = packet_sink {
+        return packet_sink.send(sq_packet_box.packet.as_ptr());
+    }
+    if interleaved {
+        av_interleaved_write_frame(out_fmt_ctx_box.fmt_ctx, sq_packet_box.packet.as_mut_ptr())
+    } else {
+        av_write_frame(out_fmt_ctx_box.fmt_ctx, sq_packet_box.packet.as_mut_ptr())
+    }
 }
 
 unsafe fn mux_fixup_ts(
@@ -440,6 +894,7 @@ unsafe fn mux_fixup_ts(
     st_last_dts_map: &mut HashMap<i32, i64>,
     packet_box: &mut PacketBox,
     out_fmt_ctx: *mut AVFormatContext,
+    output_ts_offset_us: Option<i64>,
 ) {
     let pkt = packet_box.packet.as_mut_ptr();
     let packet_data = &packet_box.packet_data;
@@ -483,6 +938,11 @@ unsafe fn mux_fixup_ts(
     }
     (*pkt).time_base = (**(*out_fmt_ctx).streams.add(stream_index as usize)).time_base;
 
+    // -output_ts_offset
+    if let Some(output_ts_offset_us) = output_ts_offset_us {
+        apply_ts_offset(pkt, av_rescale_q(output_ts_offset_us, AV_TIME_BASE_Q, (*pkt).time_base));
+    }
+
     if !st_last_dts_map.contains_key(&stream_index) {
         st_last_dts_map.insert(stream_index, AV_NOPTS_VALUE);
     }
@@ -540,6 +1000,7 @@ unsafe fn mux_fixup_ts(
             if (*pkt).pts >= (*pkt).dts {
                 (*pkt).pts = std::cmp::max((*pkt).pts, max);
             }
+            (*pkt).dts = max;
         }
     }
     *last_mux_dts = (*pkt).dts;
@@ -547,6 +1008,33 @@ unsafe fn mux_fixup_ts(
 
 
 
+/// Shifts the timestamps of the audio packet `pkt` by `offset_us` microseconds. Returns `false`,
+/// leaving `pkt` unchanged, if the packet would then start before zero.
+unsafe fn apply_audio_sync_offset(pkt: *mut AVPacket, offset_us: i64) -> bool {
+    let offset = av_rescale_q(offset_us, AV_TIME_BASE_Q, (*pkt).time_base);
+    let start = if (*pkt).pts != AV_NOPTS_VALUE { (*pkt).pts } else { (*pkt).dts };
+    if start != AV_NOPTS_VALUE && start + offset < 0 {
+        return false;
+    }
+    if (*pkt).pts != AV_NOPTS_VALUE {
+        (*pkt).pts += offset;
+    }
+    if (*pkt).dts != AV_NOPTS_VALUE {
+        (*pkt).dts += offset;
+    }
+    true
+}
+
+/// Adds `offset` to the timestamps of `pkt`, clamping those that would become negative to zero.
+unsafe fn apply_ts_offset(pkt: *mut AVPacket, offset: i64) {
+    if (*pkt).pts != AV_NOPTS_VALUE {
+        (*pkt).pts = ((*pkt).pts + offset).max(0);
+    }
+    if (*pkt).dts != AV_NOPTS_VALUE {
+        (*pkt).dts = ((*pkt).dts + offset).max(0);
+    }
+}
+
 fn min3(a: i64, b: i64, c: i64) -> i64 {
     std::cmp::min(a, std::cmp::min(b, c))
 }
@@ -554,3 +1042,122 @@ fn min3(a: i64, b: i64, c: i64) -> i64 {
 fn max3(a: i64, b: i64, c: i64) -> i64 {
     std::cmp::max(a, std::cmp::max(b, c))
 }
+
+fn take_count(counters: &[Arc<AtomicU64>], stream_index: i32) -> u64 {
+    counters
+        .get(stream_index as usize)
+        .map_or(0, |count| count.swap(0, Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_reporter(window_us: i64) -> (StreamStatsReporter, Arc<Mutex<Vec<StreamStats>>>) {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let reporter = StreamStatsReporter::new(Box::new(move |stats| sink.lock().unwrap().push(stats)), window_us);
+        (reporter, reported)
+    }
+
+    #[test]
+    fn test_stream_stats_windows() {
+        let (mut reporter, reported) = stats_reporter(1_000_000);
+        let mut windows = HashMap::new();
+        // 25 packets of 1000 bytes per second, for 1.52 seconds
+        for i in 0..38 {
+            reporter.update(&mut windows, 0, 0, 1000, i * 40_000, 40_000);
+        }
+        reporter.flush(&mut windows, 0);
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        assert_eq!((reported[0].packets, reported[0].bytes, reported[0].duration_us), (25, 25_000, 1_000_000));
+        assert_eq!(reported[0].bitrate(), 200_000.0);
+        // the last window ends with the duration of its last packet
+        assert_eq!((reported[1].start_time_us, reported[1].packets, reported[1].duration_us), (1_000_000, 13, 520_000));
+        assert_eq!(reported[1].bitrate(), 200_000.0);
+    }
+
+    #[test]
+    fn test_stream_stats_single_packet() {
+        let (mut reporter, reported) = stats_reporter(1_000_000);
+        let mut windows = HashMap::new();
+        reporter.update(&mut windows, 1, 2, 5000, 0, 40_000);
+        reporter.flush(&mut windows, 1);
+
+        let reported = reported.lock().unwrap();
+        assert_eq!((reported[0].output_index, reported[0].stream_index), (1, 2));
+        assert_eq!(reported[0].bitrate(), 1_000_000.0);
+    }
+
+    #[test]
+    fn test_stream_stats_backwards() {
+        let (mut reporter, reported) = stats_reporter(1_000_000);
+        let mut windows = HashMap::new();
+        for i in 0..10 {
+            reporter.update(&mut windows, 0, 0, 1000, i * 40_000, 40_000);
+        }
+        // the timestamps go back to 0: the partial window is reported, then a new one starts
+        for i in 0..5 {
+            reporter.update(&mut windows, 0, 0, 1000, i * 40_000, 40_000);
+        }
+        reporter.flush(&mut windows, 0);
+
+        let reported = reported.lock().unwrap();
+        let windows: Vec<_> =
+            reported.iter().map(|stats| (stats.start_time_us, stats.packets, stats.duration_us)).collect();
+        assert_eq!(windows, [(0, 10, 400_000), (0, 5, 200_000)]);
+    }
+
+    #[test]
+    fn test_suggest_bitstream_filter() {
+        let avcc = [0, 0, 0x12, 0x34, 0x65];
+        let annexb = [0, 0, 0, 1, 0x65];
+        let adts = [0xff, 0xf1, 0x50];
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_H264, &avcc, "mpegts"), Some("h264_mp4toannexb"));
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_HEVC, &avcc, "hls"), Some("hevc_mp4toannexb"));
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_H264, &annexb, "mpegts"), None);
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_H264, &avcc, "mp4"), None);
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_AAC, &adts, "mp4"), Some("aac_adtstoasc"));
+        assert_eq!(suggest_bitstream_filter(AV_CODEC_ID_AAC, &adts, "mpegts"), None);
+    }
+
+    #[test]
+    fn test_apply_ts_offset_clamps_negative_timestamps() {
+        let mut packet = Packet::empty();
+        unsafe {
+            let pkt = packet.as_mut_ptr();
+            (*pkt).pts = 3000;
+            (*pkt).dts = 1000;
+            apply_ts_offset(pkt, 9000);
+            assert_eq!(((*pkt).pts, (*pkt).dts), (12000, 10000));
+
+            apply_ts_offset(pkt, -11000);
+            assert_eq!(((*pkt).pts, (*pkt).dts), (1000, 0));
+
+            (*pkt).pts = AV_NOPTS_VALUE;
+            apply_ts_offset(pkt, 500);
+            assert_eq!(((*pkt).pts, (*pkt).dts), (AV_NOPTS_VALUE, 500));
+        }
+    }
+
+    #[test]
+    fn test_apply_audio_sync_offset_drops_leading_audio() {
+        let mut packet = Packet::empty();
+        unsafe {
+            let pkt = packet.as_mut_ptr();
+            (*pkt).time_base = AVRational { num: 1, den: 1000 };
+            (*pkt).pts = 100;
+            (*pkt).dts = 100;
+            assert!(apply_audio_sync_offset(pkt, 40_000));
+            assert_eq!(((*pkt).pts, (*pkt).dts), (140, 140));
+
+            assert!(apply_audio_sync_offset(pkt, -140_000));
+            assert_eq!(((*pkt).pts, (*pkt).dts), (0, 0));
+
+            assert!(!apply_audio_sync_offset(pkt, -1_000));
+            assert_eq!(((*pkt).pts, (*pkt).dts), (0, 0));
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::core::context::input::Input;
+use crate::core::context::output::Output;
+use crate::core::stream_info::init_format_context;
+use crate::error::Result;
+use ffmpeg_sys_next::AV_NOPTS_VALUE;
+use std::collections::HashMap;
+
+/// The container overhead [`estimate_output_size`] assumes: 2% on top of the streams, typical
+/// of MP4, MOV and Matroska files. MPEG-TS adds more (around 5-10%), as every 188 byte
+/// packet carries a header.
+pub const DEFAULT_CONTAINER_OVERHEAD: f64 = 1.02;
+
+/// Estimates the size, in bytes, of a file of `duration_us` microseconds whose video and
+/// audio streams are encoded at `video_bitrate` and `audio_bitrate` (bits per second, 0 for a
+/// missing stream), with the [default container overhead](DEFAULT_CONTAINER_OVERHEAD).
+///
+/// This is an **estimate**: encoders hit their target bitrate on average, not exactly, and
+/// quality-based rate control (`crf`, `qscale`) makes the size depend on the content.
+///
+/// # Example
+/// ```rust
+/// // 3 minutes at 2 Mb/s video and 128 kb/s audio: about 48.8 MB
+/// let bytes = estimate_output_size(180_000_000, 2_000_000, 128_000);
+/// println!("~{} MB", bytes / 1_000_000);
+/// ```
+pub fn estimate_output_size(duration_us: i64, video_bitrate: i64, audio_bitrate: i64) -> u64 {
+    estimate_output_size_with_overhead(duration_us, video_bitrate, audio_bitrate, DEFAULT_CONTAINER_OVERHEAD)
+}
+
+/// The same as [`estimate_output_size`], with the container overhead given as a factor
+/// applied to the size of the streams, e.g. `1.08` for MPEG-TS.
+pub fn estimate_output_size_with_overhead(
+    duration_us: i64,
+    video_bitrate: i64,
+    audio_bitrate: i64,
+    container_overhead: f64,
+) -> u64 {
+    let bits_per_second = (video_bitrate.max(0) + audio_bitrate.max(0)) as f64;
+    let seconds = duration_us.max(0) as f64 / 1_000_000.0;
+    (bits_per_second * seconds / 8.0 * container_overhead.max(0.0)).round() as u64
+}
+
+/// Estimates the size of `output` from its configured bitrates and duration, see
+/// [`FfmpegContextBuilder::estimated_size`](crate::core::context::ffmpeg_context_builder::FfmpegContextBuilder::estimated_size).
+pub(crate) fn estimate_builder_output_size(
+    inputs: &[Input],
+    output: &Output,
+    container_overhead: f64,
+) -> Result<Option<u64>> {
+    let video_bitrate = configured_bitrate(&output.video_codec_opts);
+    let audio_bitrate = configured_bitrate(&output.audio_codec_opts);
+    if video_bitrate.is_none() && audio_bitrate.is_none() {
+        return Ok(None);
+    }
+
+    let duration_us = match limit_us(output.start_time_us, output.recording_time_us, output.stop_time_us) {
+        Some(duration_us) => Some(duration_us),
+        None => {
+            let mut longest = None;
+            for input in inputs {
+                let Some(duration_us) = input_duration_us(input)? else {
+                    return Ok(None);
+                };
+                longest = longest.max(Some(duration_us));
+            }
+            longest
+        }
+    };
+
+    Ok(duration_us.map(|duration_us| {
+        estimate_output_size_with_overhead(
+            duration_us,
+            video_bitrate.unwrap_or(0),
+            audio_bitrate.unwrap_or(0),
+            container_overhead,
+        )
+    }))
+}
+
+/// The duration set by a recording time, or by a stop time past the start time.
+fn limit_us(start_time_us: Option<i64>, recording_time_us: Option<i64>, stop_time_us: Option<i64>) -> Option<i64> {
+    recording_time_us.or_else(|| stop_time_us.map(|stop_time_us| stop_time_us - start_time_us.unwrap_or(0)))
+}
+
+/// The duration read from `input`: what remains of the file after its start time, limited
+/// by its recording or stop time. `None` for inputs read through a callback, or files whose
+/// duration is unknown (e.g. live streams).
+fn input_duration_us(input: &Input) -> Result<Option<i64>> {
+    let Some(url) = &input.url else {
+        return Ok(None);
+    };
+    let fmt_ctx_box = init_format_context(url)?;
+    let duration = unsafe { (*fmt_ctx_box.fmt_ctx).duration };
+    if duration == AV_NOPTS_VALUE || duration <= 0 {
+        return Ok(None);
+    }
+    let remaining = (duration - input.start_time_us.unwrap_or(0)).max(0);
+    Ok(Some(match limit_us(input.start_time_us, input.recording_time_us, input.stop_time_us) {
+        Some(limit) => remaining.min(limit),
+        None => remaining,
+    }))
+}
+
+/// The bitrate set with the `b` option of an encoder, if any.
+fn configured_bitrate(codec_opts: &Option<HashMap<String, String>>) -> Option<i64> {
+    codec_opts.as_ref()?.get("b").and_then(|value| parse_bitrate(value))
+}
+
+/// Parses a bitrate the way FFmpeg options accept it: a number, optionally followed by an
+/// SI prefix (`k`, `M` or `G`), e.g. `"800000"`, `"192k"` or `"2.5M"`.
+fn parse_bitrate(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last()? {
+        (i, 'k' | 'K') => (&value[..i], 1e3),
+        (i, 'M') => (&value[..i], 1e6),
+        (i, 'G') => (&value[..i], 1e9),
+        _ => (value, 1.0),
+    };
+    let bitrate = number.parse::<f64>().ok()? * multiplier;
+    (bitrate.is_finite() && bitrate >= 0.0).then_some(bitrate.round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+
+    #[test]
+    fn test_estimate_output_size() {
+        assert_eq!(estimate_output_size_with_overhead(10_000_000, 1_000_000, 0, 1.0), 1_250_000);
+        assert_eq!(estimate_output_size(180_000_000, 2_000_000, 128_000), 48_837_600);
+        assert_eq!(estimate_output_size(0, 2_000_000, 128_000), 0);
+
+        assert_eq!(parse_bitrate("192k"), Some(192_000));
+        assert_eq!(parse_bitrate("2.5M"), Some(2_500_000));
+        assert_eq!(parse_bitrate("800000"), Some(800_000));
+        assert_eq!(parse_bitrate("fast"), None);
+    }
+
+    #[test]
+    fn test_builder_estimated_size() {
+        let builder = FfmpegContext::builder()
+            .input(Input::from("test.mp4").set_recording_time_us(2_000_000))
+            .output(Output::from("output.mp4").set_video_codec_opt("b", "1M").set_audio_codec_opt("b", "128k"))
+            // without bitrates, the size cannot be told
+            .output("output.mkv");
+        let sizes = builder.estimated_size_with_overhead(1.0).unwrap();
+        assert_eq!(sizes, vec![Some(282_000), None]);
+    }
+}
@@ -9,12 +9,15 @@ use ffmpeg_sys_next::AVMediaType::{
     AVMEDIA_TYPE_UNKNOWN, AVMEDIA_TYPE_VIDEO,
 };
 use ffmpeg_sys_next::{
-    av_dict_get, av_find_best_stream, avcodec_get_name, avformat_find_stream_info, AVCodecID,
-    AVDictionary, AVDictionaryEntry, AVRational, AV_DICT_IGNORE_SUFFIX,
+    av_dict_get, av_find_best_stream, av_get_pix_fmt_name, av_pix_fmt_desc_get, avcodec_get_name,
+    avcodec_profile_name, avformat_find_stream_info, AVCodecID, AVDictionary, AVDictionaryEntry,
+    AVFormatContext, AVRational, AV_DICT_IGNORE_SUFFIX, AV_DISPOSITION_ATTACHED_PIC,
+    AV_PIX_FMT_FLAG_RGB,
 };
 use ffmpeg_sys_next::{avformat_alloc_context, avformat_close_input, avformat_open_input};
 use crate::core::context::AVFormatContextBox;
-use crate::error::{FindStreamError, OpenInputError, Result};
+use crate::error::{Error, FindStreamError, OpenInputError, Result};
+use crate::util::ffmpeg_utils;
 
 #[derive(Debug, Clone)]
 pub enum StreamInfo {
@@ -512,6 +515,44 @@ pub fn find_attachment_stream_info(url: impl Into<String>) -> Result<Option<Stre
     }
 }
 
+/// Extracts the embedded cover art (album art or thumbnail) of the given media URL.
+///
+/// This function opens the media file or stream specified by the URL and
+/// looks for a stream with the `AV_DISPOSITION_ATTACHED_PIC` disposition. Its
+/// single packet is returned as is, so the bytes are the original image file
+/// (usually JPEG or PNG) and nothing is decoded or re-encoded.
+///
+/// # Parameters
+/// - `url`: The URL or file path of the media file.
+///
+/// # Returns
+/// - `Ok(Some(Vec<u8>))`: The encoded image bytes if an attached picture is found.
+/// - `Ok(None)`: Returned if the media has no attached picture.
+/// - `Err`: If an error occurs during the operation.
+pub fn extract_cover_art(url: impl Into<String>) -> Result<Option<Vec<u8>>> {
+    let in_fmt_ctx_box = init_format_context(url)?;
+
+    unsafe {
+        let fmt_ctx = in_fmt_ctx_box.fmt_ctx;
+        for i in 0..(*fmt_ctx).nb_streams {
+            let stream = *(*fmt_ctx).streams.add(i as usize);
+            if (*stream).disposition & AV_DISPOSITION_ATTACHED_PIC as i32 == 0 {
+                continue;
+            }
+
+            // the demuxer reads the picture packet while opening the input
+            let picture = &(*stream).attached_pic;
+            if picture.data.is_null() || picture.size <= 0 {
+                continue;
+            }
+            let bytes = std::slice::from_raw_parts(picture.data, picture.size as usize);
+            return Ok(Some(bytes.to_vec()));
+        }
+
+        Ok(None)
+    }
+}
+
 /// Finds the unknown stream information from the given media URL.
 ///
 /// This function opens the media file or stream specified by the URL and
@@ -561,141 +602,308 @@ pub fn find_unknown_stream_info(url: impl Into<String>) -> Result<Option<StreamI
 pub fn find_all_stream_infos(url: impl Into<String>) -> Result<Vec<StreamInfo>> {
     let in_fmt_ctx_box = init_format_context(url)?;
 
-    unsafe {
-        let mut stream_infos = Vec::new();
-
-        let stream_count = (*in_fmt_ctx_box.fmt_ctx).nb_streams;
-
-        for i in 0..stream_count {
-            let stream = *(*in_fmt_ctx_box.fmt_ctx).streams.add(i as usize);
-            let codec_parameters = (*stream).codecpar;
-            let codec_id = (*codec_parameters).codec_id;
-            let codec_name = CStr::from_ptr(avcodec_get_name(codec_id))
-                .to_str()
-                .unwrap_or("Unknown codec")
-                .to_string();
-
-            let index = (*stream).index;
-            let time_base = (*stream).time_base;
-            let start_time = (*stream).start_time;
-            let duration = (*stream).duration;
-            let nb_frames = (*stream).nb_frames;
-            let avg_frame_rate = (*stream).avg_frame_rate;
-            let metadata = av_dict_to_hashmap((*stream).metadata);
-
-            match (*codec_parameters).codec_type {
-                AVMEDIA_TYPE_VIDEO => {
-                    let width = (*codec_parameters).width;
-                    let height = (*codec_parameters).height;
-                    let bit_rate = (*codec_parameters).bit_rate;
-                    let pixel_format = (*codec_parameters).format;
-                    let video_delay = (*codec_parameters).video_delay;
-                    let r_frame_rate = (*stream).r_frame_rate;
-                    let sample_aspect_ratio = (*stream).sample_aspect_ratio;
-                    let fps = if avg_frame_rate.den == 0 {
-                        0.0
-                    } else {
-                        avg_frame_rate.num as f64 / avg_frame_rate.den as f64
-                    };
-
-                    // Fetch the rotation info from metadata (if present)
-                    let rotate = metadata
-                        .get("rotate")
-                        .and_then(|rotate| rotate.parse::<i32>().ok())
-                        .unwrap_or(0); // Default to 0 if no "rotate" key is found
-
-                    stream_infos.push(StreamInfo::Video {
-                        index,
-                        time_base,
-                        start_time,
-                        duration,
-                        nb_frames,
-                        r_frame_rate,
-                        sample_aspect_ratio,
-                        metadata,
-                        avg_frame_rate,
-                        codec_id,
-                        codec_name,
-                        width,
-                        height,
-                        bit_rate,
-                        pixel_format,
-                        video_delay,
-                        fps,
-                        rotate,
-                    });
-                }
-                AVMEDIA_TYPE_AUDIO => {
-                    let sample_rate = (*codec_parameters).sample_rate;
+    unsafe { Ok(stream_infos_from_format_context(in_fmt_ctx_box.fmt_ctx)) }
+}
+
+/// Builds the [`StreamInfo`] of every stream of an opened (input or output) format context.
+pub(crate) unsafe fn stream_infos_from_format_context(fmt_ctx: *const AVFormatContext) -> Vec<StreamInfo> {
+    let mut stream_infos = Vec::new();
+
+    let stream_count = (*fmt_ctx).nb_streams;
+
+    for i in 0..stream_count {
+        let stream = *(*fmt_ctx).streams.add(i as usize);
+        let codec_parameters = (*stream).codecpar;
+        let codec_id = (*codec_parameters).codec_id;
+        let codec_name = CStr::from_ptr(avcodec_get_name(codec_id))
+            .to_str()
+            .unwrap_or("Unknown codec")
+            .to_string();
+
+        let index = (*stream).index;
+        let time_base = (*stream).time_base;
+        let start_time = (*stream).start_time;
+        let duration = (*stream).duration;
+        let nb_frames = (*stream).nb_frames;
+        let avg_frame_rate = (*stream).avg_frame_rate;
+        let metadata = av_dict_to_hashmap((*stream).metadata);
+
+        match (*codec_parameters).codec_type {
+            AVMEDIA_TYPE_VIDEO => {
+                let width = (*codec_parameters).width;
+                let height = (*codec_parameters).height;
+                let bit_rate = (*codec_parameters).bit_rate;
+                let pixel_format = (*codec_parameters).format;
+                let video_delay = (*codec_parameters).video_delay;
+                let r_frame_rate = (*stream).r_frame_rate;
+                let sample_aspect_ratio = (*stream).sample_aspect_ratio;
+                let fps = if avg_frame_rate.den == 0 {
+                    0.0
+                } else {
+                    avg_frame_rate.num as f64 / avg_frame_rate.den as f64
+                };
+
+                // Fetch the rotation info from metadata (if present)
+                let rotate = metadata
+                    .get("rotate")
+                    .and_then(|rotate| rotate.parse::<i32>().ok())
+                    .unwrap_or(0); // Default to 0 if no "rotate" key is found
+
+                stream_infos.push(StreamInfo::Video {
+                    index,
+                    time_base,
+                    start_time,
+                    duration,
+                    nb_frames,
+                    r_frame_rate,
+                    sample_aspect_ratio,
+                    metadata,
+                    avg_frame_rate,
+                    codec_id,
+                    codec_name,
+                    width,
+                    height,
+                    bit_rate,
+                    pixel_format,
+                    video_delay,
+                    fps,
+                    rotate,
+                });
+            }
+            AVMEDIA_TYPE_AUDIO => {
+                let sample_rate = (*codec_parameters).sample_rate;
+                #[cfg(not(feature = "docs-rs"))]
+                let ch_layout = (*codec_parameters).ch_layout;
+                let sample_format = (*codec_parameters).format;
+                let frame_size = (*codec_parameters).frame_size;
+                let bit_rate = (*codec_parameters).bit_rate;
+
+                stream_infos.push(StreamInfo::Audio {
+                    index,
+                    time_base,
+                    start_time,
+                    duration,
+                    nb_frames,
+                    metadata,
+                    avg_frame_rate,
+                    codec_id,
+                    codec_name,
+                    sample_rate,
+                    #[cfg(not(feature = "docs-rs"))]
+                    order: ch_layout.order,
+                    #[cfg(feature = "docs-rs")]
+                    nb_channels: 0,
                     #[cfg(not(feature = "docs-rs"))]
-                    let ch_layout = (*codec_parameters).ch_layout;
-                    let sample_format = (*codec_parameters).format;
-                    let frame_size = (*codec_parameters).frame_size;
-                    let bit_rate = (*codec_parameters).bit_rate;
-
-                    stream_infos.push(StreamInfo::Audio {
-                        index,
-                        time_base,
-                        start_time,
-                        duration,
-                        nb_frames,
-                        metadata,
-                        avg_frame_rate,
-                        codec_id,
-                        codec_name,
-                        sample_rate,
-                        #[cfg(not(feature = "docs-rs"))]
-                        order: ch_layout.order,
-                        #[cfg(feature = "docs-rs")]
-                        nb_channels: 0,
-                        #[cfg(not(feature = "docs-rs"))]
-                        nb_channels: ch_layout.nb_channels,
-                        bit_rate,
-                        sample_format,
-                        frame_size,
-                    });
-                }
-                AVMEDIA_TYPE_SUBTITLE => {
-                    stream_infos.push(StreamInfo::Subtitle {
-                        index,
-                        time_base,
-                        start_time,
-                        duration,
-                        nb_frames,
-                        metadata,
-                        codec_id,
-                        codec_name,
-                    });
-                }
-                AVMEDIA_TYPE_DATA => {
-                    stream_infos.push(StreamInfo::Data {
-                        index,
-                        time_base,
-                        start_time,
-                        duration,
-                        metadata,
-                    });
-                }
-                AVMEDIA_TYPE_ATTACHMENT => {
-                    stream_infos.push(StreamInfo::Attachment {
-                        index,
-                        metadata,
-                        codec_id,
-                        codec_name,
-                    });
-                }
-                AVMEDIA_TYPE_UNKNOWN => {
-                    stream_infos.push(StreamInfo::Unknown { index, metadata });
-                }
-                _ => {}
+                    nb_channels: ch_layout.nb_channels,
+                    bit_rate,
+                    sample_format,
+                    frame_size,
+                });
+            }
+            AVMEDIA_TYPE_SUBTITLE => {
+                stream_infos.push(StreamInfo::Subtitle {
+                    index,
+                    time_base,
+                    start_time,
+                    duration,
+                    nb_frames,
+                    metadata,
+                    codec_id,
+                    codec_name,
+                });
             }
+            AVMEDIA_TYPE_DATA => {
+                stream_infos.push(StreamInfo::Data {
+                    index,
+                    time_base,
+                    start_time,
+                    duration,
+                    metadata,
+                });
+            }
+            AVMEDIA_TYPE_ATTACHMENT => {
+                stream_infos.push(StreamInfo::Attachment {
+                    index,
+                    metadata,
+                    codec_id,
+                    codec_name,
+                });
+            }
+            AVMEDIA_TYPE_UNKNOWN => {
+                stream_infos.push(StreamInfo::Unknown { index, metadata });
+            }
+            _ => {}
         }
+    }
+
+    stream_infos
+}
 
-        Ok(stream_infos)
+/// Codec level details of a video stream, to check it against the decoding constraints of
+/// a device (e.g. "H.264 High profile up to level 4.1, 8-bit 4:2:0").
+///
+/// Every field is `None` when the container or the codec parameters do not declare it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoCodecDetails {
+    /// The codec identifier (e.g., `AV_CODEC_ID_H264`).
+    pub codec_id: AVCodecID,
+
+    /// A human-readable name of the codec (e.g., `h264`).
+    pub codec_name: String,
+
+    /// The profile name as FFmpeg reports it (e.g., `High`, `Main 10`).
+    pub profile: Option<String>,
+
+    /// The raw profile number of the codec parameters.
+    pub profile_id: Option<i32>,
+
+    /// The level in the notation of the codec (e.g., `4.1` for H.264, HEVC and AV1),
+    /// or the raw level number for codecs without a known notation.
+    pub level: Option<String>,
+
+    /// The raw level number of the codec parameters (e.g., `41` for H.264 level 4.1).
+    pub level_id: Option<i32>,
+
+    /// The pixel format name (e.g., `yuv420p`, `yuv420p10le`).
+    pub pixel_format: Option<String>,
+
+    /// The number of bits of each luma (or first color) sample.
+    pub bit_depth: Option<u8>,
+
+    /// The chroma subsampling in J:a:b notation (e.g., `4:2:0`), `4:0:0` for grayscale.
+    pub chroma_subsampling: Option<String>,
+}
+
+/// Retrieves the codec profile, level and sample layout of a video stream.
+///
+/// The details come from the codec parameters the demuxer found, without decoding frames,
+/// so they describe what the stream declares, e.g. in its H.264 SPS or its `avcC` box.
+///
+/// # Parameters
+/// - `url`: The URL or file path of the media file to analyze.
+/// - `stream_index`: The index of the video stream within the media file.
+///
+/// # Returns
+/// - `Ok(VideoCodecDetails)`: The details of the stream.
+/// - `Err(Error::VideoStreamNotFound)`: If the input has no video stream `stream_index`.
+/// - `Err`: If an error occurs while opening the input or reading its stream information.
+///
+/// # Example
+/// ```rust,ignore
+/// let details = get_video_codec_details("input.mp4", 0)?;
+/// let playable = details.profile.as_deref() != Some("High 10")
+///     && details.level_id.is_some_and(|level| level <= 41)
+///     && details.chroma_subsampling.as_deref() == Some("4:2:0");
+/// ```
+pub fn get_video_codec_details(url: impl Into<String>, stream_index: usize) -> Result<VideoCodecDetails> {
+    let in_fmt_ctx_box = init_format_context(url)?;
+
+    unsafe {
+        let fmt_ctx = in_fmt_ctx_box.fmt_ctx;
+        if stream_index >= (*fmt_ctx).nb_streams as usize {
+            return Err(Error::VideoStreamNotFound(stream_index));
+        }
+        let stream = *(*fmt_ctx).streams.add(stream_index);
+        let codec_parameters = (*stream).codecpar;
+        if (*codec_parameters).codec_type != AVMEDIA_TYPE_VIDEO {
+            return Err(Error::VideoStreamNotFound(stream_index));
+        }
+
+        let codec_id = (*codec_parameters).codec_id;
+        let codec_name = CStr::from_ptr(avcodec_get_name(codec_id))
+            .to_str()
+            .unwrap_or("Unknown codec")
+            .to_string();
+
+        // AV_PROFILE_UNKNOWN and AV_LEVEL_UNKNOWN are negative
+        let profile_id = Some((*codec_parameters).profile).filter(|profile| *profile >= 0);
+        let profile = profile_id.and_then(|profile| {
+            let name = avcodec_profile_name(codec_id, profile);
+            (!name.is_null()).then(|| CStr::from_ptr(name).to_string_lossy().into_owned())
+        });
+        let level_id = Some((*codec_parameters).level).filter(|level| *level >= 0);
+        let level = level_id.and_then(|level| level_name(codec_id, level));
+
+        let format = ffmpeg_utils::pixel_format((*codec_parameters).format);
+        let (pixel_format, bit_depth, chroma_subsampling) = if let Some(format) = format {
+            let name = av_get_pix_fmt_name(format);
+            let pixel_format = (!name.is_null()).then(|| CStr::from_ptr(name).to_string_lossy().into_owned());
+            let desc = av_pix_fmt_desc_get(format);
+            if desc.is_null() {
+                (pixel_format, None, None)
+            } else {
+                let bit_depth = Some((*desc).comp[0].depth as u8);
+                let chroma_subsampling = chroma_subsampling_name(
+                    (*desc).nb_components,
+                    (*desc).flags & AV_PIX_FMT_FLAG_RGB as u64 != 0,
+                    (*desc).log2_chroma_w,
+                    (*desc).log2_chroma_h,
+                );
+                (pixel_format, bit_depth, chroma_subsampling)
+            }
+        } else {
+            (None, None, None)
+        };
+
+        Ok(VideoCodecDetails {
+            codec_id,
+            codec_name,
+            profile,
+            profile_id,
+            level,
+            level_id,
+            pixel_format,
+            bit_depth,
+            chroma_subsampling,
+        })
     }
 }
 
-fn init_format_context(url: impl Into<String>) -> Result<AVFormatContextBox> {
+/// Returns the level number of the codec parameters in the notation of the codec. FFmpeg
+/// has no helper for it, the numbering follows each codec's specification.
+fn level_name(codec_id: AVCodecID, level: i32) -> Option<String> {
+    match codec_id {
+        // level_idc, 10 times the level, 9 for level 1b
+        AVCodecID::AV_CODEC_ID_H264 if level == 9 => Some("1b".to_string()),
+        AVCodecID::AV_CODEC_ID_H264 | AVCodecID::AV_CODEC_ID_VP9 => Some(format!("{}.{}", level / 10, level % 10)),
+        // general_level_idc, 30 times the level
+        AVCodecID::AV_CODEC_ID_HEVC => Some(format!("{}.{}", level / 30, level % 30 / 3)),
+        // seq_level_idx, 31 when the stream has no level constraints
+        AVCodecID::AV_CODEC_ID_AV1 if level == 31 => None,
+        AVCodecID::AV_CODEC_ID_AV1 => Some(format!("{}.{}", 2 + (level >> 2), level & 3)),
+        AVCodecID::AV_CODEC_ID_MPEG2VIDEO => match level {
+            4 => Some("High".to_string()),
+            6 => Some("High 1440".to_string()),
+            8 => Some("Main".to_string()),
+            10 => Some("Low".to_string()),
+            _ => Some(level.to_string()),
+        },
+        _ => Some(level.to_string()),
+    }
+}
+
+/// Returns the J:a:b notation of the chroma subsampling of a pixel format.
+fn chroma_subsampling_name(nb_components: u8, rgb: bool, log2_chroma_w: u8, log2_chroma_h: u8) -> Option<String> {
+    // gray, with or without alpha
+    if nb_components <= 2 {
+        return Some("4:0:0".to_string());
+    }
+    if rgb {
+        return Some("4:4:4".to_string());
+    }
+    let name = match (log2_chroma_w, log2_chroma_h) {
+        (0, 0) => "4:4:4",
+        (0, 1) => "4:4:0",
+        (1, 0) => "4:2:2",
+        (1, 1) => "4:2:0",
+        (2, 0) => "4:1:1",
+        (2, 2) => "4:1:0",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+pub(crate) fn init_format_context(url: impl Into<String>) -> Result<AVFormatContextBox> {
     unsafe {
         let mut in_fmt_ctx = avformat_alloc_context();
         if in_fmt_ctx.is_null() {
@@ -823,9 +1031,88 @@ mod tests {
         assert!(option.is_none())
     }
 
+    #[test]
+    fn test_extract_cover_art() {
+        let cover_art = extract_cover_art("test.mp4").unwrap();
+        assert!(cover_art.is_none());
+    }
+
+    #[test]
+    fn test_extract_attached_cover_art() {
+        use ffmpeg_sys_next::AVCodecID::{AV_CODEC_ID_MJPEG, AV_CODEC_ID_SUBRIP};
+        use ffmpeg_sys_next::{
+            av_dict_set, av_mallocz, av_write_trailer, avformat_alloc_output_context2, avformat_free_context,
+            avformat_new_stream, avformat_write_header, avio_closep, avio_open, AVIO_FLAG_WRITE,
+            AV_INPUT_BUFFER_PADDING_SIZE,
+        };
+
+        // Matroska exports image attachments as attached pictures
+        let logo = std::fs::read("logo.jpg").unwrap();
+        unsafe {
+            let path = CString::new("cover_art.mkv").unwrap();
+            let mut fmt_ctx = null_mut();
+            assert!(avformat_alloc_output_context2(&mut fmt_ctx, null(), null(), path.as_ptr()) >= 0);
+            let subtitle = avformat_new_stream(fmt_ctx, null());
+            (*(*subtitle).codecpar).codec_type = AVMEDIA_TYPE_SUBTITLE;
+            (*(*subtitle).codecpar).codec_id = AV_CODEC_ID_SUBRIP;
+
+            let attachment = avformat_new_stream(fmt_ctx, null());
+            (*(*attachment).codecpar).codec_type = AVMEDIA_TYPE_ATTACHMENT;
+            (*(*attachment).codecpar).codec_id = AV_CODEC_ID_MJPEG;
+            let extradata = av_mallocz(logo.len() + AV_INPUT_BUFFER_PADDING_SIZE as usize) as *mut u8;
+            std::ptr::copy_nonoverlapping(logo.as_ptr(), extradata, logo.len());
+            (*(*attachment).codecpar).extradata = extradata;
+            (*(*attachment).codecpar).extradata_size = logo.len() as i32;
+            for (key, value) in [("filename", "cover.jpg"), ("mimetype", "image/jpeg")] {
+                let (key, value) = (CString::new(key).unwrap(), CString::new(value).unwrap());
+                av_dict_set(&mut (*attachment).metadata, key.as_ptr(), value.as_ptr(), 0);
+            }
+
+            assert!(avio_open(&mut (*fmt_ctx).pb, path.as_ptr(), AVIO_FLAG_WRITE) >= 0);
+            assert!(avformat_write_header(fmt_ctx, null_mut()) >= 0);
+            assert!(av_write_trailer(fmt_ctx) >= 0);
+            avio_closep(&mut (*fmt_ctx).pb);
+            avformat_free_context(fmt_ctx);
+        }
+
+        let cover_art = extract_cover_art("cover_art.mkv").unwrap();
+        let streams = find_all_stream_infos("cover_art.mkv").unwrap();
+        std::fs::remove_file("cover_art.mkv").unwrap();
+
+        // the original JPEG file, byte for byte
+        assert_eq!(cover_art, Some(logo));
+        assert!(streams
+            .iter()
+            .any(|stream| matches!(stream, StreamInfo::Video { codec_id: AV_CODEC_ID_MJPEG, .. })));
+    }
+
     #[test]
     fn test_find_unknown_stream_info() {
         let option = find_unknown_stream_info("test.mp4").unwrap();
         assert!(option.is_none())
     }
+    #[test]
+    fn test_get_video_codec_details() {
+        let details = get_video_codec_details("test.mp4", 0).unwrap();
+        println!("video_codec_details:{:?}", details);
+        assert_eq!(details.codec_id, AVCodecID::AV_CODEC_ID_H264);
+        assert!(details.profile.is_some());
+        assert!(details.level.is_some());
+        assert_eq!(details.pixel_format.as_deref(), Some("yuv420p"));
+        assert_eq!(details.bit_depth, Some(8));
+        assert_eq!(details.chroma_subsampling.as_deref(), Some("4:2:0"));
+
+        assert!(matches!(get_video_codec_details("test.mp4", 1), Err(Error::VideoStreamNotFound(1))));
+        assert!(matches!(get_video_codec_details("test.mp4", 9), Err(Error::VideoStreamNotFound(9))));
+    }
+
+    #[test]
+    fn test_level_name() {
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_H264, 41).as_deref(), Some("4.1"));
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_H264, 9).as_deref(), Some("1b"));
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_HEVC, 123).as_deref(), Some("4.1"));
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_HEVC, 150).as_deref(), Some("5.0"));
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_AV1, 8).as_deref(), Some("4.0"));
+        assert_eq!(level_name(AVCodecID::AV_CODEC_ID_AV1, 31), None);
+    }
 }
@@ -0,0 +1,252 @@
+use ffmpeg_next::{format, media};
+
+/// The standard tags of a media file (e.g. a music track), independent of the container.
+///
+/// Containers name these tags differently: ID3 (MP3) uses frames such as `TIT2` and `TRCK`,
+/// Vorbis comments (Ogg, FLAC, Opus) use `TITLE` and `TRACKNUMBER`, MP4 uses atoms such as
+/// `©nam`. [`read_tags`] recognizes the common spellings, and
+/// [`Output::set_tags`](crate::Output::set_tags) writes the fields under the names the
+/// output container expects.
+///
+/// Tags without a field of their own are kept in [`other`](Tags::other) with their original
+/// key and written back verbatim.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub composer: Option<String>,
+    pub genre: Option<String>,
+    pub comment: Option<String>,
+    /// The year of the `date` tag.
+    pub year: Option<u32>,
+    /// The track number, from 1.
+    pub track: Option<u32>,
+    /// The number of tracks of the album.
+    pub track_total: Option<u32>,
+    /// The disc number, from 1.
+    pub disc: Option<u32>,
+    /// The number of discs of the album.
+    pub disc_total: Option<u32>,
+    /// The remaining tags, as `(key, value)`.
+    pub other: Vec<(String, String)>,
+}
+
+/// Formats storing Vorbis comments, where the totals have tags of their own instead of
+/// being written as `track/total`.
+const VORBIS_COMMENT_FORMATS: [&str; 6] = ["ogg", "oga", "ogv", "opus", "spx", "flac"];
+
+impl Tags {
+    /// Sets the field a tag belongs to, or adds it to `other`. Keys are matched without
+    /// case, and a field already set is not replaced by a later spelling of the same tag.
+    fn insert(&mut self, key: &str, value: &str) {
+        fn set(field: &mut Option<String>, value: &str) {
+            field.get_or_insert_with(|| value.to_string());
+        }
+        fn set_number(field: &mut Option<u32>, value: &str) {
+            if field.is_none() {
+                *field = parse_number(value);
+            }
+        }
+
+        match key.to_ascii_lowercase().as_str() {
+            "title" | "tit2" => set(&mut self.title, value),
+            "artist" | "tpe1" | "author" => set(&mut self.artist, value),
+            "album" | "talb" => set(&mut self.album, value),
+            "album_artist" | "albumartist" | "album artist" | "tpe2" => set(&mut self.album_artist, value),
+            "composer" | "tcom" => set(&mut self.composer, value),
+            "genre" | "tcon" => set(&mut self.genre, value),
+            "comment" | "comm" | "description" => set(&mut self.comment, value),
+            "date" | "year" | "tdrc" | "tyer" => set_number(&mut self.year, value),
+            "track" | "tracknumber" | "trck" => {
+                set_number(&mut self.track, value);
+                set_number(&mut self.track_total, value.split_once('/').map_or("", |(_, total)| total));
+            }
+            "tracktotal" | "totaltracks" => set_number(&mut self.track_total, value),
+            "disc" | "discnumber" | "tpos" => {
+                set_number(&mut self.disc, value);
+                set_number(&mut self.disc_total, value.split_once('/').map_or("", |(_, total)| total));
+            }
+            "disctotal" | "totaldiscs" => set_number(&mut self.disc_total, value),
+            _ => self.other.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// Returns the tags as FFmpeg metadata entries for the muxer of `format_name`.
+    ///
+    /// The fields use FFmpeg's generic keys (`title`, `track`, `date`...), which the muxers
+    /// translate into the names of their container.
+    pub(crate) fn to_metadata(&self, format_name: &str) -> Vec<(String, String)> {
+        let vorbis_comments = format_name.split(',').any(|name| VORBIS_COMMENT_FORMATS.contains(&name));
+        let mut metadata = Vec::new();
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                metadata.push((key.to_string(), value));
+            }
+        };
+
+        push("title", self.title.clone());
+        push("artist", self.artist.clone());
+        push("album", self.album.clone());
+        push("album_artist", self.album_artist.clone());
+        push("composer", self.composer.clone());
+        push("genre", self.genre.clone());
+        push("comment", self.comment.clone());
+        push("date", self.year.map(|year| year.to_string()));
+        for (key, total_key, number, total) in [
+            ("track", "TRACKTOTAL", self.track, self.track_total),
+            ("disc", "DISCTOTAL", self.disc, self.disc_total),
+        ] {
+            match (number, total) {
+                (Some(number), Some(total)) if !vorbis_comments => push(key, Some(format!("{number}/{total}"))),
+                (number, total) => {
+                    push(key, number.map(|number| number.to_string()));
+                    if vorbis_comments {
+                        push(total_key, total.map(|total| total.to_string()));
+                    }
+                }
+            }
+        }
+
+        metadata.extend(self.other.iter().cloned());
+        metadata
+    }
+}
+
+/// Parses the leading number of a tag value, e.g. `3` in `"3/12"` or `"03"`, or `2019`
+/// in `"2019-05-01"`.
+fn parse_number(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let end = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+/// Reads the standard tags of a media file, whatever its container.
+///
+/// The tags of the container are read first. Formats that store them on the audio stream
+/// instead (e.g. Ogg Vorbis) are covered by also reading the tags of the first audio stream,
+/// for the fields the container did not set.
+///
+/// # Arguments
+/// - `input`: The path to the input file (e.g., `"song.mp3"`).
+///
+/// # Returns
+/// - `Result<Tags, ffmpeg_next::Error>`: Returns the tags of the file, with the fields it
+///   does not have set to `None`. If an error occurs, it returns an `ffmpeg_next::Error`.
+///
+/// # Example
+/// ```rust
+/// let tags = read_tags("song.flac").unwrap();
+/// println!("{:?} - {:?} (track {:?})", tags.artist, tags.title, tags.track);
+/// ```
+pub fn read_tags(input: impl Into<String>) -> Result<Tags, ffmpeg_next::Error> {
+    let format_context = format::input(&input.into())?;
+
+    let mut tags = Tags::default();
+    for (key, value) in format_context.metadata().iter() {
+        tags.insert(key, value);
+    }
+    if let Some(stream) = format_context.streams().best(media::Type::Audio) {
+        let mut stream_tags = Tags::default();
+        for (key, value) in stream.metadata().iter() {
+            stream_tags.insert(key, value);
+        }
+        // stream tags such as the handler name or the encoder are not tags of the file
+        stream_tags.other.clear();
+        tags = merge(tags, stream_tags);
+    }
+    Ok(tags)
+}
+
+/// Fills the fields `tags` lacks with those of `fallback`.
+fn merge(tags: Tags, fallback: Tags) -> Tags {
+    Tags {
+        title: tags.title.or(fallback.title),
+        artist: tags.artist.or(fallback.artist),
+        album: tags.album.or(fallback.album),
+        album_artist: tags.album_artist.or(fallback.album_artist),
+        composer: tags.composer.or(fallback.composer),
+        genre: tags.genre.or(fallback.genre),
+        comment: tags.comment.or(fallback.comment),
+        year: tags.year.or(fallback.year),
+        track: tags.track.or(fallback.track),
+        track_total: tags.track_total.or(fallback.track_total),
+        disc: tags.disc.or(fallback.disc),
+        disc_total: tags.disc_total.or(fallback.disc_total),
+        other: tags.other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::context::ffmpeg_context::FfmpegContext;
+    use crate::core::context::output::Output;
+
+    #[test]
+    fn test_insert_tags() {
+        let mut tags = Tags::default();
+        for (key, value) in [
+            ("TIT2", "Song"),
+            ("ARTIST", "Band"),
+            ("ALBUMARTIST", "Various"),
+            ("TRACKNUMBER", "03/12"),
+            ("DATE", "2019-05-01"),
+            ("DISCNUMBER", "1"),
+            ("DISCTOTAL", "2"),
+            ("REPLAYGAIN_TRACK_GAIN", "-6.5 dB"),
+        ] {
+            tags.insert(key, value);
+        }
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Band"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!((tags.track, tags.track_total), (Some(3), Some(12)));
+        assert_eq!((tags.disc, tags.disc_total), (Some(1), Some(2)));
+        assert_eq!(tags.year, Some(2019));
+        assert_eq!(tags.other, vec![("REPLAYGAIN_TRACK_GAIN".to_string(), "-6.5 dB".to_string())]);
+
+        let metadata = tags.to_metadata("mp3");
+        assert!(metadata.contains(&("track".to_string(), "3/12".to_string())));
+        assert!(metadata.contains(&("date".to_string(), "2019".to_string())));
+        assert!(metadata.contains(&("REPLAYGAIN_TRACK_GAIN".to_string(), "-6.5 dB".to_string())));
+
+        let metadata = tags.to_metadata("ogg");
+        assert!(metadata.contains(&("track".to_string(), "3".to_string())));
+        assert!(metadata.contains(&("TRACKTOTAL".to_string(), "12".to_string())));
+    }
+
+    #[test]
+    fn test_write_and_read_tags() {
+        let tags = Tags {
+            title: Some("Narration".to_string()),
+            artist: Some("ez-ffmpeg".to_string()),
+            year: Some(2024),
+            track: Some(2),
+            track_total: Some(9),
+            other: vec![("custom_key".to_string(), "custom value".to_string())],
+            ..Tags::default()
+        };
+
+        for file in ["output_tags.mp3", "output_tags.flac", "output_tags.m4a"] {
+            let output = Output::from(file).add_stream_map("0:a").set_tags(tags.clone());
+            let result = FfmpegContext::builder()
+                .input("test.mp4")
+                .output(output)
+                .build()
+                .unwrap()
+                .start()
+                .unwrap()
+                .wait();
+            assert!(result.is_ok(), "{file}: {:?}", result.err());
+
+            let read = read_tags(file).unwrap();
+            assert_eq!(read.title, tags.title, "{file}");
+            assert_eq!(read.artist, tags.artist, "{file}");
+            assert_eq!(read.year, tags.year, "{file}");
+            assert_eq!((read.track, read.track_total), (Some(2), Some(9)), "{file}");
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::core::context::CodecContext;
+use crate::core::stream_info::init_format_context;
+use crate::error::Error::{Decoding, Demuxing, OpenDecoder};
+use crate::error::{
+    DecodingError, DecodingOperationError, DemuxingError, DemuxingOperationError, OpenDecoderError,
+    OpenDecoderOperationError, Result,
+};
+use crate::util::ffmpeg_utils::av_err2str;
+use ffmpeg_next::{Frame, Packet};
+use ffmpeg_sys_next::AVMediaType::{AVMEDIA_TYPE_AUDIO, AVMEDIA_TYPE_SUBTITLE, AVMEDIA_TYPE_VIDEO};
+use ffmpeg_sys_next::{
+    av_frame_unref, av_packet_unref, av_read_frame, av_rescale_q, avcodec_alloc_context3, avcodec_decode_subtitle2,
+    avcodec_find_decoder, avcodec_get_name, avcodec_open2, avcodec_parameters_to_context, avcodec_receive_frame,
+    avcodec_send_packet, avsubtitle_free, AVMediaType, AVPacket, AVRational, AVStream, AVSubtitle, AVERROR,
+    AVERROR_EOF, AV_FRAME_FLAG_CORRUPT, AV_NOPTS_VALUE, AV_TIME_BASE, AV_TIME_BASE_Q, EAGAIN,
+};
+use log::{debug, error, warn};
+use std::ffi::CStr;
+use std::ptr::{null, null_mut};
+use std::time::Duration;
+
+/// Audio or video timestamps jumping forward by more than this (in microseconds) are counted
+/// as an anomaly, like FFmpeg's default `-dts_delta_threshold`.
+const TIMESTAMP_JUMP_THRESHOLD_US: i64 = 10 * AV_TIME_BASE as i64;
+
+/// The result of [`verify`]: what was found while reading and decoding a whole input.
+#[derive(Clone, Debug, Default)]
+pub struct VerifyReport {
+    /// One entry per stream of the input, in stream order.
+    pub streams: Vec<StreamVerifyReport>,
+    /// The error that stopped reading the input before its end, if any (e.g. a truncated file
+    /// or an I/O error). The streams are only verified up to that point.
+    pub read_error: Option<String>,
+}
+
+impl VerifyReport {
+    /// Returns `true` if the whole input was read without errors, and no stream had decode
+    /// errors, corrupt frames or timestamp anomalies.
+    pub fn is_clean(&self) -> bool {
+        self.read_error.is_none()
+            && self.streams.iter().all(|stream| {
+                stream.decode_errors == 0 && stream.corrupt_frames == 0 && stream.timestamp_anomalies == 0
+            })
+    }
+}
+
+/// What was found in one stream by [`verify`].
+#[derive(Clone, Debug)]
+pub struct StreamVerifyReport {
+    /// The index of the stream in the input.
+    pub index: usize,
+    /// The type of the stream.
+    pub media_type: AVMediaType,
+    /// The codec of the stream, e.g. `"h264"`.
+    pub codec_name: String,
+    /// Whether the stream was decoded. `false` for data and attachment streams, and for
+    /// codecs FFmpeg has no decoder for: only the timestamps of their packets are checked.
+    pub decoded: bool,
+    /// Packets read from the stream.
+    pub packets: u64,
+    /// Decoded frames (subtitles for subtitle streams).
+    pub frames: u64,
+    /// Packets or frames the decoder failed on.
+    pub decode_errors: u64,
+    /// Frames the decoder returned but flagged as corrupt (`AV_FRAME_FLAG_CORRUPT` or decode
+    /// error flags), e.g. with concealed macroblocks.
+    pub corrupt_frames: u64,
+    /// Packets whose DTS does not increase, whose PTS is before their DTS, or (audio and video
+    /// only) whose DTS jumps forward by more than 10 seconds.
+    pub timestamp_anomalies: u64,
+}
+
+/// Reads and decodes every packet of `input` (a path or URL) and reports the problems found,
+/// e.g. to check that archived files still decode cleanly.
+///
+/// Problems do not stop the check: every stream is decoded to the end and the decode errors,
+/// corrupt frames and timestamp anomalies are counted per stream. Use
+/// [`verify_with_exit_on_error`] to stop at the first error instead.
+///
+/// # Errors
+/// Returns an error if the input cannot be opened or probed, or a decoder cannot be opened.
+///
+/// # Example
+/// ```rust
+/// let report = verify("archive/tape_0042.mkv").unwrap();
+/// if !report.is_clean() {
+///     for stream in &report.streams {
+///         println!(
+///             "stream {} ({}): {} decode errors, {} corrupt frames, {} timestamp anomalies",
+///             stream.index, stream.codec_name, stream.decode_errors, stream.corrupt_frames,
+///             stream.timestamp_anomalies
+///         );
+///     }
+/// }
+/// ```
+pub fn verify(input: &str) -> Result<VerifyReport> {
+    verify_with_exit_on_error(input, false)
+}
+
+/// Like [`verify`], but with `exit_on_error` set (FFmpeg's `-xerror`) the check stops at the
+/// first read error, decode error or corrupt frame and returns it as an
+/// `Error::Demuxing`/`Error::Decoding` error. Timestamp anomalies are only counted.
+pub fn verify_with_exit_on_error(input: &str, exit_on_error: bool) -> Result<VerifyReport> {
+    let in_fmt_ctx_box = init_format_context(input)?;
+    let fmt_ctx = in_fmt_ctx_box.fmt_ctx;
+
+    unsafe {
+        let mut streams = Vec::new();
+        for i in 0..(*fmt_ctx).nb_streams as usize {
+            streams.push(StreamVerifier::open(*(*fmt_ctx).streams.add(i))?);
+        }
+
+        let mut read_error = None;
+        let mut packet = Packet::empty();
+        let mut frame = Frame::empty();
+        loop {
+            let ret = av_read_frame(fmt_ctx, packet.as_mut_ptr());
+            if ret == AVERROR(EAGAIN) {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            if ret < 0 {
+                if ret != AVERROR_EOF {
+                    error!("Error reading '{input}': {}", av_err2str(ret));
+                    if exit_on_error {
+                        return Err(Demuxing(DemuxingOperationError::ReadFrameError(DemuxingError::from(ret))));
+                    }
+                    read_error = Some(av_err2str(ret));
+                }
+                break;
+            }
+
+            // streams appearing after the header was read are not verified
+            let index = (*packet.as_ptr()).stream_index as usize;
+            if let Some(stream) = streams.get_mut(index) {
+                stream.check_timestamps(packet.as_ptr());
+                stream.decode(packet.as_ptr(), &mut frame, exit_on_error)?;
+            }
+            av_packet_unref(packet.as_mut_ptr());
+        }
+
+        // drain the frames the decoders still hold
+        for stream in &mut streams {
+            stream.decode(null(), &mut frame, exit_on_error)?;
+        }
+
+        Ok(VerifyReport {
+            streams: streams.into_iter().map(|stream| stream.report).collect(),
+            read_error,
+        })
+    }
+}
+
+struct StreamVerifier {
+    dec_ctx: CodecContext,
+    time_base: AVRational,
+    last_dts: i64,
+    report: StreamVerifyReport,
+}
+
+impl StreamVerifier {
+    unsafe fn open(stream: *mut AVStream) -> Result<Self> {
+        let codecpar = (*stream).codecpar;
+        let media_type = (*codecpar).codec_type;
+        let mut verifier = Self {
+            dec_ctx: CodecContext::null(),
+            time_base: (*stream).time_base,
+            last_dts: AV_NOPTS_VALUE,
+            report: StreamVerifyReport {
+                index: (*stream).index as usize,
+                media_type,
+                codec_name: CStr::from_ptr(avcodec_get_name((*codecpar).codec_id)).to_string_lossy().into_owned(),
+                decoded: false,
+                packets: 0,
+                frames: 0,
+                decode_errors: 0,
+                corrupt_frames: 0,
+                timestamp_anomalies: 0,
+            },
+        };
+        if media_type != AVMEDIA_TYPE_VIDEO && media_type != AVMEDIA_TYPE_AUDIO && media_type != AVMEDIA_TYPE_SUBTITLE {
+            return Ok(verifier);
+        }
+
+        let dec = avcodec_find_decoder((*codecpar).codec_id);
+        if dec.is_null() {
+            warn!(
+                "No decoder for stream {} ({}), only its timestamps are checked.",
+                verifier.report.index, verifier.report.codec_name
+            );
+            return Ok(verifier);
+        }
+
+        let dec_ctx = avcodec_alloc_context3(dec);
+        if dec_ctx.is_null() {
+            return Err(OpenDecoder(OpenDecoderOperationError::ContextAllocationError(OpenDecoderError::OutOfMemory)));
+        }
+        verifier.dec_ctx = CodecContext::new(dec_ctx);
+
+        let ret = avcodec_parameters_to_context(dec_ctx, codecpar);
+        if ret < 0 {
+            return Err(OpenDecoder(OpenDecoderOperationError::ParameterApplicationError(OpenDecoderError::from(ret))));
+        }
+        (*dec_ctx).pkt_timebase = (*stream).time_base;
+
+        let ret = avcodec_open2(dec_ctx, dec, null_mut());
+        if ret < 0 {
+            return Err(OpenDecoder(OpenDecoderOperationError::DecoderOpenError(OpenDecoderError::from(ret))));
+        }
+        verifier.report.decoded = true;
+        Ok(verifier)
+    }
+
+    unsafe fn check_timestamps(&mut self, pkt: *const AVPacket) {
+        self.report.packets += 1;
+        let (pts, dts) = ((*pkt).pts, (*pkt).dts);
+
+        let mut anomaly = pts != AV_NOPTS_VALUE && dts != AV_NOPTS_VALUE && pts < dts;
+        if dts != AV_NOPTS_VALUE && self.last_dts != AV_NOPTS_VALUE {
+            let jump_us = av_rescale_q(dts - self.last_dts, self.time_base, AV_TIME_BASE_Q);
+            let media_type = self.report.media_type;
+            anomaly |= dts <= self.last_dts
+                || ((media_type == AVMEDIA_TYPE_VIDEO || media_type == AVMEDIA_TYPE_AUDIO)
+                    && jump_us > TIMESTAMP_JUMP_THRESHOLD_US);
+        }
+        if anomaly {
+            debug!(
+                "Stream {}: timestamp anomaly, pts {pts} dts {dts} after dts {}",
+                self.report.index, self.last_dts
+            );
+            self.report.timestamp_anomalies += 1;
+        }
+        if dts != AV_NOPTS_VALUE {
+            self.last_dts = dts;
+        }
+    }
+
+    /// Decodes `pkt`, or drains the decoder if `pkt` is null.
+    unsafe fn decode(&mut self, pkt: *const AVPacket, frame: &mut Frame, exit_on_error: bool) -> Result<()> {
+        if !self.report.decoded {
+            return Ok(());
+        }
+        let dec_ctx = self.dec_ctx.as_mut_ptr();
+
+        if self.report.media_type == AVMEDIA_TYPE_SUBTITLE {
+            if pkt.is_null() {
+                return Ok(());
+            }
+            let mut subtitle: AVSubtitle = std::mem::zeroed();
+            let mut got_subtitle = 0;
+            let ret = avcodec_decode_subtitle2(dec_ctx, &mut subtitle, &mut got_subtitle, pkt);
+            if ret < 0 {
+                return self.decode_error(ret, exit_on_error, DecodingOperationError::DecodeSubtitleError);
+            }
+            if got_subtitle != 0 {
+                self.report.frames += 1;
+                avsubtitle_free(&mut subtitle);
+            }
+            return Ok(());
+        }
+
+        let ret = avcodec_send_packet(dec_ctx, pkt);
+        if ret < 0 && ret != AVERROR_EOF {
+            self.decode_error(ret, exit_on_error, DecodingOperationError::SendPacketError)?;
+        }
+
+        loop {
+            let ret = avcodec_receive_frame(dec_ctx, frame.as_mut_ptr());
+            if ret == AVERROR(EAGAIN) || ret == AVERROR_EOF {
+                return Ok(());
+            }
+            if ret < 0 {
+                self.decode_error(ret, exit_on_error, DecodingOperationError::ReceiveFrameError)?;
+                continue;
+            }
+
+            self.report.frames += 1;
+            let corrupt = (*frame.as_ptr()).decode_error_flags != 0
+                || (*frame.as_ptr()).flags & AV_FRAME_FLAG_CORRUPT != 0;
+            av_frame_unref(frame.as_mut_ptr());
+            if corrupt {
+                debug!("Stream {}: corrupt decoded frame", self.report.index);
+                self.report.corrupt_frames += 1;
+                if exit_on_error {
+                    return Err(Decoding(DecodingOperationError::CorruptFrame));
+                }
+            }
+        }
+    }
+
+    fn decode_error(
+        &mut self,
+        ret: i32,
+        exit_on_error: bool,
+        error: fn(DecodingError) -> DecodingOperationError,
+    ) -> Result<()> {
+        debug!("Stream {}: decoding error: {}", self.report.index, av_err2str(ret));
+        self.report.decode_errors += 1;
+        if exit_on_error {
+            return Err(Decoding(error(DecodingError::from(ret))));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let report = verify("test.mp4").unwrap();
+        assert!(report.is_clean(), "{report:?}");
+        assert!(!report.streams.is_empty());
+        for stream in report.streams.iter().filter(|stream| stream.decoded) {
+            assert!(stream.packets > 0 && stream.frames > 0, "{stream:?}");
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use ffmpeg_next::ffi::AVERROR;
 use ffmpeg_sys_next::*;
 use std::ffi::NulError;
+use std::time::Duration;
 use std::{io, result};
 
 /// Result type of all ez-ffmpeg library calls.
@@ -20,6 +21,12 @@ pub enum Error {
     #[error("Find stream info error: {0}")]
     FindStream(#[from] FindStreamError),
 
+    #[error("Input has no video stream {0}")]
+    VideoStreamNotFound(usize),
+
+    #[error("Input '{0}' timed out after {1:?}")]
+    InputTimeout(String, Duration),
+
     #[error("Decoder error: {0}")]
     Decoder(#[from] DecoderError),
 
@@ -47,6 +54,9 @@ pub enum Error {
     #[error("Output file '{0}' is the same as an input file")]
     FileSameAsInput(String),
 
+    #[error("Builder cannot be used as a template: {0}")]
+    TemplateUnsupported(String),
+
     #[error("Find devices error: {0}")]
     FindDevices(#[from] FindDevicesError),
 
@@ -109,6 +119,15 @@ pub enum Error {
     #[error("Frame filter pipeline thread exited")]
     FrameFilterThreadExited,
 
+    // ---- Frame extraction ----
+    #[cfg(feature = "image")]
+    #[error("Frame {0} is out of range, the stream has {1} frames")]
+    FrameIndexOutOfRange(u64, u64),
+
+    #[cfg(feature = "image")]
+    #[error("Frame conversion failed: {0}")]
+    FrameConversion(String),
+
     #[cfg(feature = "rtmp")]
     #[error("Rtmp stream already exists with key: {0}")]
     RtmpStreamAlreadyExists(String),
@@ -196,6 +215,9 @@ pub enum DecodingOperationError {
     #[error("corrupt decoded frame")]
     CorruptFrame,
 
+    #[error("too many decoding errors: {0} failed packets for {1} decoded frames")]
+    ErrorRateExceeded(u64, u64),
+
     #[error("during retrieve data on hw: {0}")]
     HWRetrieveDataError(DecodingError),
 
@@ -256,6 +278,9 @@ pub enum FilterGraphOperationError {
     #[error("The data in the frame is invalid or corrupted")]
     InvalidData,
 
+    #[error("The {0}x{1} video of output stream {2} does not fit the chroma subsampling of its pixel format (see Output::set_even_dimensions_policy)")]
+    OddDimensions(i32, i32, String),
+
     #[error("Thread exited")]
     ThreadExited,
 }
@@ -277,6 +302,9 @@ pub enum EncodingOperationError {
     #[error(": Muxer already finished")]
     MuxerFinished,
 
+    #[error(": Too many packets buffered for output stream, consider raising max_muxing_queue_size")]
+    MuxingQueueFull,
+
     #[error("Encode subtitle error: {0}")]
     EncodeSubtitle(#[from] EncodeSubtitleError),
 
@@ -292,6 +320,12 @@ pub enum MuxingOperationError {
     #[error("during interleaved write: {0}")]
     InterleavedWriteError(MuxingError),
 
+    #[error("during interleaved write: {0}; the stream may need the '{1}' bitstream filter (Output::set_bitstream_filter)")]
+    MissingBitstreamFilter(MuxingError, String),
+
+    #[error("during bitstream filtering: {0}")]
+    BitstreamFilterError(MuxingError),
+
     #[error("during trailer write: {0}")]
     TrailerWriteError(MuxingError),
 
@@ -387,6 +421,30 @@ pub enum OpenInputError {
 
     #[error("No seek callback is provided")]
     SeekFunctionMissing,
+
+    #[error("Start percent {0} is out of range, expected 0..=100")]
+    InvalidStartPercent(f32),
+
+    #[error("Start frame {0} is beyond the estimated frame count {1}")]
+    StartFrameOutOfRange(u64, i64),
+
+    #[error("Input duration is unknown")]
+    UnknownDuration,
+
+    #[error("Input has no video stream with a known frame rate")]
+    UnknownFrameRate,
+
+    #[error("No streams selected from input '{0}': all of its streams are ignored")]
+    NoStreamsSelected(String),
+
+    #[error("No GPU scaler for '{0}' frames is available in this FFmpeg build, supported scalers: [{1}]")]
+    HwScalerUnavailable(String, String),
+
+    #[error("Capture device format '{0}' is not available in this FFmpeg build, available device formats: [{1}]")]
+    DeviceFormatUnavailable(String, String),
+
+    #[error("File '{0}' referenced by playlist '{1}' does not exist")]
+    PlaylistEntryNotFound(String, String),
 }
 
 impl From<i32> for OpenInputError {
@@ -507,6 +565,24 @@ pub enum FilterGraphParseError {
     #[error("Filter '{0}' has output {1} ({2}) unconnected")]
     OutputUnconnected(String, usize, String),
 
+    #[error("No such filter: '{0}'")]
+    FilterNotFound(String),
+
+    #[error("Error applying option '{1}' to filter '{0}': {2}")]
+    InvalidFilterOption(String, String, String),
+
+    #[error("Error initializing filter '{0}': {1}")]
+    FilterInitFailed(String, String),
+
+    #[error("Filtergraph has {0} input pads but {1} inputs were provided")]
+    InputCountMismatch(usize, usize),
+
+    #[error("No input pad labeled '{0}' in the filtergraph")]
+    InputPadNotFound(String),
+
+    #[error("Input pad '{0}' is assigned more than once")]
+    InputPadAssignedTwice(String),
+
     #[error("An unknown error occurred. ret: {0}")]
     UnknownError(i32),
 }
@@ -641,6 +717,42 @@ pub enum OpenOutputError {
 
     #[error("Format '{0}' is unsupported")]
     FormatUnsupported(String),
+
+    #[error("Codec '{0}' is not supported by the '{1}' container")]
+    CodecNotSupportedByContainer(String, String),
+
+    #[error("faststart is not supported for output '{0}': {1}")]
+    FaststartUnsupported(String, String),
+
+    #[error("Pixel format '{0}' is not supported by encoder '{1}', supported formats: {2}")]
+    PixelFormatUnsupported(String, String, String),
+
+    #[error("Channel layout '{0}' is not supported by encoder '{1}', supported layouts: {2}")]
+    ChannelLayoutUnsupported(String, String, String),
+
+    #[error("Stream index {0} is out of range, the output has {1} streams")]
+    StreamIndexOutOfRange(usize, usize),
+
+    #[error("Invalid time base {1}/{2} for output stream {0}")]
+    InvalidTimeBase(usize, i32, i32),
+
+    #[error("Invalid {0} {1}, it must be at least {2}")]
+    OptionValueTooSmall(String, i64, i64),
+
+    #[error("Invalid creation time '{0}', expected an RFC 3339 date like '2024-05-01T12:00:00Z' or 'now'")]
+    InvalidCreationTime(String),
+
+    #[error("Invalid bitstream filter '{0}' for output stream {1}")]
+    InvalidBitstreamFilter(String, usize),
+
+    #[error("Output file '{0}' already exists")]
+    OutputExists(String),
+
+    #[error("Subtitle codec '{0}' is bitmap-based and cannot be encoded to the text format '{1}' without OCR")]
+    BitmapSubtitleToText(String, String),
+
+    #[error("Subtitle codec '{0}' is text-based and cannot be encoded to the bitmap format '{1}'")]
+    TextSubtitleToBitmap(String, String),
 }
 
 impl From<i32> for OpenOutputError {
@@ -673,6 +785,8 @@ pub enum FindDevicesError {
     OsNotSupported,
     #[error("device_description can not to string")]
     UTF8Error,
+    #[error("Input device format '{0}' is not available in this FFmpeg build")]
+    FormatNotFound(String),
 
     #[error("Memory allocation error")]
     OutOfMemory,
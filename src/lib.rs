@@ -84,6 +84,10 @@
 //! - **`rtmp`**: Enables an embedded RTMP server for local streaming scenarios.
 //! - **`flv`**: Adds FLV container parsing and handling.
 //! - **`async`**: Makes the [`FfmpegScheduler`] wait method asynchronous (you can `.await` it).
+//! - **`ndarray`**: Adds `NdarrayFilter`, which hands video frames to a callback as `ndarray` views.
+//! - **`image`**: Adds the `frame_extractor` module, which decodes single frames into `image` RGB images.
+//! - **`chrono`**: Lets `Output::set_creation_time` take a `chrono::DateTime` as well as an RFC 3339 string.
+//! - **`serde`**: Makes the [`JobReport`](crate::core::scheduler::job_report::JobReport) of a job serializable, e.g. to JSON.
 //! - **`static`**: Uses static linking for FFmpeg libraries (via `ffmpeg-next/static`).
 //!
 //! ## License Notice
@@ -103,11 +107,20 @@ pub use self::core::context::input::Input;
 pub use self::core::context::output::Output;
 pub use self::core::scheduler::ffmpeg_scheduler::FfmpegScheduler;
 pub use self::core::container_info;
+pub use self::core::tags;
 pub use self::core::stream_info;
 pub use self::core::device;
 pub use self::core::hwaccel;
 pub use self::core::codec;
+pub use self::core::remux;
+pub use self::core::clip;
+pub use self::core::size_estimate;
+pub use self::core::frame_reader;
+#[cfg(feature = "image")]
+pub use self::core::frame_extractor;
+pub use self::core::verify;
 pub use self::core::filter;
+pub use self::core::init_logging;
 
 pub use ffmpeg_sys_next::AVRational;
 pub use ffmpeg_sys_next::AVMediaType;
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use ffmpeg_sys_next::{av_dict_set, av_strerror, AVDictionary, AV_ERROR_MAX_STRING_SIZE};
+use ffmpeg_sys_next::{av_dict_set, av_strerror, AVDictionary, AVPixelFormat, AVSampleFormat, AV_ERROR_MAX_STRING_SIZE};
 
 pub(crate) fn hashmap_to_avdictionary(opts: &Option<HashMap<CString, CString>>) -> *mut AVDictionary {
     let mut av_dict: *mut AVDictionary = std::ptr::null_mut();
@@ -26,4 +26,53 @@ pub fn av_err2str(err: i32) -> String {
             Err(_) => format!("Unknown error: {}", err),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Converts a raw `format` of a video frame or stream to its `AVPixelFormat`, or `None` if it
+/// is unset (`-1`) or unknown to the FFmpeg this crate was built against.
+pub(crate) fn pixel_format(format: i32) -> Option<AVPixelFormat> {
+    if !(0..AVPixelFormat::AV_PIX_FMT_NB as i32).contains(&format) {
+        return None;
+    }
+    // SAFETY: the values of AVPixelFormat are contiguous from 0 to AV_PIX_FMT_NB
+    Some(unsafe { std::mem::transmute::<i32, AVPixelFormat>(format) })
+}
+
+/// Converts a raw `format` of an audio frame or stream to its `AVSampleFormat`, or `None` if
+/// it is unset (`-1`) or unknown to the FFmpeg this crate was built against.
+pub(crate) fn sample_format(format: i32) -> Option<AVSampleFormat> {
+    if !(0..AVSampleFormat::AV_SAMPLE_FMT_NB as i32).contains(&format) {
+        return None;
+    }
+    // SAFETY: the values of AVSampleFormat are contiguous from 0 to AV_SAMPLE_FMT_NB
+    Some(unsafe { std::mem::transmute::<i32, AVSampleFormat>(format) })
+}
+
+/// Converts a `Duration` to the microseconds FFmpeg's timestamps use. Sub-microsecond
+/// precision is truncated, and durations beyond `i64::MAX` microseconds saturate.
+pub(crate) fn duration_to_us(duration: std::time::Duration) -> i64 {
+    i64::try_from(duration.as_micros()).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_duration_to_us() {
+        assert_eq!(duration_to_us(Duration::from_secs(2)), 2_000_000);
+        assert_eq!(duration_to_us(Duration::from_nanos(1_999)), 1);
+        assert_eq!(duration_to_us(Duration::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn test_raw_formats() {
+        assert_eq!(pixel_format(AVPixelFormat::AV_PIX_FMT_NV12 as i32), Some(AVPixelFormat::AV_PIX_FMT_NV12));
+        assert_eq!(pixel_format(-1), None);
+        assert_eq!(pixel_format(AVPixelFormat::AV_PIX_FMT_NB as i32), None);
+        assert_eq!(sample_format(AVSampleFormat::AV_SAMPLE_FMT_FLTP as i32), Some(AVSampleFormat::AV_SAMPLE_FMT_FLTP));
+        assert_eq!(sample_format(-1), None);
+        assert_eq!(sample_format(i32::MAX), None);
+    }
+}